@@ -0,0 +1,152 @@
+use crate::textbuffer::*;
+use std::collections::HashSet;
+
+#[derive(Clone, PartialEq)]
+pub struct CodeCompletion {
+    pub label: String,
+    pub detail: String,
+    pub insert_text: String
+}
+
+// anything that can offer completions for a position in a textbuffer
+pub trait CodeCompletionProvider {
+    fn provide_completions(&self, text_buffer: &TextBuffer, offset: usize, completions: &mut Vec<CodeCompletion>);
+}
+
+// offers every identifier and typename found in the tokenized buffer
+#[derive(Clone, Default)]
+pub struct TokenCompletionProvider {
+    pub min_len: usize
+}
+
+impl CodeCompletionProvider for TokenCompletionProvider {
+    fn provide_completions(&self, text_buffer: &TextBuffer, offset: usize, completions: &mut Vec<CodeCompletion>) {
+        let mut seen = HashSet::new();
+        for token_chunk in &text_buffer.token_chunks {
+            match token_chunk.token_type {
+                TokenType::Identifier | TokenType::Call | TokenType::TypeName => (),
+                _ => continue
+            }
+            // skip the word being typed
+            if offset >= token_chunk.offset && offset <= token_chunk.offset + token_chunk.len {
+                continue;
+            }
+            if token_chunk.len < self.min_len.max(2) {
+                continue;
+            }
+            let label: String = text_buffer.flat_text[token_chunk.offset..(token_chunk.offset + token_chunk.len)].iter().collect();
+            if seen.insert(label.clone()) {
+                completions.push(CodeCompletion {
+                    detail: String::new(),
+                    insert_text: label.clone(),
+                    label: label
+                });
+            }
+        }
+    }
+}
+
+impl CodeCompletion {
+    // scores label against a typed pattern, None if not all pattern chars appear in order
+    pub fn fuzzy_score(pattern: &[char], label: &str) -> Option<i32> {
+        if pattern.len() == 0 {
+            return Some(0)
+        }
+        let label: Vec<char> = label.chars().collect();
+        let mut score = 0;
+        let mut pi = 0;
+        let mut last_match: Option<usize> = None;
+        for (li, ch) in label.iter().enumerate() {
+            if pi >= pattern.len() {
+                break;
+            }
+            if ch.to_lowercase().eq(pattern[pi].to_lowercase()) {
+                score += 1;
+                if *ch == pattern[pi] {
+                    score += 1;
+                }
+                if li == 0 {
+                    score += 8;
+                }
+                else if label[li - 1] == '_' || label[li - 1].is_lowercase() && ch.is_uppercase() {
+                    score += 4; // start of a word
+                }
+                if let Some(last_match) = last_match {
+                    if last_match + 1 == li {
+                        score += 3;
+                    }
+                }
+                last_match = Some(li);
+                pi += 1;
+            }
+        }
+        if pi < pattern.len() {
+            return None
+        }
+        // prefer shorter labels
+        Some(score * 4 - label.len() as i32)
+    }
+    
+    pub fn fuzzy_filter(pattern: &[char], items: &Vec<CodeCompletion>) -> Vec<CodeCompletion> {
+        let mut scored: Vec<(i32, &CodeCompletion)> = items.iter().filter_map( | item | {
+            if let Some(score) = Self::fuzzy_score(pattern, &item.label) {Some((score, item))}else {None}
+        }).collect();
+        scored.sort_by( | a, b | b.0.cmp(&a.0).then(a.1.label.cmp(&b.1.label)));
+        scored.into_iter().map( | (_, item) | item.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn chars(pattern: &str) -> Vec<char> {
+        pattern.chars().collect()
+    }
+    
+    fn completions(labels: &[&str]) -> Vec<CodeCompletion> {
+        labels.iter().map( | label | CodeCompletion {
+            label: label.to_string(),
+            detail: String::new(),
+            insert_text: label.to_string()
+        }).collect()
+    }
+    
+    fn filtered(pattern: &str, labels: &[&str]) -> Vec<String> {
+        CodeCompletion::fuzzy_filter(&chars(pattern), &completions(labels)).into_iter().map( | item | item.label).collect()
+    }
+    
+    #[test]
+    fn pattern_chars_must_appear_in_order() {
+        assert_eq!(CodeCompletion::fuzzy_score(&chars(""), "anything"), Some(0));
+        assert!(CodeCompletion::fuzzy_score(&chars("xyz"), "hello").is_none());
+        assert!(CodeCompletion::fuzzy_score(&chars("ba"), "ab").is_none());
+        assert!(CodeCompletion::fuzzy_score(&chars("TB"), "textbuffer").is_some());
+    }
+    
+    #[test]
+    fn prefix_beats_subsequence() {
+        let prefix = CodeCompletion::fuzzy_score(&chars("get"), "get_value").unwrap();
+        let inner = CodeCompletion::fuzzy_score(&chars("get"), "widget").unwrap();
+        assert!(prefix > inner);
+    }
+    
+    #[test]
+    fn word_starts_and_case_score_higher() {
+        let snake = CodeCompletion::fuzzy_score(&chars("gv"), "get_value").unwrap();
+        let camel = CodeCompletion::fuzzy_score(&chars("gV"), "getValue").unwrap();
+        let plain = CodeCompletion::fuzzy_score(&chars("gv"), "gravy").unwrap();
+        assert!(snake > plain);
+        assert!(camel > plain);
+        assert!(CodeCompletion::fuzzy_score(&chars("Te"), "Text").unwrap() > CodeCompletion::fuzzy_score(&chars("Te"), "text").unwrap());
+        assert!(CodeCompletion::fuzzy_score(&chars("map"), "map").unwrap() > CodeCompletion::fuzzy_score(&chars("map"), "map_err").unwrap());
+    }
+    
+    #[test]
+    fn filter_orders_by_score_then_label() {
+        assert_eq!(filtered("get", &["widget", "gravy", "get_value", "zzz"]), vec!["get_value", "widget"]);
+        assert_eq!(filtered("gv", &["gravy", "get_value", "give"]), vec!["get_value", "give", "gravy"]);
+        assert_eq!(filtered("ab", &["aby", "abx", "ba"]), vec!["abx", "aby"]);
+        assert_eq!(filtered("", &["b", "a"]), vec!["a", "b"]);
+    }
+}
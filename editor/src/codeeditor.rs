@@ -3,6 +3,9 @@ use widget::*;
 use crate::textbuffer::*;
 use crate::textcursor::*;
 use crate::codeicon::*;
+use crate::codecompletion::*;
+use crate::symbolindex::*;
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct CodeEditor {
@@ -18,6 +21,18 @@ pub struct CodeEditor {
     pub indent_lines: Quad,
     pub code_icon: CodeIcon,
     pub message_marker: Quad,
+    pub fold_marker: Quad,
+    pub fold_placeholder: Quad,
+    pub git_marker: Quad,
+    pub quick_fix: Quad,
+    pub debug_marker: Quad,
+    pub exec_row: Quad,
+    pub minimap_bg: Quad,
+    pub minimap_viewport: Quad,
+    pub minimap_marker: Quad,
+    pub minimap_text: Text,
+    pub popup_bg: Quad,
+    pub popup_select: Quad,
     pub text: Text,
     pub cursors: TextCursorSet,
     
@@ -25,6 +40,14 @@ pub struct CodeEditor {
     pub folded_font_size: f32,
     pub line_number_width: f32,
     pub top_padding: f32,
+    pub soft_wrap: bool,
+    pub wrap_indent: usize,
+    pub show_minimap: bool,
+    pub minimap_width: f32,
+    pub minimap_font_size: f32,
+    pub hover_delay: f64,
+    pub max_completions: usize,
+    pub completion_providers: Vec<Rc<dyn CodeCompletionProvider>>,
     pub colors: CodeEditorColors,
     pub cursor_blink_speed: f64,
     pub _bg_area: Area,
@@ -42,6 +65,30 @@ pub struct CodeEditor {
     pub _last_indent_color: Color,
     
     pub _line_geometry: Vec<LineGeom>,
+    pub _wrap_geometry: Vec<WrapGeom>,
+    // (head, max) of the cursors after a wrapped up/down, their max is a visual column
+    pub _wrap_moved: Vec<(usize, usize)>,
+    pub _wrap_right: f32,
+    pub _line_col: usize,
+    pub _line_indent: usize,
+    pub _line_first_y: f32,
+    pub _line_visual_rows: usize,
+    pub _line_folded_rows: usize,
+    pub _fold_ranges: Vec<FoldRange>,
+    pub _fold_skip: Option<usize>,
+    pub _minimap_area: Area,
+    pub _minimap_scroll: f32,
+    pub _minimap_line_height: f32,
+    pub _git_hover_change: Option<usize>,
+    pub _quick_fix_rows: Vec<(usize, usize)>,
+    pub _hover_timer: Timer,
+    pub _hover_abs: Vec2,
+    pub _hover_info: Option<(usize, String)>,
+    pub _completion_anchor: Option<usize>,
+    pub _completion_prefix: Vec<char>,
+    pub _completion_items: Vec<CodeCompletion>,
+    pub _completions: Vec<CodeCompletion>,
+    pub _completion_index: usize,
     pub _anim_select: Vec<AnimSelect>,
     pub _visible_lines: usize,
     
@@ -103,6 +150,22 @@ pub struct CodeEditorColors {
     pub marker_error: Color,
     pub marker_warning: Color,
     pub marker_log: Color,
+    pub fold_marker: Color,
+    pub fold_placeholder: Color,
+    pub git_added: Color,
+    pub git_modified: Color,
+    pub git_deleted: Color,
+    pub git_hover: Color,
+    pub quick_fix: Color,
+    pub breakpoint: Color,
+    pub exec_marker: Color,
+    pub exec_row: Color,
+    pub minimap_bg: Color,
+    pub minimap_viewport: Color,
+    pub popup_bg: Color,
+    pub popup_select: Color,
+    pub popup_text: Color,
+    pub popup_detail: Color,
     
     pub whitespace: Color,
     pub keyword: Color,
@@ -122,54 +185,102 @@ pub struct CodeEditorColors {
     pub unexpected: Color
 }
 
+impl CodeEditorColors {
+    // parens alternate colors by their nesting depth
+    pub fn token_color(&self, token_type: TokenType, paren_depth: usize) -> Color {
+        match token_type {
+            TokenType::Whitespace | TokenType::Newline => self.whitespace,
+            TokenType::BuiltinType | TokenType::Keyword | TokenType::Bool | TokenType::TypeDef | TokenType::Fn => self.keyword,
+            TokenType::Flow => self.flow,
+            TokenType::Looping => self.looping,
+            TokenType::Identifier => self.identifier,
+            TokenType::Call => self.call,
+            TokenType::TypeName => self.type_name,
+            TokenType::Regex | TokenType::String => self.string,
+            TokenType::Number => self.number,
+            TokenType::CommentMultiBegin | TokenType::CommentMultiEnd | TokenType::CommentLine | TokenType::CommentChunk => self.comment,
+            TokenType::ParenOpen | TokenType::ParenClose => match paren_depth % 2 {
+                0 => self.paren_d1,
+                _ => self.paren_d2,
+            },
+            TokenType::Operator | TokenType::Namespace | TokenType::Hash | TokenType::Splat => self.operator,
+            TokenType::Delimiter | TokenType::Colon => self.delimiter,
+            TokenType::Eof | TokenType::Unexpected => self.unexpected
+        }
+    }
+}
+
+// the diff viewer colors its tokens like the editor, so the colors build without a whole editor
+impl Default for CodeEditorColors {
+    fn default() -> Self {
+        CodeEditorColors {
+            bg: color256(30, 30, 30),
+            indent_line_unknown: color("#5"),
+            indent_line_fn: color256(220, 220, 174),
+            indent_line_typedef: color256(91, 155, 211),
+            indent_line_looping: color("darkorange"),
+            indent_line_flow: color256(196, 133, 190),
+            selection: color256(42, 78, 117),
+            selection_defocus: color256(75, 75, 75),
+            highlight: color256a(75, 75, 95, 128),
+            cursor: color256(176, 176, 176),
+            cursor_row: color256(45, 45, 45),
+            
+            paren_pair_match: color256(255, 255, 255),
+            paren_pair_fail: color256(255, 0, 0),
+            
+            marker_error: color256(200, 0, 0),
+            marker_warning: color256(0, 200, 0),
+            marker_log: color256(200, 200, 200),
+            fold_marker: color256(136, 136, 136),
+            fold_placeholder: color256(110, 110, 110),
+            git_added: color256(88, 124, 12),
+            git_modified: color256(12, 125, 157),
+            git_deleted: color256(148, 21, 27),
+            git_hover: color256a(255, 255, 255, 12),
+            quick_fix: color256(224, 190, 60),
+            breakpoint: color256(229, 20, 0),
+            exec_marker: color256(255, 204, 0),
+            exec_row: color256a(255, 204, 0, 24),
+            minimap_bg: color256(30, 30, 30),
+            minimap_viewport: color256a(255, 255, 255, 20),
+            popup_bg: color256(37, 37, 38),
+            popup_select: color256(4, 57, 94),
+            popup_text: color256(212, 212, 212),
+            popup_detail: color256(136, 136, 136),
+            line_number_normal: color256(136, 136, 136),
+            line_number_highlight: color256(212, 212, 212),
+            
+            whitespace: color256(110, 110, 110),
+            
+            keyword: color256(91, 155, 211),
+            flow: color256(196, 133, 190),
+            looping: color("darkorange"),
+            identifier: color256(212, 212, 212),
+            call: color256(220, 220, 174),
+            type_name: color256(86, 201, 177),
+            
+            string: color256(204, 145, 123),
+            number: color256(182, 206, 170),
+            
+            comment: color256(99, 141, 84),
+            doc_comment: color256(120, 171, 104),
+            paren_d1: color256(212, 212, 212),
+            //color("#eee"),
+            paren_d2: color256(212, 212, 212),
+            //color("#888"),
+            operator: color256(212, 212, 212),
+            delimiter: color256(212, 212, 212),
+            unexpected: color256(255, 0, 0),
+        }
+    }
+}
+
 impl Style for CodeEditor {
     fn style(cx: &mut Cx) -> Self {
         Self {
             cursors: TextCursorSet::new(),
-            colors: CodeEditorColors {
-                bg: color256(30, 30, 30),
-                indent_line_unknown: color("#5"),
-                indent_line_fn: color256(220, 220, 174),
-                indent_line_typedef: color256(91, 155, 211),
-                indent_line_looping: color("darkorange"),
-                indent_line_flow: color256(196, 133, 190),
-                selection: color256(42, 78, 117),
-                selection_defocus: color256(75, 75, 75),
-                highlight: color256a(75, 75, 95, 128),
-                cursor: color256(176, 176, 176),
-                cursor_row: color256(45, 45, 45),
-                
-                paren_pair_match: color256(255, 255, 255),
-                paren_pair_fail: color256(255, 0, 0),
-                
-                marker_error: color256(200, 0, 0),
-                marker_warning: color256(0, 200, 0),
-                marker_log: color256(200, 200, 200),
-                line_number_normal: color256(136, 136, 136),
-                line_number_highlight: color256(212, 212, 212),
-                
-                whitespace: color256(110, 110, 110),
-                
-                keyword: color256(91, 155, 211),
-                flow: color256(196, 133, 190),
-                looping: color("darkorange"),
-                identifier: color256(212, 212, 212),
-                call: color256(220, 220, 174),
-                type_name: color256(86, 201, 177),
-                
-                string: color256(204, 145, 123),
-                number: color256(182, 206, 170),
-                
-                comment: color256(99, 141, 84),
-                doc_comment: color256(120, 171, 104),
-                paren_d1: color256(212, 212, 212),
-                //color("#eee"),
-                paren_d2: color256(212, 212, 212),
-                //color("#888"),
-                operator: color256(212, 212, 212),
-                delimiter: color256(212, 212, 212),
-                unexpected: color256(255, 0, 0),
-            },
+            colors: CodeEditorColors::default(),
             indent_lines: Quad {
                 shader: cx.add_shader(Self::def_indent_lines_shader(), "Editor.indent_lines"),
                 ..Style::style(cx)
@@ -215,6 +326,42 @@ impl Style for CodeEditor {
                 shader: cx.add_shader(Self::def_message_marker_shader(), "Editor.message_marker"),
                 ..Style::style(cx)
             },
+            fold_marker: Quad {
+                shader: cx.add_shader(Self::def_fold_marker_shader(), "Editor.fold_marker"),
+                ..Style::style(cx)
+            },
+            fold_placeholder: Quad {
+                shader: cx.add_shader(Self::def_fold_placeholder_shader(), "Editor.fold_placeholder"),
+                ..Style::style(cx)
+            },
+            git_marker: Quad {
+                shader: cx.add_shader(Self::def_git_marker_shader(), "Editor.git_marker"),
+                ..Style::style(cx)
+            },
+            quick_fix: Quad {
+                shader: cx.add_shader(Self::def_quick_fix_shader(), "Editor.quick_fix"),
+                ..Style::style(cx)
+            },
+            debug_marker: Quad {
+                shader: cx.add_shader(Self::def_debug_marker_shader(), "Editor.debug_marker"),
+                ..Style::style(cx)
+            },
+            exec_row: Quad {
+                shader: cx.add_shader(Quad::def_quad_shader(), "Editor.exec_row"),
+                ..Style::style(cx)
+            },
+            minimap_bg: Quad::style(cx),
+            minimap_viewport: Quad::style(cx),
+            minimap_marker: Quad::style(cx),
+            popup_bg: Quad::style(cx),
+            popup_select: Quad::style(cx),
+            minimap_text: Text {
+                font: cx.load_font_style("mono_font"),
+                brightness: 1.0,
+                do_dpi_dilate: true,
+                wrapping: Wrapping::Line,
+                ..Style::style(cx)
+            },
             code_icon: CodeIcon {
                 ..Style::style(cx)
             },
@@ -236,9 +383,17 @@ impl Style for CodeEditor {
             },
             open_font_size: 11.0,
             folded_font_size: 0.5,
-            line_number_width: 45.,
+            line_number_width: 60.,
             cursor_blink_speed: 0.5,
             top_padding: 27.,
+            soft_wrap: false,
+            wrap_indent: 4,
+            show_minimap: false,
+            minimap_width: 100.,
+            minimap_font_size: 1.2,
+            hover_delay: 0.6,
+            max_completions: 10,
+            completion_providers: vec![Rc::new(TokenCompletionProvider::default())],
             _monospace_size: Vec2::zero(),
             _monospace_base: Vec2::zero(),
             _last_finger_move: None,
@@ -250,6 +405,29 @@ impl Style for CodeEditor {
             
             
             _line_geometry: Vec::new(),
+            _wrap_geometry: Vec::new(),
+            _wrap_moved: Vec::new(),
+            _wrap_right: 0.,
+            _line_col: 0,
+            _line_indent: 0,
+            _line_first_y: 0.,
+            _line_visual_rows: 1,
+            _line_folded_rows: 0,
+            _fold_ranges: Vec::new(),
+            _fold_skip: None,
+            _minimap_area: Area::Empty,
+            _minimap_scroll: 0.,
+            _minimap_line_height: 0.,
+            _git_hover_change: None,
+            _quick_fix_rows: Vec::new(),
+            _hover_timer: Timer::empty(),
+            _hover_abs: Vec2::zero(),
+            _hover_info: None,
+            _completion_anchor: None,
+            _completion_prefix: Vec::new(),
+            _completion_items: Vec::new(),
+            _completions: Vec::new(),
+            _completion_index: 0,
             
             _anim_select: Vec::new(),
             _grid_select_corner: None,
@@ -305,14 +483,21 @@ impl Style for CodeEditor {
 pub enum CodeEditorEvent {
     None,
     AutoFormat,
+    FoldChange,
     LagChange,
-    Change
+    Change,
+    CompletionRequest {offset: usize},
+    HoverRequest {offset: usize},
+    DefinitionRequest {offset: usize},
+    BreakpointChange {row: usize}
 }
 
 impl CodeEditor {
     
     pub fn def_indent_lines_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast !({
+            use df;
+            
             let indent_id: float<Instance>;
             let indent_sel: float<Uniform>;
             fn pixel() -> vec4 {
@@ -325,10 +510,10 @@ impl CodeEditor {
                 else {
                     col *= vec4(0.75, 0.75, 0.75, 0.75);
                 }
-                df_viewport(pos * vec2(w, h));
-                df_move_to(1., -1.);
-                df_line_to(1., h + 1.);
-                return df_stroke(col, thickness);
+                df::viewport(pos * vec2(w, h));
+                df::move_to(1., -1.);
+                df::line_to(1., h + 1.);
+                return df::stroke(col, thickness);
             }
         }))
     }
@@ -349,6 +534,8 @@ impl CodeEditor {
     
     pub fn def_selection_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast !({
+            use df;
+            
             let prev_x: float<Instance>;
             let prev_w: float<Instance>;
             let next_x: float<Instance>;
@@ -368,49 +555,53 @@ impl CodeEditor {
             }
             
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                df_box(0., 0., w, h, border_radius);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0., 0., w, h, border_radius);
                 if prev_w > 0. {
-                    df_box(prev_x, -h, prev_w, h, border_radius);
-                    df_gloop(gloopiness);
+                    df::round_rect(prev_x, -h, prev_w, h, border_radius);
+                    df::gloop(gloopiness);
                 }
                 if next_w > 0. {
-                    df_box(next_x, h, next_w, h, border_radius);
-                    df_gloop(gloopiness);
+                    df::round_rect(next_x, h, next_w, h, border_radius);
+                    df::gloop(gloopiness);
                 }
-                //df_shape *= cos(pos.x*8.)+cos(pos.y*16.);
-                return df_fill(color);
+                //df::shape *= cos(pos.x*8.)+cos(pos.y*16.);
+                return df::fill(color);
             }
         }))
     }
     
     pub fn def_paren_pair_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                //df_rect(0.,0.,w,h);
-                //df_rect(0.5,0.5,w-1.,h-1.);
-                //return df_stroke(color, 0.75 + dpi_dilate*0.75);
-                //df_rect(0.,h-1.-dpi_dilate,w,1.+dpi_dilate);
-                df_rect(0., h - 1.5 - dpi_dilate, w, 1.5 + dpi_dilate);
-                return df_fill(color);
-                //df_rect(0.01,0.,w,h);
-                //return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                //df::rect(0.,0.,w,h);
+                //df::rect(0.5,0.5,w-1.,h-1.);
+                //return df::stroke(color, 0.75 + dpi_dilate*0.75);
+                //df::rect(0.,h-1.-dpi_dilate,w,1.+dpi_dilate);
+                df::rect(0., h - 1.5 - dpi_dilate, w, 1.5 + dpi_dilate);
+                return df::fill(color);
+                //df::rect(0.01,0.,w,h);
+                //return df::fill(color);
             }
         }))
     }
     
     pub fn def_cursor_row_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                df_rect(0., 0., w, h);
-                return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                df::rect(0., 0., w, h);
+                return df::fill(color);
                 /*
-                df_move_to(0.,0.5);
-                df_line_to(w,0.5);
-                df_move_to(0.,h-0.5);
-                df_line_to(w,h-0.5);
+                df::move_to(0.,0.5);
+                df::line_to(w,0.5);
+                df::move_to(0.,h-0.5);
+                df::line_to(w,h-0.5);
                 returndf_stroke(color,0.75+dpi_dilate*0.75);*/
             }
         }))
@@ -418,36 +609,141 @@ impl CodeEditor {
     
     pub fn def_select_highlight_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                df_box(0.5, 0.5, w - 1., h - 1., 1.);
-                return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0.5, 0.5, w - 1., h - 1., 1.);
+                return df::fill(color);
             }
         }))
     }
     
     pub fn def_token_highlight_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             let visible: float<Uniform>;
             fn pixel() -> vec4 {
                 if visible<0.5 {
                     return vec4(0., 0., 0., 0.)
                 }
-                df_viewport(pos * vec2(w, h));
-                df_box(0.5, 0.5, w - 1., h - 1., 1.);
-                return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0.5, 0.5, w - 1., h - 1., 1.);
+                return df::fill(color);
             }
         }))
     }
     pub fn def_message_marker_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             fn pixel() -> vec4 {
                 let pos2 = vec2(pos.x, pos.y + 0.03 * sin(pos.x * w));
-                df_viewport(pos2 * vec2(w, h));
-                //df_rect(0.,0.,w,h);
-                df_move_to(0., h - 1.);
-                df_line_to(w, h - 1.);
-                return df_stroke(color, 0.8);
+                df::viewport(pos2 * vec2(w, h));
+                //df::rect(0.,0.,w,h);
+                df::move_to(0., h - 1.);
+                df::line_to(w, h - 1.);
+                return df::stroke(color, 0.8);
+            }
+        }))
+    }
+    
+    pub fn def_fold_marker_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            let folded: float<Instance>;
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                let cy = h * 0.5;
+                if folded > 0.5 { // pointing right
+                    df::move_to(2.5, cy - 3.5);
+                    df::line_to(6.5, cy);
+                    df::line_to(2.5, cy + 3.5);
+                }
+                else { // pointing down
+                    df::move_to(1., cy - 2.);
+                    df::line_to(8., cy - 2.);
+                    df::line_to(4.5, cy + 2.);
+                }
+                df::close_path();
+                return df::fill(color);
+            }
+        }))
+    }
+    
+    pub fn def_fold_placeholder_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(1., 1., w - 2., h - 2., 1.5);
+                df::stroke(color, 0.8);
+                df::circle(w * 0.3, h * 0.5, 1.);
+                df::circle(w * 0.5, h * 0.5, 1.);
+                df::circle(w * 0.7, h * 0.5, 1.);
+                return df::fill(color);
+            }
+        }))
+    }
+    
+    pub fn def_git_marker_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            let deleted: float<Instance>;
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                if deleted > 0.5 { // a small wedge between the lines
+                    df::move_to(0., 0.);
+                    df::line_to(w, h * 0.5);
+                    df::line_to(0., h);
+                    df::close_path();
+                    return df::fill(color);
+                }
+                df::rect(0., 0., w, h);
+                return df::fill(color);
+            }
+        }))
+    }
+    
+    pub fn def_quick_fix_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                // a lightbulb, the bulb and its base
+                df::circle(w * 0.5, h * 0.4, w * 0.3);
+                df::round_rect(w * 0.35, h * 0.6, w * 0.3, h * 0.2, 1.);
+                df::union();
+                return df::fill(color);
+            }
+        }))
+    }
+    
+    pub fn def_debug_marker_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            let exec: float<Instance>;
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                let cy = h * 0.5;
+                if exec > 0.5 { // an arrow at the current line
+                    df::move_to(1., cy - 4.);
+                    df::line_to(6., cy - 4.);
+                    df::line_to(10., cy);
+                    df::line_to(6., cy + 4.);
+                    df::line_to(1., cy + 4.);
+                    df::close_path();
+                }
+                else {
+                    df::circle(w * 0.5, cy, 4.);
+                }
+                return df::fill(color);
             }
         }))
     }
@@ -585,6 +881,14 @@ impl CodeEditor {
                     let delta = self.compute_next_unfolded_line_up(text_buffer);
                     self.cursors.move_up(delta, ke.modifiers.shift, text_buffer);
                 }
+                else if self.soft_wrap {
+                    self.move_cursors_wrapped(-1, ke.modifiers.shift, text_buffer);
+                }
+                else if text_buffer.folds.len() > 0 && self.cursors.set.len() == 1 {
+                    // skip over the lines hidden in folded ranges
+                    let delta = self.compute_next_unfolded_line_up(text_buffer);
+                    self.cursors.move_up(delta, ke.modifiers.shift, text_buffer);
+                }
                 else {
                     self.cursors.move_up(1, ke.modifiers.shift, text_buffer);
                 }
@@ -596,6 +900,13 @@ impl CodeEditor {
                     let delta = self.compute_next_unfolded_line_down(text_buffer);
                     self.cursors.move_down(delta, ke.modifiers.shift, text_buffer);
                 }
+                else if self.soft_wrap {
+                    self.move_cursors_wrapped(1, ke.modifiers.shift, text_buffer);
+                }
+                else if text_buffer.folds.len() > 0 && self.cursors.set.len() == 1 {
+                    let delta = self.compute_next_unfolded_line_down(text_buffer);
+                    self.cursors.move_down(delta, ke.modifiers.shift, text_buffer);
+                }
                 else {
                     self.cursors.move_down(1, ke.modifiers.shift, text_buffer);
                 }
@@ -708,6 +1019,11 @@ impl CodeEditor {
             _ => false
         };
         if cursor_moved {
+            let forward = match ke.key_code {
+                KeyCode::ArrowDown | KeyCode::ArrowRight | KeyCode::PageDown | KeyCode::End => true,
+                _ => false
+            };
+            self.skip_folded_ranges(forward, ke.modifiers.shift, text_buffer);
             self.update_highlight(cx, text_buffer);
             self.scroll_last_cursor_visible(cx, text_buffer, 0.);
             self.view.redraw_view_area(cx);
@@ -780,6 +1096,27 @@ impl CodeEditor {
             // in JS this wasn't possible performantly but in Rust its a breeze.
             self.view.redraw_view_area(cx);
         }
+        if self.show_minimap {
+            match event.hits(cx, self._minimap_area, HitOpt::default()) {
+                Event::FingerDown(fe) => {
+                    cx.set_down_mouse_cursor(MouseCursor::Default);
+                    self.scroll_to_minimap_pos(cx, fe.rel.y);
+                    return CodeEditorEvent::None
+                },
+                Event::FingerMove(fe) => {
+                    self.scroll_to_minimap_pos(cx, fe.rel.y);
+                    return CodeEditorEvent::None
+                },
+                Event::FingerHover(_fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Default);
+                    return CodeEditorEvent::None
+                },
+                Event::FingerUp(_fe) => {
+                    return CodeEditorEvent::None
+                },
+                _ => ()
+            }
+        }
         // global events
         match event {
             Event::Timer(te) => if self._cursor_blink_timer.is_timer(te) {
@@ -799,10 +1136,17 @@ impl CodeEditor {
                         return CodeEditorEvent::LagChange;
                     }
                 }
+            }
+            else if self._hover_timer.is_timer(te) {
+                self._hover_timer = Timer::empty();
+                if self._text_area != Area::Empty {
+                    let offset = self.text.find_closest_offset(cx, &self._text_area, self._hover_abs);
+                    return CodeEditorEvent::HoverRequest {offset: offset}
+                }
             },
             Event::Signal(se) => if text_buffer.signal.is_signal(se) {
                 match se.value {
-                    SIGNAL_TEXTBUFFER_MESSAGE_UPDATE | SIGNAL_TEXTBUFFER_LOADED | SIGNAL_TEXTBUFFER_DATA_UPDATE => {
+                    SIGNAL_TEXTBUFFER_MESSAGE_UPDATE | SIGNAL_TEXTBUFFER_LOADED | SIGNAL_TEXTBUFFER_DATA_UPDATE | SIGNAL_TEXTBUFFER_LINE_CHANGES_UPDATE | SIGNAL_TEXTBUFFER_DEBUG_UPDATE => {
                         self.view.redraw_view_area(cx);
                     },
                     SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET => {
@@ -837,10 +1181,70 @@ impl CodeEditor {
                 self.view.redraw_view_area(cx)
             },
             Event::FingerDown(fe) => {
+                if fe.rel.x < self.line_number_width && fe.rel.x >= self.line_number_width - 14. {
+                    let pos = self.compute_grid_text_pos_from_abs(cx, fe.abs);
+                    if let Ok(index) = self._fold_ranges.binary_search_by_key(&pos.row, | range | range.row) {
+                        self.toggle_fold_range(cx, index, text_buffer);
+                        return CodeEditorEvent::FoldChange
+                    }
+                }
+                if let Some(index) = self.find_quick_fix_at(cx, fe.rel.x, fe.abs) {
+                    if self.apply_quick_fix(cx, index, text_buffer) {
+                        return CodeEditorEvent::Change
+                    }
+                }
+                // the leftmost strip toggles breakpoints, the quick fixes have the strip next to it
+                if fe.rel.x < 12. {
+                    let row = self.compute_grid_text_pos_from_abs(cx, fe.abs).row;
+                    if row < text_buffer.lines.len() {
+                        text_buffer.debug.toggle_breakpoint(row);
+                        self.view.redraw_view_area(cx);
+                        return CodeEditorEvent::BreakpointChange {row: row}
+                    }
+                }
+                if let Some(index) = self.find_git_change_at(cx, fe.rel.x, fe.abs, text_buffer) {
+                    let change = text_buffer.line_changes.changes[index];
+                    self.cursors.revert_line_change(change, text_buffer);
+                    self._git_hover_change = None;
+                    self.view.redraw_view_area(cx);
+                    self.update_highlight(cx, text_buffer);
+                    self.reset_cursor_blinker(cx);
+                    return CodeEditorEvent::Change
+                }
+                self.hide_popups(cx);
+                // alt+click on a name jumps to its definition, ctrl+click stays for adding cursors
+                if fe.modifiers.alt && !fe.modifiers.shift && fe.rel.x > self.line_number_width {
+                    let offset = self.text.find_closest_offset(cx, &self._text_area, fe.abs);
+                    if SymbolIndex::identifier_at(text_buffer, offset).is_some() {
+                        return CodeEditorEvent::DefinitionRequest {offset: offset}
+                    }
+                }
                 self.handle_finger_down(cx, &fe, text_buffer);
             },
-            Event::FingerHover(_fe) => {
-                cx.set_hover_mouse_cursor(MouseCursor::Text);
+            Event::FingerHover(fe) => {
+                let hover_change = self.find_git_change_at(cx, fe.rel.x, fe.abs, text_buffer);
+                if hover_change.is_some() || fe.rel.x < 24. {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                }
+                else {
+                    cx.set_hover_mouse_cursor(MouseCursor::Text);
+                }
+                if hover_change != self._git_hover_change {
+                    self._git_hover_change = hover_change;
+                    self.view.redraw_view_area(cx);
+                }
+                // ask for hover info when the mouse rests on the text
+                cx.stop_timer(&mut self._hover_timer);
+                if hover_change.is_none() && fe.rel.x > self.line_number_width {
+                    if self._hover_info.is_some() && fe.abs.distance(&self._hover_abs) > 8. {
+                        self._hover_info = None;
+                        self.view.redraw_view_area(cx);
+                    }
+                    if self._hover_info.is_none() {
+                        self._hover_abs = fe.abs;
+                        self._hover_timer = cx.start_timer(self.hover_delay, false);
+                    }
+                }
             },
             Event::FingerUp(fe) => {
                 self.handle_finger_up(cx, &fe, text_buffer);
@@ -849,10 +1253,58 @@ impl CodeEditor {
                 self.handle_finger_move(cx, &fe, text_buffer);
             },
             Event::KeyDown(ke) => {
+                self.hide_hover_info(cx);
+                if self._completions.len() > 0 {
+                    match ke.key_code {
+                        KeyCode::ArrowUp => {
+                            self._completion_index = if self._completion_index == 0 {self._completions.len() - 1}else {self._completion_index - 1};
+                            self.view.redraw_view_area(cx);
+                            return CodeEditorEvent::None
+                        },
+                        KeyCode::ArrowDown => {
+                            self._completion_index = (self._completion_index + 1) % self._completions.len();
+                            self.view.redraw_view_area(cx);
+                            return CodeEditorEvent::None
+                        },
+                        KeyCode::Return | KeyCode::Tab => {
+                            self.accept_completion(cx, text_buffer);
+                            return CodeEditorEvent::Change
+                        },
+                        _ => ()
+                    }
+                }
+                if self._completion_anchor.is_some() && ke.key_code == KeyCode::Escape {
+                    self.hide_completions(cx);
+                    return CodeEditorEvent::None
+                }
+                if ke.key_code == KeyCode::Space && (ke.modifiers.logo || ke.modifiers.control) {
+                    self.open_completions(cx, text_buffer);
+                    return CodeEditorEvent::CompletionRequest {offset: self.cursors.set[self.cursors.last_cursor].head}
+                }
+                if ke.key_code == KeyCode::Period && (ke.modifiers.logo || ke.modifiers.control) {
+                    // apply the quick fix on the line of the cursor
+                    let row = text_buffer.offset_to_text_pos(self.cursors.set[self.cursors.last_cursor].head).row;
+                    if let Some((_, index)) = self._quick_fix_rows.iter().find( | (r, _) | *r == row).cloned() {
+                        if self.apply_quick_fix(cx, index, text_buffer) {
+                            return CodeEditorEvent::Change
+                        }
+                    }
+                    return CodeEditorEvent::None
+                }
+                if ke.key_code == KeyCode::F12 {
+                    return CodeEditorEvent::DefinitionRequest {offset: self.cursors.set[self.cursors.last_cursor].head}
+                }
                 if ke.key_code == KeyCode::Return && (ke.modifiers.logo || ke.modifiers.control) {
                     return CodeEditorEvent::AutoFormat
                 }
+                if (ke.key_code == KeyCode::LBracket || ke.key_code == KeyCode::RBracket) && ke.modifiers.shift && (ke.modifiers.logo || ke.modifiers.control) {
+                    if self.fold_at_last_cursor(cx, ke.key_code == KeyCode::LBracket, text_buffer) {
+                        return CodeEditorEvent::FoldChange
+                    }
+                    return CodeEditorEvent::None
+                }
                 self.handle_key_down(cx, &ke, text_buffer);
+                self.update_completions(cx, text_buffer);
             },
             Event::KeyUp(ke) => {
                 match ke.key_code {
@@ -868,6 +1320,7 @@ impl CodeEditor {
             },
             Event::TextInput(te) => {
                 self.handle_text_input(cx, &te, text_buffer);
+                self.update_completions(cx, text_buffer);
             },
             Event::TextCopy(_) => match event { // access the original event
                 Event::TextCopy(req) => {
@@ -924,9 +1377,15 @@ impl CodeEditor {
             self._highlight_area = cx.new_instance_draw_call(&self.token_highlight.shader, 0).into_area();
             //cx.new_instance_layer(self.select_highlight.shader_id, 0);
             cx.new_instance_draw_call(&self.cursor_row.shader, 0);
+            cx.new_instance_draw_call(&self.exec_row.shader, 0);
             cx.new_instance_draw_call(&self.selection.shader, 0);
             cx.new_instance_draw_call(&self.message_marker.shader, 0);
             cx.new_instance_draw_call(&self.paren_pair.shader, 0);
+            cx.new_instance_draw_call(&self.fold_marker.shader, 0);
+            cx.new_instance_draw_call(&self.fold_placeholder.shader, 0);
+            cx.new_instance_draw_call(&self.git_marker.shader, 0);
+            cx.new_instance_draw_call(&self.debug_marker.shader, 0);
+            cx.new_instance_draw_call(&self.quick_fix.shader, 0);
             self._line_number_inst = Some(self.text.begin_text(cx));
             cx.new_instance_draw_call(&self.text.shader, 0);
             // force next begin_text in another drawcall
@@ -969,6 +1428,9 @@ impl CodeEditor {
                 self._draw_messages.set_next(&text_buffer.messages.cursors);
             }
             self._last_cursor_pos = self.cursors.get_last_cursor_text_pos(text_buffer);
+            self.compute_fold_ranges(text_buffer);
+            self._fold_skip = None;
+            self._line_folded_rows = 0;
             
             // indent
             cx.move_turtle(self.line_number_width, self.top_padding);
@@ -977,6 +1439,17 @@ impl CodeEditor {
             self.do_folding_animation_step(cx);
             
             self._line_geometry.truncate(0);
+            self._wrap_geometry.truncate(0);
+            self._wrap_right = cx.get_turtle_origin().x + cx.get_width_total() - self.bg_layout.padding.r;
+            if let Some(scroll_v) = &self.view.scroll_v {
+                self._wrap_right -= scroll_v.bar_size;
+            }
+            if self.show_minimap {
+                self._wrap_right -= self.minimap_width;
+            }
+            self._line_col = 0;
+            self._line_indent = 0;
+            self._line_visual_rows = 1;
             self._scroll_pos = self.view.get_scroll_pos(cx);
             
             return Ok(())
@@ -1013,7 +1486,7 @@ impl CodeEditor {
                     }
                     else {
                         self._monospace_base.y * self.open_font_size
-                    }
+                    } * (geom.visual_rows as f32)
                 }
                 ypos += self._final_fill_height;
                 let dy = self._line_geometry[focus_line].walk.y - ypos_at_line;
@@ -1040,20 +1513,43 @@ impl CodeEditor {
     
     fn draw_new_line(&mut self, cx: &mut Cx) {
         // line geometry is used for scrolling look up of cursors
-        let line_geom = LineGeom {
-            walk: cx.get_rel_turtle_walk(),
+        let mut walk = cx.get_rel_turtle_walk();
+        let row_y = walk.y;
+        if self._line_visual_rows > 1 { // wrapped lines report the position of their first row
+            walk.y = self._line_first_y;
+        }
+        let mut line_geom = LineGeom {
+            walk: walk,
             font_size: self._line_largest_font,
             was_folded: self._line_was_folded,
+            visual_rows: self._line_visual_rows,
             indent_id: if let Some((_, id)) = self._indent_stack.last() {*id}else {0.}
         };
         
+        // a line with a folded range in it belongs to the row where the range starts
+        let first_row = self._line_geometry.len() - self._line_folded_rows;
+        if self._line_folded_rows > 0 {
+            self._line_geometry[first_row].visual_rows = self._line_visual_rows;
+            line_geom.visual_rows = 0;
+        }
+        
         // draw a linenumber if we are visible
         if self._line_was_visible {
+            if let Ok(index) = self._fold_ranges.binary_search_by_key(&first_row, | range | range.row) {
+                self.fold_marker.color = self.colors.fold_marker;
+                let inst = self.fold_marker.draw_quad(cx, Rect {
+                    x: self.line_number_width - 12.,
+                    y: line_geom.walk.y,
+                    w: 10.,
+                    h: self._monospace_size.y
+                });
+                inst.push_float(cx, if self._fold_ranges[index].folded {1.}else {0.});
+            }
             // lets format a number, we go to 4 numbers
             // yes this is dumb as rocks. but we need to be cheapnfast
             let chunk = &mut self._line_number_chunk;
             chunk.truncate(0);
-            let line_num = self._line_geometry.len() + 1;
+            let line_num = first_row + 1;
             let mut scale = 10000;
             let mut fill = false;
             loop {
@@ -1090,10 +1586,82 @@ impl CodeEditor {
         
         self._tokens_on_line = 0;
         self._line_was_visible = false;
+        self._line_col = 0;
+        self._line_indent = 0;
+        self._line_visual_rows = 1;
+        self._line_folded_rows = 0;
         
         self._draw_cursors.process_newline();
         self._draw_messages.process_newline();
         
+        self.draw_selection_highlight(cx, row_y);
+        
+        // search for all markings
+        self._line_geometry.push(line_geom);
+        self._line_largest_font = self.text.font_size;
+    }
+    
+    fn draw_folded_line(&mut self, cx: &mut Cx) {
+        // a newline hidden in a folded range, the row shares the visual line it was folded into
+        let mut walk = cx.get_rel_turtle_walk();
+        if self._line_visual_rows > 1 {
+            walk.y = self._line_first_y;
+        }
+        self._line_geometry.push(LineGeom {
+            walk: walk,
+            font_size: self._line_largest_font,
+            was_folded: self._line_was_folded,
+            visual_rows: 0,
+            indent_id: if let Some((_, id)) = self._indent_stack.last() {*id}else {0.}
+        });
+        self._line_folded_rows += 1;
+        self._line_col = 0;
+    }
+    
+    fn draw_fold_placeholder(&mut self, cx: &mut Cx, offset: usize) {
+        // the placeholder stands in for the position right after the open paren
+        if let Some(geom) = cx.walk_turtle_text(
+            self._monospace_size.x * 3.,
+            self._monospace_size.y,
+            self._scroll_pos
+        ) {
+            self.fold_placeholder.color = self.colors.fold_placeholder;
+            self.fold_placeholder.draw_quad_abs(cx, geom);
+            let cursors = &self.cursors.set;
+            let last_cursor = self.cursors.last_cursor;
+            self._draw_cursors.mark_text_with_cursor(cursors, ' ', offset, geom.x, geom.y, geom.w, geom.h, last_cursor, 0.);
+        }
+    }
+    
+    fn draw_wrap_line(&mut self, cx: &mut Cx, col: usize) {
+        // soft wrap, continue the current line on a new visual row
+        let row_y = cx.get_rel_turtle_walk().y;
+        cx.turtle_new_line_min_height(self._monospace_size.y);
+        
+        // continuation rows are indented one step further than the line itself,
+        // unless that would eat more than half of the available width
+        let max_cols = ((self._wrap_right - cx.get_turtle_walk().x - self.line_number_width) / self._monospace_size.x).max(0.) as usize;
+        let mut indent = self._line_indent + self.wrap_indent;
+        if indent > max_cols / 2 {
+            indent = self.wrap_indent.min(max_cols / 2);
+        }
+        cx.move_turtle(self.line_number_width + (indent as f32) * self._monospace_size.x, 0.);
+        
+        self._line_visual_rows += 1;
+        self._wrap_geometry.push(WrapGeom {
+            row: self._line_geometry.len(),
+            col: col,
+            indent: indent,
+            y: cx.get_rel_turtle_walk().y
+        });
+        
+        self._draw_cursors.process_newline();
+        self._draw_messages.process_newline();
+        
+        self.draw_selection_highlight(cx, row_y);
+    }
+    
+    fn draw_selection_highlight(&mut self, cx: &mut Cx, row_y: f32) {
         // highlighting the selection
         let hl_len = self._highlight_selection.len();
         if hl_len != 0 {
@@ -1111,7 +1679,7 @@ impl CodeEditor {
                     let max_x = self._line_chunk[bp + hl_len].0;
                     self.draw_token_highlight_quad(cx, Rect {
                         x: min_x,
-                        y: line_geom.walk.y + origin.y,
+                        y: row_y + origin.y,
                         w: max_x - min_x,
                         h: self._monospace_size.y,
                     });
@@ -1119,10 +1687,6 @@ impl CodeEditor {
             }
             self._line_chunk.truncate(0);
         }
-        
-        // search for all markings
-        self._line_geometry.push(line_geom);
-        self._line_largest_font = self.text.font_size;
     }
     
     fn draw_indent_lines(&mut self, cx: &mut Cx, geom_y: f32, tabs: usize) {
@@ -1155,6 +1719,20 @@ impl CodeEditor {
         let offset = token_chunk.offset; // end_offset - chunk.len() - 1;
         let next_char = token_chunk.next;
         
+        // skip everything inside a folded range up to its close paren
+        if let Some(fold_end) = self._fold_skip {
+            if offset < fold_end {
+                if token_type == TokenType::Newline {
+                    self.draw_folded_line(cx);
+                }
+                else {
+                    self._line_col += chunk.len();
+                }
+                return
+            }
+            self._fold_skip = None;
+        }
+        
         // maintain paren stack
         if token_type == TokenType::ParenOpen {
             self.draw_paren_open(token_chunks_index, offset, next_char, chunk);
@@ -1220,18 +1798,85 @@ impl CodeEditor {
                 _ => ()
             }
         }
+        if self._tokens_on_line == 0 {
+            self._line_first_y = cx.get_rel_turtle_walk().y;
+            if token_type == TokenType::Whitespace {
+                self._line_indent = chunk.len();
+            }
+            if self.soft_wrap {
+                self._wrap_geometry.push(WrapGeom {
+                    row: self._line_geometry.len(),
+                    col: 0,
+                    indent: 0,
+                    y: self._line_first_y
+                });
+            }
+        }
+        
+        if self.soft_wrap && token_type != TokenType::Newline {
+            // split the chunk over as many visual rows as it needs, preferring to wrap before it
+            let mut part_start = 0;
+            while part_start < chunk.len() {
+                let col = self._line_col + part_start;
+                let row_empty = if let Some(wrap) = self._wrap_geometry.last() {wrap.col == col}else {true};
+                let fits = ((self._wrap_right - cx.get_turtle_walk().x) / self._monospace_size.x).max(0.) as usize;
+                let rest = chunk.len() - part_start;
+                let part_len = if rest <= fits {
+                    rest
+                }
+                else if token_type == TokenType::Whitespace && fits > 0 { // whitespace fills up the row first
+                    fits
+                }
+                else if !row_empty {
+                    self.draw_wrap_line(cx, col);
+                    continue;
+                }
+                else {
+                    fits.max(1)
+                };
+                self.draw_chunk_part(cx, token_type, chunk, part_start, part_len, offset, next_char, message_cursors);
+                part_start += part_len;
+            }
+        }
+        else {
+            self.draw_chunk_part(cx, token_type, chunk, 0, chunk.len(), offset, next_char, message_cursors);
+        }
+        self._tokens_on_line += 1;
+        self._line_col += chunk.len();
+        
+        // Do all the Paren matching highlighting drawing
+        if token_chunk.token_type == TokenType::ParenClose {
+            self.draw_paren_close(cx, token_chunks_index, offset, next_char, chunk);
+        }
+        else if token_type == TokenType::ParenOpen {
+            if let Ok(index) = self._fold_ranges.binary_search_by_key(&offset, | range | range.start) {
+                if self._fold_ranges[index].folded {
+                    self.draw_fold_placeholder(cx, offset + 1);
+                    self._fold_skip = Some(self._fold_ranges[index].end);
+                }
+            }
+        }
+        else {
+            if token_type == TokenType::Newline {
+                self.draw_new_line(cx);
+            }
+        }
+    }
+    
+    fn draw_chunk_part(&mut self, cx: &mut Cx, token_type: TokenType, chunk: &[char], part_start: usize, part_len: usize, offset: usize, next_char: char, message_cursors: &Vec<TextCursor>) {
         // lets check if the geom is visible
+        let part = &chunk[part_start..(part_start + part_len)];
+        let offset = offset + part_start;
         if let Some(geom) = cx.walk_turtle_text(
-            self._monospace_size.x * (chunk.len() as f32),
-            
+            self._monospace_size.x * (part.len() as f32),
             self._monospace_size.y,
             self._scroll_pos
         ) {
             let mut mark_spaces = 0.0;
-            // determine chunk color
-            self.text.color = match token_type {
+            // indent lines, token highlights and paren geometry
+            match token_type {
                 TokenType::Whitespace => {
-                    if self._tokens_on_line == 0 && chunk[0] == ' ' {
+                    if self._tokens_on_line == 0 && part_start == 0 && chunk[0] == ' ' {
                         let tabs = chunk.len()>>2;
                         // if self._last_tabs
                         self._last_tabs = tabs;
@@ -1241,7 +1886,6 @@ impl CodeEditor {
                     else if next_char == '\n' {
                         mark_spaces = 1.0;
                     }
-                    self.colors.whitespace
                 },
                 TokenType::Newline => {
                     if self._tokens_on_line == 0 {
@@ -1252,56 +1896,14 @@ impl CodeEditor {
                         self._last_tabs = self._newline_tabs;
                         self._newline_tabs = 0;
                     }
-                    self.colors.whitespace
-                },
-                TokenType::BuiltinType => self.colors.keyword,
-                TokenType::Keyword => self.colors.keyword,
-                TokenType::Bool => self.colors.keyword,
-                TokenType::Flow => {
-                    self.colors.flow
-                }
-                TokenType::Looping => {
-                    self.colors.looping
-                }
-                TokenType::TypeDef => {
-                    self.colors.keyword
-                }
-                TokenType::Fn => {
-                    self.colors.keyword
-                }
-                TokenType::Identifier => {
-                    if chunk == &self._highlight_token[0..] {
-                        self.draw_token_highlight_quad(cx, geom);
-                        
-                    }
-                    self.colors.identifier
-                }
-                TokenType::Call => {
-                    if chunk == &self._highlight_token[0..] {
-                        self.draw_token_highlight_quad(cx, geom);
-                    }
-                    self.colors.call
                 },
-                TokenType::TypeName => {
+                TokenType::Identifier | TokenType::Call | TokenType::TypeName => {
                     if chunk == &self._highlight_token[0..] {
                         self.draw_token_highlight_quad(cx, geom);
                     }
-                    self.colors.type_name
                 },
-                TokenType::Regex => self.colors.string,
-                TokenType::String => self.colors.string,
-                TokenType::Number => self.colors.number,
-                TokenType::CommentMultiBegin => self.colors.comment,
-                TokenType::CommentMultiEnd => self.colors.comment,
-                TokenType::CommentLine => self.colors.comment,
-                TokenType::CommentChunk => self.colors.comment,
                 TokenType::ParenOpen => {
-                    let depth = self._paren_stack.len();
                     self._paren_stack.last_mut().unwrap().geom_open = Some(geom);
-                    match depth % 2 {
-                        0 => self.colors.paren_d1,
-                        _ => self.colors.paren_d2,
-                    }
                 },
                 TokenType::ParenClose => {
                     if let Some(paren) = self._paren_stack.last_mut() {
@@ -1311,22 +1913,11 @@ impl CodeEditor {
                         self.paren_pair.color = self.colors.paren_pair_fail;
                         self.paren_pair.draw_quad_abs(cx, geom);
                     }
-                    let depth = self._paren_stack.len();
-                    match depth % 2 {
-                        0 => self.colors.paren_d1,
-                        _ => self.colors.paren_d2,
-                        //_=>self.colors.paren_d3
-                    }
                 },
-                TokenType::Operator => self.colors.operator,
-                TokenType::Namespace => self.colors.operator,
-                TokenType::Hash => self.colors.operator,
-                TokenType::Delimiter => self.colors.delimiter,
-                TokenType::Colon => self.colors.delimiter,
-                TokenType::Splat => self.colors.operator,
-                TokenType::Eof => self.colors.unexpected,
-                TokenType::Unexpected => self.colors.unexpected
-            };
+                _ => ()
+            }
+            // determine chunk color
+            self.text.color = self.colors.token_color(token_type, self._paren_stack.len());
             
             if self._tokens_on_line == 0 {
                 self._visible_lines += 1;
@@ -1344,7 +1935,7 @@ impl CodeEditor {
             if self._highlight_selection.len() > 0 { // slow loop
                 //let draw_search = &mut self._draw_search;
                 let line_chunk = &mut self._line_chunk;
-                self.text.add_text(cx, geom.x, geom.y, offset, self._text_inst.as_mut().unwrap(), &chunk, | ch, offset, x, w | {
+                self.text.add_text(cx, geom.x, geom.y, offset, self._text_inst.as_mut().unwrap(), &part, | ch, offset, x, w | {
                     line_chunk.push((x, ch));
                     //draw_search.mark_text_select_only(cursors, offset, x, geom.y, w, height);
                     draw_messages.mark_text_select_only(message_cursors, offset, x, geom.y, w, height);
@@ -1352,23 +1943,12 @@ impl CodeEditor {
                 });
             }
             else { // fast loop
-                self.text.add_text(cx, geom.x, geom.y, offset, self._text_inst.as_mut().unwrap(), &chunk, | ch, offset, x, w | {
+                self.text.add_text(cx, geom.x, geom.y, offset, self._text_inst.as_mut().unwrap(), &part, | ch, offset, x, w | {
                     draw_messages.mark_text_select_only(message_cursors, offset, x, geom.y, w, height);
                     draw_cursors.mark_text_with_cursor(cursors, ch, offset, x, geom.y, w, height, last_cursor, mark_spaces)
                 });
             }
         }
-        self._tokens_on_line += 1;
-        
-        // Do all the Paren matching highlighting drawing
-        if token_chunk.token_type == TokenType::ParenClose {
-            self.draw_paren_close(cx, token_chunks_index, offset, next_char, chunk);
-        }
-        else {
-            if token_type == TokenType::Newline {
-                self.draw_new_line(cx);
-            }
-        }
     }
     
     fn draw_token_highlight_quad(&mut self, cx: &mut Cx, geom: Rect) {
@@ -1471,6 +2051,9 @@ impl CodeEditor {
         //self.do_selection_animations(cx);
         self.draw_selections(cx);
         self.draw_message_markers(cx, text_buffer);
+        self.draw_git_markers(cx, text_buffer);
+        self.draw_debug_markers(cx, text_buffer);
+        self.draw_quick_fix_markers(cx, text_buffer);
         
         // inject a final page
         self._final_fill_height = cx.get_height_total() - self._monospace_size.y;
@@ -1481,9 +2064,145 @@ impl CodeEditor {
         self.place_ime_and_draw_cursor_row(cx);
         self.set_indent_line_highlight_id(cx);
         
+        // the minimap goes on top of everything
+        if self.show_minimap {
+            self.draw_minimap(cx, text_buffer);
+        }
+        self.draw_popups(cx, text_buffer);
+        
         self.view.end_view(cx);
     }
     
+    fn draw_minimap(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        // the editor redraws on scroll, so we keep the minimap in place by offsetting it with the scroll position
+        let origin = cx.get_turtle_origin();
+        let scroll_pos = self._scroll_pos;
+        let view_height = cx.get_height_total();
+        let mut right = origin.x + cx.get_width_total();
+        if let Some(scroll_v) = &self.view.scroll_v {
+            right -= scroll_v.bar_size;
+        }
+        let rect = Rect {
+            x: right - self.minimap_width + scroll_pos.x,
+            y: origin.y + scroll_pos.y,
+            w: self.minimap_width,
+            h: view_height
+        };
+        
+        cx.new_instance_draw_call(&self.minimap_bg.shader, 0);
+        self.minimap_bg.color = self.colors.minimap_bg;
+        self._minimap_area = self.minimap_bg.draw_quad_abs(cx, rect).into_area();
+        
+        // scroll the minimap along proportionally when the file doesn't fit
+        let line_height = self._monospace_base.y * self.minimap_font_size;
+        let char_width = self._monospace_base.x * self.minimap_font_size;
+        let total_height = line_height * (text_buffer.lines.len() as f32);
+        let scroll_range = self.view.get_scroll_view_total().y - view_height;
+        self._minimap_line_height = line_height;
+        self._minimap_scroll = if total_height > view_height && scroll_range > 0. {
+            (scroll_pos.y / scroll_range).min(1.) * (total_height - view_height)
+        }
+        else {
+            0.
+        };
+        
+        // draw the text
+        cx.new_instance_draw_call(&self.minimap_text.shader, 0);
+        let mut text_inst = self.minimap_text.begin_text(cx);
+        self.minimap_text.font_size = self.minimap_font_size;
+        let first_row = (self._minimap_scroll / line_height) as usize;
+        let last_row = first_row + (view_height / line_height) as usize + 1;
+        let max_cols = ((self.minimap_width - 4.) / char_width).max(0.) as usize;
+        let mut row = 0;
+        let mut col = 0;
+        for token_chunk in &text_buffer.token_chunks {
+            if token_chunk.token_type == TokenType::Newline {
+                row += 1;
+                col = 0;
+                if row > last_row {
+                    break;
+                }
+                continue;
+            }
+            if row >= first_row && col < max_cols && token_chunk.token_type != TokenType::Whitespace {
+                let len = token_chunk.len.min(max_cols - col);
+                let chunk = &text_buffer.flat_text[token_chunk.offset..(token_chunk.offset + len)];
+                self.minimap_text.color = self.colors.token_color(token_chunk.token_type, 0);
+                self.minimap_text.add_text(
+                    cx,
+                    rect.x + 2. + (col as f32) * char_width,
+                    rect.y + (row as f32) * line_height - self._minimap_scroll,
+                    token_chunk.offset,
+                    &mut text_inst,
+                    chunk,
+                    | _, _, _, _ | {0.}
+                );
+            }
+            col += token_chunk.len;
+        }
+        self.minimap_text.end_text(cx, &text_inst);
+        
+        // the rectangle of the visible part of the editor
+        cx.new_instance_draw_call(&self.minimap_viewport.shader, 0);
+        let top_row = self.find_row_at_ypos(scroll_pos.y);
+        let bottom_row = self.find_row_at_ypos(scroll_pos.y + view_height);
+        self.minimap_viewport.color = self.colors.minimap_viewport;
+        self.minimap_viewport.draw_quad_abs(cx, Rect {
+            x: rect.x,
+            y: rect.y + (top_row as f32) * line_height - self._minimap_scroll,
+            w: rect.w,
+            h: ((bottom_row - top_row + 1) as f32) * line_height
+        });
+        
+        // and the error and warning markers along the right edge
+        if text_buffer.messages.mutation_id == text_buffer.mutation_id {
+            for (index, cursor) in text_buffer.messages.cursors.iter().enumerate() {
+                let body = &text_buffer.messages.bodies[index];
+                self.minimap_marker.color = match body.level {
+                    TextBufferMessageLevel::Warning => self.colors.marker_warning,
+                    TextBufferMessageLevel::Error => self.colors.marker_error,
+                    TextBufferMessageLevel::Log => continue,
+                };
+                let marker_row = text_buffer.offset_to_text_pos(cursor.head).row;
+                let y = (marker_row as f32) * line_height - self._minimap_scroll;
+                if y < 0. || y > view_height {
+                    continue;
+                }
+                self.minimap_marker.draw_quad_abs(cx, Rect {
+                    x: rect.x + rect.w - 4.,
+                    y: rect.y + y,
+                    w: 4.,
+                    h: line_height.max(2.)
+                });
+            }
+        }
+    }
+    
+    fn scroll_to_minimap_pos(&mut self, cx: &mut Cx, rel_y: f32) {
+        // center the editor on the line under the finger
+        if self._line_geometry.len() == 0 || self._minimap_line_height <= 0. {
+            return
+        }
+        let row = (((rel_y + self._minimap_scroll) / self._minimap_line_height).max(0.) as usize).min(self._line_geometry.len() - 1);
+        let view_height = self.view.get_rect(cx).h;
+        let scroll_pos = self.view.get_scroll_pos(cx);
+        self.view.set_scroll_pos(cx, Vec2 {
+            x: scroll_pos.x,
+            y: (self._line_geometry[row].walk.y - 0.5 * view_height).max(0.)
+        });
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn find_row_at_ypos(&self, ypos: f32) -> usize {
+        // line geometry is sorted by y, folded rows share the y of their line
+        match self._line_geometry.binary_search_by( | geom | {
+            if geom.walk.y < ypos {std::cmp::Ordering::Less}else {std::cmp::Ordering::Greater}
+        }) {
+            Ok(row) => row,
+            Err(row) => row.min(self._line_geometry.len().max(1) - 1)
+        }
+    }
+    
     fn do_jump_to_offset(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
         let offset = text_buffer.messages.jump_to_offset;
         // make one cursor, and start scrolling towards it
@@ -1521,6 +2240,155 @@ impl CodeEditor {
         }
     }
     
+    fn draw_git_markers(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        if text_buffer.line_changes.mutation_id != text_buffer.mutation_id {
+            self._git_hover_change = None;
+            return
+        }
+        let geoms = &self._line_geometry;
+        for (index, change) in text_buffer.line_changes.changes.iter().enumerate() {
+            // a deletion at the end of the file sits below the last line
+            let y = if change.start_row < geoms.len() {
+                geoms[change.start_row].walk.y
+            }
+            else if change.start_row == geoms.len() && geoms.len() > 0 {
+                geoms[geoms.len() - 1].walk.y + self._monospace_size.y
+            }
+            else {
+                break;
+            };
+            let hovered = self._git_hover_change == Some(index);
+            if change.change_type == TextLineChangeType::Deleted {
+                self.git_marker.color = self.colors.git_deleted;
+                let inst = self.git_marker.draw_quad(cx, Rect {
+                    x: self.line_number_width - 3.,
+                    y: y - 4.,
+                    w: if hovered {6.}else {4.},
+                    h: 8.
+                });
+                inst.push_float(cx, 1.);
+                continue;
+            }
+            let end_y = if change.end_row < geoms.len() {geoms[change.end_row].walk.y}else {y + self._monospace_size.y};
+            if hovered { // highlight the lines a click would revert
+                self.git_marker.color = self.colors.git_hover;
+                let inst = self.git_marker.draw_quad(cx, Rect {
+                    x: self.line_number_width,
+                    y: y,
+                    w: cx.get_width_total().max(cx.get_turtle_bounds().x) - self.line_number_width,
+                    h: end_y - y
+                });
+                inst.push_float(cx, 0.);
+            }
+            self.git_marker.color = if change.change_type == TextLineChangeType::Added {
+                self.colors.git_added
+            }
+            else {
+                self.colors.git_modified
+            };
+            let inst = self.git_marker.draw_quad(cx, Rect {
+                x: self.line_number_width - 3.,
+                y: y,
+                w: if hovered {5.}else {3.},
+                h: end_y - y
+            });
+            inst.push_float(cx, 0.);
+        }
+    }
+    
+    fn draw_quick_fix_markers(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        self._quick_fix_rows.truncate(0);
+        // offsets in the fixes are only valid for the version the compiler saw
+        if text_buffer.messages.mutation_id != text_buffer.mutation_id {
+            return
+        }
+        for (index, body) in text_buffer.messages.bodies.iter().enumerate() {
+            if body.fixes.len() == 0 || index >= text_buffer.messages.cursors.len() {
+                continue;
+            }
+            let cursor = &text_buffer.messages.cursors[index];
+            let row = text_buffer.offset_to_text_pos(cursor.head.min(cursor.tail)).row;
+            if row >= self._line_geometry.len() || self._quick_fix_rows.iter().any( | (r, _) | *r == row) {
+                continue;
+            }
+            self._quick_fix_rows.push((row, index));
+            self.quick_fix.color = self.colors.quick_fix;
+            self.quick_fix.draw_quad(cx, Rect {
+                x: 13.,
+                y: self._line_geometry[row].walk.y,
+                w: 10.,
+                h: self._monospace_size.y
+            });
+        }
+    }
+    
+    fn draw_debug_markers(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        let geoms = &self._line_geometry;
+        for row in &text_buffer.debug.breakpoints {
+            if *row >= geoms.len() {
+                break;
+            }
+            self.debug_marker.color = self.colors.breakpoint;
+            let inst = self.debug_marker.draw_quad(cx, Rect {
+                x: 1.,
+                y: geoms[*row].walk.y,
+                w: 10.,
+                h: self._monospace_size.y
+            });
+            inst.push_float(cx, 0.);
+        }
+        if let Some(row) = text_buffer.debug.exec_row {
+            if row < geoms.len() {
+                let y = geoms[row].walk.y;
+                self.exec_row.color = self.colors.exec_row;
+                self.exec_row.draw_quad(cx, Rect {
+                    x: self.line_number_width,
+                    y: y,
+                    w: cx.get_width_total().max(cx.get_turtle_bounds().x) - self.line_number_width,
+                    h: self._monospace_size.y
+                });
+                self.debug_marker.color = self.colors.exec_marker;
+                let inst = self.debug_marker.draw_quad(cx, Rect {
+                    x: 1.,
+                    y: y,
+                    w: 10.,
+                    h: self._monospace_size.y
+                });
+                inst.push_float(cx, 1.);
+            }
+        }
+    }
+    
+    fn find_quick_fix_at(&mut self, cx: &Cx, rel_x: f32, abs: Vec2) -> Option<usize> {
+        if rel_x < 12. || rel_x >= 24. {
+            return None
+        }
+        let row = self.compute_grid_text_pos_from_abs(cx, abs).row;
+        self._quick_fix_rows.iter().find( | (r, _) | *r == row).map( | (_, index) | *index)
+    }
+    
+    // applies the suggested fixes of the message at index as a single undo step
+    fn apply_quick_fix(&mut self, cx: &mut Cx, index: usize, text_buffer: &mut TextBuffer) -> bool {
+        let fixes = text_buffer.messages.bodies[index].fixes.clone();
+        if !self.cursors.apply_fixes(&fixes, text_buffer) {
+            return false
+        }
+        self._quick_fix_rows.truncate(0);
+        self.view.redraw_view_area(cx);
+        self.update_highlight(cx, text_buffer);
+        self.reset_cursor_blinker(cx);
+        true
+    }
+    
+    fn find_git_change_at(&mut self, cx: &Cx, rel_x: f32, abs: Vec2, text_buffer: &TextBuffer) -> Option<usize> {
+        // the markers sit in the gutter between the fold markers and the text
+        if rel_x < self.line_number_width - 3. || rel_x >= self.line_number_width + 2. {
+            return None
+        }
+        let row = self.compute_grid_text_pos_from_abs(cx, abs).row;
+        text_buffer.find_line_change(row)
+    }
+    
     fn draw_selections(&mut self, cx: &mut Cx) {
         let origin = cx.get_turtle_origin();
         let sel = &mut self._draw_cursors.selections;
@@ -1686,9 +2554,16 @@ impl CodeEditor {
             let geom = &self._line_geometry[row];
             let mono_size = Vec2 {x: self._monospace_base.x * geom.font_size, y: self._monospace_base.y * geom.font_size};
             //self.text.get_monospace_size(cx, geom.font_size);
+            let (vis_col, vis_y) = if let Some(wrap_index) = self.find_wrap_index(pos) {
+                let wrap = &self._wrap_geometry[wrap_index];
+                (wrap.indent + pos.col - wrap.col, wrap.y)
+            }
+            else {
+                (pos.col, geom.walk.y)
+            };
             let rect = Rect {
-                x: (pos.col as f32) * mono_size.x + self.line_number_width,
-                y: geom.walk.y - mono_size.y * 1. - 0.5 * height_pad,
+                x: (vis_col as f32) * mono_size.x + self.line_number_width,
+                y: vis_y - mono_size.y * 1. - 0.5 * height_pad,
                 w: mono_size.x * 4.,
                 h: mono_size.y * 4. + height_pad
             };
@@ -1704,8 +2579,15 @@ impl CodeEditor {
         for (row, geom) in self._line_geometry.iter().enumerate() {
             //let geom = &self._line_geometry[pos.row];
             mono_size = Vec2 {x: self._monospace_base.x * geom.font_size, y: self._monospace_base.y * geom.font_size};
-            if rel.y < geom.walk.y || rel.y >= geom.walk.y && rel.y <= geom.walk.y + mono_size.y { // its on the right line
+            if rel.y < geom.walk.y || rel.y >= geom.walk.y && rel.y <= geom.walk.y + mono_size.y * (geom.visual_rows as f32) { // its on the right line
                 let col = ((rel.x - self.line_number_width).max(0.) / mono_size.x) as usize;
+                if geom.visual_rows > 1 { // find the visual row we are on
+                    for wrap in self._wrap_geometry.iter().filter( | wrap | wrap.row == row).rev() {
+                        if rel.y >= wrap.y {
+                            return TextPos {row: row, col: wrap.col + col.max(wrap.indent) - wrap.indent};
+                        }
+                    }
+                }
                 // do a dumb calc
                 return TextPos {row: row, col: col};
             }
@@ -1722,13 +2604,390 @@ impl CodeEditor {
         for (row, geom) in self._line_geometry.iter().enumerate() {
             //let geom = &self._line_geometry[pos.row];
             mono_size = Vec2 {x: self._monospace_base.x * geom.font_size, y: self._monospace_base.y * geom.font_size};
-            if rel.y < geom.walk.y || rel.y >= geom.walk.y && rel.y <= geom.walk.y + mono_size.y { // its on the right line
+            if rel.y < geom.walk.y || rel.y >= geom.walk.y && rel.y <= geom.walk.y + mono_size.y * (geom.visual_rows as f32) { // its on the right line
                 return text_buffer.text_pos_to_offset(TextPos {row: row, col: end_col})
             }
         }
         return text_buffer.text_pos_to_offset(TextPos {row: self._line_geometry.len() - 1, col: end_col})
     }
     
+    pub fn set_soft_wrap(&mut self, cx: &mut Cx, soft_wrap: bool) {
+        self.soft_wrap = soft_wrap;
+        if soft_wrap {
+            self.view.set_scroll_pos(cx, Vec2 {x: 0., y: self.view.get_scroll_pos(cx).y});
+        }
+        self.view.redraw_view_area(cx);
+    }
+    
+    pub fn set_show_minimap(&mut self, cx: &mut Cx, show_minimap: bool) {
+        self.show_minimap = show_minimap;
+        self.view.redraw_view_area(cx);
+    }
+    
+    pub fn show_hover_info(&mut self, cx: &mut Cx, offset: usize, text: String) {
+        if text.len() == 0 {
+            return
+        }
+        self._hover_info = Some((offset, text));
+        self.view.redraw_view_area(cx);
+    }
+    
+    // merges completions from an external provider into the open list
+    pub fn show_completions(&mut self, cx: &mut Cx, offset: usize, completions: Vec<CodeCompletion>) {
+        // drop stale results if the list was closed or the cursor moved away in the meantime
+        if let Some(anchor) = self._completion_anchor {
+            if offset < anchor {
+                return
+            }
+        }
+        else {
+            return
+        }
+        for completion in completions {
+            if let Some(item) = self._completion_items.iter_mut().find( | item | item.label == completion.label) {
+                *item = completion;
+            }
+            else {
+                self._completion_items.push(completion);
+            }
+        }
+        self.filter_completions(cx);
+    }
+    
+    pub fn hide_hover_info(&mut self, cx: &mut Cx) {
+        cx.stop_timer(&mut self._hover_timer);
+        if self._hover_info.is_some() {
+            self._hover_info = None;
+            self.view.redraw_view_area(cx);
+        }
+    }
+    
+    pub fn hide_completions(&mut self, cx: &mut Cx) {
+        if self._completion_anchor.is_some() {
+            self._completion_anchor = None;
+            self._completion_items.truncate(0);
+            self._completions.truncate(0);
+            self.view.redraw_view_area(cx);
+        }
+    }
+    
+    pub fn hide_popups(&mut self, cx: &mut Cx) {
+        self.hide_hover_info(cx);
+        self.hide_completions(cx);
+    }
+    
+    fn find_word_start(offset: usize, text_buffer: &TextBuffer) -> usize {
+        let mut start = offset;
+        while start > 0 {
+            let ch = text_buffer.get_char(start - 1);
+            if !ch.is_alphanumeric() && ch != '_' {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+    
+    fn open_completions(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        let head = self.cursors.set[self.cursors.last_cursor].head;
+        self._completion_anchor = Some(Self::find_word_start(head, text_buffer));
+        self._completion_items.truncate(0);
+        for provider in &self.completion_providers {
+            provider.provide_completions(text_buffer, head, &mut self._completion_items);
+        }
+        self.update_completions(cx, text_buffer);
+    }
+    
+    fn update_completions(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        let anchor = if let Some(anchor) = self._completion_anchor {anchor}else {return};
+        // the list stays open as long as we are typing the same word
+        let head = self.cursors.set[self.cursors.last_cursor].head;
+        if head < anchor || Self::find_word_start(head, text_buffer) != anchor {
+            self.hide_completions(cx);
+            return
+        }
+        let prefix: Vec<char> = (anchor..head).map( | offset | text_buffer.get_char(offset)).collect();
+        if prefix != self._completion_prefix || self._completions.len() == 0 {
+            self._completion_prefix = prefix;
+            self.filter_completions(cx);
+        }
+    }
+    
+    fn filter_completions(&mut self, cx: &mut Cx) {
+        // keep the selected item selected when it survives the filter
+        let selected = self._completions.get(self._completion_index).map( | item | item.label.clone());
+        self._completions = CodeCompletion::fuzzy_filter(&self._completion_prefix, &self._completion_items);
+        self._completion_index = selected.and_then( | label | self._completions.iter().position( | item | item.label == label)).unwrap_or(0);
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn accept_completion(&mut self, cx: &mut Cx, text_buffer: &mut TextBuffer) {
+        let completion = self._completions[self._completion_index].clone();
+        let prefix = self._completion_prefix.clone();
+        self.hide_completions(cx);
+        // replace the typed prefix in front of every cursor that has it
+        for cursor in &mut self.cursors.set {
+            let start = cursor.head.max(prefix.len()) - prefix.len();
+            let has_prefix = cursor.head >= prefix.len() && prefix.iter().enumerate().all( | (index, ch) | text_buffer.get_char(start + index) == *ch);
+            cursor.tail = if has_prefix {start}else {cursor.head};
+        }
+        self.cursors.replace_text(&completion.insert_text, text_buffer);
+        self.update_highlight(cx, text_buffer);
+        self.scroll_last_cursor_visible(cx, text_buffer, 0.);
+        self.view.redraw_view_area(cx);
+        self.reset_cursor_blinker(cx);
+    }
+    
+    fn offset_to_draw_pos(&self, offset: usize, text_buffer: &TextBuffer) -> Option<Vec2> {
+        // returns the position just below the character at offset
+        let pos = text_buffer.offset_to_text_pos(offset);
+        if pos.row >= self._line_geometry.len() {
+            return None
+        }
+        let geom = &self._line_geometry[pos.row];
+        let mono_size = Vec2 {x: self._monospace_base.x * geom.font_size, y: self._monospace_base.y * geom.font_size};
+        let (vis_col, vis_y) = if let Some(wrap_index) = self.find_wrap_index(pos) {
+            let wrap = &self._wrap_geometry[wrap_index];
+            (wrap.indent + pos.col - wrap.col, wrap.y)
+        }
+        else {
+            (pos.col, geom.walk.y)
+        };
+        Some(Vec2 {
+            x: (vis_col as f32) * mono_size.x + self.line_number_width,
+            y: vis_y + mono_size.y
+        })
+    }
+    
+    fn draw_popups(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        let mut lines: Vec<(String, Color)> = Vec::new();
+        let mut selected = None;
+        let pos = if self._completions.len() > 0 {
+            // keep the selected item inside the visible window
+            let first = if self._completion_index >= self.max_completions {self._completion_index + 1 - self.max_completions}else {0};
+            for (index, completion) in self._completions.iter().enumerate().skip(first).take(self.max_completions) {
+                if index == self._completion_index {
+                    selected = Some(index - first);
+                }
+                let line = if completion.detail.len() > 0 {
+                    format!("{}  {}", completion.label, completion.detail)
+                }
+                else {
+                    completion.label.clone()
+                };
+                lines.push((line, self.colors.popup_text));
+            }
+            // anchor the list below the last cursor
+            if let Some(last_cursor) = self._draw_cursors.last_cursor {
+                let rc = self._draw_cursors.cursors[last_cursor];
+                Some(Vec2 {x: rc.x, y: rc.y + rc.h})
+            }
+            else {
+                None
+            }
+        }
+        else if let Some((offset, text)) = &self._hover_info {
+            for (index, line) in text.lines().take(15).enumerate() {
+                let line: String = line.chars().take(100).collect();
+                lines.push((line, if index == 0 {self.colors.popup_text}else {self.colors.popup_detail}));
+            }
+            let origin = cx.get_turtle_origin();
+            self.offset_to_draw_pos(*offset, text_buffer).map( | pos | Vec2 {x: origin.x + pos.x, y: origin.y + pos.y})
+        }
+        else {
+            None
+        };
+        let pos = if let Some(pos) = pos {pos}else {return};
+        if lines.len() == 0 {
+            return
+        }
+        
+        let mono_size = Vec2 {x: self._monospace_base.x * self.open_font_size, y: self._monospace_base.y * self.open_font_size};
+        let max_len = lines.iter().map( | (line, _) | line.chars().count()).max().unwrap_or(0);
+        let rect = Rect {
+            x: pos.x,
+            y: pos.y + 2.,
+            w: (max_len as f32) * mono_size.x + 8.,
+            h: (lines.len() as f32) * mono_size.y + 4.
+        };
+        
+        // popups need their own drawcalls to end up on top of the text
+        cx.new_instance_draw_call(&self.popup_bg.shader, 0);
+        self.popup_bg.color = self.colors.popup_bg;
+        self.popup_bg.draw_quad_abs(cx, rect);
+        if let Some(selected) = selected {
+            self.popup_select.color = self.colors.popup_select;
+            self.popup_select.draw_quad_abs(cx, Rect {
+                x: rect.x,
+                y: rect.y + 2. + (selected as f32) * mono_size.y,
+                w: rect.w,
+                h: mono_size.y
+            });
+        }
+        
+        cx.new_instance_draw_call(&self.text.shader, 0);
+        let mut text_inst = self.text.begin_text(cx);
+        self.text.font_size = self.open_font_size;
+        for (index, (line, color)) in lines.iter().enumerate() {
+            self.text.color = *color;
+            let chunk: Vec<char> = line.chars().collect();
+            self.text.add_text(
+                cx,
+                rect.x + 4.,
+                rect.y + 2. + (index as f32) * mono_size.y,
+                0,
+                &mut text_inst,
+                &chunk,
+                | _, _, _, _ | {0.}
+            );
+        }
+        self.text.end_text(cx, &text_inst);
+    }
+    
+    fn find_wrap_index(&self, pos: TextPos) -> Option<usize> {
+        // the wrap geometry is sorted by row and col, find the last visual row starting at or before pos
+        if self._wrap_geometry.len() == 0 {
+            return None
+        }
+        let index = match self._wrap_geometry.binary_search_by( | wrap | {
+            wrap.row.cmp(&pos.row).then(wrap.col.cmp(&pos.col))
+        }) {
+            Ok(index) => index,
+            Err(index) => if index == 0 {return None}else {index - 1}
+        };
+        if self._wrap_geometry[index].row != pos.row {
+            return None
+        }
+        Some(index)
+    }
+    
+    fn move_cursors_wrapped(&mut self, delta: isize, only_head: bool, text_buffer: &TextBuffer) {
+        let wrap_geometry = &self._wrap_geometry;
+        if wrap_geometry.len() == 0 {
+            if delta < 0 {
+                self.cursors.move_up(1, only_head, text_buffer);
+            }
+            else {
+                self.cursors.move_down(1, only_head, text_buffer);
+            }
+            return
+        }
+        let total_char_count = text_buffer.calc_char_count();
+        let wrap_moved = std::mem::replace(&mut self._wrap_moved, Vec::new());
+        self.cursors.move_with(only_head, text_buffer, | cursor | {
+            let pos = text_buffer.offset_to_text_pos(cursor.head);
+            let index = match wrap_geometry.binary_search_by( | wrap | wrap.row.cmp(&pos.row).then(wrap.col.cmp(&pos.col))) {
+                Ok(index) => index,
+                Err(index) => index.max(1) - 1
+            };
+            let wrap = &wrap_geometry[index];
+            // the visual column we want to keep, after our own moves cursor.max holds it across short rows
+            // any other move leaves the buffer column there
+            let vis_col = if wrap_moved.contains(&(cursor.head, cursor.max)) {
+                cursor.max
+            }
+            else {
+                wrap.indent + pos.col.max(wrap.col) - wrap.col
+            };
+            // above the first visual row the cursor stays where it is
+            let target = (index as isize + delta).max(0) as usize;
+            if target >= wrap_geometry.len() {
+                return (total_char_count, vis_col)
+            }
+            let next = &wrap_geometry[target];
+            let mut new_col = next.col + vis_col.max(next.indent) - next.indent;
+            // dont let the cursor jump onto the start of the next visual row of the same line
+            if target + 1 < wrap_geometry.len() && wrap_geometry[target + 1].row == next.row {
+                new_col = new_col.min(wrap_geometry[target + 1].col.max(next.col + 1) - 1);
+            }
+            (text_buffer.text_pos_to_offset(TextPos {row: next.row, col: new_col}), vis_col)
+        });
+        self._wrap_moved = self.cursors.set.iter().map( | cursor | (cursor.head, cursor.max)).collect();
+    }
+    
+    fn compute_fold_ranges(&mut self, text_buffer: &TextBuffer) {
+        // every paren pair spanning multiple lines can be folded, we keep the outermost one per line
+        self._fold_ranges.truncate(0);
+        let mut open_stack = Vec::new();
+        let mut row = 0;
+        for (index, token_chunk) in text_buffer.token_chunks.iter().enumerate() {
+            match token_chunk.token_type {
+                TokenType::Newline => {
+                    row += 1;
+                },
+                TokenType::ParenOpen => if token_chunk.pair_token > index {
+                    open_stack.push((row, token_chunk.offset));
+                },
+                TokenType::ParenClose => if token_chunk.pair_token < index {
+                    if let Some((open_row, start)) = open_stack.pop() {
+                        if open_row < row {
+                            self._fold_ranges.push(FoldRange {
+                                row: open_row,
+                                start: start,
+                                end: token_chunk.offset,
+                                folded: false
+                            });
+                        }
+                    }
+                },
+                _ => ()
+            }
+        }
+        self._fold_ranges.sort_by( | a, b | a.row.cmp(&b.row).then(a.start.cmp(&b.start)));
+        self._fold_ranges.dedup_by_key( | range | range.row);
+        for range in &mut self._fold_ranges {
+            if let Some(index) = text_buffer.find_fold(range.start) {
+                range.folded = text_buffer.folds[index].end == range.end;
+            }
+        }
+    }
+    
+    fn toggle_fold_range(&mut self, cx: &mut Cx, index: usize, text_buffer: &mut TextBuffer) {
+        // forget folds that no longer match the text
+        let fold_ranges = &self._fold_ranges;
+        text_buffer.folds.retain( | fold | fold_ranges.iter().any( | range | range.start == fold.start && range.end == fold.end));
+        let range = &mut self._fold_ranges[index];
+        range.folded = !range.folded;
+        text_buffer.toggle_fold(range.start, range.end);
+        self.skip_folded_ranges(false, true, text_buffer);
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn fold_at_last_cursor(&mut self, cx: &mut Cx, fold: bool, text_buffer: &mut TextBuffer) -> bool {
+        // find the innermost range around the cursor we can fold or unfold
+        let offset = self.cursors.set[self.cursors.last_cursor].head;
+        let mut found = None;
+        for (index, range) in self._fold_ranges.iter().enumerate() {
+            if range.start <= offset && offset <= range.end + 1 && range.folded != fold {
+                found = Some(index);
+            }
+        }
+        if let Some(index) = found {
+            self.toggle_fold_range(cx, index, text_buffer);
+            return true
+        }
+        false
+    }
+    
+    fn skip_folded_ranges(&mut self, forward: bool, only_head: bool, text_buffer: &TextBuffer) {
+        // cursors can't be inside a folded range, move them onto its open or close paren
+        let folds: Vec<TextFold> = text_buffer.folds.iter().filter( | fold | {
+            self._fold_ranges.iter().any( | range | range.folded && range.start == fold.start && range.end == fold.end)
+        }).cloned().collect();
+        let is_hidden = | offset: usize | folds.iter().find( | fold | offset > fold.start + 1 && offset < fold.end);
+        if !self.cursors.set.iter().any( | cursor | is_hidden(cursor.head).is_some()) {
+            return
+        }
+        self.cursors.move_with(only_head, text_buffer, | cursor | {
+            if let Some(fold) = is_hidden(cursor.head) {
+                (if forward {fold.end}else {fold.start + 1}, cursor.max)
+            }
+            else {
+                (cursor.head, cursor.max)
+            }
+        });
+    }
+    
     fn start_code_folding(&mut self, cx: &mut Cx, text_buffer: &TextBuffer, halfway: bool) {
         // start code folding anim
         let speed = 0.98;
@@ -1795,13 +3054,18 @@ impl CodeEditor {
         last_scroll_none
     }
     
+    fn is_line_unfolded(&self, row: usize) -> bool {
+        let geom = &self._line_geometry[row];
+        geom.visual_rows > 0 && (!geom.was_folded || !self._anim_folding.state.is_folded())
+    }
+    
     fn compute_next_unfolded_line_up(&self, text_buffer: &TextBuffer) -> usize {
         let pos = self.cursors.get_last_cursor_text_pos(text_buffer);
         let mut delta = 1;
         if pos.row > 0 && pos.row < self._line_geometry.len() {
             let mut scan = pos.row - 1;
             while scan >0 {
-                if !self._line_geometry[scan].was_folded {
+                if self.is_line_unfolded(scan) {
                     delta = pos.row - scan;
                     break;
                 }
@@ -1816,7 +3080,7 @@ impl CodeEditor {
         let mut delta = 1;
         let mut scan = pos.row + 1;
         while scan < self._line_geometry.len() {
-            if !self._line_geometry[scan].was_folded {
+            if self.is_line_unfolded(scan) {
                 delta = scan - pos.row;
                 break;
             }
@@ -1895,9 +3159,26 @@ pub struct LineGeom {
     walk: Vec2,
     was_folded: bool,
     font_size: f32,
+    visual_rows: usize,
     indent_id: f32
 }
 
+#[derive(Clone, Default)]
+pub struct WrapGeom {
+    row: usize,
+    col: usize,
+    indent: usize,
+    y: f32
+}
+
+#[derive(Clone, Default)]
+pub struct FoldRange {
+    row: usize,
+    start: usize,
+    end: usize,
+    folded: bool
+}
+
 #[derive(Clone, Default)]
 pub struct SelectScroll {
     // pub margin:Margin,
@@ -0,0 +1,490 @@
+use render::*;
+use widget::*;
+use crate::textbuffer::*;
+use crate::codeeditor::*;
+use serde::*;
+
+#[derive(Clone)]
+pub struct DiffViewer {
+    pub view_left: View<ScrollBar>,
+    pub view_right: View<ScrollBar>,
+    pub bg: Quad,
+    pub line_bg: Quad,
+    pub change_highlight: Quad,
+    pub text: Text,
+    pub line_number_width: f32,
+    pub top_padding: f32,
+    pub colors: DiffViewerColors,
+    pub token_colors: CodeEditorColors,
+    // the left side in the disk and head modes, the right side is the buffer
+    pub _base: TextBuffer,
+    pub _base_stale: bool,
+    pub _left_bg_area: Area,
+    pub _right_bg_area: Area,
+    pub _left_mutation_id: u64,
+    pub _right_mutation_id: u64,
+    pub _rows: Vec<DiffRow>,
+    pub _hunks: Vec<usize>,
+    pub _current_hunk: Option<usize>,
+    pub _max_cols: usize,
+    pub _monospace_size: Vec2,
+}
+
+#[derive(Clone)]
+pub struct DiffViewerColors {
+    pub bg: Color,
+    pub text: Color,
+    pub line_number: Color,
+    pub added: Color,
+    pub removed: Color,
+    pub added_change: Color,
+    pub removed_change: Color,
+    pub filler: Color,
+    pub current_hunk: Color,
+}
+
+// one visual row, shown on both sides. a side without a line gets a filler
+#[derive(Clone, Copy)]
+pub struct DiffRow {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub change_type: Option<TextLineChangeType>,
+    // the changed column ranges within a modified line
+    pub left_change: Option<(usize, usize)>,
+    pub right_change: Option<(usize, usize)>,
+}
+
+// what the right side, a buffer, is compared with
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DiffViewerMode {
+    // another buffer
+    Files,
+    // the file as it is on disk
+    Disk,
+    // the file at git HEAD
+    Head
+}
+
+impl Default for DiffViewerMode {
+    fn default() -> Self {DiffViewerMode::Files}
+}
+
+#[derive(Clone, PartialEq)]
+pub enum DiffViewerEvent {
+    None,
+    HunkSelect {left_row: usize, right_row: usize}
+}
+
+impl Style for DiffViewer {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view_left: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            view_right: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            bg: Quad {
+                do_scroll: false,
+                ..Style::style(cx)
+            },
+            line_bg: Quad {
+                shader: cx.add_shader(CodeEditor::def_cursor_row_shader(), "Editor.cursor_row"),
+                ..Style::style(cx)
+            },
+            change_highlight: Quad {
+                shader: cx.add_shader(CodeEditor::def_select_highlight_shader(), "Editor.select_highlight"),
+                ..Style::style(cx)
+            },
+            text: Text {
+                font: cx.load_font_style("mono_font"),
+                font_size: 11.0,
+                brightness: 1.0,
+                line_spacing: 1.4,
+                do_dpi_dilate: true,
+                wrapping: Wrapping::Line,
+                ..Style::style(cx)
+            },
+            colors: DiffViewerColors {
+                bg: color256(30, 30, 30),
+                text: color256(212, 212, 212),
+                line_number: color256(136, 136, 136),
+                added: color256a(88, 124, 12, 60),
+                removed: color256a(148, 21, 27, 60),
+                added_change: color256a(88, 124, 12, 140),
+                removed_change: color256a(148, 21, 27, 140),
+                filler: color256(37, 37, 37),
+                current_hunk: color256(12, 125, 157),
+            },
+            token_colors: CodeEditorColors::default(),
+            _base: TextBuffer::default(),
+            _base_stale: true,
+            line_number_width: 45.,
+            top_padding: 4.,
+            _left_bg_area: Area::Empty,
+            _right_bg_area: Area::Empty,
+            _left_mutation_id: 0,
+            _right_mutation_id: 0,
+            _rows: Vec::new(),
+            _hunks: Vec::new(),
+            _current_hunk: None,
+            _max_cols: 0,
+            _monospace_size: Vec2::zero(),
+        }
+    }
+}
+
+impl DiffViewer {
+    pub fn handle_diff_viewer(&mut self, cx: &mut Cx, event: &mut Event, left: &TextBuffer, right: &TextBuffer) -> DiffViewerEvent {
+        // keep both sides scrolled to the same rows
+        // only the visible rows are drawn, so scrolling redraws
+        if self.view_left.handle_scroll_bars(cx, event) {
+            let scroll_pos = self.view_left.get_scroll_pos(cx);
+            self.view_right.set_scroll_pos(cx, scroll_pos);
+            self.redraw_diff_viewer(cx);
+        }
+        if self.view_right.handle_scroll_bars(cx, event) {
+            let scroll_pos = self.view_right.get_scroll_pos(cx);
+            self.view_left.set_scroll_pos(cx, scroll_pos);
+            self.redraw_diff_viewer(cx);
+        }
+        match event {
+            Event::Signal(se) => if left.signal.is_signal(se) || right.signal.is_signal(se) {
+                self.redraw_diff_viewer(cx);
+            },
+            _ => ()
+        }
+        for bg_area in &[self._left_bg_area, self._right_bg_area] {
+            match event.hits(cx, *bg_area, HitOpt {no_scrolling: true, ..Default::default()}) {
+                Event::FingerDown(_fe) => {
+                    cx.set_key_focus(self._left_bg_area);
+                },
+                Event::KeyDown(ke) => {
+                    let next = match ke.key_code {
+                        KeyCode::F7 => !ke.modifiers.shift,
+                        KeyCode::ArrowDown if ke.modifiers.alt => true,
+                        KeyCode::ArrowUp if ke.modifiers.alt => false,
+                        _ => return DiffViewerEvent::None
+                    };
+                    return self.select_hunk(cx, next)
+                },
+                _ => ()
+            }
+        }
+        DiffViewerEvent::None
+    }
+    
+    pub fn handle_diff_viewer_base(&mut self, cx: &mut Cx, event: &mut Event, text_buffer: &TextBuffer) -> DiffViewerEvent {
+        match event {
+            Event::FileRead(fr) => if let Some(utf8_data) = self._base.load_read_req.as_utf8(fr) {
+                // a file that is gone compares as empty
+                self._base.lines = TextBuffer::split_string_to_lines(&utf8_data.unwrap_or("").to_string());
+                self._base.mutation_id += 1;
+                self.redraw_diff_viewer(cx);
+            },
+            // saving, a new HEAD or another program can change the base
+            Event::Signal(se) => if text_buffer.signal.is_signal(se) && (se.value == SIGNAL_TEXTBUFFER_SAVED || se.value == SIGNAL_TEXTBUFFER_LINE_CHANGES_UPDATE) {
+                self._base_stale = true;
+            },
+            Event::AppFocus => {
+                self._base_stale = true;
+                self.redraw_diff_viewer(cx);
+            },
+            _ => ()
+        }
+        let base = std::mem::replace(&mut self._base, TextBuffer::default());
+        let diff_event = self.handle_diff_viewer(cx, event, &base, text_buffer);
+        self._base = base;
+        diff_event
+    }
+    
+    // reads the disk or head version again when it went stale, file_path is the full path of the buffer
+    pub fn update_base(&mut self, cx: &mut Cx, mode: DiffViewerMode, file_path: &str, text_buffer: &TextBuffer) {
+        if !self._base_stale {
+            return
+        }
+        match mode {
+            DiffViewerMode::Files => (),
+            DiffViewerMode::Disk => {
+                self._base.load_read_req = cx.read_file(file_path);
+            },
+            DiffViewerMode::Head => {
+                if text_buffer.line_changes.base_read_req.is_loading() {
+                    return
+                }
+                self._base.lines = text_buffer.line_changes.base_lines.clone().unwrap_or(Vec::new());
+                self._base.mutation_id += 1;
+            }
+        }
+        self._base_stale = false;
+    }
+    
+    pub fn draw_diff_viewer_base(&mut self, cx: &mut Cx, text_buffer: &TextBuffer) {
+        let base = std::mem::replace(&mut self._base, TextBuffer::default());
+        self.draw_diff_viewer(cx, &base, text_buffer);
+        self._base = base;
+    }
+    
+    pub fn redraw_diff_viewer(&mut self, cx: &mut Cx) {
+        self.view_left.redraw_view_area(cx);
+        self.view_right.redraw_view_area(cx);
+    }
+    
+    pub fn next_hunk(&mut self, cx: &mut Cx) -> DiffViewerEvent {
+        self.select_hunk(cx, true)
+    }
+    
+    pub fn prev_hunk(&mut self, cx: &mut Cx) -> DiffViewerEvent {
+        self.select_hunk(cx, false)
+    }
+    
+    fn select_hunk(&mut self, cx: &mut Cx, next: bool) -> DiffViewerEvent {
+        if self._hunks.len() == 0 {
+            return DiffViewerEvent::None
+        }
+        let hunk = match self._current_hunk {
+            None => if next {0}else {self._hunks.len() - 1},
+            Some(hunk) => if next {
+                (hunk + 1) % self._hunks.len()
+            }
+            else {
+                (hunk + self._hunks.len() - 1) % self._hunks.len()
+            }
+        };
+        self._current_hunk = Some(hunk);
+        
+        // put the hunk a third down the view
+        let row = self._hunks[hunk];
+        let view_height = self.view_left.get_rect(cx).h;
+        let scroll_pos = self.view_left.get_scroll_pos(cx);
+        let scroll_y = (self.top_padding + (row as f32) * self._monospace_size.y - view_height / 3.).max(0.);
+        self.view_left.set_scroll_pos(cx, Vec2 {x: scroll_pos.x, y: scroll_y});
+        self.view_right.set_scroll_pos(cx, Vec2 {x: scroll_pos.x, y: scroll_y});
+        self.redraw_diff_viewer(cx);
+        
+        // report the first line of the hunk on both sides
+        let left_row = self._rows[row..].iter().find_map( | row | row.left).unwrap_or(0);
+        let right_row = self._rows[row..].iter().find_map( | row | row.right).unwrap_or(0);
+        DiffViewerEvent::HunkSelect {left_row: left_row, right_row: right_row}
+    }
+    
+    fn update_rows(&mut self, left: &TextBuffer, right: &TextBuffer) {
+        self._left_mutation_id = left.mutation_id;
+        self._right_mutation_id = right.mutation_id;
+        self._rows.truncate(0);
+        self._hunks.truncate(0);
+        self._current_hunk = None;
+        
+        let mut changes = Vec::new();
+        TextLineChange::diff_lines(&left.lines, &right.lines, &mut changes);
+        
+        // pair up the lines, hunks pair line by line and pad the shorter side
+        let mut left_row = 0;
+        let mut right_row = 0;
+        for change in &changes {
+            while left_row < change.base_start {
+                self._rows.push(DiffRow {left: Some(left_row), right: Some(right_row), change_type: None, left_change: None, right_change: None});
+                left_row += 1;
+                right_row += 1;
+            }
+            self._hunks.push(self._rows.len());
+            let left_len = change.base_end - change.base_start;
+            let right_len = change.end_row - change.start_row;
+            for i in 0..left_len.max(right_len) {
+                let left_line = if i < left_len {Some(change.base_start + i)}else {None};
+                let right_line = if i < right_len {Some(change.start_row + i)}else {None};
+                let (change_type, left_change, right_change) = match (left_line, right_line) {
+                    (Some(l), Some(r)) => {
+                        let (left_change, right_change) = Self::find_intra_line_change(&left.lines[l], &right.lines[r]);
+                        (TextLineChangeType::Modified, left_change, right_change)
+                    },
+                    (Some(_), None) => (TextLineChangeType::Deleted, None, None),
+                    _ => (TextLineChangeType::Added, None, None)
+                };
+                self._rows.push(DiffRow {
+                    left: left_line,
+                    right: right_line,
+                    change_type: Some(change_type),
+                    left_change: left_change,
+                    right_change: right_change
+                });
+            }
+            left_row = change.base_end;
+            right_row = change.end_row;
+        }
+        while left_row < left.lines.len() && right_row < right.lines.len() {
+            self._rows.push(DiffRow {left: Some(left_row), right: Some(right_row), change_type: None, left_change: None, right_change: None});
+            left_row += 1;
+            right_row += 1;
+        }
+        self._max_cols = left.lines.iter().chain(right.lines.iter()).map( | line | line.len()).max().unwrap_or(0);
+    }
+    
+    fn find_intra_line_change(left: &Vec<char>, right: &Vec<char>) -> (Option<(usize, usize)>, Option<(usize, usize)>) {
+        // strip the common start and end, whats left in the middle changed
+        let min_len = left.len().min(right.len());
+        let mut prefix = 0;
+        while prefix < min_len && left[prefix] == right[prefix] {
+            prefix += 1;
+        }
+        let mut suffix = 0;
+        while suffix < min_len - prefix && left[left.len() - 1 - suffix] == right[right.len() - 1 - suffix] {
+            suffix += 1;
+        }
+        if prefix == 0 && suffix == 0 { // the whole line changed, the line background says enough
+            return (None, None)
+        }
+        let left_end = left.len() - suffix;
+        let right_end = right.len() - suffix;
+        (
+            if left_end > prefix {Some((prefix, left_end))}else {None},
+            if right_end > prefix {Some((prefix, right_end))}else {None}
+        )
+    }
+    
+    pub fn draw_diff_viewer(&mut self, cx: &mut Cx, left: &TextBuffer, right: &TextBuffer) {
+        if !left.load_read_req.is_loading() && !right.load_read_req.is_loading() {
+            if self._left_mutation_id != left.mutation_id || self._right_mutation_id != right.mutation_id {
+                self.update_rows(left, right);
+            }
+        }
+        let monospace_base = self.text.get_monospace_base(cx);
+        self._monospace_size = Vec2 {x: monospace_base.x * self.text.font_size, y: monospace_base.y * self.text.font_size};
+        
+        if let Ok(()) = self.view_left.begin_view(cx, Layout {
+            width: Bounds::FillScale(0.5),
+            height: Bounds::Fill,
+            ..Default::default()
+        }) {
+            self._left_bg_area = self.draw_side(cx, true, left);
+            self.view_left.end_view(cx);
+        }
+        if let Ok(()) = self.view_right.begin_view(cx, Layout {
+            width: Bounds::Fill,
+            height: Bounds::Fill,
+            ..Default::default()
+        }) {
+            self._right_bg_area = self.draw_side(cx, false, right);
+            self.view_right.end_view(cx);
+        }
+    }
+    
+    fn draw_side(&mut self, cx: &mut Cx, is_left: bool, text_buffer: &TextBuffer) -> Area {
+        self.bg.color = self.colors.bg;
+        let bg_area = self.bg.draw_quad(cx, Rect {x: 0., y: 0., w: cx.get_width_total(), h: cx.get_height_total()}).into_area();
+        if text_buffer.load_read_req.is_loading() {
+            return bg_area
+        }
+        
+        // layering, line backgrounds under the change highlights under the text
+        cx.new_instance_draw_call(&self.line_bg.shader, 0);
+        cx.new_instance_draw_call(&self.change_highlight.shader, 0);
+        let mut text_inst = self.text.begin_text(cx);
+        
+        let origin = cx.get_turtle_origin();
+        let mono = self._monospace_size;
+        let line_width = cx.get_width_total().max(self.line_number_width + (self._max_cols as f32) * mono.x);
+        let current_hunk_row = if let Some(hunk) = self._current_hunk {Some(self._hunks[hunk])}else {None};
+        let mut line_number_chunk = Vec::new();
+        
+        // only the rows in the scroll view
+        let scroll_y = if is_left {self.view_left.get_scroll_pos(cx).y}else {self.view_right.get_scroll_pos(cx).y};
+        let first_row = (((scroll_y - self.top_padding) / mono.y).floor().max(0.) as usize).min(self._rows.len());
+        let last_row = (first_row + (cx.get_height_total() / mono.y).ceil() as usize + 1).min(self._rows.len());
+        
+        // the tokens are only usable if the tokenizer ran on this version of the buffer
+        let has_tokens = text_buffer.token_chunks_id == text_buffer.mutation_id && text_buffer.token_chunks.len() > 0;
+        let mut next_line = 0;
+        let mut next_offset = 0;
+        
+        for index in first_row..last_row {
+            let row = &self._rows[index];
+            let y = self.top_padding + (index as f32) * mono.y;
+            let line = if is_left {row.left}else {row.right};
+            
+            if row.change_type.is_some() {
+                self.line_bg.color = if line.is_none() {
+                    self.colors.filler
+                }
+                else if is_left {
+                    self.colors.removed
+                }
+                else {
+                    self.colors.added
+                };
+                self.line_bg.draw_quad(cx, Rect {x: 0., y: y, w: line_width, h: mono.y});
+                if current_hunk_row == Some(index) {
+                    self.line_bg.color = self.colors.current_hunk;
+                    self.line_bg.draw_quad(cx, Rect {x: 0., y: y, w: 3., h: mono.y});
+                }
+                // highlight what changed within the line
+                if let Some((start, end)) = if is_left {row.left_change}else {row.right_change} {
+                    self.change_highlight.color = if is_left {self.colors.removed_change}else {self.colors.added_change};
+                    self.change_highlight.draw_quad(cx, Rect {
+                        x: self.line_number_width + (start as f32) * mono.x,
+                        y: y,
+                        w: ((end - start) as f32) * mono.x,
+                        h: mono.y
+                    });
+                }
+            }
+            
+            if let Some(line) = line {
+                line_number_chunk.truncate(0);
+                line_number_chunk.extend(format!("{:>5}", line + 1).chars());
+                self.text.color = self.colors.line_number;
+                let chunk_width = mono.x * 5.0;
+                self.text.add_text(cx, origin.x + (self.line_number_width - chunk_width - 10.), origin.y + y, 0, &mut text_inst, &line_number_chunk, | _, _, _, _ | {0.});
+                let chars = &text_buffer.lines[line];
+                if !has_tokens {
+                    self.text.color = self.colors.text;
+                    self.text.add_text(cx, origin.x + self.line_number_width, origin.y + y, 0, &mut text_inst, chars, | _, _, _, _ | {0.});
+                    continue;
+                }
+                // the lines of a side come in order, so walk the offset along
+                while next_line < line {
+                    next_offset += text_buffer.lines[next_line].len() + 1;
+                    next_line += 1;
+                }
+                let line_start = next_offset;
+                let line_end = line_start + chars.len();
+                let token_chunks = &text_buffer.token_chunks;
+                let mut token = match token_chunks.binary_search_by_key(&line_start, | chunk | chunk.offset) {
+                    Ok(token) => token,
+                    Err(token) => token.max(1) - 1
+                };
+                while token < token_chunks.len() && token_chunks[token].offset < line_end {
+                    let chunk = &token_chunks[token];
+                    let start = chunk.offset.max(line_start);
+                    let end = (chunk.offset + chunk.len).min(line_end);
+                    if end > start && chunk.token_type != TokenType::Whitespace {
+                        self.text.color = self.token_colors.token_color(chunk.token_type, 0);
+                        let x = origin.x + self.line_number_width + ((start - line_start) as f32) * mono.x;
+                        self.text.add_text(cx, x, origin.y + y, 0, &mut text_inst, &text_buffer.flat_text[start..end], | _, _, _, _ | {0.});
+                    }
+                    token += 1;
+                }
+            }
+        }
+        self.text.end_text(cx, &text_inst);
+        
+        // tell the scrollview how big we are
+        cx.walk_turtle(
+            Bounds::Fix(line_width),
+            Bounds::Fix(self.top_padding + (self._rows.len() as f32 + 1.) * mono.y),
+            Margin::zero(),
+            None
+        );
+        bg_area
+    }
+}
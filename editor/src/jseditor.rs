@@ -53,6 +53,8 @@ impl JSEditor {
             }
         }
         
+        text_buffer.update_line_changes();
+        
         if let Err(_) = self.code_editor.begin_code_editor(cx, text_buffer) {
             return
         }
@@ -2,6 +2,8 @@ mod textbuffer;
 pub use crate::textbuffer::*;
 mod codeeditor;
 pub use crate::codeeditor::*;
+mod codecompletion;
+pub use crate::codecompletion::*;
 mod textcursor;
 pub use crate::textcursor::*;
 mod rusteditor;
@@ -12,5 +14,11 @@ pub mod keyboard;
 pub use crate::keyboard::*;
 mod jseditor;
 pub use crate::jseditor::*;
+mod diffviewer;
+pub use crate::diffviewer::*;
+mod symbolindex;
+pub use crate::symbolindex::*;
+mod outlineview;
+pub use crate::outlineview::*;
 //mod texteditor;
 //pub use crate::texteditor::*;
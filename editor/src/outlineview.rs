@@ -0,0 +1,146 @@
+use render::*;
+use widget::*;
+use crate::symbolindex::*;
+
+// lists the symbols of one file, clicking one jumps to it
+#[derive(Clone)]
+pub struct OutlineView {
+    pub view: View<ScrollBar>,
+    pub item_bg: Quad,
+    pub text: Text,
+    pub row_height: f32,
+    pub indent_width: f32,
+    pub colors: OutlineViewColors,
+    pub _row_areas: Vec<(Area, usize)>,
+    pub _hover_row: Option<usize>
+}
+
+#[derive(Clone)]
+pub struct OutlineViewColors {
+    pub bg_even: Color,
+    pub bg_odd: Color,
+    pub bg_hover: Color,
+    pub keyword: Color,
+    pub name: Color
+}
+
+#[derive(Clone, PartialEq)]
+pub enum OutlineViewEvent {
+    None,
+    SelectSymbol {offset: usize}
+}
+
+impl Style for OutlineView {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            item_bg: Quad::style(cx),
+            text: Text {
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            row_height: 20.,
+            indent_width: 12.,
+            colors: OutlineViewColors {
+                bg_even: cx.color("bg_selected"),
+                bg_odd: cx.color("bg_odd"),
+                bg_hover: cx.color("bg_selected_over"),
+                keyword: color256(91, 155, 211),
+                name: color256(212, 212, 212)
+            },
+            _row_areas: Vec::new(),
+            _hover_row: None
+        }
+    }
+}
+
+impl OutlineView {
+    pub fn handle_outline_view(&mut self, cx: &mut Cx, event: &mut Event, symbols: &[CodeSymbol]) -> OutlineViewEvent {
+        self.view.handle_scroll_bars(cx, event);
+        
+        let mut hover_row = None;
+        let mut hovered = false;
+        for (area, row) in &self._row_areas {
+            match event.hits(cx, *area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    if let Some(symbol) = symbols.get(*row) {
+                        return OutlineViewEvent::SelectSymbol {offset: symbol.offset}
+                    }
+                },
+                Event::FingerHover(fe) => {
+                    hovered = true;
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    if let HoverState::Out = fe.hover_state {}
+                    else {
+                        hover_row = Some(*row);
+                    }
+                },
+                _ => ()
+            }
+        }
+        if hovered && hover_row != self._hover_row {
+            self._hover_row = hover_row;
+            self.view.redraw_view_area(cx);
+        }
+        OutlineViewEvent::None
+    }
+    
+    pub fn redraw_outline_view(&mut self, cx: &mut Cx) {
+        self.view.redraw_view_area(cx);
+    }
+    
+    pub fn draw_outline_view(&mut self, cx: &mut Cx, symbols: &[CodeSymbol]) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        self._row_areas.truncate(0);
+        for (row, symbol) in symbols.iter().enumerate() {
+            self.item_bg.color = if self._hover_row == Some(row) {
+                self.colors.bg_hover
+            }
+            else if row & 1 == 0 {
+                self.colors.bg_even
+            }
+            else {
+                self.colors.bg_odd
+            };
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Fix(self.row_height),
+                padding: Padding {l: 4. + (symbol.depth as f32) * self.indent_width, t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.text.color = self.colors.keyword;
+            self.text.draw_text(cx, &format!("{} ", symbol.kind.keyword()));
+            self.text.color = self.colors.name;
+            // impl labels carry the trait as well
+            let label = if symbol.kind == CodeSymbolKind::Impl {&symbol.label[5..]}else {&symbol.name};
+            self.text.draw_text(cx, label);
+            let area = self.item_bg.end_quad(cx, &bg_inst);
+            self._row_areas.push((area, row));
+            cx.turtle_new_line();
+        }
+        
+        // fill up the rest of the view
+        let view_total = cx.get_turtle_bounds();
+        let rect_now = cx.get_turtle_rect();
+        let mut y = view_total.y;
+        let mut row = symbols.len();
+        while y < rect_now.h {
+            self.item_bg.color = if row & 1 == 0 {self.colors.bg_even}else {self.colors.bg_odd};
+            self.item_bg.draw_quad_walk(cx, Bounds::Fill, Bounds::Fix((rect_now.h - y).min(self.row_height)), Margin::zero());
+            cx.turtle_new_line();
+            y += self.row_height;
+            row += 1;
+        }
+        
+        self.view.end_view(cx);
+    }
+}
@@ -0,0 +1,250 @@
+use render::*;
+use crate::textbuffer::*;
+use crate::rusteditor::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CodeSymbolKind {
+    Fn,
+    Struct,
+    Enum,
+    Trait,
+    Impl
+}
+
+impl CodeSymbolKind {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            CodeSymbolKind::Fn => "fn",
+            CodeSymbolKind::Struct => "struct",
+            CodeSymbolKind::Enum => "enum",
+            CodeSymbolKind::Trait => "trait",
+            CodeSymbolKind::Impl => "impl",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CodeSymbol {
+    pub name: String,
+    pub label: String,
+    pub kind: CodeSymbolKind,
+    pub offset: usize,
+    pub depth: usize
+}
+
+#[derive(Clone, Default)]
+pub struct SymbolFile {
+    pub mutation_id: u64,
+    pub symbols: Vec<CodeSymbol>
+}
+
+// a name based index of the declarations in the project, built from the tokenizer output
+#[derive(Clone, Default)]
+pub struct SymbolIndex {
+    pub files: HashMap<String, SymbolFile>,
+    pub _read_reqs: Vec<(String, FileReadRequest)>
+}
+
+impl SymbolIndex {
+    pub fn index_project_file(&mut self, cx: &mut Cx, root_path: &str, path: &str) {
+        if !path.ends_with(".rs") || self.files.contains_key(path) || self._read_reqs.iter().any( | (p, _) | p == path) {
+            return
+        }
+        self._read_reqs.push((path.to_string(), cx.read_file(&format!("{}{}", root_path, path))));
+    }
+    
+    pub fn handle_file_read(&mut self, fr: &FileReadEvent) -> bool {
+        for index in 0..self._read_reqs.len() {
+            if let Some(utf8_data) = self._read_reqs[index].1.as_utf8(fr) {
+                if let Ok(utf8_data) = utf8_data {
+                    let mut text_buffer = TextBuffer {
+                        mutation_id: 1,
+                        lines: TextBuffer::split_string_to_lines(&utf8_data.to_string()),
+                        ..Default::default()
+                    };
+                    RustTokenizer::update_token_chunks(&mut text_buffer);
+                    // an open textbuffer may have beaten us to it
+                    let path = self._read_reqs[index].0.clone();
+                    self.files.entry(path).or_insert_with( || SymbolFile {
+                        mutation_id: 0,
+                        symbols: Self::scan_symbols(&text_buffer.flat_text, &text_buffer.token_chunks)
+                    });
+                }
+                self._read_reqs.remove(index);
+                return true
+            }
+        }
+        false
+    }
+    
+    // rescans a textbuffer once its tokens are up to date, returns true if the symbols changed
+    pub fn update_text_buffer(&mut self, path: &str, text_buffer: &TextBuffer) -> bool {
+        if text_buffer.token_chunks_id != text_buffer.mutation_id || text_buffer.token_chunks.len() == 0 {
+            return false
+        }
+        if let Some(file) = self.files.get(path) {
+            if file.mutation_id == text_buffer.mutation_id {
+                return false
+            }
+        }
+        self.files.insert(path.to_string(), SymbolFile {
+            mutation_id: text_buffer.mutation_id,
+            symbols: Self::scan_symbols(&text_buffer.flat_text, &text_buffer.token_chunks)
+        });
+        true
+    }
+    
+    pub fn find_definition(&self, name: &str, prefer_path: &str) -> Option<(String, usize)> {
+        let mut found: Option<(&String, usize)> = None;
+        for (path, file) in &self.files {
+            for symbol in &file.symbols {
+                if symbol.kind == CodeSymbolKind::Impl || symbol.name != name {
+                    continue;
+                }
+                if path == prefer_path {
+                    return Some((path.clone(), symbol.offset))
+                }
+                // keep the result stable across hashmap orderings
+                if found.is_none() || path < found.unwrap().0 {
+                    found = Some((path, symbol.offset));
+                }
+            }
+        }
+        found.map( | (path, offset) | (path.clone(), offset))
+    }
+    
+    pub fn identifier_at(text_buffer: &TextBuffer, offset: usize) -> Option<String> {
+        let token_chunks = &text_buffer.token_chunks;
+        let index = match token_chunks.binary_search_by_key(&offset, | chunk | chunk.offset) {
+            Ok(index) => index,
+            Err(index) => if index == 0 {return None}else {index - 1}
+        };
+        // clicking just behind a word still counts
+        for index in [index, index.max(1) - 1].iter() {
+            let chunk = &token_chunks[*index];
+            match chunk.token_type {
+                TokenType::Identifier | TokenType::Call | TokenType::TypeName => (),
+                _ => continue
+            }
+            if offset >= chunk.offset && offset <= chunk.offset + chunk.len {
+                return Some(text_buffer.flat_text[chunk.offset..(chunk.offset + chunk.len)].iter().collect())
+            }
+        }
+        None
+    }
+    
+    fn chunk_string(flat_text: &[char], chunk: &TokenChunk) -> String {
+        flat_text[chunk.offset..(chunk.offset + chunk.len)].iter().collect()
+    }
+    
+    pub fn scan_symbols(flat_text: &[char], token_chunks: &[TokenChunk]) -> Vec<CodeSymbol> {
+        let mut symbols = Vec::new();
+        let mut depth = 0;
+        for (index, chunk) in token_chunks.iter().enumerate() {
+            match chunk.token_type {
+                TokenType::ParenOpen => if flat_text[chunk.offset] == '{' {
+                    depth += 1;
+                },
+                TokenType::ParenClose => if flat_text[chunk.offset] == '}' && depth > 0 {
+                    depth -= 1;
+                },
+                TokenType::Fn | TokenType::TypeDef => {
+                    let kind = match Self::chunk_string(flat_text, chunk).as_ref() {
+                        "fn" => CodeSymbolKind::Fn,
+                        "struct" => CodeSymbolKind::Struct,
+                        "enum" => CodeSymbolKind::Enum,
+                        "trait" => CodeSymbolKind::Trait,
+                        "impl" => CodeSymbolKind::Impl,
+                        _ => continue
+                    };
+                    if kind == CodeSymbolKind::Impl {
+                        if let Some(symbol) = Self::scan_impl(flat_text, token_chunks, index + 1, depth) {
+                            symbols.push(symbol);
+                        }
+                        continue;
+                    }
+                    // the name is the next token that isn't whitespace
+                    if let Some(next) = token_chunks[index + 1..].iter().find( | chunk | !chunk.token_type.should_ignore()) {
+                        match next.token_type {
+                            TokenType::Identifier | TokenType::Call | TokenType::TypeName => {
+                                let name = Self::chunk_string(flat_text, next);
+                                symbols.push(CodeSymbol {
+                                    label: format!("{} {}", kind.keyword(), name),
+                                    name: name,
+                                    kind: kind,
+                                    offset: next.offset,
+                                    depth: depth
+                                });
+                            },
+                            _ => ()
+                        }
+                    }
+                },
+                _ => ()
+            }
+        }
+        symbols
+    }
+    
+    fn scan_impl(flat_text: &[char], token_chunks: &[TokenChunk], start: usize, depth: usize) -> Option<CodeSymbol> {
+        // impl<T> path::Trait<T> for path::Type<T> where ... {
+        let mut angle = 0;
+        let mut first: Option<&TokenChunk> = None;
+        let mut second: Option<&TokenChunk> = None;
+        let mut after_for = false;
+        for chunk in &token_chunks[start..] {
+            let text = Self::chunk_string(flat_text, chunk);
+            match chunk.token_type {
+                TokenType::ParenOpen if text == "{" => break,
+                TokenType::Delimiter if text == ";" => break,
+                TokenType::Operator if text != "->" && text != "=>" => {
+                    for ch in text.chars() {
+                        if ch == '<' {
+                            angle += 1;
+                        }
+                        else if ch == '>' && angle > 0 {
+                            angle -= 1;
+                        }
+                    }
+                },
+                TokenType::Identifier | TokenType::TypeName | TokenType::BuiltinType | TokenType::Keyword | TokenType::Looping if angle == 0 => {
+                    if text == "where" {
+                        break;
+                    }
+                    if text == "for" {
+                        after_for = true;
+                    }
+                    else if chunk.token_type != TokenType::Keyword && chunk.token_type != TokenType::Looping {
+                        // the last segment of a path wins
+                        if after_for {
+                            second = Some(chunk);
+                        }
+                        else {
+                            first = Some(chunk);
+                        }
+                    }
+                },
+                TokenType::Eof => break,
+                _ => ()
+            }
+        }
+        let (type_chunk, label) = if let (Some(first), Some(second)) = (first, second) {
+            let type_name = Self::chunk_string(flat_text, second);
+            (second, format!("impl {} for {}", Self::chunk_string(flat_text, first), type_name))
+        }
+        else if let Some(first) = first {
+            (first, format!("impl {}", Self::chunk_string(flat_text, first)))
+        }
+        else {
+            return None
+        };
+        Some(CodeSymbol {
+            name: Self::chunk_string(flat_text, type_chunk),
+            label: label,
+            kind: CodeSymbolKind::Impl,
+            offset: type_chunk.offset,
+            depth: depth
+        })
+    }
+}
@@ -2,6 +2,8 @@
 
 use crate::textcursor::*;
 use std::collections::HashMap;
+use std::time::SystemTime;
+use serde::*;
 
 #[derive(Clone, Default)]
 pub struct TextBuffer {
@@ -22,6 +24,10 @@ pub struct TextBuffer {
     pub token_chunks: Vec<TokenChunk>,
     pub token_chunks_id: u64,
     pub keyboard: TextBufferKeyboard,
+    pub folds: Vec<TextFold>,
+    pub folds_id: u64,
+    pub line_changes: TextLineChanges,
+    pub debug: TextBufferDebug,
 } 
 
 impl TextBuffer {
@@ -41,6 +47,9 @@ pub const SIGNAL_TEXTBUFFER_MESSAGE_UPDATE: usize = 2;
 pub const SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET: usize = 3;
 pub const SIGNAL_TEXTBUFFER_DATA_UPDATE: usize = 4;
 pub const SIGNAL_TEXTBUFFER_KEYBOARD_UPDATE: usize = 5;
+pub const SIGNAL_TEXTBUFFER_LINE_CHANGES_UPDATE: usize = 6;
+pub const SIGNAL_TEXTBUFFER_DEBUG_UPDATE: usize = 7;
+pub const SIGNAL_TEXTBUFFER_SAVED: usize = 8;
 
 #[derive(Clone, Default)]
 pub struct TextBufferKeyboard {
@@ -49,6 +58,42 @@ pub struct TextBufferKeyboard {
     pub key_up: Option<KeyCode>
 }
 
+// debugger state shown in the gutter, rows are 0 based
+#[derive(Clone, Default)]
+pub struct TextBufferDebug {
+    pub breakpoints: Vec<usize>,
+    pub exec_row: Option<usize>
+}
+
+impl TextBufferDebug {
+    // rows start_row+1..=end_row were replaced by new_rows rows
+    fn update_breakpoints(&mut self, start_row: usize, end_row: usize, new_rows: usize) {
+        if start_row == end_row && new_rows == 0 {
+            return
+        }
+        self.breakpoints.retain( | row | *row <= start_row || *row > end_row);
+        for row in &mut self.breakpoints {
+            if *row > end_row {
+                *row = *row + new_rows - (end_row - start_row);
+            }
+        }
+    }
+    
+    // returns if the row has a breakpoint now
+    pub fn toggle_breakpoint(&mut self, row: usize) -> bool {
+        match self.breakpoints.binary_search(&row) {
+            Ok(index) => {
+                self.breakpoints.remove(index);
+                false
+            },
+            Err(index) => {
+                self.breakpoints.insert(index, row);
+                true
+            }
+        }
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct TextBufferMessages {
     pub gc_id: u64,
@@ -60,6 +105,39 @@ pub struct TextBufferMessages {
     pub jump_to_offset: usize
 }
 
+impl TextBufferMessages {
+    // drops the messages of one source before it adds new ones,
+    // messages for an older version of the text are dropped whatever their source
+    pub fn clear_source(&mut self, source: TextBufferMessageSource, mutation_id: u64) {
+        if self.mutation_id != mutation_id {
+            self.mutation_id = mutation_id;
+            self.cursors.truncate(0);
+            self.bodies.truncate(0);
+            return
+        }
+        let mut index = 0;
+        while index < self.bodies.len() {
+            if self.bodies[index].source == source {
+                self.cursors.remove(index);
+                self.bodies.remove(index);
+            }
+            else {
+                index += 1;
+            }
+        }
+    }
+    
+    // the editor walks the message cursors in order
+    pub fn sort(&mut self) {
+        let mut messages: Vec<(TextCursor, TextBufferMessage)> = self.cursors.drain(..).zip(self.bodies.drain(..)).collect();
+        messages.sort_by_key( | (cursor, _) | cursor.head.min(cursor.tail));
+        for (cursor, body) in messages {
+            self.cursors.push(cursor);
+            self.bodies.push(body);
+        }
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum TextBufferMessageLevel {
     Error,
@@ -67,37 +145,237 @@ pub enum TextBufferMessageLevel {
     Log
 }
 
+// who put the message there, each source replaces only its own messages
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextBufferMessageSource {
+    Compiler,
+    Lsp,
+    Shader
+}
+
 #[derive(Clone)]
 pub struct TextBufferMessage {
     pub level: TextBufferMessageLevel,
-    pub body: String
+    pub source: TextBufferMessageSource,
+    pub body: String,
+    // machine applicable replacements that resolve this message
+    pub fixes: Vec<TextFix>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextFix {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String
+}
+
+#[derive(Clone, Default)]
+pub struct TextLineChanges {
+    pub base_read_req: FileReadRequest,
+    // the lines of the file at git HEAD, None if the file isn't in git
+    pub base_lines: Option<Vec<Vec<char>>>,
+    // only if this matches the textbuffer mutation id are the changes valid
+    pub mutation_id: u64,
+    pub changes: Vec<TextLineChange>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextLineChangeType {
+    Added,
+    Modified,
+    Deleted
+}
+
+// a hunk of lines, start_row..end_row in the buffer replaced base_start..base_end of the base
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TextLineChange {
+    pub change_type: TextLineChangeType,
+    pub start_row: usize,
+    pub end_row: usize,
+    pub base_start: usize,
+    pub base_end: usize
+}
+
+// past this many changed lines the diff gives up and marks the whole edited range
+pub const TEXT_DIFF_MAX_EDITS: usize = 1000;
+
+impl TextLineChange {
+    // line diff of base against lines, appends the hunks to changes
+    pub fn diff_lines(base: &Vec<Vec<char>>, lines: &Vec<Vec<char>>, changes: &mut Vec<TextLineChange>) {
+        // most edits are small, so strip the unchanged top and bottom first
+        let mut top_row = 0;
+        while top_row < lines.len() && top_row < base.len() && lines[top_row] == base[top_row] {
+            top_row += 1;
+        }
+        let mut bottom_row = lines.len();
+        let mut base_bottom_row = base.len();
+        while bottom_row > top_row && base_bottom_row > top_row && lines[bottom_row - 1] == base[base_bottom_row - 1] {
+            bottom_row -= 1;
+            base_bottom_row -= 1;
+        }
+        let rows = bottom_row - top_row;
+        let base_rows = base_bottom_row - top_row;
+        if rows == 0 && base_rows == 0 {
+            return
+        }
+        let lines = &lines[top_row..bottom_row];
+        let base = &base[top_row..base_bottom_row];
+        // the rows both keep, past the edit limit the whole middle is one change
+        let matches = if rows == 0 || base_rows == 0 {None}else {Self::myers_matches(lines, base, TEXT_DIFF_MAX_EDITS)};
+        let matches = if let Some(matches) = matches {matches}else {
+            Self::push(changes, top_row, bottom_row, top_row, base_bottom_row);
+            return
+        };
+        let mut i = 0;
+        let mut j = 0;
+        for (mi, mj) in matches.into_iter().chain(std::iter::once((rows, base_rows))) {
+            if mi > i || mj > j {
+                Self::push(changes, top_row + i, top_row + mi, top_row + j, top_row + mj);
+            }
+            i = mi + 1;
+            j = mj + 1;
+        }
+    }
+    
+    // Myers' O((N+M)D) diff, the pairs of rows of lines and base that stay, in order.
+    // None when it takes more than max_edits line inserts and removes
+    fn myers_matches(lines: &[Vec<char>], base: &[Vec<char>], max_edits: usize) -> Option<Vec<(usize, usize)>> {
+        let n = lines.len() as isize;
+        let m = base.len() as isize;
+        let max = n + m;
+        // v[k] is the furthest row of lines reached on diagonal k = row - base row
+        let mut v = vec![0isize; 2 * max as usize + 3];
+        let vi = | k: isize | (k + max + 1) as usize;
+        // the diagonals -d..=d of v after each step, to walk back the path
+        let mut trace: Vec<Vec<isize>> = Vec::new();
+        for d in 0..=(max_edits as isize).min(max) {
+            for k in (-d..=d).step_by(2) {
+                let mut x = if k == -d || (k != d && v[vi(k - 1)] < v[vi(k + 1)]) {v[vi(k + 1)]}else {v[vi(k - 1)] + 1};
+                let mut y = x - k;
+                while x < n && y < m && lines[x as usize] == base[y as usize] {
+                    x += 1;
+                    y += 1;
+                }
+                v[vi(k)] = x;
+                if x >= n && y >= m {
+                    return Some(Self::myers_backtrack(&trace, n, m, d))
+                }
+            }
+            trace.push(v[vi(-d)..=vi(d)].to_vec());
+        }
+        None
+    }
+    
+    fn myers_backtrack(trace: &Vec<Vec<isize>>, n: isize, m: isize, edits: isize) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut x = n;
+        let mut y = m;
+        for d in (1..=edits).rev() {
+            let prev = &trace[d as usize - 1];
+            let pv = | k: isize | prev[(k + d - 1) as usize];
+            let k = x - y;
+            let prev_k = if k == -d || (k != d && pv(k - 1) < pv(k + 1)) {k + 1}else {k - 1};
+            let prev_x = pv(prev_k);
+            let prev_y = prev_x - prev_k;
+            while x > prev_x && y > prev_y {
+                x -= 1;
+                y -= 1;
+                matches.push((x as usize, y as usize));
+            }
+            x = prev_x;
+            y = prev_y;
+        }
+        while x > 0 && y > 0 {
+            x -= 1;
+            y -= 1;
+            matches.push((x as usize, y as usize));
+        }
+        matches.reverse();
+        matches
+    }
+    
+    fn push(changes: &mut Vec<TextLineChange>, start_row: usize, end_row: usize, base_start: usize, base_end: usize) {
+        changes.push(TextLineChange {
+            change_type: if start_row == end_row {
+                TextLineChangeType::Deleted
+            }
+            else if base_start == base_end {
+                TextLineChangeType::Added
+            }
+            else {
+                TextLineChangeType::Modified
+            },
+            start_row: start_row,
+            end_row: end_row,
+            base_start: base_start,
+            base_end: base_end
+        })
+    }
 }
 
 pub struct TextBuffers {
     pub root_path: String,
-    pub storage: HashMap<String, TextBuffer>
+    pub storage: HashMap<String, TextBuffer>,
+    // polls the repository, a commit or checkout leaves the HEAD versions of the buffers stale
+    pub git_timer: Timer,
+    pub git_stamp: Vec<Option<SystemTime>>
 }
 
 impl TextBuffers {
     pub fn from_path(&mut self, cx: &mut Cx, path: &str) -> &mut TextBuffer {
+        if self.git_timer.is_empty() {
+            self.git_timer = cx.start_timer(2.0, true);
+            self.git_stamp = self.git_stamp();
+        }
         let root_path = &self.root_path;
         self.storage.entry(path.to_string()).or_insert_with( || {
             TextBuffer {
                 signal: cx.new_signal(),
                 mutation_id: 1,
                 load_read_req: cx.read_file(&format!("{}{}", root_path, path)),
+                line_changes: TextLineChanges {
+                    base_read_req: cx.read_git_head(&format!("{}{}", root_path, path)),
+                    ..Default::default()
+                },
                 ..Default::default()
             }
         })
     }
     
+    // the modified times of the files a commit, checkout or reset rewrites
+    fn git_stamp(&self) -> Vec<Option<SystemTime>> {
+        ["index", "HEAD"].iter().map( | name | {
+            std::fs::metadata(format!("{}.git/{}", self.root_path, name)).and_then( | meta | meta.modified()).ok()
+        }).collect()
+    }
+    
+    // reads the HEAD versions again when the repository changed
+    pub fn handle_git_head(&mut self, cx: &mut Cx, event: &mut Event) {
+        match event {
+            Event::Timer(te) => if !self.git_timer.is_timer(te) {
+                return
+            },
+            Event::AppFocus => (),
+            _ => return
+        }
+        let git_stamp = self.git_stamp();
+        if git_stamp == self.git_stamp {
+            return
+        }
+        self.git_stamp = git_stamp;
+        for (path, text_buffer) in &mut self.storage {
+            text_buffer.line_changes.base_read_req = cx.read_git_head(&format!("{}{}", self.root_path, path));
+        }
+    }
+    
     pub fn save_file(&mut self, cx: &mut Cx, path: &str) {
         let text_buffer = self.storage.get(path);
         if let Some(text_buffer) = text_buffer {
             let string = text_buffer.get_as_string();
             cx.write_file(&format!("{}{}", self.root_path, path), string.as_bytes());
+            cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_SAVED);
             //cx.http_send("POST", path, "192.168.0.20", "2001", &string);
-            
+        
         }
     }
     
@@ -112,13 +390,32 @@ impl TextBuffers {
                 }
                 return true
             }
+            if let Some(utf8_data) = text_buffer.line_changes.base_read_req.as_utf8(fr) {
+                // no base means the file isn't tracked, so no markers
+                text_buffer.line_changes.base_lines = if let Ok(utf8_data) = utf8_data {
+                    Some(TextBuffer::split_string_to_lines(&utf8_data.to_string()))
+                }
+                else {
+                    None
+                };
+                text_buffer.line_changes.mutation_id = 0;
+                cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_LINE_CHANGES_UPDATE);
+                return true
+            }
         }
         return false;
     }
-    
+
+}
+
+// a user folded region, start and end are the offsets of the paren pair enclosing it
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TextFold {
+    pub start: usize,
+    pub end: usize
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct TextPos {
     pub row: usize,
     pub col: usize
@@ -147,6 +444,8 @@ pub enum TextUndoGrouping {
     Tab,
     Cut,
     Format,
+    Revert,
+    QuickFix,
     Other
 }
 
@@ -167,6 +466,8 @@ impl TextUndoGrouping {
             TextUndoGrouping::Block => false,
             TextUndoGrouping::Tab => false,
             TextUndoGrouping::Format => false,
+            TextUndoGrouping::Revert => false,
+            TextUndoGrouping::QuickFix => false,
             TextUndoGrouping::Cut => false,
             TextUndoGrouping::Other => false
         }
@@ -433,8 +734,14 @@ impl TextBuffer {
     
     pub fn replace_range(&mut self, start: usize, len: usize, mut rep_lines: Vec<Vec<char>>) -> Vec<Vec<char>> {
         self.mutation_id += 1;
+        if self.folds.len() > 0 {
+            self.update_folds(start, len, calc_char_count(&rep_lines));
+        }
         let start_pos = self.offset_to_text_pos(start);
         let end_pos = self.offset_to_text_pos_next(start + len, start_pos, start);
+        if self.debug.breakpoints.len() > 0 {
+            self.debug.update_breakpoints(start_pos.row, end_pos.row, rep_lines.len().max(1) - 1);
+        }
         
         if start_pos.row == end_pos.row && rep_lines.len() == 1 { // replace in one line
             let rep_line_zero = rep_lines.drain(0..1).next().unwrap();
@@ -506,6 +813,60 @@ impl TextBuffer {
         }
     }
     
+    fn update_folds(&mut self, start: usize, len: usize, new_len: usize) {
+        // shift the folds behind the edit, editing anything inside a fold unfolds it
+        let end = start + len;
+        let old_count = self.folds.len();
+        self.folds.retain( | fold | end <= fold.start || start > fold.end);
+        if self.folds.len() != old_count {
+            self.folds_id += 1;
+        }
+        for fold in &mut self.folds {
+            if end <= fold.start {
+                fold.start = fold.start + new_len - len;
+                fold.end = fold.end + new_len - len;
+            }
+        }
+    }
+    
+    pub fn find_fold(&self, start: usize) -> Option<usize> {
+        self.folds.iter().position( | fold | fold.start == start)
+    }
+    
+    pub fn toggle_fold(&mut self, start: usize, end: usize) {
+        if let Some(index) = self.find_fold(start) {
+            self.folds.remove(index);
+        }
+        else {
+            let index = self.folds.iter().position( | fold | fold.start > start).unwrap_or(self.folds.len());
+            self.folds.insert(index, TextFold {start: start, end: end});
+        }
+        self.folds_id += 1;
+    }
+    
+    pub fn update_line_changes(&mut self) {
+        if self.line_changes.mutation_id == self.mutation_id || self.load_read_req.is_loading() {
+            return
+        }
+        self.line_changes.mutation_id = self.mutation_id;
+        self.line_changes.changes.truncate(0);
+        let base = if let Some(base) = &self.line_changes.base_lines {base}else {return};
+        TextLineChange::diff_lines(base, &self.lines, &mut self.line_changes.changes);
+    }
+    
+    pub fn find_line_change(&self, row: usize) -> Option<usize> {
+        // deleted hunks sit between rows, we give them the row below
+        if self.line_changes.mutation_id != self.mutation_id {
+            return None
+        }
+        // and a deletion at the end of the file the last row
+        let last_row = self.lines.len().max(1) - 1;
+        self.line_changes.changes.iter().position( | change | {
+            row >= change.start_row && row < change.end_row.max(change.start_row + 1)
+                || change.start_row > last_row && row == last_row
+        })
+    }
+    
     pub fn replace_lines(&mut self, start_row: usize, end_row: usize, rep_lines: Vec<Vec<char>>) -> TextOp {
         let start = self.text_pos_to_offset(TextPos {row: start_row, col: 0});
         let end = self.text_pos_to_offset(TextPos {row: end_row, col: 0});
@@ -631,7 +992,7 @@ impl TextBuffer {
             }
         }
     }
-    
+
 }
 
 pub struct LineTokenizer<'a> {
@@ -906,7 +1267,7 @@ impl TokenChunk {
             token_type: token_type.clone()
         })
     }
-    
+
 }
 
 pub struct TokenParserItem {
@@ -1045,5 +1406,100 @@ impl FormatOutput {
             last_line.push(' ');
         }
     }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::textbuffer::*;
+    
+    fn lines(text: &str) -> Vec<Vec<char>> {
+        TextBuffer::split_string_to_lines(&text.to_string())
+    }
+    
+    fn diff(base: &str, text: &str) -> Vec<TextLineChange> {
+        let mut changes = Vec::new();
+        TextLineChange::diff_lines(&lines(base), &lines(text), &mut changes);
+        changes
+    }
+    
+    fn change(change_type: TextLineChangeType, start_row: usize, end_row: usize, base_start: usize, base_end: usize) -> TextLineChange {
+        TextLineChange {change_type: change_type, start_row: start_row, end_row: end_row, base_start: base_start, base_end: base_end}
+    }
+    
+    #[test]
+    fn same_lines_have_no_changes() {
+        assert_eq!(diff("a\nb\nc", "a\nb\nc"), vec![]);
+    }
+    
+    #[test]
+    fn finds_added_modified_and_deleted_hunks() {
+        assert_eq!(diff("a\nb\nc\nd\ne", "a\nx\nb\nc\ne"), vec![
+            change(TextLineChangeType::Added, 1, 2, 1, 1),
+            change(TextLineChangeType::Deleted, 4, 4, 3, 4)
+        ]);
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), vec![change(TextLineChangeType::Modified, 1, 2, 1, 2)]);
+    }
+    
+    #[test]
+    fn deletion_at_the_end_starts_past_the_last_row() {
+        let changes = diff("a\nb\nc", "a");
+        assert_eq!(changes, vec![change(TextLineChangeType::Deleted, 1, 1, 1, 3)]);
+        let mut text_buffer = TextBuffer {lines: lines("a"), mutation_id: 1, ..Default::default()};
+        text_buffer.line_changes = TextLineChanges {base_lines: Some(lines("a\nb\nc")), changes: changes, mutation_id: 1, ..Default::default()};
+        assert_eq!(text_buffer.find_line_change(0), Some(0));
+    }
+    
+    // a small xorshift, so the random diffs are the same every run
+    fn random_lines(seed: &mut u32, count: usize) -> Vec<Vec<char>> {
+        (0..count).map( | _ | {
+            *seed ^= *seed << 13;
+            *seed ^= *seed >> 17;
+            *seed ^= *seed << 5;
+            vec![(b'a' + (*seed % 4) as u8) as char]
+        }).collect()
+    }
+    
+    fn lcs_len(a: &Vec<Vec<char>>, b: &Vec<Vec<char>>) -> usize {
+        let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                lcs[i + 1][j + 1] = if a[i] == b[j] {lcs[i][j] + 1}else {lcs[i][j + 1].max(lcs[i + 1][j])};
+            }
+        }
+        lcs[a.len()][b.len()]
+    }
+    
+    #[test]
+    fn random_diffs_are_minimal_and_rebuild_the_lines() {
+        let mut seed = 2463534242;
+        for round in 0..200 {
+            let base = random_lines(&mut seed, round % 30);
+            let text = random_lines(&mut seed, (round * 7) % 30);
+            let mut changes = Vec::new();
+            TextLineChange::diff_lines(&base, &text, &mut changes);
+            // put the new lines in place of the changed base lines
+            let mut rebuilt = Vec::new();
+            let mut base_row = 0;
+            let mut edits = 0;
+            for change in &changes {
+                rebuilt.extend_from_slice(&base[base_row..change.base_start]);
+                rebuilt.extend_from_slice(&text[change.start_row..change.end_row]);
+                base_row = change.base_end;
+                edits += (change.end_row - change.start_row) + (change.base_end - change.base_start);
+            }
+            rebuilt.extend_from_slice(&base[base_row..]);
+            assert_eq!(rebuilt, text, "round {}", round);
+            assert_eq!(edits, base.len() + text.len() - 2 * lcs_len(&base, &text), "round {}", round);
+        }
+    }
     
+    #[test]
+    fn too_many_edits_make_one_change() {
+        let base: Vec<Vec<char>> = (0..TEXT_DIFF_MAX_EDITS).map( | i | format!("a{}", i).chars().collect()).collect();
+        let text: Vec<Vec<char>> = (0..TEXT_DIFF_MAX_EDITS).map( | i | format!("b{}", i).chars().collect()).collect();
+        let mut changes = Vec::new();
+        TextLineChange::diff_lines(&base, &text, &mut changes);
+        assert_eq!(changes, vec![change(TextLineChangeType::Modified, 0, TEXT_DIFF_MAX_EDITS, 0, TEXT_DIFF_MAX_EDITS)]);
+    }
 }
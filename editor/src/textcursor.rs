@@ -756,6 +756,75 @@ impl TextCursorSet {
             })
         }
     }
+    pub fn revert_line_change(&mut self, change: TextLineChange, text_buffer: &mut TextBuffer) {
+        // put back the lines from git HEAD as a single undo step
+        let mut rep_lines: Vec<Vec<char>> = if let Some(base) = &text_buffer.line_changes.base_lines {
+            base[change.base_start..change.base_end].to_vec()
+        }
+        else {
+            return
+        };
+        // replace_lines needs at least one line, so we take along a neighbouring unchanged one
+        let (start_row, end_row) = if change.end_row < text_buffer.lines.len() {
+            rep_lines.push(text_buffer.lines[change.end_row].clone());
+            (change.start_row, change.end_row + 1)
+        }
+        else if change.start_row > 0 {
+            rep_lines.insert(0, text_buffer.lines[change.start_row - 1].clone());
+            (change.start_row - 1, change.end_row)
+        }
+        else {
+            if rep_lines.len() == 0 {
+                rep_lines.push(Vec::new());
+            }
+            (change.start_row, change.end_row)
+        };
+        let cursors_clone = self.clone();
+        let op = text_buffer.replace_lines(start_row, end_row, rep_lines);
+        text_buffer.redo_stack.truncate(0);
+        text_buffer.undo_stack.push(TextUndo {
+            ops: vec![op],
+            grouping: TextUndoGrouping::Revert,
+            cursors: cursors_clone
+        });
+        let offset = text_buffer.text_pos_to_offset(TextPos {row: change.start_row, col: 0});
+        self.clear_and_set_last_cursor_head_and_tail(offset, text_buffer);
+    }
+    
+    pub fn apply_fixes(&mut self, fixes: &[TextFix], text_buffer: &mut TextBuffer) -> bool {
+        // apply in order, skipping fixes that overlap an earlier one
+        let char_count = text_buffer.calc_char_count();
+        let mut fixes: Vec<&TextFix> = fixes.iter().filter( | fix | fix.start <= fix.end && fix.end <= char_count).collect();
+        fixes.sort_by_key( | fix | (fix.start, fix.end));
+        let mut ops = Vec::new();
+        let cursors_clone = self.clone();
+        let mut delta: isize = 0; // rolling delta to displace later fixes
+        let mut last_end = 0;
+        let mut cursor_pos = None;
+        for fix in fixes {
+            if fix.start < last_end {
+                continue;
+            }
+            last_end = fix.end;
+            let start = (fix.start as isize + delta) as usize;
+            let op = text_buffer.replace_lines_with_string(start, fix.end - fix.start, &fix.replacement);
+            delta += op.len as isize - (fix.end - fix.start) as isize;
+            cursor_pos = Some(op.start + op.len);
+            ops.push(op);
+        }
+        if let Some(cursor_pos) = cursor_pos {
+            text_buffer.redo_stack.truncate(0);
+            text_buffer.undo_stack.push(TextUndo {
+                ops: ops,
+                grouping: TextUndoGrouping::QuickFix,
+                cursors: cursors_clone
+            });
+            self.clear_and_set_last_cursor_head_and_tail(cursor_pos, text_buffer);
+            return true
+        }
+        false
+    }
+    
     /*
     pub fn toggle_comment(&mut self, text_buffer:&mut TextBuffer, comment_str:&str){
         let mut delta:usize = 0; // rolling delta to displace cursors
@@ -893,6 +962,18 @@ impl TextCursorSet {
         self.fuse_adjacent(text_buffer)
     }
     
+    pub fn move_with<F>(&mut self, only_head: bool, text_buffer: &TextBuffer, mut f: F)
+    where F: FnMut(&TextCursor) -> (usize, usize) {
+        self.insert_undo_group += 1;
+        for cursor in &mut self.set {
+            let (head, max) = f(cursor);
+            cursor.head = head;
+            cursor.max = max;
+            if !only_head {cursor.tail = cursor.head}
+        }
+        self.fuse_adjacent(text_buffer)
+    }
+    
     pub fn move_left(&mut self, char_count: usize, only_head: bool, text_buffer: &TextBuffer) {
         self.insert_undo_group += 1;
         let mut old_max = (TextPos {row: 0, col: 0}, 0);
@@ -76,13 +76,15 @@ impl App {
     fn def_quad_shader(cx: &mut Cx) -> Shader {
         let mut sh = Quad::def_quad_shader(cx);
         sh.add_ast(shader_ast!({
+            use df;
+            
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                df_rect(0., 0., w, h);
-                df_fill(vec4(pos.x, pos.y, sin(pos.x), 1.));
-                df_move_to(0., 0.);
-                df_line_to(w, h);
-                return df_stroke(color, 4.);
+                df::viewport(pos * vec2(w, h));
+                df::rect(0., 0., w, h);
+                df::fill(vec4(pos.x, pos.y, sin(pos.x), 1.));
+                df::move_to(0., 0.);
+                df::line_to(w, h);
+                return df::stroke(color, 4.);
             }
         }));
         sh
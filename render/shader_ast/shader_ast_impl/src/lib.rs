@@ -7,6 +7,10 @@
 //      fn pixel()->vec4{
 //          return vec4(1.);
 //      }
+//      // struct def:
+//      struct Light{pos:vec3, color:vec4}
+//      // import a shader module, its fns are called as df::circle()
+//      use df;
 //})
 // into a nested tree of shader AST structs
 // these are defined in shader.rs in the root project
@@ -29,8 +33,8 @@ use proc_macro2::TokenStream;
 use proc_macro2::Span;
 use syn::{
     Expr, Type, Pat, Stmt, PathArguments, GenericArgument, 
-    Item, Local, ItemFn, ItemConst, ItemStruct,
-    Lit, Block, FnArg, BinOp, UnOp, Ident, ReturnType, Member
+    Item, Local, ItemFn, ItemConst, ItemStruct, ItemUse, UseTree, Path,
+    Lit, Block, FnArg, BinOp, UnOp, Ident, ReturnType, Member, Fields
 };
 use quote::quote;
 use quote::quote_spanned;
@@ -41,6 +45,17 @@ fn error(span:Span, msg: &str)->TokenStream{
     quote_spanned!(span=>compile_error!(#fmsg))
 }
 
+// the location of a node in the shader_ast! block, line!() would give the line of the macro call
+// so the line and column come from the span, file!() is the file the macro is called in
+fn generate_loc(span:Span)->TokenStream{
+    let span = span.unwrap();
+    let line = span.line() as u32;
+    let col = span.column() as u32;
+    quote!{ShLoc{file:file!(), line:#line, col:#col}}
+}
+
+const STORES:[&str;8] = ["Uniform", "UniformVw", "UniformCx", "Instance", "Geometry", "Texture", "Local", "Varying"];
+
 // generate the ShVar definitions from a let statement
 fn generate_shvar_defs(stmt:Local)->TokenStream{
     // lets define a local with storage specified
@@ -68,6 +83,12 @@ fn generate_shvar_defs(stmt:Local)->TokenStream{
                             }
                             let seg = &typath.path.segments[0];
                             store = seg.ident.clone();
+                            if !STORES.contains(&store.to_string().as_ref()){
+                                return error(store.span(), &format!("Unknown storage {}, use one of {}", store, STORES.join(", ")));
+                            }
+                            if (found_type == "texture2d") != (store.to_string() == "Texture"){
+                                return error(typath.span(), "Textures are declared as texture2d<Texture> and only textures use Texture storage");
+                            }
                         }
                         else{
                             return error(arg.span(), "Only simple typenames such as float or vec4 are supported");
@@ -88,11 +109,13 @@ fn generate_shvar_defs(stmt:Local)->TokenStream{
         else{
             return error(stmt.span(), "Please give the variable a type of the form float<Local> or vec4<Uniform>");
         }
+        let loc = generate_loc(pat.ident.span());
         return quote!{
             ShVar{
                 name:#name.to_string(),
                 ty:#found_type.to_string(),
-                store:ShVarStore::#store
+                store:ShVarStore::#store,
+                loc:#loc
             }
         }
     }
@@ -154,12 +177,14 @@ fn generate_fn_def(item:ItemFn)->TokenStream{
         //return error(item.span(), "function needs to specify return type")
     }
     let block = generate_block(*item.block);
+    let loc = generate_loc(item.ident.span());
     quote!{
         ShFn{
             name:#name.to_string(),
             args:{let mut v=Vec::new();#(v.push(#args);)*v},
             ret:#return_type.to_string(),
-            block:Some(#block)
+            block:Some(#block),
+            loc:#loc
         }
     }
 }
@@ -193,11 +218,13 @@ fn generate_let(local:Local)->TokenStream{
         else{
             return error(local.span(), "let pattern misses initializer");
         }
+        let loc = generate_loc(pat.ident.span());
         return quote!{
             ShLet{
                 name:#name.to_string(),
                 ty:#found_type.to_string(),
-                init:Box::new(#init)
+                init:Box::new(#init),
+                loc:#loc
             }
         }
     }
@@ -275,32 +302,43 @@ fn get_binop(op:BinOp)->&'static str{
     }
 }
 
+// the name of a call or id, df::circle is a fn from the shader module df
+fn generate_path_name(path:&Path)->Result<String, TokenStream>{
+    match path.segments.len(){
+        1=>Ok(path.segments[0].ident.to_string()),
+        2=>Ok(format!("{}::{}", path.segments[0].ident, path.segments[1].ident)),
+        _=>Err(error(path.span(), "only simple names like x or module names like df::circle are supported"))
+    }
+}
+
 // generate the AST from an expression
 fn generate_expr(expr:Expr)->TokenStream{
     match expr{
         Expr::Call(expr)=>{
             if let Expr::Path(func) = *expr.func{
-                if func.path.segments.len() != 1{
-                    return error(func.span(), "call identifier not simple");
-                }
-                let seg = &func.path.segments[0].ident.to_string();
+                let seg = match generate_path_name(&func.path){
+                    Ok(seg)=>seg,
+                    Err(err)=>return err
+                };
+                let loc = generate_loc(func.span());
                 // lets get all fn args
                 let mut args = Vec::new();
                 for arg in expr.args{
                     args.push(generate_expr(arg));
                 }
                 
-                return quote!{ShExpr::ShCall(ShCall{call:#seg.to_string(), args:{let mut v=Vec::new();#(v.push(Box::new(#args));)*v}})}
+                return quote!{ShExpr::ShCall(ShCall{call:#seg.to_string(), args:{let mut v=Vec::new();#(v.push(Box::new(#args));)*v}, loc:#loc})}
             }
             else{
                  return error(expr.span(), "call identifier not simple");
             }
         }
         Expr::Binary(expr)=>{
+            let loc = generate_loc(expr.op.span());
             let left = generate_expr(*expr.left);
             let right = generate_expr(*expr.right);
             let op = Ident::new(get_binop(expr.op), Span::call_site());
-            return quote!{ShExpr::ShBinary(ShBinary{left:Box::new(#left),op:ShBinOp::#op,right:Box::new(#right)})}
+            return quote!{ShExpr::ShBinary(ShBinary{left:Box::new(#left),op:ShBinOp::#op,right:Box::new(#right),loc:#loc})}
         }
         Expr::Unary(expr)=>{
             let op;
@@ -313,8 +351,9 @@ fn generate_expr(expr:Expr)->TokenStream{
             else {
                 return error(expr.span(), "Deref not implemented");
             }
+            let loc = generate_loc(expr.op.span());
             let right = generate_expr(*expr.expr);
-            return quote!{ShExpr::ShUnary(ShUnary{op:ShUnaryOp::#op,expr:Box::new(#right)})}
+            return quote!{ShExpr::ShUnary(ShUnary{op:ShUnaryOp::#op,expr:Box::new(#right),loc:#loc})}
         }
         Expr::Lit(expr)=>{
             match expr.lit{
@@ -413,17 +452,20 @@ fn generate_expr(expr:Expr)->TokenStream{
             }
         }
         Expr::Assign(expr)=>{
+            let loc = generate_loc(expr.eq_token.span());
             let left = generate_expr(*expr.left);
             let right = generate_expr(*expr.right);
-            return quote!{ShExpr::ShAssign(ShAssign{left:Box::new(#left),right:Box::new(#right)})}
+            return quote!{ShExpr::ShAssign(ShAssign{left:Box::new(#left),right:Box::new(#right),loc:#loc})}
         }
         Expr::AssignOp(expr)=>{
+            let loc = generate_loc(expr.op.span());
             let left = generate_expr(*expr.left);
             let right = generate_expr(*expr.right);
             let op = Ident::new(get_binop(expr.op), Span::call_site());
-            return quote!{ShExpr::ShAssignOp(ShAssignOp{left:Box::new(#left),op:ShBinOp::#op,right:Box::new(#right)})}
+            return quote!{ShExpr::ShAssignOp(ShAssignOp{left:Box::new(#left),op:ShBinOp::#op,right:Box::new(#right),loc:#loc})}
         }
         Expr::Field(expr)=>{
+            let loc = generate_loc(expr.member.span());
             let member;
             if let Member::Named(ident) = expr.member{
                 member = ident.to_string();
@@ -432,19 +474,21 @@ fn generate_expr(expr:Expr)->TokenStream{
                 return error(expr.span(), "No unnamed members supported")
             }
             let base = generate_expr(*expr.base);
-            return quote!{ShExpr::ShField(ShField{base:Box::new(#base),member:#member.to_string()})}
+            return quote!{ShExpr::ShField(ShField{base:Box::new(#base),member:#member.to_string(),loc:#loc})}
         }
         Expr::Index(expr)=>{
+            let loc = generate_loc(expr.bracket_token.span);
             let base = generate_expr(*expr.expr);
             let index = generate_expr(*expr.index);
-            return quote!{ShExpr::ShIndex(ShIndex{base:Box::new(#base),index:Box::new(#index)})}
+            return quote!{ShExpr::ShIndex(ShIndex{base:Box::new(#base),index:Box::new(#index),loc:#loc})}
         }
         Expr::Path(expr)=>{
-            if expr.path.segments.len() != 1{
-                return error(expr.span(), "type not simple");
-            }
-            let seg = &expr.path.segments[0].ident.to_string();
-            return quote!{ShExpr::ShId(ShId{name:#seg.to_string()})}
+            let seg = match generate_path_name(&expr.path){
+                Ok(seg)=>seg,
+                Err(err)=>return err
+            };
+            let loc = generate_loc(expr.span());
+            return quote!{ShExpr::ShId(ShId{name:#seg.to_string(),loc:#loc})}
         }
         Expr::Paren(expr)=>{
             let expr = generate_expr(*expr.expr);
@@ -455,11 +499,12 @@ fn generate_expr(expr:Expr)->TokenStream{
             return quote!{ShExpr::ShBlock(#block)}
         }
         Expr::Return(expr)=>{
+            let loc = generate_loc(expr.return_token.span());
             if let Some(expr) = expr.expr{
                 let expr = generate_expr(*expr);
-                return quote!{ShExpr::ShReturn(ShReturn{expr:Some(Box::new(#expr))})}
+                return quote!{ShExpr::ShReturn(ShReturn{expr:Some(Box::new(#expr)),loc:#loc})}
             }
-            return quote!{ShExpr::ShReturn(ShReturn{expr:None})}
+            return quote!{ShExpr::ShReturn(ShReturn{expr:None,loc:#loc})}
         }
         Expr::Break(_)=>{
             return quote!{ShExpr::ShBreak(ShBreak{})}
@@ -490,19 +535,73 @@ fn generate_const_def(item:ItemConst)->TokenStream{
         return error(item.ty.span(), "const type not a basic identifier");
     }
 
+    let loc = generate_loc(item.ident.span());
     let expr = generate_expr(*item.expr);
     quote!{
         ShConst{
             name:#name.to_string(),
             ty:#ty.to_string(),
-            value:#expr
+            value:#expr,
+            loc:#loc
         }
     }
 }
 
-// generate the ShStruct defs
-fn generate_struct_def(_item:ItemStruct)->TokenStream{
-    TokenStream::new()
+// generate the ShType defs of a struct, the slots are summed up from the fields by ShaderGen
+fn generate_struct_def(item:ItemStruct)->TokenStream{
+    let name = item.ident.to_string();
+    let mut fields = Vec::new();
+    if let Fields::Named(named) = item.fields{
+        for field in named.named{
+            let field_name = if let Some(ident) = &field.ident{ident.to_string()}else{
+                return error(field.span(), "struct field needs a name")
+            };
+            if let Type::Path(typath) = &field.ty{
+                if typath.path.segments.len() != 1{
+                    return error(typath.span(), "field type not a basic identifier");
+                }
+                let ty = typath.path.segments[0].ident.to_string();
+                fields.push(quote!{
+                    ShTypeField{
+                        name:#field_name.to_string(),
+                        ty:#ty.to_string()
+                    }
+                });
+            }
+            else{
+                return error(field.ty.span(), "field type not a basic identifier");
+            }
+        }
+    }
+    else{
+        return error(item.ident.span(), "struct needs named fields");
+    }
+    if fields.len() == 0{
+        return error(item.ident.span(), "struct needs at least one field");
+    }
+    quote!{
+        ShType{
+            name:#name.to_string(),
+            slots:0,
+            prim:false,
+            fields:{let mut v=Vec::new();#(v.push(#fields);)*v}
+        }
+    }
+}
+
+// generate the ShImport of a use statement
+fn generate_import(item:ItemUse)->TokenStream{
+    if let UseTree::Name(name) = &item.tree{
+        let module = name.ident.to_string();
+        let loc = generate_loc(name.ident.span());
+        return quote!{
+            ShImport{
+                name:#module.to_string(),
+                loc:#loc
+            }
+        }
+    }
+    error(item.tree.span(), "import a shader module as use df; and call its fns as df::circle()")
 }
 
 // Generate the ShAst rootnode
@@ -511,6 +610,7 @@ fn generate_root(expr:Expr)->TokenStream{
     let mut fns = Vec::new();
     let mut consts = Vec::new();
     let mut structs = Vec::new();
+    let mut imports = Vec::new();
     match expr {
         Expr::Block(expr)=>{
             for stmt in expr.block.stmts{
@@ -529,6 +629,9 @@ fn generate_root(expr:Expr)->TokenStream{
                             Item::Fn(item)=>{
                                 fns.push(generate_fn_def(item));
                             }
+                            Item::Use(item)=>{
+                                imports.push(generate_import(item));
+                            }
                             _=>{
                                 return error(stmt.span(), "Unexpected statement")
                             }
@@ -549,10 +652,12 @@ fn generate_root(expr:Expr)->TokenStream{
     };
     quote!{ 
         ShAst{
-            types:Vec::new(),//{let mut v=Vec::new();#(v.push(#types);)*v},
+            types:{let mut v=Vec::new();#(v.push(#structs);)*v},
             vars:{let mut v=Vec::new();#(v.push(#vars);)*v},
             consts:{let mut v=Vec::new();#(v.push(#consts);)*v},
-            fns:{let mut v=Vec::new();#(v.push(#fns);)*v}
+            fns:{let mut v=Vec::new();#(v.push(#fns);)*v},
+            imports:{let mut v=Vec::new();#(v.push(#imports);)*v},
+            module:String::new()
         }
     }
 
@@ -2,6 +2,13 @@ use std::collections::HashMap;
 use std::collections::BTreeMap;
 
 pub use crate::shadergen::*;
+pub use crate::shadercheck::*;
+pub use crate::cx_wgsl::*;
+pub use crate::cx_glsl450::*;
+pub use crate::shaderparse::*;
+pub use crate::shadereval::*;
+pub use crate::shaderopt::*;
+pub use crate::shadermod::*;
 pub use crate::cx_fonts::*;
 pub use crate::cx_turtle::*;
 pub use crate::cx_cursor::*;
@@ -17,9 +24,12 @@ pub use crate::elements::*;
 pub use crate::animator::*;
 pub use crate::area::*;
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "vulkan")))]
 pub use crate::cx_ogl::*;
 
+#[cfg(all(target_os = "linux", feature = "vulkan"))]
+pub use crate::cx_vk::*;
+
 #[cfg(target_os = "macos")]
 pub use crate::cx_mtl::*;
 
@@ -35,12 +45,15 @@ pub use crate::cx_hlsl::*;
 #[cfg(target_arch = "wasm32")]
 pub use crate::cx_webgl::*;
 
-#[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+#[cfg(any(target_arch = "wasm32", all(target_os = "linux", not(feature = "vulkan"))))]
 pub use crate::cx_glsl::*;
 
 #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
 pub use crate::cx_desktop::*;
 
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+pub use crate::shadercache::*;
+
 pub struct Cx {
     pub title: String,
     pub running: bool,
@@ -58,6 +71,8 @@ pub struct Cx {
     pub textures_free: Vec<usize>,
     pub shaders: Vec<CxShader>,
     pub shader_map: HashMap<ShaderGen, usize>,
+    // shaders swapped in at runtime that the platform still has to compile
+    pub shader_recompile_ids: Vec<usize>,
     
     pub is_in_redraw_cycle: bool,
     pub window_stack: Vec<usize>,
@@ -140,6 +155,7 @@ impl Default for Cx {
             textures_free: Vec::new(),
             shaders: Vec::new(),
             shader_map: HashMap::new(),
+            shader_recompile_ids: Vec::new(),
             
             is_in_redraw_cycle: false,
             window_stack: Vec::new(),
@@ -208,7 +224,8 @@ impl Cx {
                 name: name.to_string(),
                 shader_gen: sg,
                 platform: None,
-                mapping: CxShaderMapping::default()
+                mapping: CxShaderMapping::default(),
+                reloaded: false
             });
         }
         Shader {shader_id: Some(*store_id)}
@@ -678,67 +695,185 @@ impl Cx {
         }
     }
     
-    /*
-    pub fn debug_draw_tree_recur(&mut self, draw_list_id: usize, depth:usize){
-        if draw_list_id >= self.draw_lists.len(){
-            println!("---------- Drawlist still empty ---------");
-            return
-        }
+    // the draw calls under a view as text, with the instance props of the first instance of each call
+    // skip_view_id leaves out a view, like the one showing the tree
+    pub fn debug_draw_tree(&self, view_id: usize, skip_view_id: Option<usize>) -> String {
+        let mut out = format!("draw tree for redraw_id: {}\n", self.redraw_id);
+        self.debug_draw_tree_recur(view_id, skip_view_id, 0, &mut out);
+        out
+    }
+    
+    fn debug_draw_tree_recur(&self, view_id: usize, skip_view_id: Option<usize>, depth: usize, out: &mut String) {
         let mut indent = String::new();
-        for _i in 0..depth{
+        for _i in 0..depth {
             indent.push_str("  ");
         }
-        let draw_calls_len = self.draw_lists[draw_list_id].draw_calls_len;
-        if draw_list_id == 0{
-            println!("---------- Begin Debug draw tree for redraw_id: {} ---------", self.redraw_id)
+        if view_id >= self.views.len() {
+            out.push_str(&format!("{}view {}: still empty\n", indent, view_id));
+            return
         }
-        println!("{}list {}: len:{} rect:{:?}", indent, draw_list_id, draw_calls_len, self.draw_lists[draw_list_id].rect);  
+        let cxview = &self.views[view_id];
+        out.push_str(&format!("{}view {}: len:{} rect:{:?}\n", indent, view_id, cxview.draw_calls_len, cxview.rect));
         indent.push_str("  ");
-        for draw_call_id in 0..draw_calls_len{
-            let sub_list_id = self.draw_lists[draw_list_id].draw_calls[draw_call_id].sub_list_id;
-            if sub_list_id != 0{
-                self.debug_draw_tree_recur(sub_list_id, depth + 1);
+        for draw_call_id in 0..cxview.draw_calls_len {
+            let draw_call = &cxview.draw_calls[draw_call_id];
+            if draw_call.sub_view_id != 0 {
+                if Some(draw_call.sub_view_id) != skip_view_id {
+                    self.debug_draw_tree_recur(draw_call.sub_view_id, skip_view_id, depth + 1, out);
+                }
+                continue;
             }
-            else{
-                let draw_list = &mut self.draw_lists[draw_list_id];
-                let draw_call = &mut draw_list.draw_calls[draw_call_id];
-                let sh = &self.shaders[draw_call.shader_id];
-                let shc = &self.compiled_shaders[draw_call.shader_id];
-                let slots = shc.instance_slots;
-                let instances = draw_call.instance.len() / slots;
-                println!("{}call {}: {}({}) x:{}", indent, draw_call_id, sh.name, draw_call.shader_id, instances);  
-                // lets dump the instance geometry
-                for inst in 0..instances.min(1){
-                    let mut out = String::new();
-                    let mut off = 0;
-                    for prop in &shc.named_instance_props.props{
-                        match prop.slots{
-                            1=>out.push_str(&format!("{}:{} ", prop.name,
-                                draw_call.instance[inst*slots + off])),
-                            2=>out.push_str(&format!("{}:v2({},{}) ", prop.name,
-                                draw_call.instance[inst*slots+ off],
-                                draw_call.instance[inst*slots+1+ off])),
-                            3=>out.push_str(&format!("{}:v3({},{},{}) ", prop.name,
-                                draw_call.instance[inst*slots+ off],
-                                draw_call.instance[inst*slots+1+ off],
-                                draw_call.instance[inst*slots+1+ off])),
-                            4=>out.push_str(&format!("{}:v4({},{},{},{}) ", prop.name,
-                                draw_call.instance[inst*slots+ off],
-                                draw_call.instance[inst*slots+1+ off],
-                                draw_call.instance[inst*slots+2+ off],
-                                draw_call.instance[inst*slots+3+ off])),
-                            _=>{}
-                        }
-                        off += prop.slots;
-                    }
-                    println!("  {}instance {}: {}", indent, inst, out);  
+            let sh = &self.shaders[draw_call.shader_id];
+            let slots = sh.mapping.instance_slots;
+            let instances = if slots > 0 {draw_call.instance.len() / slots}else {0};
+            out.push_str(&format!("{}call {}: {}({}) x:{}\n", indent, draw_call_id, sh.name, draw_call.shader_id, instances));
+            if instances > 0 {
+                let area = Area::Instance(InstanceArea {
+                    view_id: view_id,
+                    draw_call_id: draw_call_id,
+                    instance_offset: 0,
+                    instance_count: 1,
+                    redraw_id: cxview.redraw_id
+                });
+                let props: Vec<String> = self.debug_instance_props(area).iter().map( | (name, value) | format!("{}:{}", name, value)).collect();
+                out.push_str(&format!("  {}instance 0: {}\n", indent, props.join(" ")));
+            }
+        }
+    }
+    
+    // the topmost instance under abs in a window, views are walked in the order they paint so the last hit wins
+    // instances are found by their x/y/w/h props, skip_view_id leaves out the view doing the picking
+    pub fn debug_pick_area(&self, window_id: usize, abs: Vec2, skip_view_id: Option<usize>) -> Area {
+        let mut found = Area::Empty;
+        if let Some(pass_id) = self.windows[window_id].main_pass_id {
+            if let Some(view_id) = self.passes[pass_id].main_view_id {
+                self.debug_pick_area_recur(view_id, abs, skip_view_id, &mut found);
+            }
+        }
+        found
+    }
+    
+    fn debug_pick_area_recur(&self, view_id: usize, abs: Vec2, skip_view_id: Option<usize>, found: &mut Area) {
+        if Some(view_id) == skip_view_id || view_id >= self.views.len() {
+            return
+        }
+        let cxview = &self.views[view_id];
+        for draw_call_id in 0..cxview.draw_calls_len {
+            let draw_call = &cxview.draw_calls[draw_call_id];
+            if draw_call.sub_view_id != 0 {
+                self.debug_pick_area_recur(draw_call.sub_view_id, abs, skip_view_id, found);
+                continue;
+            }
+            let slots = self.shaders[draw_call.shader_id].mapping.instance_slots;
+            if slots == 0 {
+                continue;
+            }
+            for inst in 0..draw_call.instance.len() / slots {
+                let area = Area::Instance(InstanceArea {
+                    view_id: view_id,
+                    draw_call_id: draw_call_id,
+                    instance_offset: inst * slots,
+                    instance_count: 1,
+                    redraw_id: cxview.redraw_id
+                });
+                if area.get_rect(self, false).contains(abs.x, abs.y) {
+                    *found = area;
                 }
             }
         }
-        if draw_list_id == 0{
-            println!("---------- End Debug draw tree for redraw_id: {} ---------", self.redraw_id)
+    }
+    
+    // what the debug overlay shows for an instance area
+    pub fn debug_instance(&self, area: Area) -> Option<DebugInstance> {
+        if !area.is_valid(self) {
+            return None
+        }
+        if let Area::Instance(inst) = area {
+            let draw_call = &self.views[inst.view_id].draw_calls[inst.draw_call_id];
+            let sh = &self.shaders[draw_call.shader_id];
+            let slots = sh.mapping.instance_slots.max(1);
+            return Some(DebugInstance {
+                shader_id: draw_call.shader_id,
+                shader_name: sh.name.clone(),
+                instance_index: inst.instance_offset / slots,
+                instance_total: draw_call.instance.len() / slots,
+                rect: area.get_rect(self, false),
+                props: self.debug_instance_props(area)
+            })
+        }
+        None
+    }
+    
+    // the named instance props of the first instance in the area, formatted
+    pub fn debug_instance_props(&self, area: Area) -> Vec<(String, String)> {
+        let mut props = Vec::new();
+        if let Area::Instance(inst) = area {
+            let draw_call = &self.views[inst.view_id].draw_calls[inst.draw_call_id];
+            let sh = &self.shaders[draw_call.shader_id];
+            for prop in &sh.mapping.named_instance_props.props {
+                let value = match prop.slots {
+                    1 => format!("{:.2}", area.read_float(self, &prop.name)),
+                    2 => {
+                        let v = area.read_vec2(self, &prop.name);
+                        format!("v2({:.2},{:.2})", v.x, v.y)
+                    },
+                    3 => {
+                        let v = area.read_vec3(self, &prop.name);
+                        format!("v3({:.2},{:.2},{:.2})", v.x, v.y, v.z)
+                    },
+                    4 => {
+                        let v = area.read_vec4(self, &prop.name);
+                        format!("v4({:.2},{:.2},{:.2},{:.2})", v.x, v.y, v.z, v.w)
+                    },
+                    _ => format!("{} slots", prop.slots)
+                };
+                props.push((prop.name.clone(), value));
+            }
         }
-    }*/
+        props
+    }
+    
+    // the ShaderGen of a shader with its pixel fn wrapped to tint it and outline its geometry bounds,
+    // swap it in with reload_shader and put the original back the same way
+    pub fn debug_highlight_shader_gen(&self, shader_id: usize) -> ShaderGen {
+        let mut sg = self.shaders[shader_id].shader_gen.clone();
+        if let Some(ast) = sg.asts.iter_mut().rev().find( | ast | ast.fns.iter().any( | shfn | shfn.name == "pixel")) {
+            if let Some(shfn) = ast.fns.iter_mut().find( | shfn | shfn.name == "pixel") {
+                shfn.name = "debug_base_pixel".to_string();
+            }
+        }
+        let has_geom = sg.find_var("geom").map_or(false, | var | var.ty == "vec2" && var.store == ShVarStore::Geometry);
+        if has_geom {
+            sg.compose(shader_ast!({
+                fn pixel() -> vec4 {
+                    let base: vec4 = debug_base_pixel();
+                    let d: vec2 = min(geom, vec2(1.0, 1.0) - geom) / fwidth(geom);
+                    let edge: float = 1.0 - clamp(min(d.x, d.y) - 1.0, 0.0, 1.0);
+                    let tint: vec4 = mix(vec4(0.15, 0.0, 0.15, 0.15), vec4(1.0, 0.0, 1.0, 1.0), edge);
+                    return tint + base * (1.0 - tint.a);
+                }
+            }))
+        }
+        else {
+            sg.compose(shader_ast!({
+                fn pixel() -> vec4 {
+                    let base: vec4 = debug_base_pixel();
+                    return vec4(0.15, 0.0, 0.15, 0.15) + base * 0.85;
+                }
+            }))
+        }
+    }
+}
+
+// an instance picked by Cx::debug_pick_area, see debug_instance
+#[derive(Clone, Default, Debug)]
+pub struct DebugInstance {
+    pub shader_id: usize,
+    pub shader_name: String,
+    pub instance_index: usize,
+    pub instance_total: usize,
+    pub rect: Rect,
+    pub props: Vec<(String, String)>
 }
 
 
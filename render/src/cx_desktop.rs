@@ -3,14 +3,20 @@ use std::io::prelude::*;
 use std::fs::File;
 use std::io;
 use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
 use time::precise_time_ns;
 
 #[derive(Clone)]
 pub struct CxDesktop {
     pub file_read_id: u64,
     pub file_read_requests: Vec<FileReadRequest>,
+    pub git_read_requests: Vec<FileReadRequest>,
+    // git runs on a thread per read, the results wait here until git_read_signal comes in
+    pub git_read_results: Arc<Mutex<Vec<FileReadEvent>>>,
+    pub git_read_signal: Signal,
     pub profiler_list: Vec<u64>,
-    pub profiler_totals: Vec<u64>
+    pub profiler_totals: Vec<u64>,
+    pub shader_cache: ShaderCache
 }
 
 impl Default for CxDesktop {
@@ -18,8 +24,12 @@ impl Default for CxDesktop {
         CxDesktop {
             file_read_id: 1,
             file_read_requests: Vec::new(),
+            git_read_requests: Vec::new(),
+            git_read_results: Arc::new(Mutex::new(Vec::new())),
+            git_read_signal: Signal::empty(),
             profiler_list: Vec::new(),
-            profiler_totals: Vec::new()
+            profiler_totals: Vec::new(),
+            shader_cache: ShaderCache::default()
         }
     }
 }
@@ -38,6 +48,22 @@ impl Cx {
         file_read_req
     }
     
+    // reads the file as it is committed at git HEAD, comes back as a FileRead event
+    pub fn read_git_head(&mut self, path: &str) -> FileReadRequest {
+        if self.platform.desktop.git_read_signal.signal_id == 0 {
+            self.platform.desktop.git_read_signal = self.new_signal();
+        }
+        let desktop = &mut self.platform.desktop;
+        desktop.file_read_id += 1;
+        let read_id = desktop.file_read_id;
+        let git_read_req = FileReadRequest {
+            read_id: read_id,
+            path: path.to_string()
+        };
+        desktop.git_read_requests.push(git_read_req.clone());
+        git_read_req
+    }
+    
     pub fn write_file(&mut self, path: &str, data: &[u8]) -> u64 {
         // just write it right now
         if let Ok(mut file) = File::create(path) {
@@ -86,6 +112,15 @@ impl Cx {
             Event::AppFocusLost => {
                 self.call_all_keys_up(&mut event_handler);
             },
+            Event::Signal(se) => {
+                // git reads that finished on their thread
+                if se.signal_id != 0 && se.signal_id == self.platform.desktop.git_read_signal.signal_id {
+                    let results = std::mem::replace(&mut *self.platform.desktop.git_read_results.lock().unwrap(), Vec::new());
+                    for fr in results {
+                        event_handler(self, &mut Event::FileRead(fr));
+                    }
+                }
+            },
             _ => ()
         };
     }
@@ -143,12 +178,14 @@ impl Cx {
     pub fn process_desktop_file_read_requests<F>(&mut self, mut event_handler: F)
     where F: FnMut(&mut Cx, &mut Event)
     {
-        if self.platform.desktop.file_read_requests.len() == 0 {
+        if self.platform.desktop.file_read_requests.len() == 0 && self.platform.desktop.git_read_requests.len() == 0 {
             return
         }
         
         let file_read_requests = self.platform.desktop.file_read_requests.clone();
         self.platform.desktop.file_read_requests.truncate(0);
+        let git_read_requests = self.platform.desktop.git_read_requests.clone();
+        self.platform.desktop.git_read_requests.truncate(0);
         
         for read_req in file_read_requests {
             let file_result = File::open(&read_req.path);
@@ -176,11 +213,42 @@ impl Cx {
             }
         }
         
-        if self.platform.desktop.file_read_requests.len() != 0 {
+        for read_req in git_read_requests {
+            let git_read_results = self.platform.desktop.git_read_results.clone();
+            let git_read_signal = self.platform.desktop.git_read_signal;
+            std::thread::spawn(move || {
+                let data = Self::read_git_head_sync(&read_req.path);
+                git_read_results.lock().unwrap().push(FileReadEvent {
+                    read_id: read_req.read_id,
+                    data: data
+                });
+                Cx::send_signal(git_read_signal, 0);
+            });
+        }
+        
+        if self.platform.desktop.file_read_requests.len() != 0 || self.platform.desktop.git_read_requests.len() != 0 {
             self.process_desktop_file_read_requests(event_handler);
         }
     }
     
+    fn read_git_head_sync(path: &str) -> Result<Vec<u8>, String> {
+        // run git in the directory of the file so it finds the right repository
+        let path_buf = std::path::Path::new(path);
+        let file_name = path_buf.file_name().and_then( | name | name.to_str()).unwrap_or("");
+        let dir = path_buf.parent().and_then( | dir | dir.to_str()).unwrap_or("");
+        let dir = if dir.len() == 0 {"."}else {dir};
+        let child = spawn_process_command("git", &["show", &format!("HEAD:./{}", file_name)], dir, false);
+        if let Ok(child) = child {
+            if let Ok(output) = child.wait_with_output() {
+                if output.status.success() {
+                    return Ok(output.stdout)
+                }
+            }
+            return Err(format!("File not in git HEAD {}", path))
+        }
+        Err(format!("Failed to run git for {}", path))
+    }
+    
     pub fn process_to_wasm<F>(&mut self, _msg: u32, mut _event_handler: F) -> u32 {
         0
     }
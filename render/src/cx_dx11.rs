@@ -38,8 +38,13 @@ impl Cx {
                     if draw_call.instance.len() == 0 {
                         continue;
                     }
-                    // update the instance buffer data
-                    draw_call.platform.inst_vbuf.update_with_f32_vertex_data(d3d11_cx, &draw_call.instance);
+                    // update the instance buffer data, minus the props the optimizer dropped
+                    if sh.mapping.instance_gather.len() > 0 {
+                        draw_call.platform.inst_vbuf.update_with_f32_vertex_data(d3d11_cx, &sh.mapping.gather_instances(&draw_call.instance));
+                    }
+                    else {
+                        draw_call.platform.inst_vbuf.update_with_f32_vertex_data(d3d11_cx, &draw_call.instance);
+                    }
                 }
                 if draw_call.uniforms_dirty {
                     draw_call.uniforms_dirty = false;
@@ -63,7 +68,7 @@ impl Cx {
                 
                 d3d11_cx.set_index_buffer(&shp.geom_ibuf);
                 
-                d3d11_cx.set_vertex_buffers(&shp.geom_vbuf, sh.mapping.geometry_slots, &draw_call.platform.inst_vbuf, sh.mapping.instance_slots);
+                d3d11_cx.set_vertex_buffers(&shp.geom_vbuf, sh.mapping.geometry_slots, &draw_call.platform.inst_vbuf, sh.mapping.gpu_instance_slots());
                 
                 d3d11_cx.set_constant_buffers(&self.platform.uni_cx, &cxview.platform.uni_dl, &draw_call.platform.uni_dr);
                 
@@ -220,6 +225,11 @@ impl Cx {
                             win32_app.stop_timer(timer_id);
                         }
                         
+                        // shaders reloaded at runtime
+                        if self.shader_recompile_ids.len() > 0 {
+                            self.hlsl_recompile_shaders(&d3d11_cx);
+                        }
+                        
                         // build a list of renderpasses to repaint
                         let mut windows_need_repaint = 0;
                         self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
@@ -472,6 +482,8 @@ pub struct D3d11Cx {
     pub device: ComPtr<d3d11::ID3D11Device>,
     pub context: ComPtr<d3d11::ID3D11DeviceContext>,
     pub factory: ComPtr<dxgi1_2::IDXGIFactory2>,
+    // adapter and driver version, compiled shaders are cached per driver
+    pub driver: String,
 //    pub d2d1_factory: ComPtr<d2d1::ID2D1Factory>
 }
 
@@ -482,10 +494,12 @@ impl D3d11Cx {
         let adapter = D3d11Cx::enum_adapters(&factory).expect("cannot enum_adapters");
         let (device, context) = D3d11Cx::create_d3d11_device(&adapter).expect("cannot create_d3d11_device");
        // let d2d1_factory = D3d11Cx::create_d2d1_factory().expect("cannot create_d2d1_factory");
+        let driver = D3d11Cx::adapter_driver(&adapter);
         D3d11Cx {
             device: device,
             context: context,
             factory: factory,
+            driver: driver,
         //    d2d1_factory: d2d1_factory
         }
     }
@@ -642,6 +656,23 @@ impl D3d11Cx {
         }
     }
     
+    pub fn create_blob(&self, data: &[u8]) -> Result<ComPtr<d3dcommon::ID3DBlob>, SlErr> {
+        let mut blob = ptr::null_mut();
+        let hr = unsafe {d3dcompiler::D3DCreateBlob(data.len(), &mut blob as *mut *mut _)};
+        if winerror::SUCCEEDED(hr) {
+            let blob = unsafe {ComPtr::<d3dcommon::ID3DBlob>::from_raw(blob)};
+            unsafe {ptr::copy_nonoverlapping(data.as_ptr(), blob.GetBufferPointer() as *mut u8, data.len())};
+            Ok(blob)
+        }
+        else {
+            Err(SlErr {msg: format!("create_blob failed {}", hr)})
+        }
+    }
+    
+    pub fn blob_data(blob: &ComPtr<d3dcommon::ID3DBlob>) -> Vec<u8> {
+        unsafe {std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()).to_vec()}
+    }
+    
     pub fn create_input_layout(&self, vs: &ComPtr<d3dcommon::ID3DBlob>, layout_desc: &Vec<d3d11::D3D11_INPUT_ELEMENT_DESC>)
         -> Result<ComPtr<d3d11::ID3D11InputLayout>, SlErr> {
         let mut input_layout = ptr::null_mut();
@@ -1113,6 +1144,17 @@ impl D3d11Cx {
     }
     
     
+    fn adapter_driver(adapter: &ComPtr<dxgi::IDXGIAdapter>) -> String {
+        let mut desc: dxgi::DXGI_ADAPTER_DESC = unsafe {mem::zeroed()};
+        unsafe {adapter.GetDesc(&mut desc)};
+        let len = desc.Description.iter().position( | c | *c == 0).unwrap_or(desc.Description.len());
+        let name = String::from_utf16_lossy(&desc.Description[0..len]);
+        let mut version: winapi::shared::ntdef::LARGE_INTEGER = unsafe {mem::zeroed()};
+        let hr = unsafe {adapter.CheckInterfaceSupport(&dxgi::IDXGIDevice::uuidof(), &mut version)};
+        let version = if winerror::SUCCEEDED(hr) {unsafe {*version.QuadPart()}}else {0};
+        format!("{} {:04x}:{:04x} driver {:x}", name, desc.VendorId, desc.DeviceId, version)
+    }
+    
     fn enum_adapters(factory: &ComPtr<dxgi1_2::IDXGIFactory2>)
         -> Result<ComPtr<dxgi::IDXGIAdapter>, winerror::HRESULT> {
         let mut adapter = ptr::null_mut();
@@ -1318,11 +1360,30 @@ pub struct CxPlatformPass {
 
 use std::process::{Command, Child, Stdio};
 
-pub fn spawn_process_command(cmd: &str, args: &[&str], current_dir: &str) -> Result<Child, std::io::Error> {
-    Command::new(cmd)
+// stdin is only piped for children we talk to (gdb, language servers)
+pub fn spawn_process_command(cmd: &str, args: &[&str], current_dir: &str, piped_stdin: bool) -> Result<Child, std::io::Error> {
+    let mut command = Command::new(cmd);
+    if piped_stdin {
+        command.stdin(Stdio::piped());
+    }
+    command
         .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .current_dir(current_dir)
         .spawn()
 }
+
+// a child running on the slave side of a pseudo terminal, the master is our end
+pub struct PtyChild {
+    pub child: Child,
+    pub master: std::fs::File
+}
+
+// there is no ConPTY binding here, the terminal panel shows this error in place of a shell
+pub fn spawn_pty_command(_cmd: &str, _args: &[&str], _current_dir: &str, _cols: u16, _rows: u16) -> Result<PtyChild, std::io::Error> {
+    Err(std::io::Error::new(std::io::ErrorKind::Other, "the terminal needs a pseudo terminal, which is not supported on windows"))
+}
+
+pub fn resize_pty(_master: &std::fs::File, _cols: u16, _rows: u16) {
+}
@@ -102,6 +102,19 @@ impl Cx {
         out
     }
     
+    pub fn gl_assemble_struct(shtype: &ShType) -> String {
+        let mut out = String::new();
+        out.push_str("struct ");
+        out.push_str(&shtype.name);
+        out.push_str("{\n");
+        for var in shtype.field_vars() {
+            out.push_str("  ");
+            out.push_str(&Self::gl_assemble_vardef(&var));
+        }
+        out.push_str("};\n");
+        out
+    }
+    
     pub fn gl_assemble_unpack(base: &str, slot: usize, total_slots: usize, sv: &ShVar) -> String {
         let mut out = String::new();
         // ok we have the slot we start at
@@ -293,8 +306,26 @@ impl Cx {
         out
     }
     
+    // the generator errors for reload_shader, before the shader replaces a working one
+    pub fn assemble_platform_shader(sh: &Shader) -> Result<(), SlErr> {
+        #[cfg(target_arch = "wasm32")]
+        Self::gl_assemble_shader(sh, GLShaderType::WebGL1) ?;
+        #[cfg(not(target_arch = "wasm32"))]
+        Self::gl_assemble_shader(sh, GLShaderType::OpenGLNoPartialDeriv) ?;
+        Ok(())
+    }
+    
     pub fn gl_assemble_shader(sh: &Shader, shtype: GLShaderType) -> Result<AssembledGLShader, SlErr> {
         
+        sh.type_check() ?;
+        // the instance attributes keep the layout the widgets write, so they come from the unoptimized shader
+        let instances = sh.flat_vars(ShVarStore::Instance);
+        let (opt_sh, opt_report) = sh.optimize() ?;
+        let sh = &opt_sh;
+        if sh.log != 0 {
+            println!("---- Shader optimizer -----\n{}", opt_report);
+        }
+        
         let mut vtx_out = String::new();
         let mut pix_out = String::new();
         let mut pix_compat = String::new();
@@ -329,7 +360,6 @@ impl Cx {
         // ok now define samplers from our sh.
         let texture_slots = sh.flat_vars(ShVarStore::Texture);
         let geometries = sh.flat_vars(ShVarStore::Geometry);
-        let instances = sh.flat_vars(ShVarStore::Instance);
         let mut varyings = sh.flat_vars(ShVarStore::Varying);
         let locals = sh.flat_vars(ShVarStore::Local);
         let uniforms_cx = sh.flat_vars(ShVarStore::UniformCx);
@@ -346,7 +376,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: Vec::new(),
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let consts = sh.flat_consts();
         let mut consts_out = String::new();
@@ -375,7 +406,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["vertex".to_string()],
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let vtx_fns = assemble_fn_and_deps(sh, &mut vtx_cx) ?;
         
@@ -389,7 +421,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["pixel".to_string()],
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let pix_fns = assemble_fn_and_deps(sh, &mut pix_cx) ?;
         
@@ -402,6 +435,8 @@ impl Cx {
         let instance_slots = sh.compute_slot_total(&instances);
         let varying_slots = sh.compute_slot_total(&varyings);
         let mut shared = String::new();
+        shared.push_str("// Structs\n");
+        for shtype in sh.flat_structs() {shared.push_str(&Self::gl_assemble_struct(&shtype));}
         shared.push_str("// Consts\n");
         shared.push_str(&consts_out);
         shared.push_str("//Context uniforms\n");
@@ -0,0 +1,355 @@
+use crate::cx::*;
+
+// GLSL 450 output of the shader compiler in the vulkan flavour, the vulkan backend in cx_vk
+// turns it into SPIR-V. it has the vertex buffer and binding layout of the wgsl target,
+// with the bind groups as descriptor sets.
+
+#[derive(Clone)]
+pub struct AssembledGlsl450Shader {
+    pub vertex: String,
+    pub fragment: String,
+    pub geometry_layout: WgslVertexLayout,
+    pub instance_layout: WgslVertexLayout,
+    pub bindings: Vec<WgslBinding>,
+    pub mapping: CxShaderMapping
+}
+
+impl Cx {
+    pub fn glsl450_type(ty: &str) -> String {
+        match ty.as_ref() {
+            "texture2d" => "texture2D".to_string(),
+            ty => ty.to_string()
+        }
+    }
+    
+    pub fn glsl450_assemble_bindings(bindings: &Vec<WgslBinding>) -> String {
+        let mut out = String::new();
+        for binding in bindings {
+            match binding.ty {
+                WgslBindingType::Uniform {size} => {
+                    out.push_str(&format!("layout(set={}, binding={}) uniform {}Block {{\n  vec4 slots[{}];\n}} {};\n", binding.group, binding.binding, binding.name, size / 16, binding.name));
+                },
+                WgslBindingType::Sampler => {
+                    out.push_str(&format!("layout(set={}, binding={}) uniform sampler {};\n", binding.group, binding.binding, binding.name));
+                },
+                WgslBindingType::Texture => {
+                    out.push_str(&format!("layout(set={}, binding={}) uniform texture2D {};\n", binding.group, binding.binding, binding.name));
+                }
+            }
+        }
+        out
+    }
+    
+    // geometry and instance attributes are globals prefixed with _geom_ and _inst_
+    pub fn glsl450_assemble_attributes(prefix: &str, layout: &WgslVertexLayout) -> String {
+        let mut out = String::new();
+        for attr in &layout.attributes {
+            out.push_str(&format!("layout(location={}) in {} {}{};\n", attr.location, attr.ty, prefix, attr.name));
+        }
+        out
+    }
+    
+    pub fn glsl450_assemble_varyings(inout: &str, varyings: &Vec<ShVar>) -> String {
+        let mut out = String::new();
+        let mut location = 0;
+        for vary in varyings {
+            out.push_str(&format!("layout(location={}) {} {} _vary_{};\n", location, inout, vary.ty, vary.name));
+            location += std::cmp::max(Self::wgsl_mat_columns(&vary.ty), 1);
+        }
+        out
+    }
+    
+    pub fn glsl450_assemble_shader(sg: &ShaderGen) -> Result<AssembledGlsl450Shader, SlErr> {
+        
+        sg.type_check() ?;
+        let (opt_sg, opt_report) = sg.optimize() ?;
+        // the mapping keeps the instance layout the widgets write, the gpu gets the optimized one
+        let mapping = CxShaderMapping::construct_optimized(sg, &opt_sg);
+        let sg = &opt_sg;
+        if sg.log != 0 {
+            println!("---- Shader optimizer -----\n{}", opt_report);
+        }
+        
+        let texture_slots = sg.flat_vars(ShVarStore::Texture);
+        let geometries = sg.flat_vars(ShVarStore::Geometry);
+        let instances = sg.flat_vars(ShVarStore::Instance);
+        let mut varyings = sg.flat_vars(ShVarStore::Varying);
+        let locals = sg.flat_vars(ShVarStore::Local);
+        let uniforms_cx = sg.flat_vars(ShVarStore::UniformCx);
+        let uniforms_vw = sg.flat_vars(ShVarStore::UniformVw);
+        let uniforms_dr = sg.flat_vars(ShVarStore::Uniform);
+        
+        let mut location = 0;
+        let geometry_layout = Self::wgsl_vertex_layout(sg, "_Geom", &geometries, WgslStepMode::Vertex, &mut location) ?;
+        let instance_layout = Self::wgsl_vertex_layout(sg, "_Inst", &instances, WgslStepMode::Instance, &mut location) ?;
+        let bindings = Self::wgsl_bindings(sg, &uniforms_cx, &uniforms_vw, &uniforms_dr, &texture_slots);
+        
+        // what both stages share
+        let mut shared = "#version 450\n\n".to_string();
+        shared.push_str(&Self::glsl450_assemble_bindings(&bindings));
+        shared.push_str("\n");
+        
+        let mut const_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Constant,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: Vec::new(),
+            fn_done: Vec::new(),
+            auto_vary: Vec::new(),
+            lang: SlLang::Glsl450
+        };
+        let consts = sg.flat_consts();
+        for cnst in &consts {
+            let const_init = assemble_const_init(cnst, &mut const_cx) ?;
+            shared.push_str(&format!("const {} {} = {};\n", Self::glsl450_type(&cnst.ty), cnst.name, const_init.sl));
+        }
+        
+        for shtype in sg.flat_structs() {
+            shared.push_str(&format!("\nstruct {} {{\n", shtype.name));
+            for field in &shtype.fields {
+                shared.push_str(&format!("  {} {};\n", Self::glsl450_type(&field.ty), field.name));
+            }
+            shared.push_str("};\n");
+        }
+        if locals.len() > 0 {
+            shared.push_str("\nstruct _Loc {\n");
+            for local in &locals {
+                shared.push_str(&format!("  {} {};\n", Self::glsl450_type(&local.ty), local.name));
+            }
+            shared.push_str("};\n_Loc _loc;\n");
+        }
+        
+        // the stages are separate modules, so each gets all the functions it calls
+        let mut vtx_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Vertex,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: vec!["vertex".to_string()],
+            fn_done: Vec::new(),
+            auto_vary: Vec::new(),
+            lang: SlLang::Glsl450
+        };
+        let vtx_fns = assemble_fn_and_deps(sg, &mut vtx_cx) ?;
+        let mut pix_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Pixel,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: vec!["pixel".to_string()],
+            fn_done: Vec::new(),
+            auto_vary: Vec::new(),
+            lang: SlLang::Glsl450
+        };
+        let pix_fns = assemble_fn_and_deps(sg, &mut pix_cx) ?;
+        
+        for auto in &pix_cx.auto_vary {
+            varyings.push(auto.clone());
+        }
+        
+        let mut vertex = shared.clone();
+        vertex.push_str("\n");
+        vertex.push_str(&Self::glsl450_assemble_attributes("_geom_", &geometry_layout));
+        vertex.push_str(&Self::glsl450_assemble_attributes("_inst_", &instance_layout));
+        vertex.push_str(&Self::glsl450_assemble_varyings("out", &varyings));
+        vertex.push_str("\n//Vertex shader\n");
+        vertex.push_str(&vtx_fns);
+        vertex.push_str("\nvoid main() {\n");
+        vertex.push_str("  gl_Position = _vertex();\n");
+        // vulkan clip space has y pointing down
+        vertex.push_str("  gl_Position.y = -gl_Position.y;\n");
+        for auto in &pix_cx.auto_vary {
+            vertex.push_str(&format!("  _vary_{} = {};\n", auto.name, vtx_cx.sl_map_var(auto)));
+        }
+        vertex.push_str("}\n");
+        
+        let mut fragment = shared;
+        fragment.push_str("\n");
+        fragment.push_str(&Self::glsl450_assemble_varyings("in", &varyings));
+        fragment.push_str("layout(location=0) out vec4 _frag_color;\n");
+        fragment.push_str("\n//Pixel shader\n");
+        fragment.push_str(&pix_fns);
+        fragment.push_str("\nvoid main() {\n");
+        fragment.push_str("  _frag_color = _pixel();\n");
+        fragment.push_str("}\n");
+        
+        if sg.log != 0 {
+            println!("---- GLSL 450 vertex shader -----\n{}", vertex);
+            println!("---- GLSL 450 fragment shader -----\n{}", fragment);
+        }
+        
+        Ok(AssembledGlsl450Shader {
+            vertex: vertex,
+            fragment: fragment,
+            geometry_layout: geometry_layout,
+            instance_layout: instance_layout,
+            bindings: bindings,
+            mapping: mapping
+        })
+    }
+}
+
+impl<'a> SlCx<'a> {
+    pub fn glsl450_map_call(&self, name: &str, args: &Vec<Sl>) -> MapCallResult {
+        match name {
+            "sample2d" => {
+                let base = &args[0];
+                let coord = &args[1];
+                // only the pixel stage has derivatives for picking a mip
+                if let SlTarget::Pixel = self.target {
+                    return MapCallResult::Rewrite(
+                        format!("texture(sampler2D({}, _sampler), {})", base.sl, coord.sl),
+                        "vec4".to_string()
+                    )
+                }
+                return MapCallResult::Rewrite(
+                    format!("textureLod(sampler2D({}, _sampler), {}, 0.0)", base.sl, coord.sl),
+                    "vec4".to_string()
+                )
+            },
+            "color" => {
+                let col = color(&args[0].sl);
+                return MapCallResult::Rewrite(
+                    format!("vec4({},{},{},{})", col.r, col.g, col.b, col.a),
+                    "vec4".to_string()
+                );
+            },
+            "dfdx" => return MapCallResult::Rename("dFdx".to_string()),
+            "dfdy" => return MapCallResult::Rename("dFdy".to_string()),
+            "matrix_comp_mult" => return MapCallResult::Rename("matrixCompMult".to_string()),
+            // glsl mod floors, fmod truncates like it does in metal and hlsl
+            "fmod" => {
+                return MapCallResult::Rewrite(
+                    format!("({0} - {1} * trunc({0} / {1}))", args[0].sl, args[1].sl),
+                    args[0].ty.clone()
+                )
+            },
+            "less_than" | "less_than_equal" | "greater_than" | "greater_than_equal" | "equal" | "not_equal" => {
+                // the glsl functions only take vectors
+                if args[0].ty == "float" || args[0].ty == "int" {
+                    let op = match name {
+                        "less_than" => "<",
+                        "less_than_equal" => "<=",
+                        "greater_than" => ">",
+                        "greater_than_equal" => ">=",
+                        "equal" => "==",
+                        _ => "!="
+                    };
+                    return MapCallResult::Rewrite(
+                        format!("({} {} {})", args[0].sl, op, args[1].sl),
+                        "bool".to_string()
+                    )
+                }
+                return MapCallResult::Rename(match name {
+                    "less_than" => "lessThan",
+                    "less_than_equal" => "lessThanEqual",
+                    "greater_than" => "greaterThan",
+                    "greater_than_equal" => "greaterThanEqual",
+                    "equal" => "equal",
+                    _ => "notEqual"
+                }.to_string())
+            },
+            "not" => if args[0].ty == "bool" {
+                return MapCallResult::Rewrite(
+                    format!("!({})", args[0].sl),
+                    "bool".to_string()
+                )
+            },
+            _ => ()
+        }
+        MapCallResult::None
+    }
+    
+    pub fn glsl450_mat_mul(&self, left: &str, right: &str) -> String {
+        format!("{}*{}", left, right)
+    }
+    
+    pub fn glsl450_map_type(&self, ty: &str) -> String {
+        Cx::glsl450_type(ty)
+    }
+    
+    pub fn glsl450_map_var(&mut self, var: &ShVar) -> String {
+        let uniforms = match var.store {
+            ShVarStore::Uniform => Some(("_uni_dr", ShVarStore::Uniform)),
+            ShVarStore::UniformVw => Some(("_uni_vw", ShVarStore::UniformVw)),
+            ShVarStore::UniformCx => Some(("_uni_cx", ShVarStore::UniformCx)),
+            _ => None
+        };
+        if let Some((base, store)) = uniforms {
+            let props = NamedProps::construct(self.shader_gen, &self.shader_gen.flat_vars(store), true);
+            if let Some(prop) = props.props.iter().find( | prop | prop.name == var.name) {
+                return Cx::slot_uniform_read(base, &var.ty, prop.offset, prop.slots, Cx::glsl450_type)
+            }
+        }
+        match var.store {
+            ShVarStore::Instance | ShVarStore::Geometry => {
+                if let SlTarget::Pixel = self.target {
+                    if self.auto_vary.iter().find( | v | v.name == var.name).is_none() {
+                        self.auto_vary.push(var.clone());
+                    }
+                    return format!("_vary_{}", var.name);
+                }
+                let prefix = if let ShVarStore::Geometry = var.store {"_geom_"}else {"_inst_"};
+                let cols = Cx::wgsl_mat_columns(&var.ty);
+                if cols > 0 {
+                    let cols: Vec<String> = (0..cols).map( | i | format!("{}{}_{}", prefix, var.name, i)).collect();
+                    return format!("{}({})", var.ty, cols.join(", "))
+                }
+                return format!("{}{}", prefix, var.name)
+            },
+            ShVarStore::Texture => return format!("_tex_{}", var.name),
+            ShVarStore::Local => return format!("_loc.{}", var.name),
+            ShVarStore::Varying => return format!("_vary_{}", var.name),
+            _ => return var.name.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::path::Path;
+    
+    // compares with render/tests/glsl450/<name>.vert and .frag, SHADER_BLESS=1 writes the new output there instead
+    fn assert_golden(name: &str, sg: ShaderGen) {
+        let ash = Cx::glsl450_assemble_shader(&sg).unwrap_or_else( | err | panic!("{} does not assemble: {}", name, err.msg));
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("glsl450");
+        for (ext, source) in &[("vert", &ash.vertex), ("frag", &ash.fragment)] {
+            let path = dir.join(format!("{}.{}", name, ext));
+            if std::env::var("SHADER_BLESS").is_ok() {
+                std::fs::create_dir_all(&dir).unwrap();
+                std::fs::write(&path, source).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&path).unwrap_or_else( | _ | panic!("missing {}, run with SHADER_BLESS=1", path.display()));
+            if **source != expected {
+                panic!("{} differs from {}, run with SHADER_BLESS=1 if the change is intended\n{}", name, path.display(), source);
+            }
+        }
+    }
+    
+    #[test]
+    fn quad_matches_golden() {
+        assert_golden("quad", Quad::def_quad_shader());
+    }
+    
+    #[test]
+    fn text_matches_golden() {
+        assert_golden("text", Text::def_text_shader());
+    }
+    
+    #[test]
+    fn blit_matches_golden() {
+        assert_golden("blit", Blit::def_blit_shader());
+    }
+}
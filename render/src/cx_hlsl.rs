@@ -18,12 +18,32 @@ pub struct CxPlatformShader {
 
 impl Cx {
     pub fn hlsl_compile_all_shaders(&mut self, d3d11_cx: &D3d11Cx) {
+        self.platform.desktop.shader_cache = ShaderCache::new("dx11", &d3d11_cx.driver);
+        self.profile_begin(SHADER_COMPILE_PROFILE_ID);
         for sh in &mut self.shaders {
-            let err = Self::hlsl_compile_shader(sh, d3d11_cx);
+            let err = Self::hlsl_compile_shader(sh, d3d11_cx, &mut self.platform.desktop.shader_cache);
             if let Err(err) = err {
                 panic!("Got hlsl shader compile error: {}", err.msg);
             }
         };
+        self.profile_end(SHADER_COMPILE_PROFILE_ID);
+        self.shader_cache_report();
+    }
+    
+    pub fn hlsl_recompile_shaders(&mut self, d3d11_cx: &D3d11Cx) {
+        for shader_id in std::mem::replace(&mut self.shader_recompile_ids, Vec::new()) {
+            let sh = &mut self.shaders[shader_id];
+            // a failed compile keeps drawing with the previous version
+            if let Err(err) = Self::hlsl_compile_shader(sh, d3d11_cx, &mut self.platform.desktop.shader_cache) {
+                println!("Got hlsl shader recompile error in {}: {}", sh.name, err.msg);
+            }
+        }
+    }
+    
+    // the generator errors for reload_shader, before the shader replaces a working one
+    pub fn assemble_platform_shader(sg: &ShaderGen) -> Result<(), SlErr> {
+        Self::hlsl_assemble_shader(sg) ?;
+        Ok(())
     }
     
     pub fn hlsl_type(ty: &str) -> String {
@@ -69,12 +89,14 @@ impl Cx {
         out.push_str("{\n");
         out.push_str(field);
         for var in vars {
-            out.push_str(match Self::hlsl_type(&var.ty).as_ref() {
-                "float" => "0.0",
-                "float2" => "float2(0.0,0.0)",
-                "float3" => "float3(0.0,0.0,0.0)",
-                "float4" => "float4(0.0,0.0,0.0,0.0)",
-                _ => "",
+            let ty = Self::hlsl_type(&var.ty);
+            out.push_str(&match ty.as_ref() {
+                "float" => "0.0".to_string(),
+                "float2" => "float2(0.0,0.0)".to_string(),
+                "float3" => "float3(0.0,0.0,0.0)".to_string(),
+                "float4" => "float4(0.0,0.0,0.0,0.0)".to_string(),
+                // matrices and structs from shader_ast! zero out with a cast
+                _ => format!("({})0", ty),
             });
             out.push_str(",")
         };
@@ -82,6 +104,25 @@ impl Cx {
         out
     }
     
+    // hlsl has no struct constructors, so every struct gets a function that fills one in
+    pub fn hlsl_assemble_struct_cons(shtype: &ShType) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("{} _{}_cons(", shtype.name, shtype.name));
+        for (i, field) in shtype.fields.iter().enumerate() {
+            if i != 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&format!("{} {}", Self::hlsl_type(&field.ty), field.name));
+        }
+        out.push_str("){\n");
+        out.push_str(&format!("  {} r;\n", shtype.name));
+        for field in &shtype.fields {
+            out.push_str(&format!("  r.{} = {};\n", field.name, field.name));
+        }
+        out.push_str("  return r;\n}\n\n");
+        out
+    }
+    
     pub fn hlsl_assemble_texture_slots(textures: &Vec<ShVar>) -> String {
         let mut out = String::new();
         for (i, tex) in textures.iter().enumerate() {
@@ -94,6 +135,15 @@ impl Cx {
     
     pub fn hlsl_assemble_shader(sg: &ShaderGen) -> Result<(String, CxShaderMapping), SlErr> {
         
+        sg.type_check() ?;
+        let (opt_sg, opt_report) = sg.optimize() ?;
+        // the mapping keeps the instance layout the widgets write, the gpu gets the optimized one
+        let mapping = CxShaderMapping::construct_optimized(sg, &opt_sg);
+        let sg = &opt_sg;
+        if sg.log != 0 {
+            println!("---- Shader optimizer -----\n{}", opt_report);
+        }
+        
         let mut hlsl_out = String::new();
         
         hlsl_out.push_str("SamplerState DefaultTextureSampler{Filter = MIN_MAG_MIP_LINEAR;AddressU = Wrap;AddressV=Wrap;};\n");
@@ -108,12 +158,12 @@ impl Cx {
         let uniforms_vw = sg.flat_vars(ShVarStore::UniformVw);
         let uniforms_dr = sg.flat_vars(ShVarStore::Uniform);
         
-        // lets count the slots
-        let geometry_slots = sg.compute_slot_total(&geometries);
-        let instance_slots = sg.compute_slot_total(&instances);
-        //let varying_slots = sh.compute_slot_total(&varyings);
         hlsl_out.push_str(&Self::hlsl_assemble_texture_slots(&texture_slots));
         
+        for shtype in sg.flat_structs() {
+            hlsl_out.push_str(&Self::hlsl_assemble_struct("struct", &shtype.name, &shtype.field_vars(), "", "", ""));
+            hlsl_out.push_str(&Self::hlsl_assemble_struct_cons(&shtype));
+        }
         hlsl_out.push_str(&Self::hlsl_assemble_struct("struct", "_Geom", &geometries, "GEOM_", "", ""));
         hlsl_out.push_str(&Self::hlsl_assemble_struct("struct", "_Inst", &instances, "INST_", "", ""));
         hlsl_out.push_str(&Self::hlsl_assemble_struct("cbuffer", "_Uni_Cx", &uniforms_cx, "", "", ": register(b0)"));
@@ -135,7 +185,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: Vec::new(),
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let consts = sg.flat_consts();
         for cnst in &consts {
@@ -158,7 +209,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["vertex".to_string()],
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         
         let vtx_fns = assemble_fn_and_deps(sg, &mut vtx_cx) ?;
@@ -172,7 +224,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["pixel".to_string()],
             fn_done: vtx_cx.fn_done,
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         
         let pix_fns = assemble_fn_and_deps(sg, &mut pix_cx) ?;
@@ -236,19 +289,7 @@ impl Cx {
             }
         }
         
-        Ok((hlsl_out, CxShaderMapping {
-            rect_instance_props: RectInstanceProps::construct(sg, &instances),
-            named_instance_props: NamedProps::construct(sg, &instances, false),
-            named_uniform_props: NamedProps::construct(sg, &uniforms_dr, true),
-            geometries: geometries,
-            instances: instances,
-            geometry_slots: geometry_slots,
-            instance_slots: instance_slots,
-            uniforms_dr: uniforms_dr,
-            uniforms_vw: uniforms_vw,
-            uniforms_cx: uniforms_cx,
-            texture_slots: texture_slots,
-        }))
+        Ok((hlsl_out, mapping))
     }
     
     fn slots_to_dxgi_format(slots: usize) -> u32 {
@@ -261,18 +302,37 @@ impl Cx {
         }
     }
     
-    pub fn hlsl_compile_shader(sh: &mut CxShader, d3d11_cx: &D3d11Cx) -> Result<(), SlErr> {
-        let (hlsl, mapping) = Self::hlsl_assemble_shader(&sh.shader_gen) ?;
-        
-        let vs_blob = d3d11_cx.compile_shader("vs", "_vertex_shader".as_bytes(), hlsl.as_bytes()) ?;
-        let ps_blob = d3d11_cx.compile_shader("ps", "_pixel_shader".as_bytes(), hlsl.as_bytes()) ?;
+    pub fn hlsl_compile_shader(sh: &mut CxShader, d3d11_cx: &D3d11Cx, cache: &mut ShaderCache) -> Result<(), SlErr> {
+        // a cache hit skips both generating the hlsl and D3DCompile
+        let cached = cache.load(&sh.shader_gen);
+        let (vs_blob, ps_blob, mapping) = match cached.as_ref().map( | entry | (entry.binary("vs"), entry.binary("ps"), &entry.mapping)) {
+            Some((Some(vs_data), Some(ps_data), mapping)) => (
+                d3d11_cx.create_blob(vs_data) ?,
+                d3d11_cx.create_blob(ps_data) ?,
+                mapping.clone()
+            ),
+            _ => {
+                let (hlsl, mapping) = Self::hlsl_assemble_shader(&sh.shader_gen) ?;
+                let vs_blob = d3d11_cx.compile_shader("vs", "_vertex_shader".as_bytes(), hlsl.as_bytes()) ?;
+                let ps_blob = d3d11_cx.compile_shader("ps", "_pixel_shader".as_bytes(), hlsl.as_bytes()) ?;
+                cache.store(&sh.shader_gen, &ShaderCacheEntry {
+                    source: hlsl,
+                    mapping: mapping.clone(),
+                    binaries: vec![
+                        ("vs".to_string(), D3d11Cx::blob_data(&vs_blob)),
+                        ("ps".to_string(), D3d11Cx::blob_data(&ps_blob))
+                    ]
+                });
+                (vs_blob, ps_blob, mapping)
+            }
+        };
         
         let vs = d3d11_cx.create_vertex_shader(&vs_blob) ?;
         let ps = d3d11_cx.create_pixel_shader(&ps_blob) ?;
         
         let mut layout_desc = Vec::new();
         let geom_named = NamedProps::construct(&sh.shader_gen, &mapping.geometries, false);
-        let inst_named = NamedProps::construct(&sh.shader_gen, &mapping.instances, false);
+        let inst_named = NamedProps::construct(&sh.shader_gen, &mapping.gpu_instances, false);
         let mut strings = Vec::new();
         
         for geom in &geom_named.props {
@@ -354,7 +414,15 @@ impl<'a> SlCx<'a> {
             "dfdy" => {
                 return MapCallResult::Rename("ddy".to_string())
             },
-            _ => return MapCallResult::None
+            _ => {
+                // structs from shader_ast! go through their constructor fn
+                if let Some(shtype) = self.shader_gen.find_type(name) {
+                    if !shtype.prim {
+                        return MapCallResult::Rename(format!("_{}_cons", name))
+                    }
+                }
+                return MapCallResult::None
+            }
         }
     }
     
@@ -31,8 +31,13 @@ impl Cx {
                 
                 if draw_call.instance_dirty {
                     draw_call.instance_dirty = false;
-                    // update the instance buffer data
-                    draw_call.platform.inst_vbuf.update_with_f32_data(metal_cx, &draw_call.instance);
+                    // update the instance buffer data, minus the props the optimizer dropped
+                    if sh.mapping.instance_gather.len() > 0 {
+                        draw_call.platform.inst_vbuf.update_with_f32_data(metal_cx, &sh.mapping.gather_instances(&draw_call.instance));
+                    }
+                    else {
+                        draw_call.platform.inst_vbuf.update_with_f32_data(metal_cx, &draw_call.instance);
+                    }
                 }
                 if draw_call.uniforms_dirty {
                     draw_call.uniforms_dirty = false;
@@ -285,6 +290,11 @@ impl Cx {
                             cocoa_app.stop_timer(timer_id);
                         }
                         
+                        // shaders reloaded at runtime
+                        if self.shader_recompile_ids.len() > 0 {
+                            self.mtl_recompile_shaders(&metal_cx);
+                        }
+                        
                         // build a list of renderpasses to repaint
                         let mut windows_need_repaint = 0;
                         self.compute_passes_to_repaint(&mut passes_todo, &mut windows_need_repaint);
@@ -705,6 +715,60 @@ use closefds::*;
 use std::process::{Command, Child, Stdio};
 use std::os::unix::process::{CommandExt};
 
-pub fn spawn_process_command(cmd: &str, args: &[&str], current_dir: &str) -> Result<Child, std::io::Error> {
-    unsafe {Command::new(cmd) .args(args) .pre_exec(close_fds_on_exec(vec![0, 1, 2]).unwrap()) .stdout(Stdio::piped()) .stderr(Stdio::piped()) .current_dir(current_dir) .spawn()}
+// stdin is only piped for children we talk to (gdb, language servers)
+pub fn spawn_process_command(cmd: &str, args: &[&str], current_dir: &str, piped_stdin: bool) -> Result<Child, std::io::Error> {
+    let mut command = Command::new(cmd);
+    if piped_stdin {
+        command.stdin(Stdio::piped());
+    }
+    unsafe {command .args(args) .pre_exec(close_fds_on_exec(vec![0, 1, 2]).unwrap()) .stdout(Stdio::piped()) .stderr(Stdio::piped()) .current_dir(current_dir) .spawn()}
+}
+
+// a child running on the slave side of a pseudo terminal, the master is our end
+pub struct PtyChild {
+    pub child: Child,
+    pub master: std::fs::File
+}
+
+pub fn spawn_pty_command(cmd: &str, args: &[&str], current_dir: &str, cols: u16, rows: u16) -> Result<PtyChild, std::io::Error> {
+    use std::os::unix::io::FromRawFd;
+    let mut master: libc::c_int = 0;
+    let mut slave: libc::c_int = 0;
+    let mut size = libc::winsize {ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0};
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null_mut(), &mut size) != 0 {
+            return Err(std::io::Error::last_os_error())
+        }
+        let child = Command::new(cmd)
+            .args(args)
+            .env("TERM", "xterm-256color")
+            .current_dir(current_dir)
+            .stdin(Stdio::from_raw_fd(slave))
+            .stdout(Stdio::from_raw_fd(libc::dup(slave)))
+            .stderr(Stdio::from_raw_fd(libc::dup(slave)))
+            .pre_exec(move || {
+                // a new session with the pty as its controlling terminal
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(std::io::Error::last_os_error())
+                }
+                libc::close(master);
+                Ok(())
+            })
+            .spawn();
+        match child {
+            Ok(child) => Ok(PtyChild {child: child, master: std::fs::File::from_raw_fd(master)}),
+            Err(err) => {
+                libc::close(master);
+                Err(err)
+            }
+        }
+    }
+}
+
+pub fn resize_pty(master: &std::fs::File, cols: u16, rows: u16) {
+    use std::os::unix::io::AsRawFd;
+    let size = libc::winsize {ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0};
+    unsafe {
+        libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+    }
 }
@@ -1,6 +1,9 @@
 
 use metal::*;
 use crate::cx::*;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
 
 #[derive(Clone)]
 pub struct CxPlatformShader {
@@ -21,13 +24,35 @@ pub enum PackType {
 
 impl Cx {
     pub fn mtl_compile_all_shaders(&mut self, metal_cx: &MetalCx) {
+        // the metal compiler comes with the os, so a metallib is only good for the os build it was made on
+        let driver = format!("{} macos {}", metal_cx.device.name(), Self::mtl_os_build());
+        self.platform.desktop.shader_cache = ShaderCache::new("metal", &driver);
+        self.profile_begin(SHADER_COMPILE_PROFILE_ID);
         for sh in &mut self.shaders {
-            let mtlsh = Self::mtl_compile_shader(sh, metal_cx);
+            let mtlsh = Self::mtl_compile_shader(sh, metal_cx, &mut self.platform.desktop.shader_cache);
             if let Err(err) = mtlsh {
                 panic!("Got metal shader compile error: {}", err.msg);
             }
         };
+        self.profile_end(SHADER_COMPILE_PROFILE_ID);
+        self.shader_cache_report();
     }
+    pub fn mtl_recompile_shaders(&mut self, metal_cx: &MetalCx) {
+        for shader_id in std::mem::replace(&mut self.shader_recompile_ids, Vec::new()) {
+            let sh = &mut self.shaders[shader_id];
+            // a failed compile keeps drawing with the previous version
+            if let Err(err) = Self::mtl_compile_shader(sh, metal_cx, &mut self.platform.desktop.shader_cache) {
+                println!("Got metal shader recompile error in {}: {}", sh.name, err.msg);
+            }
+        }
+    }
+    
+    // the generator errors for reload_shader, before the shader replaces a working one
+    pub fn assemble_platform_shader(sg: &ShaderGen) -> Result<(), SlErr> {
+        Self::mtl_assemble_shader(sg) ?;
+        Ok(())
+    }
+    
     pub fn mtl_type_to_packed_metal(ty: &str) -> String {
         match ty.as_ref() {
             "float" => "float".to_string(),
@@ -98,6 +123,15 @@ impl Cx {
     
     pub fn mtl_assemble_shader(sg: &ShaderGen) -> Result<(String, CxShaderMapping), SlErr> {
         
+        sg.type_check() ?;
+        let (opt_sg, opt_report) = sg.optimize() ?;
+        // the mapping keeps the instance layout the widgets write, the gpu gets the optimized one
+        let mapping = CxShaderMapping::construct_optimized(sg, &opt_sg);
+        let sg = &opt_sg;
+        if sg.log != 0 {
+            println!("---- Shader optimizer -----\n{}", opt_report);
+        }
+        
         let mut mtl_out = "#include <metal_stdlib>\nusing namespace metal;\n".to_string();
         
         // ok now define samplers from our sh.
@@ -110,11 +144,9 @@ impl Cx {
         let uniforms_vw = sg.flat_vars(ShVarStore::UniformVw);
         let uniforms_dr = sg.flat_vars(ShVarStore::Uniform);
         
-        // lets count the slots
-        let geometry_slots = sg.compute_slot_total(&geometries);
-        let instance_slots = sg.compute_slot_total(&instances);
-        //let varying_slots = sh.compute_slot_total(&varyings);
-        
+        for shtype in sg.flat_structs() {
+            mtl_out.push_str(&Self::mtl_assemble_struct(&shtype.name, &shtype.field_vars(), PackType::Unpacked, ""));
+        }
         mtl_out.push_str(&Self::mtl_assemble_struct("_Geom", &geometries, PackType::Packed, ""));
         mtl_out.push_str(&Self::mtl_assemble_struct("_Inst", &instances, PackType::Packed, ""));
         mtl_out.push_str(&Self::mtl_assemble_struct("_UniCx", &uniforms_cx, PackType::Unpacked, ""));
@@ -137,7 +169,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: Vec::new(),
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let consts = sg.flat_consts();
         for cnst in &consts {
@@ -160,7 +193,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["vertex".to_string()],
             fn_done: Vec::new(),
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         let vtx_fns = assemble_fn_and_deps(sg, &mut vtx_cx) ?;
         let mut pix_cx = SlCx {
@@ -173,7 +207,8 @@ impl Cx {
             scope: Vec::new(),
             fn_deps: vec!["pixel".to_string()],
             fn_done: vtx_cx.fn_done,
-            auto_vary: Vec::new()
+            auto_vary: Vec::new(),
+            lang: SlLang::Platform
         };
         
         let pix_fns = assemble_fn_and_deps(sg, &mut pix_cx) ?;
@@ -232,26 +267,67 @@ impl Cx {
             println!("---- Metal shader -----\n{}", mtl_out);
         }
         
-        Ok((mtl_out, CxShaderMapping {
-            rect_instance_props: RectInstanceProps::construct(sg, &instances),
-            named_instance_props: NamedProps::construct(sg, &instances, false),
-            named_uniform_props: NamedProps::construct(sg, &uniforms_dr, true),
-            instances: instances,
-            geometries: geometries,
-            instance_slots: instance_slots,
-            geometry_slots: geometry_slots,
-            uniforms_dr: uniforms_dr,
-            uniforms_vw: uniforms_vw,
-            uniforms_cx: uniforms_cx,
-            texture_slots: texture_slots,
-        }))
+        Ok((mtl_out, mapping))
     }
     
-    pub fn mtl_compile_shader(sh: &mut CxShader, metal_cx: &MetalCx) -> Result<(), SlErr> {
-        let (mtlsl, mapping) = Self::mtl_assemble_shader(&sh.shader_gen) ?;
-        
+    // the os build from SystemVersion.plist
+    fn mtl_os_build() -> String {
+        let plist = fs::read_to_string("/System/Library/CoreServices/SystemVersion.plist").unwrap_or(String::new());
+        plist.split("<key>ProductBuildVersion</key>").nth(1)
+            .and_then( | rest | rest.split("<string>").nth(1))
+            .and_then( | rest | rest.split("</string>").next())
+            .unwrap_or("unknown")
+            .to_string()
+    }
+    
+    // compiles the source to a metallib with the command line tools, None when they are not installed
+    fn mtl_build_metallib(mtlsl: &str, cache: &ShaderCache, sg: &ShaderGen) -> Option<Vec<u8>> {
+        if cache.dir.len() == 0 {
+            return None
+        }
+        let base = PathBuf::from(&cache.dir).join(format!("metal_{:016x}", cache.key(sg)));
+        let (metal, air, metallib) = (base.with_extension("metal"), base.with_extension("air"), base.with_extension("metallib"));
+        let built = fs::create_dir_all(&cache.dir).and_then( | _ | fs::write(&metal, mtlsl)).is_ok()
+            && Command::new("xcrun").args(&["-sdk", "macosx", "metal", "-c"]).arg(&metal).arg("-o").arg(&air).status().map( | s | s.success()).unwrap_or(false)
+            && Command::new("xcrun").args(&["-sdk", "macosx", "metallib"]).arg(&air).arg("-o").arg(&metallib).status().map( | s | s.success()).unwrap_or(false);
+        let data = if built {fs::read(&metallib).ok()}else {None};
+        for path in &[metal, air, metallib] {
+            let _ = fs::remove_file(path);
+        }
+        data
+    }
+    
+    pub fn mtl_compile_shader(sh: &mut CxShader, metal_cx: &MetalCx, cache: &mut ShaderCache) -> Result<(), SlErr> {
+        // a hit loads the stored metallib, or compiles the stored source when there was no metallib
         let options = CompileOptions::new();
-        let library = metal_cx.device.new_library_with_source(&mtlsl, &options);
+        let (library, mapping) = match cache.load(&sh.shader_gen) {
+            Some(entry) => {
+                let library = match entry.binary("metallib").map( | data | metal_cx.device.new_library_with_data(data)) {
+                    Some(Ok(library)) => Ok(library),
+                    _ => metal_cx.device.new_library_with_source(&entry.source, &options)
+                };
+                (library, entry.mapping)
+            },
+            None => {
+                let (mtlsl, mapping) = Self::mtl_assemble_shader(&sh.shader_gen) ?;
+                let mut binaries = Vec::new();
+                let library = match Self::mtl_build_metallib(&mtlsl, cache, &sh.shader_gen).map( | data | (metal_cx.device.new_library_with_data(&data), data)) {
+                    Some((Ok(library), data)) => {
+                        binaries.push(("metallib".to_string(), data));
+                        Ok(library)
+                    },
+                    _ => metal_cx.device.new_library_with_source(&mtlsl, &options)
+                };
+                if library.is_ok() {
+                    cache.store(&sh.shader_gen, &ShaderCacheEntry {
+                        source: mtlsl,
+                        mapping: mapping.clone(),
+                        binaries: binaries
+                    });
+                }
+                (library, mapping)
+            }
+        };
         
         match library {
             Err(library) => return Err(SlErr {msg: library}),
@@ -313,7 +389,19 @@ impl<'a> SlCx<'a> {
                     "vec4".to_string()
                 );
             },
-            _ => return MapCallResult::None
+            _ => {
+                // structs from shader_ast! use aggregate init
+                if let Some(shtype) = self.shader_gen.find_type(name) {
+                    if !shtype.prim {
+                        let args: Vec<String> = args.iter().map( | arg | arg.sl.clone()).collect();
+                        return MapCallResult::Rewrite(
+                            format!("{}{{{}}}", name, args.join(", ")),
+                            name.to_string()
+                        )
+                    }
+                }
+                return MapCallResult::None
+            }
         }
     }
     
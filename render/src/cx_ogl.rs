@@ -448,15 +448,19 @@ impl Cx{
     pub fn stop_timer(&mut self, _id:u64){
     }
 
-    pub fn send_signal(_id:u64, _value:u64){
+    pub fn send_signal(_signal: Signal, _value: usize){
     }
     
     pub fn write_log(_data:&str){
     }
     
     pub fn compile_all_ogl_shaders(&mut self){
+        // program binaries are only good for the driver that made them
+        let driver = unsafe{format!("{} {}", Self::gl_string(gl::GetString(gl::RENDERER)), Self::gl_string(gl::GetString(gl::VERSION)))};
+        self.platform.desktop.shader_cache = ShaderCache::new("opengl", &driver);
+        self.profile_begin(SHADER_COMPILE_PROFILE_ID);
         for sh in &self.shaders{
-            let glsh = Self::compile_ogl_shader(&sh);
+            let glsh = Self::compile_ogl_shader(&sh, &mut self.platform.desktop.shader_cache);
             if let Ok(glsh) = glsh{
                 self.compiled_shaders.push(CompiledShader{
                     shader_id:self.compiled_shaders.len(),
@@ -470,6 +474,44 @@ impl Cx{
                 )
             }
         };
+        self.profile_end(SHADER_COMPILE_PROFILE_ID);
+        self.shader_cache_report();
+    }
+
+    // a program from a stored binary, None when the driver no longer takes it
+    pub fn load_program_binary(data:&Vec<u8>)->Option<gl::types::GLuint>{
+        if data.len() < 4{
+            return None
+        }
+        unsafe{
+            let format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            let program = gl::CreateProgram();
+            gl::ProgramBinary(program, format, data[4..].as_ptr() as *const _, (data.len() - 4) as gl::types::GLsizei);
+            let mut success = i32::from(gl::FALSE);
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+            if success != i32::from(gl::TRUE){
+                gl::DeleteProgram(program);
+                return None
+            }
+            Some(program)
+        }
+    }
+
+    // the binary of a linked program with its format in front, None when the driver has no binary formats
+    pub fn get_program_binary(program:gl::types::GLuint)->Option<Vec<u8>>{
+        unsafe{
+            let mut len = 0;
+            gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut len);
+            if len <= 0{
+                return None
+            }
+            let mut data = vec![0u8; len as usize + 4];
+            let mut format = 0;
+            gl::GetProgramBinary(program, len, &mut len, &mut format, data[4..].as_mut_ptr() as *mut _);
+            data[0..4].copy_from_slice(&format.to_le_bytes());
+            data.truncate(len as usize + 4);
+            Some(data)
+        }
     }
 
     pub fn compile_has_shader_error(compile:bool, shader:gl::types::GLuint, source:&str)->Option<String>{
@@ -577,41 +619,54 @@ impl Cx{
         gl_texture_slots
     }
 
-    pub fn compile_ogl_shader(sh:&Shader)->Result<CompiledShader, SlErr>{
+    pub fn compile_ogl_shader(sh:&Shader, cache:&mut ShaderCache)->Result<CompiledShader, SlErr>{
         let ash = Self::gl_assemble_shader(sh,GLShaderType::OpenGLNoPartialDeriv)?;
+        // a stored program binary skips compiling and linking
+        let cached = cache.load(sh).and_then(|entry| entry.binary("program").and_then(Self::load_program_binary));
         // now we have a pixel and a vertex shader
         // so lets now pass it to GL
         unsafe{
-            
-            let vs = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vs, 1, [ash.vertex.as_ptr() as *const _].as_ptr(), ptr::null());
-            gl::CompileShader(vs);
-            if let Some(error) = Self::compile_has_shader_error(true, vs, &ash.vertex){
-                return Err(SlErr{
-                    msg:format!("ERROR::SHADER::VERTEX::COMPILATION_FAILED\n{}",error)
-                })
-            }
+            let program = if let Some(program) = cached{program} else{
+                let vs = gl::CreateShader(gl::VERTEX_SHADER);
+                gl::ShaderSource(vs, 1, [ash.vertex.as_ptr() as *const _].as_ptr(), ptr::null());
+                gl::CompileShader(vs);
+                if let Some(error) = Self::compile_has_shader_error(true, vs, &ash.vertex){
+                    return Err(SlErr{
+                        msg:format!("ERROR::SHADER::VERTEX::COMPILATION_FAILED\n{}",error)
+                    })
+                }
 
-            let fs = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fs, 1, [ash.fragment.as_ptr() as *const _].as_ptr(), ptr::null());
-            gl::CompileShader(fs);
-            if let Some(error) = Self::compile_has_shader_error(true, fs, &ash.fragment){
-                return Err(SlErr{
-                    msg:format!("ERROR::SHADER::FRAGMENT::COMPILATION_FAILED\n{}",error)
-                })
-            }
+                let fs = gl::CreateShader(gl::FRAGMENT_SHADER);
+                gl::ShaderSource(fs, 1, [ash.fragment.as_ptr() as *const _].as_ptr(), ptr::null());
+                gl::CompileShader(fs);
+                if let Some(error) = Self::compile_has_shader_error(true, fs, &ash.fragment){
+                    return Err(SlErr{
+                        msg:format!("ERROR::SHADER::FRAGMENT::COMPILATION_FAILED\n{}",error)
+                    })
+                }
 
-            let program = gl::CreateProgram();
-            gl::AttachShader(program, vs);
-            gl::AttachShader(program, fs);
-            gl::LinkProgram(program);
-            if let Some(error) = Self::compile_has_shader_error(false, program, ""){
-                return Err(SlErr{
-                    msg:format!("ERROR::SHADER::LINK::COMPILATION_FAILED\n{}",error)
-                })
-            }
-            gl::DeleteShader(vs);
-            gl::DeleteShader(fs);
+                let program = gl::CreateProgram();
+                gl::AttachShader(program, vs);
+                gl::AttachShader(program, fs);
+                gl::ProgramParameteri(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+                gl::LinkProgram(program);
+                if let Some(error) = Self::compile_has_shader_error(false, program, ""){
+                    return Err(SlErr{
+                        msg:format!("ERROR::SHADER::LINK::COMPILATION_FAILED\n{}",error)
+                    })
+                }
+                gl::DeleteShader(vs);
+                gl::DeleteShader(fs);
+                if let Some(data) = Self::get_program_binary(program){
+                    cache.store(sh, &ShaderCacheEntry{
+                        source:format!("{}\n{}", ash.vertex, ash.fragment),
+                        // the attributes and uniforms come from the assembled shader, not the mapping
+                        mapping:Default::default(),
+                        binaries:vec![("program".to_string(), data)]
+                    });
+                }
+                program
+            };
 
             let geom_attribs = Self::compile_get_attributes(program, "geomattr", ash.geometry_slots);
             let inst_attribs = Self::compile_get_attributes(program, "instattr", ash.instance_slots);
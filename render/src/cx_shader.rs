@@ -65,7 +65,7 @@ impl NamedProps {
         for prop in in_props {
             let slots = sg.get_type_slots(&prop.ty);
             
-            if aligned && (offset & 3) + slots > 4 { // goes over the boundary
+            if aligned && (offset & 3) > 0 && (offset & 3) + slots > 4 { // goes over the boundary
                 offset += 4 - (offset & 3); // make jump to new slot
             }
             
@@ -99,6 +99,66 @@ pub struct CxShaderMapping {
     pub rect_instance_props: RectInstanceProps,
     pub named_uniform_props: NamedProps,
     pub named_instance_props: NamedProps,
+    // the instance props the optimized shader reads, in the order the gpu gets them
+    pub gpu_instances: Vec<ShVar>,
+    // the slots of the widget written instance data that go to the gpu, empty when all of them do
+    pub instance_gather: Vec<usize>,
+}
+
+impl CxShaderMapping {
+    // the layout the backends build next to their source, for shaders that come out of the shader cache
+    pub fn construct(sg: &ShaderGen) -> CxShaderMapping {
+        let instances = sg.flat_vars(ShVarStore::Instance);
+        let geometries = sg.flat_vars(ShVarStore::Geometry);
+        let uniforms_dr = sg.flat_vars(ShVarStore::Uniform);
+        CxShaderMapping {
+            rect_instance_props: RectInstanceProps::construct(sg, &instances),
+            named_instance_props: NamedProps::construct(sg, &instances, false),
+            named_uniform_props: NamedProps::construct(sg, &uniforms_dr, true),
+            instance_slots: sg.compute_slot_total(&instances),
+            geometry_slots: sg.compute_slot_total(&geometries),
+            instances: instances,
+            geometries: geometries,
+            uniforms_dr: uniforms_dr,
+            uniforms_vw: sg.flat_vars(ShVarStore::UniformVw),
+            uniforms_cx: sg.flat_vars(ShVarStore::UniformCx),
+            texture_slots: sg.flat_vars(ShVarStore::Texture),
+            gpu_instances: sg.flat_vars(ShVarStore::Instance),
+            instance_gather: Vec::new(),
+        }
+    }
+    
+    // the layout of sg as the widgets write it, with the instance props opt_sg dropped left out on the gpu
+    pub fn construct_optimized(sg: &ShaderGen, opt_sg: &ShaderGen) -> CxShaderMapping {
+        let mut mapping = Self::construct(sg);
+        mapping.gpu_instances = opt_sg.flat_vars(ShVarStore::Instance);
+        if mapping.gpu_instances.len() < mapping.instances.len() {
+            let mut slot = 0;
+            for inst in &mapping.instances {
+                let slots = sg.get_type_slots(&inst.ty);
+                if mapping.gpu_instances.iter().any( | gpu_inst | gpu_inst.name == inst.name) {
+                    mapping.instance_gather.extend(slot..slot + slots);
+                }
+                slot += slots;
+            }
+        }
+        mapping
+    }
+    
+    pub fn gpu_instance_slots(&self) -> usize {
+        if self.instance_gather.len() > 0 {self.instance_gather.len()}else {self.instance_slots}
+    }
+    
+    // copies the slots the gpu reads out of the instance data of a draw call
+    pub fn gather_instances(&self, instance: &Vec<f32>) -> Vec<f32> {
+        let mut out = Vec::with_capacity(instance.len() / self.instance_slots * self.instance_gather.len());
+        for inst in instance.chunks(self.instance_slots) {
+            for slot in &self.instance_gather {
+                out.push(inst[*slot]);
+            }
+        }
+        out
+    }
 }
 
 #[derive(Default, Clone)]
@@ -106,7 +166,9 @@ pub struct CxShader {
     pub name: String,
     pub shader_gen: ShaderGen,
     pub platform: Option<CxPlatformShader>,
-    pub mapping: CxShaderMapping
+    pub mapping: CxShaderMapping,
+    // shader_gen came from a hot reload and has its own entry in the shader_map
+    pub reloaded: bool
 }
 
 impl CxShader {
@@ -195,71 +257,71 @@ impl CxShader {
                     // F - float-like (float, vec2, vec3, etc)
                     // B - bool-vector (bvecn)
                     
-                    ShFn {name: "sizeof".to_string(), args: vec![ShFnArg::new("type", "T")], ret: "int".to_string(), block: None},
-                    ShFn {name: "color".to_string(), args: vec![ShFnArg::new("color", "string")], ret: "vec4".to_string(), block: None},
+                    ShFn {name: "sizeof".to_string(), args: vec![ShFnArg::new("type", "T")], ret: "int".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "color".to_string(), args: vec![ShFnArg::new("color", "string")], ret: "vec4".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "radians".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "degrees".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "radians".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "degrees".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "sin".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "cos".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "tan".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "asin".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "acos".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "atan".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "O")], ret: "T".to_string(), block: None},
+                    ShFn {name: "sin".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "cos".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "tan".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "asin".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "acos".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "atan".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "O")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "pow".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "exp".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "log".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "exp2".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "log2".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "pow".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "exp".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "log".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "exp2".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "log2".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "sqrt".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "inversesqrt".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "sqrt".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "inversesqrt".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "abs".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "sign".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "floor".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "ceil".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "fract".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "abs".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "sign".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "floor".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "ceil".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "fract".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "fmod".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "min".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "max".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "clamp".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("mi", "T"), ShFnArg::new("ma", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "fmod".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "min".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "max".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "clamp".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("mi", "T"), ShFnArg::new("ma", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "mix".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T"), ShFnArg::new("t", "F")], ret: "T".to_string(), block: None},
-                    ShFn {name: "step".to_string(), args: vec![ShFnArg::new("e", "T"), ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "smoothstep".to_string(), args: vec![ShFnArg::new("e0", "F"), ShFnArg::new("e1", "F"), ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "mix".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T"), ShFnArg::new("t", "F")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "step".to_string(), args: vec![ShFnArg::new("e", "T"), ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "smoothstep".to_string(), args: vec![ShFnArg::new("e0", "F"), ShFnArg::new("e1", "F"), ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "length".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "float".to_string(), block: None},
-                    ShFn {name: "distance".to_string(), args: vec![ShFnArg::new("p0", "T"), ShFnArg::new("p1", "T")], ret: "float".to_string(), block: None},
-                    ShFn {name: "dot".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "float".to_string(), block: None},
-                    ShFn {name: "cross".to_string(), args: vec![ShFnArg::new("x", "vec3"), ShFnArg::new("y", "vec3")], ret: "vec3".to_string(), block: None},
-                    ShFn {name: "normalize".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "length".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "float".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "distance".to_string(), args: vec![ShFnArg::new("p0", "T"), ShFnArg::new("p1", "T")], ret: "float".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "dot".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "float".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "cross".to_string(), args: vec![ShFnArg::new("x", "vec3"), ShFnArg::new("y", "vec3")], ret: "vec3".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "normalize".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "faceforward".to_string(), args: vec![ShFnArg::new("n", "T"), ShFnArg::new("i", "T"), ShFnArg::new("nref", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "reflect".to_string(), args: vec![ShFnArg::new("i", "T"), ShFnArg::new("n", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "refract".to_string(), args: vec![ShFnArg::new("i", "T"), ShFnArg::new("n", "T"), ShFnArg::new("eta", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "faceforward".to_string(), args: vec![ShFnArg::new("n", "T"), ShFnArg::new("i", "T"), ShFnArg::new("nref", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "reflect".to_string(), args: vec![ShFnArg::new("i", "T"), ShFnArg::new("n", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "refract".to_string(), args: vec![ShFnArg::new("i", "T"), ShFnArg::new("n", "T"), ShFnArg::new("eta", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "matrix_comp_mult".to_string(), args: vec![ShFnArg::new("a", "mat4"), ShFnArg::new("b", "mat4")], ret: "mat4".to_string(), block: None},
+                    ShFn {name: "matrix_comp_mult".to_string(), args: vec![ShFnArg::new("a", "mat4"), ShFnArg::new("b", "mat4")], ret: "mat4".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "less_than".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
-                    ShFn {name: "less_than_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
-                    ShFn {name: "greater_than".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
-                    ShFn {name: "greater_than_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
-                    ShFn {name: "equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
-                    ShFn {name: "not_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None},
+                    ShFn {name: "less_than".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "less_than_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "greater_than".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "greater_than_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "not_equal".to_string(), args: vec![ShFnArg::new("x", "T"), ShFnArg::new("y", "T")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "any".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "bool".to_string(), block: None},
-                    ShFn {name: "all".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "bool".to_string(), block: None},
-                    ShFn {name: "not".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "B".to_string(), block: None},
+                    ShFn {name: "any".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "bool".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "all".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "bool".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "not".to_string(), args: vec![ShFnArg::new("x", "B")], ret: "B".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "dfdx".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "dfdy".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
-                    ShFn {name: "fwidth".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None},
+                    ShFn {name: "dfdx".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "dfdy".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
+                    ShFn {name: "fwidth".to_string(), args: vec![ShFnArg::new("x", "T")], ret: "T".to_string(), block: None, loc: ShLoc::default()},
                     
-                    ShFn {name: "sample2d".to_string(), args: vec![ShFnArg::new("texture", "texture2d"), ShFnArg::new("coord", "vec2")], ret: "vec4".to_string(), block: None},
+                    ShFn {name: "sample2d".to_string(), args: vec![ShFnArg::new("texture", "texture2d"), ShFnArg::new("coord", "vec2")], ret: "vec4".to_string(), block: None, loc: ShLoc::default()},
                     /*
                     ShFn{name:"texture2DLod".to_string(), args:vec![ShFnArg::new("sampler","sampler2D"), ShFnArg::new("coord","vec2"), ShFnArg::new("lod","float")], ret:"vec4".to_string(), block:None},
                     ShFn{name:"texture2DProjLod".to_string(), args:vec![ShFnArg::new("sampler","sampler2D"), ShFnArg::new("coord","vec2"), ShFnArg::new("lod","float")], ret:"vec4".to_string(), block:None},
@@ -268,23 +330,30 @@ impl CxShader {
                     ShFn{name:"textureCube".to_string(), args:vec![ShFnArg::new("sampler","samplerCube"), ShFnArg::new("coord","vec3"), ShFnArg::new("bias","O")], ret:"vec4".to_string(), block:None},
                     */
                 ],
-                consts: Vec::new()
+                consts: Vec::new(),
+                imports: Vec::new(),
+                module: String::new()
             }
         )
     }
     
-    pub fn def_df(sg: ShaderGen)->ShaderGen{
-        sg.compose(shader_ast!({
-            const PI: float = 3.141592653589793;
-            const E: float = 2.718281828459045;
-            const LN2: float = 0.6931471805599453;
-            const LN10: float = 2.302585092994046;
-            const LOG2E: float = 1.4426950408889634;
-            const LOG10E: float = 0.4342944819032518;
-            const SQRT1_2: float = 0.70710678118654757;
-            const TORAD: float = 0.017453292519943295;
-            const GOLDEN: float = 1.618033988749895;
+    // the shader modules, see shadermod.rs. everything a module declares starts with its name
+    pub fn def_math() -> ShAst {
+        shader_ast!({
+            const math_PI: float = 3.141592653589793;
+            const math_E: float = 2.718281828459045;
+            const math_LN2: float = 0.6931471805599453;
+            const math_LN10: float = 2.302585092994046;
+            const math_LOG2E: float = 1.4426950408889634;
+            const math_LOG10E: float = 0.4342944819032518;
+            const math_SQRT1_2: float = 0.70710678118654757;
+            const math_TORAD: float = 0.017453292519943295;
+            const math_GOLDEN: float = 1.618033988749895;
+        })
+    }
             
+    pub fn def_df() -> ShAst {
+        shader_ast!({
             let df_pos: vec2<Local>;
             let df_result: vec4<Local>;
             let df_last_pos: vec2<Local>;
@@ -426,7 +495,7 @@ impl CxShader {
                 df_shape = min(df_shape, df_field);
             }
             
-            fn df_box(x: float, y: float, w: float, h: float, r: float) {
+            fn df_round_rect(x: float, y: float, w: float, h: float, r: float) {
                 let p: vec2 = df_pos - vec2(x, y);
                 let size: vec2 = vec2(0.5 * w, 0.5 * h);
                 let bp: vec2 = max(abs(p - size.xy) - (size.xy - vec2(2. * r, 2. * r).xy), vec2(0., 0.));
@@ -435,6 +504,11 @@ impl CxShader {
                 df_shape = min(df_shape, df_field);
             }
             
+            // deprecated, the name from before modules, use df::round_rect
+            fn df_box(x: float, y: float, w: float, h: float, r: float) {
+                df_round_rect(x, y, w, h, r);
+            }
+            
             fn df_rect(x: float, y: float, w: float, h: float) {
                 let s: vec2 = vec2(w, h) * 0.5;
                 let d: vec2 = abs(vec2(x, y) - df_pos + s) - s;
@@ -467,14 +541,18 @@ impl CxShader {
             fn df_close_path() {
                 df_line_to(df_start_pos.x, df_start_pos.y);
             }
-            
-            fn df_hsv2rgb(c: vec4) -> vec4 { //http://gamedev.stackexchange.com/questions/59797/glsl-shader-change-hue-saturation-brightness
+        })
+    }
+    
+    pub fn def_color() -> ShAst {
+        shader_ast!({
+            fn color_hsv2rgb(c: vec4) -> vec4 { //http://gamedev.stackexchange.com/questions/59797/glsl-shader-change-hue-saturation-brightness
                 let K: vec4 = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
-                let p: vec4 = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
-                return vec4(c.z * mix(K.xxx, clamp(p - K.xxx, 0.0, 1.0), c.y), c.w);
+                let p: vec3 = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
+                return vec4(c.z * mix(K.xxx, clamp(p - K.xxx, vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0)), c.y), c.w);
             }
             
-            fn df_rgb2hsv(c: vec4) -> vec4 {
+            fn color_rgb2hsv(c: vec4) -> vec4 {
                 let K: vec4 = vec4(0.0, -1.0 / 3.0, 2.0 / 3.0, -1.0);
                 let p: vec4 = mix(vec4(c.bg, K.wz), vec4(c.gb, K.xy), step(c.b, c.g));
                 let q: vec4 = mix(vec4(p.xyw, c.r), vec4(c.r, p.yzx), step(p.x, c.r));
@@ -483,6 +561,253 @@ impl CxShader {
                 let e: float = 1.0e-10;
                 return vec4(abs(q.z + (q.w - q.y) / (6.0 * d + e)), d / (q.x + e), q.x, c.w);
             }
+            
+            fn color_luminance(c: vec4) -> float {
+                return dot(c.rgb, vec3(0.2126, 0.7152, 0.0722));
+            }
+        })
+    }
+    
+    pub fn def_noise() -> ShAst {
+        shader_ast!({
+            fn noise_hash(p: vec2) -> float {
+                return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+            }
+            
+            // value noise between 0 and 1
+            fn noise_value(p: vec2) -> float {
+                let i: vec2 = floor(p);
+                let f: vec2 = fract(p);
+                let u: vec2 = f * f * (3.0 - 2.0 * f);
+                let a: float = mix(noise_hash(i), noise_hash(i + vec2(1.0, 0.0)), u.x);
+                let b: float = mix(noise_hash(i + vec2(0.0, 1.0)), noise_hash(i + vec2(1.0, 1.0)), u.x);
+                return mix(a, b, u.y);
+            }
+            
+            // five octaves of value noise
+            fn noise_fbm(p: vec2) -> float {
+                let sum: float = 0.0;
+                let amp: float = 0.5;
+                let q: vec2 = p;
+                for i in 0..5 {
+                    sum += amp * noise_value(q);
+                    q = q * 2.0;
+                    amp *= 0.5;
+                }
+                return sum;
+            }
+        })
+    }
+}
+
+impl Cx {
+    // swap in a new ShaderGen for a running shader, the platform compiles it again before the next paint
+    // the draw calls are laid out for the geometry, instance, uniform and texture vars so those have to stay
+    pub fn reload_shader(&mut self, shader: &Shader, sg: ShaderGen) -> Result<(), Vec<ShTypeError>> {
+        let shader_id = if let Some(shader_id) = shader.shader_id {shader_id}else {
+            return Ok(())
+        };
+        sg.type_check() ?;
+        let old_sg = &self.shaders[shader_id].shader_gen;
+        let stores = [
+            (ShVarStore::Geometry, "Geometry"),
+            (ShVarStore::Instance, "Instance"),
+            (ShVarStore::Uniform, "Uniform"),
+            (ShVarStore::UniformVw, "UniformVw"),
+            (ShVarStore::UniformCx, "UniformCx"),
+            (ShVarStore::Texture, "Texture")
+        ];
+        for (store, store_name) in &stores {
+            let old_vars = old_sg.flat_vars(store.clone());
+            let new_vars = sg.flat_vars(store.clone());
+            if old_vars.len() != new_vars.len() || old_vars.iter().zip(new_vars.iter()).any( | (old, new) | old.name != new.name || old.ty != new.ty) {
+                let changed = new_vars.iter().find( | new | old_vars.iter().find( | old | old.name == new.name && old.ty == new.ty).is_none());
+                return Err(vec![ShTypeError {
+                    msg: format!("changing the {} vars of shader {} needs a rebuild", store_name, self.shaders[shader_id].name),
+                    loc: changed.map_or(ShLoc::default(), | var | var.loc),
+                    fn_name: "".to_string()
+                }])
+            }
+        }
+        // what the platform shader generator refuses would only show up in the log at recompile
+        if let Err(err) = Self::assemble_platform_shader(&sg) {
+            return Err(vec![ShTypeError {
+                msg: format!("shader {} does not compile: {}", self.shaders[shader_id].name, err.msg),
+                loc: ShLoc::default(),
+                fn_name: "".to_string()
+            }])
+        }
+        // restyling looks the shader up by its original ShaderGen, that one stays mapped as well
+        // the entry of the previous reload goes, or every edit would leave one behind
+        if self.shaders[shader_id].reloaded {
+            self.shader_map.remove(&self.shaders[shader_id].shader_gen);
+        }
+        // back at the original text its entry is still there
+        let reloaded = !self.shader_map.contains_key(&sg);
+        if reloaded {
+            self.shader_map.insert(sg.clone(), shader_id);
+        }
+        self.shaders[shader_id].reloaded = reloaded;
+        self.shaders[shader_id].shader_gen = sg;
+        if !self.shader_recompile_ids.contains(&shader_id) {
+            self.shader_recompile_ids.push(shader_id);
+        }
+        self.redraw_child_area(Area::All);
+        Ok(())
+    }
+    
+    // reload the shaders that use shader_ast! blocks from this rust file, with the edited text of the file
+    // the blocks are matched to the loaded asts by the names they declare, so moving code around is fine
+    pub fn reload_shaders_from_source(&mut self, file: &str, text: &str) -> Vec<ShTypeError> {
+        // the locations need the file!() string the macro put in, so borrow it from a loaded ast
+        let static_file = self.shaders.iter()
+            .flat_map( | sh | sh.shader_gen.asts.iter())
+            .filter_map( | ast | ast_file(ast))
+            .find( | ast_file | *ast_file == file);
+        let static_file = if let Some(static_file) = static_file {static_file}else {
+            return Vec::new()
+        };
+        let mut errors = Vec::new();
+        let mut new_asts = Vec::new();
+        for block in find_shader_ast_blocks(text) {
+            match parse_shader_ast(static_file, &block.source, block.line, block.col) {
+                Ok(mut ast) => {
+                    ast.resolve_imports();
+                    new_asts.push(ast)
+                },
+                Err(errs) => errors.extend(errs)
+            }
+        }
+        if errors.len() > 0 {
+            return errors
+        }
+        for shader_id in 0..self.shaders.len() {
+            let mut sg = self.shaders[shader_id].shader_gen.clone();
+            let mut changed = false;
+            for ast in &mut sg.asts {
+                // modules are linked in from their compiled in source, editing them needs a rebuild
+                if ast_file(ast) != Some(static_file) || ast.module.len() > 0 {
+                    continue;
+                }
+                // asts compare without their locations, so only real edits recompile
+                if let Some(new_ast) = match_ast(ast, &new_asts) {
+                    if ast != new_ast {
+                        *ast = new_ast.clone();
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                // the edit can use other module items than before
+                sg.link_modules();
+                if let Err(errs) = self.reload_shader(&Shader {shader_id: Some(shader_id)}, sg) {
+                    // asts shared by several shaders give the same errors
+                    for err in errs {
+                        if errors.iter().find( | e: &&ShTypeError | e.msg == err.msg && e.loc.line == err.loc.line && e.loc.col == err.loc.col).is_none() {
+                            errors.push(err);
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+// the file an ast came from, asts built by hand have no locations
+fn ast_file(ast: &ShAst) -> Option<&'static str> {
+    let file = ast.vars.iter().map( | var | var.loc.file)
+        .chain(ast.consts.iter().map( | cnst | cnst.loc.file))
+        .chain(ast.fns.iter().map( | shfn | shfn.loc.file))
+        .find( | file | file.len() > 0);
+    file
+}
+
+fn ast_names(ast: &ShAst) -> Vec<&str> {
+    ast.vars.iter().map( | var | var.name.as_ref())
+        .chain(ast.consts.iter().map( | cnst | cnst.name.as_ref()))
+        .chain(ast.fns.iter().map( | shfn | shfn.name.as_ref()))
+        .chain(ast.types.iter().map( | ty | ty.name.as_ref()))
+        .collect()
+}
+
+fn ast_line(ast: &ShAst) -> u32 {
+    ast.vars.iter().map( | var | var.loc.line)
+        .chain(ast.consts.iter().map( | cnst | cnst.loc.line))
+        .chain(ast.fns.iter().map( | shfn | shfn.loc.line))
+        .min().unwrap_or(0)
+}
+
+// the parsed block that declares most of the same names, the nearest one when that is a tie
+fn match_ast<'a>(ast: &ShAst, new_asts: &'a Vec<ShAst>) -> Option<&'a ShAst> {
+    let names = ast_names(ast);
+    let line = ast_line(ast) as i64;
+    let mut best: Option<(&ShAst, usize, i64)> = None;
+    for new_ast in new_asts {
+        let score = ast_names(new_ast).iter().filter( | name | names.contains(name)).count();
+        let dist = (ast_line(new_ast) as i64 - line).abs();
+        if score == 0 {
+            continue;
+        }
+        if let Some((_, best_score, best_dist)) = best {
+            if score < best_score || score == best_score && dist >= best_dist {
+                continue;
+            }
+        }
+        best = Some((new_ast, score, dist));
+    }
+    best.map( | (new_ast, _, _) | new_ast)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    
+    fn def_fn_only_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                return color * 0.5;
+            }
         }))
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn reload_block_with_only_fns() {
+        let mut cx = Cx::default();
+        let shader = cx.add_shader(def_fn_only_shader(), "FnOnly");
+        let shader_id = shader.shader_id.unwrap();
+        let text = include_str!("cx_shader.rs").replace("return color * 0.5;", "return color * 0.25;");
+        let errors = cx.reload_shaders_from_source(file!(), &text);
+        assert!(errors.len() == 0, "{}", errors[0]);
+        assert!(cx.shaders[shader_id].shader_gen != def_fn_only_shader());
+        assert!(cx.shader_recompile_ids.contains(&shader_id));
+    }
+    
+    #[test]
+    fn reloads_keep_one_shader_map_entry() {
+        let mut cx = Cx::default();
+        let shader = cx.add_shader(def_fn_only_shader(), "FnOnly");
+        let mapped = cx.shader_map.len();
+        for scale in &["0.25", "0.125", "0.0625"] {
+            let text = include_str!("cx_shader.rs").replace("return color * 0.5;", &format!("return color * {};", scale));
+            assert_eq!(cx.reload_shaders_from_source(file!(), &text).len(), 0);
+            assert_eq!(cx.shader_map.len(), mapped + 1);
+        }
+        // the original text maps back to the shader, and restyling still finds it
+        let text = include_str!("cx_shader.rs");
+        assert_eq!(cx.reload_shaders_from_source(file!(), &text).len(), 0);
+        assert_eq!(cx.shader_map.len(), mapped);
+        assert_eq!(cx.add_shader(def_fn_only_shader(), "FnOnly").shader_id, shader.shader_id);
+    }
+    
+    #[test]
+    fn reload_reports_fn_locations() {
+        let mut cx = Cx::default();
+        cx.add_shader(def_fn_only_shader(), "FnOnly");
+        let text = include_str!("cx_shader.rs").replace("return color * 0.5;", "return color * undefined_var;");
+        let errors = cx.reload_shaders_from_source(file!(), &text);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].fn_name, "pixel");
+        assert!(errors[0].loc.line > 0);
+    }
+}
@@ -0,0 +1,1710 @@
+use ash::vk;
+use std::ffi::CStr;
+use std::process::{Command, Child, Stdio};
+use std::os::unix::process::{CommandExt};
+use std::sync::{Mutex, Condvar};
+use std::time::{Duration, Instant};
+use crate::cx::*;
+
+// The vulkan backend for linux, behind the vulkan feature. the shaders come from the GLSL 450
+// generator and go through naga to SPIR-V, drawing uses dynamic rendering so it needs vulkan 1.3.
+// there is no window system layer for linux in this tree, so windows render to offscreen images.
+// set capture_windows on the platform to read them back, that is also how it runs headless on lavapipe
+
+pub const VK_COLOR_FORMAT: vk::Format = vk::Format::B8G8R8A8_UNORM;
+
+// the sizes of the per submit allocations, more are made when a submit needs them
+const VK_UNIFORM_CHUNK_SIZE: u64 = 64 * 1024;
+const VK_DESCRIPTOR_POOL_SETS: u32 = 1024;
+
+impl Cx {
+    // walks the views of a pass before the rendering starts, uploading what changed and collecting the draws
+    fn vk_collect_view(&mut self, pass_id: usize, view_id: usize, vulkan_cx: &mut VulkanCx, draws: &mut Vec<VkDraw>) -> Result<(), String> {
+
+        // tad ugly otherwise the borrow checker locks 'self' and we can't recur
+        let draw_calls_len = self.views[view_id].draw_calls_len;
+        for draw_call_id in 0..draw_calls_len {
+            let sub_view_id = self.views[view_id].draw_calls[draw_call_id].sub_view_id;
+            if sub_view_id != 0 {
+                self.vk_collect_view(pass_id, sub_view_id, vulkan_cx, draws) ?;
+                continue;
+            }
+            let cxview = &mut self.views[view_id];
+            cxview.set_clipping_uniforms();
+            let draw_call = &mut cxview.draw_calls[draw_call_id];
+            let sh = &self.shaders[draw_call.shader_id];
+            let shp = if let Some(shp) = &sh.platform {shp}else {continue};
+
+            if draw_call.instance_dirty {
+                draw_call.instance_dirty = false;
+                // update the instance buffer data, minus the props the optimizer dropped
+                if sh.mapping.instance_gather.len() > 0 {
+                    vulkan_cx.update_buffer(&mut draw_call.platform.inst_vbuf, vk::BufferUsageFlags::VERTEX_BUFFER, &sh.mapping.gather_instances(&draw_call.instance)) ?;
+                }
+                else {
+                    vulkan_cx.update_buffer(&mut draw_call.platform.inst_vbuf, vk::BufferUsageFlags::VERTEX_BUFFER, &draw_call.instance) ?;
+                }
+            }
+            draw_call.uniforms_dirty = false;
+
+            let instances = draw_call.instance.len() / sh.mapping.instance_slots;
+            if instances == 0 {
+                continue;
+            }
+
+            // the pipeline layout has all three uniform sets, a block the shader does not declare gets 16 unread bytes
+            let mut sets = Vec::new();
+            let uniforms = [&self.passes[pass_id].uniforms, &cxview.uniforms, &draw_call.uniforms];
+            for (data, size) in uniforms.iter().zip(shp.layout.uniform_sizes.iter()) {
+                let info = vulkan_cx.frame_uniforms(data, *size as u64) ?;
+                let set = vulkan_cx.alloc_set(vulkan_cx.uniform_set_layout) ?;
+                let buffer_info = [info];
+                let write = vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(&buffer_info)
+                    .build();
+                unsafe {vulkan_cx.device.update_descriptor_sets(&[write], &[])};
+                sets.push(set);
+            }
+
+            // lets set our textures, what is not there or not rendered yet samples the empty texture
+            if shp.layout.texture_count > 0 {
+                let mut image_infos = Vec::new();
+                for i in 0..shp.layout.texture_count as usize {
+                    let mut view = vulkan_cx.empty_texture.view;
+                    if let Some(texture_id) = draw_call.textures_2d.get(i) {
+                        if let Some(cxtexture) = self.textures.get_mut(*texture_id as usize) {
+                            if cxtexture.upload_image {
+                                vulkan_cx.update_platform_texture_image2d(cxtexture) ?;
+                            }
+                            if cxtexture.platform.layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL {
+                                view = cxtexture.platform.view;
+                            }
+                        }
+                    }
+                    image_infos.push([vk::DescriptorImageInfo {
+                        sampler: vk::Sampler::null(),
+                        image_view: view,
+                        image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+                    }]);
+                }
+                let set = vulkan_cx.alloc_set(shp.texture_set_layout) ?;
+                let sampler_info = [vk::DescriptorImageInfo {
+                    sampler: vulkan_cx.sampler,
+                    image_view: vk::ImageView::null(),
+                    image_layout: vk::ImageLayout::UNDEFINED
+                }];
+                let mut writes = vec![vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .image_info(&sampler_info)
+                    .build()];
+                // the textures are a binding each, after the sampler
+                for (i, image_info) in image_infos.iter().enumerate() {
+                    writes.push(vk::WriteDescriptorSet::builder()
+                        .dst_set(set)
+                        .dst_binding(i as u32 + 1)
+                        .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                        .image_info(image_info)
+                        .build());
+                }
+                unsafe {vulkan_cx.device.update_descriptor_sets(&writes, &[])};
+                sets.push(set);
+            }
+
+            draws.push(VkDraw {
+                shader_id: draw_call.shader_id,
+                sets: sets,
+                inst_vbuf: draw_call.platform.inst_vbuf.buffer,
+                instances: instances as u32
+            });
+        }
+        Ok(())
+    }
+
+    // renders a pass into its color and depth textures, or into window_target for the main pass of a window
+    pub fn vk_draw_pass(&mut self, pass_id: usize, dpi_factor: f32, window_target: Option<&mut CxPlatformTexture>, vulkan_cx: &mut VulkanCx) -> Result<(), String> {
+        let view_id = if let Some(view_id) = self.passes[pass_id].main_view_id {view_id}else {
+            return Ok(())
+        };
+        let pass_size = self.passes[pass_id].pass_size;
+        let cb = vulkan_cx.command_buffer;
+        vulkan_cx.begin_commands() ?;
+
+        // copies and layout changes can't be inside the rendering, so uploads go first
+        let mut draws = Vec::new();
+        self.vk_collect_view(pass_id, view_id, vulkan_cx, &mut draws) ?;
+
+        let mut color_attachments = Vec::new();
+        let mut render_size = (0, 0);
+        let mut rendered_textures = Vec::new();
+        if let Some(target) = window_target {
+            vulkan_cx.transition(target, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+            let clear_color = match self.passes[pass_id].color_textures.get(0) {
+                Some(color_texture) => color_texture.clear_color,
+                None => Some(Color {r: 0.0, g: 0.0, b: 0.0, a: 0.0})
+            };
+            color_attachments.push(Self::vk_color_attachment(target.view, clear_color));
+            render_size = (target.width, target.height);
+        }
+        else {
+            for color_texture in &self.passes[pass_id].color_textures {
+                let cxtexture = &mut self.textures[color_texture.texture_id];
+                vulkan_cx.update_platform_render_target(cxtexture, dpi_factor, pass_size, false) ?;
+                vulkan_cx.transition(&mut cxtexture.platform, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+                color_attachments.push(Self::vk_color_attachment(cxtexture.platform.view, color_texture.clear_color));
+                render_size = (cxtexture.platform.width, cxtexture.platform.height);
+                rendered_textures.push(color_texture.texture_id);
+            }
+        }
+        let mut depth_attachment = None;
+        if let Some(depth_texture_id) = self.passes[pass_id].depth_texture {
+            let cxtexture = &mut self.textures[depth_texture_id];
+            vulkan_cx.update_platform_render_target(cxtexture, dpi_factor, pass_size, true) ?;
+            vulkan_cx.transition(&mut cxtexture.platform, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+            depth_attachment = Some(vk::RenderingAttachmentInfo::builder()
+                .image_view(cxtexture.platform.view)
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .clear_value(vk::ClearValue {depth_stencil: vk::ClearDepthStencilValue {depth: 1.0, stencil: 0}})
+                .build());
+        }
+        if color_attachments.len() == 0 || render_size.0 == 0 || render_size.1 == 0 {
+            vulkan_cx.submit_and_wait() ?;
+            return Ok(())
+        }
+
+        let key = VkPipelineKey {
+            color_count: color_attachments.len(),
+            depth: depth_attachment.is_some()
+        };
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D {x: 0, y: 0},
+            extent: vk::Extent2D {width: render_size.0, height: render_size.1}
+        };
+        let mut rendering_info = vk::RenderingInfo::builder()
+            .render_area(render_area)
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+        if let Some(depth_attachment) = &depth_attachment {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+        }
+        unsafe {
+            let device = &vulkan_cx.device;
+            device.cmd_begin_rendering(cb, &rendering_info);
+            device.cmd_set_viewport(cb, 0, &[vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: render_size.0 as f32,
+                height: render_size.1 as f32,
+                min_depth: 0.0,
+                max_depth: 1.0
+            }]);
+            device.cmd_set_scissor(cb, 0, &[render_area]);
+        }
+        for draw in &draws {
+            let sh = &mut self.shaders[draw.shader_id];
+            let index_count = sh.shader_gen.geometry_indices.len() as u32;
+            let shp = sh.platform.as_mut().unwrap();
+            let pipeline = vulkan_cx.pipeline(shp, &key) ?;
+            unsafe {
+                let device = &vulkan_cx.device;
+                device.cmd_bind_pipeline(cb, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_bind_descriptor_sets(cb, vk::PipelineBindPoint::GRAPHICS, shp.pipeline_layout, 0, &draw.sets, &[]);
+                device.cmd_bind_vertex_buffers(cb, 0, &[shp.geom_vbuf.buffer, draw.inst_vbuf], &[0, 0]);
+                device.cmd_bind_index_buffer(cb, shp.geom_ibuf.buffer, 0, vk::IndexType::UINT32);
+                device.cmd_draw_indexed(cb, index_count, draw.instances, 0, 0, 0);
+            }
+        }
+        unsafe {vulkan_cx.device.cmd_end_rendering(cb)};
+
+        // whatever samples these next reads them in the shader
+        for texture_id in rendered_textures {
+            vulkan_cx.transition(&mut self.textures[texture_id].platform, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        }
+        vulkan_cx.submit_and_wait()
+    }
+
+    fn vk_color_attachment(view: vk::ImageView, clear_color: Option<Color>) -> vk::RenderingAttachmentInfo {
+        let builder = vk::RenderingAttachmentInfo::builder()
+            .image_view(view)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .store_op(vk::AttachmentStoreOp::STORE);
+        if let Some(color) = clear_color {
+            builder
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .clear_value(vk::ClearValue {color: vk::ClearColorValue {float32: [color.r, color.g, color.b, color.a]}})
+                .build()
+        }
+        else {
+            builder.load_op(vk::AttachmentLoadOp::LOAD).build()
+        }
+    }
+
+    // paints the dirty passes, sub passes first
+    pub fn vk_paint_passes(&mut self, passes_todo: &mut Vec<usize>, windows: &mut Vec<VkWindow>, vulkan_cx: &mut VulkanCx) {
+        let mut windows_need_repaint = 0;
+        self.compute_passes_to_repaint(passes_todo, &mut windows_need_repaint);
+        for pass_id in passes_todo.iter() {
+            let result = match self.passes[*pass_id].dep_of.clone() {
+                CxPassDepOf::Window(window_id) => {
+                    let window = if let Some(window) = windows.iter_mut().find( | w | w.window_id == window_id) {window}else {
+                        continue
+                    };
+                    let dpi_factor = window.window_geom.dpi_factor;
+                    self.passes[*pass_id].set_dpi_factor(dpi_factor);
+                    let result = vulkan_cx.update_window_target(window).and_then( | _ | {
+                        self.vk_draw_pass(*pass_id, dpi_factor, Some(&mut window.target), vulkan_cx)
+                    });
+                    if result.is_ok() && self.platform.capture_windows {
+                        match vulkan_cx.read_texture(&mut window.target) {
+                            Ok(pixels) => {
+                                self.platform.window_captures.retain( | capture | capture.window_id != window_id);
+                                self.platform.window_captures.push(VkWindowCapture {
+                                    window_id: window_id,
+                                    width: window.target.width as usize,
+                                    height: window.target.height as usize,
+                                    pixels: pixels
+                                });
+                            },
+                            Err(err) => println!("Cannot capture window {}: {}", window_id, err)
+                        }
+                    }
+                    result
+                },
+                CxPassDepOf::Pass(parent_pass_id) => {
+                    let dpi_factor = self.get_delegated_dpi_factor(parent_pass_id);
+                    self.passes[*pass_id].set_dpi_factor(dpi_factor);
+                    self.vk_draw_pass(*pass_id, dpi_factor, None, vulkan_cx)
+                },
+                CxPassDepOf::None => Ok(())
+            };
+            if let Err(err) = result {
+                println!("Vulkan cannot draw pass {}: {}", pass_id, err);
+            }
+            self.passes[*pass_id].paint_dirty = false;
+        }
+    }
+
+    // construct or destruct windows
+    pub fn vk_update_windows(&mut self, windows: &mut Vec<VkWindow>, vulkan_cx: &mut VulkanCx) {
+        for (index, window) in self.windows.iter_mut().enumerate() {
+
+            window.window_state = match &window.window_state {
+                CxWindowState::Create {inner_size, position, ..} => {
+                    let vk_window = VkWindow::new(index, *inner_size, *position);
+                    window.window_geom = vk_window.window_geom.clone();
+                    windows.push(vk_window);
+                    CxWindowState::Created
+                },
+                CxWindowState::Destroy => {
+                    if let Some(pos) = windows.iter().position( | w | w.window_id == index) {
+                        let mut vk_window = windows.remove(pos);
+                        vulkan_cx.destroy_texture(&mut vk_window.target);
+                    }
+                    CxWindowState::Destroyed
+                },
+                CxWindowState::Created => CxWindowState::Created,
+                CxWindowState::Destroyed => CxWindowState::Destroyed
+            }
+        }
+    }
+
+    pub fn event_loop<F>(&mut self, mut event_handler: F)
+    where F: FnMut(&mut Cx, &mut Event),
+    {
+        self.is_desktop_build = true;
+
+        let mut vulkan_cx = match VulkanCx::new() {
+            Ok(vulkan_cx) => vulkan_cx,
+            Err(err) => panic!("Cannot start vulkan: {}", err)
+        };
+
+        self.vk_compile_all_shaders(&vulkan_cx);
+
+        self.load_fonts_from_file();
+
+        self.call_event_handler(&mut event_handler, &mut Event::Construct);
+
+        self.redraw_child_area(Area::All);
+
+        let time_start = Instant::now();
+        let mut windows: Vec<VkWindow> = Vec::new();
+        let mut timers: Vec<VkTimer> = Vec::new();
+        let mut passes_todo = Vec::new();
+
+        'event_loop: loop {
+            let mut events = Vec::new();
+            let now = Instant::now();
+            for timer in &mut timers {
+                if timer.next <= now {
+                    events.push(Event::Timer(TimerEvent {timer_id: timer.timer_id}));
+                    timer.next = now + timer.interval;
+                }
+            }
+            timers.retain( | timer | timer.repeats || timer.next > now);
+            for (signal_id, value) in VK_SIGNALS.0.lock().unwrap().drain(..) {
+                events.push(Event::Signal(SignalEvent {signal_id: signal_id, value: value}));
+            }
+            events.push(Event::Paint);
+
+            let mut paint_dirty = false;
+            for mut event in events {
+
+                self.process_desktop_pre_event(&mut event, &mut event_handler);
+
+                match &event {
+                    Event::Paint => {
+
+                        let _vsync = self.process_desktop_paint_callbacks(time_start.elapsed().as_secs_f64(), &mut event_handler);
+
+                        self.vk_update_windows(&mut windows, &mut vulkan_cx);
+
+                        while self.platform.start_timer.len() > 0 {
+                            let (timer_id, interval, repeats) = self.platform.start_timer.pop().unwrap();
+                            let interval = Duration::from_millis((interval * 1000.0) as u64);
+                            timers.push(VkTimer {timer_id: timer_id, interval: interval, repeats: repeats, next: Instant::now() + interval});
+                        }
+
+                        while self.platform.stop_timer.len() > 0 {
+                            let timer_id = self.platform.stop_timer.pop().unwrap();
+                            timers.retain( | timer | timer.timer_id != timer_id);
+                        }
+
+                        // shaders reloaded at runtime
+                        if self.shader_recompile_ids.len() > 0 {
+                            self.vk_recompile_shaders(&vulkan_cx);
+                        }
+
+                        self.vk_paint_passes(&mut passes_todo, &mut windows, &mut vulkan_cx);
+                        paint_dirty = self.passes.iter().any( | pass | pass.paint_dirty);
+                    },
+                    Event::None => {
+                    },
+                    _ => {
+                        self.call_event_handler(&mut event_handler, &mut event);
+                    }
+                }
+                if self.process_desktop_post_event(&mut event) || self.platform.quit {
+                    break 'event_loop;
+                }
+            }
+
+            if self.playing_anim_areas.len() == 0 && self.redraw_parent_areas.len() == 0 && self.redraw_child_areas.len() == 0 && self.frame_callbacks.len() == 0 && !paint_dirty {
+                // nothing to draw, sleep until the next timer or signal. without timers there is nothing left to wait for
+                let next_timer = timers.iter().map( | timer | timer.next).min();
+                let signals = VK_SIGNALS.0.lock().unwrap();
+                if signals.len() == 0 {
+                    match next_timer {
+                        Some(next) => {
+                            let wait = next.saturating_duration_since(Instant::now());
+                            let _ = VK_SIGNALS.1.wait_timeout(signals, wait);
+                        },
+                        None => break 'event_loop
+                    }
+                }
+            }
+        }
+
+        for mut window in windows {
+            vulkan_cx.destroy_texture(&mut window.target);
+        }
+        self.vk_destroy_resources(&vulkan_cx);
+    }
+
+    // frees what the shaders, draw calls and textures hold on the device
+    pub fn vk_destroy_resources(&mut self, vulkan_cx: &VulkanCx) {
+        unsafe {
+            let _ = vulkan_cx.device.device_wait_idle();
+        }
+        for sh in &mut self.shaders {
+            if let Some(shp) = sh.platform.take() {
+                vulkan_cx.destroy_shader(&shp);
+            }
+        }
+        for cxview in &mut self.views {
+            for draw_call in &mut cxview.draw_calls {
+                vulkan_cx.destroy_buffer(&mut draw_call.platform.inst_vbuf);
+            }
+        }
+        for cxtexture in &mut self.textures {
+            vulkan_cx.destroy_texture(&mut cxtexture.platform);
+        }
+    }
+
+    pub fn show_text_ime(&mut self, x: f32, y: f32) {
+        self.platform.set_ime_position = Some(Vec2 {x: x, y: y});
+    }
+
+    pub fn hide_text_ime(&mut self) {
+    }
+
+    pub fn set_window_outer_size(&mut self, size: Vec2) {
+        self.platform.set_window_outer_size = Some(size);
+    }
+
+    pub fn set_window_position(&mut self, pos: Vec2) {
+        self.platform.set_window_position = Some(pos);
+    }
+
+    pub fn start_timer(&mut self, interval: f64, repeats: bool) -> Timer {
+        self.timer_id += 1;
+        self.platform.start_timer.push((self.timer_id, interval, repeats));
+        Timer {timer_id: self.timer_id}
+    }
+
+    pub fn stop_timer(&mut self, timer: &mut Timer) {
+        if timer.timer_id != 0 {
+            self.platform.stop_timer.push(timer.timer_id);
+            timer.timer_id = 0;
+        }
+    }
+
+    pub fn send_signal(signal: Signal, value: usize) {
+        VK_SIGNALS.0.lock().unwrap().push((signal.signal_id, value));
+        VK_SIGNALS.1.notify_all();
+    }
+}
+
+// signals from other threads, the event loop sleeps on the condvar when it has nothing to draw
+static VK_SIGNALS: (Mutex<Vec<(usize, usize)>>, Condvar) = (Mutex::new(Vec::new()), Condvar::new());
+
+pub struct VkTimer {
+    pub timer_id: u64,
+    pub interval: Duration,
+    pub repeats: bool,
+    pub next: Instant
+}
+
+// a draw call as it goes into the command buffer
+pub struct VkDraw {
+    pub shader_id: usize,
+    pub sets: Vec<vk::DescriptorSet>,
+    pub inst_vbuf: vk::Buffer,
+    pub instances: u32
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VkPipelineKey {
+    pub color_count: usize,
+    pub depth: bool
+}
+
+// a window without a window system, its main pass renders into target
+pub struct VkWindow {
+    pub window_id: usize,
+    pub window_geom: WindowGeom,
+    pub target: CxPlatformTexture
+}
+
+impl VkWindow {
+    fn new(window_id: usize, inner_size: Vec2, position: Option<Vec2>) -> VkWindow {
+        VkWindow {
+            window_id: window_id,
+            window_geom: WindowGeom {
+                dpi_factor: 1.0,
+                is_fullscreen: false,
+                position: position.unwrap_or(Vec2::zero()),
+                inner_size: inner_size,
+                outer_size: inner_size
+            },
+            target: CxPlatformTexture::default()
+        }
+    }
+}
+
+// the pixels of a window after it painted, as BGRA u32s from the top row down
+#[derive(Clone, Default, Debug)]
+pub struct VkWindowCapture {
+    pub window_id: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct VkBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub size: u64
+}
+
+// what is allocated for one submit, reset when its fence signals
+#[derive(Default)]
+pub struct VkFrame {
+    pub uniform_chunks: Vec<VkBuffer>,
+    pub uniform_chunk: usize,
+    pub uniform_used: u64,
+    pub descriptor_pools: Vec<vk::DescriptorPool>,
+    pub descriptor_pool: usize,
+    pub staging: Vec<VkBuffer>
+}
+
+pub struct VulkanCx {
+    pub entry: ash::Entry,
+    pub instance: ash::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: ash::Device,
+    pub queue: vk::Queue,
+    pub command_pool: vk::CommandPool,
+    pub command_buffer: vk::CommandBuffer,
+    pub fence: vk::Fence,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub uniform_align: u64,
+    pub depth_format: vk::Format,
+    // device name and driver version, compiled shaders are cached per driver
+    pub driver: String,
+    pub uniform_set_layout: vk::DescriptorSetLayout,
+    pub sampler: vk::Sampler,
+    pub empty_texture: CxPlatformTexture,
+    pub frame: VkFrame
+}
+
+impl VulkanCx {
+
+    pub fn new() -> Result<VulkanCx, String> {
+        unsafe {
+            let entry = ash::Entry::load().map_err( | err | format!("cannot load the vulkan loader: {}", err)) ?;
+            let app_info = vk::ApplicationInfo::builder()
+                .application_name(CStr::from_bytes_with_nul(b"makepad\0").unwrap())
+                .api_version(vk::API_VERSION_1_3);
+            let instance = entry.create_instance(&vk::InstanceCreateInfo::builder().application_info(&app_info), None)
+                .map_err( | err | format!("vkCreateInstance failed: {}", err)) ?;
+
+            // a gpu when there is one, lavapipe otherwise
+            let mut candidates = Vec::new();
+            for physical_device in instance.enumerate_physical_devices().map_err( | err | format!("vkEnumeratePhysicalDevices failed: {}", err)) ? {
+                let props = instance.get_physical_device_properties(physical_device);
+                if props.api_version < vk::API_VERSION_1_3 {
+                    continue;
+                }
+                let queue_family = instance.get_physical_device_queue_family_properties(physical_device)
+                    .iter()
+                    .position( | family | family.queue_flags.contains(vk::QueueFlags::GRAPHICS));
+                if let Some(queue_family) = queue_family {
+                    let rank = match props.device_type {
+                        vk::PhysicalDeviceType::DISCRETE_GPU => 0,
+                        vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
+                        vk::PhysicalDeviceType::CPU => 3,
+                        _ => 2
+                    };
+                    candidates.push((rank, physical_device, queue_family as u32, props));
+                }
+            }
+            candidates.sort_by_key( | candidate | candidate.0);
+            let (_, physical_device, queue_family, props) = if let Some(candidate) = candidates.into_iter().next() {candidate}else {
+                instance.destroy_instance(None);
+                return Err("no vulkan 1.3 device with a graphics queue".to_string())
+            };
+
+            let mut features13 = vk::PhysicalDeviceVulkan13Features::builder().dynamic_rendering(true);
+            let queue_info = [vk::DeviceQueueCreateInfo::builder()
+                .queue_family_index(queue_family)
+                .queue_priorities(&[1.0])
+                .build()];
+            let device = instance.create_device(
+                physical_device,
+                &vk::DeviceCreateInfo::builder().queue_create_infos(&queue_info).push_next(&mut features13),
+                None
+            ).map_err( | err | format!("vkCreateDevice failed: {}", err)) ?;
+            let queue = device.get_device_queue(queue_family, 0);
+
+            let command_pool = device.create_command_pool(
+                &vk::CommandPoolCreateInfo::builder()
+                    .queue_family_index(queue_family)
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                None
+            ).map_err( | err | format!("vkCreateCommandPool failed: {}", err)) ?;
+            let command_buffer = device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::builder()
+                    .command_pool(command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(1)
+            ).map_err( | err | format!("vkAllocateCommandBuffers failed: {}", err)) ?[0];
+            let fence = device.create_fence(&vk::FenceCreateInfo::default(), None).map_err( | err | format!("vkCreateFence failed: {}", err)) ?;
+
+            let depth_format = [vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D32_SFLOAT]
+                .iter()
+                .cloned()
+                .find( | format | {
+                    instance.get_physical_device_format_properties(physical_device, *format)
+                        .optimal_tiling_features
+                        .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+                })
+                .unwrap_or(vk::Format::D32_SFLOAT);
+
+            // all three uniform blocks have this layout, a set each
+            let uniform_binding = [vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                .build()];
+            let uniform_set_layout = device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&uniform_binding),
+                None
+            ).map_err( | err | format!("vkCreateDescriptorSetLayout failed: {}", err)) ?;
+
+            let sampler = device.create_sampler(
+                &vk::SamplerCreateInfo::builder()
+                    .mag_filter(vk::Filter::LINEAR)
+                    .min_filter(vk::Filter::LINEAR)
+                    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .max_lod(0.0),
+                None
+            ).map_err( | err | format!("vkCreateSampler failed: {}", err)) ?;
+
+            let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+            let device_name = CStr::from_ptr(props.device_name.as_ptr()).to_string_lossy().to_string();
+            let mut vulkan_cx = VulkanCx {
+                driver: format!("{} {:x}", device_name, props.driver_version),
+                entry: entry,
+                instance: instance,
+                physical_device: physical_device,
+                memory_properties: memory_properties,
+                uniform_align: props.limits.min_uniform_buffer_offset_alignment.max(16),
+                device: device,
+                queue: queue,
+                command_pool: command_pool,
+                command_buffer: command_buffer,
+                fence: fence,
+                depth_format: depth_format,
+                uniform_set_layout: uniform_set_layout,
+                sampler: sampler,
+                empty_texture: CxPlatformTexture::default(),
+                frame: VkFrame::default()
+            };
+
+            // what texture slots sample when the draw call has no texture for them
+            let mut empty = CxTexture::default();
+            empty.desc = TextureDesc {format: TextureFormat::ImageBGRA, width: Some(1), height: Some(1), samples: 1};
+            empty.image_u32 = vec![0];
+            vulkan_cx.begin_commands() ?;
+            vulkan_cx.update_platform_texture_image2d(&mut empty) ?;
+            vulkan_cx.submit_and_wait() ?;
+            vulkan_cx.empty_texture = empty.platform;
+            Ok(vulkan_cx)
+        }
+    }
+
+    pub fn find_memory_type(&self, type_bits: u32, flags: vk::MemoryPropertyFlags) -> Option<u32> {
+        (0..self.memory_properties.memory_type_count).find( | i | {
+            type_bits & (1 << i) != 0 && self.memory_properties.memory_types[*i as usize].property_flags.contains(flags)
+        })
+    }
+
+    pub fn create_buffer(&self, size: u64, usage: vk::BufferUsageFlags) -> Result<VkBuffer, String> {
+        unsafe {
+            let buffer = self.device.create_buffer(
+                &vk::BufferCreateInfo::builder().size(size).usage(usage).sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None
+            ).map_err( | err | format!("vkCreateBuffer failed: {}", err)) ?;
+            let reqs = self.device.get_buffer_memory_requirements(buffer);
+            // everything is written from the cpu, device local memory is left for the images
+            let memory_type = self.find_memory_type(reqs.memory_type_bits, vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT)
+                .ok_or_else( | | "no host visible memory for a buffer".to_string()) ?;
+            let memory = self.device.allocate_memory(
+                &vk::MemoryAllocateInfo::builder().allocation_size(reqs.size).memory_type_index(memory_type),
+                None
+            ).map_err( | err | format!("vkAllocateMemory failed: {}", err)) ?;
+            self.device.bind_buffer_memory(buffer, memory, 0).map_err( | err | format!("vkBindBufferMemory failed: {}", err)) ?;
+            Ok(VkBuffer {buffer: buffer, memory: memory, size: size})
+        }
+    }
+
+    pub fn destroy_buffer(&self, buf: &mut VkBuffer) {
+        if buf.buffer != vk::Buffer::null() {
+            unsafe {
+                self.device.destroy_buffer(buf.buffer, None);
+                self.device.free_memory(buf.memory, None);
+            }
+        }
+        *buf = VkBuffer::default();
+    }
+
+    pub fn write_buffer<T: Copy>(&self, buf: &VkBuffer, offset: u64, data: &[T]) -> Result<(), String> {
+        let bytes = (data.len() * std::mem::size_of::<T>()) as u64;
+        if bytes == 0 {
+            return Ok(())
+        }
+        unsafe {
+            let p = self.device.map_memory(buf.memory, offset, bytes, vk::MemoryMapFlags::empty()).map_err( | err | format!("vkMapMemory failed: {}", err)) ?;
+            std::ptr::copy(data.as_ptr(), p as *mut T, data.len());
+            self.device.unmap_memory(buf.memory);
+        }
+        Ok(())
+    }
+
+    // the buffer grows when the data does not fit
+    pub fn update_buffer<T: Copy>(&self, buf: &mut VkBuffer, usage: vk::BufferUsageFlags, data: &Vec<T>) -> Result<(), String> {
+        let bytes = (data.len().max(1) * std::mem::size_of::<T>()) as u64;
+        if buf.size < bytes {
+            self.destroy_buffer(buf);
+            *buf = self.create_buffer(bytes, usage) ?;
+        }
+        self.write_buffer(buf, 0, data)
+    }
+
+    // uniform data for one submit, padded with zeros to the size of the block
+    pub fn frame_uniforms(&mut self, data: &Vec<f32>, size: u64) -> Result<vk::DescriptorBufferInfo, String> {
+        let size = size.max(16);
+        let mut padded = data.clone();
+        padded.resize((size / 4) as usize, 0.0);
+        let offset = (self.frame.uniform_used + self.uniform_align - 1) / self.uniform_align * self.uniform_align;
+        let fits = self.frame.uniform_chunks.get(self.frame.uniform_chunk).map_or(false, | chunk | offset + size <= chunk.size);
+        let offset = if fits {offset}else {
+            if self.frame.uniform_chunks.len() > 0 {
+                self.frame.uniform_chunk += 1;
+            }
+            if self.frame.uniform_chunk >= self.frame.uniform_chunks.len() || self.frame.uniform_chunks[self.frame.uniform_chunk].size < size {
+                let chunk = self.create_buffer(size.max(VK_UNIFORM_CHUNK_SIZE), vk::BufferUsageFlags::UNIFORM_BUFFER) ?;
+                self.frame.uniform_chunks.insert(self.frame.uniform_chunk, chunk);
+            }
+            0
+        };
+        let chunk = self.frame.uniform_chunks[self.frame.uniform_chunk].clone();
+        self.write_buffer(&chunk, offset, &padded) ?;
+        self.frame.uniform_used = offset + size;
+        Ok(vk::DescriptorBufferInfo {buffer: chunk.buffer, offset: offset, range: size})
+    }
+
+    pub fn alloc_set(&mut self, layout: vk::DescriptorSetLayout) -> Result<vk::DescriptorSet, String> {
+        loop {
+            if self.frame.descriptor_pool >= self.frame.descriptor_pools.len() {
+                let sizes = [
+                    vk::DescriptorPoolSize {ty: vk::DescriptorType::UNIFORM_BUFFER, descriptor_count: VK_DESCRIPTOR_POOL_SETS * 3},
+                    vk::DescriptorPoolSize {ty: vk::DescriptorType::SAMPLER, descriptor_count: VK_DESCRIPTOR_POOL_SETS},
+                    vk::DescriptorPoolSize {ty: vk::DescriptorType::SAMPLED_IMAGE, descriptor_count: VK_DESCRIPTOR_POOL_SETS * 2}
+                ];
+                let pool = unsafe {self.device.create_descriptor_pool(
+                    &vk::DescriptorPoolCreateInfo::builder().max_sets(VK_DESCRIPTOR_POOL_SETS).pool_sizes(&sizes),
+                    None
+                )}.map_err( | err | format!("vkCreateDescriptorPool failed: {}", err)) ?;
+                self.frame.descriptor_pools.push(pool);
+            }
+            let pool = self.frame.descriptor_pools[self.frame.descriptor_pool];
+            let layouts = [layout];
+            match unsafe {self.device.allocate_descriptor_sets(&vk::DescriptorSetAllocateInfo::builder().descriptor_pool(pool).set_layouts(&layouts))} {
+                Ok(sets) => return Ok(sets[0]),
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {
+                    self.frame.descriptor_pool += 1;
+                },
+                Err(err) => return Err(format!("vkAllocateDescriptorSets failed: {}", err))
+            }
+        }
+    }
+
+    pub fn begin_commands(&self) -> Result<(), String> {
+        unsafe {
+            self.device.reset_command_buffer(self.command_buffer, vk::CommandBufferResetFlags::empty()).map_err( | err | format!("vkResetCommandBuffer failed: {}", err)) ?;
+            self.device.begin_command_buffer(
+                self.command_buffer,
+                &vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
+            ).map_err( | err | format!("vkBeginCommandBuffer failed: {}", err))
+        }
+    }
+
+    // submits the recorded commands and waits for them, after which the frame allocations are free again
+    pub fn submit_and_wait(&mut self) -> Result<(), String> {
+        let result = unsafe {
+            let command_buffers = [self.command_buffer];
+            self.device.end_command_buffer(self.command_buffer)
+                .and_then( | _ | self.device.queue_submit(self.queue, &[vk::SubmitInfo::builder().command_buffers(&command_buffers).build()], self.fence))
+                .and_then( | _ | self.device.wait_for_fences(&[self.fence], true, u64::MAX))
+                .and_then( | _ | self.device.reset_fences(&[self.fence]))
+                .map_err( | err | format!("vulkan submit failed: {}", err))
+        };
+        for pool in &self.frame.descriptor_pools {
+            unsafe {
+                let _ = self.device.reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty());
+            }
+        }
+        self.frame.descriptor_pool = 0;
+        self.frame.uniform_chunk = 0;
+        self.frame.uniform_used = 0;
+        for mut staging in std::mem::replace(&mut self.frame.staging, Vec::new()) {
+            self.destroy_buffer(&mut staging);
+        }
+        result
+    }
+
+    pub fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+        match format {
+            vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+            vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+            _ => vk::ImageAspectFlags::COLOR
+        }
+    }
+
+    // records a layout change, everything before it finishes before anything after it starts
+    pub fn transition(&self, tex: &mut CxPlatformTexture, layout: vk::ImageLayout) {
+        if tex.layout == layout || tex.image == vk::Image::null() {
+            return
+        }
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(tex.layout)
+            .new_layout(layout)
+            .src_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(tex.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: Self::format_aspect(tex.format),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1
+            })
+            .build();
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier]
+            );
+        }
+        tex.layout = layout;
+    }
+
+    pub fn create_image(&self, tex: &mut CxPlatformTexture, width: u32, height: u32, format: vk::Format, usage: vk::ImageUsageFlags) -> Result<(), String> {
+        self.destroy_texture(tex);
+        unsafe {
+            let image = self.device.create_image(
+                &vk::ImageCreateInfo::builder()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(format)
+                    .extent(vk::Extent3D {width: width, height: height, depth: 1})
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(usage)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None
+            ).map_err( | err | format!("vkCreateImage failed: {}", err)) ?;
+            let reqs = self.device.get_image_memory_requirements(image);
+            let memory_type = self.find_memory_type(reqs.memory_type_bits, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+                .or_else( | | self.find_memory_type(reqs.memory_type_bits, vk::MemoryPropertyFlags::empty()))
+                .ok_or_else( | | "no memory for an image".to_string()) ?;
+            let memory = self.device.allocate_memory(
+                &vk::MemoryAllocateInfo::builder().allocation_size(reqs.size).memory_type_index(memory_type),
+                None
+            ).map_err( | err | format!("vkAllocateMemory failed: {}", err)) ?;
+            self.device.bind_image_memory(image, memory, 0).map_err( | err | format!("vkBindImageMemory failed: {}", err)) ?;
+            let view = self.device.create_image_view(
+                &vk::ImageViewCreateInfo::builder()
+                    .image(image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(format)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: Self::format_aspect(format),
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1
+                    }),
+                None
+            ).map_err( | err | format!("vkCreateImageView failed: {}", err)) ?;
+            tex.image = image;
+            tex.memory = memory;
+            tex.view = view;
+            tex.format = format;
+            tex.width = width;
+            tex.height = height;
+            tex.layout = vk::ImageLayout::UNDEFINED;
+        }
+        Ok(())
+    }
+
+    pub fn destroy_texture(&self, tex: &mut CxPlatformTexture) {
+        if tex.image != vk::Image::null() {
+            unsafe {
+                self.device.destroy_image_view(tex.view, None);
+                self.device.destroy_image(tex.image, None);
+                self.device.free_memory(tex.memory, None);
+            }
+        }
+        *tex = CxPlatformTexture::default();
+    }
+
+    pub fn update_platform_render_target(&self, cxtexture: &mut CxTexture, dpi_factor: f32, size: Vec2, is_depth: bool) -> Result<(), String> {
+
+        let width = if let Some(width) = cxtexture.desc.width {width as u32} else {(size.x * dpi_factor) as u32};
+        let height = if let Some(height) = cxtexture.desc.height {height as u32} else {(size.y * dpi_factor) as u32};
+
+        if cxtexture.platform.width == width && cxtexture.platform.height == height && cxtexture.platform.alloc_desc == cxtexture.desc {
+            return Ok(())
+        }
+        let (format, usage) = if !is_depth {
+            match cxtexture.desc.format {
+                TextureFormat::Default | TextureFormat::RenderBGRA => (
+                    VK_COLOR_FORMAT,
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC
+                ),
+                _ => return Err("update_platform_render_target unsupported texture format".to_string())
+            }
+        }
+        else {
+            match cxtexture.desc.format {
+                TextureFormat::Default | TextureFormat::Depth24Stencil8 => (
+                    self.depth_format,
+                    vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                ),
+                _ => return Err("update_platform_render_target unsupported depth format".to_string())
+            }
+        };
+        self.create_image(&mut cxtexture.platform, width.max(1), height.max(1), format, usage) ?;
+        cxtexture.platform.alloc_desc = cxtexture.desc.clone();
+        Ok(())
+    }
+
+    // the main pass target of a window, sized to the window
+    pub fn update_window_target(&self, window: &mut VkWindow) -> Result<(), String> {
+        let geom = &window.window_geom;
+        let width = (geom.inner_size.x * geom.dpi_factor) as u32;
+        let height = (geom.inner_size.y * geom.dpi_factor) as u32;
+        if window.target.width == width && window.target.height == height {
+            return Ok(())
+        }
+        self.create_image(
+            &mut window.target,
+            width.max(1),
+            height.max(1),
+            VK_COLOR_FORMAT,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        )
+    }
+
+    // records the upload, the command buffer has to be recording
+    pub fn update_platform_texture_image2d(&mut self, cxtexture: &mut CxTexture) -> Result<(), String> {
+
+        let (width, height) = match (cxtexture.desc.width, cxtexture.desc.height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err("update_platform_texture_image2d without width/height".to_string())
+        };
+        match cxtexture.desc.format {
+            TextureFormat::Default | TextureFormat::ImageBGRA => (),
+            _ => return Err("update_platform_texture_image2d with unsupported format".to_string())
+        }
+        if cxtexture.image_u32.len() != width * height {
+            return Err("update_platform_texture_image2d with wrong buffer_u32 size".to_string())
+        }
+
+        // allocate new texture if descriptor change
+        if cxtexture.platform.alloc_desc != cxtexture.desc || cxtexture.platform.image == vk::Image::null() {
+            self.create_image(
+                &mut cxtexture.platform,
+                width as u32,
+                height as u32,
+                VK_COLOR_FORMAT,
+                vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST
+            ) ?;
+            cxtexture.platform.alloc_desc = cxtexture.desc.clone();
+        }
+
+        let staging = self.create_buffer((width * height * 4) as u64, vk::BufferUsageFlags::TRANSFER_SRC) ?;
+        self.write_buffer(&staging, 0, &cxtexture.image_u32) ?;
+        self.transition(&mut cxtexture.platform, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        unsafe {
+            self.device.cmd_copy_buffer_to_image(
+                self.command_buffer,
+                staging.buffer,
+                cxtexture.platform.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[Self::image_copy_region(width as u32, height as u32)]
+            );
+        }
+        self.transition(&mut cxtexture.platform, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        self.frame.staging.push(staging);
+        cxtexture.upload_image = false;
+        Ok(())
+    }
+
+    fn image_copy_region(width: u32, height: u32) -> vk::BufferImageCopy {
+        vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1
+            },
+            image_offset: vk::Offset3D {x: 0, y: 0, z: 0},
+            image_extent: vk::Extent3D {width: width, height: height, depth: 1}
+        }
+    }
+
+    // copies a color image back to the cpu, in its own submit
+    pub fn read_texture(&mut self, tex: &mut CxPlatformTexture) -> Result<Vec<u32>, String> {
+        let len = (tex.width * tex.height) as usize;
+        let staging = self.create_buffer((len * 4).max(4) as u64, vk::BufferUsageFlags::TRANSFER_DST) ?;
+        self.begin_commands() ?;
+        self.transition(tex, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                self.command_buffer,
+                tex.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                staging.buffer,
+                &[Self::image_copy_region(tex.width, tex.height)]
+            );
+        }
+        self.frame.staging.push(staging.clone());
+        let mut pixels = vec![0u32; len];
+        // the staging buffer is only freed after the copy into pixels
+        let result = unsafe {
+            let command_buffers = [self.command_buffer];
+            self.device.end_command_buffer(self.command_buffer)
+                .and_then( | _ | self.device.queue_submit(self.queue, &[vk::SubmitInfo::builder().command_buffers(&command_buffers).build()], self.fence))
+                .and_then( | _ | self.device.wait_for_fences(&[self.fence], true, u64::MAX))
+                .and_then( | _ | self.device.reset_fences(&[self.fence]))
+                .and_then( | _ | self.device.map_memory(staging.memory, 0, (len * 4) as u64, vk::MemoryMapFlags::empty()))
+                .map( | p | {
+                    std::ptr::copy(p as *const u32, pixels.as_mut_ptr(), len);
+                    self.device.unmap_memory(staging.memory);
+                })
+                .map_err( | err | format!("vulkan readback failed: {}", err))
+        };
+        self.frame.staging.pop();
+        self.destroy_buffer(&mut staging.clone());
+        result.map( | _ | pixels)
+    }
+
+    // the pipeline of a shader for the attachments of a pass, made the first time a pass needs it
+    pub fn pipeline(&self, shp: &mut CxPlatformShader, key: &VkPipelineKey) -> Result<vk::Pipeline, String> {
+        if let Some((_, pipeline)) = shp.pipelines.iter().find( | (k, _) | k == key) {
+            return Ok(*pipeline)
+        }
+        let entry_name = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::builder().stage(vk::ShaderStageFlags::VERTEX).module(shp.vert_module).name(entry_name).build(),
+            vk::PipelineShaderStageCreateInfo::builder().stage(vk::ShaderStageFlags::FRAGMENT).module(shp.frag_module).name(entry_name).build()
+        ];
+        let layout = &shp.layout;
+        let bindings = [
+            vk::VertexInputBindingDescription {binding: 0, stride: layout.geometry.0.max(4), input_rate: vk::VertexInputRate::VERTEX},
+            vk::VertexInputBindingDescription {binding: 1, stride: layout.instance.0.max(4), input_rate: vk::VertexInputRate::INSTANCE}
+        ];
+        let mut attributes = Vec::new();
+        for (binding, attrs) in [(0, &layout.geometry.1), (1, &layout.instance.1)].iter() {
+            for [location, slots, offset] in attrs.iter() {
+                attributes.push(vk::VertexInputAttributeDescription {
+                    location: *location,
+                    binding: *binding,
+                    format: match slots {
+                        1 => vk::Format::R32_SFLOAT,
+                        2 => vk::Format::R32G32_SFLOAT,
+                        3 => vk::Format::R32G32B32_SFLOAT,
+                        _ => vk::Format::R32G32B32A32_SFLOAT
+                    },
+                    offset: *offset
+                });
+            }
+        }
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&bindings)
+            .vertex_attribute_descriptions(&attributes);
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::builder().topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let viewport = vk::PipelineViewportStateCreateInfo::builder().viewport_count(1).scissor_count(1);
+        let rasterization = vk::PipelineRasterizationStateCreateInfo::builder()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1.0);
+        let multisample = vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(vk::SampleCountFlags::TYPE_1);
+        let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(key.depth)
+            .depth_write_enable(key.depth)
+            .depth_compare_op(vk::CompareOp::LESS_OR_EQUAL);
+        // premultiplied alpha, like the other backends
+        let blend_attachments: Vec<vk::PipelineColorBlendAttachmentState> = (0..key.color_count).map( | _ | {
+            vk::PipelineColorBlendAttachmentState::builder()
+                .blend_enable(true)
+                .src_color_blend_factor(vk::BlendFactor::ONE)
+                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .alpha_blend_op(vk::BlendOp::ADD)
+                .color_write_mask(vk::ColorComponentFlags::RGBA)
+                .build()
+        }).collect();
+        let color_blend = vk::PipelineColorBlendStateCreateInfo::builder().attachments(&blend_attachments);
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic = vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+        let color_formats: Vec<vk::Format> = (0..key.color_count).map( | _ | VK_COLOR_FORMAT).collect();
+        let mut rendering = vk::PipelineRenderingCreateInfo::builder()
+            .color_attachment_formats(&color_formats)
+            .depth_attachment_format(if key.depth {self.depth_format}else {vk::Format::UNDEFINED});
+        if key.depth && Self::format_aspect(self.depth_format).contains(vk::ImageAspectFlags::STENCIL) {
+            rendering = rendering.stencil_attachment_format(vk::Format::UNDEFINED);
+        }
+        let info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport)
+            .rasterization_state(&rasterization)
+            .multisample_state(&multisample)
+            .depth_stencil_state(&depth_stencil)
+            .color_blend_state(&color_blend)
+            .dynamic_state(&dynamic)
+            .layout(shp.pipeline_layout)
+            .push_next(&mut rendering)
+            .build();
+        let pipeline = unsafe {self.device.create_graphics_pipelines(vk::PipelineCache::null(), &[info], None)}
+            .map_err( | (_, err) | format!("vkCreateGraphicsPipelines failed: {}", err)) ?[0];
+        shp.pipelines.push((*key, pipeline));
+        Ok(pipeline)
+    }
+
+    pub fn create_shader(&self, vert: &Vec<u32>, frag: &Vec<u32>, layout: VkShaderLayout, sg: &ShaderGen) -> Result<CxPlatformShader, String> {
+        unsafe {
+            let vert_module = self.device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(vert), None)
+                .map_err( | err | format!("vkCreateShaderModule failed: {}", err)) ?;
+            let frag_module = self.device.create_shader_module(&vk::ShaderModuleCreateInfo::builder().code(frag), None)
+                .map_err( | err | format!("vkCreateShaderModule failed: {}", err)) ?;
+            // set 3 has the sampler at binding 0 and the textures after it
+            let mut set_layouts = vec![self.uniform_set_layout; 3];
+            let mut texture_set_layout = vk::DescriptorSetLayout::null();
+            if layout.texture_count > 0 {
+                let mut bindings = vec![vk::DescriptorSetLayoutBinding::builder()
+                    .binding(0)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                    .build()];
+                for i in 0..layout.texture_count {
+                    bindings.push(vk::DescriptorSetLayoutBinding::builder()
+                        .binding(i + 1)
+                        .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                        .build());
+                }
+                texture_set_layout = self.device.create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings),
+                    None
+                ).map_err( | err | format!("vkCreateDescriptorSetLayout failed: {}", err)) ?;
+                set_layouts.push(texture_set_layout);
+            }
+            let pipeline_layout = self.device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts),
+                None
+            ).map_err( | err | format!("vkCreatePipelineLayout failed: {}", err)) ?;
+            let mut geom_vbuf = VkBuffer::default();
+            self.update_buffer(&mut geom_vbuf, vk::BufferUsageFlags::VERTEX_BUFFER, &sg.geometry_vertices) ?;
+            let mut geom_ibuf = VkBuffer::default();
+            self.update_buffer(&mut geom_ibuf, vk::BufferUsageFlags::INDEX_BUFFER, &sg.geometry_indices) ?;
+            Ok(CxPlatformShader {
+                vert_module: vert_module,
+                frag_module: frag_module,
+                texture_set_layout: texture_set_layout,
+                pipeline_layout: pipeline_layout,
+                layout: layout,
+                pipelines: Vec::new(),
+                geom_vbuf: geom_vbuf,
+                geom_ibuf: geom_ibuf
+            })
+        }
+    }
+
+    pub fn destroy_shader(&self, shp: &CxPlatformShader) {
+        unsafe {
+            for (_, pipeline) in &shp.pipelines {
+                self.device.destroy_pipeline(*pipeline, None);
+            }
+            self.device.destroy_pipeline_layout(shp.pipeline_layout, None);
+            if shp.texture_set_layout != vk::DescriptorSetLayout::null() {
+                self.device.destroy_descriptor_set_layout(shp.texture_set_layout, None);
+            }
+            self.device.destroy_shader_module(shp.vert_module, None);
+            self.device.destroy_shader_module(shp.frag_module, None);
+        }
+        self.destroy_buffer(&mut shp.geom_vbuf.clone());
+        self.destroy_buffer(&mut shp.geom_ibuf.clone());
+    }
+}
+
+impl Drop for VulkanCx {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            let mut frame = std::mem::replace(&mut self.frame, VkFrame::default());
+            for chunk in &mut frame.uniform_chunks {
+                self.destroy_buffer(chunk);
+            }
+            for pool in &frame.descriptor_pools {
+                self.device.destroy_descriptor_pool(*pool, None);
+            }
+            let mut empty_texture = self.empty_texture.clone();
+            self.destroy_texture(&mut empty_texture);
+            self.device.destroy_sampler(self.sampler, None);
+            self.device.destroy_descriptor_set_layout(self.uniform_set_layout, None);
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl Cx {
+    pub fn vk_compile_all_shaders(&mut self, vulkan_cx: &VulkanCx) {
+        self.platform.desktop.shader_cache = ShaderCache::new("vulkan", &vulkan_cx.driver);
+        self.profile_begin(SHADER_COMPILE_PROFILE_ID);
+        for sh in &mut self.shaders {
+            let vksh = Self::vk_compile_shader(sh, vulkan_cx, &mut self.platform.desktop.shader_cache);
+            if let Err(err) = vksh {
+                panic!("Got vulkan shader compile error: {}", err.msg);
+            }
+        };
+        self.profile_end(SHADER_COMPILE_PROFILE_ID);
+        self.shader_cache_report();
+    }
+
+    pub fn vk_recompile_shaders(&mut self, vulkan_cx: &VulkanCx) {
+        for shader_id in std::mem::replace(&mut self.shader_recompile_ids, Vec::new()) {
+            let sh = &mut self.shaders[shader_id];
+            // a failed compile keeps drawing with the previous version
+            if let Err(err) = Self::vk_compile_shader(sh, vulkan_cx, &mut self.platform.desktop.shader_cache) {
+                println!("Got vulkan shader recompile error in {}: {}", sh.name, err.msg);
+            }
+        }
+    }
+
+    // the generator errors for reload_shader, before the shader replaces a working one
+    pub fn assemble_platform_shader(sg: &ShaderGen) -> Result<(), SlErr> {
+        Self::glsl450_assemble_shader(sg) ?;
+        Ok(())
+    }
+
+    pub fn vk_compile_shader(sh: &mut CxShader, vulkan_cx: &VulkanCx, cache: &mut ShaderCache) -> Result<(), SlErr> {
+        // a cache hit skips the generator and naga
+        let cached = cache.load(&sh.shader_gen).and_then( | entry | {
+            Some((
+                spirv_words(entry.binary("vert") ?) ?,
+                spirv_words(entry.binary("frag") ?) ?,
+                VkShaderLayout::from_bytes(entry.binary("layout") ?) ?,
+                entry.mapping
+            ))
+        });
+        let (vert, frag, layout, mapping) = match cached {
+            Some(cached) => cached,
+            None => {
+                let glsl = Self::glsl450_assemble_shader(&sh.shader_gen) ?;
+                let vert = glsl450_to_spirv(&glsl.vertex, naga::ShaderStage::Vertex) ?;
+                let frag = glsl450_to_spirv(&glsl.fragment, naga::ShaderStage::Fragment) ?;
+                let layout = VkShaderLayout::from_assembled(&glsl);
+                cache.store(&sh.shader_gen, &ShaderCacheEntry {
+                    source: format!("{}\n{}", glsl.vertex, glsl.fragment),
+                    mapping: glsl.mapping.clone(),
+                    binaries: vec![
+                        ("vert".to_string(), vert.iter().flat_map( | word | word.to_le_bytes().to_vec()).collect()),
+                        ("frag".to_string(), frag.iter().flat_map( | word | word.to_le_bytes().to_vec()).collect()),
+                        ("layout".to_string(), layout.to_bytes())
+                    ]
+                });
+                (vert, frag, layout, glsl.mapping)
+            }
+        };
+
+        let shp = vulkan_cx.create_shader(&vert, &frag, layout, &sh.shader_gen).map_err( | err | SlErr {msg: err}) ?;
+        if let Some(old) = sh.platform.replace(shp) {
+            vulkan_cx.destroy_shader(&old);
+        }
+        sh.mapping = mapping;
+        Ok(())
+    }
+}
+
+// turns one stage of the GLSL 450 output into SPIR-V
+pub fn glsl450_to_spirv(source: &str, stage: naga::ShaderStage) -> Result<Vec<u32>, SlErr> {
+    let module = naga::front::glsl::Frontend::default()
+        .parse(&naga::front::glsl::Options::from(stage), source)
+        .map_err( | err | SlErr {msg: format!("naga cannot parse the GLSL 450: {}", err.emit_to_string(source))}) ?;
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err( | err | SlErr {msg: format!("naga cannot validate the GLSL 450: {}", err.emit_to_string(source))}) ?;
+    // no ADJUST_COORDINATE_SPACE, the generator flips y itself
+    let options = naga::back::spv::Options {
+        flags: naga::back::spv::WriterFlags::LABEL_VARYINGS,
+        ..Default::default()
+    };
+    naga::back::spv::write_vec(&module, &info, &options, None).map_err( | err | SlErr {msg: format!("naga cannot write SPIR-V: {}", err)})
+}
+
+fn spirv_words(data: &Vec<u8>) -> Option<Vec<u32>> {
+    if data.len() == 0 || data.len() % 4 != 0 {
+        return None
+    }
+    Some(data.chunks(4).map( | b | u32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
+
+// what the pipelines need of a shader, cached next to the SPIR-V
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VkShaderLayout {
+    // the stride, and per attribute the location, float slots and byte offset
+    pub geometry: (u32, Vec<[u32; 3]>),
+    pub instance: (u32, Vec<[u32; 3]>),
+    // the byte sizes of the cx, vw and dr uniform blocks, 0 when the shader has no such block
+    pub uniform_sizes: [u32; 3],
+    pub texture_count: u32
+}
+
+impl VkShaderLayout {
+    pub fn from_assembled(glsl: &AssembledGlsl450Shader) -> VkShaderLayout {
+        let vertex_layout = | layout: &WgslVertexLayout | -> (u32, Vec<[u32; 3]>) {
+            (layout.array_stride as u32, layout.attributes.iter().map( | attr | {
+                let slots = if attr.ty == "float" {1}else {attr.ty[3..].parse().unwrap_or(4)};
+                [attr.location as u32, slots, attr.offset as u32]
+            }).collect())
+        };
+        let mut layout = VkShaderLayout {
+            geometry: vertex_layout(&glsl.geometry_layout),
+            instance: vertex_layout(&glsl.instance_layout),
+            ..Default::default()
+        };
+        for binding in &glsl.bindings {
+            match binding.ty {
+                WgslBindingType::Uniform {size} => layout.uniform_sizes[binding.group] = size as u32,
+                WgslBindingType::Texture => layout.texture_count += 1,
+                WgslBindingType::Sampler => ()
+            }
+        }
+        layout
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut words = Vec::new();
+        for (stride, attrs) in &[&self.geometry, &self.instance] {
+            words.push(*stride);
+            words.push(attrs.len() as u32);
+            for attr in attrs {
+                words.extend_from_slice(attr);
+            }
+        }
+        words.extend_from_slice(&self.uniform_sizes);
+        words.push(self.texture_count);
+        words.iter().flat_map( | word | word.to_le_bytes().to_vec()).collect()
+    }
+
+    pub fn from_bytes(data: &Vec<u8>) -> Option<VkShaderLayout> {
+        let mut words = spirv_words(data) ?.into_iter();
+        let mut vertex_layout = | | -> Option<(u32, Vec<[u32; 3]>)> {
+            let stride = words.next() ?;
+            let mut attrs = Vec::new();
+            for _ in 0..words.next() ? {
+                attrs.push([words.next() ?, words.next() ?, words.next() ?]);
+            }
+            Some((stride, attrs))
+        };
+        let geometry = vertex_layout() ?;
+        let instance = vertex_layout() ?;
+        let layout = VkShaderLayout {
+            geometry: geometry,
+            instance: instance,
+            uniform_sizes: [words.next() ?, words.next() ?, words.next() ?],
+            texture_count: words.next() ?
+        };
+        if words.next().is_some() {
+            return None
+        }
+        Some(layout)
+    }
+}
+
+// the platform shader language of the vulkan backend is the GLSL 450 of cx_glsl450
+impl<'a> SlCx<'a> {
+    pub fn map_call(&self, name: &str, args: &Vec<Sl>) -> MapCallResult {
+        self.glsl450_map_call(name, args)
+    }
+
+    pub fn mat_mul(&self, left: &str, right: &str) -> String {
+        self.glsl450_mat_mul(left, right)
+    }
+
+    pub fn map_type(&self, ty: &str) -> String {
+        self.glsl450_map_type(ty)
+    }
+
+    pub fn map_var(&mut self, var: &ShVar) -> String {
+        self.glsl450_map_var(var)
+    }
+}
+
+#[derive(Clone)]
+pub struct CxPlatformShader {
+    pub vert_module: vk::ShaderModule,
+    pub frag_module: vk::ShaderModule,
+    pub texture_set_layout: vk::DescriptorSetLayout,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub layout: VkShaderLayout,
+    pub pipelines: Vec<(VkPipelineKey, vk::Pipeline)>,
+    pub geom_vbuf: VkBuffer,
+    pub geom_ibuf: VkBuffer,
+}
+
+impl PartialEq for CxPlatformShader {
+    fn eq(&self, _other: &Self) -> bool {false}
+}
+
+#[derive(Clone, Default)]
+pub struct CxPlatform {
+    pub set_window_position: Option<Vec2>,
+    pub set_window_outer_size: Option<Vec2>,
+    pub set_ime_position: Option<Vec2>,
+    pub start_timer: Vec<(u64, f64, bool)>,
+    pub stop_timer: Vec<u64>,
+    pub text_clipboard_response: Option<String>,
+    // read the windows back after they paint, into window_captures
+    pub capture_windows: bool,
+    pub window_captures: Vec<VkWindowCapture>,
+    // ends the event loop after the current event
+    pub quit: bool,
+    pub desktop: CxDesktop,
+}
+
+#[derive(Clone, Default)]
+pub struct CxPlatformView {
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct PlatformDrawCall {
+    pub inst_vbuf: VkBuffer
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct CxPlatformTexture {
+    pub alloc_desc: TextureDesc,
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+    // the layout the image is in once the recorded commands ran
+    pub layout: vk::ImageLayout,
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct CxPlatformPass {
+}
+
+// stdin is only piped for children we talk to (gdb, language servers)
+pub fn spawn_process_command(cmd: &str, args: &[&str], current_dir: &str, piped_stdin: bool) -> Result<Child, std::io::Error> {
+    let mut command = Command::new(cmd);
+    if piped_stdin {
+        command.stdin(Stdio::piped());
+    }
+    command .args(args) .stdout(Stdio::piped()) .stderr(Stdio::piped()) .current_dir(current_dir) .spawn()
+}
+
+// a child running on the slave side of a pseudo terminal, the master is our end
+pub struct PtyChild {
+    pub child: Child,
+    pub master: std::fs::File
+}
+
+pub fn spawn_pty_command(cmd: &str, args: &[&str], current_dir: &str, cols: u16, rows: u16) -> Result<PtyChild, std::io::Error> {
+    use std::os::unix::io::FromRawFd;
+    let mut master: libc::c_int = 0;
+    let mut slave: libc::c_int = 0;
+    let mut size = libc::winsize {ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0};
+    unsafe {
+        if libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null_mut(), &mut size) != 0 {
+            return Err(std::io::Error::last_os_error())
+        }
+        let child = Command::new(cmd)
+            .args(args)
+            .env("TERM", "xterm-256color")
+            .current_dir(current_dir)
+            .stdin(Stdio::from_raw_fd(slave))
+            .stdout(Stdio::from_raw_fd(libc::dup(slave)))
+            .stderr(Stdio::from_raw_fd(libc::dup(slave)))
+            .pre_exec(move || {
+                // a new session with the pty as its controlling terminal
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(std::io::Error::last_os_error())
+                }
+                libc::close(master);
+                Ok(())
+            })
+            .spawn();
+        match child {
+            Ok(child) => Ok(PtyChild {child: child, master: std::fs::File::from_raw_fd(master)}),
+            Err(err) => {
+                libc::close(master);
+                Err(err)
+            }
+        }
+    }
+}
+
+pub fn resize_pty(master: &std::fs::File, cols: u16, rows: u16) {
+    use std::os::unix::io::AsRawFd;
+    let size = libc::winsize {ws_row: rows, ws_col: cols, ws_xpixel: 0, ws_ypixel: 0};
+    unsafe {
+        libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    fn assert_spirv(name: &str, sg: ShaderGen) {
+        let glsl = Cx::glsl450_assemble_shader(&sg).unwrap_or_else( | err | panic!("{} does not assemble: {}", name, err.msg));
+        for (source, stage) in &[(&glsl.vertex, naga::ShaderStage::Vertex), (&glsl.fragment, naga::ShaderStage::Fragment)] {
+            let words = glsl450_to_spirv(source, *stage).unwrap_or_else( | err | panic!("{} {:?} does not translate: {}", name, stage, err.msg));
+            assert_eq!(words[0], 0x07230203, "{} {:?} is not SPIR-V", name, stage);
+        }
+        let layout = VkShaderLayout::from_assembled(&glsl);
+        assert_eq!(VkShaderLayout::from_bytes(&layout.to_bytes()), Some(layout));
+    }
+
+    #[test]
+    fn quad_translates_to_spirv() {
+        assert_spirv("quad", Quad::def_quad_shader());
+    }
+
+    #[test]
+    fn text_translates_to_spirv() {
+        assert_spirv("text", Text::def_text_shader());
+    }
+
+    #[test]
+    fn blit_translates_to_spirv() {
+        assert_spirv("blit", Blit::def_blit_shader());
+    }
+
+    #[test]
+    fn shader_layout_rejects_cut_off_bytes() {
+        let glsl = Cx::glsl450_assemble_shader(&Blit::def_blit_shader()).unwrap();
+        let bytes = VkShaderLayout::from_assembled(&glsl).to_bytes();
+        assert_eq!(VkShaderLayout::from_bytes(&bytes[..bytes.len() - 4].to_vec()), None);
+        assert_eq!(VkShaderLayout::from_bytes(&bytes[..bytes.len() - 1].to_vec()), None);
+    }
+
+    // the drawing tests run on whatever vulkan 1.3 device there is, lavapipe without a gpu, and skip without one
+    fn vulkan_cx() -> Option<VulkanCx> {
+        match VulkanCx::new() {
+            Ok(vulkan_cx) => Some(vulkan_cx),
+            Err(err) => {
+                println!("skipping, no vulkan device: {}", err);
+                None
+            }
+        }
+    }
+
+    fn paint<F>(cx: &mut Cx, vulkan_cx: &mut VulkanCx, mut draw: F) -> VkWindowCapture
+    where F: FnMut(&mut Cx)
+    {
+        cx.vk_compile_all_shaders(vulkan_cx);
+        let mut windows = Vec::new();
+        // the first draw creates the window, the second one draws at its size
+        for _ in 0..2 {
+            cx.redraw_child_area(Area::All);
+            cx.call_draw_event( | cx, event | if let Event::Draw = event {draw(cx)});
+            cx.vk_update_windows(&mut windows, vulkan_cx);
+        }
+        cx.platform.capture_windows = true;
+        cx.vk_paint_passes(&mut Vec::new(), &mut windows, vulkan_cx);
+        for mut window in windows {
+            vulkan_cx.destroy_texture(&mut window.target);
+        }
+        cx.vk_destroy_resources(vulkan_cx);
+        cx.platform.window_captures.pop().expect("the window did not paint")
+    }
+
+    fn pixel(capture: &VkWindowCapture, x: usize, y: usize) -> u32 {
+        capture.pixels[y * capture.width + x]
+    }
+
+    const RED: u32 = 0xffff0000;
+    const GREEN: u32 = 0xff00ff00;
+    const BLUE: u32 = 0xff0000ff;
+
+    #[test]
+    fn draws_instanced_quads_in_a_window() {
+        let mut vulkan_cx = if let Some(vulkan_cx) = vulkan_cx() {vulkan_cx}else {return};
+        let mut cx = Cx::default();
+        let mut window = Window {create_inner_size: Vec2 {x: 64., y: 64.}, ..Style::style(&mut cx)};
+        let mut pass = Pass::default();
+        let mut view = View::<NoScrollBar>::style(&mut cx);
+        let mut quad = Quad::style(&mut cx);
+        let capture = paint(&mut cx, &mut vulkan_cx, | cx | {
+            window.begin_window(cx);
+            pass.begin_pass(cx);
+            let _ = view.begin_view(cx, Layout::default());
+            // both quads go in one draw call, as two instances
+            quad.color = Color {r: 1., g: 0., b: 0., a: 1.};
+            quad.draw_quad_abs(cx, Rect {x: 0., y: 0., w: 16., h: 16.});
+            quad.color = Color {r: 0., g: 0., b: 1., a: 1.};
+            quad.draw_quad_abs(cx, Rect {x: 32., y: 32., w: 16., h: 16.});
+            view.end_view(cx);
+            pass.end_pass(cx);
+            window.end_window(cx);
+        });
+        assert_eq!((capture.width, capture.height), (64, 64));
+        assert_eq!(pixel(&capture, 8, 8), RED);
+        assert_eq!(pixel(&capture, 40, 40), BLUE);
+        assert_eq!(pixel(&capture, 56, 8), 0);
+        assert_eq!(pixel(&capture, 8, 56), 0);
+    }
+
+    #[test]
+    fn draws_a_sub_pass_with_color_and_depth_textures() {
+        let mut vulkan_cx = if let Some(vulkan_cx) = vulkan_cx() {vulkan_cx}else {return};
+        let mut cx = Cx::default();
+        let mut window = Window {create_inner_size: Vec2 {x: 64., y: 64.}, ..Style::style(&mut cx)};
+        let mut main_pass = Pass::default();
+        let mut main_view = View::<NoScrollBar>::style(&mut cx);
+        let mut sub_pass = Pass::default();
+        let mut sub_view = View::<NoScrollBar>::style(&mut cx);
+        let mut color_texture = Texture::default();
+        let mut depth_texture = Texture::default();
+        let mut quad = Quad::style(&mut cx);
+        let mut blit = Blit::style(&mut cx);
+        let capture = paint(&mut cx, &mut vulkan_cx, | cx | {
+            window.begin_window(cx);
+            main_pass.begin_pass(cx);
+
+            sub_pass.begin_pass(cx);
+            sub_pass.add_color_texture(cx, &mut color_texture, Some(Color {r: 0., g: 1., b: 0., a: 1.}));
+            sub_pass.set_depth_texture(cx, &mut depth_texture);
+            sub_pass.set_size(cx, Vec2 {x: 32., y: 32.});
+            sub_pass.set_ortho_matrix(cx, Vec2::zero(), Vec2 {x: 32., y: 32.});
+            let _ = sub_view.begin_view(cx, Layout::default());
+            quad.color = Color {r: 1., g: 0., b: 0., a: 1.};
+            quad.draw_quad_abs(cx, Rect {x: 0., y: 0., w: 16., h: 32.});
+            sub_view.end_view(cx);
+            sub_pass.end_pass(cx);
+
+            let _ = main_view.begin_view(cx, Layout::default());
+            blit.draw_blit_abs(cx, &color_texture, Rect {x: 0., y: 0., w: 32., h: 32.});
+            main_view.end_view(cx);
+            main_pass.end_pass(cx);
+            window.end_window(cx);
+        });
+        assert_eq!(pixel(&capture, 4, 4), RED);
+        assert_eq!(pixel(&capture, 4, 28), RED);
+        assert_eq!(pixel(&capture, 28, 4), GREEN);
+        assert_eq!(pixel(&capture, 48, 48), 0);
+    }
+}
@@ -373,6 +373,11 @@ impl Cx {
             self.platform.from_wasm.set_mouse_cursor(MouseCursor::Default);
         }
         
+        // shaders reloaded at runtime
+        if self.shader_recompile_ids.len() > 0 {
+            self.webgl_recompile_shaders();
+        }
+        
         if is_animation_frame && self.paint_dirty {
             self.paint_dirty = false;
             self.repaint_id += 1;
@@ -417,6 +422,11 @@ impl Cx {
         FileReadRequest{read_id:id, path:path.to_string()}
     }
     
+    pub fn read_git_head(&mut self, _path: &str) -> FileReadRequest {
+        // no git on the web
+        FileReadRequest::empty()
+    }
+    
     pub fn write_file(&mut self, _path: &str, _data: &[u8]) -> u64 {
         return 0
     }
@@ -470,6 +480,19 @@ impl Cx {
         };
     }
     
+    pub fn webgl_recompile_shaders(&mut self) {
+        for shader_id in std::mem::replace(&mut self.shader_recompile_ids, Vec::new()) {
+            // a failed compile keeps drawing with the previous version
+            match Self::compile_webgl_shader(shader_id, &self.shaders[shader_id], &mut self.platform) {
+                Ok(csh) => self.compiled_shaders[shader_id] = CompiledShader {
+                    shader_id: shader_id,
+                    ..csh
+                },
+                Err(err) => self.platform.from_wasm.log(&format!("Got webgl shader recompile error: {}", err.msg))
+            }
+        }
+    }
+    
     pub fn compile_webgl_shader(shader_id: usize, sh: &Shader, platform: &mut CxPlatform) -> Result<CompiledShader, SlErr> {
         let ash = Self::gl_assemble_shader(sh, GLShaderType::WebGL1) ?;
         //let shader_id = self.compiled_shaders.len();
@@ -0,0 +1,667 @@
+use crate::cx::*;
+
+// WGSL output of the shader compiler, for webgpu. unlike the platform backends
+// this one is always compiled in, the generator switches to it with SlLang::Wgsl
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WgslStepMode {
+    Vertex,
+    Instance
+}
+
+// offsets are in bytes, locations match the @location of the _Geom and _Inst fields
+#[derive(Clone, Debug)]
+pub struct WgslVertexAttribute {
+    pub name: String,
+    pub ty: String,
+    pub format: String,
+    pub offset: usize,
+    pub location: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct WgslVertexLayout {
+    pub array_stride: usize,
+    pub step_mode: WgslStepMode,
+    pub attributes: Vec<WgslVertexAttribute>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WgslBindingType {
+    Uniform {size: usize},
+    Sampler,
+    Texture
+}
+
+#[derive(Clone, Debug)]
+pub struct WgslBinding {
+    pub group: usize,
+    pub binding: usize,
+    pub name: String,
+    pub ty: WgslBindingType
+}
+
+#[derive(Clone)]
+pub struct AssembledWgslShader {
+    pub source: String,
+    pub geometry_layout: WgslVertexLayout,
+    pub instance_layout: WgslVertexLayout,
+    pub bindings: Vec<WgslBinding>,
+    pub mapping: CxShaderMapping
+}
+
+// the bind groups of the uniform stores and the textures
+pub const WGSL_GROUP_UNIFORMS_CX: usize = 0;
+pub const WGSL_GROUP_UNIFORMS_VW: usize = 1;
+pub const WGSL_GROUP_UNIFORMS_DR: usize = 2;
+pub const WGSL_GROUP_TEXTURES: usize = 3;
+
+impl Cx {
+    pub fn wgsl_type(ty: &str) -> String {
+        match ty.as_ref() {
+            "float" => "f32".to_string(),
+            "int" => "i32".to_string(),
+            "bool" => "bool".to_string(),
+            "vec2" => "vec2<f32>".to_string(),
+            "vec3" => "vec3<f32>".to_string(),
+            "vec4" => "vec4<f32>".to_string(),
+            "bvec2" => "vec2<bool>".to_string(),
+            "bvec3" => "vec3<bool>".to_string(),
+            "bvec4" => "vec4<bool>".to_string(),
+            "mat2" => "mat2x2<f32>".to_string(),
+            "mat3" => "mat3x3<f32>".to_string(),
+            "mat4" => "mat4x4<f32>".to_string(),
+            "texture2d" => "texture_2d<f32>".to_string(),
+            ty => ty.to_string()
+        }
+    }
+    
+    // the number of columns of a matrix type, 0 for anything else
+    pub fn wgsl_mat_columns(ty: &str) -> usize {
+        match ty.as_ref() {
+            "mat2" => 2,
+            "mat3" => 3,
+            "mat4" => 4,
+            _ => 0
+        }
+    }
+    
+    pub fn wgsl_vertex_format(slots: usize) -> String {
+        if slots == 1 {"float32".to_string()}else {format!("float32x{}", slots)}
+    }
+    
+    // uniforms are an array of vec4 slots laid out like NamedProps does it aligned,
+    // this reads a value back out of it with the constructors of the target language
+    pub fn slot_uniform_read(base: &str, ty: &str, offset: usize, slots: usize, map_type: fn(&str) -> String) -> String {
+        let comps = ["x", "y", "z", "w"];
+        if slots > 4 && offset & 3 == 0 && slots & 3 == 0 {
+            let cols: Vec<String> = (0..slots / 4).map( | i | format!("{}.slots[{}]", base, offset / 4 + i)).collect();
+            return format!("{}({})", map_type(ty), cols.join(", "))
+        }
+        if slots <= 4 && (offset & 3) + slots <= 4 {
+            let read = if slots == 4 {
+                format!("{}.slots[{}]", base, offset / 4)
+            }
+            else {
+                format!("{}.slots[{}].{}", base, offset / 4, comps[offset & 3..(offset & 3) + slots].join(""))
+            };
+            return match ty.as_ref() {
+                "int" => format!("{}({})", map_type(ty), read),
+                "bool" => format!("({} != 0.0)", read),
+                "mat2" => format!("{}({}.xy, {}.zw)", map_type(ty), read, read),
+                _ => read
+            }
+        }
+        let scalars: Vec<String> = (offset..offset + slots).map( | i | format!("{}.slots[{}].{}", base, i / 4, comps[i & 3])).collect();
+        format!("{}({})", map_type(ty), scalars.join(", "))
+    }
+    
+    // the uniform blocks that have vars, then the sampler and a binding per texture
+    pub fn wgsl_bindings(sg: &ShaderGen, uniforms_cx: &Vec<ShVar>, uniforms_vw: &Vec<ShVar>, uniforms_dr: &Vec<ShVar>, texture_slots: &Vec<ShVar>) -> Vec<WgslBinding> {
+        let mut bindings = Vec::new();
+        for (vars, group, name) in vec![
+            (uniforms_cx, WGSL_GROUP_UNIFORMS_CX, "_uni_cx"),
+            (uniforms_vw, WGSL_GROUP_UNIFORMS_VW, "_uni_vw"),
+            (uniforms_dr, WGSL_GROUP_UNIFORMS_DR, "_uni_dr")
+        ] {
+            if vars.len() == 0 {
+                continue;
+            }
+            bindings.push(WgslBinding {
+                group: group,
+                binding: 0,
+                name: name.to_string(),
+                ty: WgslBindingType::Uniform {size: NamedProps::construct(sg, vars, true).total_slots * 4}
+            });
+        }
+        if texture_slots.len() > 0 {
+            bindings.push(WgslBinding {
+                group: WGSL_GROUP_TEXTURES,
+                binding: 0,
+                name: "_sampler".to_string(),
+                ty: WgslBindingType::Sampler
+            });
+            for (i, tex) in texture_slots.iter().enumerate() {
+                bindings.push(WgslBinding {
+                    group: WGSL_GROUP_TEXTURES,
+                    binding: i + 1,
+                    name: format!("_tex_{}", tex.name),
+                    ty: WgslBindingType::Texture
+                });
+            }
+        }
+        bindings
+    }
+    
+    pub fn wgsl_assemble_bindings(bindings: &Vec<WgslBinding>) -> String {
+        let mut out = String::new();
+        for binding in bindings {
+            match binding.ty {
+                WgslBindingType::Uniform {size} => {
+                    out.push_str(&format!("struct {}Block {{\n  slots: array<vec4<f32>, {}>\n}};\n", binding.name, size / 16));
+                    out.push_str(&format!("@group({}) @binding({}) var<uniform> {}: {}Block;\n\n", binding.group, binding.binding, binding.name, binding.name));
+                },
+                WgslBindingType::Sampler => {
+                    out.push_str(&format!("@group({}) @binding({}) var {}: sampler;\n", binding.group, binding.binding, binding.name));
+                },
+                WgslBindingType::Texture => {
+                    out.push_str(&format!("@group({}) @binding({}) var {}: texture_2d<f32>;\n", binding.group, binding.binding, binding.name));
+                }
+            }
+        }
+        out
+    }
+    
+    // vertex buffers are packed floats, matrices are split up in an attribute per column
+    pub fn wgsl_vertex_layout(sg: &ShaderGen, name: &str, vars: &Vec<ShVar>, step_mode: WgslStepMode, location: &mut usize) -> Result<WgslVertexLayout, SlErr> {
+        let mut attributes = Vec::new();
+        let mut offset = 0;
+        for var in vars {
+            let cols = Self::wgsl_mat_columns(&var.ty);
+            let fields = if cols > 0 {
+                (0..cols).map( | i | (format!("{}_{}", var.name, i), cols)).collect()
+            }
+            else if var.ty == "float" || var.ty == "vec2" || var.ty == "vec3" || var.ty == "vec4" {
+                vec![(var.name.clone(), sg.get_type_slots(&var.ty))]
+            }
+            else {
+                return Err(SlErr {msg: format!("{} {} has type {}, only float vectors and matrices can be vertex inputs", name, var.name, var.ty)})
+            };
+            for (field, slots) in fields {
+                attributes.push(WgslVertexAttribute {
+                    name: field,
+                    ty: if slots == 1 {"float".to_string()}else {format!("vec{}", slots)},
+                    format: Self::wgsl_vertex_format(slots),
+                    offset: offset * 4,
+                    location: *location
+                });
+                *location += 1;
+                offset += slots;
+            }
+        }
+        Ok(WgslVertexLayout {
+            array_stride: offset * 4,
+            step_mode: step_mode,
+            attributes: attributes
+        })
+        }
+    
+    pub fn wgsl_assemble_vertex_struct(name: &str, layout: &WgslVertexLayout) -> String {
+        if layout.attributes.len() == 0 {
+            return String::new()
+        }
+        let mut out = String::new();
+        out.push_str("struct ");
+        out.push_str(name);
+        out.push_str(" {\n");
+        for attr in &layout.attributes {
+            out.push_str(&format!("  @location({}) {}: {},\n", attr.location, attr.name, Self::wgsl_type(&attr.ty)));
+        }
+        out.push_str("};\n\n");
+        out
+    }
+    
+    pub fn wgsl_assemble_shader(sg: &ShaderGen) -> Result<AssembledWgslShader, SlErr> {
+        
+        sg.type_check() ?;
+        let (opt_sg, opt_report) = sg.optimize() ?;
+        // the mapping keeps the instance layout the widgets write, the gpu gets the optimized one
+        let mapping = CxShaderMapping::construct_optimized(sg, &opt_sg);
+        let sg = &opt_sg;
+        if sg.log != 0 {
+            println!("---- Shader optimizer -----\n{}", opt_report);
+        }
+        
+        // sample2d in branches on instance data is fine for us, naga would refuse it otherwise
+        let mut wgsl_out = "diagnostic(off, derivative_uniformity);\n\n".to_string();
+        
+        let texture_slots = sg.flat_vars(ShVarStore::Texture);
+        let geometries = sg.flat_vars(ShVarStore::Geometry);
+        let instances = sg.flat_vars(ShVarStore::Instance);
+        let mut varyings = sg.flat_vars(ShVarStore::Varying);
+        let locals = sg.flat_vars(ShVarStore::Local);
+        let uniforms_cx = sg.flat_vars(ShVarStore::UniformCx);
+        let uniforms_vw = sg.flat_vars(ShVarStore::UniformVw);
+        let uniforms_dr = sg.flat_vars(ShVarStore::Uniform);
+        
+        // geometry locations come first, the instance ones continue after them
+        let mut location = 0;
+        let geometry_layout = Self::wgsl_vertex_layout(sg, "_Geom", &geometries, WgslStepMode::Vertex, &mut location) ?;
+        let instance_layout = Self::wgsl_vertex_layout(sg, "_Inst", &instances, WgslStepMode::Instance, &mut location) ?;
+        wgsl_out.push_str(&Self::wgsl_assemble_vertex_struct("_Geom", &geometry_layout));
+        wgsl_out.push_str(&Self::wgsl_assemble_vertex_struct("_Inst", &instance_layout));
+        
+        let bindings = Self::wgsl_bindings(sg, &uniforms_cx, &uniforms_vw, &uniforms_dr, &texture_slots);
+        wgsl_out.push_str(&Self::wgsl_assemble_bindings(&bindings));
+            wgsl_out.push_str("\n");
+        
+        let mut const_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Constant,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: Vec::new(),
+            fn_done: Vec::new(),
+            auto_vary: Vec::new(),
+            lang: SlLang::Wgsl
+        };
+        let consts = sg.flat_consts();
+        for cnst in &consts {
+            let const_init = assemble_const_init(cnst, &mut const_cx) ?;
+            wgsl_out.push_str(&format!("const {}: {} = {};\n", cnst.name, Self::wgsl_type(&cnst.ty), const_init.sl));
+        }
+        
+        // all state lives in private globals, so functions dont need the default args
+        let mut vtx_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Vertex,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: vec!["vertex".to_string()],
+            fn_done: Vec::new(),
+            auto_vary: Vec::new(),
+            lang: SlLang::Wgsl
+        };
+        let vtx_fns = assemble_fn_and_deps(sg, &mut vtx_cx) ?;
+        let mut pix_cx = SlCx {
+            depth: 0,
+            target: SlTarget::Pixel,
+            defargs_fn: "".to_string(),
+            defargs_call: "".to_string(),
+            call_prefix: "_".to_string(),
+            shader_gen: sg,
+            scope: Vec::new(),
+            fn_deps: vec!["pixel".to_string()],
+            fn_done: vtx_cx.fn_done,
+            auto_vary: Vec::new(),
+            lang: SlLang::Wgsl
+        };
+        let pix_fns = assemble_fn_and_deps(sg, &mut pix_cx) ?;
+        
+        for auto in &pix_cx.auto_vary {
+            varyings.push(auto.clone());
+        }
+        wgsl_out.push_str("struct _Vary {\n  @builtin(position) _position: vec4<f32>,\n");
+        for (i, vary) in varyings.iter().enumerate() {
+            if vary.ty != "float" && vary.ty != "vec2" && vary.ty != "vec3" && vary.ty != "vec4" {
+                return Err(SlErr {msg: format!("Varying {} has type {}, only float vectors can be passed to the pixel shader", vary.name, vary.ty)})
+            }
+            wgsl_out.push_str(&format!("  @location({}) {}: {},\n", i, vary.name, Self::wgsl_type(&vary.ty)));
+        }
+        wgsl_out.push_str("};\n\n");
+        
+        for shtype in sg.flat_structs() {
+            wgsl_out.push_str(&format!("struct {} {{\n", shtype.name));
+            for field in &shtype.fields {
+                wgsl_out.push_str(&format!("  {}: {},\n", field.name, Self::wgsl_type(&field.ty)));
+            }
+            wgsl_out.push_str("};\n\n");
+        }
+        if locals.len() > 0 {
+            wgsl_out.push_str("struct _Loc {\n");
+            for local in &locals {
+                wgsl_out.push_str(&format!("  {}: {},\n", local.name, Self::wgsl_type(&local.ty)));
+            }
+            wgsl_out.push_str("};\n\n");
+            wgsl_out.push_str("var<private> _loc: _Loc;\n");
+        }
+        if geometries.len() > 0 {
+            wgsl_out.push_str("var<private> _geom: _Geom;\n");
+        }
+        if instances.len() > 0 {
+            wgsl_out.push_str("var<private> _inst: _Inst;\n");
+        }
+        wgsl_out.push_str("var<private> _vary: _Vary;\n\n");
+        
+        wgsl_out.push_str("//Vertex shader\n");
+        wgsl_out.push_str(&vtx_fns);
+        wgsl_out.push_str("//Pixel shader\n");
+        wgsl_out.push_str(&pix_fns);
+        
+        let mut vtx_args = Vec::new();
+        if geometries.len() > 0 {
+            vtx_args.push("geom_in: _Geom");
+        }
+        if instances.len() > 0 {
+            vtx_args.push("inst_in: _Inst");
+        }
+        wgsl_out.push_str(&format!("\n@vertex\nfn _vertex_shader({}) -> _Vary {{\n", vtx_args.join(", ")));
+        if geometries.len() > 0 {
+            wgsl_out.push_str("  _geom = geom_in;\n");
+        }
+        if instances.len() > 0 {
+            wgsl_out.push_str("  _inst = inst_in;\n");
+        }
+        wgsl_out.push_str("  _vary._position = _vertex();\n");
+        for auto in &pix_cx.auto_vary {
+            let base = if let ShVarStore::Geometry = auto.store {"_geom"}else {"_inst"};
+            wgsl_out.push_str(&format!("  _vary.{} = {}.{};\n", auto.name, base, auto.name));
+        }
+        wgsl_out.push_str("  return _vary;\n}\n\n");
+        
+        wgsl_out.push_str("@fragment\nfn _fragment_shader(vary_in: _Vary) -> @location(0) vec4<f32> {\n");
+        wgsl_out.push_str("  _vary = vary_in;\n");
+        wgsl_out.push_str("  return _pixel();\n}\n");
+        
+        if sg.log != 0 {
+            println!("---- WGSL shader -----\n{}", wgsl_out);
+        }
+        
+        Ok(AssembledWgslShader {
+            source: wgsl_out,
+            geometry_layout: geometry_layout,
+            instance_layout: instance_layout,
+            bindings: bindings,
+            mapping: mapping
+        })
+    }
+}
+
+impl<'a> SlCx<'a> {
+    pub fn wgsl_map_call(&self, name: &str, args: &Vec<Sl>) -> MapCallResult {
+        match name {
+            "sample2d" => {
+                let base = &args[0];
+                let coord = &args[1];
+                // only the pixel stage has derivatives for picking a mip
+                if let SlTarget::Pixel = self.target {
+                    return MapCallResult::Rewrite(
+                        format!("textureSample({}, _sampler, {})", base.sl, coord.sl),
+                        "vec4".to_string()
+                    )
+                }
+                return MapCallResult::Rewrite(
+                    format!("textureSampleLevel({}, _sampler, {}, 0.0)", base.sl, coord.sl),
+                    "vec4".to_string()
+                )
+            },
+            "color" => {
+                let col = color(&args[0].sl);
+                return MapCallResult::Rewrite(
+                    format!("vec4<f32>({},{},{},{})", col.r, col.g, col.b, col.a),
+                    "vec4".to_string()
+                );
+            },
+            "dfdx" => return MapCallResult::Rename("dpdx".to_string()),
+            "dfdy" => return MapCallResult::Rename("dpdy".to_string()),
+            "inversesqrt" => return MapCallResult::Rename("inverseSqrt".to_string()),
+            "faceforward" => return MapCallResult::Rename("faceForward".to_string()),
+            "atan" => if args.len() == 2 {
+                return MapCallResult::Rename("atan2".to_string())
+            },
+            "fmod" => {
+                return MapCallResult::Rewrite(
+                    format!("({} % {})", args[0].sl, args[1].sl),
+                    args[0].ty.clone()
+                )
+            },
+            "less_than" | "less_than_equal" | "greater_than" | "greater_than_equal" | "equal" | "not_equal" => {
+                let op = match name {
+                    "less_than" => "<",
+                    "less_than_equal" => "<=",
+                    "greater_than" => ">",
+                    "greater_than_equal" => ">=",
+                    "equal" => "==",
+                    _ => "!="
+                };
+                let ty = match args[0].ty.as_ref() {
+                    "vec2" => "bvec2",
+                    "vec3" => "bvec3",
+                    "vec4" => "bvec4",
+                    _ => "bool"
+                };
+                return MapCallResult::Rewrite(
+                    format!("({} {} {})", args[0].sl, op, args[1].sl),
+                    ty.to_string()
+                )
+            },
+            "not" => {
+                return MapCallResult::Rewrite(
+                    format!("!({})", args[0].sl),
+                    args[0].ty.clone()
+                )
+            },
+            "matrix_comp_mult" => {
+                let cols: Vec<String> = (0..4).map( | i | format!("({})[{}] * ({})[{}]", args[0].sl, i, args[1].sl, i)).collect();
+                return MapCallResult::Rewrite(
+                    format!("mat4x4<f32>({})", cols.join(", ")),
+                    "mat4".to_string()
+                )
+            },
+            _ => ()
+        }
+        // the constructors are builtin fns as well
+        if self.shader_gen.find_type(name).is_some() {
+            return MapCallResult::Rename(Cx::wgsl_type(name))
+        }
+        MapCallResult::None
+    }
+    
+    pub fn wgsl_mat_mul(&self, left: &str, right: &str) -> String {
+        format!("{} * {}", left, right)
+    }
+    
+    pub fn wgsl_map_type(&self, ty: &str) -> String {
+        Cx::wgsl_type(ty)
+    }
+    
+    pub fn wgsl_map_var(&mut self, var: &ShVar) -> String {
+        let uniforms = match var.store {
+            ShVarStore::Uniform => Some(("_uni_dr", ShVarStore::Uniform)),
+            ShVarStore::UniformVw => Some(("_uni_vw", ShVarStore::UniformVw)),
+            ShVarStore::UniformCx => Some(("_uni_cx", ShVarStore::UniformCx)),
+            _ => None
+        };
+        if let Some((base, store)) = uniforms {
+            let props = NamedProps::construct(self.shader_gen, &self.shader_gen.flat_vars(store), true);
+            if let Some(prop) = props.props.iter().find( | prop | prop.name == var.name) {
+                return Cx::slot_uniform_read(base, &var.ty, prop.offset, prop.slots, Cx::wgsl_type)
+            }
+        }
+        match var.store {
+            ShVarStore::Instance | ShVarStore::Geometry => {
+                if let SlTarget::Pixel = self.target {
+                    if self.auto_vary.iter().find( | v | v.name == var.name).is_none() {
+                        self.auto_vary.push(var.clone());
+                    }
+                    return format!("_vary.{}", var.name);
+                }
+                let base = if let ShVarStore::Geometry = var.store {"_geom"}else {"_inst"};
+                let cols = Cx::wgsl_mat_columns(&var.ty);
+                if cols > 0 {
+                    let cols: Vec<String> = (0..cols).map( | i | format!("{}.{}_{}", base, var.name, i)).collect();
+                    return format!("{}({})", Cx::wgsl_type(&var.ty), cols.join(", "))
+                }
+                return format!("{}.{}", base, var.name)
+            },
+            ShVarStore::Texture => return format!("_tex_{}", var.name),
+            ShVarStore::Local => return format!("_loc.{}", var.name),
+            ShVarStore::Varying => return format!("_vary.{}", var.name),
+            _ => return var.name.clone()
+        }
+    }
+}
+
+impl ShFn {
+    // wgsl function arguments are immutable, the ones the body assigns to are copied into a var
+    pub fn wgsl_sl(&self, cx: &mut SlCx) -> Result<Sl, SlErr> {
+        let mut assigned = Vec::new();
+        if let Some(block) = &self.block {
+            wgsl_assigned_in_block(block, &mut assigned);
+        }
+        let mut out = "fn ".to_string();
+        out.push_str(&cx.call_prefix);
+        out.push_str(&self.name);
+        out.push_str("(");
+        for (i, arg) in self.args.iter().enumerate() {
+            if i != 0 {
+                out.push_str(", ");
+            }
+            out.push_str(&arg.name);
+            if assigned.contains(&arg.name) {
+                out.push_str("_in");
+            }
+            out.push_str(": ");
+            out.push_str(&cx.sl_map_type(&arg.ty));
+            cx.scope.push(SlDecl {
+                name: arg.name.clone(),
+                ty: arg.ty.clone()
+            });
+        };
+        out.push_str(")");
+        if self.ret != "void" && self.ret != "" {
+            out.push_str(" -> ");
+            out.push_str(&cx.sl_map_type(&self.ret));
+        }
+        if let Some(block) = &self.block {
+            let block = block.sl(cx) ?;
+            out.push_str(" {\n");
+            for arg in &self.args {
+                if assigned.contains(&arg.name) {
+                    out.push_str(&format!("  var {} = {}_in;\n", arg.name, arg.name));
+                }
+            }
+            // the block starts with its own {\n
+            out.push_str(&block.sl[2..]);
+        };
+        Ok(Sl {
+            sl: out,
+            ty: self.name.clone()
+        })
+    }
+}
+
+fn wgsl_assigned_in_block(block: &ShBlock, out: &mut Vec<String>) {
+    for stmt in &block.stmts {
+        match &**stmt {
+            ShStmt::ShLet(stmt) => wgsl_assigned_in_expr(&stmt.init, out),
+            ShStmt::ShExpr(expr) | ShStmt::ShSemi(expr) => wgsl_assigned_in_expr(expr, out)
+        }
+    }
+}
+
+fn wgsl_assigned_in_expr(expr: &ShExpr, out: &mut Vec<String>) {
+    match expr {
+        ShExpr::ShAssign(x) => {
+            wgsl_assigned_root(&x.left, out);
+            wgsl_assigned_in_expr(&x.right, out);
+        },
+        ShExpr::ShAssignOp(x) => {
+            wgsl_assigned_root(&x.left, out);
+            wgsl_assigned_in_expr(&x.right, out);
+        },
+        ShExpr::ShField(x) => wgsl_assigned_in_expr(&x.base, out),
+        ShExpr::ShIndex(x) => {
+            wgsl_assigned_in_expr(&x.base, out);
+            wgsl_assigned_in_expr(&x.index, out);
+        },
+        ShExpr::ShBinary(x) => {
+            wgsl_assigned_in_expr(&x.left, out);
+            wgsl_assigned_in_expr(&x.right, out);
+        },
+        ShExpr::ShUnary(x) => wgsl_assigned_in_expr(&x.expr, out),
+        ShExpr::ShParen(x) => wgsl_assigned_in_expr(&x.expr, out),
+        ShExpr::ShBlock(x) => wgsl_assigned_in_block(x, out),
+        ShExpr::ShCall(x) => for arg in &x.args {
+            wgsl_assigned_in_expr(arg, out);
+        },
+        ShExpr::ShIf(x) => {
+            wgsl_assigned_in_expr(&x.cond, out);
+            wgsl_assigned_in_block(&x.then_branch, out);
+            if let Some(else_branch) = &x.else_branch {
+                wgsl_assigned_in_expr(else_branch, out);
+            }
+        },
+        ShExpr::ShWhile(x) => {
+            wgsl_assigned_in_expr(&x.cond, out);
+            wgsl_assigned_in_block(&x.body, out);
+        },
+        ShExpr::ShForLoop(x) => {
+            wgsl_assigned_in_expr(&x.from, out);
+            wgsl_assigned_in_expr(&x.to, out);
+            wgsl_assigned_in_block(&x.body, out);
+        },
+        ShExpr::ShReturn(x) => if let Some(expr) = &x.expr {
+            wgsl_assigned_in_expr(expr, out);
+        },
+        ShExpr::ShId(_) | ShExpr::ShLit(_) | ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => ()
+    }
+}
+
+// the name at the root of an assignment target like a.xy or a[i]
+fn wgsl_assigned_root(expr: &ShExpr, out: &mut Vec<String>) {
+    match expr {
+        ShExpr::ShId(x) => if !out.contains(&x.name) {
+            out.push(x.name.clone());
+        },
+        ShExpr::ShField(x) => wgsl_assigned_root(&x.base, out),
+        ShExpr::ShIndex(x) => {
+            wgsl_assigned_root(&x.base, out);
+            wgsl_assigned_in_expr(&x.index, out);
+        },
+        ShExpr::ShParen(x) => wgsl_assigned_root(&x.expr, out),
+        _ => wgsl_assigned_in_expr(expr, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use std::path::Path;
+    
+    // compares with render/tests/wgsl/<name>.wgsl, SHADER_BLESS=1 writes the new output there instead
+    fn assert_golden(name: &str, sg: ShaderGen) {
+        let ash = Cx::wgsl_assemble_shader(&sg).unwrap_or_else( | err | panic!("{} does not assemble: {}", name, err.msg));
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("wgsl").join(format!("{}.wgsl", name));
+        if std::env::var("SHADER_BLESS").is_ok() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &ash.source).unwrap();
+            return
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else( | _ | panic!("missing {}, run with SHADER_BLESS=1", path.display()));
+        if ash.source != expected {
+            panic!("{} differs from {}, run with SHADER_BLESS=1 if the change is intended\n{}", name, path.display(), ash.source);
+        }
+    }
+    
+    #[test]
+    fn quad_matches_golden() {
+        assert_golden("quad", Quad::def_quad_shader());
+    }
+    
+    #[test]
+    fn text_matches_golden() {
+        assert_golden("text", Text::def_text_shader());
+    }
+    
+    #[test]
+    fn blit_matches_golden() {
+        assert_golden("blit", Blit::def_blit_shader());
+    }
+}
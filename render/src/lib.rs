@@ -1,8 +1,10 @@
 #![allow(dead_code)]
 
 // renderer specific modules
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", not(feature = "vulkan")))]
 mod cx_ogl; 
+#[cfg(all(target_os = "linux", feature = "vulkan"))]
+mod cx_vk; 
 
 #[cfg(target_os = "macos")]
 mod cx_mtl; 
@@ -21,11 +23,13 @@ mod cx_win32;
 #[cfg(target_arch = "wasm32")]
 mod cx_webgl; 
 
-#[cfg(any(target_arch = "wasm32", target_os = "linux"))]
+#[cfg(any(target_arch = "wasm32", all(target_os = "linux", not(feature = "vulkan"))))]
 mod cx_glsl; 
 
 #[cfg(any(target_os = "linux", target_os="macos", target_os="windows"))]
 mod cx_desktop; 
+#[cfg(any(target_os = "linux", target_os="macos", target_os="windows"))]
+mod shadercache;
 
 // shared modules
 #[macro_use]
@@ -44,6 +48,13 @@ mod math;
 mod colors;
 mod area;
 mod shadergen;
+mod shadercheck;
+mod cx_wgsl;
+mod cx_glsl450;
+mod shaderparse;
+mod shadereval;
+mod shaderopt;
+mod shadermod;
 mod quad;
 mod blit;
 mod text;
@@ -0,0 +1,421 @@
+// On-disk cache of generated shader source, the shader mapping and compiled program binaries
+// entries are keyed by the ShaderGen hash and the backend, and ignored when they were
+// written under another driver or another build of the app
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fs;
+use std::path::PathBuf;
+use crate::cx::*;
+
+// the profiler slot the startup shader compile is timed in
+pub const SHADER_COMPILE_PROFILE_ID: usize = 0;
+
+const SHADER_CACHE_MAGIC: u32 = 0x4353_504d;
+const SHADER_CACHE_VERSION: u32 = 2;
+
+#[derive(Clone, Default)]
+pub struct ShaderCacheEntry {
+    pub source: String,
+    // the mapping of the optimized shader, so a hit does not run the generator or the optimizer
+    pub mapping: CxShaderMapping,
+    // named program binaries, for backends whose compiled programs can be stored
+    pub binaries: Vec<(String, Vec<u8>)>
+}
+
+impl ShaderCacheEntry {
+    pub fn binary(&self, name: &str) -> Option<&Vec<u8>> {
+        self.binaries.iter().find( | (n, _) | n == name).map( | (_, data) | data)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ShaderCache {
+    // empty turns the cache off
+    pub dir: String,
+    pub backend: String,
+    pub driver: String,
+    pub hits: usize,
+    pub misses: usize,
+    // print the compile time and the hits after the startup compile
+    pub report: bool
+}
+
+impl ShaderCache {
+    // MAKEPAD_SHADER_CACHE picks the directory, set it to an empty string to turn the cache off
+    // MAKEPAD_SHADER_CACHE_REPORT turns on the startup report
+    pub fn new(backend: &str, driver: &str) -> ShaderCache {
+        let dir = match std::env::var("MAKEPAD_SHADER_CACHE") {
+            Ok(dir) => dir,
+            Err(_) => std::env::temp_dir().join("makepad_shader_cache").to_string_lossy().to_string()
+        };
+        ShaderCache {
+            dir: dir,
+            backend: backend.to_string(),
+            // a rebuild can change what the generators emit for the same ShaderGen
+            driver: format!("{} build {}", driver, Self::build_stamp()),
+            hits: 0,
+            misses: 0,
+            report: std::env::var("MAKEPAD_SHADER_CACHE_REPORT").is_ok()
+        }
+    }
+    
+    fn build_stamp() -> u64 {
+        let modified = std::env::current_exe().and_then( | exe | exe.metadata()).and_then( | meta | meta.modified());
+        match modified.map( | time | time.duration_since(std::time::UNIX_EPOCH)) {
+            Ok(Ok(since)) => since.as_secs(),
+            _ => 0
+        }
+    }
+    
+    pub fn key(&self, sg: &ShaderGen) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.backend.hash(&mut hasher);
+        sg.hash(&mut hasher);
+        hasher.finish()
+    }
+    
+    fn entry_path(&self, sg: &ShaderGen) -> PathBuf {
+        PathBuf::from(&self.dir).join(format!("{}_{:016x}.bin", self.backend, self.key(sg)))
+    }
+    
+    pub fn load(&mut self, sg: &ShaderGen) -> Option<ShaderCacheEntry> {
+        if self.dir.len() == 0 {
+            return None
+        }
+        let entry = fs::read(self.entry_path(sg)).ok().and_then( | data | self.parse(data).ok());
+        if entry.is_some() {
+            self.hits += 1;
+        }
+        else {
+            self.misses += 1;
+        }
+        entry
+    }
+    
+    pub fn store(&self, sg: &ShaderGen, entry: &ShaderCacheEntry) {
+        if self.dir.len() == 0 {
+            return
+        }
+        let mut data = Vec::new();
+        data.extend_from_slice(&SHADER_CACHE_MAGIC.to_le_bytes());
+        data.extend_from_slice(&SHADER_CACHE_VERSION.to_le_bytes());
+        push_bytes(&mut data, self.driver.as_bytes());
+        push_bytes(&mut data, entry.source.as_bytes());
+        push_mapping(&mut data, &entry.mapping);
+        data.extend_from_slice(&(entry.binaries.len() as u32).to_le_bytes());
+        for (name, binary) in &entry.binaries {
+            push_bytes(&mut data, name.as_bytes());
+            push_bytes(&mut data, binary);
+        }
+        // write next to it and rename, so a crash never leaves half an entry
+        let path = self.entry_path(sg);
+        let tmp_path = path.with_extension("tmp");
+        let result = fs::create_dir_all(&self.dir)
+            .and_then( | _ | fs::write(&tmp_path, &data))
+            .and_then( | _ | fs::rename(&tmp_path, &path));
+        if let Err(err) = result {
+            println!("Cannot write shader cache entry {}: {}", path.to_string_lossy(), err);
+        }
+    }
+    
+    fn parse(&self, data: Vec<u8>) -> Result<ShaderCacheEntry, String> {
+        let mut read = BinaryReader::new_from_vec("shader cache".to_string(), data);
+        if read.u32() ? != SHADER_CACHE_MAGIC || read.u32() ? != SHADER_CACHE_VERSION {
+            return Err("not a shader cache entry".to_string())
+        }
+        if read_bytes(&mut read) ? != self.driver.as_bytes() {
+            return Err("shader cache entry is from another driver or build".to_string())
+        }
+        let source = read_string(&mut read) ?;
+        let mapping = read_mapping(&mut read) ?;
+        let mut binaries = Vec::new();
+        for _ in 0..read.u32() ? {
+            let name = read_string(&mut read) ?;
+            binaries.push((name, read_bytes(&mut read) ?));
+        }
+        Ok(ShaderCacheEntry {
+            source: source,
+            mapping: mapping,
+            binaries: binaries
+        })
+    }
+}
+
+fn push_bytes(data: &mut Vec<u8>, bytes: &[u8]) {
+    data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+fn read_bytes(read: &mut BinaryReader) -> Result<Vec<u8>, String> {
+    let len = read.u32() ? as usize;
+    if len > read.vec_obj.len() - read.parse as usize {
+        return Err("shader cache entry is cut off".to_string())
+    }
+    let mut bytes = vec![0u8; len];
+    read.read(&mut bytes) ?;
+    Ok(bytes)
+}
+
+fn read_string(read: &mut BinaryReader) -> Result<String, String> {
+    String::from_utf8(read_bytes(read) ?).map_err( | _ | "shader cache string is not utf8".to_string())
+}
+
+fn push_usize(data: &mut Vec<u8>, value: usize) {
+    data.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+// None is stored as u32::MAX
+fn push_option(data: &mut Vec<u8>, value: Option<usize>) {
+    data.extend_from_slice(&value.map_or(std::u32::MAX, | value | value as u32).to_le_bytes());
+}
+
+fn read_option(read: &mut BinaryReader) -> Result<Option<usize>, String> {
+    let value = read.u32() ?;
+    Ok(if value == std::u32::MAX {None}else {Some(value as usize)})
+}
+
+fn push_vars(data: &mut Vec<u8>, vars: &Vec<ShVar>) {
+    push_usize(data, vars.len());
+    for var in vars {
+        push_bytes(data, var.name.as_bytes());
+        push_bytes(data, var.ty.as_bytes());
+    }
+}
+
+fn read_vars(read: &mut BinaryReader, store: ShVarStore) -> Result<Vec<ShVar>, String> {
+    let mut vars = Vec::new();
+    for _ in 0..read.u32() ? {
+        vars.push(ShVar {
+            name: read_string(read) ?,
+            ty: read_string(read) ?,
+            store: store.clone(),
+            loc: ShLoc::default()
+        });
+    }
+    Ok(vars)
+}
+
+fn push_named_props(data: &mut Vec<u8>, named: &NamedProps) {
+    push_usize(data, named.total_slots);
+    push_usize(data, named.props.len());
+    for prop in &named.props {
+        push_bytes(data, prop.name.as_bytes());
+        push_usize(data, prop.offset);
+        push_usize(data, prop.slots);
+    }
+}
+
+fn read_named_props(read: &mut BinaryReader) -> Result<NamedProps, String> {
+    let total_slots = read.u32() ? as usize;
+    let mut props = Vec::new();
+    for _ in 0..read.u32() ? {
+        props.push(NamedProp {
+            name: read_string(read) ?,
+            offset: read.u32() ? as usize,
+            slots: read.u32() ? as usize
+        });
+    }
+    Ok(NamedProps {
+        props: props,
+        total_slots: total_slots
+    })
+}
+
+fn push_mapping(data: &mut Vec<u8>, mapping: &CxShaderMapping) {
+    push_usize(data, mapping.instance_slots);
+    push_usize(data, mapping.geometry_slots);
+    push_vars(data, &mapping.geometries);
+    push_vars(data, &mapping.instances);
+    push_vars(data, &mapping.uniforms_dr);
+    push_vars(data, &mapping.uniforms_vw);
+    push_vars(data, &mapping.uniforms_cx);
+    push_vars(data, &mapping.texture_slots);
+    let rect = &mapping.rect_instance_props;
+    for value in &[rect.x, rect.y, rect.w, rect.h] {
+        push_option(data, *value);
+    }
+    push_named_props(data, &mapping.named_uniform_props);
+    push_named_props(data, &mapping.named_instance_props);
+    push_vars(data, &mapping.gpu_instances);
+    push_usize(data, mapping.instance_gather.len());
+    for slot in &mapping.instance_gather {
+        push_usize(data, *slot);
+    }
+}
+
+fn read_mapping(read: &mut BinaryReader) -> Result<CxShaderMapping, String> {
+    let instance_slots = read.u32() ? as usize;
+    let geometry_slots = read.u32() ? as usize;
+    let geometries = read_vars(read, ShVarStore::Geometry) ?;
+    let instances = read_vars(read, ShVarStore::Instance) ?;
+    let uniforms_dr = read_vars(read, ShVarStore::Uniform) ?;
+    let uniforms_vw = read_vars(read, ShVarStore::UniformVw) ?;
+    let uniforms_cx = read_vars(read, ShVarStore::UniformCx) ?;
+    let texture_slots = read_vars(read, ShVarStore::Texture) ?;
+    let rect_instance_props = RectInstanceProps {
+        x: read_option(read) ?,
+        y: read_option(read) ?,
+        w: read_option(read) ?,
+        h: read_option(read) ?
+    };
+    let named_uniform_props = read_named_props(read) ?;
+    let named_instance_props = read_named_props(read) ?;
+    let gpu_instances = read_vars(read, ShVarStore::Instance) ?;
+    let mut instance_gather = Vec::new();
+    for _ in 0..read.u32() ? {
+        instance_gather.push(read.u32() ? as usize);
+    }
+    Ok(CxShaderMapping {
+        instance_slots: instance_slots,
+        geometry_slots: geometry_slots,
+        geometries: geometries,
+        instances: instances,
+        uniforms_dr: uniforms_dr,
+        uniforms_vw: uniforms_vw,
+        uniforms_cx: uniforms_cx,
+        texture_slots: texture_slots,
+        rect_instance_props: rect_instance_props,
+        named_uniform_props: named_uniform_props,
+        named_instance_props: named_instance_props,
+        gpu_instances: gpu_instances,
+        instance_gather: instance_gather,
+    })
+}
+
+impl Cx {
+    // prints how long the startup shader compile took and how much came from the cache, when the cache report is on
+    pub fn shader_cache_report(&mut self) {
+        let shaders_len = self.shaders.len();
+        let desktop = &mut self.platform.desktop;
+        let cache = &desktop.shader_cache;
+        if let Some(total) = desktop.profiler_totals.get_mut(SHADER_COMPILE_PROFILE_ID) {
+            if cache.report {
+                println!(
+                    "Compiled {} shaders in {} usec, {} from the shader cache in {}",
+                    shaders_len,
+                    *total / 1_000,
+                    cache.hits,
+                    cache.dir
+                );
+            }
+            *total = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    
+    // a cache in its own directory, so the tests don't see each other's entries
+    fn test_cache(name: &str, backend: &str, driver: &str) -> ShaderCache {
+        let dir = std::env::temp_dir().join(format!("makepad_shader_cache_test_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        ShaderCache {
+            dir: dir.to_string_lossy().to_string(),
+            backend: backend.to_string(),
+            driver: driver.to_string(),
+            ..Default::default()
+        }
+    }
+    
+    fn test_entry(sg: &ShaderGen) -> ShaderCacheEntry {
+        let (opt_sg, _) = sg.optimize().unwrap();
+        ShaderCacheEntry {
+            source: "void main(){}".to_string(),
+            mapping: CxShaderMapping::construct_optimized(sg, &opt_sg),
+            binaries: vec![("vs".to_string(), vec![1, 2, 3]), ("ps".to_string(), Vec::new())]
+        }
+    }
+    
+    fn red_quad_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                return vec4(1., 0., 0., 1.);
+            }
+        }))
+    }
+    
+    #[test]
+    fn stores_and_loads_entries() {
+        let sg = red_quad_shader();
+        let entry = test_entry(&sg);
+        let mut cache = test_cache("roundtrip", "test", "driver 1");
+        assert!(cache.load(&sg).is_none());
+        cache.store(&sg, &entry);
+        let loaded = cache.load(&sg).unwrap();
+        assert_eq!((cache.hits, cache.misses), (1, 1));
+        assert_eq!(loaded.source, entry.source);
+        assert_eq!(loaded.binary("vs"), Some(&vec![1, 2, 3]));
+        assert_eq!(loaded.binary("ps"), Some(&Vec::new()));
+        assert!(loaded.binary("fs").is_none());
+        
+        // the mapping comes back as the optimizer left it, gather and all
+        let (lm, em) = (&loaded.mapping, &entry.mapping);
+        assert_eq!(lm.instance_slots, em.instance_slots);
+        assert_eq!(lm.geometry_slots, em.geometry_slots);
+        assert_eq!(lm.instance_gather, em.instance_gather);
+        assert!(lm.instance_gather.len() > 0);
+        let names = | vars: &Vec<ShVar> | vars.iter().map( | var | format!("{}:{}", var.name, var.ty)).collect::<Vec<_>>();
+        assert_eq!(names(&lm.instances), names(&em.instances));
+        assert_eq!(names(&lm.gpu_instances), names(&em.gpu_instances));
+        assert_eq!(names(&lm.uniforms_cx), names(&em.uniforms_cx));
+        assert_eq!(names(&lm.texture_slots), names(&em.texture_slots));
+        assert!(lm.instances.iter().all( | var | var.store == ShVarStore::Instance));
+        assert_eq!(lm.rect_instance_props.x, em.rect_instance_props.x);
+        assert_eq!(lm.rect_instance_props.h, em.rect_instance_props.h);
+        let props = | named: &NamedProps | named.props.iter().map( | prop | (prop.name.clone(), prop.offset, prop.slots)).collect::<Vec<_>>();
+        assert_eq!(props(&lm.named_instance_props), props(&em.named_instance_props));
+        assert_eq!(props(&lm.named_uniform_props), props(&em.named_uniform_props));
+        assert_eq!(lm.named_uniform_props.total_slots, em.named_uniform_props.total_slots);
+    }
+    
+    #[test]
+    fn keys_on_the_shader_and_the_backend() {
+        let sg = red_quad_shader();
+        let mut cache = test_cache("keys", "test", "driver 1");
+        cache.store(&sg, &test_entry(&sg));
+        assert!(cache.load(&Quad::def_quad_shader()).is_none());
+        
+        let mut other_backend = ShaderCache {backend: "other".to_string(), ..cache.clone()};
+        assert_ne!(other_backend.key(&sg), cache.key(&sg));
+        assert!(other_backend.load(&sg).is_none());
+        assert!(cache.load(&sg).is_some());
+    }
+    
+    #[test]
+    fn ignores_entries_of_another_driver() {
+        let sg = red_quad_shader();
+        let cache = test_cache("driver", "test", "driver 1");
+        cache.store(&sg, &test_entry(&sg));
+        let mut updated = ShaderCache {driver: "driver 2".to_string(), ..cache.clone()};
+        assert!(updated.load(&sg).is_none());
+        assert_eq!(updated.misses, 1);
+        
+        // the new driver writes over it, after which the old one misses
+        updated.store(&sg, &test_entry(&sg));
+        assert!(updated.load(&sg).is_some());
+        assert!(ShaderCache {..cache}.load(&sg).is_none());
+    }
+    
+    #[test]
+    fn ignores_cut_off_entries() {
+        let sg = red_quad_shader();
+        let mut cache = test_cache("cut_off", "test", "driver 1");
+        cache.store(&sg, &test_entry(&sg));
+        let path = std::path::PathBuf::from(&cache.dir).join(format!("test_{:016x}.bin", cache.key(&sg)));
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[0..data.len() - 2]).unwrap();
+        assert!(cache.load(&sg).is_none());
+    }
+    
+    #[test]
+    fn empty_dir_turns_the_cache_off() {
+        let sg = red_quad_shader();
+        let mut cache = ShaderCache {dir: String::new(), ..test_cache("off", "test", "driver 1")};
+        cache.store(&sg, &test_entry(&sg));
+        assert!(cache.load(&sg).is_none());
+        assert_eq!(cache.misses, 0);
+    }
+}
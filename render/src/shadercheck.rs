@@ -0,0 +1,891 @@
+// Type checks a composed ShaderGen before it goes to the glsl/metal/hlsl generators,
+// so mistakes are reported at the line in the shader_ast! block and not as broken generated code
+
+use std::fmt;
+use crate::shadergen::*;
+use crate::shadermod::*;
+
+#[derive(Clone, Debug)]
+pub struct ShTypeError {
+    pub msg: String,
+    pub loc: ShLoc,
+    // empty outside of a function
+    pub fn_name: String
+}
+
+impl fmt::Display for ShTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.fn_name.len() > 0 {
+            write!(f, "{}: {} (in fn {})", self.loc, self.msg, self.fn_name)
+        }
+        else {
+            write!(f, "{}: {}", self.loc, self.msg)
+        }
+    }
+}
+
+impl From<Vec<ShTypeError>> for SlErr {
+    fn from(errors: Vec<ShTypeError>) -> SlErr {
+        let lines: Vec<String> = errors.iter().map( | err | err.to_string()).collect();
+        SlErr {msg: lines.join("\n")}
+    }
+}
+
+// the functions that only exist in the pixel shader
+const PIXEL_ONLY_FNS: [&str; 3] = ["dfdx", "dfdy", "fwidth"];
+
+// components of a type when used in a constructor, float/int/bool are interchangeable there
+fn type_components(ty: &str) -> Option<usize> {
+    match ty {
+        "float" | "int" | "bool" => Some(1),
+        "vec2" | "bvec2" => Some(2),
+        "vec3" | "bvec3" => Some(3),
+        "vec4" | "bvec4" | "mat2" => Some(4),
+        "mat3" => Some(9),
+        "mat4" => Some(16),
+        _ => None
+    }
+}
+
+fn vec_size(ty: &str) -> Option<usize> {
+    match ty {
+        "float" => Some(1),
+        "vec2" => Some(2),
+        "vec3" => Some(3),
+        "vec4" => Some(4),
+        _ => None
+    }
+}
+
+fn is_float_like(ty: &str) -> bool {
+    vec_size(ty).is_some()
+}
+
+fn is_float_based(ty: &str) -> bool {
+    is_float_like(ty) || ty == "mat2" || ty == "mat3" || ty == "mat4"
+}
+
+fn is_bool_like(ty: &str) -> bool {
+    ty == "bool" || ty == "bvec2" || ty == "bvec3" || ty == "bvec4"
+}
+
+fn vec_of_size(size: usize) -> String {
+    if size == 1 {"float".to_string()}else {format!("vec{}", size)}
+}
+
+// the type of a swizzle like .xy or .rgba on a float or vector of base_size components
+fn swizzle_type(base_size: usize, member: &str) -> Result<String, String> {
+    if member.len() == 0 || member.len() > 4 {
+        return Err(format!("swizzle .{} can have at most 4 components", member))
+    }
+    let mut set = None;
+    for chr in member.chars() {
+        let (chr_set, index) = if let Some(index) = "xyzw".find(chr) {(0, index)}
+        else if let Some(index) = "rgba".find(chr) {(1, index)}
+        else {
+            return Err(format!("{} is not a swizzle component, use xyzw or rgba", chr))
+        };
+        if *set.get_or_insert(chr_set) != chr_set {
+            return Err(format!("swizzle .{} mixes xyzw and rgba", member))
+        }
+        if index >= base_size {
+            return Err(format!("swizzle .{} reads component {} of a {}", member, chr, vec_of_size(base_size)))
+        }
+    }
+    Ok(vec_of_size(member.len()))
+}
+
+// the result type of a binary op, mirroring what ShBinary::sl can generate
+fn binary_type(op: &ShBinOp, left: &str, right: &str) -> Result<String, String> {
+    let mismatch = || Err(format!("cannot use {} on {} and {}", op.to_string(), left, right));
+    match op {
+        ShBinOp::And | ShBinOp::Or => {
+            if left == "bool" && right == "bool" {Ok("bool".to_string())}else {mismatch()}
+        },
+        ShBinOp::Eq | ShBinOp::Ne => {
+            if left != right {
+                mismatch()
+            }
+            else if left == "float" || left == "int" || left == "bool" {
+                Ok("bool".to_string())
+            }
+            else {
+                Err(format!("cannot use {} on {}, compare vectors with equal or not_equal", op.to_string(), left))
+            }
+        },
+        ShBinOp::Lt | ShBinOp::Le | ShBinOp::Gt | ShBinOp::Ge => {
+            if left == right && (left == "float" || left == "int") {Ok("bool".to_string())}
+            else if left == right && is_float_like(left) {
+                Err(format!("cannot use {} on {}, compare vectors with less_than or greater_than", op.to_string(), left))
+            }
+            else {mismatch()}
+        },
+        ShBinOp::Add | ShBinOp::Sub | ShBinOp::Mul | ShBinOp::Div => {
+            if left == right && (left == "int" || is_float_based(left)) {
+                Ok(left.to_string())
+            }
+            else if left == "float" && is_float_like(right) {
+                Ok(right.to_string())
+            }
+            else if right == "float" && is_float_like(left) {
+                Ok(left.to_string())
+            }
+            else if let ShBinOp::Mul = op {
+                // only vector times matrix, thats the order mat_mul generates
+                match (left, right) {
+                    ("vec2", "mat2") | ("vec3", "mat3") | ("vec4", "mat4") => Ok(left.to_string()),
+                    _ => mismatch()
+                }
+            }
+            else if (left == "int" && right == "float") || (left == "float" && right == "int") {
+                Err(format!("cannot use {} on {} and {}, convert with float() or int()", op.to_string(), left, right))
+            }
+            else {
+                mismatch()
+            }
+        },
+        ShBinOp::Rem => {
+            if left == "int" && right == "int" {Ok("int".to_string())}
+            else if is_float_like(left) {Err(format!("cannot use % on {}, use fmod", left))}
+            else {mismatch()}
+        },
+        ShBinOp::BitXor | ShBinOp::BitAnd | ShBinOp::BitOr | ShBinOp::Shl | ShBinOp::Shr => {
+            if left == "int" && right == "int" {Ok("int".to_string())}else {mismatch()}
+        },
+        _ => Err(format!("{} is not a binary operator", op.to_string()))
+    }
+}
+
+fn assign_op_to_binary(op: &ShBinOp) -> ShBinOp {
+    match op {
+        ShBinOp::AddEq => ShBinOp::Add,
+        ShBinOp::SubEq => ShBinOp::Sub,
+        ShBinOp::MulEq => ShBinOp::Mul,
+        ShBinOp::DivEq => ShBinOp::Div,
+        ShBinOp::RemEq => ShBinOp::Rem,
+        ShBinOp::BitXorEq => ShBinOp::BitXor,
+        ShBinOp::BitAndEq => ShBinOp::BitAnd,
+        ShBinOp::BitOrEq => ShBinOp::BitOr,
+        ShBinOp::ShlEq => ShBinOp::Shl,
+        ShBinOp::ShrEq => ShBinOp::Shr,
+        op => op.clone()
+    }
+}
+
+// what one function does that matters for the stage it ends up in
+#[derive(Default)]
+struct ShFnUse {
+    calls: Vec<String>,
+    varying_writes: Vec<(String, ShLoc)>,
+    pixel_only: Vec<(String, ShLoc)>
+}
+
+struct ShCheckCx<'a> {
+    sg: &'a ShaderGen,
+    fn_name: String,
+    ret: String,
+    scope: Vec<(String, String)>,
+    loop_depth: usize,
+    returns: usize,
+    loc: ShLoc,
+    fn_use: ShFnUse,
+    errors: Vec<ShTypeError>
+}
+
+impl<'a> ShCheckCx<'a> {
+    fn new(sg: &'a ShaderGen) -> Self {
+        Self {
+            sg: sg,
+            fn_name: String::new(),
+            ret: String::new(),
+            scope: Vec::new(),
+            loop_depth: 0,
+            returns: 0,
+            loc: ShLoc::default(),
+            fn_use: ShFnUse::default(),
+            errors: Vec::new()
+        }
+    }
+    
+    fn error(&mut self, loc: ShLoc, msg: String) {
+        self.errors.push(ShTypeError {
+            msg: msg,
+            loc: loc,
+            fn_name: self.fn_name.clone()
+        });
+    }
+    
+    fn is_type(&self, ty: &str) -> bool {
+        ty == "texture2d" || self.sg.find_type(ty).is_some()
+    }
+    
+    fn scan_scope(&self, name: &str) -> Option<&str> {
+        self.scope.iter().rev().find( | (n, _) | n == name).map( | (_, ty) | ty.as_ref())
+    }
+    
+    // the types are strings like everywhere else, an empty one means an error was already reported
+    fn expr(&mut self, expr: &ShExpr) -> String {
+        match expr {
+            ShExpr::ShId(x) => {
+                self.loc = x.loc;
+                if let Some(ty) = self.scan_scope(&x.name) {
+                    ty.to_string()
+                }
+                else if let Some(cnst) = self.sg.find_const(&x.name) {
+                    cnst.ty.clone()
+                }
+                else if let Some(var) = self.sg.find_var(&x.name) {
+                    var.ty.clone()
+                }
+                else if let Some(module) = module_of(&x.name) {
+                    self.error(x.loc, format!("cannot find {}, is the shader module imported with use {};?", x.name, module));
+                    String::new()
+                }
+                else {
+                    self.error(x.loc, format!("cannot find {}, is it declared?", x.name));
+                    String::new()
+                }
+            },
+            ShExpr::ShLit(x) => match x {
+                ShLit::Int(_) => "int".to_string(),
+                ShLit::Float(_) => "float".to_string(),
+                ShLit::Str(_) => "string".to_string(),
+                ShLit::Bool(_) => "bool".to_string()
+            },
+            ShExpr::ShField(x) => {
+                let base = self.expr(&x.base);
+                self.loc = x.loc;
+                if base.len() == 0 {
+                    return base
+                }
+                if let Some(size) = vec_size(&base) {
+                    return match swizzle_type(size, &x.member) {
+                        Ok(ty) => ty,
+                        Err(msg) => {
+                            self.error(x.loc, msg);
+                            String::new()
+                        }
+                    }
+                }
+                if let Some(field) = self.sg.find_type(&base).and_then( | ty | ty.fields.iter().find( | f | f.name == x.member)) {
+                    return field.ty.clone()
+                }
+                self.error(x.loc, format!("{} has no field {}", base, x.member));
+                String::new()
+            },
+            ShExpr::ShIndex(x) => {
+                let base = self.expr(&x.base);
+                let index = self.expr(&x.index);
+                if index.len() > 0 && index != "int" {
+                    self.error(x.loc, format!("index has to be an int, got {}", index));
+                }
+                match base.as_ref() {
+                    "" => base,
+                    "vec2" | "vec3" | "vec4" => "float".to_string(),
+                    "mat2" => "vec2".to_string(),
+                    "mat3" => "vec3".to_string(),
+                    "mat4" => "vec4".to_string(),
+                    _ => {
+                        self.error(x.loc, format!("cannot index a {}", base));
+                        String::new()
+                    }
+                }
+            },
+            ShExpr::ShAssign(x) => {
+                let left = self.expr(&x.left);
+                let right = self.expr(&x.right);
+                self.assignable(&x.left, x.loc);
+                if left.len() > 0 && right.len() > 0 && left != right {
+                    self.error(x.loc, format!("cannot assign a {} to a {}", right, left));
+                }
+                left
+            },
+            ShExpr::ShAssignOp(x) => {
+                let left = self.expr(&x.left);
+                let right = self.expr(&x.right);
+                self.assignable(&x.left, x.loc);
+                if left.len() > 0 && right.len() > 0 {
+                    match binary_type(&assign_op_to_binary(&x.op), &left, &right) {
+                        Ok(ty) => if ty != left {
+                            self.error(x.loc, format!("{} on {} and {} gives a {}, not a {}", x.op.to_string(), left, right, ty, left));
+                        },
+                        Err(msg) => self.error(x.loc, msg)
+                    }
+                }
+                left
+            },
+            ShExpr::ShBinary(x) => {
+                let left = self.expr(&x.left);
+                let right = self.expr(&x.right);
+                if left.len() == 0 || right.len() == 0 {
+                    return String::new()
+                }
+                match binary_type(&x.op, &left, &right) {
+                    Ok(ty) => ty,
+                    Err(msg) => {
+                        self.error(x.loc, msg);
+                        String::new()
+                    }
+                }
+            },
+            ShExpr::ShUnary(x) => {
+                let ty = self.expr(&x.expr);
+                if ty.len() == 0 {
+                    return ty
+                }
+                let ok = match x.op {
+                    ShUnaryOp::Not => ty == "bool",
+                    ShUnaryOp::Neg => ty == "int" || is_float_based(&ty)
+                };
+                if !ok {
+                    self.error(x.loc, format!("cannot use {} on {}", x.op.to_string(), ty));
+                    return String::new()
+                }
+                ty
+            },
+            ShExpr::ShParen(x) => self.expr(&x.expr),
+            ShExpr::ShBlock(x) => {
+                self.block(x);
+                "void".to_string()
+            },
+            ShExpr::ShCall(x) => self.call(x),
+            ShExpr::ShIf(x) => {
+                self.cond(&x.cond, "if");
+                self.block(&x.then_branch);
+                if let Some(else_branch) = &x.else_branch {
+                    self.expr(else_branch);
+                }
+                "void".to_string()
+            },
+            ShExpr::ShWhile(x) => {
+                self.cond(&x.cond, "while");
+                self.loop_depth += 1;
+                self.block(&x.body);
+                self.loop_depth -= 1;
+                "void".to_string()
+            },
+            ShExpr::ShForLoop(x) => {
+                for range in &[&x.from, &x.to] {
+                    let ty = self.expr(range);
+                    if ty.len() > 0 && ty != "int" {
+                        let loc = self.loc;
+                        self.error(loc, format!("for loop ranges are ints, got {}", ty));
+                    }
+                }
+                self.scope.push((x.iter.clone(), "int".to_string()));
+                self.loop_depth += 1;
+                self.block(&x.body);
+                self.loop_depth -= 1;
+                self.scope.pop();
+                "void".to_string()
+            },
+            ShExpr::ShReturn(x) => {
+                self.loc = x.loc;
+                self.returns += 1;
+                let ty = if let Some(expr) = &x.expr {self.expr(expr)}else {"void".to_string()};
+                if ty.len() > 0 && ty != self.ret {
+                    if self.ret == "void" {
+                        self.error(x.loc, format!("fn {} has no return type but returns a {}", self.fn_name, ty));
+                    }
+                    else {
+                        self.error(x.loc, format!("fn {} returns a {}, not a {}", self.fn_name, self.ret, ty));
+                    }
+                }
+                "void".to_string()
+            },
+            ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => {
+                if self.loop_depth == 0 {
+                    let loc = self.loc;
+                    self.error(loc, "break or continue outside of a loop".to_string());
+                }
+                "void".to_string()
+            }
+        }
+    }
+    
+    fn cond(&mut self, cond: &ShExpr, what: &str) {
+        let ty = self.expr(cond);
+        if ty.len() > 0 && ty != "bool" {
+            let loc = self.loc;
+            self.error(loc, format!("{} condition has to be a bool, got {}", what, ty));
+        }
+    }
+    
+    fn block(&mut self, block: &ShBlock) {
+        let scope_len = self.scope.len();
+        for stmt in &block.stmts {
+            match &**stmt {
+                ShStmt::ShLet(x) => {
+                    let init = self.expr(&x.init);
+                    self.loc = x.loc;
+                    let ty = if x.ty.len() == 0 {
+                        init
+                    }
+                    else if !self.is_type(&x.ty) {
+                        self.error(x.loc, format!("unknown type {} for {}", x.ty, x.name));
+                        String::new()
+                    }
+                    else {
+                        if init.len() > 0 && init != x.ty {
+                            self.error(x.loc, format!("{} is declared as {} but initialized with a {}", x.name, x.ty, init));
+                        }
+                        x.ty.clone()
+                    };
+                    if ty == "void" {
+                        self.error(x.loc, format!("{} is initialized with something that has no value", x.name));
+                    }
+                    self.scope.push((x.name.clone(), ty));
+                },
+                ShStmt::ShExpr(x) | ShStmt::ShSemi(x) => {
+                    self.expr(x);
+                }
+            }
+        }
+        self.scope.truncate(scope_len);
+    }
+    
+    // only locals, lets, args and varyings can be written to
+    fn assignable(&mut self, expr: &ShExpr, loc: ShLoc) {
+        match expr {
+            ShExpr::ShParen(x) => self.assignable(&x.expr, loc),
+            ShExpr::ShIndex(x) => self.assignable(&x.base, loc),
+            ShExpr::ShField(x) => {
+                let mut members: Vec<char> = x.member.chars().collect();
+                members.sort();
+                members.dedup();
+                if members.len() != x.member.len() {
+                    self.error(x.loc, format!("cannot assign to swizzle .{} with a repeated component", x.member));
+                }
+                self.assignable(&x.base, loc)
+            },
+            ShExpr::ShId(x) => {
+                if self.scan_scope(&x.name).is_some() {
+                    return
+                }
+                if self.sg.find_const(&x.name).is_some() {
+                    self.error(x.loc, format!("cannot assign to const {}", x.name));
+                }
+                else if let Some(var) = self.sg.find_var(&x.name) {
+                    match var.store {
+                        ShVarStore::Local => (),
+                        ShVarStore::Varying => self.fn_use.varying_writes.push((x.name.clone(), x.loc)),
+                        _ => self.error(x.loc, format!("cannot assign to {}, it has {} storage", x.name, store_name(&var.store)))
+                    }
+                }
+            },
+            _ => self.error(loc, "can only assign to a variable, a field or an index".to_string())
+        }
+    }
+    
+    fn call(&mut self, x: &ShCall) -> String {
+        let args: Vec<String> = x.args.iter().map( | arg | self.expr(arg)).collect();
+        self.loc = x.loc;
+        let sg = self.sg;
+        if let Some(shfn) = sg.find_fn(&x.call) {
+            self.fn_use.calls.push(x.call.clone());
+            if PIXEL_ONLY_FNS.contains(&x.call.as_ref()) {
+                self.fn_use.pixel_only.push((x.call.clone(), x.loc));
+            }
+            if args.iter().any( | ty | ty.len() == 0) {
+                return if shfn.ret == "T" || shfn.ret == "B" {String::new()}else {shfn.ret.clone()}
+            }
+            if shfn.block.is_some() {
+                if args.len() != shfn.args.len() {
+                    self.error(x.loc, format!("{} takes {} arguments, got {}", x.call, shfn.args.len(), args.len()));
+                }
+                for (i, (arg, fnarg)) in args.iter().zip(shfn.args.iter()).enumerate() {
+                    if *arg != fnarg.ty {
+                        self.error(x.loc, format!("argument {} ({}) of {} is a {}, got a {}", i + 1, fnarg.name, x.call, fnarg.ty, arg));
+                    }
+                }
+                return shfn.ret.clone()
+            }
+            // a builtin with the generic T, F, B and O markers
+            if args.len() > shfn.args.len() {
+                self.error(x.loc, format!("{} takes at most {} arguments, got {}", x.call, shfn.args.len(), args.len()));
+                return String::new()
+            }
+            let mut gen_t = String::new();
+            for (i, fnarg) in shfn.args.iter().enumerate() {
+                let arg = if let Some(arg) = args.get(i) {arg}else {
+                    if fnarg.ty != "O" {
+                        self.error(x.loc, format!("{} takes {} arguments, got {}", x.call, shfn.args.len(), args.len()));
+                        return String::new()
+                    }
+                    continue;
+                };
+                let msg = match fnarg.ty.as_ref() {
+                    "T" | "O" => if gen_t.len() > 0 && gen_t != *arg {
+                        Some(format!("argument {} of {} has to be a {} like the ones before it, got a {}", i + 1, x.call, gen_t, arg))
+                    }
+                    else if !is_float_based(arg) && x.call != "sizeof" {
+                        Some(format!("argument {} of {} has to be a float, vec or mat, got a {}", i + 1, x.call, arg))
+                    }
+                    else {
+                        gen_t = arg.clone();
+                        None
+                    },
+                    "F" => if !is_float_like(arg) {
+                        Some(format!("argument {} of {} has to be a float or vec, got a {}", i + 1, x.call, arg))
+                    }
+                    else {
+                        None
+                    },
+                    "B" => if !is_bool_like(arg) {
+                        Some(format!("argument {} of {} has to be a bool, got a {}", i + 1, x.call, arg))
+                    }
+                    else {
+                        gen_t = arg.clone();
+                        None
+                    },
+                    ty => if ty != arg {
+                        Some(format!("argument {} of {} has to be a {}, got a {}", i + 1, x.call, ty, arg))
+                    }
+                    else {
+                        None
+                    }
+                };
+                if let Some(msg) = msg {
+                    self.error(x.loc, msg);
+                    return String::new()
+                }
+            }
+            return if shfn.ret == "T" || shfn.ret == "B" {gen_t}else {shfn.ret.clone()}
+        }
+        // its a constructor call
+        if let Some(ty) = sg.find_type(&x.call) {
+            if args.iter().any( | ty | ty.len() == 0) {
+                return ty.name.clone()
+            }
+            if let Some(size) = type_components(&ty.name) {
+                let mut total = 0;
+                for arg in &args {
+                    if let Some(comps) = type_components(arg) {
+                        total += comps;
+                    }
+                    else {
+                        self.error(x.loc, format!("cannot build a {} from a {}", ty.name, arg));
+                        return ty.name.clone()
+                    }
+                }
+                // a single scalar fills all components
+                let splat = args.len() == 1 && total == 1;
+                if total != size && !splat {
+                    self.error(x.loc, format!("{} needs {} components, got {}", ty.name, size, total));
+                }
+            }
+            else if args.len() != ty.fields.len() || args.iter().zip(ty.fields.iter()).any( | (arg, field) | *arg != field.ty) {
+                let fields: Vec<&str> = ty.fields.iter().map( | f | f.ty.as_ref()).collect();
+                self.error(x.loc, format!("{} is built from ({}), got ({})", ty.name, fields.join(", "), args.join(", ")));
+            }
+            return ty.name.clone()
+        }
+        if let Some(module) = module_of(&x.call) {
+            self.error(x.loc, format!("cannot find function {}, is the shader module imported with use {};?", x.call, module));
+        }
+        else {
+        self.error(x.loc, format!("cannot find function {}", x.call));
+        }
+        String::new()
+    }
+}
+
+fn declares(ast: &ShAst, name: &str) -> bool {
+    ast.vars.iter().any( | var | var.name == name)
+        || ast.consts.iter().any( | cnst | cnst.name == name)
+        || ast.fns.iter().any( | shfn | shfn.name == name)
+}
+
+// the module of a name like df::circle that was not resolved
+fn module_of(name: &str) -> Option<&str> {
+    name.find("::").map( | pos | &name[0..pos])
+}
+
+fn store_name(store: &ShVarStore) -> &'static str {
+    match store {
+        ShVarStore::Uniform => "Uniform",
+        ShVarStore::UniformVw => "UniformVw",
+        ShVarStore::UniformCx => "UniformCx",
+        ShVarStore::Instance => "Instance",
+        ShVarStore::Geometry => "Geometry",
+        ShVarStore::Texture => "Texture",
+        ShVarStore::Local => "Local",
+        ShVarStore::Varying => "Varying"
+    }
+}
+
+impl ShaderGen {
+    // checks the vars, consts and every function the vertex and pixel shader reach
+    pub fn type_check(&self) -> Result<(), Vec<ShTypeError>> {
+        let mut cx = ShCheckCx::new(self);
+        
+        // modules keep to their prefix so they cannot collide with each other or with the shader
+        for ast in &self.asts {
+            for import in &ast.imports {
+                if shader_module(&import.name).is_none() {
+                    cx.error(import.loc, format!("unknown shader module {}, there are {}", import.name, SHADER_MODULES.join(", ")));
+                }
+            }
+            let names = ast.vars.iter().map( | var | (&var.name, var.loc))
+                .chain(ast.consts.iter().map( | cnst | (&cnst.name, cnst.loc)))
+                .chain(ast.fns.iter().map( | shfn | (&shfn.name, shfn.loc)));
+            for (name, loc) in names {
+                if ast.module.len() > 0 {
+                    if !name.starts_with(&format!("{}_", ast.module)) {
+                        cx.error(loc, format!("shader module {} declares {}, its names have to start with {}_", ast.module, name, ast.module));
+                    }
+                }
+                else if let Some(module) = self.asts.iter().find( | module | module.module.len() > 0 && declares(module, name)) {
+                    cx.error(loc, format!("{} is also declared by the shader module {}", name, module.module));
+                }
+            }
+        }
+        
+        // struct fields have to be declared before use, that also keeps structs from containing themselves
+        let structs = self.flat_structs();
+        for (index, shtype) in structs.iter().enumerate() {
+            for field in &shtype.fields {
+                if !cx.is_type(&field.ty) || field.ty == "texture2d" {
+                    cx.error(ShLoc::default(), format!("struct {} field {} has unusable type {}", shtype.name, field.name, field.ty));
+                }
+                else if let Some(pos) = structs.iter().position( | ty | ty.name == field.ty) {
+                    if pos >= index {
+                        cx.error(ShLoc::default(), format!("struct {} field {} uses struct {} before it is declared", shtype.name, field.name, field.ty));
+                    }
+                }
+            }
+        }
+        
+        let mut vars: Vec<&ShVar> = Vec::new();
+        for ast in &self.asts {
+            for var in &ast.vars {
+                if !cx.is_type(&var.ty) {
+                    cx.error(var.loc, format!("unknown type {} for {}", var.ty, var.name));
+                }
+                else if let ShVarStore::Texture = var.store {
+                    if var.ty != "texture2d" {
+                        cx.error(var.loc, format!("{} has Texture storage so it has to be a texture2d", var.name));
+                    }
+                }
+                else if var.ty == "texture2d" {
+                    cx.error(var.loc, format!("{} is a texture2d so it needs Texture storage", var.name));
+                }
+                else if var.store != ShVarStore::Local && !is_float_based(&var.ty) {
+                    cx.error(var.loc, format!("{} has {} storage which only holds floats, vecs and mats, not a {}", var.name, store_name(&var.store), var.ty));
+                }
+                if let Some(prev) = vars.iter().find( | prev | prev.name == var.name) {
+                    if prev.ty != var.ty || prev.store != var.store {
+                        cx.error(var.loc, format!("{} is declared again as {}<{}>, it was {}<{}> at {}", var.name, var.ty, store_name(&var.store), prev.ty, store_name(&prev.store), prev.loc));
+                    }
+                }
+                vars.push(var);
+            }
+        }
+        
+        for cnst in self.flat_consts() {
+            let ty = cx.expr(&cnst.value);
+            if ty.len() > 0 && ty != cnst.ty {
+                cx.error(cnst.loc, format!("const {} is a {} but its value is a {}", cnst.name, cnst.ty, ty));
+            }
+            let calls = std::mem::replace(&mut cx.fn_use.calls, Vec::new());
+            for call in calls {
+                if self.find_fn(&call).map_or(false, | shfn | shfn.block.is_some()) {
+                    cx.error(cnst.loc, format!("const {} cannot call fn {}", cnst.name, call));
+                }
+            }
+        }
+        cx.fn_use = ShFnUse::default();
+        
+        // check every function the entry points reach, once, and remember what they use
+        let mut fn_uses: Vec<(String, ShFnUse)> = Vec::new();
+        let mut todo = Vec::new();
+        for entry in &["vertex", "pixel"] {
+            if let Some(shfn) = self.find_fn(entry) {
+                if shfn.args.len() != 0 || shfn.ret != "vec4" {
+                    cx.error(ShLoc::default(), format!("fn {} has to look like fn {}() -> vec4", entry, entry));
+                }
+                todo.push(entry.to_string());
+            }
+            else {
+                cx.error(ShLoc::default(), format!("shader has no fn {}", entry));
+            }
+        }
+        while let Some(name) = todo.pop() {
+            if fn_uses.iter().any( | (n, _) | *n == name) {
+                continue;
+            }
+            let shfn = if let Some(shfn) = self.find_fn(&name) {shfn}else {continue};
+            let block = if let Some(block) = &shfn.block {block}else {continue};
+            cx.fn_name = name.clone();
+            cx.ret = shfn.ret.clone();
+            cx.returns = 0;
+            cx.loc = ShLoc::default();
+            cx.scope.truncate(0);
+            for arg in &shfn.args {
+                if !cx.is_type(&arg.ty) {
+                    cx.error(ShLoc::default(), format!("unknown type {} for argument {}", arg.ty, arg.name));
+                }
+                cx.scope.push((arg.name.clone(), arg.ty.clone()));
+            }
+            if shfn.ret != "void" && !cx.is_type(&shfn.ret) {
+                cx.error(ShLoc::default(), format!("unknown return type {}", shfn.ret));
+            }
+            cx.block(block);
+            if shfn.ret != "void" && cx.returns == 0 {
+                let loc = cx.loc;
+                cx.error(loc, format!("fn {} never returns its {}", name, shfn.ret));
+            }
+            let fn_use = std::mem::replace(&mut cx.fn_use, ShFnUse::default());
+            todo.extend(fn_use.calls.iter().cloned());
+            fn_uses.push((name, fn_use));
+        }
+        cx.fn_name = String::new();
+        
+        // varyings are written by the vertex shader and derivatives only exist in the pixel shader
+        for entry in &["vertex", "pixel"] {
+            let mut reached: Vec<&String> = Vec::new();
+            let mut stack = vec![entry.to_string()];
+            while let Some(name) = stack.pop() {
+                if let Some((name, fn_use)) = fn_uses.iter().find( | (n, _) | *n == name) {
+                    if reached.contains(&name) {
+                        continue;
+                    }
+                    reached.push(name);
+                    stack.extend(fn_use.calls.iter().cloned());
+                }
+            }
+            for (name, fn_use) in fn_uses.iter().filter( | (n, _) | reached.contains(&n)) {
+                if *entry == "pixel" {
+                    for (var, loc) in &fn_use.varying_writes {
+                        cx.errors.push(ShTypeError {
+                            msg: format!("varying {} can only be written by the vertex shader", var),
+                            loc: *loc,
+                            fn_name: name.clone()
+                        });
+                    }
+                }
+                else {
+                    for (call, loc) in &fn_use.pixel_only {
+                        cx.errors.push(ShTypeError {
+                            msg: format!("{} only works in the pixel shader", call),
+                            loc: *loc,
+                            fn_name: name.clone()
+                        });
+                    }
+                }
+            }
+        }
+        
+        if cx.errors.len() > 0 {
+            return Err(cx.errors)
+        }
+        Ok(())
+    }
+    
+    // for tests, panics with every type error of the shader
+    pub fn assert_type_check(&self) {
+        if let Err(errors) = self.type_check() {
+            let lines: Vec<String> = errors.iter().map( | err | err.to_string()).collect();
+            panic!("shader {} has type errors:\n{}", self.name, lines.join("\n"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    
+    #[test]
+    fn builtin_shaders_type_check() {
+        Quad::def_quad_shader().assert_type_check();
+        Text::def_text_shader().assert_type_check();
+        Blit::def_blit_shader().assert_type_check();
+    }
+    
+    #[test]
+    fn return_type_mismatch_is_reported() {
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                return vec3(1., 0., 0.);
+            }
+        }));
+        let errors = sg.type_check().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].fn_name, "pixel");
+        assert!(errors[0].loc.line > 0);
+    }
+    
+    #[test]
+    #[should_panic(expected = "has type errors")]
+    fn assert_type_check_panics_on_errors() {
+        Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                return undefined_var;
+            }
+        })).assert_type_check();
+    }
+    
+    fn quad_errors(sg: ShaderGen) -> Vec<String> {
+        sg.type_check().unwrap_err().iter().map( | err | err.msg.clone()).collect()
+    }
+    
+    fn assert_error(errors: &[String], msg: &str) {
+        assert!(errors.iter().any( | err | err == msg), "expected {:?} in {:?}", msg, errors);
+    }
+    
+    #[test]
+    fn invalid_swizzles_are_reported() {
+        let errors = quad_errors(Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                let a: vec3 = vec3(geom.xy, geom.z);
+                let b: vec2 = color.xg;
+                let c: vec2 = color.xq;
+                return vec4(a, b.x + c.x);
+            }
+        })));
+        assert_error(&errors, "swizzle .z reads component z of a vec2");
+        assert_error(&errors, "swizzle .xg mixes xyzw and rgba");
+        assert_error(&errors, "q is not a swizzle component, use xyzw or rgba");
+    }
+    
+    #[test]
+    fn constructor_arity_is_reported() {
+        let errors = quad_errors(Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                let v: vec3 = vec3(geom, 1., 2.);
+                return vec4(v.xy, 1.);
+            }
+        })));
+        assert_error(&errors, "vec3 needs 3 components, got 4");
+        assert_error(&errors, "vec4 needs 4 components, got 3");
+    }
+    
+    #[test]
+    fn storage_qualifiers_are_reported() {
+        let errors = quad_errors(Quad::def_quad_shader().compose(shader_ast!({
+            let count: int<Instance>;
+            fn pixel() -> vec4 {
+                w = 2.;
+                return vec4(float(count), 0., 0., 1.);
+            }
+        })));
+        assert_error(&errors, "count has Instance storage which only holds floats, vecs and mats, not a int");
+        assert_error(&errors, "cannot assign to w, it has Instance storage");
+    }
+    
+    #[test]
+    fn function_signatures_are_reported() {
+        let errors = quad_errors(Quad::def_quad_shader().compose(shader_ast!({
+            fn scale(v: float, f: float) -> float {
+                return v * f;
+            }
+            fn pixel() -> vec4 {
+                let a: float = scale(1.);
+                let b: float = scale(geom, 2.);
+                return vec4(a, b, 0., 1.);
+            }
+        })));
+        assert_error(&errors, "scale takes 2 arguments, got 1");
+        assert_error(&errors, "argument 1 (v) of scale is a float, got a vec2");
+    }
+}
@@ -0,0 +1,1015 @@
+// CPU evaluator for shader functions
+// runs the ShFn bodies of a ShaderGen with concrete values, the builtins included
+// so distance field helpers and pixel shaders can be checked without a GPU
+
+use std::collections::HashMap;
+use crate::shadergen::*;
+use crate::math::*;
+use crate::colors::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShValue {
+    Void,
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    // vec2, vec3 and vec4 by length
+    Vec(Vec<f32>),
+    BVec(Vec<bool>),
+    // mat2, mat3 and mat4 by length, column major like Mat4
+    Mat(Vec<f32>),
+    Struct(String, Vec<(String, ShValue)>),
+    // a texture var, sampled through ShEval::textures
+    Texture(String)
+}
+
+impl ShValue {
+    pub fn ty(&self) -> String {
+        match self {
+            ShValue::Void => "void".to_string(),
+            ShValue::Bool(_) => "bool".to_string(),
+            ShValue::Int(_) => "int".to_string(),
+            ShValue::Float(_) => "float".to_string(),
+            ShValue::Vec(v) => format!("vec{}", v.len()),
+            ShValue::BVec(v) => format!("bvec{}", v.len()),
+            ShValue::Mat(v) => format!("mat{}", mat_dim(v.len())),
+            ShValue::Struct(name, _) => name.clone(),
+            ShValue::Texture(_) => "texture2d".to_string()
+        }
+    }
+    
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            ShValue::Float(v) => Some(*v),
+            ShValue::Int(v) => Some(*v as f32),
+            _ => None
+        }
+    }
+    
+    pub fn as_vec2(&self) -> Option<Vec2> {
+        match self {
+            ShValue::Vec(v) if v.len() == 2 => Some(Vec2 {x: v[0], y: v[1]}),
+            _ => None
+        }
+    }
+    
+    pub fn as_vec3(&self) -> Option<Vec3> {
+        match self {
+            ShValue::Vec(v) if v.len() == 3 => Some(Vec3 {x: v[0], y: v[1], z: v[2]}),
+            _ => None
+        }
+    }
+    
+    pub fn as_vec4(&self) -> Option<Vec4> {
+        match self {
+            ShValue::Vec(v) if v.len() == 4 => Some(Vec4 {x: v[0], y: v[1], z: v[2], w: v[3]}),
+            _ => None
+        }
+    }
+    
+    // the float components of a scalar, vector or matrix
+    fn comps(&self) -> Option<Vec<f32>> {
+        match self {
+            ShValue::Float(v) => Some(vec![*v]),
+            ShValue::Int(v) => Some(vec![*v as f32]),
+            ShValue::Vec(v) | ShValue::Mat(v) => Some(v.clone()),
+            _ => None
+        }
+    }
+    
+    // a value shaped like this one with other components
+    fn reshape(&self, comps: Vec<f32>) -> ShValue {
+        match self {
+            ShValue::Mat(_) => ShValue::Mat(comps),
+            _ => if comps.len() == 1 {ShValue::Float(comps[0])}else {ShValue::Vec(comps)}
+        }
+    }
+    
+    fn is_scalar(&self) -> bool {
+        match self {
+            ShValue::Float(_) | ShValue::Int(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl From<f32> for ShValue {
+    fn from(v: f32) -> ShValue {ShValue::Float(v)}
+}
+
+impl From<Vec2> for ShValue {
+    fn from(v: Vec2) -> ShValue {ShValue::Vec(vec![v.x, v.y])}
+}
+
+impl From<Vec3> for ShValue {
+    fn from(v: Vec3) -> ShValue {ShValue::Vec(vec![v.x, v.y, v.z])}
+}
+
+impl From<Vec4> for ShValue {
+    fn from(v: Vec4) -> ShValue {ShValue::Vec(vec![v.x, v.y, v.z, v.w])}
+}
+
+impl From<Color> for ShValue {
+    fn from(v: Color) -> ShValue {ShValue::Vec(vec![v.r, v.g, v.b, v.a])}
+}
+
+impl From<Mat4> for ShValue {
+    fn from(v: Mat4) -> ShValue {ShValue::Mat(v.v.to_vec())}
+}
+
+fn mat_dim(len: usize) -> usize {
+    match len {
+        4 => 2,
+        9 => 3,
+        _ => 4
+    }
+}
+
+// how evaluation leaves an expression other than with a value
+enum ShEvalFlow {
+    Return(ShValue),
+    Break,
+    Continue,
+    Err(String)
+}
+
+fn eval_err<T>(msg: String) -> Result<T, ShEvalFlow> {
+    Err(ShEvalFlow::Err(msg))
+}
+
+pub struct ShEval<'a> {
+    pub shader_gen: &'a ShaderGen,
+    // the values of the shader vars, declared vars that are not set start out as zero
+    pub vars: HashMap<String, ShValue>,
+    // sample2d looks the texture var up here
+    pub textures: HashMap<String, Box<dyn Fn(Vec2) -> Vec4>>,
+    // there are no neighbouring pixels on the cpu, so dfdx, dfdy and fwidth assume their argument
+    // is a position that moves pixel_size per pixel along x and y. they return pixel_size
+    // whatever the argument is, dfdx(p * 2.) is not twice dfdx(p) here
+    pub pixel_size: f32,
+    scopes: Vec<Vec<(String, ShValue)>>,
+    frame: usize
+}
+
+impl<'a> ShEval<'a> {
+    pub fn new(sg: &'a ShaderGen) -> Self {
+        ShEval {
+            shader_gen: sg,
+            vars: HashMap::new(),
+            textures: HashMap::new(),
+            pixel_size: 1.0,
+            scopes: Vec::new(),
+            frame: 0
+        }
+    }
+    
+    pub fn set_var<T: Into<ShValue>>(&mut self, name: &str, value: T) {
+        self.vars.insert(name.to_string(), value.into());
+    }
+    
+    pub fn get_var(&mut self, name: &str) -> Result<ShValue, SlErr> {
+        let ret = self.read_var(name);
+        self.flow_result(ret)
+    }
+    
+    // call a shader function or builtin, returns what it returns
+    pub fn call(&mut self, name: &str, args: Vec<ShValue>) -> Result<ShValue, SlErr> {
+        let ret = self.call_fn(name, args);
+        self.flow_result(ret)
+    }
+    
+    // evaluate a single expression, like the value of a const
+    pub fn eval(&mut self, expr: &ShExpr) -> Result<ShValue, SlErr> {
+        let ret = self.eval_expr(expr);
+        self.flow_result(ret)
+    }
+    
+    fn flow_result(&self, ret: Result<ShValue, ShEvalFlow>) -> Result<ShValue, SlErr> {
+        match ret {
+            Ok(value) | Err(ShEvalFlow::Return(value)) => Ok(value),
+            Err(ShEvalFlow::Err(msg)) => Err(SlErr {msg: msg}),
+            Err(_) => Err(SlErr {msg: "break or continue outside of a loop".to_string()})
+        }
+    }
+    
+    pub fn zero_value(&self, ty: &str) -> Result<ShValue, SlErr> {
+        Ok(match ty {
+            "float" => ShValue::Float(0.0),
+            "int" => ShValue::Int(0),
+            "bool" => ShValue::Bool(false),
+            "vec2" => ShValue::Vec(vec![0.0; 2]),
+            "vec3" => ShValue::Vec(vec![0.0; 3]),
+            "vec4" => ShValue::Vec(vec![0.0; 4]),
+            "bvec2" => ShValue::BVec(vec![false; 2]),
+            "bvec3" => ShValue::BVec(vec![false; 3]),
+            "bvec4" => ShValue::BVec(vec![false; 4]),
+            "mat2" => ShValue::Mat(vec![0.0; 4]),
+            "mat3" => ShValue::Mat(vec![0.0; 9]),
+            "mat4" => ShValue::Mat(vec![0.0; 16]),
+            _ => {
+                if let Some(shtype) = self.shader_gen.find_type(ty) {
+                    if !shtype.prim {
+                        let mut fields = Vec::new();
+                        for field in &shtype.fields {
+                            fields.push((field.name.clone(), self.zero_value(&field.ty) ?));
+                        }
+                        return Ok(ShValue::Struct(ty.to_string(), fields))
+                    }
+                }
+                return Err(SlErr {msg: format!("no cpu value for type {}", ty)})
+            }
+        })
+    }
+    
+    fn read_var(&mut self, name: &str) -> Result<ShValue, ShEvalFlow> {
+        for scope in self.scopes[self.frame..].iter().rev() {
+            if let Some((_, value)) = scope.iter().rev().find( | (n, _) | n == name) {
+                return Ok(value.clone())
+            }
+        }
+        if let Some(value) = self.vars.get(name) {
+            return Ok(value.clone())
+        }
+        if let Some(shvar) = self.shader_gen.find_var(name) {
+            let value = if let ShVarStore::Texture = shvar.store {
+                ShValue::Texture(name.to_string())
+            }
+            else {
+                match self.zero_value(&shvar.ty) {
+                    Ok(value) => value,
+                    Err(err) => return eval_err(err.msg)
+                }
+            };
+            self.vars.insert(name.to_string(), value.clone());
+            return Ok(value)
+        }
+        if let Some(cnst) = self.shader_gen.find_const(name) {
+            return self.eval_expr(&cnst.value)
+        }
+        eval_err(format!("cannot find {}", name))
+    }
+    
+    fn write_var(&mut self, name: &str, value: ShValue) -> Result<(), ShEvalFlow> {
+        let frame = self.frame;
+        for scope in self.scopes[frame..].iter_mut().rev() {
+            if let Some((_, slot)) = scope.iter_mut().rev().find( | (n, _) | n == name) {
+                *slot = value;
+                return Ok(())
+            }
+        }
+        if self.vars.contains_key(name) || self.shader_gen.find_var(name).is_some() {
+            self.vars.insert(name.to_string(), value);
+            return Ok(())
+        }
+        eval_err(format!("cannot assign to {}", name))
+    }
+    
+    fn call_fn(&mut self, name: &str, args: Vec<ShValue>) -> Result<ShValue, ShEvalFlow> {
+        let sg = self.shader_gen;
+        if let Some(shfn) = sg.find_fn(name) {
+            if let Some(block) = &shfn.block {
+                if args.len() != shfn.args.len() {
+                    return eval_err(format!("fn {} takes {} args, got {}", name, shfn.args.len(), args.len()))
+                }
+                let mut scope = Vec::new();
+                for (arg, value) in shfn.args.iter().zip(args.into_iter()) {
+                    scope.push((arg.name.clone(), value));
+                }
+                let old_frame = self.frame;
+                self.frame = self.scopes.len();
+                self.scopes.push(scope);
+                let ret = self.exec_block(block);
+                self.scopes.truncate(self.frame);
+                self.frame = old_frame;
+                return match ret {
+                    Ok(_) => Ok(ShValue::Void),
+                    Err(ShEvalFlow::Return(value)) => Ok(value),
+                    Err(ShEvalFlow::Err(msg)) => eval_err(format!("{}\n  in fn {}", msg, name)),
+                    Err(_) => eval_err(format!("break or continue outside of a loop in fn {}", name))
+                }
+            }
+            return self.call_builtin(name, args)
+        }
+        if sg.find_type(name).is_some() {
+            return self.construct(name, args)
+        }
+        eval_err(format!("cannot find function {}", name))
+    }
+    
+    fn exec_block(&mut self, block: &ShBlock) -> Result<ShValue, ShEvalFlow> {
+        self.scopes.push(Vec::new());
+        let mut ret = Ok(ShValue::Void);
+        for stmt in &block.stmts {
+            ret = match &**stmt {
+                ShStmt::ShLet(shlet) => {
+                    match self.eval_expr(&shlet.init) {
+                        Ok(value) => {
+                            self.scopes.last_mut().unwrap().push((shlet.name.clone(), value));
+                            Ok(ShValue::Void)
+                        },
+                        Err(err) => Err(err)
+                    }
+                },
+                ShStmt::ShSemi(expr) => self.eval_expr(expr).map( | _ | ShValue::Void),
+                ShStmt::ShExpr(expr) => self.eval_expr(expr)
+            };
+            if ret.is_err() {
+                break;
+            }
+        }
+        self.scopes.pop();
+        ret
+    }
+    
+    fn eval_expr(&mut self, expr: &ShExpr) -> Result<ShValue, ShEvalFlow> {
+        match expr {
+            ShExpr::ShId(x) => self.read_var(&x.name).or_else( | err | match err {
+                ShEvalFlow::Err(msg) => eval_err(format!("{}: {}", x.loc, msg)),
+                err => Err(err)
+            }),
+            ShExpr::ShLit(x) => match x {
+                ShLit::Int(v) => Ok(ShValue::Int(*v)),
+                ShLit::Float(v) => Ok(ShValue::Float(*v as f32)),
+                ShLit::Bool(v) => Ok(ShValue::Bool(*v)),
+                ShLit::Str(_) => eval_err("strings are only used as the argument of color".to_string())
+            },
+            ShExpr::ShField(x) => {
+                let base = self.eval_expr(&x.base) ?;
+                read_field(&base, &x.member).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg)))
+            },
+            ShExpr::ShIndex(x) => {
+                let base = self.eval_expr(&x.base) ?;
+                let index = self.eval_expr(&x.index) ?;
+                read_index(&base, &index).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg)))
+            },
+            ShExpr::ShAssign(x) => {
+                let value = self.eval_expr(&x.right) ?;
+                self.assign(&x.left, value.clone()) ?;
+                Ok(value)
+            },
+            ShExpr::ShAssignOp(x) => {
+                let left = self.eval_expr(&x.left) ?;
+                let right = self.eval_expr(&x.right) ?;
+                let op = match x.op {
+                    ShBinOp::AddEq => ShBinOp::Add,
+                    ShBinOp::SubEq => ShBinOp::Sub,
+                    ShBinOp::MulEq => ShBinOp::Mul,
+                    ShBinOp::DivEq => ShBinOp::Div,
+                    ShBinOp::RemEq => ShBinOp::Rem,
+                    ShBinOp::BitXorEq => ShBinOp::BitXor,
+                    ShBinOp::BitAndEq => ShBinOp::BitAnd,
+                    ShBinOp::BitOrEq => ShBinOp::BitOr,
+                    ShBinOp::ShlEq => ShBinOp::Shl,
+                    _ => ShBinOp::Shr
+                };
+                let value = binary(&op, &left, &right).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg))) ?;
+                self.assign(&x.left, value.clone()) ?;
+                Ok(value)
+            },
+            ShExpr::ShBinary(x) => {
+                let left = self.eval_expr(&x.left) ?;
+                // && and || only evaluate the right side when it matters
+                match (&x.op, &left) {
+                    (ShBinOp::And, ShValue::Bool(false)) => return Ok(ShValue::Bool(false)),
+                    (ShBinOp::Or, ShValue::Bool(true)) => return Ok(ShValue::Bool(true)),
+                    _ => ()
+                }
+                let right = self.eval_expr(&x.right) ?;
+                binary(&x.op, &left, &right).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg)))
+            },
+            ShExpr::ShUnary(x) => {
+                let value = self.eval_expr(&x.expr) ?;
+                match (&x.op, value) {
+                    (ShUnaryOp::Not, ShValue::Bool(v)) => Ok(ShValue::Bool(!v)),
+                    (ShUnaryOp::Not, ShValue::BVec(v)) => Ok(ShValue::BVec(v.iter().map( | v | !v).collect())),
+                    (ShUnaryOp::Neg, ShValue::Int(v)) => Ok(ShValue::Int(-v)),
+                    (ShUnaryOp::Neg, value) => {
+                        if let Some(comps) = value.comps() {
+                            return Ok(value.reshape(comps.iter().map( | v | -v).collect()))
+                        }
+                        eval_err(format!("{}: cannot negate a {}", x.loc, value.ty()))
+                    },
+                    (ShUnaryOp::Not, value) => eval_err(format!("{}: cannot use ! on a {}", x.loc, value.ty()))
+                }
+            },
+            ShExpr::ShParen(x) => self.eval_expr(&x.expr),
+            ShExpr::ShBlock(x) => self.exec_block(x),
+            ShExpr::ShCall(x) => {
+                // color takes its name as a string literal
+                if x.call == "color" && x.args.len() == 1 {
+                    if let ShExpr::ShLit(ShLit::Str(name)) = &*x.args[0] {
+                        return Ok(color(name).into())
+                    }
+                }
+                let mut args = Vec::new();
+                for arg in &x.args {
+                    args.push(self.eval_expr(arg) ?);
+                }
+                self.call_fn(&x.call, args).or_else( | err | match err {
+                    ShEvalFlow::Err(msg) => eval_err(format!("{}: {}", x.loc, msg)),
+                    err => Err(err)
+                })
+            },
+            ShExpr::ShIf(x) => {
+                match self.eval_expr(&x.cond) ? {
+                    ShValue::Bool(true) => self.exec_block(&x.then_branch),
+                    ShValue::Bool(false) => {
+                        if let Some(else_branch) = &x.else_branch {
+                            return self.eval_expr(else_branch)
+                        }
+                        Ok(ShValue::Void)
+                    },
+                    value => eval_err(format!("if condition is a {}, not a bool", value.ty()))
+                }
+            },
+            ShExpr::ShWhile(x) => {
+                loop {
+                    match self.eval_expr(&x.cond) ? {
+                        ShValue::Bool(true) => (),
+                        ShValue::Bool(false) => break,
+                        value => return eval_err(format!("while condition is a {}, not a bool", value.ty()))
+                    }
+                    match self.exec_block(&x.body) {
+                        Ok(_) | Err(ShEvalFlow::Continue) => (),
+                        Err(ShEvalFlow::Break) => break,
+                        Err(err) => return Err(err)
+                    }
+                }
+                Ok(ShValue::Void)
+            },
+            ShExpr::ShForLoop(x) => {
+                let from = self.eval_expr(&x.from) ?.as_float().unwrap_or(0.0) as i64;
+                let to = self.eval_expr(&x.to) ?.as_float().unwrap_or(0.0) as i64;
+                for i in from..to {
+                    self.scopes.push(vec![(x.iter.clone(), ShValue::Int(i))]);
+                    let ret = self.exec_block(&x.body);
+                    self.scopes.pop();
+                    match ret {
+                        Ok(_) | Err(ShEvalFlow::Continue) => (),
+                        Err(ShEvalFlow::Break) => break,
+                        Err(err) => return Err(err)
+                    }
+                }
+                Ok(ShValue::Void)
+            },
+            ShExpr::ShReturn(x) => {
+                let value = if let Some(expr) = &x.expr {self.eval_expr(expr) ?}else {ShValue::Void};
+                Err(ShEvalFlow::Return(value))
+            },
+            ShExpr::ShBreak(_) => Err(ShEvalFlow::Break),
+            ShExpr::ShContinue(_) => Err(ShEvalFlow::Continue)
+        }
+    }
+    
+    // assign to a var, a field or swizzle of one, or an index of one
+    fn assign(&mut self, target: &ShExpr, value: ShValue) -> Result<(), ShEvalFlow> {
+        match target {
+            ShExpr::ShId(x) => self.write_var(&x.name, value),
+            ShExpr::ShField(x) => {
+                let base = self.eval_expr(&x.base) ?;
+                let base = write_field(base, &x.member, value).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg))) ?;
+                self.assign(&x.base, base)
+            },
+            ShExpr::ShIndex(x) => {
+                let base = self.eval_expr(&x.base) ?;
+                let index = self.eval_expr(&x.index) ?;
+                let base = write_index(base, &index, value).or_else( | msg | eval_err(format!("{}: {}", x.loc, msg))) ?;
+                self.assign(&x.base, base)
+            },
+            ShExpr::ShParen(x) => self.assign(&x.expr, value),
+            _ => eval_err("cannot assign to this expression".to_string())
+        }
+    }
+    
+    fn construct(&mut self, name: &str, args: Vec<ShValue>) -> Result<ShValue, ShEvalFlow> {
+        let sg = self.shader_gen;
+        if let Some(shtype) = sg.find_type(name) {
+            if !shtype.prim {
+                if args.len() != shtype.fields.len() {
+                    return eval_err(format!("struct {} has {} fields, got {} args", name, shtype.fields.len(), args.len()))
+                }
+                let fields = shtype.fields.iter().map( | field | field.name.clone()).zip(args.into_iter()).collect();
+                return Ok(ShValue::Struct(name.to_string(), fields))
+            }
+        }
+        let mut comps = Vec::new();
+        for arg in &args {
+            match arg.comps() {
+                Some(arg_comps) => comps.extend(arg_comps),
+                None => return eval_err(format!("cannot construct a {} from a {}", name, arg.ty()))
+            }
+        }
+        let size = match name {
+            "float" | "int" => 1,
+            "vec2" => 2,
+            "vec3" => 3,
+            "vec4" | "mat2" => 4,
+            "mat3" => 9,
+            "mat4" => 16,
+            _ => return eval_err(format!("cannot construct a {} on the cpu", name))
+        };
+        if name == "int" {
+            return Ok(ShValue::Int(comps.get(0).cloned().unwrap_or(0.0) as i64))
+        }
+        if comps.len() == 1 && size > 1 {
+            // a single float fills a vector, and the diagonal of a matrix
+            if name.starts_with("mat") {
+                let dim = mat_dim(size);
+                let mut mat = vec![0.0; size];
+                for i in 0..dim {
+                    mat[i * dim + i] = comps[0];
+                }
+                return Ok(ShValue::Mat(mat))
+            }
+            return Ok(ShValue::Vec(vec![comps[0]; size]))
+        }
+        if comps.len() != size {
+            return eval_err(format!("{} needs {} components, got {}", name, size, comps.len()))
+        }
+        if name.starts_with("mat") {
+            return Ok(ShValue::Mat(comps))
+        }
+        Ok(ShValue::Float(0.0).reshape(comps))
+    }
+    
+    fn call_builtin(&mut self, name: &str, args: Vec<ShValue>) -> Result<ShValue, ShEvalFlow> {
+        let arg = | i: usize | -> Result<&ShValue, ShEvalFlow> {
+            if let Some(arg) = args.get(i) {
+                return Ok(arg)
+            }
+            eval_err(format!("{} is missing arg {}", name, i + 1))
+        };
+        let ret = match name {
+            "radians" => map1(arg(0) ?, | x | x.to_radians()),
+            "degrees" => map1(arg(0) ?, | x | x.to_degrees()),
+            "sin" => map1(arg(0) ?, f32::sin),
+            "cos" => map1(arg(0) ?, f32::cos),
+            "tan" => map1(arg(0) ?, f32::tan),
+            "asin" => map1(arg(0) ?, f32::asin),
+            "acos" => map1(arg(0) ?, f32::acos),
+            "atan" => if args.len() == 2 {
+                map2(arg(0) ?, arg(1) ?, f32::atan2)
+            }
+            else {
+                map1(arg(0) ?, f32::atan)
+            },
+            "pow" => map2(arg(0) ?, arg(1) ?, f32::powf),
+            "exp" => map1(arg(0) ?, f32::exp),
+            "log" => map1(arg(0) ?, f32::ln),
+            "exp2" => map1(arg(0) ?, f32::exp2),
+            "log2" => map1(arg(0) ?, f32::log2),
+            "sqrt" => map1(arg(0) ?, f32::sqrt),
+            "inversesqrt" => map1(arg(0) ?, | x | 1.0 / x.sqrt()),
+            "abs" => map1(arg(0) ?, f32::abs),
+            "sign" => map1(arg(0) ?, | x | if x > 0.0 {1.0}else if x < 0.0 {-1.0}else {0.0}),
+            "floor" => map1(arg(0) ?, f32::floor),
+            "ceil" => map1(arg(0) ?, f32::ceil),
+            "fract" => map1(arg(0) ?, | x | x - x.floor()),
+            "fmod" => map2(arg(0) ?, arg(1) ?, | x, y | x % y),
+            "min" => map2(arg(0) ?, arg(1) ?, f32::min),
+            "max" => map2(arg(0) ?, arg(1) ?, f32::max),
+            "clamp" => map3(arg(0) ?, arg(1) ?, arg(2) ?, | x, mi, ma | x.max(mi).min(ma)),
+            "mix" => map3(arg(0) ?, arg(1) ?, arg(2) ?, | x, y, t | x * (1.0 - t) + y * t),
+            "step" => map2(arg(0) ?, arg(1) ?, | e, x | if x < e {0.0}else {1.0}),
+            "smoothstep" => map3(arg(0) ?, arg(1) ?, arg(2) ?, | e0, e1, x | {
+                let t = ((x - e0) / (e1 - e0)).max(0.0).min(1.0);
+                t * t * (3.0 - 2.0 * t)
+            }),
+            "length" => comps1(arg(0) ?).map( | a | ShValue::Float(dot(&a, &a).sqrt())),
+            "distance" => comps2(arg(0) ?, arg(1) ?).map( | (a, b) | {
+                let d: Vec<f32> = a.iter().zip(b.iter()).map( | (a, b) | a - b).collect();
+                ShValue::Float(dot(&d, &d).sqrt())
+            }),
+            "dot" => comps2(arg(0) ?, arg(1) ?).map( | (a, b) | ShValue::Float(dot(&a, &b))),
+            "cross" => comps2(arg(0) ?, arg(1) ?).and_then( | (a, b) | {
+                if a.len() != 3 || b.len() != 3 {
+                    return Err("cross needs two vec3".to_string())
+                }
+                Ok(ShValue::Vec(vec![a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]))
+            }),
+            "normalize" => {
+                let x = arg(0) ?;
+                comps1(x).map( | a | {
+                let len = dot(&a, &a).sqrt();
+                    x.reshape(a.iter().map( | v | v / len).collect())
+                })
+            },
+            "faceforward" => {
+                let n = arg(0) ?;
+                comps2(arg(1) ?, arg(2) ?).and_then( | (i, nref) | {
+                    if dot(&nref, &i) < 0.0 {Ok(n.clone())}else {map1(n, | v | -v)}
+                })
+            },
+            "reflect" => {
+                let x = arg(0) ?;
+                comps2(x, arg(1) ?).map( | (i, n) | {
+                let d = dot(&n, &i);
+                    x.reshape(i.iter().zip(n.iter()).map( | (i, n) | i - 2.0 * d * n).collect())
+                })
+            },
+            "refract" => {
+                let (x, eta) = (arg(0) ?, arg(2) ?);
+                comps2(x, arg(1) ?).and_then( | (i, n) | {
+                    let eta = comps1(eta) ?[0];
+                let d = dot(&n, &i);
+                let k = 1.0 - eta * eta * (1.0 - d * d);
+                let out = if k < 0.0 {vec![0.0; i.len()]}else {
+                    i.iter().zip(n.iter()).map( | (i, n) | eta * i - (eta * d + k.sqrt()) * n).collect()
+                };
+                    Ok(x.reshape(out))
+                })
+            },
+            "matrix_comp_mult" => map2(arg(0) ?, arg(1) ?, | a, b | a * b),
+            "less_than" => compare(arg(0) ?, arg(1) ?, | a, b | a < b),
+            "less_than_equal" => compare(arg(0) ?, arg(1) ?, | a, b | a <= b),
+            "greater_than" => compare(arg(0) ?, arg(1) ?, | a, b | a > b),
+            "greater_than_equal" => compare(arg(0) ?, arg(1) ?, | a, b | a >= b),
+            "equal" => compare(arg(0) ?, arg(1) ?, | a, b | a == b),
+            "not_equal" => compare(arg(0) ?, arg(1) ?, | a, b | a != b),
+            "any" | "all" | "not" => match arg(0) ? {
+                ShValue::Bool(v) => Ok(ShValue::Bool(if name == "not" {!v}else {*v})),
+                ShValue::BVec(v) => Ok(match name {
+                    "any" => ShValue::Bool(v.iter().any( | v | *v)),
+                    "all" => ShValue::Bool(v.iter().all( | v | *v)),
+                    _ => ShValue::BVec(v.iter().map( | v | !v).collect())
+                }),
+                value => Err(format!("{} needs a bool vector, got a {}", name, value.ty()))
+            },
+            "dfdx" | "dfdy" | "fwidth" => {
+                // only the type of the argument is used, its value makes no difference,
+                // so this is right for positions like df_pos and wrong for anything else
+                let x = arg(0) ?;
+                comps1(x).map( | a | {
+                // a float counts as x and y at once
+                let mut d = vec![0.0; a.len()];
+                let y = a.len().min(2) - 1;
+                match name {
+                    "dfdx" => d[0] = self.pixel_size,
+                    "dfdy" => d[y] = self.pixel_size,
+                    _ => {
+                        d[0] = self.pixel_size;
+                        d[y] += self.pixel_size;
+                    }
+                }
+                    x.reshape(d)
+                })
+            },
+            "sample2d" => match (arg(0) ?, arg(1) ?.as_vec2()) {
+                (ShValue::Texture(tex), Some(coord)) => {
+                    if let Some(sample) = self.textures.get(tex) {
+                        Ok(sample(coord).into())
+                    }
+                    else {
+                        Err(format!("texture {} has nothing to sample, add it to ShEval::textures", tex))
+                    }
+                },
+                _ => Err("sample2d needs a texture and a vec2".to_string())
+            },
+            "sizeof" => Ok(ShValue::Int(self.shader_gen.get_type_slots(&arg(0) ?.ty()) as i64)),
+            _ => Err(format!("builtin {} does not run on the cpu", name))
+        };
+        ret.or_else( | msg | eval_err(msg))
+    }
+}
+
+fn dot(a: &Vec<f32>, b: &Vec<f32>) -> f32 {
+    a.iter().zip(b.iter()).map( | (a, b) | a * b).sum()
+}
+
+fn comps1(a: &ShValue) -> Result<Vec<f32>, String> {
+    a.comps().ok_or_else( || format!("expected a float type, got a {}", a.ty()))
+}
+
+fn comps2(a: &ShValue, b: &ShValue) -> Result<(Vec<f32>, Vec<f32>), String> {
+    Ok((comps1(a) ?, comps1(b) ?))
+}
+
+// the shape of the result when combining a and b, a scalar combines with anything
+fn broadcast<'b>(a: &'b ShValue, b: &'b ShValue) -> Result<&'b ShValue, String> {
+    let (ca, cb) = comps2(a, b) ?;
+    if a.is_scalar() {
+        return Ok(b)
+    }
+    if b.is_scalar() || ca.len() == cb.len() {
+        return Ok(a)
+    }
+    Err(format!("cannot combine a {} and a {}", a.ty(), b.ty()))
+}
+
+fn comp(comps: &Vec<f32>, i: usize) -> f32 {
+    if comps.len() == 1 {comps[0]}else {comps[i]}
+}
+
+fn map1<F: Fn(f32) -> f32>(a: &ShValue, f: F) -> Result<ShValue, String> {
+    let ca = comps1(a) ?;
+    Ok(a.reshape(ca.iter().map( | x | f(*x)).collect()))
+}
+
+fn map2<F: Fn(f32, f32) -> f32>(a: &ShValue, b: &ShValue, f: F) -> Result<ShValue, String> {
+    let shape = broadcast(a, b) ?;
+    let (ca, cb) = comps2(a, b) ?;
+    let len = comps1(shape) ?.len();
+    Ok(shape.reshape((0..len).map( | i | f(comp(&ca, i), comp(&cb, i))).collect()))
+}
+
+fn map3<F: Fn(f32, f32, f32) -> f32>(a: &ShValue, b: &ShValue, c: &ShValue, f: F) -> Result<ShValue, String> {
+    let shape = broadcast(broadcast(a, b) ?, c) ?;
+    let (ca, cb) = comps2(a, b) ?;
+    let cc = comps1(c) ?;
+    let len = comps1(shape) ?.len();
+    Ok(shape.reshape((0..len).map( | i | f(comp(&ca, i), comp(&cb, i), comp(&cc, i))).collect()))
+}
+
+fn compare<F: Fn(f32, f32) -> bool>(a: &ShValue, b: &ShValue, f: F) -> Result<ShValue, String> {
+    let shape = broadcast(a, b) ?;
+    let (ca, cb) = comps2(a, b) ?;
+    let len = comps1(shape) ?.len();
+    let out: Vec<bool> = (0..len).map( | i | f(comp(&ca, i), comp(&cb, i))).collect();
+    if shape.is_scalar() {
+        return Ok(ShValue::Bool(out[0]))
+    }
+    Ok(ShValue::BVec(out))
+}
+
+fn binary(op: &ShBinOp, a: &ShValue, b: &ShValue) -> Result<ShValue, String> {
+    // ints stay ints
+    if let (ShValue::Int(a), ShValue::Int(b)) = (a, b) {
+        let (a, b) = (*a, *b);
+        return Ok(match op {
+            ShBinOp::Add => ShValue::Int(a + b),
+            ShBinOp::Sub => ShValue::Int(a - b),
+            ShBinOp::Mul => ShValue::Int(a * b),
+            ShBinOp::Div | ShBinOp::Rem if b == 0 => return Err("integer division by zero".to_string()),
+            ShBinOp::Div => ShValue::Int(a / b),
+            ShBinOp::Rem => ShValue::Int(a % b),
+            ShBinOp::BitXor => ShValue::Int(a ^ b),
+            ShBinOp::BitAnd => ShValue::Int(a & b),
+            ShBinOp::BitOr => ShValue::Int(a | b),
+            ShBinOp::Shl => ShValue::Int(a << b),
+            ShBinOp::Shr => ShValue::Int(a >> b),
+            ShBinOp::Eq => ShValue::Bool(a == b),
+            ShBinOp::Ne => ShValue::Bool(a != b),
+            ShBinOp::Lt => ShValue::Bool(a < b),
+            ShBinOp::Le => ShValue::Bool(a <= b),
+            ShBinOp::Gt => ShValue::Bool(a > b),
+            ShBinOp::Ge => ShValue::Bool(a >= b),
+            _ => return Err(format!("cannot use {} on ints", op.to_string()))
+        })
+    }
+    match op {
+        ShBinOp::And | ShBinOp::Or => {
+            if let (ShValue::Bool(a), ShValue::Bool(b)) = (a, b) {
+                return Ok(ShValue::Bool(if let ShBinOp::And = op {*a && *b}else {*a || *b}))
+            }
+            Err(format!("cannot use {} on a {} and a {}", op.to_string(), a.ty(), b.ty()))
+        },
+        ShBinOp::Eq => Ok(ShValue::Bool(a == b || a.comps().is_some() && a.comps() == b.comps())),
+        ShBinOp::Ne => Ok(ShValue::Bool(!(a == b || a.comps().is_some() && a.comps() == b.comps()))),
+        ShBinOp::Lt | ShBinOp::Le | ShBinOp::Gt | ShBinOp::Ge => {
+            match (a.as_float(), b.as_float()) {
+                (Some(a), Some(b)) => Ok(ShValue::Bool(match op {
+                    ShBinOp::Lt => a < b,
+                    ShBinOp::Le => a <= b,
+                    ShBinOp::Gt => a > b,
+                    _ => a >= b
+                })),
+                _ => Err(format!("cannot use {} on a {} and a {}", op.to_string(), a.ty(), b.ty()))
+            }
+        },
+        ShBinOp::Mul => match (a, b) {
+            (ShValue::Mat(m), ShValue::Mat(n)) if m.len() == n.len() => {
+                let dim = mat_dim(m.len());
+                let mut out = vec![0.0; m.len()];
+                for c in 0..dim {
+                    for r in 0..dim {
+                        out[c * dim + r] = (0..dim).map( | k | m[k * dim + r] * n[c * dim + k]).sum();
+                    }
+                }
+                Ok(ShValue::Mat(out))
+            },
+            (ShValue::Mat(m), ShValue::Vec(v)) if mat_dim(m.len()) == v.len() => {
+                let dim = v.len();
+                Ok(ShValue::Vec((0..dim).map( | r | (0..dim).map( | c | m[c * dim + r] * v[c]).sum()).collect()))
+            },
+            (ShValue::Vec(v), ShValue::Mat(m)) if mat_dim(m.len()) == v.len() => {
+                let dim = v.len();
+                Ok(ShValue::Vec((0..dim).map( | c | (0..dim).map( | r | v[r] * m[c * dim + r]).sum()).collect()))
+            },
+            _ => map2(a, b, | a, b | a * b)
+        },
+        ShBinOp::Add => map2(a, b, | a, b | a + b),
+        ShBinOp::Sub => map2(a, b, | a, b | a - b),
+        ShBinOp::Div => map2(a, b, | a, b | a / b),
+        ShBinOp::Rem => map2(a, b, | a, b | a % b),
+        _ => Err(format!("cannot use {} on a {} and a {}", op.to_string(), a.ty(), b.ty()))
+    }
+}
+
+// the component indices of a swizzle like xy or rgba
+fn swizzle(member: &str, len: usize) -> Option<Vec<usize>> {
+    let mut out = Vec::new();
+    for chr in member.chars() {
+        let index = match chr {
+            'x' | 'r' => 0,
+            'y' | 'g' => 1,
+            'z' | 'b' => 2,
+            'w' | 'a' => 3,
+            _ => return None
+        };
+        if index >= len {
+            return None
+        }
+        out.push(index);
+    }
+    if out.len() == 0 || out.len() > 4 {
+        return None
+    }
+    Some(out)
+}
+
+fn read_field(base: &ShValue, member: &str) -> Result<ShValue, String> {
+    match base {
+        ShValue::Struct(name, fields) => {
+            if let Some((_, value)) = fields.iter().find( | (n, _) | n == member) {
+                return Ok(value.clone())
+            }
+            Err(format!("struct {} has no field {}", name, member))
+        },
+        ShValue::Vec(v) => {
+            if let Some(indices) = swizzle(member, v.len()) {
+                return Ok(ShValue::Float(0.0).reshape(indices.iter().map( | i | v[*i]).collect()))
+            }
+            Err(format!("{} has no field {}", base.ty(), member))
+        },
+        // the matrix types declare their elements as fields a to p
+        ShValue::Mat(m) if member.len() == 1 => {
+            let index = (member.as_bytes()[0] as usize).wrapping_sub('a' as usize);
+            if index < m.len() {
+                return Ok(ShValue::Float(m[index]))
+            }
+            Err(format!("{} has no field {}", base.ty(), member))
+        },
+        _ => Err(format!("{} has no field {}", base.ty(), member))
+    }
+}
+
+fn write_field(base: ShValue, member: &str, value: ShValue) -> Result<ShValue, String> {
+    match base {
+        ShValue::Struct(name, mut fields) => {
+            if let Some((_, slot)) = fields.iter_mut().find( | (n, _) | n == member) {
+                *slot = value;
+                return Ok(ShValue::Struct(name, fields))
+            }
+            Err(format!("struct {} has no field {}", name, member))
+        },
+        ShValue::Vec(mut v) => {
+            let indices = swizzle(member, v.len()).ok_or_else( || format!("vec{} has no field {}", v.len(), member)) ?;
+            let comps = comps1(&value) ?;
+            if comps.len() != indices.len() {
+                return Err(format!("cannot assign a {} to .{}", value.ty(), member))
+            }
+            for (index, comp) in indices.iter().zip(comps.iter()) {
+                v[*index] = *comp;
+            }
+            Ok(ShValue::Vec(v))
+        },
+        ShValue::Mat(mut m) if member.len() == 1 => {
+            let index = (member.as_bytes()[0] as usize).wrapping_sub('a' as usize);
+            match (value.as_float(), index < m.len()) {
+                (Some(v), true) => {
+                    m[index] = v;
+                    Ok(ShValue::Mat(m))
+                },
+                _ => Err(format!("cannot assign a {} to .{}", value.ty(), member))
+            }
+        },
+        base => Err(format!("{} has no field {}", base.ty(), member))
+    }
+}
+
+fn read_index(base: &ShValue, index: &ShValue) -> Result<ShValue, String> {
+    let i = index.as_float().ok_or_else( || format!("cannot index with a {}", index.ty())) ? as usize;
+    match base {
+        ShValue::Vec(v) if i < v.len() => Ok(ShValue::Float(v[i])),
+        // indexing a matrix gives a column
+        ShValue::Mat(m) if i < mat_dim(m.len()) => {
+            let dim = mat_dim(m.len());
+            Ok(ShValue::Vec(m[i * dim..(i + 1) * dim].to_vec()))
+        },
+        _ => Err(format!("cannot index a {} with {}", base.ty(), i))
+    }
+}
+
+fn write_index(base: ShValue, index: &ShValue, value: ShValue) -> Result<ShValue, String> {
+    let i = index.as_float().ok_or_else( || format!("cannot index with a {}", index.ty())) ? as usize;
+    match (base, value.comps()) {
+        (ShValue::Vec(mut v), Some(comps)) if i < v.len() && comps.len() == 1 => {
+            v[i] = comps[0];
+            Ok(ShValue::Vec(v))
+        },
+        (ShValue::Mat(mut m), Some(comps)) if i < mat_dim(m.len()) && comps.len() == mat_dim(m.len()) => {
+            let dim = mat_dim(m.len());
+            m[i * dim..(i + 1) * dim].copy_from_slice(&comps);
+            Ok(ShValue::Mat(m))
+        },
+        (base, _) => Err(format!("cannot assign a {} to an index of a {}", value.ty(), base.ty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    
+    // only the module fns a shader uses are linked in
+    fn def_df_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                df::circle(0., 0., 1.);
+                df::round_rect(0., 0., 1., 1., 1.);
+                df::move_to(0., 0.);
+                df::line_to(1., 1.);
+                return df::fill(color);
+            }
+        }))
+    }
+    
+    // the distance of p to the shape the calls draw, negative inside
+    fn df_field(sg: &ShaderGen, p: Vec2, calls: &[(&str, Vec<f32>)]) -> f32 {
+        let mut ev = ShEval::new(sg);
+        ev.call("df_viewport", vec![p.into()]).unwrap();
+        for (name, args) in calls {
+            ev.call(name, args.iter().map( | arg | (*arg).into()).collect()).unwrap();
+        }
+        ev.get_var("df_field").unwrap().as_float().unwrap()
+    }
+    
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 1e-4, "{} is not {}", value, expected);
+    }
+    
+    #[test]
+    fn circle_distance() {
+        let sg = def_df_shader();
+        let circle = [("df_circle", vec![10., 10., 5.])];
+        assert_near(df_field(&sg, Vec2 {x: 10., y: 10.}, &circle), -5.);
+        assert_near(df_field(&sg, Vec2 {x: 15., y: 10.}, &circle), 0.);
+        assert_near(df_field(&sg, Vec2 {x: 10., y: 22.}, &circle), 7.);
+        assert_near(df_field(&sg, Vec2 {x: 13., y: 14.}, &circle), 0.);
+    }
+    
+    #[test]
+    fn round_rect_distance() {
+        let sg = def_df_shader();
+        // a 20x10 box at 0,0, the corner radius is doubled so 1. rounds by 2
+        let rect = [("df_round_rect", vec![0., 0., 20., 10., 1.])];
+        // inside it does not go below minus the rounding
+        assert_near(df_field(&sg, Vec2 {x: 10., y: 5.}, &rect), -2.);
+        assert_near(df_field(&sg, Vec2 {x: 10., y: 0.}, &rect), 0.);
+        assert_near(df_field(&sg, Vec2 {x: 25., y: 5.}, &rect), 5.);
+        assert_near(df_field(&sg, Vec2 {x: 10., y: -3.}, &rect), 3.);
+        // past the corner the distance is to the rounding, whose center is 2 in from both edges
+        assert_near(df_field(&sg, Vec2 {x: 23., y: 13.}, &rect), (5f32 * 5. + 5. * 5.).sqrt() - 2.);
+    }
+    
+    #[test]
+    fn deprecated_df_names_link_the_module() {
+        // written like before modules, without use df; and with df_box
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                df_viewport(pos * vec2(w, h));
+                df_box(0., 0., 1., 1., 1.);
+                return df_fill(color);
+            }
+        }));
+        sg.assert_type_check();
+        let rect = [("df_box", vec![0., 0., 20., 10., 1.])];
+        assert_near(df_field(&sg, Vec2 {x: 25., y: 5.}, &rect), 5.);
+        assert_near(df_field(&sg, Vec2 {x: 10., y: 5.}, &rect), -2.);
+    }
+    
+    #[test]
+    fn line_to_distance() {
+        let sg = def_df_shader();
+        let line = [("df_move_to", vec![0., 0.]), ("df_line_to", vec![10., 0.])];
+        assert_near(df_field(&sg, Vec2 {x: 5., y: 3.}, &line), 3.);
+        assert_near(df_field(&sg, Vec2 {x: 5., y: -4.}, &line), 4.);
+        assert_near(df_field(&sg, Vec2 {x: 5., y: 0.}, &line), 0.);
+        // beyond the end points it is the distance to the nearest one
+        assert_near(df_field(&sg, Vec2 {x: 13., y: 4.}, &line), 5.);
+        assert_near(df_field(&sg, Vec2 {x: -3., y: 0.}, &line), 3.);
+    }
+    
+    #[test]
+    fn derivatives_ignore_the_argument_value() {
+        let sg = def_df_shader();
+        let mut ev = ShEval::new(&sg);
+        ev.pixel_size = 0.5;
+        let p = Vec2 {x: 3., y: 4.};
+        assert_eq!(ev.call("dfdx", vec![p.into()]).unwrap().as_vec2(), Some(Vec2 {x: 0.5, y: 0.}));
+        assert_eq!(ev.call("dfdy", vec![Vec2 {x: 30., y: -8.}.into()]).unwrap().as_vec2(), Some(Vec2 {x: 0., y: 0.5}));
+        assert_eq!(ev.call("fwidth", vec![ShValue::Float(7.)]).unwrap().as_float(), Some(1.));
+    }
+}
@@ -1,6 +1,7 @@
 // Shared shader-compiler code for generating GLSL and Metal shading language
 
 use std::hash::{Hash, Hasher};
+use std::fmt;
 
 use crate::cx_shader::*;
 
@@ -31,14 +32,16 @@ impl ShaderGen {
     pub fn new() -> Self {
         let sg = ShaderGen::default();
         let sg = CxShader::def_builtins(sg);
-        let sg = CxShader::def_df(sg);
         let sg = CxPass::def_uniforms(sg);
         let sg = CxView::def_uniforms(sg);
         sg
     }
     
     pub fn compose(mut self, ast: ShAst) -> Self {
+        let mut ast = ast;
+        ast.resolve_imports();
         self.asts.push(ast);
+        self.link_modules();
         self
     }
     
@@ -114,11 +117,30 @@ impl ShaderGen {
     
     pub fn get_type_slots(&self, name: &str) -> usize {
         if let Some(ty) = self.find_type(name) {
+            // structs from shader_ast! add up their fields
+            if !ty.prim {
+                return ty.fields.iter().map( | field | self.get_type_slots(&field.ty)).sum()
+            }
             return ty.slots;
         }
         0
     }
     
+    // the struct types declared in the shader, in the order they were declared
+    pub fn flat_structs(&self) -> Vec<ShType> {
+        let mut ret: Vec<ShType> = Vec::new();
+        for ast in self.asts.iter() {
+            for shtype in &ast.types {
+                if !shtype.prim && ret.iter().find( | ty | ty.name == shtype.name).is_none() {
+                    if let Some(shtype) = self.find_type(&shtype.name) {
+                        ret.push(shtype.clone());
+                    }
+                }
+            }
+        }
+        ret
+    }
+    
     pub fn compute_slot_total(&self, vars: &Vec<ShVar>) -> usize {
         let mut slots: usize = 0;
         for var in vars {
@@ -135,7 +157,17 @@ pub struct ShAst {
     pub types: Vec<ShType>,
     pub vars: Vec<ShVar>,
     pub consts: Vec<ShConst>,
-    pub fns: Vec<ShFn>
+    pub fns: Vec<ShFn>,
+    // the shader modules the block imports with use df;
+    pub imports: Vec<ShImport>,
+    // the name of the shader module this block was linked in from, empty for normal blocks
+    pub module: String
+}
+
+#[derive(Clone, Hash, PartialEq)]
+pub struct ShImport {
+    pub name: String,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -159,6 +191,7 @@ pub struct ShFn {
     pub args: Vec<ShFnArg>,
     pub ret: String,
     pub block: Option<ShBlock>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -177,14 +210,16 @@ pub enum ShVarStore {
 pub struct ShVar {
     pub name: String,
     pub ty: String,
-    pub store: ShVarStore
+    pub store: ShVarStore,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShConst {
     pub name: String,
     pub ty: String,
-    pub value: ShExpr
+    pub value: ShExpr,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -210,6 +245,49 @@ pub struct ShType {
     pub fields: Vec<ShTypeField>
 }
 
+impl ShType {
+    // the fields of a struct as local vars, so the backends can emit them like their other structs
+    pub fn field_vars(&self) -> Vec<ShVar> {
+        self.fields.iter().map( | field | ShVar {
+            name: field.name.clone(),
+            ty: field.ty.clone(),
+            store: ShVarStore::Local,
+            loc: ShLoc::default()
+        }).collect()
+    }
+}
+
+// where a node was written in the shader_ast! block, the macro fills it in.
+// it is left out of hashing and comparing so the same shader from two places is still one shader
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ShLoc {
+    pub file: &'static str,
+    pub line: u32,
+    pub col: u32
+}
+
+impl Hash for ShLoc {
+    fn hash<H: Hasher>(&self, _state: &mut H) {
+    }
+}
+
+impl PartialEq for ShLoc {
+    fn eq(&self, _other: &ShLoc) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for ShLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.file.len() == 0 {
+            write!(f, "<builtin>")
+        }
+        else {
+            write!(f, "{}:{}:{}", self.file, self.line, self.col)
+        }
+    }
+}
+
 // AST tree nodes
 
 #[derive(Clone, Hash, PartialEq)]
@@ -302,7 +380,8 @@ impl ShBinOp {
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShId {
-    pub name: String
+    pub name: String,
+    pub loc: ShLoc
 }
 
 #[derive(Clone)]
@@ -350,33 +429,38 @@ impl PartialEq for ShLit {
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShField {
     pub base: Box<ShExpr>,
-    pub member: String
+    pub member: String,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShIndex {
     pub base: Box<ShExpr>,
-    pub index: Box<ShExpr>
+    pub index: Box<ShExpr>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShAssign {
     pub left: Box<ShExpr>,
-    pub right: Box<ShExpr>
+    pub right: Box<ShExpr>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShAssignOp {
     pub left: Box<ShExpr>,
     pub right: Box<ShExpr>,
-    pub op: ShBinOp
+    pub op: ShBinOp,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShBinary {
     pub left: Box<ShExpr>,
     pub right: Box<ShExpr>,
-    pub op: ShBinOp
+    pub op: ShBinOp,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -397,7 +481,8 @@ impl ShUnaryOp {
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShUnary {
     pub expr: Box<ShExpr>,
-    pub op: ShUnaryOp
+    pub op: ShUnaryOp,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -420,7 +505,8 @@ pub struct ShBlock {
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShCall {
     pub call: String,
-    pub args: Vec<Box<ShExpr>>
+    pub args: Vec<Box<ShExpr>>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -446,7 +532,8 @@ pub struct ShForLoop {
 
 #[derive(Clone, Hash, PartialEq)]
 pub struct ShReturn {
-    pub expr: Option<Box<ShExpr>>
+    pub expr: Option<Box<ShExpr>>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone, Hash, PartialEq)]
@@ -461,7 +548,8 @@ pub struct ShContinue {
 pub struct ShLet {
     pub name: String,
     pub ty: String,
-    pub init: Box<ShExpr>
+    pub init: Box<ShExpr>,
+    pub loc: ShLoc
 }
 
 #[derive(Clone)]
@@ -470,7 +558,7 @@ pub struct Sl {
     pub ty: String
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct SlErr {
     pub msg: String
 }
@@ -487,6 +575,15 @@ pub enum SlTarget {
     Constant
 }
 
+// which language the shared generator writes, the platform one is glsl, hlsl or metal
+// depending on which of those backends got compiled in, glsl450 is the vulkan flavour
+#[derive(Clone, PartialEq)]
+pub enum SlLang {
+    Platform,
+    Wgsl,
+    Glsl450
+}
+
 pub struct SlCx<'a> {
     pub depth: usize,
     pub target: SlTarget,
@@ -497,7 +594,8 @@ pub struct SlCx<'a> {
     pub scope: Vec<SlDecl>,
     pub fn_deps: Vec<String>,
     pub fn_done: Vec<Sl>,
-    pub auto_vary: Vec<ShVar>
+    pub auto_vary: Vec<ShVar>,
+    pub lang: SlLang
 }
 
 pub enum MapCallResult {
@@ -520,6 +618,39 @@ impl<'a> SlCx<'a> {
         }
         Err(SlErr {msg: format!("Cannot find type {}", name)})
     }
+    
+    // the platform backend implements map_var and friends, wgsl and glsl450 have their own
+    pub fn sl_map_var(&mut self, var: &ShVar) -> String {
+        match self.lang {
+            SlLang::Platform => self.map_var(var),
+            SlLang::Wgsl => self.wgsl_map_var(var),
+            SlLang::Glsl450 => self.glsl450_map_var(var)
+        }
+    }
+    
+    pub fn sl_map_call(&self, name: &str, args: &Vec<Sl>) -> MapCallResult {
+        match self.lang {
+            SlLang::Platform => self.map_call(name, args),
+            SlLang::Wgsl => self.wgsl_map_call(name, args),
+            SlLang::Glsl450 => self.glsl450_map_call(name, args)
+        }
+    }
+    
+    pub fn sl_map_type(&self, ty: &str) -> String {
+        match self.lang {
+            SlLang::Platform => self.map_type(ty),
+            SlLang::Wgsl => self.wgsl_map_type(ty),
+            SlLang::Glsl450 => self.glsl450_map_type(ty)
+        }
+    }
+    
+    pub fn sl_mat_mul(&self, left: &str, right: &str) -> String {
+        match self.lang {
+            SlLang::Platform => self.mat_mul(left, right),
+            SlLang::Wgsl => self.wgsl_mat_mul(left, right),
+            SlLang::Glsl450 => self.glsl450_mat_mul(left, right)
+        }
+    }
 }
 
 impl ShExpr {
@@ -557,7 +688,7 @@ impl ShId {
             Ok(Sl {sl: self.name.to_string(), ty: cnst.ty.to_string()})
         }
         else if let Some(var) = slcx.shader_gen.find_var(&self.name) {
-            Ok(Sl {sl: slcx.map_var(var), ty: var.ty.to_string()})
+            Ok(Sl {sl: slcx.sl_map_var(var), ty: var.ty.to_string()})
         }
         else { // id not found.. lets give an error
             Err(SlErr {
@@ -643,6 +774,15 @@ impl ShField {
                 }
             }
             
+            // wgsl cant swizzle a scalar, splat it instead
+            if let (SlLang::Wgsl, "float") = (&slcx.lang, shty.name.as_ref()) {
+                let len = self.member.len();
+                return Ok(Sl {
+                    sl: if len == 1 {base.sl}else {format!("vec{}<f32>({})", len, base.sl)},
+                    ty: if len == 1 {"float".to_string()}else {format!("vec{}", len)}
+                })
+            }
+            
             match self.member.len() {
                 1 => return Ok(Sl {
                     sl: format!("{}.{}", base.sl, self.member),
@@ -698,6 +838,15 @@ impl ShAssign {
                 msg: format!("Left type {} not the same as right {} in assign {}={}", left.ty, right.ty, left.sl, right.sl)
             })
         }
+        else if let (SlLang::Wgsl, ShExpr::ShAssign(chain)) = (&slcx.lang, &*self.right) {
+            // wgsl assignments are statements, so a = b = c becomes b = c; a = b
+            let chain_left = chain.left.sl(slcx) ?;
+            let indent = "  ".repeat(slcx.depth);
+            Ok(Sl {
+                sl: format!("{};\n{}{} = {}", right.sl, indent, left.sl, chain_left.sl),
+                ty: left.ty
+            })
+        }
         else {
             Ok(Sl {
                 sl: format!("{} = {}", left.sl, right.sl),
@@ -745,19 +894,19 @@ impl ShBinary {
             }
             else if right.ty == "mat4" && left.ty == "vec4" {
                 Ok(Sl {
-                    sl: slcx.mat_mul(&left.sl, &right.sl),
+                    sl: slcx.sl_mat_mul(&left.sl, &right.sl),
                     ty: left.ty
                 })
             }
             else if right.ty == "mat3" && left.ty == "vec3" {
                 Ok(Sl {
-                    sl: slcx.mat_mul(&left.sl, &right.sl),
+                    sl: slcx.sl_mat_mul(&left.sl, &right.sl),
                     ty: left.ty
                 })
             }
             else if right.ty == "mat2" && left.ty == "vec2" {
                 Ok(Sl {
-                    sl: slcx.mat_mul(&left.sl, &right.sl),
+                    sl: slcx.sl_mat_mul(&left.sl, &right.sl),
                     ty: left.ty
                 })
             }
@@ -770,7 +919,7 @@ impl ShBinary {
         else {
             if left.ty == "mat4" || left.ty == "mat3" || left.ty == "mat2" {
                 Ok(Sl {
-                    sl: slcx.mat_mul(&left.sl, &right.sl),
+                    sl: slcx.sl_mat_mul(&left.sl, &right.sl),
                     ty: left.ty
                 })
                 
@@ -867,7 +1016,7 @@ impl ShCall {
                 args_gl.push(arg_gl);
             };
             
-            let map_call = slcx.map_call(&self.call, &args_gl);
+            let map_call = slcx.sl_map_call(&self.call, &args_gl);
             let ret_ty;
             
             if let MapCallResult::Rewrite(rewrite, rty) = map_call {
@@ -968,7 +1117,34 @@ impl ShCall {
         else {
             // its a constructor call
             if let Some(glty) = slcx.shader_gen.find_type(&self.call) {
-                out.push_str(&slcx.map_type(&self.call));
+                // structs from shader_ast! can be constructed differently per language
+                if !glty.prim {
+                    let mut args_gl = Vec::new();
+                    for arg in &self.args {
+                        args_gl.push(arg.sl(slcx) ?);
+                    }
+                    match slcx.sl_map_call(&self.call, &args_gl) {
+                        MapCallResult::Rewrite(rewrite, rty) => return Ok(Sl {
+                            sl: rewrite,
+                            ty: rty
+                        }),
+                        MapCallResult::Rename(name) => out.push_str(&name),
+                        MapCallResult::None => out.push_str(&slcx.sl_map_type(&self.call))
+                    }
+                    out.push_str("(");
+                    for (i, arg_gl) in args_gl.iter().enumerate() {
+                        if i != 0 {
+                            out.push_str(", ");
+                        }
+                        out.push_str(&arg_gl.sl);
+                    }
+                    out.push_str(")");
+                    return Ok(Sl {
+                        sl: out,
+                        ty: glty.name.clone()
+                    })
+                }
+                out.push_str(&slcx.sl_map_type(&self.call));
                 out.push_str("(");
                 // TODO check args
                 for (i, arg) in self.args.iter().enumerate() {
@@ -1041,9 +1217,16 @@ impl ShForLoop {
     pub fn sl(&self, cx: &mut SlCx) -> Result<Sl, SlErr> {
         let mut out = "".to_string();
         
+        if let SlLang::Wgsl = cx.lang {
+            out.push_str("for(var ");
+            out.push_str(&self.iter);
+            out.push_str(": i32 = ");
+        }
+        else {
         out.push_str("for(int ");
         out.push_str(&self.iter);
         out.push_str("=");
+        }
         
         let from = self.from.sl(cx) ?;
         out.push_str(&from.sl);
@@ -1118,9 +1301,17 @@ impl ShLet {
             })
         }
         
-        out.push_str(&cx.map_type(&ty));
-        out.push_str(" ");
-        out.push_str(&self.name);
+        if let SlLang::Wgsl = cx.lang {
+            out.push_str("var ");
+            out.push_str(&self.name);
+            out.push_str(": ");
+            out.push_str(&cx.sl_map_type(&ty));
+        }
+        else {
+            out.push_str(&cx.sl_map_type(&ty));
+            out.push_str(" ");
+            out.push_str(&self.name);
+        }
         out.push_str(" = ");
         
         // lets define our identifier on scope
@@ -1139,8 +1330,11 @@ impl ShLet {
 
 impl ShFn {
     pub fn sl(&self, cx: &mut SlCx) -> Result<Sl, SlErr> {
+        if let SlLang::Wgsl = cx.lang {
+            return self.wgsl_sl(cx)
+        }
         let mut out = "".to_string();
-        out.push_str(&cx.map_type(&self.ret));
+        out.push_str(&cx.sl_map_type(&self.ret));
         out.push_str(" ");
         out.push_str(&cx.call_prefix);
         out.push_str(&self.name);
@@ -1149,7 +1343,7 @@ impl ShFn {
             if i != 0 {
                 out.push_str(", ");
             }
-            out.push_str(&cx.map_type(&arg.ty));
+            out.push_str(&cx.sl_map_type(&arg.ty));
             out.push_str(" ");
             out.push_str(&arg.name);
             cx.scope.push(SlDecl {
@@ -0,0 +1,239 @@
+// Shader modules, named libraries of shader code a shader_ast! block imports with use df;
+// a module prefixes everything it declares with its name, so df::circle() in the block is df_circle in the module.
+// compose links in only the module fns, vars and consts a shader reaches.
+// shaders from before modules that call df_circle() without use df; still get the module linked in,
+// the math constants are not covered by that, PI is math::PI now
+
+use std::collections::HashSet;
+use crate::shaderopt::*;
+use crate::cx_shader::*;
+
+pub const SHADER_MODULES: [&str; 4] = ["math", "df", "color", "noise"];
+
+// a module by name, with its own imports resolved
+pub fn shader_module(name: &str) -> Option<ShAst> {
+    let mut ast = match name {
+        "math" => CxShader::def_math(),
+        "df" => CxShader::def_df(),
+        "color" => CxShader::def_color(),
+        "noise" => CxShader::def_noise(),
+        _ => return None
+    };
+    ast.resolve_imports();
+    ast.module = name.to_string();
+    Some(ast)
+}
+
+impl ShAst {
+    // turns df::circle into df_circle for the modules this block imports,
+    // names of modules it does not import stay as they are so the type checker can report them
+    pub fn resolve_imports(&mut self) {
+        if self.imports.len() == 0 {
+            return
+        }
+        let imports: Vec<String> = self.imports.iter().map( | import | import.name.clone()).collect();
+        let mut resolve = | expr: &mut ShExpr | {
+            let name = match expr {
+                ShExpr::ShCall(x) => &mut x.call,
+                ShExpr::ShId(x) => &mut x.name,
+                _ => return
+            };
+            if let Some(pos) = name.find("::") {
+                if imports.iter().any( | import | *import == name[0..pos]) {
+                    *name = format!("{}_{}", &name[0..pos], &name[pos + 2..]);
+                }
+            }
+        };
+        for cnst in &mut self.consts {
+            walk_expr_mut(&mut cnst.value, &mut resolve);
+        }
+        for shfn in &mut self.fns {
+            if let Some(block) = &mut shfn.block {
+                walk_block_mut(block, &mut resolve);
+            }
+        }
+    }
+    
+    // deprecated: the modules this block uses by their prefixed names without a use, like df_circle
+    fn implicit_imports(&self) -> Vec<String> {
+        let mut used = HashSet::new();
+        self.mentions(&mut used);
+        let mut imports = Vec::new();
+        for name in SHADER_MODULES.iter() {
+            if self.imports.iter().any( | import | import.name == *name) {
+                continue;
+            }
+            let prefix = format!("{}_", name);
+            if !used.iter().any( | id | id.starts_with(&prefix)) {
+                continue;
+            }
+            if let Some(module) = shader_module(name) {
+                let declared = | id: &String | module.vars.iter().any( | var | var.name == *id)
+                    || module.consts.iter().any( | cnst | cnst.name == *id)
+                    || module.fns.iter().any( | shfn | shfn.name == *id);
+                if used.iter().any( | id | id.starts_with(&prefix) && declared(id)) {
+                    imports.push(name.to_string());
+                }
+            }
+        }
+        imports
+    }
+    
+    // the names of the fns, vars and consts it uses
+    fn mentions(&self, used: &mut HashSet<String>) {
+        for cnst in &self.consts {
+            walk_expr(&cnst.value, &mut | expr | collect_id(expr, true, used));
+        }
+        for shfn in &self.fns {
+            fn_mentions(shfn, used);
+        }
+    }
+}
+
+fn fn_mentions(shfn: &ShFn, used: &mut HashSet<String>) {
+    if let Some(block) = &shfn.block {
+        walk_block(block, &mut | expr | {
+            if let ShExpr::ShCall(x) = expr {
+                used.insert(x.call.clone());
+            }
+            collect_id(expr, true, used);
+        });
+    }
+}
+
+impl ShaderGen {
+    // rebuilds the blocks linked in from modules, each goes in front of the first block that imports it
+    pub fn link_modules(&mut self) {
+        let mut asts: Vec<ShAst> = self.asts.drain(..).filter( | ast | ast.module.len() == 0).collect();
+        for ast in &mut asts {
+            for name in ast.implicit_imports() {
+                ast.imports.push(ShImport {name: name, loc: ShLoc::default()});
+            }
+        }
+        
+        // modules can import modules as well
+        let mut modules: Vec<ShAst> = Vec::new();
+        let mut todo: Vec<String> = asts.iter().flat_map( | ast | ast.imports.iter().map( | import | import.name.clone())).collect();
+        while let Some(name) = todo.pop() {
+            if modules.iter().any( | module | module.module == name) {
+                continue;
+            }
+            if let Some(module) = shader_module(&name) {
+                todo.extend(module.imports.iter().map( | import | import.name.clone()));
+                modules.push(module);
+            }
+        }
+        
+        // what the blocks use, and then what the module items they use need in turn
+        let mut used = HashSet::new();
+        for ast in &asts {
+            ast.mentions(&mut used);
+        }
+        let mut done = HashSet::new();
+        loop {
+            let todo: Vec<String> = used.iter().filter( | name | !done.contains(*name)).cloned().collect();
+            if todo.len() == 0 {
+                break;
+            }
+            for name in todo {
+                for module in &modules {
+                    if let Some(shfn) = module.fns.iter().find( | shfn | shfn.name == name) {
+                        fn_mentions(shfn, &mut used);
+                    }
+                    if let Some(cnst) = module.consts.iter().find( | cnst | cnst.name == name) {
+                        walk_expr(&cnst.value, &mut | expr | collect_id(expr, true, &mut used));
+                    }
+                }
+                done.insert(name);
+            }
+        }
+        let linked: Vec<ShAst> = modules.iter().map( | module | ShAst {
+            types: module.types.clone(),
+            vars: module.vars.iter().filter( | var | used.contains(&var.name)).cloned().collect(),
+            consts: module.consts.iter().filter( | cnst | used.contains(&cnst.name)).cloned().collect(),
+            fns: module.fns.iter().filter( | shfn | used.contains(&shfn.name)).cloned().collect(),
+            imports: module.imports.clone(),
+            module: module.module.clone()
+        }).collect();
+        
+        for ast in asts {
+            for import in &ast.imports {
+                self.push_module(&import.name, &linked);
+            }
+            self.asts.push(ast);
+        }
+    }
+    
+    fn push_module(&mut self, name: &str, linked: &Vec<ShAst>) {
+        if self.asts.iter().any( | ast | ast.module == name) {
+            return
+        }
+        if let Some(module) = linked.iter().find( | module | module.module == name) {
+            for import in &module.imports {
+                self.push_module(&import.name, linked);
+            }
+            if module.types.len() + module.vars.len() + module.consts.len() + module.fns.len() > 0 {
+                self.asts.push(module.clone());
+            }
+        }
+    }
+}
+
+// like walk_expr, but mutable and including the targets of assignments
+pub fn walk_expr_mut<F: FnMut(&mut ShExpr)>(expr: &mut ShExpr, f: &mut F) {
+    f(expr);
+    match expr {
+        ShExpr::ShId(_) | ShExpr::ShLit(_) | ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => (),
+        ShExpr::ShField(x) => walk_expr_mut(&mut x.base, f),
+        ShExpr::ShIndex(x) => {
+            walk_expr_mut(&mut x.base, f);
+            walk_expr_mut(&mut x.index, f);
+        },
+        ShExpr::ShAssign(x) => {
+            walk_expr_mut(&mut x.left, f);
+            walk_expr_mut(&mut x.right, f);
+        },
+        ShExpr::ShAssignOp(x) => {
+            walk_expr_mut(&mut x.left, f);
+            walk_expr_mut(&mut x.right, f);
+        },
+        ShExpr::ShBinary(x) => {
+            walk_expr_mut(&mut x.left, f);
+            walk_expr_mut(&mut x.right, f);
+        },
+        ShExpr::ShUnary(x) => walk_expr_mut(&mut x.expr, f),
+        ShExpr::ShParen(x) => walk_expr_mut(&mut x.expr, f),
+        ShExpr::ShBlock(x) => walk_block_mut(x, f),
+        ShExpr::ShCall(x) => for arg in &mut x.args {
+            walk_expr_mut(arg, f);
+        },
+        ShExpr::ShIf(x) => {
+            walk_expr_mut(&mut x.cond, f);
+            walk_block_mut(&mut x.then_branch, f);
+            if let Some(else_branch) = &mut x.else_branch {
+                walk_expr_mut(else_branch, f);
+            }
+        },
+        ShExpr::ShWhile(x) => {
+            walk_expr_mut(&mut x.cond, f);
+            walk_block_mut(&mut x.body, f);
+        },
+        ShExpr::ShForLoop(x) => {
+            walk_expr_mut(&mut x.from, f);
+            walk_expr_mut(&mut x.to, f);
+            walk_block_mut(&mut x.body, f);
+        },
+        ShExpr::ShReturn(x) => if let Some(expr) = &mut x.expr {
+            walk_expr_mut(expr, f);
+        }
+    }
+}
+
+pub fn walk_block_mut<F: FnMut(&mut ShExpr)>(block: &mut ShBlock, f: &mut F) {
+    for stmt in &mut block.stmts {
+        match &mut **stmt {
+            ShStmt::ShLet(x) => walk_expr_mut(&mut x.init, f),
+            ShStmt::ShExpr(x) | ShStmt::ShSemi(x) => walk_expr_mut(x, f)
+        }
+    }
+}
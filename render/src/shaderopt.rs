@@ -0,0 +1,762 @@
+// Shader optimizer
+// folds constant expressions, inlines one line helpers and drops the fns, consts and vars
+// the vertex and pixel entry points never get to
+
+use std::collections::HashSet;
+use std::fmt;
+use crate::shadergen::*;
+use crate::shadereval::*;
+
+#[derive(Clone, Default, Debug)]
+pub struct ShOptReport {
+    pub folded: usize,
+    pub inlined: usize,
+    pub removed_fns: Vec<String>,
+    pub removed_consts: Vec<String>,
+    pub removed_vars: Vec<String>,
+    // instance props that no shader code reads. the widgets still write them,
+    // CxShaderMapping::construct_optimized maps their data onto the smaller layout
+    pub removed_instances: Vec<String>,
+    pub instance_slots: usize,
+    pub removed_instance_slots: usize
+}
+
+impl fmt::Display for ShOptReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "folded {} expressions, inlined {} calls", self.folded, self.inlined) ?;
+        writeln!(f, "removed fns: {}", self.removed_fns.join(", ")) ?;
+        writeln!(f, "removed consts: {}", self.removed_consts.join(", ")) ?;
+        writeln!(f, "removed vars: {}", self.removed_vars.join(", ")) ?;
+        write!(
+            f,
+            "removed instance props: {} ({} of {} slots)",
+            self.removed_instances.join(", "),
+            self.removed_instance_slots,
+            self.instance_slots
+        )
+    }
+}
+
+impl ShaderGen {
+    // returns the optimized shader, it has the same geometry, uniform and texture layout
+    // but leaves out the instance props it never reads
+    pub fn optimize(&self) -> Result<(ShaderGen, ShOptReport), SlErr> {
+        let mut sg = self.clone();
+        let mut report = ShOptReport::default();
+        // inlining and folding feed each other, so run until nothing changes
+        for _pass in 0..16 {
+            let snapshot = sg.clone();
+            let mut cx = ShOptCx::new(&snapshot);
+            for ast in &mut sg.asts {
+                for cnst in &mut ast.consts {
+                    cx.scope.clear();
+                    cx.opt_expr(&mut cnst.value);
+                }
+                for shfn in &mut ast.fns {
+                    if let Some(block) = &mut shfn.block {
+                        cx.scope = shfn.args.iter().map( | arg | arg.name.clone()).collect();
+                        cx.opt_block(block);
+                    }
+                }
+            }
+            report.folded += cx.folded;
+            report.inlined += cx.inlined;
+            if cx.folded + cx.inlined + cx.trimmed == 0 {
+                break;
+            }
+        }
+        sg.prune(&mut report);
+        
+        let instances = sg.flat_vars(ShVarStore::Instance);
+        report.instance_slots = sg.compute_slot_total(&instances);
+        let mentions = sg.mentions();
+        let unused: Vec<&ShVar> = instances.iter().filter( | inst | !mentions.contains(&inst.name)).collect();
+        // a shader needs at least one instance prop to be drawn instanced
+        if unused.len() < instances.len() {
+            for inst in unused {
+                report.removed_instances.push(inst.name.clone());
+                report.removed_instance_slots += sg.get_type_slots(&inst.ty);
+            }
+            for ast in &mut sg.asts {
+                ast.vars.retain( | var | var.store != ShVarStore::Instance || !report.removed_instances.contains(&var.name));
+            }
+        }
+        
+        // anything that fails here is a bug in the optimizer, not in the shader
+        if let Err(errors) = sg.type_check() {
+            let err: SlErr = errors.into();
+            return Err(SlErr {msg: format!("shader optimizer produced an invalid shader:\n{}", err.msg)})
+        }
+        Ok((sg, report))
+    }
+    
+    fn prune(&mut self, report: &mut ShOptReport) {
+        let reached = self.reachable_fns();
+        for ast in &mut self.asts {
+            ast.fns.retain( | shfn | {
+                if shfn.block.is_none() || reached.contains(&shfn.name) {
+                    return true
+                }
+                if !report.removed_fns.contains(&shfn.name) {
+                    report.removed_fns.push(shfn.name.clone());
+                }
+                false
+            });
+        }
+        
+        // locals and varyings that are only ever assigned can go, along with the assignments
+        loop {
+            let reads = self.reads();
+            let dead: Vec<String> = self.asts.iter().flat_map( | ast | ast.vars.iter()).filter( | var | {
+                (var.store == ShVarStore::Local || var.store == ShVarStore::Varying) && !reads.contains(&var.name)
+            }).map( | var | var.name.clone()).collect();
+            let sg = self.clone();
+            let mut removed = 0;
+            for ast in &mut self.asts {
+                for shfn in &mut ast.fns {
+                    if let Some(block) = &mut shfn.block {
+                        removed += remove_assigns(&sg, block, &dead);
+                    }
+                }
+            }
+            if removed == 0 {
+                break;
+            }
+        }
+        
+        let mentions = self.mentions();
+        for ast in &mut self.asts {
+            ast.vars.retain( | var | {
+                if var.store != ShVarStore::Local && var.store != ShVarStore::Varying || mentions.contains(&var.name) {
+                    return true
+                }
+                if !report.removed_vars.contains(&var.name) {
+                    report.removed_vars.push(var.name.clone());
+                }
+                false
+            });
+            ast.consts.retain( | cnst | {
+                if mentions.contains(&cnst.name) {
+                    return true
+                }
+                if !report.removed_consts.contains(&cnst.name) {
+                    report.removed_consts.push(cnst.name.clone());
+                }
+                false
+            });
+        }
+    }
+    
+    fn reachable_fns(&self) -> Vec<String> {
+        let mut reached = vec!["vertex".to_string(), "pixel".to_string()];
+        let mut index = 0;
+        while index < reached.len() {
+            if let Some(shfn) = self.find_fn(&reached[index]) {
+                if let Some(block) = &shfn.block {
+                    let mut calls = Vec::new();
+                    walk_block(block, &mut | expr | {
+                        if let ShExpr::ShCall(x) = expr {
+                            calls.push(x.call.clone());
+                        }
+                    });
+                    for call in calls {
+                        if !reached.contains(&call) {
+                            reached.push(call);
+                        }
+                    }
+                }
+            }
+            index += 1;
+        }
+        reached
+    }
+    
+    // the names read by the reachable fns and by the consts they use
+    fn reads(&self) -> HashSet<String> {
+        self.collect_ids(false)
+    }
+    
+    // the names read or assigned
+    fn mentions(&self) -> HashSet<String> {
+        self.collect_ids(true)
+    }
+    
+    fn collect_ids(&self, with_assigns: bool) -> HashSet<String> {
+        let mut ids = HashSet::new();
+        for name in self.reachable_fns() {
+            if let Some(shfn) = self.find_fn(&name) {
+                if let Some(block) = &shfn.block {
+                    walk_block(block, &mut | expr | collect_id(expr, with_assigns, &mut ids));
+                }
+            }
+        }
+        // consts can use other consts
+        let mut done = HashSet::new();
+        loop {
+            let todo: Vec<String> = ids.iter().filter( | id | !done.contains(*id)).cloned().collect();
+            if todo.len() == 0 {
+                break;
+            }
+            for id in todo {
+                if let Some(cnst) = self.find_const(&id) {
+                    walk_expr(&cnst.value, &mut | expr | collect_id(expr, with_assigns, &mut ids));
+                }
+                done.insert(id);
+            }
+        }
+        ids
+    }
+}
+
+pub(crate) fn collect_id(expr: &ShExpr, with_assigns: bool, ids: &mut HashSet<String>) {
+    match expr {
+        ShExpr::ShId(x) => {
+            ids.insert(x.name.clone());
+        },
+        ShExpr::ShAssign(x) if with_assigns => if let ShExpr::ShId(x) = &*x.left {
+            ids.insert(x.name.clone());
+        },
+        _ => ()
+    }
+}
+
+// calls f on every expression below and including expr, except the direct target of an assignment
+// when it is a plain id, assigning to a name is not reading it
+pub(crate) fn walk_expr<F: FnMut(&ShExpr)>(expr: &ShExpr, f: &mut F) {
+    f(expr);
+    match expr {
+        ShExpr::ShId(_) | ShExpr::ShLit(_) | ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => (),
+        ShExpr::ShField(x) => walk_expr(&x.base, f),
+        ShExpr::ShIndex(x) => {
+            walk_expr(&x.base, f);
+            walk_expr(&x.index, f);
+        },
+        ShExpr::ShAssign(x) => {
+            if let ShExpr::ShId(_) = &*x.left {}else {walk_expr(&x.left, f)}
+            walk_expr(&x.right, f);
+        },
+        ShExpr::ShAssignOp(x) => {
+            walk_expr(&x.left, f);
+            walk_expr(&x.right, f);
+        },
+        ShExpr::ShBinary(x) => {
+            walk_expr(&x.left, f);
+            walk_expr(&x.right, f);
+        },
+        ShExpr::ShUnary(x) => walk_expr(&x.expr, f),
+        ShExpr::ShParen(x) => walk_expr(&x.expr, f),
+        ShExpr::ShBlock(x) => walk_block(x, f),
+        ShExpr::ShCall(x) => for arg in &x.args {
+            walk_expr(arg, f);
+        },
+        ShExpr::ShIf(x) => {
+            walk_expr(&x.cond, f);
+            walk_block(&x.then_branch, f);
+            if let Some(else_branch) = &x.else_branch {
+                walk_expr(else_branch, f);
+            }
+        },
+        ShExpr::ShWhile(x) => {
+            walk_expr(&x.cond, f);
+            walk_block(&x.body, f);
+        },
+        ShExpr::ShForLoop(x) => {
+            walk_expr(&x.from, f);
+            walk_expr(&x.to, f);
+            walk_block(&x.body, f);
+        },
+        ShExpr::ShReturn(x) => if let Some(expr) = &x.expr {
+            walk_expr(expr, f);
+        }
+    }
+}
+
+pub(crate) fn walk_block<F: FnMut(&ShExpr)>(block: &ShBlock, f: &mut F) {
+    for stmt in &block.stmts {
+        match &**stmt {
+            ShStmt::ShLet(x) => walk_expr(&x.init, f),
+            ShStmt::ShExpr(x) | ShStmt::ShSemi(x) => walk_expr(x, f)
+        }
+    }
+}
+
+// drops `name = expr;` statements for dead names when expr has no side effects
+fn remove_assigns(sg: &ShaderGen, block: &mut ShBlock, dead: &Vec<String>) -> usize {
+    let mut removed = 0;
+    block.stmts.retain( | stmt | {
+        if let ShStmt::ShExpr(ShExpr::ShAssign(x)) | ShStmt::ShSemi(ShExpr::ShAssign(x)) = &**stmt {
+            if let ShExpr::ShId(id) = &*x.left {
+                if dead.contains(&id.name) && is_pure(sg, &x.right) {
+                    removed += 1;
+                    return false
+                }
+            }
+        }
+        true
+    });
+    for stmt in &mut block.stmts {
+        if let ShStmt::ShExpr(x) | ShStmt::ShSemi(x) = &mut **stmt {
+            removed += remove_assigns_expr(sg, x, dead);
+        }
+    }
+    removed
+}
+
+fn remove_assigns_expr(sg: &ShaderGen, expr: &mut ShExpr, dead: &Vec<String>) -> usize {
+    match expr {
+        ShExpr::ShBlock(x) => remove_assigns(sg, x, dead),
+        ShExpr::ShIf(x) => {
+            let mut removed = remove_assigns(sg, &mut x.then_branch, dead);
+            if let Some(else_branch) = &mut x.else_branch {
+                removed += remove_assigns_expr(sg, else_branch, dead);
+            }
+            removed
+        },
+        ShExpr::ShWhile(x) => remove_assigns(sg, &mut x.body, dead),
+        ShExpr::ShForLoop(x) => remove_assigns(sg, &mut x.body, dead),
+        _ => 0
+    }
+}
+
+// a builtin or a type constructor, these have no side effects
+fn is_builtin_call(sg: &ShaderGen, call: &str) -> bool {
+    if let Some(shfn) = sg.find_fn(call) {
+        return shfn.block.is_none()
+    }
+    sg.find_type(call).is_some()
+}
+
+fn is_pure(sg: &ShaderGen, expr: &ShExpr) -> bool {
+    match expr {
+        ShExpr::ShId(_) | ShExpr::ShLit(_) => true,
+        ShExpr::ShField(x) => is_pure(sg, &x.base),
+        ShExpr::ShIndex(x) => is_pure(sg, &x.base) && is_pure(sg, &x.index),
+        ShExpr::ShBinary(x) => is_pure(sg, &x.left) && is_pure(sg, &x.right),
+        ShExpr::ShUnary(x) => is_pure(sg, &x.expr),
+        ShExpr::ShParen(x) => is_pure(sg, &x.expr),
+        ShExpr::ShCall(x) => is_builtin_call(sg, &x.call) && x.args.iter().all( | arg | is_pure(sg, arg)),
+        _ => false
+    }
+}
+
+// a value the optimizer can compute, literals and constructors of literals
+fn is_const(sg: &ShaderGen, expr: &ShExpr) -> bool {
+    match expr {
+        ShExpr::ShLit(ShLit::Str(_)) => false,
+        ShExpr::ShLit(_) => true,
+        ShExpr::ShParen(x) => is_const(sg, &x.expr),
+        ShExpr::ShUnary(x) => is_const(sg, &x.expr),
+        ShExpr::ShCall(x) => {
+            if let Some(shtype) = sg.find_type(&x.call) {
+                return shtype.prim && x.args.iter().all( | arg | is_const(sg, arg))
+            }
+            false
+        },
+        _ => false
+    }
+}
+
+// literals and whatever else can be pasted into an expression without parens
+fn is_atom(expr: &ShExpr) -> bool {
+    match expr {
+        ShExpr::ShId(_) | ShExpr::ShParen(_) | ShExpr::ShCall(_) => true,
+        ShExpr::ShLit(ShLit::Float(v)) => !v.is_sign_negative(),
+        ShExpr::ShLit(ShLit::Int(v)) => *v >= 0,
+        ShExpr::ShLit(_) => true,
+        ShExpr::ShField(x) => is_atom(&x.base),
+        _ => false
+    }
+}
+
+fn paren(expr: ShExpr) -> ShExpr {
+    if is_atom(&expr) {
+        return expr
+    }
+    ShExpr::ShParen(ShParen {expr: Box::new(expr)})
+}
+
+fn float_lit(v: f32) -> ShExpr {
+    // go through the f32 text so 0.1 stays 0.1 instead of 0.10000000149011612
+    let v64 = format!("{}", v).parse::<f64>().unwrap_or(v as f64);
+    paren(ShExpr::ShLit(ShLit::Float(v64)))
+}
+
+fn value_to_expr(value: &ShValue, loc: ShLoc) -> Option<ShExpr> {
+    match value {
+        ShValue::Float(v) if v.is_finite() => Some(float_lit(*v)),
+        ShValue::Int(v) => Some(paren(ShExpr::ShLit(ShLit::Int(*v)))),
+        ShValue::Bool(v) => Some(ShExpr::ShLit(ShLit::Bool(*v))),
+        ShValue::Vec(v) if v.iter().all( | v | v.is_finite()) => Some(ShExpr::ShCall(ShCall {
+            call: value.ty(),
+            args: v.iter().map( | v | Box::new(float_lit(*v))).collect(),
+            loc: loc
+        })),
+        _ => None
+    }
+}
+
+fn count_id(expr: &ShExpr, name: &str) -> usize {
+    let mut count = 0;
+    walk_expr(expr, &mut | expr | if let ShExpr::ShId(x) = expr {
+        if x.name == name {
+            count += 1;
+        }
+    });
+    count
+}
+
+fn substitute(expr: &mut ShExpr, params: &Vec<(String, ShExpr)>) {
+    if let ShExpr::ShId(x) = expr {
+        if let Some((_, arg)) = params.iter().find( | (name, _) | *name == x.name) {
+            *expr = paren(arg.clone());
+        }
+        return
+    }
+    match expr {
+        ShExpr::ShField(x) => substitute(&mut x.base, params),
+        ShExpr::ShIndex(x) => {
+            substitute(&mut x.base, params);
+            substitute(&mut x.index, params);
+        },
+        ShExpr::ShBinary(x) => {
+            substitute(&mut x.left, params);
+            substitute(&mut x.right, params);
+        },
+        ShExpr::ShUnary(x) => substitute(&mut x.expr, params),
+        ShExpr::ShParen(x) => substitute(&mut x.expr, params),
+        ShExpr::ShCall(x) => for arg in &mut x.args {
+            substitute(arg, params);
+        },
+        _ => ()
+    }
+}
+
+// builtins that depend on more than their arguments
+const UNFOLDABLE: [&str; 6] = ["dfdx", "dfdy", "fwidth", "sample2d", "sizeof", "color"];
+
+struct ShOptCx<'a> {
+    sg: &'a ShaderGen,
+    // consts that have a literal value
+    consts: Vec<(String, ShExpr)>,
+    scope: Vec<String>,
+    folded: usize,
+    inlined: usize,
+    trimmed: usize
+}
+
+impl<'a> ShOptCx<'a> {
+    fn new(sg: &'a ShaderGen) -> Self {
+        let mut consts = Vec::new();
+        for cnst in sg.flat_consts() {
+            if consts.iter().any( | (name, _) | *name == cnst.name) || sg.find_var(&cnst.name).is_some() {
+                continue;
+            }
+            if let Some(cnst) = sg.find_const(&cnst.name) {
+                let value = match &cnst.value {
+                    ShExpr::ShParen(x) => &*x.expr,
+                    value => value
+                };
+                if let ShExpr::ShLit(ShLit::Float(_)) | ShExpr::ShLit(ShLit::Int(_)) | ShExpr::ShLit(ShLit::Bool(_)) = value {
+                    consts.push((cnst.name.clone(), cnst.value.clone()));
+                }
+            }
+        }
+        ShOptCx {
+            sg: sg,
+            consts: consts,
+            scope: Vec::new(),
+            folded: 0,
+            inlined: 0,
+            trimmed: 0
+        }
+    }
+    
+    fn opt_block(&mut self, block: &mut ShBlock) {
+        let scope_len = self.scope.len();
+        let mut stmts = Vec::new();
+        let total = block.stmts.len();
+        for mut stmt in std::mem::replace(&mut block.stmts, Vec::new()) {
+            let exits = match &mut *stmt {
+                ShStmt::ShLet(x) => {
+                    self.opt_expr(&mut x.init);
+                    self.scope.push(x.name.clone());
+                    false
+                },
+                ShStmt::ShExpr(x) | ShStmt::ShSemi(x) => {
+                    self.opt_expr(x);
+                    // an if or while that folded away leaves an empty block
+                    if let ShExpr::ShBlock(x) = x {
+                        if x.stmts.len() == 0 {
+                            continue;
+                        }
+                    }
+                    match x {
+                        ShExpr::ShReturn(_) | ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => true,
+                        _ => false
+                    }
+                }
+            };
+            stmts.push(stmt);
+            // nothing after a return runs
+            if exits {
+                break;
+            }
+        }
+        self.trimmed += total - stmts.len();
+        block.stmts = stmts;
+        self.scope.truncate(scope_len);
+    }
+    
+    fn opt_expr(&mut self, expr: &mut ShExpr) {
+        match expr {
+            ShExpr::ShId(_) | ShExpr::ShLit(_) | ShExpr::ShBreak(_) | ShExpr::ShContinue(_) => (),
+            ShExpr::ShField(x) => self.opt_expr(&mut x.base),
+            ShExpr::ShIndex(x) => {
+                self.opt_expr(&mut x.base);
+                self.opt_expr(&mut x.index);
+            },
+            ShExpr::ShAssign(x) => {
+                self.opt_expr(&mut x.left);
+                self.opt_expr(&mut x.right);
+            },
+            ShExpr::ShAssignOp(x) => {
+                self.opt_expr(&mut x.left);
+                self.opt_expr(&mut x.right);
+            },
+            ShExpr::ShBinary(x) => {
+                self.opt_expr(&mut x.left);
+                self.opt_expr(&mut x.right);
+            },
+            ShExpr::ShUnary(x) => self.opt_expr(&mut x.expr),
+            ShExpr::ShParen(x) => self.opt_expr(&mut x.expr),
+            ShExpr::ShBlock(x) => self.opt_block(x),
+            ShExpr::ShCall(x) => for arg in &mut x.args {
+                self.opt_expr(arg);
+            },
+            ShExpr::ShIf(x) => {
+                self.opt_expr(&mut x.cond);
+                self.opt_block(&mut x.then_branch);
+                if let Some(else_branch) = &mut x.else_branch {
+                    self.opt_expr(else_branch);
+                }
+            },
+            ShExpr::ShWhile(x) => {
+                self.opt_expr(&mut x.cond);
+                self.opt_block(&mut x.body);
+            },
+            ShExpr::ShForLoop(x) => {
+                self.opt_expr(&mut x.from);
+                self.opt_expr(&mut x.to);
+                self.scope.push(x.iter.clone());
+                self.opt_block(&mut x.body);
+                self.scope.pop();
+            },
+            ShExpr::ShReturn(x) => if let Some(expr) = &mut x.expr {
+                self.opt_expr(expr);
+            }
+        }
+        if let Some(new_expr) = self.fold(expr) {
+            self.folded += 1;
+            *expr = new_expr;
+        }
+        else if let Some(new_expr) = self.inline(expr) {
+            self.inlined += 1;
+            *expr = new_expr;
+        }
+    }
+    
+    fn fold(&self, expr: &ShExpr) -> Option<ShExpr> {
+        let sg = self.sg;
+        let (foldable, loc) = match expr {
+            ShExpr::ShId(x) => {
+                if self.scope.contains(&x.name) {
+                    return None
+                }
+                return self.consts.iter().find( | (name, _) | *name == x.name).map( | (_, value) | value.clone())
+            },
+            ShExpr::ShBinary(x) => (is_const(sg, &x.left) && is_const(sg, &x.right), x.loc),
+            // a negative literal is kept as a literal in parens
+            ShExpr::ShUnary(x) => (is_const(sg, &x.expr), x.loc),
+            ShExpr::ShCall(x) => {
+                let prim_cons = x.call == "float" || x.call == "int" || x.call == "bool";
+                let builtin = sg.find_fn(&x.call).map_or(false, | shfn | shfn.block.is_none());
+                (
+                    (prim_cons || builtin && !UNFOLDABLE.contains(&x.call.as_str())) && x.args.iter().all( | arg | is_const(sg, arg)),
+                    x.loc
+                )
+            },
+            // an if or while on a constant only keeps what runs
+            ShExpr::ShIf(x) => {
+                if let ShExpr::ShLit(ShLit::Bool(cond)) = &*x.cond {
+                    if *cond {
+                        return Some(ShExpr::ShBlock(x.then_branch.clone()))
+                    }
+                    if let Some(else_branch) = &x.else_branch {
+                        return Some((**else_branch).clone())
+                    }
+                    return Some(ShExpr::ShBlock(ShBlock {stmts: Vec::new()}))
+                }
+                return None
+            },
+            ShExpr::ShWhile(x) => {
+                if let ShExpr::ShLit(ShLit::Bool(false)) = &*x.cond {
+                    return Some(ShExpr::ShBlock(ShBlock {stmts: Vec::new()}))
+                }
+                return None
+            },
+            _ => (false, ShLoc::default())
+        };
+        if !foldable {
+            return None
+        }
+        let value = ShEval::new(sg).eval(expr).ok() ?;
+        let new_expr = value_to_expr(&value, loc) ?;
+        if new_expr == *expr {
+            return None
+        }
+        Some(new_expr)
+    }
+    
+    // a call to a helper that only returns an expression of its args becomes that expression
+    fn inline(&self, expr: &ShExpr) -> Option<ShExpr> {
+        let sg = self.sg;
+        let call = if let ShExpr::ShCall(x) = expr {x}else {return None};
+        let shfn = sg.find_fn(&call.call) ?;
+        if shfn.name == "vertex" || shfn.name == "pixel" || shfn.args.len() != call.args.len() {
+            return None
+        }
+        let block = shfn.block.as_ref() ?;
+        if block.stmts.len() != 1 {
+            return None
+        }
+        let body = match &*block.stmts[0] {
+            ShStmt::ShExpr(ShExpr::ShReturn(ret)) | ShStmt::ShSemi(ShExpr::ShReturn(ret)) => ret.expr.as_ref() ?,
+            _ => return None
+        };
+        if !self.args_only(body, &shfn.args) {
+            return None
+        }
+        let mut params = Vec::new();
+        for (arg, value) in shfn.args.iter().zip(call.args.iter()) {
+            // args are evaluated once in a call, so only simple ones can be pasted in more than once
+            let uses = count_id(body, &arg.name);
+            if !is_pure(sg, value) || uses > 1 && !is_atom(value) {
+                return None
+            }
+            params.push((arg.name.clone(), (**value).clone()));
+        }
+        let mut body = (**body).clone();
+        substitute(&mut body, &params);
+        Some(paren(body))
+    }
+    
+    fn args_only(&self, expr: &ShExpr, args: &Vec<ShFnArg>) -> bool {
+        match expr {
+            ShExpr::ShId(x) => args.iter().any( | arg | arg.name == x.name),
+            ShExpr::ShLit(_) => true,
+            ShExpr::ShField(x) => self.args_only(&x.base, args),
+            ShExpr::ShIndex(x) => self.args_only(&x.base, args) && self.args_only(&x.index, args),
+            ShExpr::ShBinary(x) => self.args_only(&x.left, args) && self.args_only(&x.right, args),
+            ShExpr::ShUnary(x) => self.args_only(&x.expr, args),
+            ShExpr::ShParen(x) => self.args_only(&x.expr, args),
+            ShExpr::ShCall(x) => is_builtin_call(self.sg, &x.call) && x.args.iter().all( | arg | self.args_only(arg, args)),
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use super::walk_block;
+    
+    fn pixel_block(sg: &ShaderGen) -> &ShBlock {
+        sg.find_fn("pixel").unwrap().block.as_ref().unwrap()
+    }
+    
+    fn pixel_has(sg: &ShaderGen, pred: impl Fn(&ShExpr) -> bool) -> bool {
+        let mut found = false;
+        walk_block(pixel_block(sg), &mut | expr | found = found || pred(expr));
+        found
+    }
+    
+    #[test]
+    fn folds_constant_expressions() {
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            const half_alpha: float = 0.5;
+            
+            fn pixel() -> vec4 {
+                return vec4(color.rgb, 1. - half_alpha * 2. * 0.25);
+            }
+        }));
+        let (opt_sg, report) = sg.optimize().unwrap();
+        assert!(report.folded > 0);
+        assert_eq!(report.removed_consts, vec!["half_alpha".to_string()]);
+        assert!(!pixel_has(&opt_sg, | expr | if let ShExpr::ShBinary(_) = expr {true}else {false}));
+        assert!(pixel_has(&opt_sg, | expr | *expr == ShExpr::ShLit(ShLit::Float(0.75))));
+    }
+    
+    #[test]
+    fn inlines_one_line_helpers() {
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            fn premul(c: vec4) -> vec3 {
+                return c.rgb * c.a;
+            }
+            
+            fn pixel() -> vec4 {
+                return vec4(premul(color), color.a);
+            }
+        }));
+        let (opt_sg, report) = sg.optimize().unwrap();
+        assert_eq!(report.inlined, 1);
+        assert_eq!(report.removed_fns, vec!["premul".to_string()]);
+        assert!(opt_sg.find_fn("premul").is_none());
+        assert!(!pixel_has(&opt_sg, | expr | if let ShExpr::ShCall(x) = expr {x.call == "premul"}else {false}));
+    }
+    
+    #[test]
+    fn removes_dead_assignments() {
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            let unused: vec4<Local>;
+            
+            fn pixel() -> vec4 {
+                unused = color * 2.;
+                return color;
+            }
+        }));
+        let (opt_sg, report) = sg.optimize().unwrap();
+        // pos goes as well, only the default pixel fn reads it
+        assert!(report.removed_vars.contains(&"unused".to_string()));
+        assert_eq!(pixel_block(&opt_sg).stmts.len(), 1);
+        assert!(opt_sg.flat_vars(ShVarStore::Local).iter().all( | var | var.name != "unused"));
+    }
+    
+    #[test]
+    fn removes_unread_instances() {
+        let sg = Quad::def_quad_shader().compose(shader_ast!({
+            fn pixel() -> vec4 {
+                return vec4(1., 0., 0., 1.);
+            }
+        }));
+        let (opt_sg, report) = sg.optimize().unwrap();
+        assert_eq!(report.removed_instances, vec!["color".to_string()]);
+        assert_eq!(report.removed_instance_slots, 4);
+        
+        // the widgets keep writing x, y, w, h, color, the gpu only gets x, y, w, h
+        let mapping = CxShaderMapping::construct_optimized(&sg, &opt_sg);
+        assert_eq!(mapping.instance_slots, 8);
+        assert_eq!(mapping.gpu_instance_slots(), 4);
+        let instance: Vec<f32> = (0..16).map( | i | i as f32).collect();
+        assert_eq!(mapping.gather_instances(&instance), vec![0., 1., 2., 3., 8., 9., 10., 11.]);
+    }
+    
+    #[test]
+    fn keeps_instances_when_all_are_read() {
+        let sg = Quad::def_quad_shader();
+        let (opt_sg, report) = sg.optimize().unwrap();
+        assert!(report.removed_instances.is_empty());
+        let mapping = CxShaderMapping::construct_optimized(&sg, &opt_sg);
+        assert!(mapping.instance_gather.is_empty());
+        assert_eq!(mapping.gpu_instance_slots(), mapping.instance_slots);
+    }
+}
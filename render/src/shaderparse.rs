@@ -0,0 +1,829 @@
+// Runtime parser for shader source text
+// it reads the same rust subset as the shader_ast! macro and builds the same ShAst
+// so a shader can be edited in the running application and swapped in without a rebuild
+
+use crate::shadergen::*;
+use crate::shadercheck::*;
+
+const STORES: [&str; 8] = ["Uniform", "UniformVw", "UniformCx", "Instance", "Geometry", "Texture", "Local", "Varying"];
+
+const PUNCTS: [&str; 41] = [
+    "<<=", ">>=", "::", "->", "..", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>",
+    "+", "-", "*", "/", "%", "^", "&", "|", "!", "<", ">", "=", "(", ")", "{", "}", "[", "]", ",", ";"
+];
+
+// a shader_ast! block found in a rust source file, line and col are where its { starts, both count from 1 like ShLoc
+#[derive(Clone, Debug)]
+pub struct ShSourceBlock {
+    pub line: u32,
+    pub col: u32,
+    pub source: String
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ShTok {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Punct(&'static str),
+    Eof
+}
+
+#[derive(Clone, Debug)]
+struct ShToken {
+    tok: ShTok,
+    loc: ShLoc
+}
+
+struct ShParser {
+    toks: Vec<ShToken>,
+    pos: usize,
+    // the function being parsed, for the diagnostics
+    fn_name: String
+}
+
+// parse shader source text into a ShAst, line and col are where the source starts in its file
+// so the locations in the ast and in the errors point into that file
+pub fn parse_shader_ast(file: &'static str, source: &str, line: u32, col: u32) -> Result<ShAst, Vec<ShTypeError>> {
+    let toks = tokenize(file, source, line, col).map_err( | err | vec![err]) ?;
+    let mut parser = ShParser {
+        toks: toks,
+        pos: 0,
+        fn_name: "".to_string()
+    };
+    parser.parse_root().map_err( | err | vec![err])
+}
+
+// find the shader_ast!({..}) blocks in a rust source file
+pub fn find_shader_ast_blocks(text: &str) -> Vec<ShSourceBlock> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut col = 1;
+    while i < chars.len() {
+        let skip = skip_non_code(&chars, i);
+        if skip > i {
+            while i < skip {
+                advance(&chars, &mut i, &mut line, &mut col);
+            }
+            continue;
+        }
+        if starts_with(&chars, i, "shader_ast!") && (i == 0 || !is_ident_char(chars[i - 1])) {
+            for _ in 0.."shader_ast!".len() {
+                advance(&chars, &mut i, &mut line, &mut col);
+            }
+            // skip up to the opening brace of the block
+            while i < chars.len() && (chars[i].is_whitespace() || chars[i] == '(') {
+                advance(&chars, &mut i, &mut line, &mut col);
+            }
+            if i >= chars.len() || chars[i] != '{' {
+                continue;
+            }
+            let (start, start_line, start_col) = (i, line, col);
+            let mut depth = 0;
+            while i < chars.len() {
+                let skip = skip_non_code(&chars, i);
+                if skip > i {
+                    while i < skip {
+                        advance(&chars, &mut i, &mut line, &mut col);
+                    }
+                    continue;
+                }
+                let chr = chars[i];
+                advance(&chars, &mut i, &mut line, &mut col);
+                if chr == '{' {
+                    depth += 1;
+                }
+                else if chr == '}' {
+                    depth -= 1;
+                    if depth == 0 {
+                        blocks.push(ShSourceBlock {
+                            line: start_line,
+                            col: start_col,
+                            source: chars[start..i].iter().collect()
+                        });
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+        advance(&chars, &mut i, &mut line, &mut col);
+    }
+    blocks
+}
+
+fn advance(chars: &Vec<char>, i: &mut usize, line: &mut u32, col: &mut u32) {
+    if chars[*i] == '\n' {
+        *line += 1;
+        *col = 1;
+    }
+    else {
+        *col += 1;
+    }
+    *i += 1;
+}
+
+fn starts_with(chars: &Vec<char>, i: usize, what: &str) -> bool {
+    let mut j = i;
+    for chr in what.chars() {
+        if j >= chars.len() || chars[j] != chr {
+            return false
+        }
+        j += 1;
+    }
+    true
+}
+
+fn is_ident_char(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_'
+}
+
+// returns the index after a comment, string or char literal starting at i, or i if there is none
+fn skip_non_code(chars: &Vec<char>, i: usize) -> usize {
+    if starts_with(chars, i, "//") {
+        let mut j = i;
+        while j < chars.len() && chars[j] != '\n' {
+            j += 1;
+        }
+        return j
+    }
+    if starts_with(chars, i, "/*") {
+        let mut j = i + 2;
+        while j < chars.len() && !starts_with(chars, j, "*/") {
+            j += 1;
+        }
+        return (j + 2).min(chars.len())
+    }
+    if chars[i] == '"' {
+        let mut j = i + 1;
+        while j < chars.len() && chars[j] != '"' {
+            if chars[j] == '\\' {
+                j += 1;
+            }
+            j += 1;
+        }
+        return (j + 1).min(chars.len())
+    }
+    // char literals like '{', but not lifetimes like 'a
+    if chars[i] == '\'' {
+        if i + 2 < chars.len() && chars[i + 1] != '\\' && chars[i + 2] == '\'' {
+            return i + 3
+        }
+        if i + 3 < chars.len() && chars[i + 1] == '\\' && chars[i + 3] == '\'' {
+            return i + 4
+        }
+    }
+    i
+}
+
+fn tokenize(file: &'static str, source: &str, line: u32, col: u32) -> Result<Vec<ShToken>, ShTypeError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut toks = Vec::new();
+    let mut i = 0;
+    let mut line = line;
+    let mut col = col;
+    while i < chars.len() {
+        let chr = chars[i];
+        if chr.is_whitespace() {
+            advance(&chars, &mut i, &mut line, &mut col);
+            continue;
+        }
+        if starts_with(&chars, i, "//") || starts_with(&chars, i, "/*") {
+            let skip = skip_non_code(&chars, i);
+            while i < skip {
+                advance(&chars, &mut i, &mut line, &mut col);
+            }
+            continue;
+        }
+        let loc = ShLoc {file: file, line: line, col: col};
+        let start = i;
+        let tok = if chr.is_alphabetic() || chr == '_' {
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            ShTok::Ident(chars[start..i].iter().collect())
+        }
+        else if chr.is_ascii_digit() {
+            let mut is_float = false;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '_') {
+                i += 1;
+            }
+            // 1. and 1.5 are floats, 1..2 is a range and 1.x a field
+            if i < chars.len() && chars[i] == '.' && (i + 1 >= chars.len() || (chars[i + 1] != '.' && !chars[i + 1].is_alphabetic() && chars[i + 1] != '_')) {
+                is_float = true;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '_') {
+                    i += 1;
+                }
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    is_float = true;
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let digits: String = chars[start..i].iter().filter( | c | **c != '_').collect();
+            // type suffixes like 1f32 or 2u32
+            let suffix_start = i;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            if i > suffix_start && chars[suffix_start] == 'f' {
+                is_float = true;
+            }
+            if is_float {
+                match digits.parse::<f64>() {
+                    Ok(value) => ShTok::Float(value),
+                    Err(_) => return Err(parse_error(loc, "", format!("invalid float literal {}", digits)))
+                }
+            }
+            else {
+                match digits.parse::<i64>() {
+                    Ok(value) => ShTok::Int(value),
+                    Err(_) => return Err(parse_error(loc, "", format!("invalid integer literal {}", digits)))
+                }
+            }
+        }
+        else if chr == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                    value.push(match chars[i] {
+                        'n' => '\n',
+                        't' => '\t',
+                        chr => chr
+                    });
+                }
+                else {
+                    value.push(chars[i]);
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(parse_error(loc, "", "unterminated string literal".to_string()))
+            }
+            i += 1;
+            ShTok::Str(value)
+        }
+        else if let Some(punct) = PUNCTS.iter().find( | punct | starts_with(&chars, i, punct)) {
+            i += punct.len();
+            ShTok::Punct(punct)
+        }
+        else if chr == ':' || chr == '.' {
+            i += 1;
+            ShTok::Punct(if chr == ':' {":"}else {"."})
+        }
+        else {
+            return Err(parse_error(loc, "", format!("unexpected character {}", chr)))
+        };
+        // strings can span lines
+        let mut j = start;
+        while j < i {
+            advance(&chars, &mut j, &mut line, &mut col);
+        }
+        toks.push(ShToken {tok: tok, loc: loc});
+    }
+    toks.push(ShToken {tok: ShTok::Eof, loc: ShLoc {file: file, line: line, col: col}});
+    Ok(toks)
+}
+
+fn parse_error(loc: ShLoc, fn_name: &str, msg: String) -> ShTypeError {
+    ShTypeError {
+        msg: msg,
+        loc: loc,
+        fn_name: fn_name.to_string()
+    }
+}
+
+fn get_binop(punct: &str) -> Option<(ShBinOp, usize)> {
+    match punct {
+        "||" => Some((ShBinOp::Or, 1)),
+        "&&" => Some((ShBinOp::And, 2)),
+        "==" => Some((ShBinOp::Eq, 3)),
+        "!=" => Some((ShBinOp::Ne, 3)),
+        "<" => Some((ShBinOp::Lt, 3)),
+        "<=" => Some((ShBinOp::Le, 3)),
+        ">" => Some((ShBinOp::Gt, 3)),
+        ">=" => Some((ShBinOp::Ge, 3)),
+        "|" => Some((ShBinOp::BitOr, 4)),
+        "^" => Some((ShBinOp::BitXor, 5)),
+        "&" => Some((ShBinOp::BitAnd, 6)),
+        "<<" => Some((ShBinOp::Shl, 7)),
+        ">>" => Some((ShBinOp::Shr, 7)),
+        "+" => Some((ShBinOp::Add, 8)),
+        "-" => Some((ShBinOp::Sub, 8)),
+        "*" => Some((ShBinOp::Mul, 9)),
+        "/" => Some((ShBinOp::Div, 9)),
+        "%" => Some((ShBinOp::Rem, 9)),
+        _ => None
+    }
+}
+
+fn get_assignop(punct: &str) -> Option<ShBinOp> {
+    match punct {
+        "+=" => Some(ShBinOp::AddEq),
+        "-=" => Some(ShBinOp::SubEq),
+        "*=" => Some(ShBinOp::MulEq),
+        "/=" => Some(ShBinOp::DivEq),
+        "%=" => Some(ShBinOp::RemEq),
+        "^=" => Some(ShBinOp::BitXorEq),
+        "&=" => Some(ShBinOp::BitAndEq),
+        "|=" => Some(ShBinOp::BitOrEq),
+        "<<=" => Some(ShBinOp::ShlEq),
+        ">>=" => Some(ShBinOp::ShrEq),
+        _ => None
+    }
+}
+
+fn get_store(name: &str) -> ShVarStore {
+    match name {
+        "Uniform" => ShVarStore::Uniform,
+        "UniformVw" => ShVarStore::UniformVw,
+        "UniformCx" => ShVarStore::UniformCx,
+        "Instance" => ShVarStore::Instance,
+        "Geometry" => ShVarStore::Geometry,
+        "Texture" => ShVarStore::Texture,
+        "Varying" => ShVarStore::Varying,
+        _ => ShVarStore::Local
+    }
+}
+
+impl ShParser {
+    fn peek(&self) -> &ShTok {
+        &self.toks[self.pos].tok
+    }
+    
+    fn loc(&self) -> ShLoc {
+        self.toks[self.pos].loc
+    }
+    
+    fn next(&mut self) -> ShToken {
+        let tok = self.toks[self.pos].clone();
+        if self.pos < self.toks.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+    
+    fn error<T>(&self, msg: &str) -> Result<T, ShTypeError> {
+        let found = match self.peek() {
+            ShTok::Ident(name) => name.clone(),
+            ShTok::Int(value) => value.to_string(),
+            ShTok::Float(value) => value.to_string(),
+            ShTok::Str(value) => format!("\"{}\"", value),
+            ShTok::Punct(punct) => punct.to_string(),
+            ShTok::Eof => "end of input".to_string()
+        };
+        Err(parse_error(self.loc(), &self.fn_name, format!("{}, found {}", msg, found)))
+    }
+    
+    fn is_punct(&self, what: &str) -> bool {
+        if let ShTok::Punct(punct) = self.peek() {
+            return *punct == what
+        }
+        false
+    }
+    
+    fn is_ident(&self, what: &str) -> bool {
+        if let ShTok::Ident(name) = self.peek() {
+            return name == what
+        }
+        false
+    }
+    
+    fn expect_punct(&mut self, what: &str) -> Result<ShLoc, ShTypeError> {
+        if !self.is_punct(what) {
+            return self.error(&format!("expected {}", what))
+        }
+        Ok(self.next().loc)
+    }
+    
+    fn expect_ident(&mut self, what: &str) -> Result<(String, ShLoc), ShTypeError> {
+        if let ShTok::Ident(name) = self.peek() {
+            let name = name.clone();
+            return Ok((name, self.next().loc))
+        }
+        self.error(&format!("expected {}", what))
+    }
+    
+    fn parse_root(&mut self) -> Result<ShAst, ShTypeError> {
+        let mut ast = ShAst {
+            types: Vec::new(),
+            vars: Vec::new(),
+            consts: Vec::new(),
+            fns: Vec::new(),
+            imports: Vec::new(),
+            module: String::new()
+        };
+        // the source can be the block of a shader_ast! or just its contents
+        let braced = self.is_punct("{");
+        if braced {
+            self.next();
+        }
+        loop {
+            if braced && self.is_punct("}") {
+                self.next();
+                break;
+            }
+            if *self.peek() == ShTok::Eof {
+                if braced {
+                    return self.error("expected }")
+                }
+                break;
+            }
+            if self.is_ident("let") {
+                ast.vars.push(self.parse_var_def() ?);
+            }
+            else if self.is_ident("const") {
+                ast.consts.push(self.parse_const_def() ?);
+            }
+            else if self.is_ident("struct") {
+                ast.types.push(self.parse_struct_def() ?);
+            }
+            else if self.is_ident("fn") {
+                ast.fns.push(self.parse_fn_def() ?);
+            }
+            else if self.is_ident("use") {
+                ast.imports.push(self.parse_import() ?);
+            }
+            else {
+                return self.error("expected let, const, struct, fn or use")
+            }
+        }
+        if *self.peek() != ShTok::Eof {
+            return self.error("expected end of shader")
+        }
+        Ok(ast)
+    }
+    
+    fn parse_var_def(&mut self) -> Result<ShVar, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("variable name") ?;
+        self.expect_punct(":") ?;
+        let (ty, _) = self.expect_ident("type") ?;
+        if !self.is_punct("<") {
+            return self.error("type should have storage specifier like float<Uniform> or float<Local>")
+        }
+        self.next();
+        let store_loc = self.loc();
+        let (store, _) = self.expect_ident("storage") ?;
+        if !STORES.contains(&store.as_ref()) {
+            return Err(parse_error(store_loc, "", format!("Unknown storage {}, use one of {}", store, STORES.join(", "))))
+        }
+        if (ty == "texture2d") != (store == "Texture") {
+            return Err(parse_error(store_loc, "", "Textures are declared as texture2d<Texture> and only textures use Texture storage".to_string()))
+        }
+        self.expect_punct(">") ?;
+        self.expect_punct(";") ?;
+        Ok(ShVar {
+            name: name,
+            ty: ty,
+            store: get_store(&store),
+            loc: loc
+        })
+    }
+    
+    fn parse_import(&mut self) -> Result<ShImport, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("module name") ?;
+        if self.is_punct("::") {
+            return self.error("import a shader module as use df; and call its fns as df::circle()")
+        }
+        self.expect_punct(";") ?;
+        Ok(ShImport {
+            name: name,
+            loc: loc
+        })
+    }
+    
+    fn parse_const_def(&mut self) -> Result<ShConst, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("const name") ?;
+        self.expect_punct(":") ?;
+        let (ty, _) = self.expect_ident("type") ?;
+        self.expect_punct("=") ?;
+        let value = self.parse_expr() ?;
+        self.expect_punct(";") ?;
+        Ok(ShConst {
+            name: name,
+            ty: ty,
+            value: value,
+            loc: loc
+        })
+    }
+    
+    fn parse_struct_def(&mut self) -> Result<ShType, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("struct name") ?;
+        self.expect_punct("{") ?;
+        let mut fields = Vec::new();
+        while !self.is_punct("}") {
+            let (field_name, _) = self.expect_ident("field name") ?;
+            self.expect_punct(":") ?;
+            let (ty, _) = self.expect_ident("field type") ?;
+            fields.push(ShTypeField::new(&field_name, &ty));
+            if !self.is_punct(",") {
+                break;
+            }
+            self.next();
+        }
+        self.expect_punct("}") ?;
+        if fields.len() == 0 {
+            return Err(parse_error(loc, "", "struct needs at least one field".to_string()))
+        }
+        Ok(ShType {
+            name: name,
+            slots: 0,
+            prim: false,
+            fields: fields
+        })
+    }
+    
+    fn parse_fn_def(&mut self) -> Result<ShFn, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("function name") ?;
+        self.fn_name = name.clone();
+        self.expect_punct("(") ?;
+        let mut args = Vec::new();
+        while !self.is_punct(")") {
+            let (arg_name, _) = self.expect_ident("argument name") ?;
+            self.expect_punct(":") ?;
+            let (ty, _) = self.expect_ident("argument type") ?;
+            args.push(ShFnArg::new(&arg_name, &ty));
+            if !self.is_punct(",") {
+                break;
+            }
+            self.next();
+        }
+        self.expect_punct(")") ?;
+        let ret = if self.is_punct("->") {
+            self.next();
+            self.expect_ident("return type") ?.0
+        }
+        else {
+            "void".to_string()
+        };
+        let block = self.parse_block() ?;
+        self.fn_name = "".to_string();
+        Ok(ShFn {
+            name: name,
+            args: args,
+            ret: ret,
+            block: Some(block),
+            loc: loc
+        })
+    }
+    
+    fn parse_block(&mut self) -> Result<ShBlock, ShTypeError> {
+        self.expect_punct("{") ?;
+        let mut stmts = Vec::new();
+        while !self.is_punct("}") {
+            if self.is_ident("let") {
+                stmts.push(Box::new(ShStmt::ShLet(self.parse_let() ?)));
+                continue;
+            }
+            let block_like = self.is_ident("if") || self.is_ident("while") || self.is_ident("for") || self.is_punct("{");
+            let expr = self.parse_expr() ?;
+            if self.is_punct(";") {
+                self.next();
+                stmts.push(Box::new(ShStmt::ShSemi(expr)));
+            }
+            else if block_like || self.is_punct("}") {
+                stmts.push(Box::new(ShStmt::ShExpr(expr)));
+            }
+            else {
+                return self.error("expected ;")
+            }
+        }
+        self.next();
+        Ok(ShBlock {stmts: stmts})
+    }
+    
+    fn parse_let(&mut self) -> Result<ShLet, ShTypeError> {
+        self.next();
+        let (name, loc) = self.expect_ident("variable name") ?;
+        let ty = if self.is_punct(":") {
+            self.next();
+            self.expect_ident("type") ?.0
+        }
+        else {
+            "".to_string()
+        };
+        if !self.is_punct("=") {
+            return self.error("let pattern misses initializer")
+        }
+        self.next();
+        let init = self.parse_expr() ?;
+        self.expect_punct(";") ?;
+        Ok(ShLet {
+            name: name,
+            ty: ty,
+            init: Box::new(init),
+            loc: loc
+        })
+    }
+    
+    fn parse_expr(&mut self) -> Result<ShExpr, ShTypeError> {
+        let left = self.parse_binary(1) ?;
+        let loc = self.loc();
+        if self.is_punct("=") {
+            self.next();
+            let right = self.parse_expr() ?;
+            return Ok(ShExpr::ShAssign(ShAssign {left: Box::new(left), right: Box::new(right), loc: loc}))
+        }
+        if let ShTok::Punct(punct) = self.peek() {
+            if let Some(op) = get_assignop(punct) {
+                self.next();
+                let right = self.parse_expr() ?;
+                return Ok(ShExpr::ShAssignOp(ShAssignOp {left: Box::new(left), op: op, right: Box::new(right), loc: loc}))
+            }
+        }
+        Ok(left)
+    }
+    
+    // precedence climbing, higher binds tighter
+    fn parse_binary(&mut self, min_prec: usize) -> Result<ShExpr, ShTypeError> {
+        let mut left = self.parse_unary() ?;
+        loop {
+            let (op, prec) = if let ShTok::Punct(punct) = self.peek() {
+                if let Some(binop) = get_binop(punct) {binop}else {break}
+            }
+            else {
+                break
+            };
+            if prec < min_prec {
+                break
+            }
+            let loc = self.next().loc;
+            let right = self.parse_binary(prec + 1) ?;
+            left = ShExpr::ShBinary(ShBinary {left: Box::new(left), op: op, right: Box::new(right), loc: loc});
+        }
+        Ok(left)
+    }
+    
+    fn parse_unary(&mut self) -> Result<ShExpr, ShTypeError> {
+        let op = if self.is_punct("-") {ShUnaryOp::Neg}
+        else if self.is_punct("!") {ShUnaryOp::Not}
+        else {
+            return self.parse_postfix()
+        };
+        let loc = self.next().loc;
+        let expr = self.parse_unary() ?;
+        Ok(ShExpr::ShUnary(ShUnary {op: op, expr: Box::new(expr), loc: loc}))
+    }
+    
+    fn parse_postfix(&mut self) -> Result<ShExpr, ShTypeError> {
+        let mut base = self.parse_primary() ?;
+        loop {
+            if self.is_punct(".") {
+                self.next();
+                let (member, loc) = self.expect_ident("field name") ?;
+                if self.is_punct("(") {
+                    return Err(parse_error(loc, &self.fn_name, format!("method call {} is not supported, call it as a function", member)))
+                }
+                base = ShExpr::ShField(ShField {base: Box::new(base), member: member, loc: loc});
+            }
+            else if self.is_punct("[") {
+                let loc = self.next().loc;
+                let index = self.parse_expr() ?;
+                self.expect_punct("]") ?;
+                base = ShExpr::ShIndex(ShIndex {base: Box::new(base), index: Box::new(index), loc: loc});
+            }
+            else {
+                return Ok(base)
+            }
+        }
+    }
+    
+    fn parse_primary(&mut self) -> Result<ShExpr, ShTypeError> {
+        match self.peek().clone() {
+            ShTok::Int(value) => {
+                self.next();
+                Ok(ShExpr::ShLit(ShLit::Int(value)))
+            },
+            ShTok::Float(value) => {
+                self.next();
+                Ok(ShExpr::ShLit(ShLit::Float(value)))
+            },
+            ShTok::Str(value) => {
+                self.next();
+                Ok(ShExpr::ShLit(ShLit::Str(value)))
+            },
+            ShTok::Punct("(") => {
+                self.next();
+                let expr = self.parse_expr() ?;
+                self.expect_punct(")") ?;
+                Ok(ShExpr::ShParen(ShParen {expr: Box::new(expr)}))
+            },
+            ShTok::Punct("{") => {
+                Ok(ShExpr::ShBlock(self.parse_block() ?))
+            },
+            ShTok::Ident(name) => {
+                match name.as_ref() {
+                    "true" | "false" => {
+                        self.next();
+                        Ok(ShExpr::ShLit(ShLit::Bool(name == "true")))
+                    },
+                    "if" => Ok(ShExpr::ShIf(self.parse_if() ?)),
+                    "while" => {
+                        self.next();
+                        let cond = self.parse_binary(1) ?;
+                        let body = self.parse_block() ?;
+                        Ok(ShExpr::ShWhile(ShWhile {cond: Box::new(cond), body: body}))
+                    },
+                    "for" => {
+                        self.next();
+                        let (iter, _) = self.expect_ident("loop variable") ?;
+                        if !self.is_ident("in") {
+                            return self.error("expected in")
+                        }
+                        self.next();
+                        let from = self.parse_binary(1) ?;
+                        if !self.is_punct("..") {
+                            return self.error("Must provide range expression")
+                        }
+                        self.next();
+                        let to = self.parse_binary(1) ?;
+                        let body = self.parse_block() ?;
+                        Ok(ShExpr::ShForLoop(ShForLoop {iter: iter, from: Box::new(from), to: Box::new(to), body: body}))
+                    },
+                    "return" => {
+                        let loc = self.next().loc;
+                        if self.is_punct(";") || self.is_punct("}") {
+                            return Ok(ShExpr::ShReturn(ShReturn {expr: None, loc: loc}))
+                        }
+                        let expr = self.parse_expr() ?;
+                        Ok(ShExpr::ShReturn(ShReturn {expr: Some(Box::new(expr)), loc: loc}))
+                    },
+                    "break" => {
+                        self.next();
+                        Ok(ShExpr::ShBreak(ShBreak {}))
+                    },
+                    "continue" => {
+                        self.next();
+                        Ok(ShExpr::ShContinue(ShContinue {}))
+                    },
+                    "let" | "fn" | "const" | "struct" | "use" | "else" | "in" => {
+                        self.error("expected expression")
+                    },
+                    _ => {
+                        let loc = self.next().loc;
+                        // a name from a shader module, like df::circle
+                        let mut name = name;
+                        if self.is_punct("::") {
+                            self.next();
+                            let (item, _) = self.expect_ident("name after ::") ?;
+                            name = format!("{}::{}", name, item);
+                        }
+                        if self.is_punct("(") {
+                            self.next();
+                            let mut args = Vec::new();
+                            while !self.is_punct(")") {
+                                args.push(Box::new(self.parse_expr() ?));
+                                if !self.is_punct(",") {
+                                    break;
+                                }
+                                self.next();
+                            }
+                            self.expect_punct(")") ?;
+                            return Ok(ShExpr::ShCall(ShCall {call: name, args: args, loc: loc}))
+                        }
+                        Ok(ShExpr::ShId(ShId {name: name, loc: loc}))
+                    }
+                }
+            },
+            _ => self.error("expected expression")
+        }
+    }
+    
+    fn parse_if(&mut self) -> Result<ShIf, ShTypeError> {
+        self.next();
+        let cond = self.parse_binary(1) ?;
+        let then_branch = self.parse_block() ?;
+        let else_branch = if self.is_ident("else") {
+            self.next();
+            if self.is_ident("if") {
+                Some(Box::new(ShExpr::ShIf(self.parse_if() ?)))
+            }
+            else {
+                Some(Box::new(ShExpr::ShBlock(self.parse_block() ?)))
+            }
+        }
+        else {
+            None
+        };
+        Ok(ShIf {cond: Box::new(cond), then_branch: then_branch, else_branch: else_branch})
+    }
+}
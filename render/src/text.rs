@@ -55,6 +55,8 @@ impl Text{
         ];
 
         sg.compose(shader_ast!({
+            use df;
+            
             let geom:vec2<Geometry>;
             let texturez:texture2d<Texture>;
             let tex_size:vec2<Uniform>;
@@ -79,18 +81,18 @@ impl Text{
 
             fn pixel()->vec4{
                 if marker>0.5{
-                    df_viewport(clipped);
+                    df::viewport(clipped);
                     let center = (rect.xy+rect.zw)*0.5;
-                    df_circle(center.x, center.y, 1.);
-                    return df_fill(color); 
+                    df::circle(center.x, center.y, 1.);
+                    return df::fill(color); 
                 }
                 else{
                     let s = sample2d(texturez, tex_coord.xy);
                     let sig_dist =  max(min(s.r, s.g), min(max(s.r, s.g), s.b)) - 0.5;
-                    //let scale = pow(df_antialias(clipped) * 0.002,0.5);
-                    df_viewport(tex_coord * tex_size * (0.1 - dpi_dilate*0.03));
-                    df_shape = (-sig_dist - (0.5 / df_aa)) - dpi_dilate*0.1;
-                    return df_fill(color*brightness); 
+                    //let scale = pow(df::antialias(clipped) * 0.002,0.5);
+                    df::viewport(tex_coord * tex_size * (0.1 - dpi_dilate*0.03));
+                    df::shape = (-sig_dist - (0.5 / df::aa)) - dpi_dilate*0.1;
+                    return df::fill(color*brightness); 
                 }
             }
             
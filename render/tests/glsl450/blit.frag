@@ -0,0 +1,26 @@
+#version 450
+
+layout(set=0, binding=0) uniform _uni_cxBlock {
+  vec4 slots[5];
+} _uni_cx;
+layout(set=1, binding=0) uniform _uni_vwBlock {
+  vec4 slots[2];
+} _uni_vw;
+layout(set=2, binding=0) uniform _uni_drBlock {
+  vec4 slots[1];
+} _uni_dr;
+layout(set=3, binding=0) uniform sampler _sampler;
+layout(set=3, binding=1) uniform texture2D _tex_texturez;
+
+
+layout(location=0) in vec2 _vary_geom;
+layout(location=0) out vec4 _frag_color;
+
+//Pixel shader
+vec4 _pixel(){
+  return vec4(texture(sampler2D(_tex_texturez, _sampler), _vary_geom.xy).rgb, 1.0);
+}
+
+void main() {
+  _frag_color = _pixel();
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(set=0, binding=0) uniform _uni_cxBlock {
+  vec4 slots[5];
+} _uni_cx;
+layout(set=1, binding=0) uniform _uni_vwBlock {
+  vec4 slots[2];
+} _uni_vw;
+layout(set=2, binding=0) uniform _uni_drBlock {
+  vec4 slots[1];
+} _uni_dr;
+
+
+layout(location=0) in vec4 _vary_color;
+layout(location=0) out vec4 _frag_color;
+
+//Pixel shader
+vec4 _pixel(){
+  return vec4(_vary_color.rgb*_vary_color.a, _vary_color.a);
+}
+
+void main() {
+  _frag_color = _pixel();
+}
@@ -0,0 +1,33 @@
+#version 450
+
+layout(set=0, binding=0) uniform _uni_cxBlock {
+  vec4 slots[5];
+} _uni_cx;
+layout(set=1, binding=0) uniform _uni_vwBlock {
+  vec4 slots[2];
+} _uni_vw;
+layout(set=2, binding=0) uniform _uni_drBlock {
+  vec4 slots[1];
+} _uni_dr;
+
+
+layout(location=0) in vec2 _geom_geom;
+layout(location=1) in float _inst_x;
+layout(location=2) in float _inst_y;
+layout(location=3) in float _inst_w;
+layout(location=4) in float _inst_h;
+layout(location=5) in vec4 _inst_color;
+layout(location=0) out vec4 _vary_color;
+
+//Vertex shader
+vec4 _vertex(){
+  vec2 shift = -_uni_vw.slots[0].xy*_uni_dr.slots[0].x;
+  vec2 clipped = clamp(_geom_geom*vec2(_inst_w, _inst_h)+vec2(_inst_x, _inst_y)+shift, _uni_vw.slots[1].xy, _uni_vw.slots[1].zw);
+  return vec4(clipped.x, clipped.y, 0.0, 1.0)*mat4(_uni_cx.slots[0], _uni_cx.slots[1], _uni_cx.slots[2], _uni_cx.slots[3]);
+}
+
+void main() {
+  gl_Position = _vertex();
+  gl_Position.y = -gl_Position.y;
+  _vary_color = _inst_color;
+}
@@ -0,0 +1,98 @@
+#version 450
+
+layout(set=0, binding=0) uniform _uni_cxBlock {
+  vec4 slots[5];
+} _uni_cx;
+layout(set=1, binding=0) uniform _uni_vwBlock {
+  vec4 slots[2];
+} _uni_vw;
+layout(set=2, binding=0) uniform _uni_drBlock {
+  vec4 slots[1];
+} _uni_dr;
+layout(set=3, binding=0) uniform sampler _sampler;
+layout(set=3, binding=1) uniform texture2D _tex_texturez;
+
+
+struct _Loc {
+  vec2 df_pos;
+  vec4 df_result;
+  float df_shape;
+  float df_clip;
+  float df_has_clip;
+  float df_old_shape;
+  float df_blur;
+  float df_aa;
+  float df_scale;
+  float df_field;
+};
+_Loc _loc;
+
+layout(location=0) in vec2 _vary_tex_coord;
+layout(location=1) in vec2 _vary_clipped;
+layout(location=2) in vec4 _vary_rect;
+layout(location=3) in float _vary_marker;
+layout(location=4) in vec4 _vary_color;
+layout(location=0) out vec4 _frag_color;
+
+//Pixel shader
+float _df_calc_blur(float w){
+  float wa = clamp(-w*_loc.df_aa, 0.0, 1.0);
+  float wb = 1.0;
+  if(_loc.df_blur>0.001){
+    wb = clamp(-w/_loc.df_blur, 0.0, 1.0);
+};
+  return wa*wb;
+}
+vec4 _df_fill_keep(vec4 color){
+  float f = _df_calc_blur(_loc.df_shape);
+  vec4 source = vec4(color.rgb*color.a, color.a);
+  _loc.df_result = source*f+_loc.df_result*(1.0-source.a*f);
+  if(_loc.df_has_clip>0.5){
+    float f2 = 1.0-_df_calc_blur(-_loc.df_clip);
+    _loc.df_result = source*f2+_loc.df_result*(1.0-source.a*f2);
+};
+  return _loc.df_result;
+}
+vec4 _df_fill(vec4 color){
+  _df_fill_keep(color);
+  _loc.df_old_shape = _loc.df_shape = 100000000000000000000.0;
+  _loc.df_clip = (-100000000000000000000.0);
+  _loc.df_has_clip = 0.0;
+  return _loc.df_result;
+}
+vec2 _df_viewport(vec2 pos){
+  _loc.df_pos = pos;
+  _loc.df_result = vec4(0.0, 0.0, 0.0, 0.0);
+  _loc.df_old_shape = _loc.df_shape = 100000000000000000000.0;
+  _loc.df_clip = (-100000000000000000000.0);
+  _loc.df_blur = 0.00001;
+  _loc.df_aa = (1.0/length(vec2(length(dFdx(pos)), length(dFdy(pos)))));
+  _loc.df_scale = 1.0;
+  _loc.df_field = 0.0;
+  _loc.df_clip = 0.0;
+  _loc.df_has_clip = 0.0;
+  return _loc.df_pos;
+}
+void _df_circle(float x, float y, float r){
+  vec2 c = _loc.df_pos-vec2(x, y);
+  _loc.df_field = (length(c.xy)-r)/_loc.df_scale;
+  _loc.df_shape = min(_loc.df_shape, _loc.df_field);
+}
+vec4 _pixel(){
+  if(_vary_marker>0.5){
+    _df_viewport(_vary_clipped);
+    vec2 center = (_vary_rect.xy+_vary_rect.zw)*0.5;
+    _df_circle(center.x, center.y, 1.0);
+    return _df_fill(_vary_color);
+}else {
+    vec4 s = texture(sampler2D(_tex_texturez, _sampler), _vary_tex_coord.xy);
+    float sig_dist = max(min(s.r, s.g), min(max(s.r, s.g), s.b))-0.5;
+    _df_viewport(_vary_tex_coord*_uni_dr.slots[0].xy*(0.1-_uni_cx.slots[4].y*0.03));
+    _loc.df_shape = (-sig_dist-(0.5/_loc.df_aa))-_uni_cx.slots[4].y*0.1;
+    return _df_fill(_vary_color*_uni_dr.slots[0].z);
+};
+}
+
+void main() {
+  _frag_color = _pixel();
+}
@@ -0,0 +1,61 @@
+#version 450
+
+layout(set=0, binding=0) uniform _uni_cxBlock {
+  vec4 slots[5];
+} _uni_cx;
+layout(set=1, binding=0) uniform _uni_vwBlock {
+  vec4 slots[2];
+} _uni_vw;
+layout(set=2, binding=0) uniform _uni_drBlock {
+  vec4 slots[1];
+} _uni_dr;
+layout(set=3, binding=0) uniform sampler _sampler;
+layout(set=3, binding=1) uniform texture2D _tex_texturez;
+
+
+struct _Loc {
+  vec2 df_pos;
+  vec4 df_result;
+  float df_shape;
+  float df_clip;
+  float df_has_clip;
+  float df_old_shape;
+  float df_blur;
+  float df_aa;
+  float df_scale;
+  float df_field;
+};
+_Loc _loc;
+
+layout(location=0) in vec2 _geom_geom;
+layout(location=1) in vec4 _inst_font_geom;
+layout(location=2) in vec4 _inst_font_tc;
+layout(location=3) in vec4 _inst_color;
+layout(location=4) in float _inst_x;
+layout(location=5) in float _inst_y;
+layout(location=6) in float _inst_font_size;
+layout(location=7) in float _inst_marker;
+layout(location=0) out vec2 _vary_tex_coord;
+layout(location=1) out vec2 _vary_clipped;
+layout(location=2) out vec4 _vary_rect;
+layout(location=3) out float _vary_marker;
+layout(location=4) out vec4 _vary_color;
+
+//Vertex shader
+vec4 _vertex(){
+  vec2 shift = -_uni_vw.slots[0].xy;
+  vec2 min_pos = vec2(_inst_x+_inst_font_size*_inst_font_geom.x, _inst_y-_inst_font_size*_inst_font_geom.y+_inst_font_size);
+  vec2 max_pos = vec2(_inst_x+_inst_font_size*_inst_font_geom.z, _inst_y-_inst_font_size*_inst_font_geom.w+_inst_font_size);
+  _vary_clipped = clamp(mix(min_pos, max_pos, _geom_geom)+shift, _uni_vw.slots[1].xy, _uni_vw.slots[1].zw);
+  vec2 normalized = (_vary_clipped-min_pos-shift)/(max_pos-min_pos);
+  _vary_rect = vec4(min_pos.x, min_pos.y, max_pos.x, max_pos.y)+shift.xyxy;
+  _vary_tex_coord = mix(_inst_font_tc.xy, _inst_font_tc.zw, normalized.xy);
+  return vec4(_vary_clipped, 0.0, 1.0)*mat4(_uni_cx.slots[0], _uni_cx.slots[1], _uni_cx.slots[2], _uni_cx.slots[3]);
+}
+
+void main() {
+  gl_Position = _vertex();
+  gl_Position.y = -gl_Position.y;
+  _vary_marker = _inst_marker;
+  _vary_color = _inst_color;
+}
@@ -0,0 +1,64 @@
+diagnostic(off, derivative_uniformity);
+
+struct _Geom {
+  @location(0) geom: vec2<f32>,
+};
+
+struct _Inst {
+  @location(1) x: f32,
+  @location(2) y: f32,
+  @location(3) w: f32,
+  @location(4) h: f32,
+  @location(5) color: vec4<f32>,
+};
+
+struct _uni_cxBlock {
+  slots: array<vec4<f32>, 5>
+};
+@group(0) @binding(0) var<uniform> _uni_cx: _uni_cxBlock;
+
+struct _uni_vwBlock {
+  slots: array<vec4<f32>, 2>
+};
+@group(1) @binding(0) var<uniform> _uni_vw: _uni_vwBlock;
+
+struct _uni_drBlock {
+  slots: array<vec4<f32>, 1>
+};
+@group(2) @binding(0) var<uniform> _uni_dr: _uni_drBlock;
+
+
+struct _Vary {
+  @builtin(position) _position: vec4<f32>,
+  @location(0) color: vec4<f32>,
+};
+
+var<private> _geom: _Geom;
+var<private> _inst: _Inst;
+var<private> _vary: _Vary;
+
+//Vertex shader
+fn _vertex() -> vec4<f32> {
+  var shift: vec2<f32> = -_uni_vw.slots[0].xy*_uni_dr.slots[0].x;
+  var clipped: vec2<f32> = clamp(_geom.geom*vec2<f32>(_inst.w, _inst.h)+vec2<f32>(_inst.x, _inst.y)+shift, _uni_vw.slots[1].xy, _uni_vw.slots[1].zw);
+  return vec4<f32>(clipped.x, clipped.y, 0.0, 1.0) * mat4x4<f32>(_uni_cx.slots[0], _uni_cx.slots[1], _uni_cx.slots[2], _uni_cx.slots[3]);
+}
+//Pixel shader
+fn _pixel() -> vec4<f32> {
+  return vec4<f32>(_vary.color.rgb*_vary.color.a, _vary.color.a);
+}
+
+@vertex
+fn _vertex_shader(geom_in: _Geom, inst_in: _Inst) -> _Vary {
+  _geom = geom_in;
+  _inst = inst_in;
+  _vary._position = _vertex();
+  _vary.color = _inst.color;
+  return _vary;
+}
+
+@fragment
+fn _fragment_shader(vary_in: _Vary) -> @location(0) vec4<f32> {
+  _vary = vary_in;
+  return _pixel();
+}
@@ -0,0 +1,148 @@
+diagnostic(off, derivative_uniformity);
+
+struct _Geom {
+  @location(0) geom: vec2<f32>,
+};
+
+struct _Inst {
+  @location(1) font_geom: vec4<f32>,
+  @location(2) font_tc: vec4<f32>,
+  @location(3) color: vec4<f32>,
+  @location(4) x: f32,
+  @location(5) y: f32,
+  @location(6) font_size: f32,
+  @location(7) marker: f32,
+};
+
+struct _uni_cxBlock {
+  slots: array<vec4<f32>, 5>
+};
+@group(0) @binding(0) var<uniform> _uni_cx: _uni_cxBlock;
+
+struct _uni_vwBlock {
+  slots: array<vec4<f32>, 2>
+};
+@group(1) @binding(0) var<uniform> _uni_vw: _uni_vwBlock;
+
+struct _uni_drBlock {
+  slots: array<vec4<f32>, 1>
+};
+@group(2) @binding(0) var<uniform> _uni_dr: _uni_drBlock;
+
+@group(3) @binding(0) var _sampler: sampler;
+@group(3) @binding(1) var _tex_texturez: texture_2d<f32>;
+
+struct _Vary {
+  @builtin(position) _position: vec4<f32>,
+  @location(0) tex_coord: vec2<f32>,
+  @location(1) clipped: vec2<f32>,
+  @location(2) rect: vec4<f32>,
+  @location(3) marker: f32,
+  @location(4) color: vec4<f32>,
+};
+
+struct _Loc {
+  df_pos: vec2<f32>,
+  df_result: vec4<f32>,
+  df_shape: f32,
+  df_clip: f32,
+  df_has_clip: f32,
+  df_old_shape: f32,
+  df_blur: f32,
+  df_aa: f32,
+  df_scale: f32,
+  df_field: f32,
+};
+
+var<private> _loc: _Loc;
+var<private> _geom: _Geom;
+var<private> _inst: _Inst;
+var<private> _vary: _Vary;
+
+//Vertex shader
+fn _vertex() -> vec4<f32> {
+  var shift: vec2<f32> = -_uni_vw.slots[0].xy;
+  var min_pos: vec2<f32> = vec2<f32>(_inst.x+_inst.font_size*_inst.font_geom.x, _inst.y-_inst.font_size*_inst.font_geom.y+_inst.font_size);
+  var max_pos: vec2<f32> = vec2<f32>(_inst.x+_inst.font_size*_inst.font_geom.z, _inst.y-_inst.font_size*_inst.font_geom.w+_inst.font_size);
+  _vary.clipped = clamp(mix(min_pos, max_pos, _geom.geom)+shift, _uni_vw.slots[1].xy, _uni_vw.slots[1].zw);
+  var normalized: vec2<f32> = (_vary.clipped-min_pos-shift)/(max_pos-min_pos);
+  _vary.rect = vec4<f32>(min_pos.x, min_pos.y, max_pos.x, max_pos.y)+shift.xyxy;
+  _vary.tex_coord = mix(_inst.font_tc.xy, _inst.font_tc.zw, normalized.xy);
+  return vec4<f32>(_vary.clipped, 0.0, 1.0) * mat4x4<f32>(_uni_cx.slots[0], _uni_cx.slots[1], _uni_cx.slots[2], _uni_cx.slots[3]);
+}
+//Pixel shader
+fn _df_calc_blur(w: f32) -> f32 {
+  var wa: f32 = clamp(-w*_loc.df_aa, 0.0, 1.0);
+  var wb: f32 = 1.0;
+  if(_loc.df_blur>0.001){
+    wb = clamp(-w/_loc.df_blur, 0.0, 1.0);
+};
+  return wa*wb;
+}
+fn _df_fill_keep(color: vec4<f32>) -> vec4<f32> {
+  var f: f32 = _df_calc_blur(_loc.df_shape);
+  var source: vec4<f32> = vec4<f32>(color.rgb*color.a, color.a);
+  _loc.df_result = source*f+_loc.df_result*(1.0-source.a*f);
+  if(_loc.df_has_clip>0.5){
+    var f2: f32 = 1.0-_df_calc_blur(-_loc.df_clip);
+    _loc.df_result = source*f2+_loc.df_result*(1.0-source.a*f2);
+};
+  return _loc.df_result;
+}
+fn _df_fill(color: vec4<f32>) -> vec4<f32> {
+  _df_fill_keep(color);
+  _loc.df_shape = 100000000000000000000.0;
+  _loc.df_old_shape = _loc.df_shape;
+  _loc.df_clip = (-100000000000000000000.0);
+  _loc.df_has_clip = 0.0;
+  return _loc.df_result;
+}
+fn _df_viewport(pos: vec2<f32>) -> vec2<f32> {
+  _loc.df_pos = pos;
+  _loc.df_result = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+  _loc.df_shape = 100000000000000000000.0;
+  _loc.df_old_shape = _loc.df_shape;
+  _loc.df_clip = (-100000000000000000000.0);
+  _loc.df_blur = 0.00001;
+  _loc.df_aa = (1.0/length(vec2<f32>(length(dpdx(pos)), length(dpdy(pos)))));
+  _loc.df_scale = 1.0;
+  _loc.df_field = 0.0;
+  _loc.df_clip = 0.0;
+  _loc.df_has_clip = 0.0;
+  return _loc.df_pos;
+}
+fn _df_circle(x: f32, y: f32, r: f32) {
+  var c: vec2<f32> = _loc.df_pos-vec2<f32>(x, y);
+  _loc.df_field = (length(c.xy)-r)/_loc.df_scale;
+  _loc.df_shape = min(_loc.df_shape, _loc.df_field);
+}
+fn _pixel() -> vec4<f32> {
+  if(_vary.marker>0.5){
+    _df_viewport(_vary.clipped);
+    var center: vec2<f32> = (_vary.rect.xy+_vary.rect.zw)*0.5;
+    _df_circle(center.x, center.y, 1.0);
+    return _df_fill(_vary.color);
+}else {
+    var s: vec4<f32> = textureSample(_tex_texturez, _sampler, _vary.tex_coord.xy);
+    var sig_dist: f32 = max(min(s.r, s.g), min(max(s.r, s.g), s.b))-0.5;
+    _df_viewport(_vary.tex_coord*_uni_dr.slots[0].xy*(0.1-_uni_cx.slots[4].y*0.03));
+    _loc.df_shape = (-sig_dist-(0.5/_loc.df_aa))-_uni_cx.slots[4].y*0.1;
+    return _df_fill(_vary.color*_uni_dr.slots[0].z);
+};
+}
+
+@vertex
+fn _vertex_shader(geom_in: _Geom, inst_in: _Inst) -> _Vary {
+  _geom = geom_in;
+  _inst = inst_in;
+  _vary._position = _vertex();
+  _vary.marker = _inst.marker;
+  _vary.color = _inst.color;
+  return _vary;
+}
+
+@fragment
+fn _fragment_shader(vary_in: _Vary) -> @location(0) vec4<f32> {
+  _vary = vary_in;
+  return _pixel();
+}
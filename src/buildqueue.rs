@@ -0,0 +1,550 @@
+use render::*;
+
+use std::io::Read;
+use std::sync::mpsc;
+use std::process::Child;
+use std::time::Instant;
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BuildJobKind {
+    Check,
+    Build,
+    DebugBuild,
+    Run,
+    Test
+}
+
+impl BuildJobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildJobKind::Check => "Check",
+            BuildJobKind::Build => "Build",
+            BuildJobKind::DebugBuild => "Debug build",
+            BuildJobKind::Run => "Run",
+            BuildJobKind::Test => "Test"
+        }
+    }
+    
+    pub fn verb(&self) -> &'static str {
+        match self {
+            BuildJobKind::Check => "Checking",
+            BuildJobKind::Build => "Building",
+            BuildJobKind::DebugBuild => "Building for the debugger",
+            BuildJobKind::Run => "Starting",
+            BuildJobKind::Test => "Testing"
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuildJobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Canceled
+}
+
+// one cargo invocation, jobs run one at a time in the order they were queued
+#[derive(Clone)]
+pub struct BuildJob {
+    pub id: u64,
+    pub kind: BuildJobKind,
+    pub args: Vec<String>,
+    pub state: BuildJobState,
+    pub needs: Option<BuildJobKind>,
+    pub units_done: usize,
+    pub units_expected: usize,
+    pub current_unit: String,
+    pub error: String,
+    pub started: Option<Instant>,
+    pub finished: Option<Instant>
+}
+
+impl BuildJob {
+    pub fn is_done(&self) -> bool {
+        match self.state {
+            BuildJobState::Queued | BuildJobState::Running => false,
+            _ => true
+        }
+    }
+    
+    pub fn elapsed(&self) -> f64 {
+        match (self.started, self.finished) {
+            (Some(started), Some(finished)) => finished.duration_since(started).as_secs_f64(),
+            (Some(started), None) => started.elapsed().as_secs_f64(),
+            _ => 0.
+        }
+    }
+    
+    // the unit count of the last successful run of this kind is our estimate
+    pub fn progress(&self) -> Option<f32> {
+        if self.units_expected == 0 {
+            return None
+        }
+        Some((self.units_done as f32 / self.units_expected as f32).min(1.))
+    }
+    
+    pub fn describe(&self) -> String {
+        match self.state {
+            BuildJobState::Queued => format!("{} queued", self.kind.name()),
+            BuildJobState::Running => {
+                let mut out = self.kind.verb().to_string();
+                if self.units_expected > 0 {
+                    out.push_str(&format!(" {}/{}", self.units_done.min(self.units_expected), self.units_expected));
+                }
+                else if self.units_done > 0 {
+                    out.push_str(&format!(" {}", self.units_done));
+                }
+                if self.current_unit.len() > 0 {
+                    out.push_str(&format!(" {}", self.current_unit));
+                }
+                format!("{} ({})", out, format_elapsed(self.elapsed()))
+            },
+            BuildJobState::Succeeded => format!("{} done in {}", self.kind.name(), format_elapsed(self.elapsed())),
+            BuildJobState::Failed => if self.error.len() > 0 {
+                format!("{} failed: {}", self.kind.name(), self.error)
+            }
+            else {
+                format!("{} failed after {}", self.kind.name(), format_elapsed(self.elapsed()))
+            },
+            BuildJobState::Canceled => format!("{} canceled", self.kind.name())
+        }
+    }
+}
+
+pub fn format_elapsed(secs: f64) -> String {
+    if secs < 60. {
+        format!("{:.1}s", secs)
+    }
+    else {
+        format!("{}:{:02}", secs as u64 / 60, secs as u64 % 60)
+    }
+}
+
+pub enum BuildQueueEvent {
+    Started {kind: BuildJobKind},
+    Output {kind: BuildJobKind, lines: Vec<String>},
+    Finished {kind: BuildJobKind, state: BuildJobState},
+    StartRun {child: Child, title: String}
+}
+
+pub struct BuildQueue {
+    pub workdir: String,
+    pub max_history: usize,
+    pub jobs: Vec<BuildJob>,
+    pub _signal: Signal,
+    pub _timer: Timer,
+    pub _child: Option<Child>,
+    pub _running: Option<u64>,
+    pub _rx: Option<mpsc::Receiver<(u64, Vec<u8>)>>,
+    pub _line: Vec<u8>,
+    pub _next_id: u64,
+    pub _expected_units: HashMap<BuildJobKind, usize>
+}
+
+const SIGNAL_BUILD_QUEUE: usize = 1;
+
+impl BuildQueue {
+    pub fn new(cx: &mut Cx) -> Self {
+        Self {
+            workdir: "./".to_string(),
+            max_history: 8,
+            jobs: Vec::new(),
+            _signal: cx.new_signal(),
+            _timer: Timer::empty(),
+            _child: None,
+            _running: None,
+            _rx: None,
+            _line: Vec::new(),
+            _next_id: 1,
+            _expected_units: HashMap::new()
+        }
+    }
+    
+    pub fn enqueue(&mut self, cx: &mut Cx, kind: BuildJobKind, args: Vec<String>, needs: Option<BuildJobKind>) -> u64 {
+        let id = self._next_id;
+        self._next_id += 1;
+        self.jobs.push(BuildJob {
+            id: id,
+            kind: kind,
+            args: args,
+            state: BuildJobState::Queued,
+            needs: needs,
+            units_done: 0,
+            units_expected: *self._expected_units.get(&kind).unwrap_or(&0),
+            current_unit: String::new(),
+            error: String::new(),
+            started: None,
+            finished: None
+        });
+        // the job starts from the signal handler so its events come out of handle_build_queue
+        cx.send_signal_before_draw(self._signal, SIGNAL_BUILD_QUEUE);
+        id
+    }
+    
+    // cancels queued and running jobs of a kind, returns how many there were
+    pub fn cancel(&mut self, cx: &mut Cx, kind: BuildJobKind) -> usize {
+        let mut count = 0;
+        for job in &mut self.jobs {
+            if job.kind != kind || job.is_done() {
+                continue;
+            }
+            if job.state == BuildJobState::Running {
+                if let Some(child) = &mut self._child {
+                    let _ = child.kill();
+                }
+            }
+            job.state = BuildJobState::Canceled;
+            job.finished = Some(Instant::now());
+            count += 1;
+        }
+        if count > 0 {
+            cx.send_signal_before_draw(self._signal, SIGNAL_BUILD_QUEUE);
+        }
+        count
+    }
+    
+    pub fn cancel_all(&mut self, cx: &mut Cx) -> usize {
+        let mut count = 0;
+        for kind in &[BuildJobKind::Check, BuildJobKind::Build, BuildJobKind::DebugBuild, BuildJobKind::Run, BuildJobKind::Test] {
+            count += self.cancel(cx, *kind);
+        }
+        count
+    }
+    
+    pub fn is_busy(&self) -> bool {
+        self.jobs.iter().any( | job | !job.is_done())
+    }
+    
+    pub fn is_pending(&self, kind: BuildJobKind) -> bool {
+        self.jobs.iter().any( | job | job.kind == kind && !job.is_done())
+    }
+    
+    pub fn running_job(&self) -> Option<&BuildJob> {
+        self.jobs.iter().find( | job | job.state == BuildJobState::Running)
+    }
+    
+    pub fn last_job(&self, kind: BuildJobKind) -> Option<&BuildJob> {
+        self.jobs.iter().rev().find( | job | job.kind == kind)
+    }
+    
+    pub fn is_build_queue_event(&self, event: &Event) -> bool {
+        match event {
+            Event::Signal(se) => self._signal.is_signal(se),
+            Event::Timer(te) => te.timer_id == self._timer.timer_id && !self._timer.is_empty(),
+            _ => false
+        }
+    }
+    
+    pub fn handle_build_queue(&mut self, cx: &mut Cx, event: &mut Event) -> Vec<BuildQueueEvent> {
+        let mut events = Vec::new();
+        match event {
+            Event::Signal(se) => if self._signal.is_signal(se) {
+                let mut datas = Vec::new();
+                if let Some(rx) = &self._rx {
+                    while let Ok(data) = rx.try_recv() {
+                        datas.push(data);
+                    }
+                }
+                for (id, data) in datas {
+                    self.process_output(id, data, &mut events);
+                }
+                self.start_next(cx, &mut events);
+            },
+            _ => ()
+        }
+        events
+    }
+    
+    fn process_output(&mut self, id: u64, data: Vec<u8>, events: &mut Vec<BuildQueueEvent>) {
+        if self._running != Some(id) {
+            return
+        }
+        let job_index = if let Some(index) = self.jobs.iter().position( | job | job.id == id) {index}else {return};
+        let kind = self.jobs[job_index].kind;
+        if data.len() == 0 { // the process closed its stdout
+            let success = if let Some(child) = &mut self._child {
+                child.wait().map_or(false, | status | status.success())
+            }
+            else {
+                false
+            };
+            self._child = None;
+            self._running = None;
+            self._line.truncate(0);
+            let job = &mut self.jobs[job_index];
+            if job.state == BuildJobState::Running {
+                job.state = if success {BuildJobState::Succeeded}else {BuildJobState::Failed};
+                job.finished = Some(Instant::now());
+                if success {
+                    self._expected_units.insert(kind, job.units_done);
+                }
+            }
+            events.push(BuildQueueEvent::Finished {kind: kind, state: job.state});
+            return
+        }
+        if self.jobs[job_index].state != BuildJobState::Running {
+            return
+        }
+        let mut lines = Vec::new();
+        for ch in data {
+            if ch != '\n' as u8 {
+                self._line.push(ch);
+                continue;
+            }
+            // a line is only decoded when complete, so split utf8 sequences survive
+            let line = String::from_utf8_lossy(&self._line).to_string();
+            self._line.truncate(0);
+            // every compiled crate reports an artifact, that is our unit of progress
+            if line.contains("\"reason\":\"compiler-artifact\"") {
+                let job = &mut self.jobs[job_index];
+                job.units_done += 1;
+                if let Ok(value) = serde_json::from_str::<Value>(&line) {
+                    if let Some(name) = value["target"]["name"].as_str() {
+                        job.current_unit = name.to_string();
+                    }
+                }
+            }
+            lines.push(line);
+        }
+        if lines.len() > 0 {
+            events.push(BuildQueueEvent::Output {kind: kind, lines: lines});
+        }
+    }
+    
+    fn start_next(&mut self, cx: &mut Cx, events: &mut Vec<BuildQueueEvent>) {
+        while self._child.is_none() {
+            let index = if let Some(index) = self.jobs.iter().position( | job | job.state == BuildJobState::Queued) {index}else {break};
+            let kind = self.jobs[index].kind;
+            
+            // a job that needs another one only runs when the last of those succeeded
+            if let Some(needs) = self.jobs[index].needs {
+                let ok = self.jobs[0..index].iter().rev().find( | job | job.kind == needs).map_or(false, | job | job.state == BuildJobState::Succeeded);
+                if !ok {
+                    self.jobs[index].state = BuildJobState::Canceled;
+                    self.jobs[index].finished = Some(Instant::now());
+                    events.push(BuildQueueEvent::Finished {kind: kind, state: BuildJobState::Canceled});
+                    continue;
+                }
+            }
+            
+            let args: Vec<&str> = self.jobs[index].args.iter().map( | arg | arg.as_ref()).collect();
+            let title = format!("cargo {}", args.join(" "));
+            // the console types into a running program, the other jobs get no stdin
+            let spawned = spawn_process_command("cargo", &args, &self.workdir, kind == BuildJobKind::Run);
+            let job = &mut self.jobs[index];
+            job.started = Some(Instant::now());
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(err) => {
+                    job.error = format!("cannot start {}: {}", title, err);
+                    job.state = BuildJobState::Failed;
+                    job.finished = job.started;
+                    events.push(BuildQueueEvent::Finished {kind: kind, state: BuildJobState::Failed});
+                    continue;
+                }
+            };
+            
+            // the console owns a running program, for us it is done once it started
+            if kind == BuildJobKind::Run {
+                job.state = BuildJobState::Succeeded;
+                job.finished = job.started;
+                events.push(BuildQueueEvent::StartRun {child: child, title: title});
+                continue;
+            }
+            
+            // cargo reports its progress on stderr, keep it from filling up
+            if let Some(mut stderr) = child.stderr.take() {
+                std::thread::spawn(move || {
+                    let mut data = [0u8; 1024];
+                    while let Ok(n_bytes_read) = stderr.read(&mut data) {
+                        if n_bytes_read == 0 {
+                            return
+                        }
+                    }
+                });
+            }
+            
+            let mut stdout = if let Some(stdout) = child.stdout.take() {stdout}else {
+                let _ = child.kill();
+                job.state = BuildJobState::Failed;
+                job.finished = Some(Instant::now());
+                events.push(BuildQueueEvent::Finished {kind: kind, state: BuildJobState::Failed});
+                continue;
+            };
+            
+            let (tx, rx) = mpsc::channel();
+            let signal = self._signal;
+            let id = job.id;
+            std::thread::spawn(move || {
+                loop {
+                    let mut data = vec![0; 4096];
+                    let n_bytes_read = stdout.read(&mut data).unwrap_or(0);
+                    data.truncate(n_bytes_read);
+                    let _ = tx.send((id, data));
+                    Cx::send_signal(signal, SIGNAL_BUILD_QUEUE);
+                    if n_bytes_read == 0 {
+                        return
+                    }
+                }
+            });
+            job.state = BuildJobState::Running;
+            self._rx = Some(rx);
+            self._child = Some(child);
+            self._running = Some(id);
+            events.push(BuildQueueEvent::Started {kind: kind});
+        }
+        
+        // tick the elapsed time while something runs
+        if self._child.is_some() {
+            if self._timer.is_empty() {
+                self._timer = cx.start_timer(1.0, true);
+            }
+        }
+        else if !self._timer.is_empty() {
+            cx.stop_timer(&mut self._timer);
+        }
+        
+        // forget the oldest finished jobs, but keep the last one of each kind for needs
+        while self.jobs.len() > self.max_history {
+            let jobs = &self.jobs;
+            let superseded = jobs.iter().enumerate().position( | (index, job) | {
+                job.is_done() && jobs[index + 1..].iter().any( | later | later.kind == job.kind)
+            });
+            if let Some(index) = superseded {
+                self.jobs.remove(index);
+            }
+            else {
+                break
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // cargo cannot start in a directory that does not exist, so every job fails right away
+    fn test_queue(cx: &mut Cx) -> BuildQueue {
+        let mut build_queue = BuildQueue::new(cx);
+        build_queue.workdir = "/nonexistent/build_queue_test".to_string();
+        build_queue
+    }
+    
+    fn finished(events: &[BuildQueueEvent]) -> Vec<(BuildJobKind, BuildJobState)> {
+        events.iter().filter_map( | event | match event {
+            BuildQueueEvent::Finished {kind, state} => Some((*kind, *state)),
+            _ => None
+        }).collect()
+    }
+    
+    #[test]
+    fn jobs_run_in_queue_order() {
+        let mut cx = Cx::default();
+        let mut build_queue = test_queue(&mut cx);
+        build_queue.enqueue(&mut cx, BuildJobKind::Test, vec!["test".to_string()], None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Check, vec!["check".to_string()], None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Build, vec!["build".to_string()], None);
+        let mut events = Vec::new();
+        build_queue.start_next(&mut cx, &mut events);
+        assert_eq!(finished(&events), vec![
+            (BuildJobKind::Test, BuildJobState::Failed),
+            (BuildJobKind::Check, BuildJobState::Failed),
+            (BuildJobKind::Build, BuildJobState::Failed)
+        ]);
+        assert!(!build_queue.is_busy());
+    }
+    
+    #[test]
+    fn failed_start_is_described() {
+        let mut cx = Cx::default();
+        let mut build_queue = test_queue(&mut cx);
+        build_queue.enqueue(&mut cx, BuildJobKind::Check, vec!["check".to_string()], None);
+        build_queue.start_next(&mut cx, &mut Vec::new());
+        let job = build_queue.last_job(BuildJobKind::Check).unwrap();
+        assert!(job.error.starts_with("cannot start cargo check: "));
+        assert!(job.describe().starts_with("Check failed: cannot start cargo check"));
+    }
+    
+    #[test]
+    fn cancel_skips_queued_jobs() {
+        let mut cx = Cx::default();
+        let mut build_queue = test_queue(&mut cx);
+        build_queue.enqueue(&mut cx, BuildJobKind::Check, Vec::new(), None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Build, Vec::new(), None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Check, Vec::new(), None);
+        assert_eq!(build_queue.cancel(&mut cx, BuildJobKind::Check), 2);
+        assert!(build_queue.is_pending(BuildJobKind::Build));
+        assert!(!build_queue.is_pending(BuildJobKind::Check));
+        
+        let mut events = Vec::new();
+        build_queue.start_next(&mut cx, &mut events);
+        assert_eq!(finished(&events), vec![(BuildJobKind::Build, BuildJobState::Failed)]);
+        assert_eq!(build_queue.last_job(BuildJobKind::Check).unwrap().state, BuildJobState::Canceled);
+        // done jobs are not canceled again
+        assert_eq!(build_queue.cancel_all(&mut cx), 0);
+    }
+    
+    #[test]
+    fn needs_the_last_job_of_a_kind_to_succeed() {
+        let mut cx = Cx::default();
+        let mut build_queue = test_queue(&mut cx);
+        build_queue.enqueue(&mut cx, BuildJobKind::Build, Vec::new(), None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Run, Vec::new(), Some(BuildJobKind::Build));
+        let mut events = Vec::new();
+        build_queue.start_next(&mut cx, &mut events);
+        assert_eq!(finished(&events), vec![
+            (BuildJobKind::Build, BuildJobState::Failed),
+            (BuildJobKind::Run, BuildJobState::Canceled)
+        ]);
+        
+        // with the build done the run is attempted, and fails on the missing workdir itself
+        build_queue.enqueue(&mut cx, BuildJobKind::Build, Vec::new(), None);
+        build_queue.enqueue(&mut cx, BuildJobKind::Run, Vec::new(), Some(BuildJobKind::Build));
+        let build_index = build_queue.jobs.len() - 2;
+        build_queue.jobs[build_index].state = BuildJobState::Succeeded;
+        let mut events = Vec::new();
+        build_queue.start_next(&mut cx, &mut events);
+        assert_eq!(finished(&events), vec![(BuildJobKind::Run, BuildJobState::Failed)]);
+        
+        // a job needing a kind that never ran is canceled
+        build_queue.enqueue(&mut cx, BuildJobKind::Test, Vec::new(), Some(BuildJobKind::Check));
+        let mut events = Vec::new();
+        build_queue.start_next(&mut cx, &mut events);
+        assert_eq!(finished(&events), vec![(BuildJobKind::Test, BuildJobState::Canceled)]);
+    }
+    
+    #[test]
+    fn output_is_split_into_lines_and_decoded_whole() {
+        let mut cx = Cx::default();
+        let mut build_queue = test_queue(&mut cx);
+        let id = build_queue.enqueue(&mut cx, BuildJobKind::Build, Vec::new(), None);
+        build_queue.jobs[0].state = BuildJobState::Running;
+        build_queue._running = Some(id);
+        
+        let mut events = Vec::new();
+        build_queue.process_output(id, b"caf\xc3".to_vec(), &mut events);
+        build_queue.process_output(id, b"\xa9\n{\"reason\":\"compiler-artifact\",\"target\":{\"name\":\"render\"}}\npartial".to_vec(), &mut events);
+        let lines: Vec<String> = events.iter().flat_map( | event | match event {
+            BuildQueueEvent::Output {lines, ..} => lines.clone(),
+            _ => Vec::new()
+        }).collect();
+        assert_eq!(lines, vec![
+            "café".to_string(),
+            "{\"reason\":\"compiler-artifact\",\"target\":{\"name\":\"render\"}}".to_string()
+        ]);
+        assert_eq!(build_queue.jobs[0].units_done, 1);
+        assert_eq!(build_queue.jobs[0].current_unit, "render");
+        assert_eq!(build_queue._line, b"partial".to_vec());
+        
+        // output of a job that is not running anymore is dropped
+        let mut events = Vec::new();
+        build_queue.process_output(id + 1, b"other\n".to_vec(), &mut events);
+        assert_eq!(events.len(), 0);
+    }
+}
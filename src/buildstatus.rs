@@ -0,0 +1,145 @@
+use render::*;
+use widget::*;
+use editor::*;
+use crate::buildqueue::*;
+
+// a one line summary of the build queue at the bottom of the window, click it to cancel
+// the language server reports its problems here too
+#[derive(Clone)]
+pub struct BuildStatus {
+    pub view: View<ScrollBar>,
+    pub bg: Quad,
+    pub progress: Quad,
+    pub text: Text,
+    pub code_icon: CodeIcon,
+    pub height: f32,
+    pub colors: BuildStatusColors,
+    pub _bg_area: Area,
+    pub _hover: bool
+}
+
+#[derive(Clone)]
+pub struct BuildStatusColors {
+    pub bg: Color,
+    pub bg_hover: Color,
+    pub progress: Color,
+    pub text: Color,
+    pub hint: Color,
+    pub error: Color
+}
+
+#[derive(Clone, PartialEq)]
+pub enum BuildStatusEvent {
+    None,
+    CancelAll
+}
+
+impl Style for BuildStatus {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View::style(cx),
+            bg: Quad::style(cx),
+            progress: Quad::style(cx),
+            text: Text {
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            code_icon: CodeIcon::style(cx),
+            height: 22.,
+            colors: BuildStatusColors {
+                bg: cx.color("bg_odd"),
+                bg_hover: cx.color("bg_selected_over"),
+                progress: color256(0, 122, 204),
+                text: color("#bbb"),
+                hint: color("#777"),
+                error: color("#e66")
+            },
+            _bg_area: Area::Empty,
+            _hover: false
+        }
+    }
+}
+
+impl BuildStatus {
+    pub fn handle_build_status(&mut self, cx: &mut Cx, event: &mut Event, build_queue: &BuildQueue) -> BuildStatusEvent {
+        // job changes and the elapsed time ticks
+        if build_queue.is_build_queue_event(event) {
+            self.view.redraw_view_area(cx);
+        }
+        match event.hits(cx, self._bg_area, HitOpt::default()) {
+            Event::FingerDown(_fe) => {
+                if build_queue.is_busy() {
+                    return BuildStatusEvent::CancelAll
+                }
+            },
+            Event::FingerHover(fe) => {
+                if build_queue.is_busy() {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                }
+                let hover = if let HoverState::Out = fe.hover_state {false}else {true};
+                if hover != self._hover {
+                    self._hover = hover;
+                    self.view.redraw_view_area(cx);
+                }
+            },
+            _ => ()
+        }
+        BuildStatusEvent::None
+    }
+    
+    pub fn draw_build_status(&mut self, cx: &mut Cx, build_queue: &BuildQueue, lsp_status: &str) {
+        if let Err(_) = self.view.begin_view(cx, Layout {
+            width: Bounds::Fill,
+            height: Bounds::Fix(self.height),
+            ..Default::default()
+        }) {
+            return
+        }
+        let busy = build_queue.is_busy();
+        self.bg.color = if self._hover && busy {self.colors.bg_hover}else {self.colors.bg};
+        let bg_inst = self.bg.begin_quad(cx, &Layout {
+            width: Bounds::Fill,
+            height: Bounds::Fill,
+            padding: Padding {l: 4., t: 3., b: 2., r: 4.},
+            ..Default::default()
+        });
+        
+        // the running job first, otherwise the one that finished last
+        let job = build_queue.running_job().or(build_queue.jobs.last());
+        if let Some(job) = job {
+            if let Some(progress) = job.progress() {
+                if job.state == BuildJobState::Running {
+                    let rect = cx.get_turtle_rect();
+                    self.progress.color = self.colors.progress;
+                    self.progress.draw_quad_abs(cx, Rect {x: rect.x, y: rect.y + rect.h - 2., w: rect.w * progress, h: 2.});
+                }
+            }
+            self.code_icon.draw_icon_walk(cx, match job.state {
+                BuildJobState::Queued | BuildJobState::Running => CodeIconType::Wait,
+                BuildJobState::Succeeded => CodeIconType::Ok,
+                BuildJobState::Failed => CodeIconType::Error,
+                BuildJobState::Canceled => CodeIconType::Warning
+            });
+            self.text.color = self.colors.text;
+            self.text.draw_text(cx, &job.describe());
+            let queued = build_queue.jobs.iter().filter( | job | job.state == BuildJobState::Queued).count();
+            self.text.color = self.colors.hint;
+            if queued > 0 {
+                self.text.draw_text(cx, &format!(" +{} queued", queued));
+            }
+            if busy {
+                self.text.draw_text(cx, " - click to cancel");
+            }
+        }
+        else {
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, "Idle");
+        }
+        if lsp_status.len() > 0 {
+            self.text.color = self.colors.error;
+            self.text.draw_text(cx, &format!("  {}", lsp_status));
+        }
+        self._bg_area = self.bg.end_quad(cx, &bg_inst);
+        self.view.end_view(cx);
+    }
+}
@@ -0,0 +1,832 @@
+use render::*;
+use widget::*;
+
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::process::{Child, ChildStdin};
+
+// the output of a running program, with ansi colors, search, file links and stdin
+pub struct Console {
+    pub view: View<ScrollBar>,
+    pub bg: Quad,
+    pub match_bg: Quad,
+    pub text: Text,
+    pub row_height: f32,
+    pub max_lines: usize,
+    pub colors: ConsoleColors,
+    pub _signal: Signal,
+    pub _bg_area: Area,
+    pub _child: Option<Child>,
+    pub _stdin: Option<ChildStdin>,
+    pub _rx: Option<mpsc::Receiver<(ConsoleStream, Vec<u8>)>>,
+    pub _open_streams: usize,
+    pub _streams: [ConsoleStreamState; 2],
+    pub _lines: Vec<ConsoleLine>,
+    pub _link_areas: Vec<(Area, usize)>,
+    pub _input: String,
+    pub _search: String,
+    pub _search_active: bool,
+    pub _matches: Vec<usize>,
+    pub _match_index: usize,
+    pub _scroll_to_row: Option<usize>,
+    pub _follow: bool
+}
+
+#[derive(Clone)]
+pub struct ConsoleColors {
+    pub bg: Color,
+    pub text: Color,
+    pub text_bold: Color,
+    pub stderr: Color,
+    pub stdin: Color,
+    pub info: Color,
+    pub link: Color,
+    pub search_match: Color,
+    pub search_current: Color,
+    pub ansi: [Color; 16]
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConsoleStream {
+    Stdout,
+    Stderr,
+    Stdin,
+    Info
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConsoleColor {
+    Default,
+    Index(usize),
+    Rgb(u8, u8, u8)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ConsoleStyle {
+    pub fg: ConsoleColor,
+    pub bold: bool
+}
+
+impl Default for ConsoleStyle {
+    fn default() -> Self {
+        ConsoleStyle {fg: ConsoleColor::Default, bold: false}
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConsoleLink {
+    pub path: String,
+    pub row: usize,
+    pub col: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Clone)]
+pub struct ConsoleLine {
+    pub stream: ConsoleStream,
+    pub chars: Vec<char>,
+    // the style that starts at each char offset
+    pub styles: Vec<(usize, ConsoleStyle)>,
+    pub link: Option<ConsoleLink>
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AnsiState {
+    Text,
+    Escape,
+    Csi(String)
+}
+
+impl Default for AnsiState {
+    fn default() -> Self {AnsiState::Text}
+}
+
+// per output stream decoding state, escapes and utf8 can straddle reads
+#[derive(Clone, Default)]
+pub struct ConsoleStreamState {
+    pub utf8: Vec<u8>,
+    pub ansi: AnsiState,
+    pub style: ConsoleStyle,
+    pub line: Option<usize>
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ConsoleEvent {
+    None,
+    SelectLink {path: String, row: usize, col: usize}
+}
+
+const SIGNAL_CONSOLE_OUTPUT: usize = 1;
+
+impl Style for Console {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            bg: Quad {
+                do_scroll: false,
+                ..Style::style(cx)
+            },
+            match_bg: Quad::style(cx),
+            text: Text {
+                font: cx.load_font_style("mono_font"),
+                font_size: 11.0,
+                line_spacing: 1.4,
+                do_dpi_dilate: true,
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            row_height: 16.,
+            max_lines: 10000,
+            colors: ConsoleColors {
+                bg: color256(30, 30, 30),
+                text: color256(204, 204, 204),
+                text_bold: color256(255, 255, 255),
+                stderr: color256(241, 118, 118),
+                stdin: color256(156, 220, 254),
+                info: color256(128, 128, 128),
+                link: color256(78, 148, 206),
+                search_match: color256a(255, 255, 255, 20),
+                search_current: color256(4, 57, 94),
+                ansi: [
+                    color256(0, 0, 0),
+                    color256(205, 49, 49),
+                    color256(13, 188, 121),
+                    color256(229, 229, 16),
+                    color256(36, 114, 200),
+                    color256(188, 63, 188),
+                    color256(17, 168, 205),
+                    color256(229, 229, 229),
+                    color256(102, 102, 102),
+                    color256(241, 76, 76),
+                    color256(35, 209, 139),
+                    color256(245, 245, 67),
+                    color256(59, 142, 234),
+                    color256(214, 112, 214),
+                    color256(41, 184, 219),
+                    color256(255, 255, 255),
+                ]
+            },
+            _signal: cx.new_signal(),
+            _bg_area: Area::Empty,
+            _child: None,
+            _stdin: None,
+            _rx: None,
+            _open_streams: 0,
+            _streams: Default::default(),
+            _lines: Vec::new(),
+            _link_areas: Vec::new(),
+            _input: String::new(),
+            _search: String::new(),
+            _search_active: false,
+            _matches: Vec::new(),
+            _match_index: 0,
+            _scroll_to_row: None,
+            _follow: true
+        }
+    }
+}
+
+impl Console {
+    pub fn is_running(&self) -> bool {
+        self._child.is_some()
+    }
+    
+    pub fn kill_child(&mut self) {
+        if let Some(child) = &mut self._child {
+            let _ = child.kill();
+        }
+        self._child = None;
+        self._stdin = None;
+        self._rx = None;
+    }
+    
+    // takes over the pipes of a freshly spawned child
+    pub fn start_child(&mut self, cx: &mut Cx, mut child: Child, title: &str) {
+        self.kill_child();
+        self._streams = Default::default();
+        self.push_line(ConsoleStream::Info, title);
+        
+        let (tx, rx) = mpsc::channel();
+        self._open_streams = 0;
+        if let Some(stdout) = child.stdout.take() {
+            Self::spawn_reader(ConsoleStream::Stdout, stdout, tx.clone(), self._signal);
+            self._open_streams += 1;
+        }
+        if let Some(stderr) = child.stderr.take() {
+            Self::spawn_reader(ConsoleStream::Stderr, stderr, tx.clone(), self._signal);
+            self._open_streams += 1;
+        }
+        self._stdin = child.stdin.take();
+        self._rx = Some(rx);
+        self._child = Some(child);
+        self._follow = true;
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn spawn_reader<R: Read + Send + 'static>(stream: ConsoleStream, mut reader: R, tx: mpsc::Sender<(ConsoleStream, Vec<u8>)>, signal: Signal) {
+        std::thread::spawn(move || {
+            loop {
+                let mut data = vec![0; 4096];
+                let n_bytes_read = reader.read(&mut data).unwrap_or(0);
+                data.truncate(n_bytes_read);
+                if tx.send((stream, data)).is_err() {
+                    return
+                }
+                Cx::send_signal(signal, SIGNAL_CONSOLE_OUTPUT);
+                if n_bytes_read == 0 {
+                    return
+                }
+            }
+        });
+    }
+    
+    pub fn clear(&mut self, cx: &mut Cx) {
+        self._lines.truncate(0);
+        for state in &mut self._streams {
+            state.line = None;
+        }
+        self.update_matches();
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn push_line(&mut self, stream: ConsoleStream, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        let link = Self::find_link(&chars);
+        self._lines.push(ConsoleLine {
+            stream: stream,
+            chars: chars,
+            styles: vec![(0, ConsoleStyle::default())],
+            link: link
+        });
+        self.limit_scrollback();
+    }
+    
+    fn limit_scrollback(&mut self) {
+        if self._lines.len() <= self.max_lines {
+            return
+        }
+        let drop = self._lines.len() - self.max_lines;
+        self._lines.drain(0..drop);
+        // open lines move up with the rest
+        for state in &mut self._streams {
+            state.line = match state.line {
+                Some(line) if line >= drop => Some(line - drop),
+                _ => None
+            };
+        }
+    }
+    
+    fn process_output(&mut self, stream: ConsoleStream, data: &[u8]) {
+        let index = if stream == ConsoleStream::Stderr {1}else {0};
+        self._streams[index].utf8.extend_from_slice(data);
+        let bytes = std::mem::replace(&mut self._streams[index].utf8, Vec::new());
+        // keep an incomplete utf8 sequence at the end for the next read
+        let (text, rest) = match std::str::from_utf8(&bytes) {
+            Ok(text) => (text.to_string(), &bytes[bytes.len()..]),
+            Err(err) if err.error_len().is_none() => {
+                let valid = err.valid_up_to();
+                (String::from_utf8_lossy(&bytes[..valid]).to_string(), &bytes[valid..])
+            },
+            Err(_) => (String::from_utf8_lossy(&bytes).to_string(), &bytes[bytes.len()..])
+        };
+        self._streams[index].utf8 = rest.to_vec();
+        for ch in text.chars() {
+            self.process_char(stream, index, ch);
+        }
+    }
+    
+    fn process_char(&mut self, stream: ConsoleStream, index: usize, ch: char) {
+        let state = &mut self._streams[index];
+        match &mut state.ansi {
+            AnsiState::Escape => {
+                state.ansi = if ch == '[' {AnsiState::Csi(String::new())}else {AnsiState::Text};
+                return
+            },
+            AnsiState::Csi(params) => {
+                if ch >= '@' && ch <= '~' { // final byte
+                    let params = params.clone();
+                    state.ansi = AnsiState::Text;
+                    if ch == 'm' {
+                        Self::apply_sgr(&mut state.style, &params);
+                    }
+                }
+                else {
+                    params.push(ch);
+                }
+                return
+            },
+            AnsiState::Text => ()
+        }
+        match ch {
+            '\x1b' => {
+                state.ansi = AnsiState::Escape;
+            },
+            '\r' => (),
+            '\n' => {
+                if state.line.is_none() {
+                    self.push_line(stream, "");
+                }
+                let state = &mut self._streams[index];
+                if let Some(line) = state.line.take() {
+                    let line = &mut self._lines[line];
+                    line.link = Self::find_link(&line.chars);
+                }
+            },
+            _ => {
+                if state.line.is_none() {
+                    let style = state.style;
+                    self.push_line(stream, "");
+                    let line = self._lines.len() - 1;
+                    self._lines[line].styles[0].1 = style;
+                    self._streams[index].line = Some(line);
+                }
+                let state = &mut self._streams[index];
+                let line = &mut self._lines[state.line.unwrap()];
+                if line.styles.last().unwrap().1 != state.style {
+                    if line.styles.last().unwrap().0 == line.chars.len() {
+                        line.styles.pop();
+                    }
+                    line.styles.push((line.chars.len(), state.style));
+                }
+                line.chars.push(ch);
+            }
+        }
+    }
+    
+    fn apply_sgr(style: &mut ConsoleStyle, params: &str) {
+        let codes: Vec<usize> = params.split(';').map( | p | p.parse::<usize>().unwrap_or(0)).collect();
+        let mut i = 0;
+        while i < codes.len() {
+            match codes[i] {
+                0 => *style = ConsoleStyle::default(),
+                1 => style.bold = true,
+                22 => style.bold = false,
+                30..=37 => style.fg = ConsoleColor::Index(codes[i] - 30),
+                90..=97 => style.fg = ConsoleColor::Index(codes[i] - 90 + 8),
+                39 => style.fg = ConsoleColor::Default,
+                38 => { // 38;5;n or 38;2;r;g;b
+                    if i + 2 < codes.len() && codes[i + 1] == 5 {
+                        style.fg = Self::color_256(codes[i + 2]);
+                        i += 2;
+                    }
+                    else if i + 4 < codes.len() && codes[i + 1] == 2 {
+                        style.fg = ConsoleColor::Rgb(codes[i + 2] as u8, codes[i + 3] as u8, codes[i + 4] as u8);
+                        i += 4;
+                    }
+                },
+                48 => { // skip background colors
+                    if i + 1 < codes.len() {
+                        i += if codes[i + 1] == 5 {2}else if codes[i + 1] == 2 {4}else {0};
+                    }
+                },
+                _ => ()
+            }
+            i += 1;
+        }
+    }
+    
+    fn color_256(index: usize) -> ConsoleColor {
+        if index < 16 {
+            return ConsoleColor::Index(index)
+        }
+        if index < 232 {
+            let level = | v: usize | if v == 0 {0}else {55 + v as u8 * 40};
+            let i = index - 16;
+            return ConsoleColor::Rgb(level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        let gray = 8 + (index.min(255) - 232) as u8 * 10;
+        ConsoleColor::Rgb(gray, gray, gray)
+    }
+    
+    fn resolve_color(&self, stream: ConsoleStream, style: &ConsoleStyle) -> Color {
+        match style.fg {
+            ConsoleColor::Index(index) => if style.bold && index < 8 {self.colors.ansi[index + 8]}else {self.colors.ansi[index]},
+            ConsoleColor::Rgb(r, g, b) => color256(r as i32, g as i32, b as i32),
+            ConsoleColor::Default => match stream {
+                ConsoleStream::Stderr => self.colors.stderr,
+                ConsoleStream::Stdin => self.colors.stdin,
+                ConsoleStream::Info => self.colors.info,
+                ConsoleStream::Stdout => if style.bold {self.colors.text_bold}else {self.colors.text}
+            }
+        }
+    }
+    
+    // finds a path.ext:row or path.ext:row:col like rustc, panics and dbg! print them
+    pub fn find_link(chars: &[char]) -> Option<ConsoleLink> {
+        let is_path_char = | ch: char | ch.is_alphanumeric() || ch == '_' || ch == '-' || ch == '.' || ch == '/' || ch == '\\';
+        let mut start = 0;
+        while start < chars.len() {
+            if !is_path_char(chars[start]) {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < chars.len() && is_path_char(chars[end]) {
+                end += 1;
+            }
+            let path: String = chars[start..end].iter().collect();
+            let has_ext = path.rfind('.').map_or(false, | dot | path[dot + 1..].starts_with( | ch: char | ch.is_alphabetic()));
+            if has_ext && end < chars.len() && chars[end] == ':' {
+                if let Some((row, row_end)) = Self::parse_number(chars, end + 1) {
+                    let (col, link_end) = if row_end < chars.len() && chars[row_end] == ':' {
+                        Self::parse_number(chars, row_end + 1).unwrap_or((1, row_end))
+                    }
+                    else {
+                        (1, row_end)
+                    };
+                    return Some(ConsoleLink {
+                        path: path.trim_start_matches("./").to_string(),
+                        row: row,
+                        col: col,
+                        start: start,
+                        end: link_end
+                    })
+                }
+            }
+            start = end;
+        }
+        None
+    }
+    
+    fn parse_number(chars: &[char], start: usize) -> Option<(usize, usize)> {
+        let mut end = start;
+        while end < chars.len() && chars[end].is_ascii_digit() {
+            end += 1;
+        }
+        if end == start {
+            return None
+        }
+        let num: String = chars[start..end].iter().collect();
+        num.parse::<usize>().ok().map( | num | (num, end))
+    }
+    
+    fn send_input(&mut self, cx: &mut Cx) {
+        let input = std::mem::replace(&mut self._input, String::new());
+        if let Some(stdin) = &mut self._stdin {
+            let _ = stdin.write_all(format!("{}\n", input).as_bytes());
+            let _ = stdin.flush();
+            self.push_line(ConsoleStream::Stdin, &input);
+            self._follow = true;
+        }
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn update_matches(&mut self) {
+        self._matches.truncate(0);
+        if self._search.len() == 0 {
+            return
+        }
+        let search = self._search.to_lowercase();
+        for (index, line) in self._lines.iter().enumerate() {
+            let text: String = line.chars.iter().collect();
+            if text.to_lowercase().contains(&search) {
+                self._matches.push(index);
+            }
+        }
+        if self._match_index >= self._matches.len() {
+            self._match_index = self._matches.len().max(1) - 1;
+        }
+    }
+    
+    fn select_match(&mut self, cx: &mut Cx, next: bool) {
+        if self._matches.len() == 0 {
+            return
+        }
+        self._match_index = if next {
+            (self._match_index + 1) % self._matches.len()
+        }
+        else {
+            (self._match_index + self._matches.len() - 1) % self._matches.len()
+        };
+        self._scroll_to_row = Some(self._matches[self._match_index]);
+        self._follow = false;
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn process_signal(&mut self, cx: &mut Cx) {
+        let mut datas = Vec::new();
+        if let Some(rx) = &self._rx {
+            while let Ok(data) = rx.try_recv() {
+                datas.push(data);
+            }
+        }
+        if datas.len() == 0 {
+            return
+        }
+        for (stream, data) in datas {
+            if data.len() == 0 { // stream closed
+                self._open_streams = self._open_streams.max(1) - 1;
+                continue;
+            }
+            self.process_output(stream, &data);
+        }
+        if self._open_streams == 0 {
+            if let Some(child) = &mut self._child {
+                let status = match child.wait() {
+                    Ok(status) => format!("process exited with {}", status),
+                    Err(_) => "process exited".to_string()
+                };
+                self.push_line(ConsoleStream::Info, &status);
+            }
+            self._child = None;
+            self._stdin = None;
+        }
+        if self._search_active {
+            self.update_matches();
+        }
+        self.view.redraw_view_area(cx);
+    }
+    
+    pub fn handle_console(&mut self, cx: &mut Cx, event: &mut Event) -> ConsoleEvent {
+        if self.view.handle_scroll_bars(cx, event) {
+            // scrolling away from the bottom stops following the output
+            let scroll = self.view.get_scroll_pos(cx);
+            let total = self.view.get_scroll_view_total();
+            let visible = self.view.get_rect(cx).h;
+            self._follow = scroll.y + visible >= total.y - self.row_height;
+        }
+        
+        if let Event::Signal(se) = event {
+            if self._signal.is_signal(se) {
+                self.process_signal(cx);
+            }
+        }
+        
+        for (area, line) in &self._link_areas {
+            match event.hits(cx, *area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    if let Some(link) = self._lines.get(*line).and_then( | line | line.link.as_ref()) {
+                        cx.set_down_mouse_cursor(MouseCursor::Hand);
+                        return ConsoleEvent::SelectLink {path: link.path.clone(), row: link.row, col: link.col}
+                    }
+                },
+                Event::FingerHover(_fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                },
+                _ => ()
+            }
+        }
+        
+        match event.hits(cx, self._bg_area, HitOpt {no_scrolling: true, ..Default::default()}) {
+            Event::FingerDown(_fe) => {
+                cx.set_key_focus(self._bg_area);
+            },
+            Event::KeyFocus(_) | Event::KeyFocusLost(_) => {
+                self.view.redraw_view_area(cx);
+            },
+            Event::KeyDown(ke) => {
+                match ke.key_code {
+                    KeyCode::KeyF if ke.modifiers.control || ke.modifiers.logo => {
+                        self._search_active = !self._search_active;
+                        self.update_matches();
+                        self.view.redraw_view_area(cx);
+                    },
+                    KeyCode::Escape => if self._search_active {
+                        self._search_active = false;
+                        self._matches.truncate(0);
+                        self.view.redraw_view_area(cx);
+                    },
+                    KeyCode::F3 => {
+                        self.select_match(cx, !ke.modifiers.shift);
+                    },
+                    KeyCode::Return => if self._search_active {
+                        self.select_match(cx, !ke.modifiers.shift);
+                    }
+                    else {
+                        self.send_input(cx);
+                    },
+                    KeyCode::Backspace => {
+                        if self._search_active {
+                            self._search.pop();
+                            self.update_matches();
+                        }
+                        else {
+                            self._input.pop();
+                        }
+                        self.view.redraw_view_area(cx);
+                    },
+                    _ => ()
+                }
+            },
+            Event::TextInput(te) => {
+                let input: String = te.input.chars().filter( | ch | *ch != '\n' && *ch != '\r').collect();
+                if self._search_active {
+                    self._search.push_str(&input);
+                    self.update_matches();
+                    if let Some(row) = self._matches.get(self._match_index) {
+                        self._scroll_to_row = Some(*row);
+                        self._follow = false;
+                    }
+                }
+                else {
+                    self._input.push_str(&input);
+                }
+                self.view.redraw_view_area(cx);
+            },
+            _ => ()
+        }
+        ConsoleEvent::None
+    }
+    
+    pub fn draw_console(&mut self, cx: &mut Cx) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        self.bg.color = self.colors.bg;
+        let bg_area = self.bg.draw_quad(cx, Rect {x: 0., y: 0., w: cx.get_width_total(), h: cx.get_height_total()}).into_area();
+        cx.update_area_refs(self._bg_area, bg_area);
+        self._bg_area = bg_area;
+        cx.new_instance_draw_call(&self.match_bg.shader, 0);
+        
+        // only draw the rows that are visible
+        let scroll = self.view.get_scroll_pos(cx);
+        let visible = cx.get_turtle_rect().h;
+        let first = ((scroll.y / self.row_height) as usize).min(self._lines.len());
+        let last = (((scroll.y + visible) / self.row_height) as usize + 1).min(self._lines.len());
+        if first > 0 {
+            cx.walk_turtle(Bounds::Fix(0.), Bounds::Fix(first as f32 * self.row_height), Margin::zero(), None);
+            cx.turtle_new_line();
+        }
+        
+        self._link_areas.truncate(0);
+        let current_match = if self._search_active {self._matches.get(self._match_index).cloned()}else {None};
+        for index in first..last {
+            if self._search_active && self._matches.binary_search(&index).is_ok() {
+                self.match_bg.color = if current_match == Some(index) {self.colors.search_current}else {self.colors.search_match};
+                self.match_bg.draw_quad(cx, Rect {
+                    x: 0.,
+                    y: index as f32 * self.row_height,
+                    w: cx.get_width_total(),
+                    h: self.row_height
+                });
+            }
+            cx.walk_turtle(Bounds::Fix(0.), Bounds::Fix(self.row_height), Margin::zero(), None);
+            let line = &self._lines[index];
+            // split the styled runs on the link boundaries
+            let mut cuts: Vec<usize> = line.styles.iter().map( | (start, _) | *start).collect();
+            if let Some(link) = &line.link {
+                cuts.push(link.start);
+                cuts.push(link.end);
+            }
+            cuts.push(line.chars.len());
+            cuts.sort();
+            cuts.dedup();
+            for pair in cuts.windows(2) {
+                let (start, end) = (pair[0], pair[1]);
+                if start >= end {
+                    continue;
+                }
+                let style_index = match line.styles.binary_search_by_key(&start, | (s, _) | *s) {
+                    Ok(i) => i,
+                    Err(i) => i.max(1) - 1
+                };
+                let in_link = line.link.as_ref().map_or(false, | link | start >= link.start && end <= link.end);
+                self.text.color = if in_link {self.colors.link}else {self.resolve_color(line.stream, &line.styles[style_index].1)};
+                let text: String = line.chars[start..end].iter().collect();
+                let area = self.text.draw_text(cx, &text);
+                if in_link {
+                    self._link_areas.push((area, index));
+                }
+            }
+            cx.turtle_new_line();
+        }
+        if last < self._lines.len() {
+            cx.walk_turtle(Bounds::Fix(0.), Bounds::Fix((self._lines.len() - last) as f32 * self.row_height), Margin::zero(), None);
+            cx.turtle_new_line();
+        }
+        
+        // the prompt line, for stdin or the search
+        cx.walk_turtle(Bounds::Fix(0.), Bounds::Fix(self.row_height), Margin::zero(), None);
+        let caret = if cx.has_key_focus(self._bg_area) {"_"}else {""};
+        if self._search_active {
+            self.text.color = self.colors.info;
+            self.text.draw_text(cx, &format!("find: {}{} ({}/{})", self._search, caret, if self._matches.len() > 0 {self._match_index + 1}else {0}, self._matches.len()));
+        }
+        else if self.is_running() {
+            self.text.color = self.colors.stdin;
+            self.text.draw_text(cx, &format!("> {}{}", self._input, caret));
+        }
+        cx.turtle_new_line();
+        
+        if let Some(row) = self._scroll_to_row.take() {
+            self.view.scroll_into_view(cx, Rect {x: 0., y: row as f32 * self.row_height, w: 0., h: self.row_height});
+        }
+        else if self._follow {
+            let y = self._lines.len() as f32 * self.row_height;
+            self.view.scroll_into_view(cx, Rect {x: 0., y: y, w: 0., h: self.row_height});
+        }
+        self.view.end_view(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn new_console() -> Console {
+        let mut cx = Cx::default();
+        set_dark_style(&mut cx);
+        Console::style(&mut cx)
+    }
+    
+    fn line_text(line: &ConsoleLine) -> String {
+        line.chars.iter().collect()
+    }
+    
+    fn find_link(text: &str) -> Option<ConsoleLink> {
+        Console::find_link(&text.chars().collect::<Vec<char>>())
+    }
+    
+    #[test]
+    fn sgr_splits_lines_into_color_spans() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stdout, b"plain \x1b[1;31mred\x1b[0m done\n");
+        let line = &console._lines[0];
+        assert_eq!(line_text(line), "plain red done");
+        assert_eq!(line.styles, vec![
+            (0, ConsoleStyle::default()),
+            (6, ConsoleStyle {fg: ConsoleColor::Index(1), bold: true}),
+            (9, ConsoleStyle::default())
+        ]);
+    }
+    
+    #[test]
+    fn sgr_extended_colors() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stdout, b"\x1b[38;5;196ma\x1b[38;2;1;2;3mb\x1b[48;5;20mc\x1b[95md\x1b[39me\n");
+        let fgs: Vec<ConsoleColor> = console._lines[0].styles.iter().map( | (_, style) | style.fg).collect();
+        // the background color is skipped without eating the chars after it
+        assert_eq!(line_text(&console._lines[0]), "abcde");
+        assert_eq!(fgs, vec![ConsoleColor::Rgb(255, 0, 0), ConsoleColor::Rgb(1, 2, 3), ConsoleColor::Index(13), ConsoleColor::Default]);
+    }
+    
+    #[test]
+    fn style_carries_over_to_the_next_line() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stdout, b"\x1b[32mgreen\nstill green\n");
+        assert_eq!(console._lines[1].styles, vec![(0, ConsoleStyle {fg: ConsoleColor::Index(2), bold: false})]);
+    }
+    
+    #[test]
+    fn escapes_and_utf8_split_across_reads() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stdout, b"a\x1b[3");
+        console.process_output(ConsoleStream::Stdout, b"4mb\xc3");
+        console.process_output(ConsoleStream::Stdout, b"\xa9\x1b");
+        console.process_output(ConsoleStream::Stdout, b"[0mc\n");
+        let line = &console._lines[0];
+        assert_eq!(line_text(line), "abéc");
+        assert_eq!(line.styles, vec![
+            (0, ConsoleStyle::default()),
+            (1, ConsoleStyle {fg: ConsoleColor::Index(4), bold: false}),
+            (3, ConsoleStyle::default())
+        ]);
+    }
+    
+    #[test]
+    fn streams_keep_their_own_lines() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stdout, b"abc");
+        console.process_output(ConsoleStream::Stderr, b"\x1b[1merr\n");
+        console.process_output(ConsoleStream::Stdout, b"def\n");
+        let texts: Vec<String> = console._lines.iter().map( | line | line_text(line)).collect();
+        assert_eq!(texts, vec!["abcdef", "err"]);
+        assert_eq!(console._lines[1].stream, ConsoleStream::Stderr);
+        assert_eq!(console._lines[0].styles, vec![(0, ConsoleStyle::default())]);
+    }
+    
+    #[test]
+    fn finished_lines_get_their_link() {
+        let mut console = new_console();
+        console.process_output(ConsoleStream::Stderr, b"  --> src/main.rs:");
+        assert_eq!(console._lines[0].link, None);
+        console.process_output(ConsoleStream::Stderr, b"12:5\n");
+        assert_eq!(console._lines[0].link, Some(ConsoleLink {path: "src/main.rs".to_string(), row: 12, col: 5, start: 6, end: 22}));
+    }
+    
+    #[test]
+    fn finds_rustc_and_panic_links() {
+        assert_eq!(find_link("error: src/lib.rs:3:14: 3:20"), Some(ConsoleLink {path: "src/lib.rs".to_string(), row: 3, col: 14, start: 7, end: 22}));
+        let link = find_link("thread 'main' panicked at 'oops', ./src/main.rs:7:5").unwrap();
+        assert_eq!((link.path.as_ref(), link.row, link.col), ("src/main.rs", 7, 5));
+        // no column means the start of the line
+        let link = find_link("[src/main.rs:9] x = 1").unwrap();
+        assert_eq!((link.path.as_ref(), link.row, link.col, link.start, link.end), ("src/main.rs", 9, 1, 1, 14));
+    }
+    
+    #[test]
+    fn skips_things_that_are_not_links() {
+        assert_eq!(find_link("Compiling makepad v0.1.0"), None);
+        assert_eq!(find_link("listening on 127.0.0.1:8000"), None);
+        assert_eq!(find_link("see src/main.rs for more"), None);
+        assert_eq!(find_link("src/main.rs:abc"), None);
+    }
+}
@@ -0,0 +1,485 @@
+use render::*;
+use editor::*;
+use crate::gdbmi::*;
+
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::process::{Child, ChildStdin};
+use std::collections::HashMap;
+
+// runs a binary under gdb and talks to it with the machine interface
+pub struct Debugger {
+    pub gdb: String,
+    pub workdir: String,
+    pub max_log: usize,
+    pub state: DebuggerState,
+    pub frames: Vec<DebugFrame>,
+    pub variables: Vec<DebugVariable>,
+    pub selected_frame: usize,
+    pub log: Vec<String>,
+    pub status: String,
+    pub _signal: Signal,
+    pub _child: Option<Child>,
+    pub _stdin: Option<ChildStdin>,
+    pub _rx: Option<mpsc::Receiver<Vec<u8>>>,
+    pub _line: String,
+    pub _abs_workdir: String,
+    pub _next_token: u64,
+    pub _requests: HashMap<u64, DebugRequest>,
+    pub _breakpoints: Vec<DebugBreakpoint>,
+    pub _exec_path: Option<String>
+}
+
+const SIGNAL_DEBUGGER_OUTPUT: usize = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebuggerState {
+    NotRunning,
+    Running,
+    Stopped,
+    Exited
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugCommand {
+    Start,
+    Continue,
+    Pause,
+    StepOver,
+    StepInto,
+    StepOut,
+    Stop
+}
+
+impl DebugCommand {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DebugCommand::Start => "Start F5",
+            DebugCommand::Continue => "Continue F5",
+            DebugCommand::Pause => "Pause",
+            DebugCommand::StepOver => "Over F6",
+            DebugCommand::StepInto => "Into F7",
+            DebugCommand::StepOut => "Out S-F7",
+            DebugCommand::Stop => "Stop S-F5"
+        }
+    }
+}
+
+// path is relative to the workdir and None for code outside of it, rows are 0 based
+#[derive(Clone, Debug)]
+pub struct DebugFrame {
+    pub level: usize,
+    pub func: String,
+    pub file: String,
+    pub path: Option<String>,
+    pub row: usize,
+    pub addr: String
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugVariable {
+    pub name: String,
+    pub type_name: String,
+    pub value: String
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugBreakpoint {
+    pub path: String,
+    pub row: usize,
+    pub number: String
+}
+
+#[derive(Clone)]
+pub enum DebugRequest {
+    BreakInsert {path: String, row: usize},
+    StackFrames,
+    Variables,
+    Other
+}
+
+#[derive(Clone)]
+pub enum DebuggerEvent {
+    StartRequest,
+    Stopped {path: String, row: usize}
+}
+
+impl Debugger {
+    pub fn new(cx: &mut Cx) -> Self {
+        Self {
+            gdb: "gdb".to_string(),
+            workdir: "./".to_string(),
+            max_log: 200,
+            state: DebuggerState::NotRunning,
+            frames: Vec::new(),
+            variables: Vec::new(),
+            selected_frame: 0,
+            log: Vec::new(),
+            status: String::new(),
+            _signal: cx.new_signal(),
+            _child: None,
+            _stdin: None,
+            _rx: None,
+            _line: String::new(),
+            _abs_workdir: String::new(),
+            _next_token: 1,
+            _requests: HashMap::new(),
+            _breakpoints: Vec::new(),
+            _exec_path: None
+        }
+    }
+    
+    pub fn is_running(&self) -> bool {
+        self._child.is_some()
+    }
+    
+    pub fn is_debugger_event(&self, event: &Event) -> bool {
+        if let Event::Signal(se) = event {
+            return self._signal.is_signal(se)
+        }
+        false
+    }
+    
+    pub fn start(&mut self, cx: &mut Cx, executable: &str, text_buffers: &mut TextBuffers) {
+        self.stop(cx, text_buffers);
+        self.log.truncate(0);
+        self.status.truncate(0);
+        
+        let args = ["--interpreter=mi2", "--quiet", executable];
+        let mut child = match spawn_process_command(&self.gdb, &args, &self.workdir, true) {
+            Ok(child) => child,
+            Err(err) => {
+                self.push_log(format!("cannot start {}: {:?}", self.gdb, err));
+                return
+            }
+        };
+        
+        let stdin = child.stdin.take();
+        let stdout_opt = child.stdout.take();
+        if stdin.is_none() || stdout_opt.is_none() {
+            let _ = child.kill();
+            return;
+        }
+        let mut stdout = stdout_opt.unwrap();
+        
+        if let Some(mut stderr) = child.stderr.take() {
+            std::thread::spawn(move || {
+                let mut data = [0u8; 1024];
+                while let Ok(len) = stderr.read(&mut data) {
+                    if len == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+        
+        let (tx, rx) = mpsc::channel();
+        let signal = self._signal;
+        std::thread::spawn(move || {
+            loop {
+                let mut data = vec![0; 4096];
+                let n_bytes_read = stdout.read(&mut data).unwrap_or(0);
+                data.truncate(n_bytes_read);
+                let _ = tx.send(data);
+                Cx::send_signal(signal, SIGNAL_DEBUGGER_OUTPUT);
+                if n_bytes_read == 0 {
+                    return
+                }
+            }
+        });
+        self._rx = Some(rx);
+        self._stdin = stdin;
+        self._child = Some(child);
+        self._abs_workdir = if let Ok(abs_workdir) = std::fs::canonicalize(&self.workdir) {
+            abs_workdir.to_string_lossy().replace("\\", "/")
+        }
+        else {
+            self.workdir.clone()
+        };
+        
+        // async mode lets us interrupt a running program
+        self.send_command(DebugRequest::Other, "-gdb-set mi-async on");
+        let mut breakpoints = Vec::new();
+        for (path, text_buffer) in &text_buffers.storage {
+            for row in &text_buffer.debug.breakpoints {
+                breakpoints.push((path.clone(), *row));
+            }
+        }
+        for (path, row) in breakpoints {
+            self.insert_breakpoint(&path, row);
+        }
+        self.send_command(DebugRequest::Other, "-exec-run");
+        self.state = DebuggerState::Running;
+    }
+    
+    pub fn stop(&mut self, cx: &mut Cx, text_buffers: &mut TextBuffers) {
+        if let Some(child) = &mut self._child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self._child = None;
+        self._stdin = None;
+        self._rx = None;
+        self._line.truncate(0);
+        self._requests.clear();
+        self._breakpoints.truncate(0);
+        self.frames.truncate(0);
+        self.variables.truncate(0);
+        self.state = DebuggerState::NotRunning;
+        self.set_exec_location(cx, text_buffers, None);
+    }
+    
+    pub fn command(&mut self, cx: &mut Cx, command: DebugCommand, text_buffers: &mut TextBuffers) {
+        if !self.is_running() {
+            return
+        }
+        let stopped = self.state == DebuggerState::Stopped;
+        match command {
+            DebugCommand::Start => (),
+            DebugCommand::Continue => if stopped {
+                self.send_command(DebugRequest::Other, "-exec-continue");
+            },
+            DebugCommand::Pause => if self.state == DebuggerState::Running {
+                self.send_command(DebugRequest::Other, "-exec-interrupt");
+            },
+            DebugCommand::StepOver => if stopped {
+                self.send_command(DebugRequest::Other, "-exec-next");
+            },
+            DebugCommand::StepInto => if stopped {
+                self.send_command(DebugRequest::Other, "-exec-step");
+            },
+            DebugCommand::StepOut => if stopped {
+                self.send_command(DebugRequest::Other, "-exec-finish");
+            },
+            DebugCommand::Stop => {
+                self.stop(cx, text_buffers);
+            }
+        }
+    }
+    
+    // makes a frame of the call stack current, returns where its code is
+    pub fn select_frame(&mut self, cx: &mut Cx, index: usize, text_buffers: &mut TextBuffers) -> Option<(String, usize)> {
+        if self.state != DebuggerState::Stopped || index >= self.frames.len() {
+            return None
+        }
+        self.selected_frame = index;
+        let level = self.frames[index].level;
+        self.send_command(DebugRequest::Other, &format!("-stack-select-frame {}", level));
+        self.send_command(DebugRequest::Variables, "-stack-list-variables --simple-values");
+        let frame = &self.frames[index];
+        let location = frame.path.clone().map( | path | (path, frame.row));
+        self.set_exec_location(cx, text_buffers, location.clone());
+        location
+    }
+    
+    // follows a breakpoint toggle in an editor while gdb runs
+    pub fn update_breakpoint(&mut self, path: &str, row: usize, enabled: bool) {
+        if !self.is_running() {
+            return
+        }
+        if enabled {
+            self.insert_breakpoint(path, row);
+        }
+        else if let Some(index) = self._breakpoints.iter().position( | bp | bp.path == path && bp.row == row) {
+            let bp = self._breakpoints.remove(index);
+            self.send_command(DebugRequest::Other, &format!("-break-delete {}", bp.number));
+        }
+    }
+    
+    fn insert_breakpoint(&mut self, path: &str, row: usize) {
+        // -f keeps it pending until the code is loaded
+        let location = format!("{}/{}:{}", self._abs_workdir, path, row + 1);
+        self.send_command(DebugRequest::BreakInsert {path: path.to_string(), row: row}, &format!("-break-insert -f {}", mi_quote(&location)));
+    }
+    
+    fn send_command(&mut self, request: DebugRequest, command: &str) {
+        let token = self._next_token;
+        self._next_token += 1;
+        self._requests.insert(token, request);
+        if let Some(stdin) = &mut self._stdin {
+            let line = format!("{}{}\n", token, command);
+            if let Err(err) = stdin.write_all(line.as_bytes()).and_then( | _ | stdin.flush()) {
+                // shown next to the state in the debug panel
+                self.status = format!("cannot write to {}: {}", self.gdb, err);
+                self.push_log(format!("{} failed: {}", command, err));
+                Cx::send_signal(self._signal, 0);
+            }
+        }
+    }
+    
+    pub fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > self.max_log {
+            let excess = self.log.len() - self.max_log;
+            self.log.drain(0..excess);
+        }
+    }
+    
+    fn set_exec_location(&mut self, cx: &mut Cx, text_buffers: &mut TextBuffers, location: Option<(String, usize)>) {
+        if let Some(path) = self._exec_path.take() {
+            if let Some(text_buffer) = text_buffers.storage.get_mut(&path) {
+                text_buffer.debug.exec_row = None;
+                cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_DEBUG_UPDATE);
+            }
+        }
+        if let Some((path, row)) = location {
+            let text_buffer = text_buffers.from_path(cx, &path);
+            text_buffer.debug.exec_row = Some(row);
+            cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_DEBUG_UPDATE);
+            self._exec_path = Some(path);
+        }
+    }
+    
+    // the path of a source file relative to our workdir, if it is in there
+    fn frame_from_mi(&self, frame: &MiValue) -> DebugFrame {
+        let fullname = frame.get_str("fullname").replace("\\", "/");
+        let prefix = format!("{}/", self._abs_workdir);
+        DebugFrame {
+            level: frame.get_str("level").parse().unwrap_or(0),
+            func: frame.get_str("func").to_string(),
+            file: frame.get_str("file").to_string(),
+            path: if fullname.starts_with(&prefix) {Some(fullname[prefix.len()..].to_string())}else {None},
+            row: frame.get_str("line").parse::<usize>().unwrap_or(1).max(1) - 1,
+            addr: frame.get_str("addr").to_string()
+        }
+    }
+    
+    pub fn handle_debugger(&mut self, cx: &mut Cx, event: &mut Event, text_buffers: &mut TextBuffers) -> Vec<DebuggerEvent> {
+        let mut events = Vec::new();
+        match event {
+            Event::KeyDown(ke) => {
+                let command = match ke.key_code {
+                    KeyCode::F5 => Some(if ke.modifiers.shift {
+                        DebugCommand::Stop
+                    }
+                    else if self.is_running() {
+                        DebugCommand::Continue
+                    }
+                    else {
+                        DebugCommand::Start
+                    }),
+                    KeyCode::F6 => Some(DebugCommand::StepOver),
+                    KeyCode::F7 => Some(if ke.modifiers.shift {DebugCommand::StepOut}else {DebugCommand::StepInto}),
+                    _ => None
+                };
+                match command {
+                    Some(DebugCommand::Start) => events.push(DebuggerEvent::StartRequest),
+                    Some(command) => self.command(cx, command, text_buffers),
+                    None => ()
+                }
+            },
+            Event::Signal(se) => if self._signal.is_signal(se) {
+                let mut datas = Vec::new();
+                if let Some(rx) = &self._rx {
+                    while let Ok(data) = rx.try_recv() {
+                        datas.push(data);
+                    }
+                }
+                for data in datas {
+                    if data.len() == 0 { // gdb is gone
+                        let exited = self.state == DebuggerState::Exited;
+                        self.stop(cx, text_buffers);
+                        if exited {
+                            self.state = DebuggerState::Exited;
+                        }
+                        break;
+                    }
+                    for ch in data {
+                        if ch != '\n' as u8 {
+                            self._line.push(ch as char);
+                            continue;
+                        }
+                        let line = std::mem::replace(&mut self._line, String::new());
+                        self.process_line(cx, &line, text_buffers, &mut events);
+                    }
+                }
+            },
+            _ => ()
+        }
+        events
+    }
+    
+    fn process_line(&mut self, cx: &mut Cx, line: &str, text_buffers: &mut TextBuffers, events: &mut Vec<DebuggerEvent>) {
+        let record = if let Some(record) = parse_mi_line(line) {record}else {
+            // the program shares the output of gdb
+            if line.trim().len() > 0 {
+                self.push_log(line.trim_end().to_string());
+            }
+            return
+        };
+        match record {
+            MiRecord::Result {token, class, results} => {
+                let request = token.and_then( | token | self._requests.remove(&token)).unwrap_or(DebugRequest::Other);
+                if class == "error" {
+                    self.push_log(format!("error: {}", results.get_str("msg")));
+                    return
+                }
+                match request {
+                    DebugRequest::BreakInsert {path, row} => {
+                        if let Some(bkpt) = results.get("bkpt") {
+                            self._breakpoints.push(DebugBreakpoint {path: path, row: row, number: bkpt.get_str("number").to_string()});
+                        }
+                    },
+                    DebugRequest::StackFrames => {
+                        let frames = results.get("stack").map_or(Vec::new(), | stack | {
+                            stack.as_list().iter().map( | frame | self.frame_from_mi(frame)).collect()
+                        });
+                        self.frames = frames;
+                    },
+                    DebugRequest::Variables => {
+                        self.variables = results.get("variables").map_or(Vec::new(), | variables | {
+                            variables.as_list().iter().map( | var | DebugVariable {
+                                name: var.get_str("name").to_string(),
+                                type_name: var.get_str("type").to_string(),
+                                // aggregates have no simple value
+                                value: var.get("value").map_or("{...}".to_string(), | value | value.as_str().to_string())
+                            }).collect()
+                        });
+                    },
+                    DebugRequest::Other => ()
+                }
+            },
+            MiRecord::Async {kind: '*', class, results, ..} => {
+                if class == "running" {
+                    self.state = DebuggerState::Running;
+                    self.frames.truncate(0);
+                    self.variables.truncate(0);
+                    self.set_exec_location(cx, text_buffers, None);
+                }
+                else if class == "stopped" {
+                    let reason = results.get_str("reason").to_string();
+                    if reason.starts_with("exited") {
+                        let code = results.get_str("exit-code");
+                        self.push_log(format!("program exited{}", if code.len() > 0 {format!(" with code {}", code)}else {String::new()}));
+                        self.state = DebuggerState::Exited;
+                        self.set_exec_location(cx, text_buffers, None);
+                        self.send_command(DebugRequest::Other, "-gdb-exit");
+                        return
+                    }
+                    self.state = DebuggerState::Stopped;
+                    self.selected_frame = 0;
+                    if let Some(frame) = results.get("frame") {
+                        let frame = self.frame_from_mi(frame);
+                        if let Some(path) = &frame.path {
+                            self.set_exec_location(cx, text_buffers, Some((path.clone(), frame.row)));
+                            events.push(DebuggerEvent::Stopped {path: path.clone(), row: frame.row});
+                        }
+                        else {
+                            self.set_exec_location(cx, text_buffers, None);
+                        }
+                    }
+                    self.send_command(DebugRequest::StackFrames, "-stack-list-frames");
+                    self.send_command(DebugRequest::Variables, "-stack-list-variables --simple-values");
+                }
+            },
+            MiRecord::Stream {kind, text} => {
+                if kind != '&' || text.starts_with("warning") {
+                    for line in text.lines() {
+                        self.push_log(line.to_string());
+                    }
+                }
+            },
+            _ => ()
+        }
+    }
+}
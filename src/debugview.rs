@@ -0,0 +1,321 @@
+use render::*;
+use widget::*;
+use crate::debugger::*;
+
+// the debug controls with the call stack of the stopped program and the gdb log below it
+#[derive(Clone)]
+pub struct CallStackView {
+    pub view: View<ScrollBar>,
+    pub item_bg: Quad,
+    pub button_bg: Quad,
+    pub text: Text,
+    pub row_height: f32,
+    pub colors: DebugViewColors,
+    pub _button_areas: Vec<(Area, DebugCommand)>,
+    pub _row_areas: Vec<(Area, usize)>,
+    pub _hover_button: Option<DebugCommand>,
+    pub _hover_row: Option<usize>
+}
+
+// the variables of the selected frame
+#[derive(Clone)]
+pub struct VariablesView {
+    pub view: View<ScrollBar>,
+    pub item_bg: Quad,
+    pub text: Text,
+    pub row_height: f32,
+    pub colors: DebugViewColors
+}
+
+#[derive(Clone)]
+pub struct DebugViewColors {
+    pub bg_even: Color,
+    pub bg_odd: Color,
+    pub bg_hover: Color,
+    pub bg_selected: Color,
+    pub button: Color,
+    pub button_hover: Color,
+    pub name: Color,
+    pub value: Color,
+    pub hint: Color,
+    pub error: Color
+}
+
+#[derive(Clone, PartialEq)]
+pub enum CallStackEvent {
+    None,
+    Command(DebugCommand),
+    SelectFrame {index: usize}
+}
+
+impl Style for DebugViewColors {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            bg_even: cx.color("bg_selected"),
+            bg_odd: cx.color("bg_odd"),
+            bg_hover: cx.color("bg_selected_over"),
+            bg_selected: color256(38, 79, 120),
+            button: color256(60, 60, 60),
+            button_hover: color256(80, 80, 80),
+            name: color256(156, 220, 254),
+            value: color256(212, 212, 212),
+            hint: color("#777"),
+            error: color("#e66")
+        }
+    }
+}
+
+fn debug_list_view(cx: &mut Cx) -> View<ScrollBar> {
+    View {
+        scroll_h: Some(ScrollBar::style(cx)),
+        scroll_v: Some(ScrollBar {
+            smoothing: Some(0.15),
+            ..Style::style(cx)
+        }),
+        ..Style::style(cx)
+    }
+}
+
+impl Style for CallStackView {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: debug_list_view(cx),
+            item_bg: Quad::style(cx),
+            button_bg: Quad::style(cx),
+            text: Text {
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            row_height: 20.,
+            colors: DebugViewColors::style(cx),
+            _button_areas: Vec::new(),
+            _row_areas: Vec::new(),
+            _hover_button: None,
+            _hover_row: None
+        }
+    }
+}
+
+impl Style for VariablesView {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: debug_list_view(cx),
+            item_bg: Quad::style(cx),
+            text: Text {
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            row_height: 20.,
+            colors: DebugViewColors::style(cx)
+        }
+    }
+}
+
+// alternating rows, starting from where the turtle is, down to the bottom of the view
+fn fill_debug_rows(cx: &mut Cx, item_bg: &mut Quad, colors: &DebugViewColors, row_height: f32, mut row: usize) {
+    let view_total = cx.get_turtle_bounds();
+    let rect_now = cx.get_turtle_rect();
+    let mut y = view_total.y;
+    while y < rect_now.h {
+        item_bg.color = if row & 1 == 0 {colors.bg_even}else {colors.bg_odd};
+        item_bg.draw_quad_walk(cx, Bounds::Fill, Bounds::Fix((rect_now.h - y).min(row_height)), Margin::zero());
+        cx.turtle_new_line();
+        y += row_height;
+        row += 1;
+    }
+}
+
+impl CallStackView {
+    pub fn handle_call_stack_view(&mut self, cx: &mut Cx, event: &mut Event, debugger: &Debugger) -> CallStackEvent {
+        if debugger.is_debugger_event(event) {
+            self.view.redraw_view_area(cx);
+        }
+        self.view.handle_scroll_bars(cx, event);
+        
+        let mut hover_button = self._hover_button;
+        for (area, command) in &self._button_areas {
+            match event.hits(cx, *area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    return CallStackEvent::Command(*command)
+                },
+                Event::FingerHover(fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    if let HoverState::Out = fe.hover_state {
+                        if hover_button == Some(*command) {
+                            hover_button = None;
+                        }
+                    }
+                    else {
+                        hover_button = Some(*command);
+                    }
+                },
+                _ => ()
+            }
+        }
+        let mut hover_row = self._hover_row;
+        for (area, index) in &self._row_areas {
+            match event.hits(cx, *area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    return CallStackEvent::SelectFrame {index: *index}
+                },
+                Event::FingerHover(fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    if let HoverState::Out = fe.hover_state {
+                        if hover_row == Some(*index) {
+                            hover_row = None;
+                        }
+                    }
+                    else {
+                        hover_row = Some(*index);
+                    }
+                },
+                _ => ()
+            }
+        }
+        if hover_button != self._hover_button || hover_row != self._hover_row {
+            self._hover_button = hover_button;
+            self._hover_row = hover_row;
+            self.view.redraw_view_area(cx);
+        }
+        CallStackEvent::None
+    }
+    
+    pub fn draw_call_stack_view(&mut self, cx: &mut Cx, debugger: &Debugger) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        self._button_areas.truncate(0);
+        self._row_areas.truncate(0);
+        
+        // the toolbar only shows what can be done right now
+        let commands: &[DebugCommand] = match debugger.state {
+            DebuggerState::NotRunning | DebuggerState::Exited => &[DebugCommand::Start],
+            DebuggerState::Running => &[DebugCommand::Pause, DebugCommand::Stop],
+            DebuggerState::Stopped => &[
+                DebugCommand::Continue,
+                DebugCommand::StepOver,
+                DebugCommand::StepInto,
+                DebugCommand::StepOut,
+                DebugCommand::Stop
+            ]
+        };
+        self.item_bg.color = self.colors.bg_odd;
+        let bar_inst = self.item_bg.begin_quad(cx, &Layout {
+            width: Bounds::Fill,
+            height: Bounds::Fix(self.row_height + 4.),
+            padding: Padding {l: 2., t: 2., b: 2., r: 2.},
+            ..Default::default()
+        });
+        for command in commands {
+            self.button_bg.color = if self._hover_button == Some(*command) {self.colors.button_hover}else {self.colors.button};
+            let button_inst = self.button_bg.begin_quad(cx, &Layout {
+                width: Bounds::Compute,
+                height: Bounds::Fill,
+                margin: Margin {l: 0., t: 0., r: 2., b: 0.},
+                padding: Padding {l: 6., t: 3., b: 2., r: 6.},
+                ..Default::default()
+            });
+            self.text.color = self.colors.value;
+            self.text.draw_text(cx, command.label());
+            let area = self.button_bg.end_quad(cx, &button_inst);
+            self._button_areas.push((area, *command));
+        }
+        self.text.color = self.colors.hint;
+        self.text.draw_text(cx, match debugger.state {
+            DebuggerState::NotRunning => " not running",
+            DebuggerState::Running => " running",
+            DebuggerState::Stopped => " paused",
+            DebuggerState::Exited => " exited"
+        });
+        if debugger.status.len() > 0 {
+            self.text.color = self.colors.error;
+            self.text.draw_text(cx, &format!(" {}", debugger.status));
+        }
+        self.item_bg.end_quad(cx, &bar_inst);
+        cx.turtle_new_line();
+        
+        for (index, frame) in debugger.frames.iter().enumerate() {
+            self.item_bg.color = if self._hover_row == Some(index) {
+                self.colors.bg_hover
+            }
+            else if debugger.selected_frame == index {
+                self.colors.bg_selected
+            }
+            else if index & 1 == 0 {
+                self.colors.bg_even
+            }
+            else {
+                self.colors.bg_odd
+            };
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Fix(self.row_height),
+                padding: Padding {l: 4., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.text.color = self.colors.value;
+            self.text.draw_text(cx, if frame.func.len() > 0 {&frame.func}else {&frame.addr});
+            self.text.color = self.colors.hint;
+            if frame.file.len() > 0 {
+                self.text.draw_text(cx, &format!(" {}:{}", frame.file, frame.row + 1));
+            }
+            let area = self.item_bg.end_quad(cx, &bg_inst);
+            self._row_areas.push((area, index));
+            cx.turtle_new_line();
+        }
+        
+        // what gdb and the program printed
+        for (index, line) in debugger.log.iter().enumerate() {
+            let row = debugger.frames.len() + index;
+            self.item_bg.color = if row & 1 == 0 {self.colors.bg_even}else {self.colors.bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Fix(self.row_height),
+                padding: Padding {l: 4., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, line);
+            self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+        }
+        
+        fill_debug_rows(cx, &mut self.item_bg, &self.colors, self.row_height, debugger.frames.len() + debugger.log.len());
+        self.view.end_view(cx);
+    }
+}
+
+impl VariablesView {
+    pub fn handle_variables_view(&mut self, cx: &mut Cx, event: &mut Event, debugger: &Debugger) {
+        if debugger.is_debugger_event(event) {
+            self.view.redraw_view_area(cx);
+        }
+        self.view.handle_scroll_bars(cx, event);
+    }
+    
+    pub fn draw_variables_view(&mut self, cx: &mut Cx, debugger: &Debugger) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        for (row, var) in debugger.variables.iter().enumerate() {
+            self.item_bg.color = if row & 1 == 0 {self.colors.bg_even}else {self.colors.bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Fix(self.row_height),
+                padding: Padding {l: 4., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.text.color = self.colors.name;
+            self.text.draw_text(cx, &var.name);
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, &format!(": {} = ", var.type_name));
+            self.text.color = self.colors.value;
+            // keep long values on one row
+            self.text.draw_text(cx, &var.value.replace('\n', " "));
+            self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+        }
+        fill_debug_rows(cx, &mut self.item_bg, &self.colors, self.row_height, debugger.variables.len());
+        self.view.end_view(cx);
+    }
+}
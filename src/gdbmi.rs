@@ -0,0 +1,284 @@
+// the gdb machine interface output syntax
+// https://sourceware.org/gdb/current/onlinedocs/gdb/GDB_002fMI-Output-Syntax.html
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MiValue {
+    Const(String),
+    Tuple(Vec<(String, MiValue)>),
+    // the names in a list of results are dropped, they repeat anyway
+    List(Vec<MiValue>)
+}
+
+impl MiValue {
+    pub fn get(&self, name: &str) -> Option<&MiValue> {
+        match self {
+            MiValue::Tuple(results) => results.iter().find( | (n, _) | n == name).map( | (_, v) | v),
+            _ => None
+        }
+    }
+    
+    pub fn as_str(&self) -> &str {
+        match self {
+            MiValue::Const(s) => s,
+            _ => ""
+        }
+    }
+    
+    pub fn get_str(&self, name: &str) -> &str {
+        self.get(name).map_or("", | v | v.as_str())
+    }
+    
+    pub fn as_list(&self) -> &[MiValue] {
+        match self {
+            MiValue::List(items) => items,
+            _ => &[]
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MiRecord {
+    // ^done, ^running, ^connected, ^error, ^exit
+    Result {token: Option<u64>, class: String, results: MiValue},
+    // * exec, + status and = notify records
+    Async {token: Option<u64>, kind: char, class: String, results: MiValue},
+    // ~ console, @ target and & log output
+    Stream {kind: char, text: String},
+    Prompt
+}
+
+pub fn parse_mi_line(line: &str) -> Option<MiRecord> {
+    let line = line.trim_end();
+    if line == "(gdb)" {
+        return Some(MiRecord::Prompt)
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut pos = 0;
+    let mut token = None;
+    while pos < chars.len() && chars[pos].is_ascii_digit() {
+        pos += 1;
+    }
+    if pos > 0 {
+        token = line[0..pos].parse::<u64>().ok();
+    }
+    let kind = *chars.get(pos)?;
+    pos += 1;
+    match kind {
+        '~' | '@' | '&' => {
+            let text = parse_c_string(&chars, &mut pos)?;
+            Some(MiRecord::Stream {kind: kind, text: text})
+        },
+        '^' | '*' | '+' | '=' => {
+            let start = pos;
+            while pos < chars.len() && chars[pos] != ',' {
+                pos += 1;
+            }
+            let class: String = chars[start..pos].iter().collect();
+            let mut results = Vec::new();
+            while pos < chars.len() && chars[pos] == ',' {
+                pos += 1;
+                results.push(parse_result(&chars, &mut pos)?);
+            }
+            if kind == '^' {
+                Some(MiRecord::Result {token: token, class: class, results: MiValue::Tuple(results)})
+            }
+            else {
+                Some(MiRecord::Async {token: token, kind: kind, class: class, results: MiValue::Tuple(results)})
+            }
+        },
+        _ => None
+    }
+}
+
+fn parse_result(chars: &[char], pos: &mut usize) -> Option<(String, MiValue)> {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos] != '=' {
+        *pos += 1;
+    }
+    let name: String = chars[start..*pos].iter().collect();
+    *pos += 1;
+    let value = parse_value(chars, pos)?;
+    Some((name, value))
+}
+
+fn parse_value(chars: &[char], pos: &mut usize) -> Option<MiValue> {
+    match *chars.get(*pos)? {
+        '"' => Some(MiValue::Const(parse_c_string(chars, pos)?)),
+        '{' => {
+            *pos += 1;
+            let mut results = Vec::new();
+            while *chars.get(*pos)? != '}' {
+                results.push(parse_result(chars, pos)?);
+                if chars.get(*pos) == Some(&',') {
+                    *pos += 1;
+                }
+            }
+            *pos += 1;
+            Some(MiValue::Tuple(results))
+        },
+        '[' => {
+            *pos += 1;
+            let mut items = Vec::new();
+            while *chars.get(*pos)? != ']' {
+                // either values or name=value results
+                let ch = chars[*pos];
+                if ch == '"' || ch == '{' || ch == '[' {
+                    items.push(parse_value(chars, pos)?);
+                }
+                else {
+                    items.push(parse_result(chars, pos)?.1);
+                }
+                if chars.get(*pos) == Some(&',') {
+                    *pos += 1;
+                }
+            }
+            *pos += 1;
+            Some(MiValue::List(items))
+        },
+        _ => None
+    }
+}
+
+fn parse_c_string(chars: &[char], pos: &mut usize) -> Option<String> {
+    if chars.get(*pos) != Some(&'"') {
+        return None
+    }
+    *pos += 1;
+    let mut out = String::new();
+    loop {
+        let ch = *chars.get(*pos)?;
+        *pos += 1;
+        match ch {
+            '"' => return Some(out),
+            '\\' => {
+                let esc = *chars.get(*pos)?;
+                *pos += 1;
+                out.push(match esc {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'e' => '\x1b',
+                    _ => esc
+                });
+            },
+            _ => out.push(ch)
+        }
+    }
+}
+
+// quotes a string for use as an mi command argument
+pub fn mi_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parse_stopped_at_breakpoint() {
+        let line = r#"*stopped,reason="breakpoint-hit",disp="keep",bkptno="1",frame={addr="0x0000555555559a2e",func="hello::main",args=[],file="src/main.rs",fullname="/home/user/hello/src/main.rs",line="3",arch="i386:x86-64"},thread-id="1",stopped-threads="all",core="2""#;
+        if let Some(MiRecord::Async {token, kind, class, results}) = parse_mi_line(line) {
+            assert_eq!(token, None);
+            assert_eq!(kind, '*');
+            assert_eq!(class, "stopped");
+            assert_eq!(results.get_str("reason"), "breakpoint-hit");
+            let frame = results.get("frame").unwrap();
+            assert_eq!(frame.get_str("func"), "hello::main");
+            assert_eq!(frame.get_str("fullname"), "/home/user/hello/src/main.rs");
+            assert_eq!(frame.get_str("line"), "3");
+            assert_eq!(frame.get("args"), Some(&MiValue::List(Vec::new())));
+            assert_eq!(results.get_str("core"), "2");
+        }
+        else {
+            panic!("not an async record");
+        }
+    }
+    
+    #[test]
+    fn parse_break_insert_result() {
+        let line = r#"1^done,bkpt={number="1",type="breakpoint",disp="keep",enabled="y",addr="0x0000000000007a2e",func="hello::main",file="src/main.rs",fullname="/home/user/hello/src/main.rs",line="3",thread-groups=["i1"],times="0",original-location="/home/user/hello/src/main.rs:3"}"#;
+        if let Some(MiRecord::Result {token, class, results}) = parse_mi_line(line) {
+            assert_eq!(token, Some(1));
+            assert_eq!(class, "done");
+            let bkpt = results.get("bkpt").unwrap();
+            assert_eq!(bkpt.get_str("number"), "1");
+            assert_eq!(bkpt.get("thread-groups").unwrap().as_list(), &[MiValue::Const("i1".to_string())]);
+            assert_eq!(bkpt.get_str("original-location"), "/home/user/hello/src/main.rs:3");
+        }
+        else {
+            panic!("not a result record");
+        }
+    }
+    
+    #[test]
+    fn parse_stack_list_frames() {
+        // the frames are name=value results inside a list
+        let line = r#"12^done,stack=[frame={level="0",addr="0x0000555555559a2e",func="hello::add",file="src/main.rs",fullname="/home/user/hello/src/main.rs",line="8",arch="i386:x86-64"},frame={level="1",addr="0x0000555555559a80",func="hello::main",file="src/main.rs",fullname="/home/user/hello/src/main.rs",line="3",arch="i386:x86-64"}]"#;
+        if let Some(MiRecord::Result {token, results, ..}) = parse_mi_line(line) {
+            assert_eq!(token, Some(12));
+            let frames = results.get("stack").unwrap().as_list();
+            assert_eq!(frames.len(), 2);
+            assert_eq!(frames[0].get_str("func"), "hello::add");
+            assert_eq!(frames[1].get_str("level"), "1");
+            assert_eq!(frames[1].get_str("line"), "3");
+        }
+        else {
+            panic!("not a result record");
+        }
+    }
+    
+    #[test]
+    fn parse_lldb_mi_variables() {
+        let line = r#"4^done,variables=[{name="x",type="i32",value="5"},{name="s",type="&str",value="\"hi\""}]"#;
+        if let Some(MiRecord::Result {results, ..}) = parse_mi_line(line) {
+            let vars = results.get("variables").unwrap().as_list();
+            assert_eq!(vars.len(), 2);
+            assert_eq!(vars[0].get_str("name"), "x");
+            assert_eq!(vars[0].get_str("type"), "i32");
+            assert_eq!(vars[1].get_str("value"), "\"hi\"");
+        }
+        else {
+            panic!("not a result record");
+        }
+    }
+    
+    #[test]
+    fn parse_streams_and_prompt() {
+        assert_eq!(parse_mi_line(r#"~"Breakpoint 1, hello::main () at src/main.rs:3\n""#), Some(MiRecord::Stream {
+            kind: '~',
+            text: "Breakpoint 1, hello::main () at src/main.rs:3\n".to_string()
+        }));
+        assert_eq!(parse_mi_line(r#"&"warning: Error disabling address space randomization: Operation not permitted\n""#), Some(MiRecord::Stream {
+            kind: '&',
+            text: "warning: Error disabling address space randomization: Operation not permitted\n".to_string()
+        }));
+        assert_eq!(parse_mi_line("(gdb) \n"), Some(MiRecord::Prompt));
+    }
+    
+    #[test]
+    fn parse_error_and_notify() {
+        let line = r#"^error,msg="No symbol table is loaded.  Use the \"file\" command.""#;
+        if let Some(MiRecord::Result {token, class, results}) = parse_mi_line(line) {
+            assert_eq!(token, None);
+            assert_eq!(class, "error");
+            assert_eq!(results.get_str("msg"), "No symbol table is loaded.  Use the \"file\" command.");
+        }
+        else {
+            panic!("not a result record");
+        }
+        assert_eq!(parse_mi_line(r#"=thread-group-added,id="i1""#), Some(MiRecord::Async {
+            token: None,
+            kind: '=',
+            class: "thread-group-added".to_string(),
+            results: MiValue::Tuple(vec![("id".to_string(), MiValue::Const("i1".to_string()))])
+        }));
+        assert_eq!(parse_mi_line("2^running"), Some(MiRecord::Result {token: Some(2), class: "running".to_string(), results: MiValue::Tuple(Vec::new())}));
+    }
+    
+    #[test]
+    fn quote_arguments() {
+        assert_eq!(mi_quote(r#"C:\src\main.rs"#), r#""C:\\src\\main.rs""#);
+        assert_eq!(parse_mi_line(&format!("~{}", mi_quote("say \"hi\""))), Some(MiRecord::Stream {kind: '~', text: "say \"hi\"".to_string()}));
+    }
+}
@@ -0,0 +1,756 @@
+use render::*;
+use editor::*;
+
+use std::io::{Read, Write, BufRead};
+use std::sync::mpsc;
+use std::process::Child;
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+// talks the language server protocol to a server process over stdio
+pub struct LspClient {
+    pub _signal: Signal,
+    pub _child: Option<Child>,
+    pub _stdin: Option<Box<dyn Write>>,
+    pub _rx: Option<mpsc::Receiver<Vec<u8>>>,
+    pub _cmd: String,
+    pub _root_path: String,
+    pub _root_uri: String,
+    pub _initialized: bool,
+    pub _queue: Vec<String>,
+    pub _next_id: u64,
+    pub _requests: HashMap<u64, LspRequest>,
+    // path -> (document version, mutation id we sent)
+    pub _open_files: HashMap<String, (u64, u64)>,
+    // the last problem with the server, shown in the status bar
+    pub status: String
+}
+
+const SIGNAL_LSP_MESSAGE: usize = 1;
+const SIGNAL_LSP_STATUS: usize = 2;
+
+#[derive(Clone)]
+pub enum LspRequest {
+    Initialize,
+    Completion {window_index: usize, editor_id: u64, offset: usize},
+    Hover {window_index: usize, editor_id: u64, offset: usize},
+    Definition
+}
+
+#[derive(Clone)]
+pub enum LspClientEvent {
+    None,
+    Completion {window_index: usize, editor_id: u64, offset: usize, items: Vec<CodeCompletion>},
+    Hover {window_index: usize, editor_id: u64, offset: usize, text: String},
+    Definition {path: String, pos: TextPos},
+    Status
+}
+
+impl LspClient {
+    pub fn new(cx: &mut Cx) -> Self {
+        Self {
+            _signal: cx.new_signal(),
+            _child: None,
+            _stdin: None,
+            _rx: None,
+            _cmd: String::new(),
+            _root_path: String::new(),
+            _root_uri: String::new(),
+            _initialized: false,
+            _queue: Vec::new(),
+            _next_id: 1,
+            _requests: HashMap::new(),
+            _open_files: HashMap::new(),
+            status: String::new()
+        }
+    }
+    
+    pub fn is_running(&self) -> bool {
+        self._child.is_some()
+    }
+    
+    pub fn start(&mut self, cmd: &str, args: &[&str], root_path: &str) {
+        self.stop();
+        
+        self._cmd = cmd.to_string();
+        let mut child = match spawn_process_command(cmd, args, root_path, true) {
+            Ok(child) => child,
+            Err(err) => {
+                self.set_status(format!("cannot start {}: {}", cmd, err));
+                return
+            }
+        };
+        
+        let stdin = child.stdin.take();
+        let stdout_opt = child.stdout.take();
+        
+        if stdin.is_none() || stdout_opt.is_none() {
+            let _ = child.kill();
+            let _ = child.wait();
+            self.set_status(format!("cannot start {}: no stdio", cmd));
+            return;
+        }
+        
+        let stdout = stdout_opt.unwrap();
+        self.set_status(String::new());
+        
+        // drain stderr so a chatty server never blocks on a full pipe
+        if let Some(mut stderr) = child.stderr.take() {
+            std::thread::spawn(move || {
+                let mut data = [0u8; 1024];
+                while let Ok(len) = stderr.read(&mut data) {
+                    if len == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+        
+        let (tx, rx) = mpsc::channel();
+        let signal = self._signal;
+        std::thread::spawn(move || {
+            let mut reader = std::io::BufReader::new(stdout);
+            while let Some(data) = read_lsp_message(&mut reader) {
+                let _ = tx.send(data);
+                Cx::send_signal(signal, SIGNAL_LSP_MESSAGE);
+            }
+            // an empty message tells the main thread the server went away
+            let _ = tx.send(Vec::new());
+            Cx::send_signal(signal, SIGNAL_LSP_MESSAGE);
+        });
+        self._rx = Some(rx);
+        self._stdin = stdin.map( | stdin | Box::new(stdin) as Box<dyn Write>);
+        self._child = Some(child);
+        
+        self._root_path = root_path.to_string();
+        let abs_root = if let Ok(abs_root) = std::fs::canonicalize(root_path) {
+            abs_root.to_string_lossy().replace("\\", "/")
+        }
+        else {
+            root_path.to_string()
+        };
+        self._root_uri = format!("file://{}{}", if abs_root.starts_with("/") {""}else {"/"}, percent_encode(abs_root.trim_end_matches('/')));
+        
+        let root_uri = self._root_uri.clone();
+        self.send_request(LspRequest::Initialize, "initialize", json!({
+            "processId": std::process::id(),
+            "rootUri": root_uri,
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": {"didSave": false},
+                    "completion": {"completionItem": {"snippetSupport": false}},
+                    "hover": {"contentFormat": ["plaintext", "markdown"]},
+                    "definition": {},
+                    "publishDiagnostics": {}
+                }
+            }
+        }));
+    }
+    
+    pub fn stop(&mut self) {
+        // reap the server so it doesn't linger as a zombie
+        if let Some(mut child) = self._child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self._stdin = None;
+        self._rx = None;
+        self._initialized = false;
+        self._queue.truncate(0);
+        self._requests.clear();
+        self._open_files.clear();
+    }
+    
+    // the status can change outside of an event, so the redraw goes through our signal
+    fn set_status(&mut self, status: String) {
+        self.status = status;
+        Cx::send_signal(self._signal, SIGNAL_LSP_STATUS);
+    }
+    
+    pub fn path_to_uri(&self, path: &str) -> String {
+        format!("{}/{}", self._root_uri, percent_encode(path))
+    }
+    
+    pub fn uri_to_path(&self, uri: &str) -> Option<String> {
+        let uri = percent_decode(uri);
+        let prefix = format!("{}/", percent_decode(&self._root_uri));
+        if uri.starts_with(&prefix) {
+            return Some(uri[prefix.len()..].to_string())
+        }
+        None
+    }
+    
+    fn write_message(&mut self, body: &str) {
+        let failed = if let Some(stdin) = &mut self._stdin {
+            stdin.write_all(frame_lsp_message(body).as_bytes()).is_err() || stdin.flush().is_err()
+        }
+        else {
+            false
+        };
+        if failed {
+            let status = format!("cannot write to {}", self._cmd);
+            self.set_status(status);
+        }
+    }
+    
+    fn send_request(&mut self, request: LspRequest, method: &str, params: Value) {
+        let id = self._next_id;
+        self._next_id += 1;
+        self._requests.insert(id, request);
+        let body = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}).to_string();
+        if !self._initialized && method != "initialize" { // hold on to it until the server is ready
+            self._queue.push(body);
+            return
+        }
+        self.write_message(&body);
+    }
+    
+    fn send_notification(&mut self, method: &str, params: Value) {
+        let body = json!({"jsonrpc": "2.0", "method": method, "params": params}).to_string();
+        if !self._initialized && method != "initialized" {
+            self._queue.push(body);
+            return
+        }
+        self.write_message(&body);
+    }
+    
+    fn send_response(&mut self, id: Value, result: Value) {
+        let body = json!({"jsonrpc": "2.0", "id": id, "result": result}).to_string();
+        self.write_message(&body);
+    }
+    
+    fn text_document_position(&self, path: &str, offset: usize, text_buffer: &TextBuffer) -> Value {
+        let pos = text_buffer.offset_to_text_pos(offset);
+        json!({
+            "textDocument": {"uri": self.path_to_uri(path)},
+            "position": lsp_position(text_buffer, pos)
+        })
+    }
+    
+    // sends didOpen the first time we see a buffer, and the full text with didChange after edits
+    pub fn sync_text_buffer(&mut self, path: &str, text_buffer: &TextBuffer) {
+        if !self.is_running() || text_buffer.load_read_req.is_loading() {
+            return
+        }
+        let uri = self.path_to_uri(path);
+        match self._open_files.get(path).cloned() {
+            None => {
+                self._open_files.insert(path.to_string(), (1, text_buffer.mutation_id));
+                self.send_notification("textDocument/didOpen", json!({
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": if path.ends_with(".js") {"javascript"}else {"rust"},
+                        "version": 1,
+                        "text": text_buffer.get_as_string()
+                    }
+                }));
+            },
+            Some((version, mutation_id)) => if mutation_id != text_buffer.mutation_id {
+                self._open_files.insert(path.to_string(), (version + 1, text_buffer.mutation_id));
+                self.send_notification("textDocument/didChange", json!({
+                    "textDocument": {"uri": uri, "version": version + 1},
+                    "contentChanges": [{"text": text_buffer.get_as_string()}]
+                }));
+            }
+        }
+    }
+    
+    pub fn request_completion(&mut self, window_index: usize, editor_id: u64, path: &str, offset: usize, text_buffer: &TextBuffer) {
+        if !self.is_running() {
+            return
+        }
+        self.sync_text_buffer(path, text_buffer);
+        let params = self.text_document_position(path, offset, text_buffer);
+        self.send_request(LspRequest::Completion {window_index: window_index, editor_id: editor_id, offset: offset}, "textDocument/completion", params);
+    }
+    
+    pub fn request_hover(&mut self, window_index: usize, editor_id: u64, path: &str, offset: usize, text_buffer: &TextBuffer) {
+        if !self.is_running() {
+            return
+        }
+        self.sync_text_buffer(path, text_buffer);
+        let params = self.text_document_position(path, offset, text_buffer);
+        self.send_request(LspRequest::Hover {window_index: window_index, editor_id: editor_id, offset: offset}, "textDocument/hover", params);
+    }
+    
+    pub fn request_definition(&mut self, path: &str, offset: usize, text_buffer: &TextBuffer) {
+        if !self.is_running() {
+            return
+        }
+        self.sync_text_buffer(path, text_buffer);
+        let params = self.text_document_position(path, offset, text_buffer);
+        self.send_request(LspRequest::Definition, "textDocument/definition", params);
+    }
+    
+    pub fn handle_lsp_client(&mut self, cx: &mut Cx, event: &mut Event, text_buffers: &mut TextBuffers) -> Vec<LspClientEvent> {
+        let mut events = Vec::new();
+        match event {
+            Event::Signal(se) => if self._signal.is_signal(se) && se.value == SIGNAL_LSP_STATUS {
+                events.push(LspClientEvent::Status);
+            }
+            else if self._signal.is_signal(se) {
+                let mut datas = Vec::new();
+                if let Some(rx) = &self._rx {
+                    while let Ok(data) = rx.try_recv() {
+                        datas.push(data);
+                    }
+                }
+                for data in datas {
+                    if data.len() == 0 { // the server went away
+                        self.stop();
+                        let status = format!("{} terminated", self._cmd);
+                        self.set_status(status);
+                        break;
+                    }
+                    if let Ok(msg) = serde_json::from_slice::<Value>(&data) {
+                        let event = self.process_message(cx, msg, text_buffers);
+                        if let LspClientEvent::None = event {
+                            continue;
+                        }
+                        events.push(event);
+                    }
+                }
+            },
+            _ => ()
+        }
+        events
+    }
+    
+    fn process_message(&mut self, cx: &mut Cx, msg: Value, text_buffers: &mut TextBuffers) -> LspClientEvent {
+        if let Some(method) = msg["method"].as_str() {
+            if !msg["id"].is_null() { // a request from the server, we don't do any of them
+                self.send_response(msg["id"].clone(), Value::Null);
+                return LspClientEvent::None
+            }
+            if method == "textDocument/publishDiagnostics" {
+                self.process_diagnostics(cx, &msg["params"], text_buffers);
+            }
+            return LspClientEvent::None
+        }
+        let request = if let Some(id) = msg["id"].as_u64() {
+            if let Some(request) = self._requests.remove(&id) {request}else {return LspClientEvent::None}
+        }
+        else {
+            return LspClientEvent::None
+        };
+        if !msg["error"].is_null() {
+            let status = format!("{}: {}", self._cmd, msg["error"]["message"].as_str().unwrap_or("error"));
+            self.set_status(status);
+            return LspClientEvent::None
+        }
+        let result = &msg["result"];
+        match request {
+            LspRequest::Initialize => {
+                self._initialized = true;
+                self.send_notification("initialized", json!({}));
+                for body in std::mem::replace(&mut self._queue, Vec::new()) {
+                    self.write_message(&body);
+                }
+                LspClientEvent::None
+            },
+            LspRequest::Completion {window_index, editor_id, offset} => {
+                let items = if result["items"].is_array() {&result["items"]}else {result};
+                let mut completions = Vec::new();
+                if let Some(items) = items.as_array() {
+                    for item in items {
+                        let label = item["label"].as_str().unwrap_or("").to_string();
+                        let insert_text = if let Some(text) = item["textEdit"]["newText"].as_str() {
+                            text.to_string()
+                        }
+                        else if let Some(text) = item["insertText"].as_str() {
+                            text.to_string()
+                        }
+                        else {
+                            label.clone()
+                        };
+                        completions.push(CodeCompletion {
+                            label: label,
+                            detail: item["detail"].as_str().unwrap_or("").to_string(),
+                            insert_text: insert_text
+                        });
+                    }
+                }
+                LspClientEvent::Completion {window_index: window_index, editor_id: editor_id, offset: offset, items: completions}
+            },
+            LspRequest::Hover {window_index, editor_id, offset} => {
+                let text = Self::hover_contents_to_string(&result["contents"]);
+                if text.len() == 0 {
+                    return LspClientEvent::None
+                }
+                LspClientEvent::Hover {window_index: window_index, editor_id: editor_id, offset: offset, text: text}
+            },
+            LspRequest::Definition => {
+                // Location, Location[] or LocationLink[]
+                let location = if result.is_array() {&result[0]}else {result};
+                let uri = location["uri"].as_str().or(location["targetUri"].as_str());
+                let range = if location["targetSelectionRange"].is_object() {&location["targetSelectionRange"]}else {&location["range"]};
+                if let Some(uri) = uri {
+                    if let Some(path) = self.uri_to_path(uri) {
+                        // a buffer that is still loading has no lines, we keep the utf16 column then
+                        let text_buffer = text_buffers.from_path(cx, &path);
+                        return LspClientEvent::Definition {
+                            pos: text_pos(text_buffer, &range["start"]),
+                            path: path
+                        }
+                    }
+                }
+                LspClientEvent::None
+            }
+        }
+    }
+    
+    fn hover_contents_to_string(contents: &Value) -> String {
+        // MarkupContent, MarkedString or an array of MarkedStrings
+        if let Some(text) = contents.as_str() {
+            return text.to_string()
+        }
+        if let Some(text) = contents["value"].as_str() {
+            return text.to_string()
+        }
+        if let Some(array) = contents.as_array() {
+            let parts: Vec<String> = array.iter().map( | part | Self::hover_contents_to_string(part)).collect();
+            return parts.join("\n")
+        }
+        String::new()
+    }
+    
+    fn process_diagnostics(&mut self, cx: &mut Cx, params: &Value, text_buffers: &mut TextBuffers) {
+        let path = if let Some(path) = params["uri"].as_str().and_then( | uri | self.uri_to_path(uri)) {path}else {return};
+        let text_buffer = text_buffers.from_path(cx, &path);
+        if text_buffer.load_read_req.is_loading() {
+            return
+        }
+        let mut markers = Vec::new();
+        if let Some(diagnostics) = params["diagnostics"].as_array() {
+            for diagnostic in diagnostics {
+                let level = match diagnostic["severity"].as_u64() {
+                    Some(1) => TextBufferMessageLevel::Error,
+                    Some(2) => TextBufferMessageLevel::Warning,
+                    _ => TextBufferMessageLevel::Log
+                };
+                let range = &diagnostic["range"];
+                let start = text_buffer.text_pos_to_offset(text_pos(text_buffer, &range["start"]));
+                let end = text_buffer.text_pos_to_offset(text_pos(text_buffer, &range["end"]));
+                markers.push((start, end, TextBufferMessage {
+                    level: level,
+                    source: TextBufferMessageSource::Lsp,
+                    body: diagnostic["message"].as_str().unwrap_or("").to_string(),
+                    fixes: Vec::new()
+                }));
+            }
+        }
+        // the compiler and shader messages stay
+        let messages = &mut text_buffer.messages;
+        messages.clear_source(TextBufferMessageSource::Lsp, text_buffer.mutation_id);
+        for (start, end, body) in markers {
+            messages.cursors.push(TextCursor {head: end, tail: start, max: 0});
+            messages.bodies.push(body);
+        }
+        messages.sort();
+        cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_MESSAGE_UPDATE);
+    }
+}
+
+// each message is a Content-Length header block followed by a json body, None at the end of the stream
+pub fn read_lsp_message(reader: &mut dyn BufRead) -> Option<Vec<u8>> {
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return None
+        }
+        let line = line.trim();
+        if line.len() == 0 {
+            break
+        }
+        if line.starts_with("Content-Length:") {
+            content_length = line[15..].trim().parse::<usize>().unwrap_or(0);
+        }
+    }
+    let mut data = vec![0; content_length];
+    if reader.read_exact(&mut data).is_err() {
+        return None
+    }
+    Some(data)
+}
+
+// the length counts bytes, not chars
+pub fn frame_lsp_message(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+// lsp positions count utf16 code units, TextPos counts chars
+fn lsp_position(text_buffer: &TextBuffer, pos: TextPos) -> Value {
+    let character: usize = if let Some(line) = text_buffer.lines.get(pos.row) {
+        line.iter().take(pos.col).map( | ch | ch.len_utf16()).sum()
+    }
+    else {
+        pos.col
+    };
+    json!({"line": pos.row, "character": character})
+}
+
+fn text_pos(text_buffer: &TextBuffer, position: &Value) -> TextPos {
+    let row = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    if let Some(line) = text_buffer.lines.get(row) {
+        let mut col = 0;
+        let mut units = 0;
+        while col < line.len() && units < character {
+            units += line[col].len_utf16();
+            col += 1;
+        }
+        return TextPos {row: row, col: col}
+    }
+    TextPos {row: row, col: character}
+}
+
+// everything but the unreserved characters and the path separators is escaped
+fn percent_encode(path: &str) -> String {
+    let mut out = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte))
+        }
+    }
+    out
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then( | hex | u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    
+    // stands in for the server end of stdin, the tests read back what the client sent
+    #[derive(Clone, Default)]
+    struct FakeServer {
+        written: Arc<Mutex<Vec<u8>>>
+    }
+    
+    impl Write for FakeServer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    
+    impl FakeServer {
+        fn take_messages(&self) -> Vec<Value> {
+            let data = std::mem::replace(&mut *self.written.lock().unwrap(), Vec::new());
+            let mut reader = Cursor::new(data);
+            let mut messages = Vec::new();
+            while let Some(body) = read_lsp_message(&mut reader) {
+                messages.push(serde_json::from_slice(&body).unwrap());
+            }
+            messages
+        }
+    }
+    
+    fn new_client(cx: &mut Cx, server: &FakeServer) -> LspClient {
+        let mut client = LspClient::new(cx);
+        client._stdin = Some(Box::new(server.clone()));
+        client._cmd = "fake-analyzer".to_string();
+        client._root_uri = "file:///work/my%20crate".to_string();
+        client
+    }
+    
+    fn new_text_buffers(path: &str, text: &str) -> TextBuffers {
+        let mut text_buffers = TextBuffers {
+            root_path: String::new(),
+            storage: HashMap::new(),
+            git_timer: Timer::empty(),
+            git_stamp: Vec::new()
+        };
+        text_buffers.storage.insert(path.to_string(), TextBuffer {
+            lines: text.lines().map( | line | line.chars().collect()).collect(),
+            ..Default::default()
+        });
+        text_buffers
+    }
+    
+    #[test]
+    fn reads_messages_back_to_back() {
+        let mut data = frame_lsp_message(r#"{"id":1}"#);
+        data.push_str("Content-Length: 9\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{\"id\":22}");
+        let mut reader = Cursor::new(data.into_bytes());
+        assert_eq!(read_lsp_message(&mut reader).unwrap(), br#"{"id":1}"#.to_vec());
+        assert_eq!(read_lsp_message(&mut reader).unwrap(), br#"{"id":22}"#.to_vec());
+        assert_eq!(read_lsp_message(&mut reader), None);
+    }
+    
+    #[test]
+    fn content_length_counts_bytes() {
+        let body = r#"{"text":"ü😀"}"#;
+        let framed = frame_lsp_message(body);
+        assert!(framed.starts_with(&format!("Content-Length: {}\r\n\r\n", body.len())));
+        let mut reader = Cursor::new(framed.into_bytes());
+        assert_eq!(read_lsp_message(&mut reader).unwrap(), body.as_bytes().to_vec());
+    }
+    
+    #[test]
+    fn truncated_message_ends_the_stream() {
+        let mut reader = Cursor::new(b"Content-Length: 20\r\n\r\n{\"id\":1}".to_vec());
+        assert_eq!(read_lsp_message(&mut reader), None);
+        let mut reader = Cursor::new(b"Content-Length: 20\r\n".to_vec());
+        assert_eq!(read_lsp_message(&mut reader), None);
+    }
+    
+    #[test]
+    fn uris_are_percent_encoded() {
+        let mut cx = Cx::default();
+        let client = new_client(&mut cx, &FakeServer::default());
+        let uri = client.path_to_uri("src/a b#ü.rs");
+        assert_eq!(uri, "file:///work/my%20crate/src/a%20b%23%C3%BC.rs");
+        assert_eq!(client.uri_to_path(&uri), Some("src/a b#ü.rs".to_string()));
+        assert_eq!(client.uri_to_path("file:///work/other/src/main.rs"), None);
+    }
+    
+    #[test]
+    fn positions_are_utf16_code_units() {
+        let text_buffers = new_text_buffers("src/main.rs", "fn main() {\n    let ü = \"😀x\";\n}");
+        let text_buffer = &text_buffers.storage["src/main.rs"];
+        // the x sits after a 2 unit emoji
+        let pos = TextPos {row: 1, col: 15};
+        assert_eq!(lsp_position(text_buffer, pos), json!({"line": 1, "character": 16}));
+        assert_eq!(text_pos(text_buffer, &json!({"line": 1, "character": 16})), pos);
+        // past the end of the line clamps to the end
+        assert_eq!(text_pos(text_buffer, &json!({"line": 0, "character": 40})), TextPos {row: 0, col: 11});
+    }
+    
+    #[test]
+    fn requests_wait_for_initialize() {
+        let mut cx = Cx::default();
+        let server = FakeServer::default();
+        let mut client = new_client(&mut cx, &server);
+        let mut text_buffers = new_text_buffers("src/main.rs", "");
+        client.send_request(LspRequest::Initialize, "initialize", json!({}));
+        client.send_request(LspRequest::Definition, "textDocument/definition", json!({}));
+        let sent = server.take_messages();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0]["method"], "initialize");
+        
+        client.process_message(&mut cx, json!({"jsonrpc": "2.0", "id": sent[0]["id"], "result": {}}), &mut text_buffers);
+        let methods: Vec<Value> = server.take_messages().iter().map( | msg | msg["method"].clone()).collect();
+        assert_eq!(methods, vec![json!("initialized"), json!("textDocument/definition")]);
+    }
+    
+    #[test]
+    fn answers_server_requests_with_null() {
+        let mut cx = Cx::default();
+        let server = FakeServer::default();
+        let mut client = new_client(&mut cx, &server);
+        let mut text_buffers = new_text_buffers("src/main.rs", "");
+        let msg = json!({"jsonrpc": "2.0", "id": 7, "method": "workspace/configuration", "params": {}});
+        if let LspClientEvent::None = client.process_message(&mut cx, msg, &mut text_buffers) {}else {panic!()}
+        let sent = server.take_messages();
+        assert_eq!(sent, vec![json!({"jsonrpc": "2.0", "id": 7, "result": null})]);
+    }
+    
+    #[test]
+    fn dispatches_responses_by_id() {
+        let mut cx = Cx::default();
+        let server = FakeServer::default();
+        let mut client = new_client(&mut cx, &server);
+        client._initialized = true;
+        let mut text_buffers = new_text_buffers("src/main.rs", "fn main() {\n    let ü = \"😀x\";\n}");
+        
+        client.send_request(LspRequest::Completion {window_index: 0, editor_id: 3, offset: 10}, "textDocument/completion", json!({}));
+        client.send_request(LspRequest::Hover {window_index: 1, editor_id: 4, offset: 20}, "textDocument/hover", json!({}));
+        client.send_request(LspRequest::Definition, "textDocument/definition", json!({}));
+        let ids: Vec<Value> = server.take_messages().iter().map( | msg | msg["id"].clone()).collect();
+        
+        // answered out of order
+        let hover = client.process_message(&mut cx, json!({"id": ids[1], "result": {"contents": ["fn main()", {"language": "rust", "value": "pub"}]}}), &mut text_buffers);
+        if let LspClientEvent::Hover {window_index, editor_id, offset, text} = hover {
+            assert_eq!((window_index, editor_id, offset), (1, 4, 20));
+            assert_eq!(text, "fn main()\npub");
+        }
+        else {panic!("expected hover")}
+        
+        let completion = client.process_message(&mut cx, json!({"id": ids[0], "result": {"items": [
+            {"label": "len", "detail": "fn len(&self)", "textEdit": {"newText": "len()"}},
+            {"label": "push", "insertText": "push"},
+            {"label": "iter"}
+        ]}}), &mut text_buffers);
+        if let LspClientEvent::Completion {editor_id, items, ..} = completion {
+            assert_eq!(editor_id, 3);
+            let inserts: Vec<&str> = items.iter().map( | item | item.insert_text.as_ref()).collect();
+            assert_eq!(inserts, vec!["len()", "push", "iter"]);
+            assert_eq!(items[0].detail, "fn len(&self)");
+        }
+        else {panic!("expected completion")}
+        
+        let definition = client.process_message(&mut cx, json!({"id": ids[2], "result": [{
+            "targetUri": "file:///work/my%20crate/src/main.rs",
+            "targetSelectionRange": {"start": {"line": 1, "character": 16}, "end": {"line": 1, "character": 17}}
+        }]}), &mut text_buffers);
+        if let LspClientEvent::Definition {path, pos} = definition {
+            assert_eq!(path, "src/main.rs");
+            assert_eq!(pos, TextPos {row: 1, col: 15});
+        }
+        else {panic!("expected definition")}
+        
+        // a second answer to the same id is ignored
+        if let LspClientEvent::None = client.process_message(&mut cx, json!({"id": ids[2], "result": null}), &mut text_buffers) {}else {panic!()}
+    }
+    
+    #[test]
+    fn errors_go_to_the_status() {
+        let mut cx = Cx::default();
+        let server = FakeServer::default();
+        let mut client = new_client(&mut cx, &server);
+        client._initialized = true;
+        let mut text_buffers = new_text_buffers("src/main.rs", "");
+        client.send_request(LspRequest::Definition, "textDocument/definition", json!({}));
+        let id = server.take_messages()[0]["id"].clone();
+        let msg = json!({"id": id, "error": {"code": -32801, "message": "content modified"}});
+        if let LspClientEvent::None = client.process_message(&mut cx, msg, &mut text_buffers) {}else {panic!()}
+        assert_eq!(client.status, "fake-analyzer: content modified");
+    }
+    
+    #[test]
+    fn diagnostics_become_messages() {
+        let mut cx = Cx::default();
+        let server = FakeServer::default();
+        let mut client = new_client(&mut cx, &server);
+        let mut text_buffers = new_text_buffers("src/main.rs", "fn main() {\n    let ü = \"😀x\";\n}");
+        let msg = json!({"method": "textDocument/publishDiagnostics", "params": {
+            "uri": "file:///work/my%20crate/src/main.rs",
+            "diagnostics": [{
+                "severity": 1,
+                "message": "unknown x",
+                "range": {"start": {"line": 1, "character": 16}, "end": {"line": 1, "character": 17}}
+            }]
+        }});
+        client.process_message(&mut cx, msg, &mut text_buffers);
+        let text_buffer = &text_buffers.storage["src/main.rs"];
+        let start = text_buffer.text_pos_to_offset(TextPos {row: 1, col: 15});
+        assert_eq!(text_buffer.messages.bodies.len(), 1);
+        assert_eq!(text_buffer.messages.bodies[0].body, "unknown x");
+        assert_eq!((text_buffer.messages.cursors[0].tail, text_buffer.messages.cursors[0].head), (start, start + 1));
+    }
+}
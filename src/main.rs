@@ -4,6 +4,28 @@ use widget::*;
 use editor::*;
 mod rustcompiler;
 pub use crate::rustcompiler::*;
+mod lspclient;
+pub use crate::lspclient::*;
+mod console;
+pub use crate::console::*;
+mod buildqueue;
+pub use crate::buildqueue::*;
+mod buildstatus;
+pub use crate::buildstatus::*;
+mod terminalgrid;
+pub use crate::terminalgrid::*;
+mod terminal;
+pub use crate::terminal::*;
+mod gdbmi;
+pub use crate::gdbmi::*;
+mod debugger;
+pub use crate::debugger::*;
+mod debugview;
+pub use crate::debugview::*;
+mod workspace;
+pub use crate::workspace::*;
+mod projectview;
+pub use crate::projectview::*;
 use std::collections::HashMap;
 //use std::borrow::Cow;
 use serde::*;
@@ -11,10 +33,17 @@ use serde::*;
 #[derive(Clone, Serialize, Deserialize)]
 enum Panel {
     RustCompiler,
+    Console,
+    Terminal,
+    CallStack,
+    Variables,
     Keyboard,
     FileTree,
+    Outline,
+    Project,
     FileEditorTarget,
-    FileEditor {path: String, editor_id: u64}
+    FileEditor {path: String, editor_id: u64},
+    DiffViewer {left_path: String, right_path: String, viewer_id: u64, #[serde(default)] mode: DiffViewerMode}
 }
 
 #[derive(Clone)]
@@ -23,14 +52,26 @@ struct AppWindow {
     file_tree: FileTree,
     keyboard: Keyboard,
     file_editors: Elements<u64, FileEditor, FileEditorTemplates>,
+    diff_viewers: Elements<u64, DiffViewer, DiffViewer>,
+    outline_view: OutlineView,
+    outline_path: String,
+    project_view: ProjectView,
+    call_stack_view: CallStackView,
+    variables_view: VariablesView,
+    build_status: BuildStatus,
     dock: Dock<Panel>,
 }
 
 struct AppGlobal {
+    state_path: String,
     file_tree_data: String,
     file_tree_reload_signal: Signal,
     text_buffers: TextBuffers,
     rust_compiler: RustCompiler,
+    lsp_client: LspClient,
+    terminal: Terminal,
+    debugger: Debugger,
+    symbol_index: SymbolIndex,
     state: AppState,
     index_read_req: FileReadRequest,
     app_state_read_req: FileReadRequest,
@@ -52,7 +93,15 @@ struct AppWindowState {
 
 #[derive(Default, Clone, Serialize, Deserialize)]
 struct AppState {
-    windows: Vec<AppWindowState>
+    windows: Vec<AppWindowState>,
+    #[serde(default)]
+    folds: HashMap<String, Vec<TextFold>>,
+    #[serde(default)]
+    build_config: RustBuildConfig,
+    #[serde(default)]
+    project_path: String,
+    #[serde(default)]
+    recent_projects: Vec<String>
 }
 
 main_app!(App, "HALLO WORLD");
@@ -65,8 +114,15 @@ impl Style for AppWindow {
                 rust_editor: RustEditor::style(cx),
                 js_editor: JSEditor::style(cx)
             }),
+            diff_viewers: Elements::new(DiffViewer::style(cx)),
             keyboard: Keyboard::style(cx),
             file_tree: FileTree::style(cx),
+            outline_view: OutlineView::style(cx),
+            outline_path: String::new(),
+            project_view: ProjectView::style(cx),
+            call_stack_view: CallStackView::style(cx),
+            variables_view: VariablesView::style(cx),
+            build_status: BuildStatus::style(cx),
             dock: Dock ::style(cx),
         }
     }
@@ -87,11 +143,23 @@ impl Style for App {
                     pos: 150.0,
                     first: Box::new(DockItem::TabControl {
                         current: 0,
-                        tabs: vec![DockTab {
-                            closeable: false,
-                            title: "Files".to_string(),
-                            item: Panel::FileTree
-                        }]
+                        tabs: vec![
+                            DockTab {
+                                closeable: false,
+                                title: "Files".to_string(),
+                                item: Panel::FileTree
+                            },
+                            DockTab {
+                                closeable: false,
+                                title: "Outline".to_string(),
+                                item: Panel::Outline
+                            },
+                            DockTab {
+                                closeable: false,
+                                title: "Project".to_string(),
+                                item: Panel::Project
+                            }
+                        ]
                     }),
                     last: Box::new(DockItem::Splitter {
                         axis: Axis::Horizontal,
@@ -120,6 +188,26 @@ impl Style for App {
                                     title: "Rust Compiler".to_string(),
                                     item: Panel::RustCompiler
                                 },
+                                DockTab {
+                                    closeable: false,
+                                    title: "Console".to_string(),
+                                    item: Panel::Console
+                                },
+                                DockTab {
+                                    closeable: false,
+                                    title: "Terminal".to_string(),
+                                    item: Panel::Terminal
+                                },
+                                DockTab {
+                                    closeable: false,
+                                    title: "Call Stack".to_string(),
+                                    item: Panel::CallStack
+                                },
+                                DockTab {
+                                    closeable: false,
+                                    title: "Variables".to_string(),
+                                    item: Panel::Variables
+                                },
                                 DockTab {
                                     closeable: false,
                                     title: "Keyboard".to_string(),
@@ -133,10 +221,17 @@ impl Style for App {
             windows: vec![],
             app_global: AppGlobal {
                 rust_compiler: RustCompiler::style(cx),
+                lsp_client: LspClient::new(cx),
+                terminal: Terminal::style(cx),
+                debugger: Debugger::new(cx),
+                symbol_index: SymbolIndex::default(),
                 text_buffers: TextBuffers {
                     root_path: "./".to_string(),
-                    storage: HashMap::new()
+                    storage: HashMap::new(),
+                    git_timer: Timer::empty(),
+                    git_stamp: Vec::new()
                 },
+                state_path: String::new(),
                 index_read_req: FileReadRequest::empty(),
                 app_state_read_req: FileReadRequest::empty(),
                 file_tree_data: String::new(),
@@ -175,9 +270,22 @@ impl AppWindow {
             _ => ()
         }
         
+        match self.build_status.handle_build_status(cx, event, &app_global.rust_compiler.build_queue) {
+            BuildStatusEvent::CancelAll => {
+                app_global.rust_compiler.cancel_jobs(cx);
+            },
+            _ => ()
+        }
+        
         let dock_items = &mut app_global.state.windows[window_index].dock_items;
         let mut dock_walker = self.dock.walker(dock_items);
         let mut file_tree_event = FileTreeEvent::None;
+        let mut folds_changed = false;
+        let mut jump_to = None;
+        let mut start_debugger = false;
+        let mut debug_build_done = None;
+        let mut open_project = None;
+        let mut select_package = None;
         while let Some(item) = dock_walker.walk_handle_dock(cx, event) {
             match item {
                 Panel::RustCompiler => {
@@ -187,9 +295,58 @@ impl AppWindow {
                             file_tree_event = FileTreeEvent::SelectFile {path: path};
                             
                         },
+                        RustCompilerEvent::SelectTest {name} => {
+                            // libtest only gives us the path of the test fn, so look it up by name
+                            let fn_name = name.rsplit("::").next().unwrap_or("");
+                            jump_to = app_global.symbol_index.find_definition(fn_name, "");
+                        },
+                        RustCompilerEvent::ApplyFixes {paths} => {
+                            // write the fixed files and check again
+                            for path in &paths {
+                                app_global.text_buffers.save_file(cx, path);
+                                app_global.lsp_client.sync_text_buffer(path, &app_global.text_buffers.storage[path]);
+                            }
+                            app_global.rust_compiler.restart_rust_checker(cx, &mut app_global.text_buffers);
+                        },
+                        RustCompilerEvent::DebugBuildDone {executable} => {
+                            debug_build_done = Some(executable);
+                        },
+                        _ => ()
+                    }
+                },
+                Panel::Console => {
+                    match app_global.rust_compiler.console.handle_console(cx, event) {
+                        ConsoleEvent::SelectLink {path, row, col} => {
+                            let text_buffer = app_global.text_buffers.from_path(cx, &path);
+                            let offset = text_buffer.text_pos_to_offset(TextPos {row: row.max(1) - 1, col: col.max(1) - 1});
+                            jump_to = Some((path, offset));
+                        },
+                        _ => ()
+                    }
+                },
+                Panel::Terminal => {
+                    app_global.terminal.handle_terminal(cx, event);
+                },
+                Panel::CallStack => {
+                    match self.call_stack_view.handle_call_stack_view(cx, event, &app_global.debugger) {
+                        CallStackEvent::Command(DebugCommand::Start) => {
+                            start_debugger = true;
+                        },
+                        CallStackEvent::Command(command) => {
+                            app_global.debugger.command(cx, command, &mut app_global.text_buffers);
+                        },
+                        CallStackEvent::SelectFrame {index} => {
+                            if let Some((path, row)) = app_global.debugger.select_frame(cx, index, &mut app_global.text_buffers) {
+                                let text_buffer = app_global.text_buffers.from_path(cx, &path);
+                                jump_to = Some((path, text_buffer.text_pos_to_offset(TextPos {row: row, col: 0})));
+                            }
+                        },
                         _ => ()
                     }
                 },
+                Panel::Variables => {
+                    self.variables_view.handle_variables_view(cx, event, &app_global.debugger);
+                },
                 Panel::Keyboard => {
                     self.keyboard.handle_keyboard(cx, event, &mut app_global.text_buffers);
                 },
@@ -199,22 +356,127 @@ impl AppWindow {
                 Panel::FileTree => {
                     file_tree_event = self.file_tree.handle_file_tree(cx, event);
                 },
+                Panel::Project => {
+                    match self.project_view.handle_project_view(cx, event, &app_global.rust_compiler.workspace) {
+                        ProjectViewEvent::OpenFolder {path} => {
+                            open_project = Some(path);
+                        },
+                        ProjectViewEvent::SelectPackage {name} => {
+                            select_package = Some(name);
+                        },
+                        _ => ()
+                    }
+                },
+                Panel::Outline => {
+                    let symbols = app_global.symbol_index.files.get(&self.outline_path).map( | file | &file.symbols[..]).unwrap_or(&[]);
+                    match self.outline_view.handle_outline_view(cx, event, symbols) {
+                        OutlineViewEvent::SelectSymbol {offset} => {
+                            jump_to = Some((self.outline_path.clone(), offset));
+                        },
+                        _ => ()
+                    }
+                },
                 Panel::FileEditor {path, editor_id} => {
                     if let Some(file_editor) = &mut self.file_editors.get(*editor_id) {
+                        // folds are kept per project
+                        let fold_key = format!("{}{}", app_global.text_buffers.root_path, path);
                         let text_buffer = app_global.text_buffers.from_path(cx, path);
-                        match file_editor.handle_file_editor(cx, event, text_buffer) {
+                        let file_editor_event = file_editor.handle_file_editor(cx, event, text_buffer);
+                        // the outline follows the focussed editor
+                        if file_editor.has_key_focus(cx) && self.outline_path != *path {
+                            self.outline_path = path.clone();
+                            self.outline_view.redraw_outline_view(cx);
+                        }
+                        match file_editor_event {
+                            FileEditorEvent::FoldChange => {
+                                // persist the folded ranges of this file
+                                let folds = text_buffer.folds.clone();
+                                app_global.state.folds.insert(fold_key, folds);
+                                folds_changed = true;
+                            },
                             FileEditorEvent::LagChange => {
+                                // edits shift or drop the folded ranges
+                                if app_global.state.folds.get(&fold_key) != Some(&text_buffer.folds) {
+                                    let folds = text_buffer.folds.clone();
+                                    app_global.state.folds.insert(fold_key, folds);
+                                    folds_changed = true;
+                                }
                                 app_global.text_buffers.save_file(cx, path);
                                 // lets save the textbuffer to disk
                                 // lets re-trigger the rust compiler
                                 app_global.rust_compiler.restart_rust_checker(cx, &mut app_global.text_buffers);
+                                app_global.lsp_client.sync_text_buffer(path, &app_global.text_buffers.storage[path]);
+                                // shader_ast! blocks in the file hot reload into the running ui
+                                if path.ends_with(".rs") {
+                                    let text_buffer = app_global.text_buffers.from_path(cx, path);
+                                    let errors = cx.reload_shaders_from_source(path, &text_buffer.get_as_string());
+                                    export_shader_errors(cx, text_buffer, &errors);
+                                }
+                            },
+                            FileEditorEvent::CompletionRequest {offset} => {
+                                app_global.lsp_client.request_completion(window_index, *editor_id, path, offset, text_buffer);
+                            },
+                            FileEditorEvent::HoverRequest {offset} => {
+                                app_global.lsp_client.request_hover(window_index, *editor_id, path, offset, text_buffer);
+                            },
+                            FileEditorEvent::DefinitionRequest {offset} => {
+                                if app_global.lsp_client.is_running() {
+                                    app_global.lsp_client.request_definition(path, offset, text_buffer);
+                                }
+                                else if let Some(name) = SymbolIndex::identifier_at(text_buffer, offset) {
+                                    jump_to = app_global.symbol_index.find_definition(&name, path);
+                                }
+                            },
+                            FileEditorEvent::BreakpointChange {row} => {
+                                let enabled = text_buffer.debug.breakpoints.contains(&row);
+                                app_global.debugger.update_breakpoint(path, row, enabled);
                             },
                             _ => ()
                         }
                     }
+                },
+                Panel::DiffViewer {left_path, right_path, viewer_id, mode} => {
+                    if let Some(diff_viewer) = &mut self.diff_viewers.get(*viewer_id) {
+                        app_global.text_buffers.from_path(cx, left_path);
+                        app_global.text_buffers.from_path(cx, right_path);
+                        let storage = &app_global.text_buffers.storage;
+                        let diff_viewer_event = if *mode == DiffViewerMode::Files {
+                            diff_viewer.handle_diff_viewer(cx, event, &storage[left_path], &storage[right_path])
+                        }
+                        else {
+                            diff_viewer.handle_diff_viewer_base(cx, event, &storage[right_path])
+                        };
+                        // the editors of the right file follow the selected hunk
+                        if let DiffViewerEvent::HunkSelect {right_row, ..} = diff_viewer_event {
+                            let text_buffer = app_global.text_buffers.from_path(cx, right_path);
+                            text_buffer.messages.jump_to_offset = text_buffer.text_pos_to_offset(TextPos {row: right_row, col: 0});
+                            cx.send_signal_after_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET);
+                        }
+                    }
                 }
             }
         }
+        if folds_changed {
+            app_global.save_state(cx);
+        }
+        if start_debugger {
+            app_global.start_debugger(cx);
+        }
+        if let Some(executable) = debug_build_done {
+            app_global.debug_build_done(cx, executable);
+        }
+        if let Some(name) = select_package {
+            // clicking the selected crate goes back to the whole workspace
+            let mut build_config = app_global.state.build_config.clone();
+            build_config.package = if build_config.package.as_ref() == Some(&name) {None}else {Some(name)};
+            app_global.state.build_config = build_config.clone();
+            app_global.rust_compiler.set_build_config(cx, build_config, &mut app_global.text_buffers);
+            app_global.save_state(cx);
+            self.project_view.view.redraw_view_area(cx);
+        }
+        if let Some(path) = open_project {
+            app_global.open_project(cx, &path);
+        }
         match file_tree_event {
             FileTreeEvent::DragMove {fe, ..} => {
                 self.dock.dock_drag_move(cx, fe);
@@ -224,9 +486,18 @@ impl AppWindow {
             },
             FileTreeEvent::DragEnd {fe, paths} => {
                 let mut tabs = Vec::new();
-                for path in paths {
-                    // find a free editor id
-                    tabs.push(self.new_file_editor_tab(window_index, app_global, &path));
+                if paths.len() == 2 && fe.modifiers.alt { // dropping two files with alt compares them
+                    tabs.push(self.new_diff_viewer_tab(window_index, app_global, &paths[0], &paths[1], DiffViewerMode::Files));
+                }
+                else if paths.len() == 1 && fe.modifiers.alt { // one file compares with git HEAD, with shift with the disk
+                    let mode = if fe.modifiers.shift {DiffViewerMode::Disk}else {DiffViewerMode::Head};
+                    tabs.push(self.new_diff_viewer_tab(window_index, app_global, &paths[0], &paths[0], mode));
+                }
+                else {
+                    for path in paths {
+                        // find a free editor id
+                        tabs.push(self.new_file_editor_tab(window_index, app_global, &path));
+                    }
                 }
                 self.dock.dock_drag_end(cx, fe, tabs);
             },
@@ -238,6 +509,14 @@ impl AppWindow {
             },
             _ => {}
         }
+        if let Some((path, offset)) = jump_to {
+            if self.focus_or_new_editor(cx, window_index, app_global, &path) {
+                app_global.save_state(cx);
+            }
+            let text_buffer = app_global.text_buffers.from_path(cx, &path);
+            text_buffer.messages.jump_to_offset = offset;
+            cx.send_signal_after_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET);
+        }
         
         let dock_items = &mut app_global.state.windows[window_index].dock_items;
         match self.dock.handle_dock(cx, event, dock_items) {
@@ -252,6 +531,12 @@ impl AppWindow {
         if let Err(()) = self.desktop_window.begin_desktop_window(cx) {
             return
         }
+        // the dock leaves room for the status bar
+        cx.begin_turtle(&Layout {
+            width: Bounds::Fill,
+            height: Bounds::FillPad(self.build_status.height),
+            ..Default::default()
+        }, Area::Empty);
         self.dock.draw_dock(cx);
         
         let dock_items = &mut app_global.state.windows[window_index].dock_items;
@@ -261,6 +546,18 @@ impl AppWindow {
                 Panel::RustCompiler => {
                     app_global.rust_compiler.draw_rust_compiler(cx);
                 },
+                Panel::Console => {
+                    app_global.rust_compiler.console.draw_console(cx);
+                },
+                Panel::Terminal => {
+                    app_global.terminal.draw_terminal(cx);
+                },
+                Panel::CallStack => {
+                    self.call_stack_view.draw_call_stack_view(cx, &app_global.debugger);
+                },
+                Panel::Variables => {
+                    self.variables_view.draw_variables_view(cx, &app_global.debugger);
+                },
                 Panel::Keyboard => {
                     self.keyboard.draw_keyboard(cx);
                 },
@@ -268,6 +565,15 @@ impl AppWindow {
                 Panel::FileTree => {
                     self.file_tree.draw_file_tree(cx);
                 },
+                Panel::Project => {
+                    let recent = &app_global.state.recent_projects;
+                    let package = app_global.state.build_config.package.as_ref();
+                    self.project_view.draw_project_view(cx, &app_global.text_buffers.root_path, &app_global.rust_compiler.workspace, recent, package);
+                },
+                Panel::Outline => {
+                    let symbols = app_global.symbol_index.files.get(&self.outline_path).map( | file | &file.symbols[..]).unwrap_or(&[]);
+                    self.outline_view.draw_outline_view(cx, symbols);
+                },
                 Panel::FileEditor {path, editor_id} => {
                     let text_buffer = app_global.text_buffers.from_path(cx, path);
                     let mut set_key_focus = false;
@@ -279,9 +585,37 @@ impl AppWindow {
                     if set_key_focus {
                         file_editor.set_key_focus(cx);
                     }
+                    if app_global.symbol_index.update_text_buffer(path, text_buffer) && *path == self.outline_path {
+                        self.outline_view.redraw_outline_view(cx);
+                    }
+                },
+                Panel::DiffViewer {left_path, right_path, viewer_id, mode} => {
+                    // the diff colors the tokens, js files get theirs once a js editor opens them
+                    for path in &[left_path.as_str(), right_path.as_str()] {
+                        let text_buffer = app_global.text_buffers.from_path(cx, path);
+                        if !path.ends_with(".js") {
+                            RustTokenizer::update_token_chunks(text_buffer);
+                        }
+                    }
+                    let storage = &app_global.text_buffers.storage;
+                    let diff_viewer = self.diff_viewers.get_draw(cx, *viewer_id, | _cx, tmpl | tmpl.clone());
+                    if *mode == DiffViewerMode::Files {
+                        diff_viewer.draw_diff_viewer(cx, &storage[left_path], &storage[right_path]);
+                    }
+                    else {
+                        let file_path = format!("{}{}", app_global.text_buffers.root_path, right_path);
+                        diff_viewer.update_base(cx, *mode, &file_path, &storage[right_path]);
+                        if !right_path.ends_with(".js") {
+                            RustTokenizer::update_token_chunks(&mut diff_viewer._base);
+                        }
+                        diff_viewer.draw_diff_viewer_base(cx, &storage[right_path]);
+                    }
                 }
             }
         }
+        cx.end_turtle(Area::Empty);
+        cx.turtle_new_line();
+        self.build_status.draw_build_status(cx, &app_global.rust_compiler.build_queue, &app_global.lsp_client.status);
         self.desktop_window.end_desktop_window(cx);
     }
     
@@ -314,6 +648,31 @@ impl AppWindow {
         }
     }
     
+    fn new_diff_viewer_tab(&mut self, window_index: usize, app_global: &mut AppGlobal, left_path: &str, right_path: &str, mode: DiffViewerMode) -> DockTab<Panel> {
+        let mut max_id = 0;
+        let dock_items = &mut app_global.state.windows[window_index].dock_items;
+        let mut dock_walker = self.dock.walker(dock_items);
+        while let Some(dock_item) = dock_walker.walk_dock_item() {
+            if let DockItem::TabControl {tabs, ..} = dock_item {
+                for tab in tabs.iter() {
+                    if let Panel::DiffViewer {viewer_id, ..} = &tab.item {
+                        max_id = max_id.max(*viewer_id);
+                    }
+                }
+            }
+        }
+        let left_name = match mode {
+            DiffViewerMode::Files => path_file_name(left_path),
+            DiffViewerMode::Disk => "disk".to_string(),
+            DiffViewerMode::Head => "HEAD".to_string()
+        };
+        DockTab {
+            closeable: true,
+            title: format!("{} <> {}", left_name, path_file_name(right_path)),
+            item: Panel::DiffViewer {left_path: left_path.to_string(), right_path: right_path.to_string(), viewer_id: max_id + 1, mode: mode}
+        }
+    }
+    
     fn focus_or_new_editor(&mut self, cx: &mut Cx, window_index: usize, app_global: &mut AppGlobal, file_path: &str) -> bool {
         let mut target_ctrl_id = 0;
         let mut only_focus_editor = false;
@@ -374,21 +733,102 @@ impl AppGlobal {
         if cx.is_desktop_build {
             self.text_buffers.root_path = "./edit_repo/".to_string();
         }
+        // the app state stays next to the default project, it knows which one was open last
+        self.state_path = format!("{}makepad_state.json", self.text_buffers.root_path);
+        self.app_state_read_req = cx.read_file(&self.state_path);
+    }
+    
+    // the folder on the command line wins over the one that was open last
+    fn startup_project_path(&self, cx: &Cx) -> String {
+        if cx.is_desktop_build {
+            if let Some(path) = std::env::args().nth(1) {
+                return path
+            }
+        }
+        if self.state.project_path.len() > 0 {
+            self.state.project_path.clone()
+        }
+        else {
+            self.text_buffers.root_path.clone()
+        }
+    }
+    
+    // points the file tree, the compiler, the debugger and rust-analyzer at a project folder
+    fn load_project(&mut self, cx: &mut Cx, path: &str) {
+        let root_path = format!("{}/", path.replace("\\", "/").trim_end_matches('/'));
+        self.debugger.stop(cx, &mut self.text_buffers);
+        self.text_buffers.storage.clear();
+        self.text_buffers.root_path = root_path.clone();
+        self.symbol_index = SymbolIndex::default();
+        if cx.is_desktop_build {
+            self.file_tree_data = scan_project_folder(&root_path);
+            cx.send_signal_before_draw(self.file_tree_reload_signal, 0);
+            self.index_project_files(cx);
+        }
+        else {
+            self.index_read_req = cx.read_file(&format!("{}index.json", root_path));
+        }
         
-        self.index_read_req = cx.read_file(&format!("{}index.json", self.text_buffers.root_path));
-        self.app_state_read_req = cx.read_file(&format!("{}makepad_state.json", self.text_buffers.root_path));
+        self.rust_compiler.set_workdir(cx, &root_path, &mut self.text_buffers);
+        self.debugger.workdir = root_path.clone();
+        self.terminal.workdir = root_path.clone();
+        if cx.is_desktop_build {
+            self.lsp_client.stop();
+            self.lsp_client.start("rust-analyzer", &[], &root_path);
+        }
         
-        self.rust_compiler.init(cx, &mut self.text_buffers);
+        self.state.recent_projects.retain( | recent | *recent != root_path);
+        self.state.recent_projects.insert(0, root_path.clone());
+        self.state.recent_projects.truncate(8);
+        self.state.project_path = root_path;
+    }
+    
+    // switches to another folder at runtime, the editors of the old one close
+    fn open_project(&mut self, cx: &mut Cx, path: &str) {
+        if !std::path::Path::new(path).is_dir() {
+            return
+        }
+        for window in &mut self.state.windows {
+            close_file_tabs(&mut window.dock_items);
+        }
+        self.load_project(cx, path);
+        self.save_state(cx);
+        cx.redraw_child_area(Area::All);
+    }
+    
+    fn index_project_files(&mut self, cx: &mut Cx) {
+        // index the declarations of all project files for go to definition
+        for path in FileTree::file_paths_from_json(&self.file_tree_data) {
+            self.symbol_index.index_project_file(cx, &self.text_buffers.root_path, &path);
+        }
+    }
+    
+    fn start_debugger(&mut self, cx: &mut Cx) {
+        // the debugger starts when the debug build is done
+        self.rust_compiler.build_for_debugger(cx);
+        self.debugger.push_log("building a debug binary".to_string());
+        cx.send_signal_before_draw(self.debugger._signal, 0);
+    }
+    
+    fn debug_build_done(&mut self, cx: &mut Cx, executable: Option<String>) {
+        if let Some(executable) = executable {
+            self.debugger.start(cx, &executable, &mut self.text_buffers);
+        }
+        else {
+            self.debugger.push_log("the debug build failed or has no binary, see the build messages".to_string());
+        }
+        cx.send_signal_before_draw(self.debugger._signal, 0);
     }
     
     fn save_state(&mut self, cx: &mut Cx) {
         let json = serde_json::to_string(&self.state).unwrap();
-        cx.write_file(&format!("{}makepad_state.json", self.text_buffers.root_path), json.as_bytes());
+        cx.write_file(&self.state_path, json.as_bytes());
     }
 }
 
 impl App {
     fn handle_app(&mut self, cx: &mut Cx, event: &mut Event) {
+        self.app_global.text_buffers.handle_git_head(cx, event);
         match event {
             Event::Construct => {
                 self.app_global.handle_construct(cx);
@@ -399,12 +839,22 @@ impl App {
                     if let Ok(utf8_data) = utf8_data {
                         self.app_global.file_tree_data = utf8_data.to_string();
                         cx.send_signal_before_draw(self.app_global.file_tree_reload_signal, 0);
+                        self.app_global.index_project_files(cx);
                     }
                 }
                 else if let Some(utf8_data) = self.app_global.app_state_read_req.as_utf8(fr) {
                     if let Ok(utf8_data) = utf8_data {
                         if let Ok(state) = serde_json::from_str(&utf8_data) {
                             self.app_global.state = state;
+                            self.app_global.rust_compiler.build_config = self.app_global.state.build_config.clone();
+                            let project_path = self.app_global.startup_project_path(cx);
+                            if project_path != self.app_global.state.project_path {
+                                // the open editors belong to another folder
+                                for window in &mut self.app_global.state.windows {
+                                    close_file_tabs(&mut window.dock_items);
+                                }
+                            }
+                            self.app_global.load_project(cx, &project_path);
                             
                             // create our windows with the serialized positions/size
                             for window_state in &self.app_global.state.windows {
@@ -440,12 +890,29 @@ impl App {
                     }
                     else { // load default window
                         println!("DOING DEFAULT");
+                        let project_path = self.app_global.startup_project_path(cx);
+                        self.app_global.load_project(cx, &project_path);
                         self.app_global.state.windows = vec![self.app_window_state_template.clone()];
                         self.windows = vec![self.app_window_template.clone()];
                         cx.redraw_child_area(Area::All);
                     }
                 }
+                else if self.app_global.symbol_index.handle_file_read(&fr) {
+                    // a file got indexed, an outline may be showing it
+                    for window in &mut self.windows {
+                        window.outline_view.redraw_outline_view(cx);
+                    }
+                }
                 else if self.app_global.text_buffers.handle_file_read(cx, &fr) {
+                    // restore the folded ranges of freshly loaded files
+                    for (path, text_buffer) in &mut self.app_global.text_buffers.storage {
+                        if text_buffer.folds_id == 0 && !text_buffer.load_read_req.is_loading() {
+                            if let Some(folds) = self.app_global.state.folds.get(&format!("{}{}", self.app_global.text_buffers.root_path, path)) {
+                                text_buffer.folds = folds.clone();
+                            }
+                            text_buffer.folds_id = 1;
+                        }
+                    }
                     // this should work already
                     //cx.redraw_child_area(Area::All);
                 }
@@ -453,6 +920,53 @@ impl App {
             
             _ => ()
         }
+        for lsp_event in self.app_global.lsp_client.handle_lsp_client(cx, event, &mut self.app_global.text_buffers) {
+            match lsp_event {
+                LspClientEvent::Completion {window_index, editor_id, offset, items} => {
+                    if let Some(file_editor) = self.windows.get_mut(window_index).and_then( | window | window.file_editors.get(editor_id)) {
+                        file_editor.code_editor().show_completions(cx, offset, items);
+                    }
+                },
+                LspClientEvent::Hover {window_index, editor_id, offset, text} => {
+                    if let Some(file_editor) = self.windows.get_mut(window_index).and_then( | window | window.file_editors.get(editor_id)) {
+                        file_editor.code_editor().show_hover_info(cx, offset, text);
+                    }
+                },
+                LspClientEvent::Definition {path, pos} => {
+                    for (window_index, window) in self.windows.iter_mut().enumerate() {
+                        if window.focus_or_new_editor(cx, window_index, &mut self.app_global, &path) {
+                            break;
+                        }
+                    }
+                    let text_buffer = self.app_global.text_buffers.from_path(cx, &path);
+                    text_buffer.messages.jump_to_offset = text_buffer.text_pos_to_offset(pos);
+                    cx.send_signal_after_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET);
+                },
+                LspClientEvent::Status => {
+                    for window in &mut self.windows {
+                        window.build_status.view.redraw_view_area(cx);
+                    }
+                },
+                LspClientEvent::None => ()
+            }
+        }
+        for debugger_event in self.app_global.debugger.handle_debugger(cx, event, &mut self.app_global.text_buffers) {
+            match debugger_event {
+                DebuggerEvent::StartRequest => {
+                    self.app_global.start_debugger(cx);
+                },
+                DebuggerEvent::Stopped {path, row} => {
+                    for (window_index, window) in self.windows.iter_mut().enumerate() {
+                        if window.focus_or_new_editor(cx, window_index, &mut self.app_global, &path) {
+                            break;
+                        }
+                    }
+                    let text_buffer = self.app_global.text_buffers.from_path(cx, &path);
+                    text_buffer.messages.jump_to_offset = text_buffer.text_pos_to_offset(TextPos {row: row, col: 0});
+                    cx.send_signal_after_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_JUMP_TO_OFFSET);
+                }
+            }
+        }
         for (window_index, window) in self.windows.iter_mut().enumerate() {
             window.handle_app_window(cx, event, window_index, &mut self.app_global);
             // break;
@@ -485,8 +999,13 @@ enum FileEditor {
 #[derive(Clone)]
 enum FileEditorEvent {
     None,
+    FoldChange,
     LagChange,
-    Change
+    Change,
+    CompletionRequest {offset: usize},
+    HoverRequest {offset: usize},
+    DefinitionRequest {offset: usize},
+    BreakpointChange {row: usize}
 }
 
 impl FileEditor {
@@ -495,14 +1014,24 @@ impl FileEditor {
             FileEditor::Rust(re) => {
                 match re.handle_rust_editor(cx, event, text_buffer) {
                     CodeEditorEvent::Change => FileEditorEvent::Change,
+                    CodeEditorEvent::FoldChange => FileEditorEvent::FoldChange,
                     CodeEditorEvent::LagChange => FileEditorEvent::LagChange,
+                    CodeEditorEvent::CompletionRequest {offset} => FileEditorEvent::CompletionRequest {offset: offset},
+                    CodeEditorEvent::HoverRequest {offset} => FileEditorEvent::HoverRequest {offset: offset},
+                    CodeEditorEvent::DefinitionRequest {offset} => FileEditorEvent::DefinitionRequest {offset: offset},
+                    CodeEditorEvent::BreakpointChange {row} => FileEditorEvent::BreakpointChange {row: row},
                     _ => FileEditorEvent::None
                 }
             },
             FileEditor::JS(re) => {
                 match re.handle_js_editor(cx, event, text_buffer) {
                     CodeEditorEvent::Change => FileEditorEvent::Change,
+                    CodeEditorEvent::FoldChange => FileEditorEvent::FoldChange,
                     CodeEditorEvent::LagChange => FileEditorEvent::LagChange,
+                    CodeEditorEvent::CompletionRequest {offset} => FileEditorEvent::CompletionRequest {offset: offset},
+                    CodeEditorEvent::HoverRequest {offset} => FileEditorEvent::HoverRequest {offset: offset},
+                    CodeEditorEvent::DefinitionRequest {offset} => FileEditorEvent::DefinitionRequest {offset: offset},
+                    CodeEditorEvent::BreakpointChange {row} => FileEditorEvent::BreakpointChange {row: row},
                     _ => FileEditorEvent::None
                 }
             },
@@ -510,9 +1039,17 @@ impl FileEditor {
     }
     
     fn set_key_focus(&mut self, cx: &mut Cx) {
+        self.code_editor().set_key_focus(cx)
+    }
+    
+    fn has_key_focus(&mut self, cx: &Cx) -> bool {
+        self.code_editor().has_key_focus(cx)
+    }
+    
+    fn code_editor(&mut self) -> &mut CodeEditor {
         match self {
-            FileEditor::Rust(re) => re.code_editor.set_key_focus(cx),
-            FileEditor::JS(re) => re.code_editor.set_key_focus(cx),
+            FileEditor::Rust(re) => &mut re.code_editor,
+            FileEditor::JS(re) => &mut re.code_editor,
         }
     }
     
@@ -544,6 +1081,48 @@ impl FileEditor {
 }
 
 
+// drops the editors and diffs of files, they belong to the project that was open
+fn close_file_tabs(dock_item: &mut DockItem<Panel>) {
+    match dock_item {
+        DockItem::TabControl {current, tabs} => {
+            tabs.retain( | tab | match tab.item {
+                Panel::FileEditor {..} | Panel::DiffViewer {..} => false,
+                _ => true
+            });
+            if *current >= tabs.len() {
+                *current = tabs.len().max(1) - 1;
+            }
+        },
+        DockItem::Splitter {first, last, ..} => {
+            close_file_tabs(first);
+            close_file_tabs(last);
+        },
+        DockItem::Single(_) => ()
+    }
+}
+
+// shows the errors of a shader hot reload as messages in the text buffer, a clean reload clears them
+fn export_shader_errors(cx: &mut Cx, text_buffer: &mut TextBuffer, errors: &Vec<ShTypeError>) {
+    text_buffer.messages.clear_source(TextBufferMessageSource::Shader, text_buffer.mutation_id);
+    let mut errors = errors.clone();
+    errors.sort_by_key( | err | (err.loc.line, err.loc.col));
+    for err in errors {
+        let offset = text_buffer.text_pos_to_offset(TextPos {
+            row: (err.loc.line as usize).max(1) - 1,
+            col: (err.loc.col as usize).max(1) - 1
+        });
+        text_buffer.messages.cursors.push(TextCursor {head: offset, tail: offset, max: 0});
+        text_buffer.messages.bodies.push(TextBufferMessage {
+            level: TextBufferMessageLevel::Error,
+            source: TextBufferMessageSource::Shader,
+            body: if err.fn_name.len() > 0 {format!("{} (in fn {})", err.msg, err.fn_name)}else {err.msg},
+            fixes: Vec::new()
+        });
+    }
+    text_buffer.messages.sort();
+    cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_MESSAGE_UPDATE);
+}
+
 fn path_file_name(path: &str) -> String {
     if let Some(pos) = path.rfind('/') {
         path[pos + 1..path.len()].to_string()
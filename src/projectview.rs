@@ -0,0 +1,265 @@
+use render::*;
+use widget::*;
+use crate::workspace::*;
+
+// the open project folder with its crates, a path to open another folder and the recent ones
+#[derive(Clone)]
+pub struct ProjectView {
+    pub view: View<ScrollBar>,
+    pub item_bg: Quad,
+    pub text: Text,
+    pub row_height: f32,
+    pub colors: ProjectViewColors,
+    pub _input_area: Area,
+    pub _input: String,
+    pub _row_areas: Vec<(Area, ProjectViewEvent)>,
+    pub _hover_row: Option<usize>
+}
+
+#[derive(Clone)]
+pub struct ProjectViewColors {
+    pub bg_even: Color,
+    pub bg_odd: Color,
+    pub bg_hover: Color,
+    pub bg_selected: Color,
+    pub header: Color,
+    pub name: Color,
+    pub hint: Color,
+    pub error: Color
+}
+
+#[derive(Clone, PartialEq)]
+pub enum ProjectViewEvent {
+    None,
+    OpenFolder {path: String},
+    SelectPackage {name: String}
+}
+
+impl Style for ProjectView {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            item_bg: Quad::style(cx),
+            text: Text {
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            row_height: 20.,
+            colors: ProjectViewColors {
+                bg_even: cx.color("bg_selected"),
+                bg_odd: cx.color("bg_odd"),
+                bg_hover: cx.color("bg_selected_over"),
+                bg_selected: color256(38, 79, 120),
+                header: color256(91, 155, 211),
+                name: color256(212, 212, 212),
+                hint: color("#777"),
+                error: color256(229, 20, 0)
+            },
+            _input_area: Area::Empty,
+            _input: String::new(),
+            _row_areas: Vec::new(),
+            _hover_row: None
+        }
+    }
+}
+
+impl ProjectView {
+    pub fn handle_project_view(&mut self, cx: &mut Cx, event: &mut Event, workspace: &CargoWorkspace) -> ProjectViewEvent {
+        if workspace.is_workspace_event(event) {
+            self.view.redraw_view_area(cx);
+        }
+        self.view.handle_scroll_bars(cx, event);
+        
+        let mut hover_row = self._hover_row;
+        for (index, (area, row_event)) in self._row_areas.iter().enumerate() {
+            match event.hits(cx, *area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    return row_event.clone()
+                },
+                Event::FingerHover(fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                    if let HoverState::Out = fe.hover_state {
+                        if hover_row == Some(index) {
+                            hover_row = None;
+                        }
+                    }
+                    else {
+                        hover_row = Some(index);
+                    }
+                },
+                _ => ()
+            }
+        }
+        if hover_row != self._hover_row {
+            self._hover_row = hover_row;
+            self.view.redraw_view_area(cx);
+        }
+        
+        match event.hits(cx, self._input_area, HitOpt::default()) {
+            Event::FingerDown(_fe) => {
+                cx.set_key_focus(self._input_area);
+            },
+            Event::FingerHover(_fe) => {
+                cx.set_hover_mouse_cursor(MouseCursor::Text);
+            },
+            Event::KeyFocus(_) | Event::KeyFocusLost(_) => {
+                self.view.redraw_view_area(cx);
+            },
+            Event::KeyDown(ke) => {
+                match ke.key_code {
+                    KeyCode::Return => if self._input.trim().len() > 0 {
+                        let path = self._input.trim().to_string();
+                        self._input.truncate(0);
+                        self.view.redraw_view_area(cx);
+                        return ProjectViewEvent::OpenFolder {path: path}
+                    },
+                    KeyCode::Backspace => {
+                        self._input.pop();
+                        self.view.redraw_view_area(cx);
+                    },
+                    KeyCode::Escape => {
+                        self._input.truncate(0);
+                        self.view.redraw_view_area(cx);
+                    },
+                    _ => ()
+                }
+            },
+            Event::TextInput(te) => {
+                let input: String = te.input.chars().filter( | ch | *ch != '\n' && *ch != '\r').collect();
+                self._input.push_str(&input);
+                self.view.redraw_view_area(cx);
+            },
+            _ => ()
+        }
+        ProjectViewEvent::None
+    }
+    
+    fn begin_row(&mut self, cx: &mut Cx, row: usize, color: Option<Color>) -> InstanceArea {
+        self.item_bg.color = if let Some(color) = color {color}
+        else if row & 1 == 0 {self.colors.bg_even}
+        else {self.colors.bg_odd};
+        self.item_bg.begin_quad(cx, &Layout {
+            width: Bounds::Fill,
+            height: Bounds::Fix(self.row_height),
+            padding: Padding {l: 4., t: 3., b: 2., r: 0.},
+            ..Default::default()
+        })
+    }
+    
+    pub fn draw_project_view(&mut self, cx: &mut Cx, root_path: &str, workspace: &CargoWorkspace, recent: &[String], package: Option<&String>) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        self._row_areas.truncate(0);
+        let mut row = 0;
+        
+        let inst = self.begin_row(cx, row, None);
+        self.text.color = self.colors.header;
+        self.text.draw_text(cx, "Folder ");
+        self.text.color = self.colors.name;
+        self.text.draw_text(cx, root_path);
+        self.item_bg.end_quad(cx, &inst);
+        cx.turtle_new_line();
+        row += 1;
+        
+        // type a path and press return to open it
+        let inst = self.begin_row(cx, row, None);
+        self.text.color = self.colors.header;
+        self.text.draw_text(cx, "Open ");
+        self.text.color = self.colors.name;
+        let caret = if cx.has_key_focus(self._input_area) {"_"}else {""};
+        if self._input.len() == 0 && caret.len() == 0 {
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, "click to type a folder path");
+        }
+        else {
+            self.text.draw_text(cx, &format!("{}{}", self._input, caret));
+        }
+        let input_area = self.item_bg.end_quad(cx, &inst);
+        cx.update_area_refs(self._input_area, input_area);
+        self._input_area = input_area;
+        cx.turtle_new_line();
+        row += 1;
+        
+        let inst = self.begin_row(cx, row, None);
+        self.text.color = self.colors.header;
+        self.text.draw_text(cx, "Crates");
+        if workspace.is_loading() {
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, " loading");
+        }
+        else if let Some(error) = &workspace.error {
+            self.text.color = self.colors.error;
+            self.text.draw_text(cx, &format!(" {}", error));
+        }
+        else {
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, " click one to build only that");
+        }
+        self.item_bg.end_quad(cx, &inst);
+        cx.turtle_new_line();
+        row += 1;
+        
+        let mut packages: Vec<&CargoPackage> = workspace.packages.iter().collect();
+        packages.sort_by( | a, b | a.root.cmp(&b.root));
+        for pkg in packages {
+            let color = if self._hover_row == Some(self._row_areas.len()) {
+                Some(self.colors.bg_hover)
+            }
+            else if package == Some(&pkg.name) {
+                Some(self.colors.bg_selected)
+            }
+            else {
+                None
+            };
+            let inst = self.begin_row(cx, row, color);
+            self.text.color = self.colors.name;
+            self.text.draw_text(cx, &format!("  {}", pkg.name));
+            self.text.color = self.colors.hint;
+            self.text.draw_text(cx, &format!(" {}", if pkg.root.len() > 0 {&pkg.root}else {"./"}));
+            let area = self.item_bg.end_quad(cx, &inst);
+            self._row_areas.push((area, ProjectViewEvent::SelectPackage {name: pkg.name.clone()}));
+            cx.turtle_new_line();
+            row += 1;
+        }
+        
+        if recent.len() > 1 {
+            let inst = self.begin_row(cx, row, None);
+            self.text.color = self.colors.header;
+            self.text.draw_text(cx, "Recent");
+            self.item_bg.end_quad(cx, &inst);
+            cx.turtle_new_line();
+            row += 1;
+            for path in recent.iter().filter( | path | *path != root_path) {
+                let color = if self._hover_row == Some(self._row_areas.len()) {Some(self.colors.bg_hover)}else {None};
+                let inst = self.begin_row(cx, row, color);
+                self.text.color = self.colors.name;
+                self.text.draw_text(cx, &format!("  {}", path));
+                let area = self.item_bg.end_quad(cx, &inst);
+                self._row_areas.push((area, ProjectViewEvent::OpenFolder {path: path.clone()}));
+                cx.turtle_new_line();
+                row += 1;
+            }
+        }
+        
+        // fill up the rest of the view
+        let view_total = cx.get_turtle_bounds();
+        let rect_now = cx.get_turtle_rect();
+        let mut y = view_total.y;
+        while y < rect_now.h {
+            self.item_bg.color = if row & 1 == 0 {self.colors.bg_even}else {self.colors.bg_odd};
+            self.item_bg.draw_quad_walk(cx, Bounds::Fill, Bounds::Fix((rect_now.h - y).min(self.row_height)), Margin::zero());
+            cx.turtle_new_line();
+            y += self.row_height;
+            row += 1;
+        }
+        self.view.end_view(cx);
+    }
+}
@@ -1,12 +1,11 @@
 use render::*;
 use widget::*;
 use editor::*;
+use crate::console::*;
+use crate::buildqueue::*;
+use crate::workspace::*;
 
-use std::io::Read;
-use std::sync::mpsc;
-use std::process::{Child};
-
-use serde_json::{Result};
+use serde_json::{Result, Value};
 use serde::*;
 
 //#[derive(Clone)]
@@ -19,44 +18,151 @@ pub struct RustCompiler {
     pub row_height: f32,
     pub path_color: Color,
     pub message_color: Color,
-    pub _check_signal: Signal,
+    pub workdir: String,
+    pub build_config: RustBuildConfig,
+    pub console: Console,
+    pub build_queue: BuildQueue,
+    pub workspace: CargoWorkspace,
     
-    pub _check_child: Option<Child>,
-    pub _build_child: Option<Child>,
-    pub _run_child: Option<Child>,
-    pub _run_when_done: bool,
-    pub _program_running: bool,
+    pub _test_results: Vec<RustTestResult>,
+    pub _test_rows: Vec<RustTestRow>,
+    pub _test_running: bool,
+    pub _test_error: Option<String>,
     pub _messages_updated: bool,
     
-    pub _rustc_build_stages: BuildStage,
-    pub _rx: Option<mpsc::Receiver<std::vec::Vec<u8>>>,
-    
-    pub _thread: Option<std::thread::JoinHandle<()>>,
-    
-    pub _data: Vec<String>,
     pub _visible_window: (usize, usize),
     pub _draw_messages: Vec<RustDrawMessage>,
     // pub _rustc_spans:Vec<RustcSpan>,
     pub _rustc_messages: Vec<RustcCompilerMessage>,
     pub _rustc_artifacts: Vec<RustcCompilerArtifact>,
     pub _rustc_done: bool,
+    // the binary of the last debug build, for the debugger
+    pub _debug_executable: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RustBuildProfile {
+    Debug,
+    Release,
+    Custom(String)
 }
 
-const SIGNAL_RUST_CHECKER: usize = 1;
-const SIGNAL_BUILD_COMPLETE: usize = 2;
-const SIGNAL_RUN_OUTPUT: usize = 3;
+// the cargo flags shared by check, build, run and test
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RustBuildConfig {
+    pub profile: RustBuildProfile,
+    pub target: Option<String>,
+    pub package: Option<String>,
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub use_clippy: bool
+}
 
-#[derive(PartialEq, Debug)]
-pub enum BuildStage {
-    NotRunning,
-    Building,
-    Complete
+impl Default for RustBuildConfig {
+    fn default() -> Self {
+        Self {
+            profile: RustBuildProfile::Release,
+            target: None,
+            package: None,
+            features: Vec::new(),
+            all_features: false,
+            no_default_features: false,
+            use_clippy: false
+        }
+    }
+}
+
+impl RustBuildConfig {
+    pub fn cargo_args(&self, command: &str, message_json: bool) -> Vec<String> {
+        let mut args = vec![command.to_string()];
+        if message_json {
+            args.push("--message-format=json".to_string());
+        }
+        // the diagnostics don't depend on the profile, so checking stays a quick debug check
+        let is_check = command == "check" || command == "clippy";
+        if !is_check {
+            match &self.profile {
+                RustBuildProfile::Debug => (),
+                RustBuildProfile::Release => args.push("--release".to_string()),
+                RustBuildProfile::Custom(name) => {
+                    args.push("--profile".to_string());
+                    args.push(name.clone());
+                }
+            }
+        }
+        if let Some(target) = &self.target {
+            args.push("--target".to_string());
+            args.push(target.clone());
+        }
+        if let Some(package) = &self.package {
+            args.push("-p".to_string());
+            args.push(package.clone());
+        }
+        if self.features.len() > 0 {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        args
+    }
+    
+    pub fn describe(&self) -> String {
+        let mut out = match &self.profile {
+            RustBuildProfile::Debug => "debug".to_string(),
+            RustBuildProfile::Release => "release".to_string(),
+            RustBuildProfile::Custom(name) => name.clone()
+        };
+        if let Some(target) = &self.target {
+            out.push_str(&format!(", {}", target));
+        }
+        if self.features.len() > 0 {
+            out.push_str(&format!(", features: {}", self.features.join(",")));
+        }
+        if self.use_clippy {
+            out.push_str(", clippy");
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RustTestState {
+    Running,
+    Ok,
+    Failed,
+    Ignored
+}
+
+#[derive(Clone)]
+pub struct RustTestResult {
+    pub name: String,
+    pub state: RustTestState,
+    pub exec_time: Option<f64>,
+    pub stdout: String
+}
+
+// a row in the test tree, either a module or a test
+#[derive(Clone)]
+pub struct RustTestRow {
+    pub label: String,
+    pub name: String,
+    pub depth: usize,
+    pub state: RustTestState,
+    pub is_test: bool,
+    pub area: Area
 }
 
 #[derive(Clone)]
 pub struct RustDrawMessage {
     animator: Animator,
     path: String,
+    package: String,
     body: String,
     more_lines: Vec<String>,
     row: usize,
@@ -64,12 +170,37 @@ pub struct RustDrawMessage {
     head: usize,
     tail: usize,
     level: TextBufferMessageLevel,
+    fixes: Vec<RustFix>,
     is_selected: bool
 }
 
+// a machine applicable suggestion from rustc, rows and cols are 0 based
+#[derive(Clone, PartialEq)]
+pub struct RustFix {
+    path: String,
+    start_row: usize,
+    start_col: usize,
+    end_row: usize,
+    end_col: usize,
+    replacement: String
+}
+
+impl RustFix {
+    fn to_text_fix(&self, text_buffer: &TextBuffer) -> TextFix {
+        TextFix {
+            start: text_buffer.text_pos_to_offset(TextPos {row: self.start_row, col: self.start_col}),
+            end: text_buffer.text_pos_to_offset(TextPos {row: self.end_row, col: self.end_col}),
+            replacement: self.replacement.clone()
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum RustCompilerEvent {
     SelectMessage {path: String},
+    SelectTest {name: String},
+    ApplyFixes {paths: Vec<String>},
+    DebugBuildDone {executable: Option<String>},
     None,
 }
 
@@ -102,32 +233,49 @@ impl Style for RustCompiler {
             path_color: color("#999"),
             message_color: color("#bbb"),
             row_height: 20.0,
-            _check_signal: Signal::empty(),
-            _check_child: None,
-            _build_child: None,
-            _run_child: None,
-            _run_when_done: false,
-            _program_running: false,
-            _rustc_build_stages: BuildStage::NotRunning,
-            _thread: None,
-            _rx: None,
+            workdir: "./edit_repo".to_string(),
+            build_config: RustBuildConfig::default(),
+            console: Console::style(cx),
+            build_queue: BuildQueue::new(cx),
+            workspace: CargoWorkspace::new(cx),
+            _test_results: Vec::new(),
+            _test_rows: Vec::new(),
+            _test_running: false,
+            _test_error: None,
             //_rustc_spans:Vec::new(),
             _draw_messages: Vec::new(),
             _messages_updated: true,
             _visible_window: (0, 0),
             _rustc_messages: Vec::new(),
             _rustc_artifacts: Vec::new(),
+            _debug_executable: None,
             _rustc_done: false,
             //_items:Vec::new(),
-            _data: Vec::new()
         }
     }
 }
 
 impl RustCompiler {
     pub fn init(&mut self, cx: &mut Cx, text_buffers: &mut TextBuffers) {
-        self._check_signal = cx.new_signal();
-        self.restart_rust_checker(cx, text_buffers);
+        self.build_queue.workdir = self.workdir.clone();
+        // the first check waits for the crate roots to map its paths
+        self.workspace.workdir = self.workdir.clone();
+        self.workspace.load();
+        if !self.workspace.is_loading() {
+            self.restart_rust_checker(cx, text_buffers);
+        }
+    }
+    
+    // moves all cargo invocations to another project folder
+    pub fn set_workdir(&mut self, cx: &mut Cx, workdir: &str, text_buffers: &mut TextBuffers) {
+        self.build_queue.cancel_all(cx);
+        self.console.kill_child();
+        self._debug_executable = None;
+        self._test_results.truncate(0);
+        self.update_test_rows();
+        self.workdir = workdir.to_string();
+        self.init(cx, text_buffers);
+        self.view.redraw_view_area(cx);
     }
     
     pub fn get_default_anim(cx: &Cx, counter: usize, marked: bool) -> Anim {
@@ -150,11 +298,12 @@ impl RustCompiler {
         // clear all files we missed
         for (_, text_buffer) in &mut text_buffers.storage {
             if text_buffer.messages.gc_id != cx.event_id {
-                text_buffer.messages.cursors.truncate(0);
-                text_buffer.messages.bodies.truncate(0);
+                let mutation_id = text_buffer.messages.mutation_id;
+                text_buffer.messages.clear_source(TextBufferMessageSource::Compiler, mutation_id);
                 cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_MESSAGE_UPDATE);
             }
             else {
+                text_buffer.messages.sort();
                 cx.send_signal_before_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_MESSAGE_UPDATE);
             }
         }
@@ -167,11 +316,9 @@ impl RustCompiler {
             let text_buffer = text_buffers.from_path(cx, &dm.path);
             
             let messages = &mut text_buffer.messages;
-            messages.mutation_id = text_buffer.mutation_id;
             if messages.gc_id != cx.event_id {
                 messages.gc_id = cx.event_id;
-                messages.cursors.truncate(0);
-                messages.bodies.truncate(0);
+                messages.clear_source(TextBufferMessageSource::Compiler, text_buffer.mutation_id);
             }
             
             if dm.level == TextBufferMessageLevel::Log {
@@ -193,14 +340,17 @@ impl RustCompiler {
             }
             
             //println!("PROCESING MESSAGES FOR {} {} {}", span.byte_start, span.byte_end+1, path);
+            let fixes = dm.fixes.iter().filter( | fix | fix.path == dm.path).map( | fix | fix.to_text_fix(text_buffer)).collect();
             text_buffer.messages.bodies.push(TextBufferMessage {
                 body: dm.body.clone(),
-                level: dm.level.clone()
+                level: dm.level.clone(),
+                source: TextBufferMessageSource::Compiler,
+                fixes: fixes
             });
             //}
         }
         self.clear_textbuffer_messages(cx, text_buffers);
-        
+    
     }
     
     pub fn handle_rust_compiler(&mut self, cx: &mut Cx, event: &mut Event, text_buffers: &mut TextBuffers) -> RustCompilerEvent {
@@ -210,19 +360,16 @@ impl RustCompiler {
         }
         
         let mut dm_to_select = None;
+        let mut fix_to_apply = None;
+        let mut debug_build_done = None;
         
         match event {
             Event::KeyDown(ke) => match ke.key_code {
                 KeyCode::F9 => {
-                    if self._rustc_build_stages == BuildStage::Complete {
-                        self.run_program();
-                        println!("RUNNING PROGRAM!");
-                    }
-                    else {
-                        println!("BUILDSTAGE {:?}", self._rustc_build_stages);
-                        self._run_when_done = true;
-                    }
-                    self.view.redraw_view_area(cx);
+                    self.run_program(cx);
+                },
+                KeyCode::F10 => {
+                    self.run_tests(cx);
                 },
                 KeyCode::F8 => { // next error
                     if self._draw_messages.len() > 0 {
@@ -268,38 +415,58 @@ impl RustCompiler {
                 },
                 _ => ()
             },
-            Event::Signal(se) => {
-                if self._check_signal.is_signal(se) {
-                    match se.value {
-                        SIGNAL_RUST_CHECKER | SIGNAL_RUN_OUTPUT => {
-                            let mut datas = Vec::new();
-                            if let Some(rx) = &self._rx {
-                                while let Ok(data) = rx.try_recv() {
-                                    datas.push(data);
-                                }
-                            }
-                            if datas.len() > 0 {
-                                if se.value == SIGNAL_RUST_CHECKER {
-                                    self.process_compiler_messages(cx, datas);
-                                    self.export_messages(cx, text_buffers);
-                                }
-                                else {
-                                    self.process_run_messages(cx, datas);
-                                }
-                            }
-                        },
-                        SIGNAL_BUILD_COMPLETE => {
-                            self._rustc_build_stages = BuildStage::Complete;
-                            if self._run_when_done {
-                                self.run_program();
-                            }
-                            self.view.redraw_view_area(cx);
-                        },
-                        _ => ()
+            _ => ()
+        }
+        
+        if self.workspace.handle_workspace(cx, event) {
+            self.restart_rust_checker(cx, text_buffers);
+        }
+        if self.build_queue.is_build_queue_event(event) {
+            // the elapsed times tick
+            self.view.redraw_view_area(cx);
+        }
+        for queue_event in self.build_queue.handle_build_queue(cx, event) {
+            match queue_event {
+                BuildQueueEvent::Started {kind} => {
+                    // a running program keeps its executable locked
+                    if kind == BuildJobKind::Build || kind == BuildJobKind::DebugBuild {
+                        self.console.kill_child();
                     }
+                },
+                BuildQueueEvent::Output {kind, lines} => match kind {
+                    BuildJobKind::Check => {
+                        self.process_compiler_messages(cx, lines);
+                        self.export_messages(cx, text_buffers);
+                    },
+                    BuildJobKind::DebugBuild => {
+                        self.process_build_artifacts(lines);
+                    },
+                    BuildJobKind::Test => {
+                        self.process_test_messages(cx, lines);
+                    },
+                    _ => ()
+                },
+                BuildQueueEvent::Finished {kind, state} => match kind {
+                    BuildJobKind::Check => {
+                        self._rustc_done = !self.build_queue.is_pending(BuildJobKind::Check);
+                    },
+                    BuildJobKind::DebugBuild => if state != BuildJobState::Canceled {
+                        debug_build_done = Some(if state == BuildJobState::Succeeded {self._debug_executable.clone()}else {None});
+                    },
+                    BuildJobKind::Test => {
+                        self._test_running = self.build_queue.is_pending(BuildJobKind::Test);
+                        // on a stable toolchain libtest refuses the json format and nothing gets parsed
+                        if state == BuildJobState::Failed && self._test_results.len() == 0 {
+                            self._test_error = Some("cargo test reported no tests, see the build errors or use a nightly toolchain for the json test output".to_string());
+                        }
+                    },
+                    _ => ()
+                },
+                BuildQueueEvent::StartRun {child, title} => {
+                    self.console.start_child(cx, child, &title);
                 }
-            },
-            _ => ()
+            }
+            self.view.redraw_view_area(cx);
         }
         
         //let mut unmark_nodes = false;
@@ -308,10 +475,13 @@ impl RustCompiler {
                 Event::Animate(ae) => {
                     dm.animator.calc(cx, ae.time, dm.animator.area, "bg.color");
                 },
-                Event::FingerDown(_fe) => {
+                Event::FingerDown(fe) => {
                     cx.set_down_mouse_cursor(MouseCursor::Hand);
                     // mark ourselves, unmark others
                     dm_to_select = Some(counter);
+                    if fe.tap_count == 2 && dm.fixes.len() > 0 {
+                        fix_to_apply = Some(counter);
+                    }
                 },
                 Event::FingerUp(_fe) => {
                 },
@@ -333,6 +503,32 @@ impl RustCompiler {
             }
         };
         
+        for row in &self._test_rows {
+            match event.hits(cx, row.area, HitOpt::default()) {
+                Event::FingerDown(_fe) => {
+                    cx.set_down_mouse_cursor(MouseCursor::Hand);
+                    if row.is_test {
+                        return RustCompilerEvent::SelectTest {name: row.name.clone()}
+                    }
+                },
+                Event::FingerHover(_fe) => {
+                    cx.set_hover_mouse_cursor(MouseCursor::Hand);
+                },
+                _ => ()
+            }
+        }
+        
+        if let Some(executable) = debug_build_done {
+            return RustCompilerEvent::DebugBuildDone {executable: executable}
+        }
+        
+        if let Some(fix_to_apply) = fix_to_apply {
+            let paths = self.apply_fixes(cx, fix_to_apply, text_buffers);
+            if paths.len() > 0 {
+                return RustCompilerEvent::ApplyFixes {paths: paths}
+            }
+        }
+        
         if let Some(dm_to_select) = dm_to_select {
             
             for (counter, dm) in self._draw_messages.iter_mut().enumerate() {
@@ -393,6 +589,10 @@ impl RustCompiler {
             }
             
             self.text.color = self.path_color;
+            // name the crate when there is more than one
+            if self.workspace.packages.len() > 1 && dm.package.len() > 0 {
+                self.text.draw_text(cx, &format!("[{}] ", dm.package));
+            }
             self.text.draw_text(cx, &format!("{}:{} - ", dm.path, dm.row));
             let walk = cx.get_rel_turtle_walk();
             cx.set_turtle_padding(Padding {l: walk.x, t: 3., b: 2., r: 0.});
@@ -412,13 +612,89 @@ impl RustCompiler {
             cx.turtle_new_line();
             
             counter += 1;
-            
-            
+        
+        
         }
         
         let bg_even = cx.color("bg_selected");
         let bg_odd = cx.color("bg_odd");
         
+        // the test tree
+        for row in &mut self._test_rows {
+            self.item_bg.color = if counter & 1 == 0 {bg_even}else {bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Compute,
+                padding: Padding {l: 2. + (row.depth as f32) * 12., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.code_icon.draw_icon_walk(cx, match row.state {
+                RustTestState::Running => CodeIconType::Wait,
+                RustTestState::Ok => CodeIconType::Ok,
+                RustTestState::Failed => CodeIconType::Error,
+                RustTestState::Ignored => CodeIconType::Warning
+            });
+            self.text.color = if row.is_test {self.message_color}else {self.path_color};
+            self.text.draw_text(cx, &row.label);
+            row.area = self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+            counter += 1;
+        }
+        if let Some(test_error) = &self._test_error {
+            self.item_bg.color = if counter & 1 == 0 {bg_even}else {bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Compute,
+                padding: Padding {l: 2., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.code_icon.draw_icon_walk(cx, CodeIconType::Error);
+            self.text.color = self.message_color;
+            self.text.draw_text(cx, test_error);
+            self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+            counter += 1;
+        }
+        else if self._test_running || self._test_results.len() > 0 {
+            self.item_bg.color = if counter & 1 == 0 {bg_even}else {bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Compute,
+                padding: Padding {l: 2., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            let count = | state | self._test_results.iter().filter( | result | result.state == state).count();
+            let (passed, failed, ignored) = (count(RustTestState::Ok), count(RustTestState::Failed), count(RustTestState::Ignored));
+            self.code_icon.draw_icon_walk(cx, if self._test_running {CodeIconType::Wait} else if failed > 0 {CodeIconType::Error} else {CodeIconType::Ok});
+            self.text.color = self.path_color;
+            self.text.draw_text(cx, &format!("{}{} passed, {} failed, {} ignored", if self._test_running {"Testing: "}else {"Tests: "}, passed, failed, ignored));
+            self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+            counter += 1;
+        }
+        
+        // one row per job in the build queue
+        for job in &self.build_queue.jobs {
+            self.item_bg.color = if counter & 1 == 0 {bg_even}else {bg_odd};
+            let bg_inst = self.item_bg.begin_quad(cx, &Layout {
+                width: Bounds::Fill,
+                height: Bounds::Compute,
+                padding: Padding {l: 2., t: 3., b: 2., r: 0.},
+                ..Default::default()
+            });
+            self.code_icon.draw_icon_walk(cx, match job.state {
+                BuildJobState::Queued | BuildJobState::Running => CodeIconType::Wait,
+                BuildJobState::Succeeded => CodeIconType::Ok,
+                BuildJobState::Failed => CodeIconType::Error,
+                BuildJobState::Canceled => CodeIconType::Warning
+            });
+            self.text.color = if job.is_done() {self.path_color}else {self.message_color};
+            self.text.draw_text(cx, &job.describe());
+            self.item_bg.end_quad(cx, &bg_inst);
+            cx.turtle_new_line();
+            counter += 1;
+        }
+        
         self.item_bg.color = if counter & 1 == 0 {bg_even}else {bg_odd};
         let bg_inst = self.item_bg.begin_quad(cx, &Layout {
             width: Bounds::Fill,
@@ -426,39 +702,22 @@ impl RustCompiler {
             padding: Padding {l: 2., t: 3., b: 2., r: 0.},
             ..Default::default()
         });
-        if self._rustc_done == true {
-            self.text.color = self.path_color;
-            match self._rustc_build_stages {
-                BuildStage::NotRunning => {
-                    self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
-                    self.text.draw_text(cx, "Done");
-                }
-                BuildStage::Building => {
-                    if self._run_when_done {
-                        self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
-                        self.text.draw_text(cx, "Running when ready");
-                    }
-                    else {
-                        self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
-                        self.text.draw_text(cx, "Building");
-                    }
-                },
-                BuildStage::Complete => {
-                    if !self._program_running {
-                        self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
-                        self.text.draw_text(cx, "Press F9 to run");
-                    }
-                    else if self._draw_messages.len() == 0 {
-                        self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
-                        self.text.draw_text(cx, "Application running");
-                    }
-                }
-            };
+        self.text.color = self.path_color;
+        if self.console.is_running() {
+            self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
+            self.text.draw_text(cx, "Application running");
         }
-        else {
+        else if self.build_queue.is_busy() {
             self.code_icon.draw_icon_walk(cx, CodeIconType::Wait);
-            self.text.color = self.path_color;
-            self.text.draw_text(cx, &format!("Checking({})", self._rustc_artifacts.len()));
+            self.text.draw_text(cx, &format!("Working ({})", self.build_config.describe()));
+        }
+        else if self.build_queue.last_job(BuildJobKind::Build).map_or(false, | job | job.state == BuildJobState::Succeeded) {
+            self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
+            self.text.draw_text(cx, &format!("Press F9 to run ({})", self.build_config.describe()));
+        }
+        else {
+            self.code_icon.draw_icon_walk(cx, CodeIconType::Ok);
+            self.text.draw_text(cx, &format!("Done ({})", self.build_config.describe()));
         }
         self.item_bg.end_quad(cx, &bg_inst);
         cx.turtle_new_line();
@@ -482,292 +741,305 @@ impl RustCompiler {
         if self._messages_updated {
             self._messages_updated = false;
             // scroll to bottom
-            
+        
         }
     }
     
-    pub fn start_rust_builder(&mut self) {
-        if let Some(child) = &mut self._build_child {
-            let _ = child.kill();
-        }
-        if let Some(child) = &mut self._run_child {
-            let _ = child.kill();
-        }
-        
-        // start a release build
-        self._rustc_build_stages = BuildStage::Building;
-        
-        let mut _child = spawn_process_command("cargo", &["build", "--release", "--message-format=json"], "./edit_repo");
-        
-        if let Err(_) = _child {
-            return;
+    pub fn run_program(&mut self, cx: &mut Cx) {
+        // run after our own build so its errors show up here and not in the console
+        if !self.build_queue.is_pending(BuildJobKind::Build) && self.build_queue.last_job(BuildJobKind::Build).map_or(true, | job | job.state != BuildJobState::Succeeded) {
+            let args = self.build_config.cargo_args("build", true);
+            self.build_queue.enqueue(cx, BuildJobKind::Build, args, Some(BuildJobKind::Check));
         }
-        
-        let mut child = _child.unwrap();
-        
-        let mut stdout = child.stdout.take().unwrap();
-        let signal = self._check_signal;
-        std::thread::spawn(move || {
-            loop {
-                let mut data = vec![0; 4096];
-                let n_bytes_read = stdout.read(&mut data).expect("cannot read");
-                data.truncate(n_bytes_read);
-                if n_bytes_read == 0 {
-                    Cx::send_signal(signal, SIGNAL_BUILD_COMPLETE);
-                    return
-                }
-            }
-        });
-        self._build_child = Some(child);
+        self.build_queue.cancel(cx, BuildJobKind::Run);
+        let args = self.build_config.cargo_args("run", false);
+        self.build_queue.enqueue(cx, BuildJobKind::Run, args, Some(BuildJobKind::Build));
+        self.view.redraw_view_area(cx);
     }
     
-    pub fn run_program(&mut self) {
-        self._run_when_done = false;
-        self._program_running = true;
-        if let Some(child) = &mut self._run_child {
-            let _ = child.kill();
-        }
-        
-        let mut _child = spawn_process_command("cargo", &["run", "--release"], "./edit_repo");
-        
-        let mut child = _child.unwrap();
-        
-        let stdout_opt = child.stdout.take();
-        
-        if let None = stdout_opt {
-            return;
-        }
-        
-        let mut stdout = stdout_opt.unwrap();
-        
-        let (tx, rx) = mpsc::channel();
-        let signal = self._check_signal;
-        let thread = std::thread::spawn(move || {
-            loop {
-                let mut data = vec![0; 4096];
-                let n_bytes_read = stdout.read(&mut data).expect("cannot read");
-                data.truncate(n_bytes_read);
-                let _ = tx.send(data);
-                Cx::send_signal(signal, SIGNAL_RUN_OUTPUT);
-                if n_bytes_read == 0 {
-                    return
-                }
-            }
-        });
-        self._rx = Some(rx);
-        self._thread = Some(thread);
-        self._run_child = Some(child);
+    pub fn cancel_jobs(&mut self, cx: &mut Cx) {
+        self.build_queue.cancel_all(cx);
+        self._test_running = false;
+        self.view.redraw_view_area(cx);
     }
     
     pub fn restart_rust_checker(&mut self, cx: &mut Cx, text_buffers: &mut TextBuffers) {
-        self._data.truncate(0);
         self._rustc_messages.truncate(0);
         self._rustc_artifacts.truncate(0);
         self._draw_messages.truncate(0);
         self._rustc_done = false;
-        self._rustc_build_stages = BuildStage::NotRunning;
-        self._data.push(String::new());
         self.clear_textbuffer_messages(cx, text_buffers);
         
-        if let Some(child) = &mut self._check_child {
-            let _ = child.kill();
-        }
-        
-        if let Some(child) = &mut self._build_child {
-            let _ = child.kill();
-        }
-        
-        let mut _child = spawn_process_command("cargo", &["check", "--message-format=json"], "./edit_repo");
-        
-        if let Err(_) = _child {
-            return;
-        }
-        
-        let mut child = _child.unwrap();
-        
-        let stdout_opt = child.stdout.take();
+        self.build_queue.cancel(cx, BuildJobKind::Check);
+        self.build_queue.cancel(cx, BuildJobKind::Build);
+        // a run that was waiting moves behind the new build
+        let rerun = self.build_queue.cancel(cx, BuildJobKind::Run) > 0;
         
-        if let None = stdout_opt {
-            return;
+        let args = self.build_config.cargo_args(if self.build_config.use_clippy {"clippy"}else {"check"}, true);
+        self.build_queue.enqueue(cx, BuildJobKind::Check, args, None);
+        // the build starts when the check had no errors
+        let args = self.build_config.cargo_args("build", true);
+        self.build_queue.enqueue(cx, BuildJobKind::Build, args, Some(BuildJobKind::Check));
+        if rerun {
+            let args = self.build_config.cargo_args("run", false);
+            self.build_queue.enqueue(cx, BuildJobKind::Run, args, Some(BuildJobKind::Build));
         }
-        
-        let mut stdout = stdout_opt.unwrap();
-        
-        let (tx, rx) = mpsc::channel();
-        let signal = self._check_signal;
-        let thread = std::thread::spawn(move || {
-            loop {
-                let mut data = vec![0; 4096];
-                let n_bytes_read = stdout.read(&mut data).expect("cannot read");
-                data.truncate(n_bytes_read);
-                let _ = tx.send(data);
-                Cx::send_signal(signal, SIGNAL_RUST_CHECKER);
-                if n_bytes_read == 0 {
-                    return
+        self.view.redraw_view_area(cx);
+    }
+    
+    // the release profile has no debug info worth stepping through, so the debugger gets its own debug build
+    pub fn build_for_debugger(&mut self, cx: &mut Cx) {
+        self.build_queue.cancel(cx, BuildJobKind::DebugBuild);
+        self._debug_executable = None;
+        let build_config = RustBuildConfig {
+            profile: RustBuildProfile::Debug,
+            ..self.build_config.clone()
+        };
+        let args = build_config.cargo_args("build", true);
+        self.build_queue.enqueue(cx, BuildJobKind::DebugBuild, args, None);
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn process_build_artifacts(&mut self, lines: Vec<String>) {
+        for line in lines {
+            if line.contains("\"reason\":\"compiler-artifact\"") {
+                if let Ok(artifact) = serde_json::from_str::<RustcCompilerArtifact>(&line) {
+                    if artifact.executable.is_some() && artifact.target.kind.iter().any( | kind | kind == "bin") {
+                        self._debug_executable = artifact.executable;
+                    }
                 }
             }
-        });
-        self._rx = Some(rx);
-        self._thread = Some(thread);
-        self._check_child = Some(child);
+        }
     }
     
-    pub fn process_compiler_messages(&mut self, cx: &mut Cx, datas: Vec<Vec<u8>>) {
-        for data in datas {
-            if data.len() == 0 { // last event
-                self._rustc_done = true;
-                // the check is done, do we have any errors? ifnot start a release build
-                let mut has_errors = false;
-                for dm in &self._draw_messages {
-                    if dm.level == TextBufferMessageLevel::Error {
-                        has_errors = true;
-                        break;
+    pub fn process_compiler_messages(&mut self, cx: &mut Cx, lines: Vec<String>) {
+        for line in lines {
+            if line.contains("\"reason\":\"compiler-artifact\"") {
+                let parsed: Result<RustcCompilerArtifact> = serde_json::from_str(&line);
+                match parsed {
+                    Err(err) => println!("JSON PARSE ERROR {:?} {}", err, line),
+                    Ok(parsed) => {
+                        self._rustc_artifacts.push(parsed);
                     }
                 }
-                if !has_errors { // start release build
-                    self.start_rust_builder();
-                }
                 self.view.redraw_view_area(cx);
             }
-            else {
-                for ch in data {
-                    if ch == '\n' as u8 {
-                        // parse it
-                        let line = self._data.last_mut().unwrap();
-                        // parse the line
-                        if line.contains("\"reason\":\"compiler-artifact\"") {
-                            let parsed: Result<RustcCompilerArtifact> = serde_json::from_str(line);
-                            match parsed {
-                                Err(err) => println!("JSON PARSE ERROR {:?} {}", err, line),
-                                Ok(parsed) => {
-                                    self._rustc_artifacts.push(parsed);
+            else if line.contains("\"reason\":\"compiler-message\"") {
+                let parsed: Result<RustcCompilerMessage> = serde_json::from_str(&line);
+                match parsed {
+                    Err(err) => println!("JSON PARSE ERROR {:?} {}", err, line),
+                    Ok(parsed) => {
+                        let spans = &parsed.message.spans;
+                        if spans.len() > 0 {
+                            for i in 0..spans.len() {
+                                let mut span = spans[i].clone();
+                                if !span.is_primary {
+                                    continue
                                 }
-                            }
-                            self.view.redraw_view_area(cx);
-                        }
-                        else if line.contains("\"reason\":\"compiler-message\"") {
-                            let parsed: Result<RustcCompilerMessage> = serde_json::from_str(line);
-                            match parsed {
-                                Err(err) => println!("JSON PARSE ERROR {:?} {}", err, line),
-                                Ok(parsed) => {
-                                    let spans = &parsed.message.spans;
-                                    if spans.len() > 0 {
-                                        for i in 0..spans.len() {
-                                            let mut span = spans[i].clone();
-                                            if !span.is_primary {
-                                                continue
-                                            }
-                                            let mut more_lines = vec![];
-                                            if let Some(label) = span.label {
-                                                more_lines.push(label);
-                                            }
-                                            // if we have children fo process
-                                            for child in &parsed.message.children {
-                                                more_lines.push(child.message.clone());
-                                            }
-                                            //span.file_name = format!("/{}",span.file_name);
-                                            span.level = Some(parsed.message.level.clone());
-                                            self._draw_messages.push(RustDrawMessage {
-                                                animator: Animator::new(Self::get_default_anim(cx, self._draw_messages.len(), false)),
-                                                is_selected: false,
-                                                path: span.file_name,
-                                                row: span.line_start as usize,
-                                                col: span.column_start as usize,
-                                                tail: span.byte_start as usize,
-                                                head: span.byte_end as usize,
-                                                body: parsed.message.message.clone(),
-                                                more_lines: more_lines,
-                                                level: match parsed.message.level.as_ref() {
-                                                    "warning" => TextBufferMessageLevel::Warning,
-                                                    "error" => TextBufferMessageLevel::Error,
-                                                    _ => TextBufferMessageLevel::Warning
-                                                }
+                                let mut more_lines = vec![];
+                                if let Some(label) = span.label {
+                                    more_lines.push(label);
+                                }
+                                // if we have children fo process
+                                let mut fixes = Vec::new();
+                                for child in &parsed.message.children {
+                                    more_lines.push(child.message.clone());
+                                    for child_span in &child.spans {
+                                        if child_span.suggestion_applicability.as_ref().map_or(true, | a | a != "MachineApplicable") {
+                                            continue
+                                        }
+                                        if let Some(replacement) = &child_span.suggested_replacement {
+                                            fixes.push(RustFix {
+                                                path: self.workspace.resolve_path(&child_span.file_name),
+                                                start_row: (child_span.line_start as usize).max(1) - 1,
+                                                start_col: (child_span.column_start as usize).max(1) - 1,
+                                                end_row: (child_span.line_end as usize).max(1) - 1,
+                                                end_col: (child_span.column_end as usize).max(1) - 1,
+                                                replacement: replacement.clone()
                                             });
                                         }
                                     }
-                                    self._rustc_messages.push(parsed);
                                 }
+                                if fixes.len() > 0 {
+                                    more_lines.push("double click to apply the suggested fix".to_string());
+                                }
+                                //span.file_name = format!("/{}",span.file_name);
+                                span.level = Some(parsed.message.level.clone());
+                                // name the lint for clippy messages
+                                let body = match &parsed.message.code {
+                                    Some(code) if code.code.starts_with("clippy::") => format!("{} [{}]", parsed.message.message, code.code),
+                                    _ => parsed.message.message.clone()
+                                };
+                                self._draw_messages.push(RustDrawMessage {
+                                    animator: Animator::new(Self::get_default_anim(cx, self._draw_messages.len(), false)),
+                                    is_selected: false,
+                                    path: self.workspace.resolve_path(&span.file_name),
+                                    package: self.workspace.package_by_id(&parsed.package_id).map_or(String::new(), | package | package.name.clone()),
+                                    row: span.line_start as usize,
+                                    col: span.column_start as usize,
+                                    tail: span.byte_start as usize,
+                                    head: span.byte_end as usize,
+                                    body: body,
+                                    more_lines: more_lines,
+                                    fixes: fixes,
+                                    level: match parsed.message.level.as_ref() {
+                                        "warning" => TextBufferMessageLevel::Warning,
+                                        "error" => TextBufferMessageLevel::Error,
+                                        _ => TextBufferMessageLevel::Warning
+                                    }
+                                });
                             }
-                            self.view.redraw_view_area(cx);
                         }
-                        self._data.push(String::new());
-                    }
-                    else {
-                        self._data.last_mut().unwrap().push(ch as char);
+                        self._rustc_messages.push(parsed);
                     }
                 }
+                self.view.redraw_view_area(cx);
             }
         }
     }
     
-    pub fn process_run_messages(&mut self, cx: &mut Cx, datas: Vec<Vec<u8>>) {
-        for data in datas {
-            if data.len() == 0 { // last event
-                self._program_running = false;
-                self.view.redraw_view_area(cx);
+    // applies the fixes of a message to the textbuffers they touch, one undo group per file
+    pub fn apply_fixes(&mut self, cx: &mut Cx, index: usize, text_buffers: &mut TextBuffers) -> Vec<String> {
+        let fixes = self._draw_messages[index].fixes.clone();
+        let mut paths: Vec<String> = Vec::new();
+        for fix in &fixes {
+            if !paths.contains(&fix.path) {
+                paths.push(fix.path.clone());
+            }
+        }
+        paths.retain( | path | {
+            let text_buffer = text_buffers.from_path(cx, path);
+            if text_buffer.lines.len() == 0 {
+                return false
+            }
+            let text_fixes: Vec<TextFix> = fixes.iter().filter( | fix | fix.path == *path).map( | fix | fix.to_text_fix(text_buffer)).collect();
+            let mut cursors = TextCursorSet::new();
+            if !cursors.apply_fixes(&text_fixes, text_buffer) {
+                return false
+            }
+            cx.send_signal_after_draw(text_buffer.signal, SIGNAL_TEXTBUFFER_DATA_UPDATE);
+            true
+        });
+        // the fixes that went in are stale now, the ones for files that are still loading or failed stay
+        self._draw_messages[index].fixes.retain( | fix | !paths.contains(&fix.path));
+        paths
+    }
+    
+    pub fn set_build_config(&mut self, cx: &mut Cx, build_config: RustBuildConfig, text_buffers: &mut TextBuffers) {
+        if self.build_config != build_config {
+            self.build_config = build_config;
+            self.restart_rust_checker(cx, text_buffers);
+        }
+    }
+    
+    pub fn run_tests(&mut self, cx: &mut Cx) {
+        self.build_queue.cancel(cx, BuildJobKind::Test);
+        self._test_results.truncate(0);
+        self._test_running = true;
+        self._test_error = None;
+        self.update_test_rows();
+        self.view.redraw_view_area(cx);
+        
+        // the libtest json format is still unstable, so this needs a nightly toolchain
+        let mut args = self.build_config.cargo_args("test", false);
+        for arg in &["--", "-Z", "unstable-options", "--format", "json", "--report-time"] {
+            args.push(arg.to_string());
+        }
+        self.build_queue.enqueue(cx, BuildJobKind::Test, args, None);
+    }
+    
+    pub fn process_test_messages(&mut self, cx: &mut Cx, lines: Vec<String>) {
+        for line in lines {
+            let parsed: Result<Value> = serde_json::from_str(&line);
+            let value = if let Ok(value) = parsed {value}else {continue};
+            if value["type"] != "test" {
+                continue;
+            }
+            let name = if let Some(name) = value["name"].as_str() {name.to_string()}else {continue};
+            let state = match value["event"].as_str() {
+                Some("started") => RustTestState::Running,
+                Some("ok") => RustTestState::Ok,
+                Some("failed") => RustTestState::Failed,
+                Some("ignored") => RustTestState::Ignored,
+                _ => continue
+            };
+            let result = RustTestResult {
+                name: name,
+                state: state,
+                exec_time: value["exec_time"].as_f64(),
+                stdout: value["stdout"].as_str().unwrap_or("").to_string()
+            };
+            if let Some(existing) = self._test_results.iter_mut().find( | existing | existing.name == result.name) {
+                *existing = result;
             }
             else {
-                for ch in data {
-                    if ch == '\n' as u8 {
-                        // parse it
-                        let line = self._data.last_mut().unwrap();
-                        // lets parse line
-                        let mut tok = LineTokenizer::new(&line);
-                        let mut path = String::new();
-                        let mut row_str = String::new();
-                        let mut col_str = String::new();
-                        let mut body = String::new();
-                        if tok.next == '[' {
-                            tok.advance();
-                            while tok.next != ':' && tok.next != '\0' {
-                                path.push(tok.next);
-                                tok.advance();
-                            }
-                            tok.advance();
-                            while tok.next != ':' && tok.next != '\0' {
-                                row_str.push(tok.next);
-                                tok.advance();
-                            }
-                            tok.advance();
-                            while tok.next != ']' && tok.next != '\0' {
-                                col_str.push(tok.next);
-                                tok.advance();
-                            }
-                            tok.advance();
-                            tok.advance();
-                            while tok.next != '\0' {
-                                body.push(tok.next);
-                                tok.advance();
-                            }
-                        }
-                        else {
-                            body = line.clone();
-                        }
-                        let row = if let Ok(row) = row_str.parse::<u32>() {row as usize}else {0};
-                        let col = if let Ok(col) = col_str.parse::<u32>() {col as usize}else {0};
-                        
-                        self._draw_messages.push(RustDrawMessage {
-                            animator: Animator::new(Self::get_default_anim(cx, self._draw_messages.len(), false)),
-                            is_selected: false,
-                            more_lines: Vec::new(),
-                            path: path,
-                            row: row,
-                            col: col,
-                            tail: 0,
-                            head: 0,
-                            body: body,
-                            level: TextBufferMessageLevel::Log
-                        });
-                        self._messages_updated = true;
-                        self.view.redraw_view_area(cx);
-                        self._data.push(String::new());
-                    }
-                    else {
-                        self._data.last_mut().unwrap().push(ch as char);
-                    }
+                self._test_results.push(result);
+            }
+        }
+        self.update_test_rows();
+        self.view.redraw_view_area(cx);
+    }
+    
+    fn update_test_rows(&mut self) {
+        // group the tests by module path into a tree
+        let mut results = self._test_results.clone();
+        results.sort_by( | a, b | a.name.cmp(&b.name));
+        let mut rows: Vec<RustTestRow> = Vec::new();
+        let mut last_modules: Vec<String> = Vec::new();
+        for result in &results {
+            let segments: Vec<&str> = result.name.split("::").collect();
+            let modules = &segments[0..segments.len() - 1];
+            let mut common = 0;
+            while common < modules.len() && common < last_modules.len() && modules[common] == last_modules[common] {
+                common += 1;
+            }
+            for depth in common..modules.len() {
+                rows.push(RustTestRow {
+                    label: modules[depth].to_string(),
+                    name: modules[0..depth + 1].join("::"),
+                    depth: depth,
+                    state: RustTestState::Ignored,
+                    is_test: false,
+                    area: Area::Empty
+                });
+            }
+            let label = if let Some(exec_time) = result.exec_time {
+                format!("{} ({:.3}s)", segments[segments.len() - 1], exec_time)
+            }
+            else {
+                segments[segments.len() - 1].to_string()
+            };
+            rows.push(RustTestRow {
+                label: label,
+                name: result.name.clone(),
+                depth: modules.len(),
+                state: result.state,
+                is_test: true,
+                area: Area::Empty
+            });
+            last_modules = modules.iter().map( | module | module.to_string()).collect();
+        }
+        // a module shows the worst state of its tests
+        for index in 0..rows.len() {
+            if rows[index].is_test {
+                continue;
+            }
+            let prefix = format!("{}::", rows[index].name);
+            let mut state = RustTestState::Ignored;
+            for result in &results {
+                if !result.name.starts_with(&prefix) {
+                    continue;
                 }
+                state = match (state, result.state) {
+                    (RustTestState::Failed, _) | (_, RustTestState::Failed) => RustTestState::Failed,
+                    (RustTestState::Running, _) | (_, RustTestState::Running) => RustTestState::Running,
+                    (RustTestState::Ok, _) | (_, RustTestState::Ok) => RustTestState::Ok,
+                    _ => RustTestState::Ignored
+                };
             }
+            rows[index].state = state;
         }
+        self._test_rows = rows;
     }
 }
 
@@ -802,7 +1074,7 @@ pub struct RustcSpan {
     text: Vec<RustcText>,
     label: Option<String>,
     suggested_replacement: Option<String>,
-    sugggested_applicability: Option<String>,
+    suggestion_applicability: Option<String>,
     expansion: Option<Box<RustcExpansion>>,
     level: Option<String>
 }
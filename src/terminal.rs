@@ -0,0 +1,469 @@
+use render::*;
+use widget::*;
+use crate::terminalgrid::*;
+
+use std::io::{Read, Write};
+use std::sync::mpsc;
+
+// a shell running under a pty, drawn as a grid of monospace cells
+pub struct Terminal {
+    pub view: View<ScrollBar>,
+    pub bg: Quad,
+    pub cell_bg: Quad,
+    pub cursor: Quad,
+    pub text: Text,
+    pub shell: String,
+    pub workdir: String,
+    pub colors: TerminalColors,
+    pub _signal: Signal,
+    pub _bg_area: Area,
+    pub _grid: TerminalGrid,
+    pub _pty: Option<PtyChild>,
+    pub _rx: Option<mpsc::Receiver<Vec<u8>>>,
+    pub _cell_size: Vec2,
+    pub _exited: bool,
+    pub _follow: bool,
+    pub _select_start: Option<TerminalPos>,
+    pub _select_end: Option<TerminalPos>
+}
+
+#[derive(Clone)]
+pub struct TerminalColors {
+    pub bg: Color,
+    pub fg: Color,
+    pub fg_bold: Color,
+    pub cursor: Color,
+    pub cursor_defocus: Color,
+    pub selection: Color,
+    pub ansi: [Color; 16]
+}
+
+const SIGNAL_TERMINAL_OUTPUT: usize = 1;
+
+impl Style for Terminal {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View {
+                scroll_h: Some(ScrollBar::style(cx)),
+                scroll_v: Some(ScrollBar {
+                    smoothing: Some(0.15),
+                    ..Style::style(cx)
+                }),
+                ..Style::style(cx)
+            },
+            bg: Quad {
+                do_scroll: false,
+                ..Style::style(cx)
+            },
+            cell_bg: Quad::style(cx),
+            cursor: Quad::style(cx),
+            text: Text {
+                font: cx.load_font_style("mono_font"),
+                font_size: 11.0,
+                line_spacing: 1.4,
+                do_dpi_dilate: true,
+                wrapping: Wrapping::None,
+                ..Style::style(cx)
+            },
+            shell: std::env::var("SHELL").unwrap_or("/bin/sh".to_string()),
+            workdir: "./".to_string(),
+            colors: TerminalColors {
+                bg: color256(30, 30, 30),
+                fg: color256(204, 204, 204),
+                fg_bold: color256(255, 255, 255),
+                cursor: color256a(220, 220, 220, 200),
+                cursor_defocus: color256a(220, 220, 220, 80),
+                selection: color256(42, 78, 117),
+                ansi: [
+                    color256(0, 0, 0),
+                    color256(205, 49, 49),
+                    color256(13, 188, 121),
+                    color256(229, 229, 16),
+                    color256(36, 114, 200),
+                    color256(188, 63, 188),
+                    color256(17, 168, 205),
+                    color256(229, 229, 229),
+                    color256(102, 102, 102),
+                    color256(241, 76, 76),
+                    color256(35, 209, 139),
+                    color256(245, 245, 67),
+                    color256(59, 142, 234),
+                    color256(214, 112, 214),
+                    color256(41, 184, 219),
+                    color256(255, 255, 255),
+                ]
+            },
+            _signal: cx.new_signal(),
+            _bg_area: Area::Empty,
+            _grid: TerminalGrid::new(80, 24),
+            _pty: None,
+            _rx: None,
+            _cell_size: Vec2::zero(),
+            _exited: false,
+            _follow: true,
+            _select_start: None,
+            _select_end: None
+        }
+    }
+}
+
+impl Terminal {
+    pub fn start_shell(&mut self, cx: &mut Cx) {
+        self.stop_shell();
+        let (cols, rows) = (self._grid.cols as u16, self._grid.rows as u16);
+        let pty = match spawn_pty_command(&self.shell, &[], &self.workdir, cols, rows) {
+            Ok(pty) => pty,
+            Err(err) => {
+                // in red, so it does not pass for shell output
+                self._grid.process(format!("\x1b[31mcannot start {}: {}\x1b[0m\r\n", self.shell, err).as_bytes());
+                self._exited = true;
+                self.view.redraw_view_area(cx);
+                return
+            }
+        };
+        let mut reader = match pty.master.try_clone() {
+            Ok(reader) => reader,
+            Err(_) => return
+        };
+        let (tx, rx) = mpsc::channel();
+        let signal = self._signal;
+        std::thread::spawn(move || {
+            loop {
+                let mut data = vec![0; 4096];
+                // reading the master fails once the shell is gone
+                let n_bytes_read = reader.read(&mut data).unwrap_or(0);
+                data.truncate(n_bytes_read);
+                let _ = tx.send(data);
+                Cx::send_signal(signal, SIGNAL_TERMINAL_OUTPUT);
+                if n_bytes_read == 0 {
+                    return
+                }
+            }
+        });
+        self._rx = Some(rx);
+        self._pty = Some(pty);
+        self._exited = false;
+        self._follow = true;
+    }
+    
+    pub fn stop_shell(&mut self) {
+        if let Some(pty) = &mut self._pty {
+            let _ = pty.child.kill();
+            let _ = pty.child.wait();
+        }
+        self._pty = None;
+        self._rx = None;
+    }
+    
+    fn write_pty(&mut self, data: &[u8]) {
+        if let Some(pty) = &mut self._pty {
+            let _ = pty.master.write_all(data);
+            let _ = pty.master.flush();
+        }
+    }
+    
+    fn process_output(&mut self, cx: &mut Cx) {
+        let mut datas = Vec::new();
+        if let Some(rx) = &self._rx {
+            while let Ok(data) = rx.try_recv() {
+                datas.push(data);
+            }
+        }
+        for data in datas {
+            if data.len() == 0 {
+                self.stop_shell();
+                self._exited = true;
+                self._grid.process(b"\r\n[process exited, press return to restart]\r\n");
+                continue;
+            }
+            self._grid.process(&data);
+        }
+        // answer cursor position and device queries
+        if self._grid.responses.len() > 0 {
+            let responses = std::mem::replace(&mut self._grid.responses, Vec::new());
+            self.write_pty(&responses);
+        }
+        self.view.redraw_view_area(cx);
+    }
+    
+    // the bytes a key sends, following xterm
+    fn key_to_bytes(&self, ke: &KeyEvent) -> Option<Vec<u8>> {
+        let cursor_key = | ch: char | if self._grid.app_cursor_keys {format!("\x1bO{}", ch)}else {format!("\x1b[{}", ch)};
+        let bytes = match ke.key_code {
+            KeyCode::Return | KeyCode::NumpadEnter => "\r".to_string(),
+            KeyCode::Backspace => "\x7f".to_string(),
+            KeyCode::Tab => if ke.modifiers.shift {"\x1b[Z".to_string()}else {"\t".to_string()},
+            KeyCode::Escape => "\x1b".to_string(),
+            KeyCode::ArrowUp => cursor_key('A'),
+            KeyCode::ArrowDown => cursor_key('B'),
+            KeyCode::ArrowRight => cursor_key('C'),
+            KeyCode::ArrowLeft => cursor_key('D'),
+            KeyCode::Home => cursor_key('H'),
+            KeyCode::End => cursor_key('F'),
+            KeyCode::Insert => "\x1b[2~".to_string(),
+            KeyCode::Delete => "\x1b[3~".to_string(),
+            KeyCode::PageUp => "\x1b[5~".to_string(),
+            KeyCode::PageDown => "\x1b[6~".to_string(),
+            KeyCode::F1 => "\x1bOP".to_string(),
+            KeyCode::F2 => "\x1bOQ".to_string(),
+            KeyCode::F3 => "\x1bOR".to_string(),
+            KeyCode::F4 => "\x1bOS".to_string(),
+            KeyCode::F5 => "\x1b[15~".to_string(),
+            KeyCode::F6 => "\x1b[17~".to_string(),
+            KeyCode::F7 => "\x1b[18~".to_string(),
+            KeyCode::F8 => "\x1b[19~".to_string(),
+            KeyCode::F9 => "\x1b[20~".to_string(),
+            KeyCode::F10 => "\x1b[21~".to_string(),
+            KeyCode::F11 => "\x1b[23~".to_string(),
+            KeyCode::F12 => "\x1b[24~".to_string(),
+            _ => {
+                // control combinations map onto the c0 control codes
+                if !ke.modifiers.control || ke.modifiers.logo {
+                    return None
+                }
+                let ch = match ke.key_code {
+                    KeyCode::KeyA => 'a', KeyCode::KeyB => 'b', KeyCode::KeyC => 'c', KeyCode::KeyD => 'd',
+                    KeyCode::KeyE => 'e', KeyCode::KeyF => 'f', KeyCode::KeyG => 'g', KeyCode::KeyH => 'h',
+                    KeyCode::KeyI => 'i', KeyCode::KeyJ => 'j', KeyCode::KeyK => 'k', KeyCode::KeyL => 'l',
+                    KeyCode::KeyM => 'm', KeyCode::KeyN => 'n', KeyCode::KeyO => 'o', KeyCode::KeyP => 'p',
+                    KeyCode::KeyQ => 'q', KeyCode::KeyR => 'r', KeyCode::KeyS => 's', KeyCode::KeyT => 't',
+                    KeyCode::KeyU => 'u', KeyCode::KeyV => 'v', KeyCode::KeyW => 'w', KeyCode::KeyX => 'x',
+                    KeyCode::KeyY => 'y', KeyCode::KeyZ => 'z',
+                    KeyCode::LBracket => '[', KeyCode::Backslash => '\\', KeyCode::RBracket => ']',
+                    KeyCode::Space => '@',
+                    _ => return None
+                };
+                let code = if ch >= 'a' && ch <= 'z' {ch as u8 - b'a' + 1}else {ch as u8 - b'@'};
+                return Some(vec![code])
+            }
+        };
+        Some(bytes.into_bytes())
+    }
+    
+    fn pos_from_rel(&self, cx: &Cx, rel: Vec2) -> TerminalPos {
+        let scroll = self.view.get_scroll_pos(cx);
+        let row = ((rel.y + scroll.y) / self._cell_size.y).max(0.) as usize;
+        let col = ((rel.x + scroll.x) / self._cell_size.x + 0.5).max(0.) as usize;
+        TerminalPos {row: row.min(self._grid.total_rows().max(1) - 1), col: col.min(self._grid.cols)}
+    }
+    
+    fn selection(&self) -> Option<(TerminalPos, TerminalPos)> {
+        if let (Some(start), Some(end)) = (self._select_start, self._select_end) {
+            if start == end {
+                return None
+            }
+            if (start.row, start.col) < (end.row, end.col) {Some((start, end))}else {Some((end, start))}
+        }
+        else {
+            None
+        }
+    }
+    
+    pub fn handle_terminal(&mut self, cx: &mut Cx, event: &mut Event) {
+        if self.view.handle_scroll_bars(cx, event) {
+            // scrolling up into the history stops following the output
+            let scroll = self.view.get_scroll_pos(cx);
+            let total = self.view.get_scroll_view_total();
+            let visible = self.view.get_rect(cx).h;
+            self._follow = scroll.y + visible >= total.y - self._cell_size.y;
+        }
+        
+        if let Event::Signal(se) = event {
+            if self._signal.is_signal(se) {
+                self.process_output(cx);
+            }
+        }
+        
+        match event.hits(cx, self._bg_area, HitOpt {no_scrolling: true, ..Default::default()}) {
+            Event::FingerDown(fe) => {
+                cx.set_key_focus(self._bg_area);
+                let pos = self.pos_from_rel(cx, fe.rel);
+                self._select_start = Some(pos);
+                self._select_end = Some(pos);
+                self.view.redraw_view_area(cx);
+            },
+            Event::FingerMove(fe) => {
+                self._select_end = Some(self.pos_from_rel(cx, fe.rel));
+                self.view.redraw_view_area(cx);
+            },
+            Event::FingerHover(_fe) => {
+                cx.set_hover_mouse_cursor(MouseCursor::Text);
+            },
+            Event::KeyFocus(_) | Event::KeyFocusLost(_) => {
+                self.view.redraw_view_area(cx);
+            },
+            Event::KeyDown(ke) => {
+                if self._exited {
+                    if ke.key_code == KeyCode::Return {
+                        self.start_shell(cx);
+                    }
+                    return
+                }
+                if let Some(bytes) = self.key_to_bytes(&ke) {
+                    self._select_start = None;
+                    self._select_end = None;
+                    self._follow = true;
+                    self.write_pty(&bytes);
+                    self.view.redraw_view_area(cx);
+                }
+            },
+            Event::TextInput(te) => {
+                if self._exited {
+                    return
+                }
+                let mut input = te.input.replace("\r\n", "\r").replace("\n", "\r");
+                if te.was_paste && self._grid.bracketed_paste {
+                    input = format!("\x1b[200~{}\x1b[201~", input);
+                }
+                self._select_start = None;
+                self._select_end = None;
+                self._follow = true;
+                self.write_pty(input.as_bytes());
+                self.view.redraw_view_area(cx);
+            },
+            Event::TextCopy(_) => match event { // access the original event
+                Event::TextCopy(req) => {
+                    if let Some((start, end)) = self.selection() {
+                        req.response = Some(self._grid.text_range(start, end));
+                    }
+                },
+                _ => ()
+            },
+            _ => ()
+        }
+    }
+    
+    fn resolve_color(&self, color: TerminalColor, bold: bool, default: Color) -> Color {
+        match color {
+            TerminalColor::Default => default,
+            TerminalColor::Index(index) => {
+                let index = index as usize;
+                if index < 16 {
+                    if bold && index < 8 {self.colors.ansi[index + 8]}else {self.colors.ansi[index]}
+                }
+                else if index < 232 { // the 6x6x6 color cube
+                    let level = | v: usize | if v == 0 {0}else {55 + v as i32 * 40};
+                    let i = index - 16;
+                    color256(level(i / 36), level((i / 6) % 6), level(i % 6))
+                }
+                else {
+                    let gray = 8 + (index - 232) as i32 * 10;
+                    color256(gray, gray, gray)
+                }
+            },
+            TerminalColor::Rgb(r, g, b) => color256(r as i32, g as i32, b as i32)
+        }
+    }
+    
+    fn cell_colors(&self, cell: &TerminalCell) -> (Color, Option<Color>) {
+        let default_fg = if cell.bold {self.colors.fg_bold}else {self.colors.fg};
+        let fg = self.resolve_color(cell.fg, cell.bold, default_fg);
+        let bg = if cell.bg == TerminalColor::Default {None}else {Some(self.resolve_color(cell.bg, false, self.colors.bg))};
+        if cell.inverse {
+            (bg.unwrap_or(self.colors.bg), Some(fg))
+        }
+        else {
+            (fg, bg)
+        }
+    }
+    
+    pub fn draw_terminal(&mut self, cx: &mut Cx) {
+        if let Err(_) = self.view.begin_view(cx, Layout::default()) {
+            return
+        }
+        self.bg.color = self.colors.bg;
+        let bg_area = self.bg.draw_quad(cx, Rect {x: 0., y: 0., w: cx.get_width_total(), h: cx.get_height_total()}).into_area();
+        cx.update_area_refs(self._bg_area, bg_area);
+        self._bg_area = bg_area;
+        // cell backgrounds go under the text
+        cx.new_instance_draw_call(&self.cell_bg.shader, 0);
+        
+        // the grid follows the size of the view
+        let base = self.text.get_monospace_base(cx);
+        self._cell_size = Vec2 {x: base.x * self.text.font_size, y: base.y * self.text.font_size};
+        let rect = cx.get_turtle_rect();
+        let cols = ((rect.w / self._cell_size.x) as usize).max(1);
+        let rows = ((rect.h / self._cell_size.y) as usize).max(1);
+        if cols != self._grid.cols || rows != self._grid.rows {
+            self._grid.resize(cols, rows);
+            if let Some(pty) = &self._pty {
+                resize_pty(&pty.master, cols as u16, rows as u16);
+            }
+        }
+        if self._pty.is_none() && !self._exited {
+            self.start_shell(cx);
+        }
+        
+        let origin = cx.get_turtle_origin();
+        let scroll = self.view.get_scroll_pos(cx);
+        let total_rows = self._grid.total_rows();
+        let first = ((scroll.y / self._cell_size.y) as usize).min(total_rows);
+        let last = (((scroll.y + rect.h) / self._cell_size.y) as usize + 1).min(total_rows);
+        let selection = self.selection();
+        let (cw, ch) = (self._cell_size.x, self._cell_size.y);
+        
+        for row in first..last {
+            let cells = self._grid.row(row);
+            let y = origin.y + row as f32 * ch;
+            for (col, cell) in cells.iter().enumerate() {
+                let selected = selection.map_or(false, | (start, end) | {
+                    (row, col) >= (start.row, start.col) && (row, col) < (end.row, end.col)
+                });
+                let bg = if selected {Some(self.colors.selection)}else {self.cell_colors(cell).1};
+                if let Some(bg) = bg {
+                    self.cell_bg.color = bg;
+                    self.cell_bg.draw_quad_abs(cx, Rect {x: origin.x + col as f32 * cw, y: y, w: cw, h: ch});
+                }
+                if cell.underline {
+                    self.cell_bg.color = self.cell_colors(cell).0;
+                    self.cell_bg.draw_quad_abs(cx, Rect {x: origin.x + col as f32 * cw, y: y + ch - 2., w: cw, h: 1.});
+                }
+            }
+        }
+        
+        // runs of equal color share one add_text
+        let mut aligned = self.text.begin_text(cx);
+        let mut chunk = Vec::new();
+        for row in first..last {
+            let cells = self._grid.row(row);
+            let y = origin.y + row as f32 * ch;
+            let mut col = 0;
+            while col < cells.len() {
+                let color = self.cell_colors(&cells[col]).0;
+                let start = col;
+                chunk.truncate(0);
+                while col < cells.len() && {
+                    let next = self.cell_colors(&cells[col]).0;
+                    next.r == color.r && next.g == color.g && next.b == color.b && next.a == color.a
+                } {
+                    chunk.push(cells[col].ch);
+                    col += 1;
+                }
+                if chunk.iter().all( | ch | *ch == ' ') {
+                    continue;
+                }
+                self.text.color = color;
+                self.text.add_text(cx, origin.x + start as f32 * cw, y, 0, &mut aligned, &chunk, | _, _, _, _ | {0.});
+            }
+        }
+        self.text.end_text(cx, &aligned);
+        
+        let cursor_row = self._grid.scrollback.len() + self._grid.cursor.row;
+        if self._grid.cursor_visible && !self._exited {
+            cx.new_instance_draw_call(&self.cursor.shader, 0);
+            self.cursor.color = if cx.has_key_focus(self._bg_area) {self.colors.cursor}else {self.colors.cursor_defocus};
+            self.cursor.draw_quad_abs(cx, Rect {
+                x: origin.x + self._grid.cursor.col as f32 * cw,
+                y: origin.y + cursor_row as f32 * ch,
+                w: cw,
+                h: ch
+            });
+        }
+        
+        cx.walk_turtle(Bounds::Fix(self._grid.cols as f32 * cw), Bounds::Fix(total_rows as f32 * ch), Margin::zero(), None);
+        if self._follow {
+            self.view.scroll_into_view(cx, Rect {x: 0., y: (total_rows - 1) as f32 * ch, w: 0., h: ch});
+        }
+        self.view.end_view(cx);
+    }
+}
@@ -0,0 +1,818 @@
+// the screen state of a vt100/xterm terminal, fed with the bytes coming out of the pty
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TerminalColor {
+    Default,
+    Index(u8),
+    Rgb(u8, u8, u8)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TerminalCell {
+    pub ch: char,
+    pub fg: TerminalColor,
+    pub bg: TerminalColor,
+    pub bold: bool,
+    pub underline: bool,
+    pub inverse: bool
+}
+
+impl Default for TerminalCell {
+    fn default() -> Self {
+        TerminalCell {
+            ch: ' ',
+            fg: TerminalColor::Default,
+            bg: TerminalColor::Default,
+            bold: false,
+            underline: false,
+            inverse: false
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TerminalPos {
+    pub row: usize,
+    pub col: usize
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum VtState {
+    Ground,
+    Escape,
+    EscapeHash,
+    Charset(char),
+    Csi,
+    Osc,
+    OscEscape,
+    Ignore,
+    IgnoreEscape
+}
+
+#[derive(Clone)]
+pub struct TerminalGrid {
+    pub cols: usize,
+    pub rows: usize,
+    pub lines: Vec<Vec<TerminalCell>>,
+    pub scrollback: Vec<Vec<TerminalCell>>,
+    pub max_scrollback: usize,
+    pub cursor: TerminalPos,
+    pub cursor_visible: bool,
+    pub app_cursor_keys: bool,
+    pub bracketed_paste: bool,
+    pub title: String,
+    // replies to queries, these need to go back into the pty
+    pub responses: Vec<u8>,
+    pub _pen: TerminalCell,
+    pub _wrap_pending: bool,
+    pub _autowrap: bool,
+    pub _insert_mode: bool,
+    pub _scroll_top: usize,
+    pub _scroll_bottom: usize,
+    pub _saved_cursor: Option<(TerminalPos, TerminalCell)>,
+    pub _main_screen: Option<(Vec<Vec<TerminalCell>>, TerminalPos)>,
+    pub _dec_graphics: bool,
+    pub _state: VtState,
+    pub _params: String,
+    pub _intermediates: String,
+    pub _osc: String,
+    pub _utf8: Vec<u8>
+}
+
+impl TerminalGrid {
+    pub fn new(cols: usize, rows: usize) -> TerminalGrid {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        TerminalGrid {
+            cols: cols,
+            rows: rows,
+            lines: vec![vec![TerminalCell::default(); cols]; rows],
+            scrollback: Vec::new(),
+            max_scrollback: 5000,
+            cursor: TerminalPos {row: 0, col: 0},
+            cursor_visible: true,
+            app_cursor_keys: false,
+            bracketed_paste: false,
+            title: String::new(),
+            responses: Vec::new(),
+            _pen: TerminalCell::default(),
+            _wrap_pending: false,
+            _autowrap: true,
+            _insert_mode: false,
+            _scroll_top: 0,
+            _scroll_bottom: rows - 1,
+            _saved_cursor: None,
+            _main_screen: None,
+            _dec_graphics: false,
+            _state: VtState::Ground,
+            _params: String::new(),
+            _intermediates: String::new(),
+            _osc: String::new(),
+            _utf8: Vec::new()
+        }
+    }
+    
+    // scrollback rows come first, then the screen
+    pub fn total_rows(&self) -> usize {
+        self.scrollback.len() + self.rows
+    }
+    
+    pub fn row(&self, index: usize) -> &[TerminalCell] {
+        if index < self.scrollback.len() {
+            &self.scrollback[index]
+        }
+        else {
+            &self.lines[index - self.scrollback.len()]
+        }
+    }
+    
+    pub fn text_range(&self, start: TerminalPos, end: TerminalPos) -> String {
+        let mut out = String::new();
+        for row in start.row..(end.row + 1).min(self.total_rows()) {
+            let cells = self.row(row);
+            let from = if row == start.row {start.col.min(cells.len())}else {0};
+            let to = if row == end.row {end.col.min(cells.len())}else {cells.len()};
+            let line: String = cells[from..to.max(from)].iter().map( | cell | cell.ch).collect();
+            out.push_str(line.trim_end());
+            if row != end.row {
+                out.push('\n');
+            }
+        }
+        out
+    }
+    
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        if cols == self.cols && rows == self.rows {
+            return
+        }
+        for line in &mut self.lines {
+            line.resize(cols, TerminalCell::default());
+        }
+        // shrinking drops empty lines below the cursor first, then pushes lines into the scrollback
+        while self.lines.len() > rows {
+            if self.cursor.row + 1 < self.lines.len() {
+                self.lines.pop();
+            }
+            else {
+                let line = self.lines.remove(0);
+                self.push_scrollback(line);
+                self.cursor.row -= 1;
+            }
+        }
+        while self.lines.len() < rows {
+            self.lines.push(vec![TerminalCell::default(); cols]);
+        }
+        if let Some((screen, _)) = &mut self._main_screen {
+            screen.resize(rows, vec![TerminalCell::default(); cols]);
+            for line in screen {
+                line.resize(cols, TerminalCell::default());
+            }
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self._scroll_top = 0;
+        self._scroll_bottom = rows - 1;
+        self._wrap_pending = false;
+        self.cursor.row = self.cursor.row.min(rows - 1);
+        self.cursor.col = self.cursor.col.min(cols - 1);
+    }
+    
+    pub fn process(&mut self, data: &[u8]) {
+        self._utf8.extend_from_slice(data);
+        let bytes = std::mem::replace(&mut self._utf8, Vec::new());
+        // keep an incomplete utf8 sequence for the next read
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(_) => bytes.len()
+        };
+        let text = String::from_utf8_lossy(&bytes[..valid]).to_string();
+        self._utf8 = bytes[valid..].to_vec();
+        for ch in text.chars() {
+            self.process_char(ch);
+        }
+    }
+    
+    fn process_char(&mut self, ch: char) {
+        match self._state {
+            VtState::Ground => {
+                if ch == '\x1b' {
+                    self._state = VtState::Escape;
+                }
+                else if (ch as u32) < 0x20 || ch == '\x7f' {
+                    self.execute(ch);
+                }
+                else {
+                    self.print(ch);
+                }
+            },
+            VtState::Escape => {
+                self._state = VtState::Ground;
+                match ch {
+                    '[' => {
+                        self._params.truncate(0);
+                        self._intermediates.truncate(0);
+                        self._state = VtState::Csi;
+                    },
+                    ']' => {
+                        self._osc.truncate(0);
+                        self._state = VtState::Osc;
+                    },
+                    '(' | ')' | '*' | '+' => self._state = VtState::Charset(ch),
+                    '#' => self._state = VtState::EscapeHash,
+                    'P' | 'X' | '^' | '_' => self._state = VtState::Ignore,
+                    '7' => self.save_cursor(),
+                    '8' => self.restore_cursor(),
+                    'D' => self.linefeed(),
+                    'E' => {
+                        self.cursor.col = 0;
+                        self.linefeed();
+                    },
+                    'M' => self.reverse_index(),
+                    'c' => self.reset(),
+                    _ => ()
+                }
+            },
+            VtState::EscapeHash => {
+                self._state = VtState::Ground;
+            },
+            VtState::Charset(which) => {
+                if which == '(' {
+                    self._dec_graphics = ch == '0';
+                }
+                self._state = VtState::Ground;
+            },
+            VtState::Csi => {
+                if ch == '\x1b' {
+                    self._state = VtState::Escape;
+                }
+                else if (ch as u32) < 0x20 {
+                    self.execute(ch);
+                }
+                else if ch >= '0' && ch <= '?' {
+                    self._params.push(ch);
+                }
+                else if ch >= ' ' && ch <= '/' {
+                    self._intermediates.push(ch);
+                }
+                else {
+                    self._state = VtState::Ground;
+                    if ch >= '@' && ch <= '~' {
+                        self.csi_dispatch(ch);
+                    }
+                }
+            },
+            VtState::Osc => {
+                match ch {
+                    '\x07' => {
+                        self._state = VtState::Ground;
+                        self.osc_dispatch();
+                    },
+                    '\x1b' => self._state = VtState::OscEscape,
+                    _ => if self._osc.len() < 4096 {
+                        self._osc.push(ch);
+                    }
+                }
+            },
+            VtState::OscEscape => {
+                self._state = VtState::Ground;
+                if ch == '\\' {
+                    self.osc_dispatch();
+                }
+            },
+            VtState::Ignore => {
+                match ch {
+                    '\x07' => self._state = VtState::Ground,
+                    '\x1b' => self._state = VtState::IgnoreEscape,
+                    _ => ()
+                }
+            },
+            VtState::IgnoreEscape => {
+                self._state = if ch == '\\' {VtState::Ground}else {VtState::Ignore};
+            }
+        }
+    }
+    
+    fn execute(&mut self, ch: char) {
+        match ch {
+            '\x08' => {
+                self._wrap_pending = false;
+                if self.cursor.col > 0 {
+                    self.cursor.col -= 1;
+                }
+            },
+            '\t' => {
+                self._wrap_pending = false;
+                self.cursor.col = ((self.cursor.col / 8 + 1) * 8).min(self.cols - 1);
+            },
+            '\n' | '\x0b' | '\x0c' => self.linefeed(),
+            '\r' => {
+                self._wrap_pending = false;
+                self.cursor.col = 0;
+            },
+            '\x0e' | '\x0f' => (), // shift out and in
+            _ => ()
+        }
+    }
+    
+    fn print(&mut self, ch: char) {
+        let ch = if self._dec_graphics {Self::dec_graphics_char(ch)}else {ch};
+        if self._wrap_pending {
+            self._wrap_pending = false;
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+        let row = self.cursor.row;
+        let col = self.cursor.col;
+        if self._insert_mode {
+            let line = &mut self.lines[row];
+            line.insert(col, TerminalCell::default());
+            line.truncate(self.cols);
+        }
+        self.lines[row][col] = TerminalCell {ch: ch, ..self._pen};
+        if col + 1 >= self.cols {
+            self._wrap_pending = self._autowrap;
+        }
+        else {
+            self.cursor.col += 1;
+        }
+    }
+    
+    fn dec_graphics_char(ch: char) -> char {
+        match ch {
+            'j' => '┘',
+            'k' => '┐',
+            'l' => '┌',
+            'm' => '└',
+            'n' => '┼',
+            'q' => '─',
+            't' => '├',
+            'u' => '┤',
+            'v' => '┴',
+            'w' => '┬',
+            'x' => '│',
+            'a' => '▒',
+            '`' => '◆',
+            '~' => '·',
+            _ => ch
+        }
+    }
+    
+    fn blank_cell(&self) -> TerminalCell {
+        // erased cells keep the current background
+        TerminalCell {bg: self._pen.bg, ..TerminalCell::default()}
+    }
+    
+    fn blank_line(&self) -> Vec<TerminalCell> {
+        vec![self.blank_cell(); self.cols]
+    }
+    
+    fn push_scrollback(&mut self, line: Vec<TerminalCell>) {
+        // the alternate screen of fullscreen programs has no history
+        if self._main_screen.is_some() {
+            return
+        }
+        self.scrollback.push(line);
+        if self.scrollback.len() > self.max_scrollback {
+            let drop = self.scrollback.len() - self.max_scrollback;
+            self.scrollback.drain(0..drop);
+        }
+    }
+    
+    fn linefeed(&mut self) {
+        self._wrap_pending = false;
+        if self.cursor.row == self._scroll_bottom {
+            self.scroll_up(1, true);
+        }
+        else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+    
+    fn reverse_index(&mut self) {
+        self._wrap_pending = false;
+        if self.cursor.row == self._scroll_top {
+            self.scroll_down(1);
+        }
+        else if self.cursor.row > 0 {
+            self.cursor.row -= 1;
+        }
+    }
+    
+    // only output scrolling off the top goes into the history, deleted lines don't
+    fn scroll_up(&mut self, count: usize, history: bool) {
+        for _ in 0..count.min(self._scroll_bottom - self._scroll_top + 1) {
+            let line = self.lines.remove(self._scroll_top);
+            if history && self._scroll_top == 0 {
+                self.push_scrollback(line);
+            }
+            let blank = self.blank_line();
+            self.lines.insert(self._scroll_bottom, blank);
+        }
+    }
+    
+    fn scroll_down(&mut self, count: usize) {
+        for _ in 0..count.min(self._scroll_bottom - self._scroll_top + 1) {
+            self.lines.remove(self._scroll_bottom);
+            let blank = self.blank_line();
+            self.lines.insert(self._scroll_top, blank);
+        }
+    }
+    
+    fn save_cursor(&mut self) {
+        self._saved_cursor = Some((self.cursor, self._pen));
+    }
+    
+    fn restore_cursor(&mut self) {
+        if let Some((cursor, pen)) = self._saved_cursor {
+            self.cursor = TerminalPos {row: cursor.row.min(self.rows - 1), col: cursor.col.min(self.cols - 1)};
+            self._pen = pen;
+        }
+        self._wrap_pending = false;
+    }
+    
+    fn reset(&mut self) {
+        let scrollback = std::mem::replace(&mut self.scrollback, Vec::new());
+        let max_scrollback = self.max_scrollback;
+        *self = TerminalGrid::new(self.cols, self.rows);
+        self.scrollback = scrollback;
+        self.max_scrollback = max_scrollback;
+    }
+    
+    fn set_alt_screen(&mut self, on: bool) {
+        if on && self._main_screen.is_none() {
+            let blank = vec![vec![TerminalCell::default(); self.cols]; self.rows];
+            let screen = std::mem::replace(&mut self.lines, blank);
+            self._main_screen = Some((screen, self.cursor));
+        }
+        else if !on {
+            if let Some((screen, cursor)) = self._main_screen.take() {
+                self.lines = screen;
+                self.cursor = cursor;
+            }
+        }
+        self._wrap_pending = false;
+    }
+    
+    fn params(&self) -> Vec<usize> {
+        self._params.trim_start_matches( | ch | ch == '?' || ch == '>' || ch == '<' || ch == '=').split(';').map( | p | {
+            // ignore colon separated sub parameters
+            p.split(':').next().unwrap_or("").parse::<usize>().unwrap_or(0)
+        }).collect()
+    }
+    
+    fn csi_dispatch(&mut self, action: char) {
+        let params = self.params();
+        // counts treat 0 and missing as 1
+        let p = | index: usize | params.get(index).cloned().unwrap_or(0).max(1);
+        let private = self._params.starts_with('?');
+        if self._intermediates.len() > 0 && action != 'q' {
+            return
+        }
+        match action {
+            '@' => {
+                let (row, col) = (self.cursor.row, self.cursor.col);
+                let blank = self.blank_cell();
+                for _ in 0..p(0).min(self.cols - col) {
+                    self.lines[row].insert(col, blank);
+                }
+                self.lines[row].truncate(self.cols);
+            },
+            'A' => self.cursor.row = self.cursor.row.saturating_sub(p(0)).max(if self.cursor.row >= self._scroll_top {self._scroll_top}else {0}),
+            'B' | 'e' => self.cursor.row = (self.cursor.row + p(0)).min(if self.cursor.row <= self._scroll_bottom {self._scroll_bottom}else {self.rows - 1}),
+            'C' | 'a' => self.cursor.col = (self.cursor.col + p(0)).min(self.cols - 1),
+            'D' => self.cursor.col = self.cursor.col.saturating_sub(p(0)),
+            'E' => {
+                self.cursor.row = (self.cursor.row + p(0)).min(self.rows - 1);
+                self.cursor.col = 0;
+            },
+            'F' => {
+                self.cursor.row = self.cursor.row.saturating_sub(p(0));
+                self.cursor.col = 0;
+            },
+            'G' | '`' => self.cursor.col = (p(0) - 1).min(self.cols - 1),
+            'd' => self.cursor.row = (p(0) - 1).min(self.rows - 1),
+            'H' | 'f' => {
+                self.cursor.row = (p(0) - 1).min(self.rows - 1);
+                self.cursor.col = (p(1) - 1).min(self.cols - 1);
+            },
+            'J' => self.erase_display(params.get(0).cloned().unwrap_or(0)),
+            'K' => self.erase_line(params.get(0).cloned().unwrap_or(0)),
+            'L' | 'M' => if self.cursor.row >= self._scroll_top && self.cursor.row <= self._scroll_bottom {
+                let top = self._scroll_top;
+                self._scroll_top = self.cursor.row;
+                if action == 'L' {self.scroll_down(p(0))}else {self.scroll_up(p(0), false)};
+                self._scroll_top = top;
+                self.cursor.col = 0;
+            },
+            'P' => {
+                let (row, col) = (self.cursor.row, self.cursor.col);
+                let count = p(0).min(self.cols - col);
+                self.lines[row].drain(col..col + count);
+                let blank = self.blank_cell();
+                self.lines[row].resize(self.cols, blank);
+            },
+            'X' => {
+                let (row, col) = (self.cursor.row, self.cursor.col);
+                let blank = self.blank_cell();
+                let end = (col + p(0)).min(self.cols);
+                for cell in &mut self.lines[row][col..end] {
+                    *cell = blank;
+                }
+            },
+            'S' => self.scroll_up(p(0), false),
+            'T' => self.scroll_down(p(0)),
+            'm' => self.select_graphic_rendition(&params),
+            'r' => {
+                let top = p(0) - 1;
+                let bottom = params.get(1).cloned().filter( | b | *b > 0).unwrap_or(self.rows).min(self.rows) - 1;
+                if top < bottom {
+                    self._scroll_top = top;
+                    self._scroll_bottom = bottom;
+                    self.cursor = TerminalPos {row: 0, col: 0};
+                }
+            },
+            's' => self.save_cursor(),
+            'u' => self.restore_cursor(),
+            'h' | 'l' => {
+                let on = action == 'h';
+                for mode in &params {
+                    match (private, mode) {
+                        (true, 1) => self.app_cursor_keys = on,
+                        (true, 7) => self._autowrap = on,
+                        (true, 25) => self.cursor_visible = on,
+                        (true, 47) | (true, 1047) => self.set_alt_screen(on),
+                        (true, 1049) => {
+                            if on {
+                                self.save_cursor();
+                                self.set_alt_screen(true);
+                            }
+                            else {
+                                self.set_alt_screen(false);
+                                self.restore_cursor();
+                            }
+                        },
+                        (true, 2004) => self.bracketed_paste = on,
+                        (false, 4) => self._insert_mode = on,
+                        _ => ()
+                    }
+                }
+            },
+            'n' => match params.get(0).cloned().unwrap_or(0) {
+                5 => self.responses.extend_from_slice(b"\x1b[0n"),
+                6 => {
+                    let reply = format!("\x1b[{};{}R", self.cursor.row + 1, self.cursor.col + 1);
+                    self.responses.extend_from_slice(reply.as_bytes());
+                },
+                _ => ()
+            },
+            'c' => if !self._params.starts_with('>') {
+                self.responses.extend_from_slice(b"\x1b[?6c");
+            },
+            _ => ()
+        }
+        if action != 'm' && action != 'n' && action != 'c' {
+            self._wrap_pending = false;
+        }
+    }
+    
+    fn erase_display(&mut self, mode: usize) {
+        let blank = self.blank_cell();
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        match mode {
+            0 => {
+                for cell in &mut self.lines[row][col..] {
+                    *cell = blank;
+                }
+                for line in &mut self.lines[row + 1..] {
+                    *line = vec![blank; self.cols];
+                }
+            },
+            1 => {
+                for cell in &mut self.lines[row][..=col] {
+                    *cell = blank;
+                }
+                for line in &mut self.lines[..row] {
+                    *line = vec![blank; self.cols];
+                }
+            },
+            2 => {
+                for line in &mut self.lines {
+                    *line = vec![blank; self.cols];
+                }
+            },
+            3 => self.scrollback.truncate(0),
+            _ => ()
+        }
+    }
+    
+    fn erase_line(&mut self, mode: usize) {
+        let blank = self.blank_cell();
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let cells = match mode {
+            0 => &mut self.lines[row][col..],
+            1 => &mut self.lines[row][..=col],
+            2 => &mut self.lines[row][..],
+            _ => return
+        };
+        for cell in cells {
+            *cell = blank;
+        }
+    }
+    
+    fn select_graphic_rendition(&mut self, params: &[usize]) {
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => self._pen = TerminalCell::default(),
+                1 => self._pen.bold = true,
+                4 => self._pen.underline = true,
+                7 => self._pen.inverse = true,
+                22 => self._pen.bold = false,
+                24 => self._pen.underline = false,
+                27 => self._pen.inverse = false,
+                30..=37 => self._pen.fg = TerminalColor::Index((params[i] - 30) as u8),
+                39 => self._pen.fg = TerminalColor::Default,
+                40..=47 => self._pen.bg = TerminalColor::Index((params[i] - 40) as u8),
+                49 => self._pen.bg = TerminalColor::Default,
+                90..=97 => self._pen.fg = TerminalColor::Index((params[i] - 90 + 8) as u8),
+                100..=107 => self._pen.bg = TerminalColor::Index((params[i] - 100 + 8) as u8),
+                38 | 48 => {
+                    let is_fg = params[i] == 38;
+                    let color = if i + 2 < params.len() && params[i + 1] == 5 {
+                        i += 2;
+                        Some(TerminalColor::Index(params[i].min(255) as u8))
+                    }
+                    else if i + 4 < params.len() && params[i + 1] == 2 {
+                        i += 4;
+                        Some(TerminalColor::Rgb(params[i - 2] as u8, params[i - 1] as u8, params[i] as u8))
+                    }
+                    else {
+                        None
+                    };
+                    if let Some(color) = color {
+                        if is_fg {self._pen.fg = color}else {self._pen.bg = color}
+                    }
+                },
+                _ => ()
+            }
+            i += 1;
+        }
+    }
+    
+    fn osc_dispatch(&mut self) {
+        // 0 and 2 set the window title
+        let mut parts = self._osc.splitn(2, ';');
+        match (parts.next(), parts.next()) {
+            (Some("0"), Some(title)) | (Some("2"), Some(title)) => self.title = title.to_string(),
+            _ => ()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn grid_with(cols: usize, rows: usize, data: &str) -> TerminalGrid {
+        let mut grid = TerminalGrid::new(cols, rows);
+        grid.process(data.as_bytes());
+        grid
+    }
+    
+    fn screen(grid: &TerminalGrid) -> Vec<String> {
+        grid.lines.iter().map( | line | line.iter().map( | cell | cell.ch).collect::<String>().trim_end().to_string()).collect()
+    }
+    
+    #[test]
+    fn prints_and_wraps_at_the_last_column() {
+        let grid = grid_with(5, 3, "hello world");
+        assert_eq!(screen(&grid), vec!["hello", " worl", "d"]);
+        assert_eq!(grid.cursor, TerminalPos {row: 2, col: 1});
+        // the cursor stays on the last column until the next char
+        let grid = grid_with(5, 3, "hello");
+        assert_eq!(grid.cursor, TerminalPos {row: 0, col: 4});
+        assert!(grid._wrap_pending);
+    }
+    
+    #[test]
+    fn moves_the_cursor() {
+        let mut grid = grid_with(10, 5, "\x1b[3;4Hx");
+        assert_eq!(grid.cursor, TerminalPos {row: 2, col: 4});
+        grid.process(b"\x1b[2A\x1b[3D");
+        assert_eq!(grid.cursor, TerminalPos {row: 0, col: 1});
+        grid.process(b"\x1b[B\x1b[5C");
+        assert_eq!(grid.cursor, TerminalPos {row: 1, col: 6});
+        // moves clamp to the screen, missing and 0 counts are 1
+        grid.process(b"\x1b[99;99H");
+        assert_eq!(grid.cursor, TerminalPos {row: 4, col: 9});
+        grid.process(b"\x1b[H\x1b[0B\x1b[C");
+        assert_eq!(grid.cursor, TerminalPos {row: 1, col: 1});
+        // backspace from the pending wrap goes back to the char before the last column
+        grid.process(b"\r\tab\x08");
+        assert_eq!(grid.cursor, TerminalPos {row: 1, col: 8});
+        assert_eq!(screen(&grid)[1], "        ab");
+        assert_eq!(screen(&grid)[2], "   x");
+    }
+    
+    #[test]
+    fn erases_lines_and_the_display() {
+        let mut grid = grid_with(6, 3, "abcdef\r\nghijkl\r\nmnopqr");
+        grid.process(b"\x1b[2;3H\x1b[K");
+        assert_eq!(screen(&grid), vec!["abcdef", "gh", "mnopqr"]);
+        grid.process(b"\x1b[1K");
+        assert_eq!(screen(&grid), vec!["abcdef", "", "mnopqr"]);
+        grid.process(b"\x1b[1;4H\x1b[J");
+        assert_eq!(screen(&grid), vec!["abc", "", ""]);
+        grid.process(b"\x1b[2J");
+        assert_eq!(screen(&grid), vec!["", "", ""]);
+        // erasing doesn't move the cursor
+        assert_eq!(grid.cursor, TerminalPos {row: 0, col: 3});
+    }
+    
+    #[test]
+    fn inserts_and_deletes_chars() {
+        let mut grid = grid_with(6, 1, "abcdef\x1b[1;2H\x1b[2P");
+        assert_eq!(screen(&grid), vec!["adef"]);
+        grid.process(b"\x1b[@");
+        assert_eq!(screen(&grid), vec!["a def"]);
+        grid.process(b"\x1b[3X");
+        assert_eq!(screen(&grid), vec!["a   f"]);
+    }
+    
+    #[test]
+    fn sets_graphic_rendition() {
+        let grid = grid_with(10, 1, "\x1b[1;31ma\x1b[22;44mb\x1b[38;5;200;48;2;1;2;3mc\x1b[0md\x1b[93me");
+        let cells = &grid.lines[0];
+        assert_eq!((cells[0].bold, cells[0].fg, cells[0].bg), (true, TerminalColor::Index(1), TerminalColor::Default));
+        assert_eq!((cells[1].bold, cells[1].fg, cells[1].bg), (false, TerminalColor::Index(1), TerminalColor::Index(4)));
+        assert_eq!((cells[2].fg, cells[2].bg), (TerminalColor::Index(200), TerminalColor::Rgb(1, 2, 3)));
+        assert_eq!(cells[3], TerminalCell {ch: 'd', ..TerminalCell::default()});
+        assert_eq!(cells[4].fg, TerminalColor::Index(11));
+    }
+    
+    #[test]
+    fn erased_cells_keep_the_background() {
+        let grid = grid_with(4, 2, "\x1b[42m\x1b[2J");
+        assert!(grid.lines.iter().all( | line | line.iter().all( | cell | cell.bg == TerminalColor::Index(2) && cell.ch == ' ')));
+    }
+    
+    #[test]
+    fn scrolls_inside_the_scroll_region() {
+        let mut grid = grid_with(3, 4, "1\r\n2\r\n3\r\n4");
+        // rows 2 and 3 scroll, 1 and 4 stay put
+        grid.process(b"\x1b[2;3r");
+        assert_eq!(grid.cursor, TerminalPos {row: 0, col: 0});
+        grid.process(b"\x1b[3;1H\nx");
+        assert_eq!(screen(&grid), vec!["1", "3", "x", "4"]);
+        assert_eq!(grid.scrollback.len(), 0);
+        // reverse index at the top of the region scrolls it down
+        grid.process(b"\x1b[2;1H\x1bM");
+        assert_eq!(screen(&grid), vec!["1", "", "3", "4"]);
+        // inserting and deleting lines stays inside the region too
+        grid.process(b"\x1b[3;1H\x1b[M");
+        assert_eq!(screen(&grid), vec!["1", "", "", "4"]);
+        grid.process(b"\x1b[r\x1b[4;1H\n");
+        assert_eq!(screen(&grid), vec!["", "", "4", ""]);
+        assert_eq!(grid.scrollback.len(), 1);
+    }
+    
+    #[test]
+    fn output_scrolls_into_the_history() {
+        let grid = grid_with(3, 2, "a\r\nb\r\nc\r\nd");
+        assert_eq!(screen(&grid), vec!["c", "d"]);
+        assert_eq!(grid.total_rows(), 4);
+        assert_eq!(grid.text_range(TerminalPos {row: 0, col: 0}, TerminalPos {row: 3, col: 3}), "a\nb\nc\nd");
+    }
+    
+    #[test]
+    fn escapes_and_utf8_split_across_reads() {
+        let mut grid = TerminalGrid::new(5, 1);
+        grid.process(b"\x1b[3");
+        grid.process(b"1mx\x1b");
+        grid.process(&"[2Cé".as_bytes()[..4]);
+        grid.process(&"é".as_bytes()[1..]);
+        assert_eq!(screen(&grid), vec!["x  é"]);
+        assert_eq!(grid.lines[0][3].fg, TerminalColor::Index(1));
+    }
+    
+    #[test]
+    fn answers_queries_and_sets_the_title() {
+        let grid = grid_with(10, 5, "\x1b[2;3H\x1b[6n\x1b]2;my title\x07");
+        assert_eq!(grid.responses, b"\x1b[2;3R".to_vec());
+        assert_eq!(grid.title, "my title");
+    }
+    
+    #[test]
+    fn alternate_screen_restores_the_main_screen() {
+        let mut grid = grid_with(4, 2, "main\x1b[?1049h");
+        assert_eq!(screen(&grid), vec!["", ""]);
+        grid.process(b"alt\r\n\n\n");
+        assert_eq!(grid.scrollback.len(), 0);
+        grid.process(b"\x1b[?1049l");
+        assert_eq!(screen(&grid), vec!["main", ""]);
+        assert_eq!(grid.cursor, TerminalPos {row: 0, col: 3});
+    }
+}
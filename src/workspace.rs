@@ -0,0 +1,214 @@
+use render::*;
+
+use std::sync::mpsc;
+use serde_json::{json, Value};
+use serde::*;
+
+// the crates of the cargo workspace in the project folder, from cargo metadata
+pub struct CargoWorkspace {
+    pub workdir: String,
+    pub workspace_root: String,
+    pub packages: Vec<CargoPackage>,
+    pub error: Option<String>,
+    pub _signal: Signal,
+    pub _rx: Option<mpsc::Receiver<(Vec<u8>, Vec<u8>)>>,
+    pub _abs_workdir: String
+}
+
+// root is relative to the workdir and ends with a slash, it is empty for the workdir itself
+#[derive(Clone, Debug)]
+pub struct CargoPackage {
+    pub name: String,
+    pub id: String,
+    pub root: String,
+    pub targets: Vec<String>
+}
+
+#[derive(Clone, Deserialize, Default)]
+struct CargoMetadata {
+    packages: Vec<CargoMetadataPackage>,
+    workspace_root: String
+}
+
+#[derive(Clone, Deserialize, Default)]
+struct CargoMetadataPackage {
+    name: String,
+    id: String,
+    manifest_path: String,
+    targets: Vec<CargoMetadataTarget>
+}
+
+#[derive(Clone, Deserialize, Default)]
+struct CargoMetadataTarget {
+    name: String
+}
+
+const SIGNAL_WORKSPACE_LOADED: usize = 1;
+
+// an absolute path with forward slashes and without the windows verbatim prefix
+pub fn abs_path(path: &str) -> String {
+    let abs = if let Ok(abs) = std::fs::canonicalize(path) {
+        abs.to_string_lossy().replace("\\", "/")
+    }
+    else {
+        path.replace("\\", "/")
+    };
+    abs.trim_start_matches("//?/").trim_end_matches('/').to_string()
+}
+
+impl CargoWorkspace {
+    pub fn new(cx: &mut Cx) -> Self {
+        Self {
+            workdir: "./".to_string(),
+            workspace_root: String::new(),
+            packages: Vec::new(),
+            error: None,
+            _signal: cx.new_signal(),
+            _rx: None,
+            _abs_workdir: String::new()
+        }
+    }
+    
+    pub fn load(&mut self) {
+        self.packages.truncate(0);
+        self.workspace_root = String::new();
+        self.error = None;
+        self._abs_workdir = abs_path(&self.workdir);
+        let args = ["metadata", "--format-version", "1", "--no-deps"];
+        let child = match spawn_process_command("cargo", &args, &self.workdir, false) {
+            Ok(child) => child,
+            Err(err) => {
+                self.error = Some(format!("cannot run cargo metadata: {}", err));
+                return
+            }
+        };
+        let (tx, rx) = mpsc::channel();
+        let signal = self._signal;
+        std::thread::spawn(move || {
+            let (stdout, stderr) = match child.wait_with_output() {
+                Ok(output) => (output.stdout, output.stderr),
+                Err(err) => (Vec::new(), err.to_string().into_bytes())
+            };
+            let _ = tx.send((stdout, stderr));
+            Cx::send_signal(signal, SIGNAL_WORKSPACE_LOADED);
+        });
+        self._rx = Some(rx);
+    }
+    
+    pub fn is_loading(&self) -> bool {
+        self._rx.is_some()
+    }
+    
+    pub fn is_workspace_event(&self, event: &Event) -> bool {
+        if let Event::Signal(se) = event {
+            return self._signal.is_signal(se)
+        }
+        false
+    }
+    
+    // returns true when the metadata came in
+    pub fn handle_workspace(&mut self, _cx: &mut Cx, event: &mut Event) -> bool {
+        if let Event::Signal(se) = event {
+            if !self._signal.is_signal(se) {
+                return false
+            }
+            let result = if let Some(rx) = &self._rx {rx.try_recv()}else {return false};
+            let (stdout, stderr) = if let Ok(result) = result {result}else {return false};
+            self._rx = None;
+            match serde_json::from_slice::<CargoMetadata>(&stdout) {
+                Ok(metadata) => {
+                    self.workspace_root = metadata.workspace_root.replace("\\", "/");
+                    for package in metadata.packages {
+                        let manifest_dir = package.manifest_path.replace("\\", "/");
+                        let manifest_dir = manifest_dir.trim_end_matches("Cargo.toml");
+                        self.packages.push(CargoPackage {
+                            root: self.relative_path(manifest_dir).unwrap_or(manifest_dir.to_string()),
+                            name: package.name,
+                            id: package.id,
+                            targets: package.targets.into_iter().map( | target | target.name).collect()
+                        });
+                    }
+                    // the longest roots first, so nested crates win
+                    self.packages.sort_by( | a, b | b.root.len().cmp(&a.root.len()).then(a.name.cmp(&b.name)));
+                },
+                Err(_) => {
+                    let stderr = String::from_utf8_lossy(&stderr);
+                    let line = stderr.lines().find( | line | line.trim().len() > 0).unwrap_or("no cargo workspace found");
+                    self.error = Some(line.trim().to_string());
+                }
+            }
+            return true
+        }
+        false
+    }
+    
+    pub fn package_by_id(&self, id: &str) -> Option<&CargoPackage> {
+        self.packages.iter().find( | package | package.id == id)
+    }
+    
+    pub fn package_for_path(&self, path: &str) -> Option<&CargoPackage> {
+        self.packages.iter().find( | package | path.starts_with(&package.root))
+    }
+    
+    // an absolute path relative to the workdir, if it is inside of it
+    pub fn relative_path(&self, abs: &str) -> Option<String> {
+        let abs = abs.replace("\\", "/");
+        let prefix = format!("{}/", self._abs_workdir);
+        if abs.starts_with(&prefix) {
+            Some(abs[prefix.len()..].to_string())
+        }
+        else if abs.trim_end_matches('/') == self._abs_workdir {
+            Some(String::new())
+        }
+        else {
+            None
+        }
+    }
+    
+    // maps a file name from a rustc message to a path relative to the workdir,
+    // cargo hands rustc paths relative to the workspace root or absolute ones
+    pub fn resolve_path(&self, file_name: &str) -> String {
+        let file_name = file_name.replace("\\", "/");
+        if self.workspace_root.len() == 0 {
+            return file_name
+        }
+        let abs = if std::path::Path::new(&file_name).is_absolute() {
+            file_name.clone()
+        }
+        else {
+            format!("{}/{}", self.workspace_root.trim_end_matches('/'), file_name)
+        };
+        self.relative_path(&abs).unwrap_or(file_name)
+    }
+}
+
+// builds the file tree json of a folder on disk, the same shape build_index.js writes
+pub fn scan_project_folder(path: &str) -> String {
+    fn scan(path: &std::path::Path, name: &str, depth: usize) -> Value {
+        let mut folders = Vec::new();
+        let mut files = Vec::new();
+        let mut entries: Vec<_> = std::fs::read_dir(path).map( | dir | dir.filter_map( | entry | entry.ok()).collect()).unwrap_or(Vec::new());
+        entries.sort_by_key( | entry | entry.file_name());
+        for entry in entries {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if entry.path().is_dir() {
+                if file_name == "target" {
+                    continue;
+                }
+                let sub = scan(&entry.path(), &file_name, depth + 1);
+                // prune empty dirs
+                if sub["folders"].as_array().map_or(0, | a | a.len()) > 0 || sub["files"].as_array().map_or(0, | a | a.len()) > 0 {
+                    folders.push(sub);
+                }
+            }
+            else if file_name.ends_with(".rs") || file_name.ends_with(".js") || file_name.ends_with(".toml") {
+                files.push(json!({"name": file_name}));
+            }
+        }
+        json!({"name": name, "open": depth < 2, "folders": folders, "files": files})
+    }
+    scan(std::path::Path::new(path), "", 0).to_string()
+}
@@ -61,6 +61,7 @@ pub enum ButtonEvent {
 impl Button {
     pub fn def_bg_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
             
             let border_color: vec4<Instance>;
             let glow_size: float<Instance>;
@@ -70,13 +71,13 @@ impl Button {
             const border_width: float = 1.0;
             
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
-                df_box(0., 0., w, h, border_radius);
-                df_shape += 3.;
-                df_fill_keep(color);
-                df_stroke_keep(border_color, border_width);
-                df_blur = 2.;
-                return df_glow(glow_color, glow_size);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0., 0., w, h, border_radius);
+                df::shape += 3.;
+                df::fill_keep(color);
+                df::stroke_keep(border_color, border_width);
+                df::blur = 2.;
+                return df::glow(glow_color, glow_size);
             }
         }))
     }
@@ -0,0 +1,192 @@
+use render::*;
+
+// shows the shader instance under the mouse: its shader, instance index and instance props
+// ctrl+F11 turns it on and off, ctrl+shift+F11 also re-renders the hovered shader with its geometry bounds highlighted
+// ctrl+alt+F11 shows the draw tree of the window
+#[derive(Clone)]
+pub struct DebugOverlay {
+    pub view: View<NoScrollBar>,
+    pub tree_view: View<NoScrollBar>,
+    pub outline: Quad,
+    pub bg: Quad,
+    pub bg_layout: Layout,
+    pub text: Text,
+    pub on: bool,
+    pub highlight: bool,
+    pub tree: bool,
+    pub _window_id: usize,
+    pub _hover_pos: Vec2,
+    // the highlighted shader with the shader gen it had and the highlight one put in its place
+    pub _highlight: Option<(usize, ShaderGen, ShaderGen)>
+}
+
+impl Style for DebugOverlay {
+    fn style(cx: &mut Cx) -> Self {
+        Self {
+            view: View {
+                is_overlay: true,
+                is_clipped: false,
+                ..Style::style(cx)
+            },
+            tree_view: View {
+                is_overlay: true,
+                is_clipped: false,
+                ..Style::style(cx)
+            },
+            outline: Quad {
+                color: color("magenta"),
+                shader: cx.add_shader(Self::def_outline_shader(), "DebugOverlay.outline"),
+                ..Style::style(cx)
+            },
+            bg: Quad {
+                color: Color {r: 0.0, g: 0.0, b: 0.0, a: 0.85},
+                ..Style::style(cx)
+            },
+            bg_layout: Layout {
+                padding: Padding {l: 6., t: 4., r: 6., b: 4.},
+                width: Bounds::Compute,
+                height: Bounds::Compute,
+                ..Default::default()
+            },
+            text: Text {
+                wrapping: Wrapping::Line,
+                ..Style::style(cx)
+            },
+            on: false,
+            highlight: false,
+            tree: false,
+            _window_id: 0,
+            _hover_pos: Vec2::zero(),
+            _highlight: None
+        }
+    }
+}
+
+impl DebugOverlay {
+    pub fn def_outline_shader() -> ShaderGen {
+        Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
+            fn pixel() -> vec4 {
+                df::viewport(pos * vec2(w, h));
+                df::rect(0., 0., w, h);
+                return df::stroke(color, 1.);
+            }
+        }))
+    }
+    
+    pub fn handle_debug_overlay(&mut self, cx: &mut Cx, event: &mut Event) {
+        match event {
+            Event::KeyDown(ke) => if ke.key_code == KeyCode::F11 && ke.modifiers.control {
+                if ke.modifiers.alt {
+                    self.tree = !self.tree;
+                    cx.redraw_child_area(Area::All);
+                    return
+                }
+                if ke.modifiers.shift {
+                    self.highlight = !self.highlight;
+                    self.on = self.on || self.highlight;
+                }
+                else {
+                    self.on = !self.on;
+                }
+                if !self.on {
+                    cx.debug_area = Area::Empty;
+                }
+                self.update_highlight(cx);
+                cx.redraw_child_area(Area::All);
+            },
+            Event::FingerHover(fe) => if self.on {
+                self._window_id = fe.window_id;
+                self._hover_pos = fe.abs;
+                let area = cx.debug_pick_area(fe.window_id, fe.abs, self.view.view_id);
+                if area != cx.debug_area {
+                    cx.debug_area = area;
+                    self.update_highlight(cx);
+                    self.view.redraw_view_area(cx);
+                }
+            },
+            _ => ()
+        }
+    }
+    
+    // swaps the highlight override onto the shader of the debug area, putting the previous shader back
+    fn update_highlight(&mut self, cx: &mut Cx) {
+        let shader_id = if self.on && self.highlight {
+            cx.debug_instance(cx.debug_area).map( | info | info.shader_id)
+        }
+        else {
+            None
+        };
+        if let Some((old_id, old_sg, highlight_sg)) = self._highlight.take() {
+            // a hot reload while highlighted already replaced the highlight, putting the old shader back would undo it
+            if cx.shaders[old_id].shader_gen == highlight_sg {
+                if Some(old_id) == shader_id {
+                    self._highlight = Some((old_id, old_sg, highlight_sg));
+                    return
+                }
+                let _ = cx.reload_shader(&Shader {shader_id: Some(old_id)}, old_sg);
+            }
+        }
+        if let Some(shader_id) = shader_id {
+            let sg = cx.shaders[shader_id].shader_gen.clone();
+            let highlight_sg = cx.debug_highlight_shader_gen(shader_id);
+            if let Err(errors) = cx.reload_shader(&Shader {shader_id: Some(shader_id)}, highlight_sg.clone()) {
+                for error in errors {
+                    println!("debug highlight of {}: {}", cx.shaders[shader_id].name, error.msg);
+                }
+                return
+            }
+            self._highlight = Some((shader_id, sg, highlight_sg));
+        }
+    }
+    
+    pub fn draw_debug_overlay(&mut self, cx: &mut Cx) {
+        if self.tree {
+            self.draw_tree(cx);
+        }
+        if !self.on {
+            return
+        }
+        // pick again, what is under the mouse can have been redrawn this cycle
+        cx.debug_area = cx.debug_pick_area(self._window_id, self._hover_pos, self.view.view_id);
+        let info = if let Some(info) = cx.debug_instance(cx.debug_area) {info}else {
+            return
+        };
+        if let Ok(()) = self.view.begin_view(cx, Layout {
+            abs_origin: Some(Vec2 {x: self._hover_pos.x + 12., y: self._hover_pos.y + 12.}),
+            ..Default::default()
+        }) {
+            self.outline.draw_quad_abs(cx, info.rect);
+            let inst = self.bg.begin_quad(cx, &self.bg_layout);
+            self.text.draw_text(cx, &format!("{}({}) instance {} of {}", info.shader_name, info.shader_id, info.instance_index, info.instance_total));
+            cx.turtle_new_line();
+            for (name, value) in &info.props {
+                self.text.draw_text(cx, &format!("{}: {}", name, value));
+                cx.turtle_new_line();
+            }
+            self.bg.end_quad(cx, &inst);
+            self.view.end_view(cx);
+        }
+    }
+    
+    // the tree of the window we are drawn in, in the top left corner
+    fn draw_tree(&mut self, cx: &mut Cx) {
+        let window_id = if let Some(window_id) = cx.window_stack.last() {*window_id}else {return};
+        let main_view_id = cx.windows[window_id].main_pass_id.and_then( | pass_id | cx.passes[pass_id].main_view_id);
+        let main_view_id = if let Some(main_view_id) = main_view_id {main_view_id}else {return};
+        let tree = cx.debug_draw_tree(main_view_id, self.tree_view.view_id);
+        if let Ok(()) = self.tree_view.begin_view(cx, Layout {
+            abs_origin: Some(Vec2::zero()),
+            ..Default::default()
+        }) {
+            let inst = self.bg.begin_quad(cx, &self.bg_layout);
+            for line in tree.lines() {
+                self.text.draw_text(cx, line);
+                cx.turtle_new_line();
+            }
+            self.bg.end_quad(cx, &inst);
+            self.tree_view.end_view(cx);
+        }
+    }
+}
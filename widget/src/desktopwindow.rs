@@ -1,5 +1,6 @@
 use render::*;
 use crate::scrollbar::*;
+use crate::debugoverlay::*;
 
 #[derive(Clone)]
 pub struct DesktopWindow {
@@ -9,6 +10,7 @@ pub struct DesktopWindow {
     pub depth_texture: Texture,
     pub main_view: View<ScrollBar>, // we have a root view otherwise is_overlay subviews can't attach topmost
     pub inner_view: View<ScrollBar>,
+    pub debug_overlay: DebugOverlay,
     
     // testing
     pub test_rtt: bool,
@@ -35,6 +37,7 @@ impl Style for DesktopWindow {
             depth_texture: Texture::default(),
             main_view: View::style(cx),
             inner_view: View::style(cx),
+            debug_overlay: DebugOverlay::style(cx),
 
             test_rtt: false,
             sub_pass: Pass::default(),
@@ -75,6 +78,7 @@ impl DesktopWindow {
                 DesktopWindowEvent::EventForOtherWindow
             }
             else {
+                self.debug_overlay.handle_debug_overlay(cx, event);
                 DesktopWindowEvent::None
             }
         }
@@ -109,6 +113,8 @@ impl DesktopWindow {
     
     pub fn end_desktop_window(&mut self, cx: &mut Cx) {
         
+        // drawn inside inner_view so redrawing just the overlay redraws the window
+        self.debug_overlay.draw_debug_overlay(cx);
         self.inner_view.end_view(cx);
         self.main_view.end_view(cx);
         if self.test_rtt {
@@ -221,33 +221,36 @@ impl FileTree{
 
     pub fn def_drag_bg_shader()->ShaderGen{
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             fn pixel()->vec4{
-                df_viewport(pos * vec2(w, h));
-                df_box(0., 0., w, h, 2.);
-                return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0., 0., w, h, 2.);
+                return df::fill(color);
             }
         }))
     }
 
     pub fn def_filler_shader()->ShaderGen{
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
 
             let line_vec:vec2<Instance>;
             let anim_pos:float<Instance>;
 
             fn pixel()->vec4{
-                df_viewport(pos * vec2(w, h));
+                df::viewport(pos * vec2(w, h));
                 if anim_pos<-0.5{
-                    df_move_to(0.5*w,line_vec.x*h);
-                    df_line_to(0.5*w,line_vec.y*h);
-                    return df_stroke(color, 1.);
+                    df::move_to(0.5*w,line_vec.x*h);
+                    df::line_to(0.5*w,line_vec.y*h);
+                    return df::stroke(color, 1.);
                 }
                 else{ // its a folder
-                    df_box(0.*w, 0.39*h, 0.87*w, 0.39*h, 0.75);
-                    df_box(0.*w, 0.32*h, 0.5*w, 0.3*h, 1.);
-                    df_union();
+                    df::round_rect(0.*w, 0.39*h, 0.87*w, 0.39*h, 0.75);
+                    df::round_rect(0.*w, 0.32*h, 0.5*w, 0.3*h, 1.);
+                    df::union();
                     // ok so. 
-                    return df_fill(color);
+                    return df::fill(color);
                 }
             }
         }))
@@ -272,6 +275,26 @@ impl FileTree{
         }
     } 
 
+    pub fn file_paths_from_json(json_data:&str)->Vec<String>{
+        let mut paths = Vec::new();
+        let value:Result<JsonFolder> = serde_json::from_str(json_data);
+        if let Ok(value) = value{
+            Self::collect_json_paths(&value, "", &mut paths);
+        }
+        paths
+    }
+
+    fn collect_json_paths(node:&JsonFolder, base:&str, paths:&mut Vec<String>){
+        // the root folder has an empty name
+        let base = if node.name.len() == 0{base.to_string()} else {format!("{}{}/", base, node.name)};
+        for folder in &node.folders{
+            Self::collect_json_paths(folder, &base, paths);
+        }
+        for file in &node.files{
+            paths.push(format!("{}{}", base, file.name));
+        }
+    }
+
     pub fn load_from_json(&mut self, cx:&mut Cx, json_data:&str){
         let value:Result<JsonFolder> = serde_json::from_str(json_data); 
         if let Ok(value) = value{
@@ -18,6 +18,8 @@ mod filetree;
 pub use crate::filetree::*;
 mod desktopwindow;
 pub use crate::desktopwindow::*;
+mod debugoverlay;
+pub use crate::debugoverlay::*;
 //mod textbuffer;
 //pub use crate::textbuffer::*;
 //mod editor;
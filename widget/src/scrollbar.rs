@@ -71,6 +71,7 @@ impl Style for ScrollBar {
 impl ScrollBar {
     pub fn def_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
             
             let is_vertical: float<Instance>;
             
@@ -90,14 +91,14 @@ impl ScrollBar {
             }
             
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
+                df::viewport(pos * vec2(w, h));
                 if is_vertical > 0.5 {
-                    df_box(1., h * norm_scroll, w * 0.5, h * norm_handle, border_radius);
+                    df::round_rect(1., h * norm_scroll, w * 0.5, h * norm_handle, border_radius);
                 }
                 else {
-                    df_box(w * norm_scroll, 1., w * norm_handle, h * 0.5, border_radius);
+                    df::round_rect(w * norm_scroll, 1., w * norm_handle, h * 0.5, border_radius);
                 }
-                return df_fill_keep(color);
+                return df::fill_keep(color);
             }
         }))
     }
@@ -80,13 +80,14 @@ impl Splitter{
 
     pub fn def_split_shader()->ShaderGen{
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
 
             const border_radius:float = 1.5;
 
             fn pixel()->vec4{
-                df_viewport(pos * vec2(w, h));
-                df_box(0., 0., w, h, 0.5);
-                return df_fill(color);
+                df::viewport(pos * vec2(w, h));
+                df::round_rect(0., 0., w, h, 0.5);
+                return df::fill(color);
             }
         }))
     }
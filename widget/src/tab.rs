@@ -132,19 +132,20 @@ impl Tab{
 
     pub fn def_bg_shader()->ShaderGen{
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
 
             let border_color:vec4<Instance>;
             const border_width:float = 1.0;
 
             fn pixel()->vec4{
-                df_viewport(pos * vec2(w, h));
-                df_rect(-1.,-1.,w+2.,h+2.);
-                df_fill(color);
-                df_move_to(w,0.);
-                df_line_to(w,h);
-                df_move_to(0.,0.);
-                df_line_to(0.,h);
-                return df_stroke(border_color, 1.);
+                df::viewport(pos * vec2(w, h));
+                df::rect(-1.,-1.,w+2.,h+2.);
+                df::fill(color);
+                df::move_to(w,0.);
+                df::line_to(w,h);
+                df::move_to(0.,0.);
+                df::line_to(0.,h);
+                return df::stroke(border_color, 1.);
             }
         }))
     }
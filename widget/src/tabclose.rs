@@ -44,20 +44,22 @@ impl Style for TabClose {
 impl TabClose {
     pub fn def_bg_shader() -> ShaderGen {
         Quad::def_quad_shader().compose(shader_ast!({
+            use df;
+            
             let hover: float<Instance>;
             let down: float<Instance>;
             fn pixel() -> vec4 {
-                df_viewport(pos * vec2(w, h));
+                df::viewport(pos * vec2(w, h));
                 let hover_max: float = (hover * 0.2 + 0.8) * 0.5;
                 let hover_min: float = 1. - hover_max;
                 let c: vec2 = vec2(w, h) * 0.5;
-                df_rotate(down, c.x, c.y);
-                df_move_to(c.x * hover_min, c.y * hover_min);
-                df_line_to(c.x + c.x * hover_max, c.y + c.y * hover_max);
-                df_move_to(c.x + c.x * hover_max, c.y * hover_min);
-                df_line_to(c.x * hover_min, c.y + c.y * hover_max);
-                return df_stroke(color, 1. + down * 0.2);
-                //return df_fill(color);
+                df::rotate(down, c.x, c.y);
+                df::move_to(c.x * hover_min, c.y * hover_min);
+                df::line_to(c.x + c.x * hover_max, c.y + c.y * hover_max);
+                df::move_to(c.x + c.x * hover_max, c.y * hover_min);
+                df::line_to(c.x * hover_min, c.y + c.y * hover_max);
+                return df::stroke(color, 1. + down * 0.2);
+                //return df::fill(color);
             }
         }))
     }